@@ -74,9 +74,11 @@ fn new_node(tokio_handle: Handle) -> node_cli::service::NewFullBase {
 		database: DatabaseSource::RocksDb { path: root.join("db"), cache_size: 128 },
 		state_cache_size: 67108864,
 		state_cache_child_ratio: None,
+		trie_cache_size: 67108864,
 		state_pruning: PruningMode::ArchiveAll,
 		keep_blocks: KeepBlocks::All,
 		transaction_storage: TransactionStorageMode::BlockBody,
+		state_diff_index: false,
 		chain_spec: spec,
 		wasm_method: WasmExecutionMethod::Compiled,
 		execution_strategies: ExecutionStrategies {
@@ -70,6 +70,12 @@ pub enum Subcommand {
 	/// Validate blocks.
 	CheckBlock(sc_cli::CheckBlockCmd),
 
+	/// Check the consistency of the database.
+	CheckDb(sc_cli::CheckDbCmd),
+
+	/// Convert the database to another backend.
+	ConvertDb(sc_cli::ConvertDbCmd),
+
 	/// Export blocks.
 	ExportBlocks(sc_cli::ExportBlocksCmd),
 
@@ -79,6 +85,9 @@ pub enum Subcommand {
 	/// Import blocks.
 	ImportBlocks(sc_cli::ImportBlocksCmd),
 
+	/// Export or import a snapshot of the state at a finalized block.
+	Snapshot(sc_cli::SnapshotCmd),
+
 	/// Remove the whole chain.
 	PurgeChain(sc_cli::PurgeChainCmd),
 
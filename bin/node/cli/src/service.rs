@@ -231,6 +231,8 @@ pub fn new_partial(
 		let (_, grandpa_link, babe_link) = &import_setup;
 
 		let justification_stream = grandpa_link.justification_stream();
+		let equivocation_stream = grandpa_link.equivocation_stream();
+		let round_history = grandpa_link.round_history();
 		let shared_authority_set = grandpa_link.shared_authority_set().clone();
 		let shared_voter_state = grandpa::SharedVoterState::empty();
 		let rpc_setup = shared_voter_state.clone();
@@ -264,7 +266,9 @@ pub fn new_partial(
 				grandpa: node_rpc::GrandpaDeps {
 					shared_voter_state: shared_voter_state.clone(),
 					shared_authority_set: shared_authority_set.clone(),
+					round_history: round_history.clone(),
 					justification_stream: justification_stream.clone(),
+					equivocation_stream: equivocation_stream.clone(),
 					subscription_executor,
 					finality_provider: finality_proof_provider.clone(),
 				},
@@ -455,6 +459,9 @@ pub fn new_full_base(
 			sc_authority_discovery::new_worker_and_service_with_config(
 				sc_authority_discovery::WorkerConfig {
 					publish_non_global_ips: auth_disc_publish_non_global_ips,
+					authority_peer_sets: vec![
+						grandpa::grandpa_peers_set_config().notifications_protocol,
+					],
 					..Default::default()
 				},
 				client.clone(),
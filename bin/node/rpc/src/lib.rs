@@ -38,7 +38,8 @@ use sc_consensus_babe::{Config, Epoch};
 use sc_consensus_babe_rpc::BabeRpcHandler;
 use sc_consensus_epochs::SharedEpochChanges;
 use sc_finality_grandpa::{
-	FinalityProofProvider, GrandpaJustificationStream, SharedAuthoritySet, SharedVoterState,
+	FinalityProofProvider, GrandpaEquivocationStream, GrandpaJustificationStream,
+	SharedAuthoritySet, SharedRoundHistory, SharedVoterState,
 };
 use sc_finality_grandpa_rpc::GrandpaRpcHandler;
 use sc_rpc::SubscriptionTaskExecutor;
@@ -67,8 +68,12 @@ pub struct GrandpaDeps<B> {
 	pub shared_voter_state: SharedVoterState,
 	/// Authority set info.
 	pub shared_authority_set: SharedAuthoritySet<Hash, BlockNumber>,
+	/// Participation in the recently completed rounds.
+	pub round_history: SharedRoundHistory,
 	/// Receives notifications about justification events from Grandpa.
 	pub justification_stream: GrandpaJustificationStream<Block>,
+	/// Receives notifications about equivocations detected by Grandpa.
+	pub equivocation_stream: GrandpaEquivocationStream<Block>,
 	/// Executor to drive the subscription manager in the Grandpa RPC handler.
 	pub subscription_executor: SubscriptionTaskExecutor,
 	/// Finality proof provider.
@@ -131,7 +136,9 @@ where
 	let GrandpaDeps {
 		shared_voter_state,
 		shared_authority_set,
+		round_history,
 		justification_stream,
+		equivocation_stream,
 		subscription_executor,
 		finality_provider,
 	} = grandpa;
@@ -154,7 +161,9 @@ where
 	io.extend_with(sc_finality_grandpa_rpc::GrandpaApi::to_delegate(GrandpaRpcHandler::new(
 		shared_authority_set.clone(),
 		shared_voter_state,
+		round_history,
 		justification_stream,
+		equivocation_stream,
 		subscription_executor,
 		finality_provider,
 	)));
@@ -390,10 +390,12 @@ impl BenchDb {
 		let db_config = sc_client_db::DatabaseSettings {
 			state_cache_size: 16 * 1024 * 1024,
 			state_cache_child_ratio: Some((0, 100)),
+			trie_cache_size: 16 * 1024 * 1024,
 			state_pruning: PruningMode::ArchiveAll,
 			source: database_type.into_settings(dir.into()),
 			keep_blocks: sc_client_db::KeepBlocks::All,
 			transaction_storage: sc_client_db::TransactionStorageMode::BlockBody,
+			state_diff_index: false,
 		};
 		let task_executor = TaskExecutor::new();
 
@@ -29,7 +29,7 @@ use sp_core::offchain::OffchainStorage;
 use sp_runtime::{
 	generic::BlockId,
 	traits::{Block as BlockT, HashFor, NumberFor},
-	Justification, Justifications, Storage,
+	Justification, Justifications, StateVersion, Storage,
 };
 use sp_state_machine::{
 	ChildStorageCollection, IndexOperation, OffchainChangesCollection, StorageCollection,
@@ -70,6 +70,51 @@ pub struct ImportSummary<Block: BlockT> {
 	pub tree_route: Option<sp_blockchain::TreeRoute<Block>>,
 }
 
+/// Keys written by a block, as recorded by the state diff index of the backend.
+///
+/// Keys are kept sorted, so that membership can be checked without decoding the
+/// whole diff into a set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, codec::Encode, codec::Decode)]
+pub struct StateDiff {
+	/// Keys of the top trie.
+	pub top: Vec<Vec<u8>>,
+	/// Keys of child tries, indexed by the (unprefixed) child storage key.
+	pub children: Vec<(Vec<u8>, Vec<Vec<u8>>)>,
+}
+
+impl StateDiff {
+	/// Build a diff from the storage changes of a block.
+	pub fn from_changes(top: &StorageCollection, children: &ChildStorageCollection) -> Self {
+		let sorted_keys = |changes: &StorageCollection| {
+			let mut keys: Vec<_> = changes.iter().map(|(k, _)| k.clone()).collect();
+			keys.sort();
+			keys.dedup();
+			keys
+		};
+		let mut children: Vec<_> = children
+			.iter()
+			.map(|(child, changes)| (child.clone(), sorted_keys(changes)))
+			.collect();
+		children.sort_by(|a, b| a.0.cmp(&b.0));
+		StateDiff { top: sorted_keys(top), children }
+	}
+
+	/// Returns true if the block wrote `key`, either in the top trie or in the given child trie.
+	pub fn contains(&self, child_info: Option<&ChildInfo>, key: &[u8]) -> bool {
+		let keys = match child_info {
+			None => &self.top,
+			Some(child_info) => match self
+				.children
+				.binary_search_by(|(child, _)| child[..].cmp(child_info.storage_key()))
+			{
+				Ok(index) => &self.children[index].1,
+				Err(_) => return false,
+			},
+		};
+		keys.binary_search_by(|k| k[..].cmp(key)).is_ok()
+	}
+}
+
 /// Import operation wrapper
 pub struct ClientImportOperation<Block: BlockT, B: Backend<Block>> {
 	/// DB Operation.
@@ -166,10 +211,15 @@ pub trait BlockImportOperation<Block: BlockT> {
 		&mut self,
 		storage: Storage,
 		commit: bool,
+		state_version: StateVersion,
 	) -> sp_blockchain::Result<Block::Hash>;
 
 	/// Inject storage data into the database replacing any existing data.
-	fn reset_storage(&mut self, storage: Storage) -> sp_blockchain::Result<Block::Hash>;
+	fn reset_storage(
+		&mut self,
+		storage: Storage,
+		state_version: StateVersion,
+	) -> sp_blockchain::Result<Block::Hash>;
 
 	/// Set storage changes.
 	fn update_storage(
@@ -207,6 +257,11 @@ pub trait BlockImportOperation<Block: BlockT> {
 	/// Add a transaction index operation.
 	fn update_transaction_index(&mut self, index: Vec<IndexOperation>)
 		-> sp_blockchain::Result<()>;
+
+	/// Use the state written with [`Backend::write_state_nodes`] as the state of the block.
+	fn set_written_state(&mut self) -> sp_blockchain::Result<()> {
+		Err(sp_blockchain::Error::Backend("Writing state nodes is not supported".into()))
+	}
 }
 
 /// Interface for performing operations on the backend.
@@ -407,6 +462,12 @@ pub trait StorageProvider<Block: BlockT, B: Backend<Block>> {
 		child_info: &ChildInfo,
 		key: &StorageKey,
 	) -> sp_blockchain::Result<Option<Block::Hash>>;
+
+	/// Given a block hash, return the keys written by that block.
+	///
+	/// Returns `None` if the backend does not keep a state diff index or the block is not
+	/// indexed.
+	fn state_diff(&self, hash: &Block::Hash) -> sp_blockchain::Result<Option<StateDiff>>;
 }
 
 /// Client backend.
@@ -482,6 +543,21 @@ pub trait Backend<Block: BlockT>: AuxStore + Send + Sync {
 	/// Returns state backend with post-state of given block.
 	fn state_at(&self, block: BlockId<Block>) -> sp_blockchain::Result<Self::State>;
 
+	/// Write the trie nodes of a state imported in pieces, e.g. from a snapshot. The nodes are
+	/// keyed like in a [`PrefixedMemoryDB`](sp_trie::PrefixedMemoryDB).
+	///
+	/// The nodes become the state of a block once it is imported with
+	/// [`BlockImportOperation::set_written_state`]. Nodes of a block that is never imported are
+	/// never pruned.
+	fn write_state_nodes(&self, _nodes: Vec<(Vec<u8>, Vec<u8>)>) -> sp_blockchain::Result<()> {
+		Err(sp_blockchain::Error::Backend("Writing state nodes is not supported".into()))
+	}
+
+	/// Returns the keys written by the given block, if the backend keeps a state diff index.
+	fn state_diff(&self, _hash: &Block::Hash) -> sp_blockchain::Result<Option<StateDiff>> {
+		Ok(None)
+	}
+
 	/// Attempts to revert the chain by `n` blocks. If `revert_finalized` is set it will attempt to
 	/// revert past any finalized block, this is unsafe and can potentially leave the node in an
 	/// inconsistent state.
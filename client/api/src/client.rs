@@ -182,8 +182,8 @@ pub struct StateDbMemoryInfo {
 /// Memory statistics for client instance.
 #[derive(Default, Clone, Debug)]
 pub struct MemoryInfo {
-	/// Size of state cache.
-	pub state_cache: MemorySize,
+	/// Size of trie node cache.
+	pub trie_cache: MemorySize,
 	/// Size of backend database cache.
 	pub database_cache: MemorySize,
 	/// Size of the state db.
@@ -215,6 +215,10 @@ pub struct IoInfo {
 	pub state_writes_cache: u64,
 	/// State write (trie nodes) to backend db.
 	pub state_writes_nodes: u64,
+	/// Total trie node reads served by the trie node cache.
+	pub trie_cache_hits: u64,
+	/// Total trie node reads that missed the trie node cache.
+	pub trie_cache_misses: u64,
 }
 
 /// Usage statistics for running client instance.
@@ -234,10 +238,10 @@ impl fmt::Display for UsageInfo {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		write!(
 			f,
-			"caches: ({} state, {} db overlay), \
+			"caches: ({} trie, {} db overlay), \
 			 state db: ({} non-canonical, {} pruning, {} pinned), \
 			 i/o: ({} tx, {} write, {} read, {} avg tx, {}/{} key cache reads/total, {} trie nodes writes)",
-			self.memory.state_cache,
+			self.memory.trie_cache,
 			self.memory.database_cache,
 			self.memory.state_db.non_canonical,
 			self.memory.state_db.pruning.unwrap_or_default(),
@@ -32,7 +32,7 @@ pub use crate::{
 	worker::{NetworkProvider, Role, Worker},
 };
 
-use std::{collections::HashSet, sync::Arc, time::Duration};
+use std::{borrow::Cow, collections::HashSet, sync::Arc, time::Duration};
 
 use futures::{
 	channel::{mpsc, oneshot},
@@ -78,6 +78,22 @@ pub struct WorkerConfig {
 	///
 	/// Defaults to `true` to avoid the surprise factor.
 	pub publish_non_global_ips: bool,
+
+	/// Interval at which the authority set is checked for changes, e.g. at a session change. The
+	/// addresses of a new authority set are looked up right away.
+	///
+	/// By default this is set to 1 minute.
+	pub authority_set_refresh_interval: Duration,
+
+	/// Protocol names of the peer sets whose reserved peers are the current authorities.
+	///
+	/// While the local node is part of the authority set, the reserved peers of these sets are
+	/// the discovered addresses of the other authorities, updated whenever they change. Otherwise
+	/// the reserved peers are cleared. The sets should be configured as reserved-only, so that
+	/// authorities only connect to each other.
+	///
+	/// Empty by default.
+	pub authority_peer_sets: Vec<Cow<'static, str>>,
 }
 
 impl Default for WorkerConfig {
@@ -98,6 +114,8 @@ impl Default for WorkerConfig {
 			// `authority_discovery_dht_event_received`.
 			max_query_interval: Duration::from_secs(10 * 60),
 			publish_non_global_ips: true,
+			authority_set_refresh_interval: Duration::from_secs(60),
+			authority_peer_sets: Vec::new(),
 		}
 	}
 }
@@ -23,6 +23,7 @@ use crate::{
 };
 
 use std::{
+	borrow::Cow,
 	collections::{HashMap, HashSet},
 	convert::TryInto,
 	marker::PhantomData,
@@ -104,6 +105,8 @@ pub enum Role {
 ///    network peerset.
 ///
 ///    5. Allow querying of the collected addresses via the [`crate::Service`].
+///
+///    6. Set the collected addresses as the reserved peers of the authority peer sets.
 pub struct Worker<Client, Network, Block, DhtEventStream> {
 	/// Channel receiver for messages send by a [`crate::Service`].
 	from_service: Fuse<mpsc::Receiver<ServicetoWorkerMsg>>,
@@ -133,6 +136,17 @@ pub struct Worker<Client, Network, Block, DhtEventStream> {
 	/// Set of in-flight lookups.
 	in_flight_lookups: HashMap<libp2p::kad::record::Key, AuthorityId>,
 
+	/// Interval at which to check whether the authority set has changed.
+	authority_set_interval: ExpIncInterval,
+	/// Authority set at the latest refill of the pending lookups queue.
+	latest_authorities: HashSet<AuthorityId>,
+	/// Whether the local node is part of `latest_authorities`.
+	is_authority: bool,
+	/// Same value as in the configuration.
+	authority_peer_sets: Vec<Cow<'static, str>>,
+	/// Reserved peers last set on `authority_peer_sets`.
+	reserved_peers: HashSet<Multiaddr>,
+
 	addr_cache: addr_cache::AddrCache,
 
 	metrics: Option<Metrics>,
@@ -174,6 +188,10 @@ where
 		// is more simple.
 		let publish_if_changed_interval =
 			ExpIncInterval::new(config.keystore_refresh_interval, config.keystore_refresh_interval);
+		let authority_set_interval = ExpIncInterval::new(
+			config.authority_set_refresh_interval,
+			config.authority_set_refresh_interval,
+		);
 
 		let addr_cache = AddrCache::new();
 
@@ -200,6 +218,11 @@ where
 			query_interval,
 			pending_lookups: Vec::new(),
 			in_flight_lookups: HashMap::new(),
+			authority_set_interval,
+			latest_authorities: HashSet::new(),
+			is_authority: false,
+			authority_peer_sets: config.authority_peer_sets,
+			reserved_peers: HashSet::new(),
 			addr_cache,
 			role,
 			metrics,
@@ -248,6 +271,15 @@ where
 						);
 					}
 				},
+				// Look up the addresses of a new authority set.
+				_ = self.authority_set_interval.next().fuse() => {
+					if let Err(e) = self.refill_if_authority_set_changed().await {
+						error!(
+							target: LOG_TARGET,
+							"Failed to check the authority set: {:?}", e,
+						);
+					}
+				},
 			}
 		}
 	}
@@ -367,16 +399,22 @@ where
 			Role::Discover => HashSet::new(),
 		};
 
-		let mut authorities = self
+		let all_authorities = self
 			.client
 			.runtime_api()
 			.authorities(&id)
-			.map_err(|e| Error::CallingRuntime(e.into()))?
-			.into_iter()
+			.map_err(|e| Error::CallingRuntime(e.into()))?;
+		self.is_authority = all_authorities.iter().any(|id| local_keys.contains(id.as_ref()));
+
+		let mut authorities = all_authorities
+			.iter()
 			.filter(|id| !local_keys.contains(id.as_ref()))
+			.cloned()
 			.collect::<Vec<_>>();
+		self.latest_authorities = all_authorities.into_iter().collect();
 
 		self.addr_cache.retain_ids(&authorities);
+		self.update_reserved_peers();
 
 		authorities.shuffle(&mut thread_rng());
 		self.pending_lookups = authorities;
@@ -393,6 +431,59 @@ where
 		Ok(())
 	}
 
+	/// Refill the pending lookups queue if the authority set has changed since the latest refill.
+	async fn refill_if_authority_set_changed(&mut self) -> Result<()> {
+		let id = BlockId::hash(self.client.info().best_hash);
+		let authorities = self
+			.client
+			.runtime_api()
+			.authorities(&id)
+			.map_err(|e| Error::CallingRuntime(e.into()))?
+			.into_iter()
+			.collect::<HashSet<_>>();
+
+		if authorities == self.latest_authorities {
+			return Ok(())
+		}
+
+		debug!(target: LOG_TARGET, "Authority set changed, looking up the new authorities.");
+		self.refill_pending_lookups_queue().await
+	}
+
+	/// Set the addresses of the authorities as the reserved peers of the authority peer sets, if
+	/// they have changed.
+	fn update_reserved_peers(&mut self) {
+		if self.authority_peer_sets.is_empty() {
+			return
+		}
+
+		let reserved_peers = if self.is_authority {
+			self.addr_cache.addresses().cloned().collect::<HashSet<_>>()
+		} else {
+			HashSet::new()
+		};
+		if reserved_peers == self.reserved_peers {
+			return
+		}
+
+		debug!(
+			target: LOG_TARGET,
+			"Setting {} authority addresses as reserved peers.",
+			reserved_peers.len(),
+		);
+		for protocol in &self.authority_peer_sets {
+			if let Err(e) =
+				self.network.set_reserved_peers(protocol.clone(), reserved_peers.clone())
+			{
+				error!(
+					target: LOG_TARGET,
+					"Failed to set the reserved peers of {}: {}", protocol, e,
+				);
+			}
+		}
+		self.reserved_peers = reserved_peers;
+	}
+
 	fn start_new_lookups(&mut self) {
 		while self.in_flight_lookups.len() < MAX_IN_FLIGHT_LOOKUPS {
 			let authority_id = match self.pending_lookups.pop() {
@@ -550,6 +641,7 @@ where
 					.known_authorities_count
 					.set(self.addr_cache.num_authority_ids().try_into().unwrap_or(std::u64::MAX));
 			}
+			self.update_reserved_peers();
 		}
 		Ok(())
 	}
@@ -598,6 +690,13 @@ pub trait NetworkProvider: NetworkStateInfo {
 
 	/// Start getting a value from the Dht.
 	fn get_value(&self, key: &libp2p::kad::record::Key);
+
+	/// Replace the reserved peers of the peer set with the given protocol name.
+	fn set_reserved_peers(
+		&self,
+		protocol: Cow<'static, str>,
+		peers: HashSet<Multiaddr>,
+	) -> std::result::Result<(), String>;
 }
 
 #[async_trait::async_trait]
@@ -612,6 +711,13 @@ where
 	fn get_value(&self, key: &libp2p::kad::record::Key) {
 		self.get_value(key)
 	}
+	fn set_reserved_peers(
+		&self,
+		protocol: Cow<'static, str>,
+		peers: HashSet<Multiaddr>,
+	) -> std::result::Result<(), String> {
+		self.set_reserved_peers(protocol, peers)
+	}
 }
 
 fn hash_authority_id(id: &[u8]) -> libp2p::kad::record::Key {
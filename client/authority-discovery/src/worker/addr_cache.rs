@@ -116,6 +116,11 @@ impl AddrCache {
 		self.authority_id_to_addresses.get(authority_id)
 	}
 
+	/// Returns the addresses of all the authorities in the cache.
+	pub fn addresses(&self) -> impl Iterator<Item = &Multiaddr> {
+		self.authority_id_to_addresses.values().flatten()
+	}
+
 	/// Returns the [`AuthorityId`]s for the given [`PeerId`].
 	///
 	/// As the authority id can change between sessions, one [`PeerId`] can be mapped to
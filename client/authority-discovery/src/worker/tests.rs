@@ -19,6 +19,7 @@
 use crate::worker::schema;
 
 use std::{
+	borrow::Cow,
 	collections::HashSet,
 	sync::{Arc, Mutex},
 	task::Poll,
@@ -125,6 +126,7 @@ pub struct TestNetwork {
 	// vectors below.
 	pub put_value_call: Arc<Mutex<Vec<(kad::record::Key, Vec<u8>)>>>,
 	pub get_value_call: Arc<Mutex<Vec<kad::record::Key>>>,
+	pub set_reserved_peers_call: Arc<Mutex<Vec<(Cow<'static, str>, HashSet<Multiaddr>)>>>,
 	event_sender: mpsc::UnboundedSender<TestNetworkEvent>,
 	event_receiver: Option<mpsc::UnboundedReceiver<TestNetworkEvent>>,
 }
@@ -143,6 +145,7 @@ impl Default for TestNetwork {
 			external_addresses: vec!["/ip6/2001:db8::/tcp/30333".parse().unwrap()],
 			put_value_call: Default::default(),
 			get_value_call: Default::default(),
+			set_reserved_peers_call: Default::default(),
 			event_sender: tx,
 			event_receiver: Some(rx),
 		}
@@ -165,6 +168,14 @@ impl NetworkProvider for TestNetwork {
 			.unbounded_send(TestNetworkEvent::GetCalled(key.clone()))
 			.unwrap();
 	}
+	fn set_reserved_peers(
+		&self,
+		protocol: Cow<'static, str>,
+		peers: HashSet<Multiaddr>,
+	) -> std::result::Result<(), String> {
+		self.set_reserved_peers_call.lock().unwrap().push((protocol, peers));
+		Ok(())
+	}
 }
 
 impl NetworkStateInfo for TestNetwork {
@@ -569,6 +580,130 @@ fn do_not_cache_addresses_without_peer_id() {
 	);
 }
 
+#[test]
+fn authorities_are_reserved_peers_of_authority_peer_sets() {
+	let remote_key_store = KeyStore::new();
+	let remote_public =
+		block_on(remote_key_store.sr25519_generate_new(key_types::AUTHORITY_DISCOVERY, None))
+			.unwrap();
+	let local_key_store = KeyStore::new();
+	let local_public =
+		block_on(local_key_store.sr25519_generate_new(key_types::AUTHORITY_DISCOVERY, None))
+			.unwrap();
+
+	let remote_addr: Multiaddr = "/ip6/2001:db8:0:0:0:0:0:2/tcp/30333".parse().unwrap();
+	let remote_addr = remote_addr.with(multiaddr::Protocol::P2p(PeerId::random().into()));
+	let dht_event = block_on(build_dht_event(
+		vec![remote_addr.clone()],
+		remote_public.into(),
+		&remote_key_store,
+	));
+
+	let (_dht_event_tx, dht_event_rx) = channel(1);
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+	let (_to_worker, from_service) = mpsc::channel(0);
+	let mut worker = Worker::new(
+		from_service,
+		Arc::new(TestApi { authorities: vec![remote_public.into(), local_public.into()] }),
+		network.clone(),
+		Box::pin(dht_event_rx),
+		Role::PublishAndDiscover(Arc::new(local_key_store)),
+		None,
+		crate::WorkerConfig {
+			authority_peer_sets: vec!["/authorities".into()],
+			..Default::default()
+		},
+	);
+
+	block_on(worker.refill_pending_lookups_queue()).unwrap();
+	worker.start_new_lookups();
+	assert!(network.set_reserved_peers_call.lock().unwrap().is_empty());
+
+	worker.handle_dht_value_found_event(vec![dht_event]).unwrap();
+	assert_eq!(
+		*network.set_reserved_peers_call.lock().unwrap(),
+		vec![("/authorities".into(), HashSet::from([remote_addr]))],
+	);
+
+	// The local node left the authority set.
+	worker.client = Arc::new(TestApi { authorities: vec![remote_public.into()] });
+	block_on(worker.refill_if_authority_set_changed()).unwrap();
+	assert_eq!(
+		network.set_reserved_peers_call.lock().unwrap().last(),
+		Some(&("/authorities".into(), HashSet::new())),
+	);
+}
+
+#[test]
+fn authority_set_changes_update_reserved_peers_of_authority_peer_sets() {
+	let local_key_store = KeyStore::new();
+	let local_public =
+		block_on(local_key_store.sr25519_generate_new(key_types::AUTHORITY_DISCOVERY, None))
+			.unwrap();
+
+	let mut remotes = Vec::new();
+	for i in 1..=3 {
+		let key_store = KeyStore::new();
+		let public =
+			block_on(key_store.sr25519_generate_new(key_types::AUTHORITY_DISCOVERY, None)).unwrap();
+		let addr: Multiaddr = format!("/ip6/2001:db8:0:0:0:0:0:{}/tcp/30333", i).parse().unwrap();
+		let addr = addr.with(multiaddr::Protocol::P2p(PeerId::random().into()));
+		let dht_event = block_on(build_dht_event(vec![addr.clone()], public.into(), &key_store));
+		remotes.push((AuthorityId::from(public), addr, dht_event));
+	}
+	let [(remote_1, addr_1, event_1), (remote_2, addr_2, event_2), (remote_3, addr_3, event_3)] =
+		<[_; 3]>::try_from(remotes).unwrap();
+
+	let (_dht_event_tx, dht_event_rx) = channel(1);
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+	let (_to_worker, from_service) = mpsc::channel(0);
+	let mut worker = Worker::new(
+		from_service,
+		Arc::new(TestApi {
+			authorities: vec![remote_1.clone(), remote_2.clone(), local_public.into()],
+		}),
+		network.clone(),
+		Box::pin(dht_event_rx),
+		Role::PublishAndDiscover(Arc::new(local_key_store)),
+		None,
+		crate::WorkerConfig {
+			authority_peer_sets: vec!["/authorities".into()],
+			..Default::default()
+		},
+	);
+
+	block_on(worker.refill_pending_lookups_queue()).unwrap();
+	worker.start_new_lookups();
+	worker.handle_dht_value_found_event(vec![event_1]).unwrap();
+	worker.handle_dht_value_found_event(vec![event_2]).unwrap();
+	assert_eq!(
+		network.set_reserved_peers_call.lock().unwrap().last(),
+		Some(&("/authorities".into(), HashSet::from([addr_1.clone(), addr_2]))),
+	);
+
+	// An unchanged authority set does not touch the reserved peers.
+	let calls = network.set_reserved_peers_call.lock().unwrap().len();
+	block_on(worker.refill_if_authority_set_changed()).unwrap();
+	assert_eq!(network.set_reserved_peers_call.lock().unwrap().len(), calls);
+
+	// `remote_2` is replaced by `remote_3`, its address is dropped right away and the one of
+	// `remote_3` is added once discovered.
+	worker.client =
+		Arc::new(TestApi { authorities: vec![remote_1, remote_3, local_public.into()] });
+	block_on(worker.refill_if_authority_set_changed()).unwrap();
+	assert_eq!(
+		network.set_reserved_peers_call.lock().unwrap().last(),
+		Some(&("/authorities".into(), HashSet::from([addr_1.clone()]))),
+	);
+
+	worker.start_new_lookups();
+	worker.handle_dht_value_found_event(vec![event_3]).unwrap();
+	assert_eq!(
+		network.set_reserved_peers_call.lock().unwrap().last(),
+		Some(&("/authorities".into(), HashSet::from([addr_1, addr_3]))),
+	);
+}
+
 #[test]
 fn addresses_to_publish_adds_p2p() {
 	let (_dht_event_tx, dht_event_rx) = channel(1000);
@@ -85,6 +85,10 @@ pub struct ProposerFactory<A, B, C, PR> {
 	telemetry: Option<TelemetryHandle>,
 	/// When estimating the block size, should the proof be included?
 	include_proof_in_block_size_estimation: bool,
+	/// The maximum estimated size of the compact storage proof of a block.
+	///
+	/// Transactions are no longer included once it is reached.
+	proof_size_limit: Option<usize>,
 	/// phantom member to pin the `Backend`/`ProofRecording` type.
 	_phantom: PhantomData<(B, PR)>,
 }
@@ -110,6 +114,7 @@ impl<A, B, C> ProposerFactory<A, B, C, DisableProofRecording> {
 			telemetry,
 			client,
 			include_proof_in_block_size_estimation: false,
+			proof_size_limit: None,
 			_phantom: PhantomData,
 		}
 	}
@@ -138,6 +143,7 @@ impl<A, B, C> ProposerFactory<A, B, C, EnableProofRecording> {
 			soft_deadline_percent: DEFAULT_SOFT_DEADLINE_PERCENT,
 			telemetry,
 			include_proof_in_block_size_estimation: true,
+			proof_size_limit: None,
 			_phantom: PhantomData,
 		}
 	}
@@ -146,6 +152,14 @@ impl<A, B, C> ProposerFactory<A, B, C, EnableProofRecording> {
 	pub fn disable_proof_in_block_size_estimation(&mut self) {
 		self.include_proof_in_block_size_estimation = false;
 	}
+
+	/// Set the maximum size in bytes of the compact storage proof of a block.
+	///
+	/// Transactions which would make the proof exceed the limit are not included, in the same way
+	/// as transactions exhausting the block resources. There is no limit by default.
+	pub fn set_proof_size_limit(&mut self, limit: usize) {
+		self.proof_size_limit = Some(limit);
+	}
 }
 
 impl<A, B, C, PR> ProposerFactory<A, B, C, PR> {
@@ -216,6 +230,7 @@ where
 			telemetry: self.telemetry.clone(),
 			_phantom: PhantomData,
 			include_proof_in_block_size_estimation: self.include_proof_in_block_size_estimation,
+			proof_size_limit: self.proof_size_limit,
 		};
 
 		proposer
@@ -258,6 +273,7 @@ pub struct Proposer<B, Block: BlockT, C, A: TransactionPool, PR> {
 	metrics: PrometheusMetrics,
 	default_block_size_limit: usize,
 	include_proof_in_block_size_estimation: bool,
+	proof_size_limit: Option<usize>,
 	soft_deadline_percent: Percent,
 	telemetry: Option<TelemetryHandle>,
 	_phantom: PhantomData<(B, PR)>,
@@ -380,6 +396,9 @@ where
 			}
 		}
 
+		// The inherents are always included, the proof size limit only applies to transactions.
+		block_builder.set_proof_size_limit(self.proof_size_limit);
+
 		// proceed with transactions
 		// We calculate soft deadline used only in case we start skipping transactions.
 		let now = (self.now)();
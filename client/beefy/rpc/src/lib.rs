@@ -22,17 +22,63 @@
 
 use std::sync::Arc;
 
+use parking_lot::RwLock;
 use sp_runtime::traits::Block as BlockT;
 
-use futures::{FutureExt, SinkExt, StreamExt};
+use futures::{future, task::SpawnExt, FutureExt, SinkExt, StreamExt};
 use jsonrpc_derive::rpc;
 use jsonrpc_pubsub::{manager::SubscriptionManager, typed::Subscriber, SubscriptionId};
 use log::warn;
 
-use beefy_gadget::notification::BeefySignedCommitmentStream;
+use beefy_gadget::{
+	notification::{BeefyBestBlockStream, BeefySignedCommitmentStream},
+	request_response::BeefyJustificationRequester,
+};
 
 mod notification;
 
+type FutureResult<T> = jsonrpc_core::BoxFuture<Result<T, jsonrpc_core::Error>>;
+
+#[derive(Debug, thiserror::Error)]
+/// Top-level error type for the RPC handler
+pub enum Error {
+	/// The BEEFY RPC endpoint is not ready.
+	#[error("BEEFY RPC endpoint not ready")]
+	EndpointNotReady,
+	/// The BEEFY gadget is not running.
+	#[error("BEEFY justification request failed")]
+	JustificationRequestFailed,
+}
+
+/// The error codes returned by jsonrpc.
+pub enum ErrorCode {
+	/// Returned when BEEFY RPC endpoint is not ready.
+	NotReady = 1,
+	/// Returned when the BEEFY gadget could not handle a justification request.
+	JustificationRequest,
+}
+
+impl From<Error> for ErrorCode {
+	fn from(error: Error) -> Self {
+		match error {
+			Error::EndpointNotReady => ErrorCode::NotReady,
+			Error::JustificationRequestFailed => ErrorCode::JustificationRequest,
+		}
+	}
+}
+
+impl From<Error> for jsonrpc_core::Error {
+	fn from(error: Error) -> Self {
+		let message = error.to_string();
+		let code = ErrorCode::from(error);
+		jsonrpc_core::Error {
+			message,
+			code: jsonrpc_core::ErrorCode::ServerError(code as i64),
+			data: None,
+		}
+	}
+}
+
 /// Provides RPC methods for interacting with BEEFY.
 #[rpc]
 pub trait BeefyApi<Notification, Hash> {
@@ -62,26 +108,62 @@ pub trait BeefyApi<Notification, Hash> {
 		metadata: Option<Self::Metadata>,
 		id: SubscriptionId,
 	) -> jsonrpc_core::Result<bool>;
+
+	/// Returns hash of the latest BEEFY finalized block as seen by this client.
+	///
+	/// The latest BEEFY block might not be available if the BEEFY gadget is not running
+	/// in the network or if the client is still initializing or syncing with the network.
+	/// In such case an error would be returned.
+	#[rpc(name = "beefy_getFinalizedHead")]
+	fn latest_finalized(&self) -> FutureResult<Hash>;
+
+	/// Returns the SCALE-encoded BEEFY justification of the given finalized block.
+	///
+	/// Justifications missing from the local storage are requested from peers. Returns
+	/// `null` if the block has no justification or none of the peers could provide it, and
+	/// fails if too many justifications are being requested from peers already.
+	#[rpc(name = "beefy_justificationAt")]
+	fn justification_at(&self, block: Hash) -> FutureResult<Option<sp_core::Bytes>>;
 }
 
 /// Implements the BeefyApi RPC trait for interacting with BEEFY.
 pub struct BeefyRpcHandler<Block: BlockT> {
 	signed_commitment_stream: BeefySignedCommitmentStream<Block>,
+	beefy_best_block: Arc<RwLock<Option<Block::Hash>>>,
+	justification_requester: BeefyJustificationRequester<Block>,
 	manager: SubscriptionManager,
 }
 
 impl<Block: BlockT> BeefyRpcHandler<Block> {
 	/// Creates a new BeefyRpcHandler instance.
-	pub fn new<E>(signed_commitment_stream: BeefySignedCommitmentStream<Block>, executor: E) -> Self
+	pub fn new<E>(
+		signed_commitment_stream: BeefySignedCommitmentStream<Block>,
+		best_block_stream: BeefyBestBlockStream<Block>,
+		justification_requester: BeefyJustificationRequester<Block>,
+		executor: E,
+	) -> Self
 	where
 		E: futures::task::Spawn + Send + Sync + 'static,
 	{
+		let beefy_best_block = Arc::new(RwLock::new(None));
+
+		let stream = best_block_stream.subscribe();
+		let closure_clone = beefy_best_block.clone();
+		let future = stream.for_each(move |best_beefy| {
+			let async_clone = closure_clone.clone();
+			async move { *async_clone.write() = Some(best_beefy) }
+		});
+
+		if let Err(e) = executor.spawn(future) {
+			warn!("Failed to spawn BEEFY best block tracking task: {:?}", e);
+		}
+
 		let manager = SubscriptionManager::new(Arc::new(executor));
-		Self { signed_commitment_stream, manager }
+		Self { signed_commitment_stream, beefy_best_block, justification_requester, manager }
 	}
 }
 
-impl<Block> BeefyApi<notification::SignedCommitment, Block> for BeefyRpcHandler<Block>
+impl<Block> BeefyApi<notification::SignedCommitment, Block::Hash> for BeefyRpcHandler<Block>
 where
 	Block: BlockT,
 {
@@ -111,4 +193,124 @@ where
 	) -> jsonrpc_core::Result<bool> {
 		Ok(self.manager.cancel(id))
 	}
+
+	fn latest_finalized(&self) -> FutureResult<Block::Hash> {
+		let result = self.beefy_best_block.read().ok_or(Error::EndpointNotReady);
+		future::ready(result.map_err(jsonrpc_core::Error::from)).boxed()
+	}
+
+	fn justification_at(&self, block: Block::Hash) -> FutureResult<Option<sp_core::Bytes>> {
+		let requester = self.justification_requester.clone();
+		async move {
+			requester
+				.justification(block)
+				.await
+				.map(|justification| justification.map(Into::into))
+				.map_err(|_| Error::JustificationRequestFailed.into())
+		}
+		.boxed()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::time::Duration;
+
+	use beefy_gadget::{
+		notification::{BeefyBestBlockSender, BeefySignedCommitmentStream},
+		request_response::BeefyJustificationRequests,
+	};
+	use sp_core::H256;
+	use substrate_test_runtime_client::runtime::Block;
+
+	fn setup_io_handler() -> (
+		jsonrpc_core::MetaIoHandler<sc_rpc::Metadata>,
+		BeefyBestBlockSender<Block>,
+		BeefyJustificationRequests<Block>,
+	) {
+		let (_, signed_commitment_stream) = BeefySignedCommitmentStream::channel();
+		let (best_block_sender, best_block_stream) = BeefyBestBlockStream::channel();
+		let (justification_requester, justification_requests) =
+			BeefyJustificationRequester::channel();
+
+		let handler = BeefyRpcHandler::new(
+			signed_commitment_stream,
+			best_block_stream,
+			justification_requester,
+			sc_rpc::testing::TaskExecutor,
+		);
+
+		let mut io = jsonrpc_core::MetaIoHandler::default();
+		io.extend_with(BeefyApi::to_delegate(handler));
+
+		(io, best_block_sender, justification_requests)
+	}
+
+	#[test]
+	fn uninitialized_rpc_handler() {
+		let (io, _, _) = setup_io_handler();
+
+		let request = r#"{"jsonrpc":"2.0","method":"beefy_getFinalizedHead","params":[],"id":1}"#;
+		let response = r#"{"jsonrpc":"2.0","error":{"code":1,"message":"BEEFY RPC endpoint not ready"},"id":1}"#;
+
+		let meta = sc_rpc::Metadata::default();
+		assert_eq!(Some(response.into()), io.handle_request_sync(request, meta));
+	}
+
+	#[test]
+	fn latest_finalized_rpc() {
+		let (io, best_block_sender, _) = setup_io_handler();
+
+		let hash = H256::repeat_byte(0x42);
+		best_block_sender.notify(hash);
+
+		let request = r#"{"jsonrpc":"2.0","method":"beefy_getFinalizedHead","params":[],"id":1}"#;
+		let response = "{\"jsonrpc\":\"2.0\",\"result\":\"0x\
+			4242424242424242424242424242424242424242424242424242424242424242\",\"id\":1}";
+
+		// the best block is tracked in the background.
+		let mut attempts = 0;
+		while io.handle_request_sync(request, Default::default()) != Some(response.into()) {
+			attempts += 1;
+			assert!(attempts < 100, "best BEEFY block not reported");
+			std::thread::sleep(Duration::from_millis(10));
+		}
+	}
+
+	#[test]
+	fn justification_at_rpc() {
+		let (io, _, justification_requests) = setup_io_handler();
+
+		let justified = H256::repeat_byte(0x42);
+		sc_rpc::testing::TaskExecutor
+			.spawn(justification_requests.for_each(move |(block, response_sender)| {
+				let justification = if block == justified { Some(vec![1, 2, 3]) } else { None };
+				let _ = response_sender.send(justification);
+				future::ready(())
+			}))
+			.unwrap();
+
+		let request = "{\"jsonrpc\":\"2.0\",\"method\":\"beefy_justificationAt\",\"params\":[\"0x\
+			4242424242424242424242424242424242424242424242424242424242424242\"],\"id\":1}";
+		let response = r#"{"jsonrpc":"2.0","result":"0x010203","id":1}"#;
+		assert_eq!(Some(response.into()), io.handle_request_sync(request, Default::default()));
+
+		let request = "{\"jsonrpc\":\"2.0\",\"method\":\"beefy_justificationAt\",\"params\":[\"0x\
+			4343434343434343434343434343434343434343434343434343434343434343\"],\"id\":1}";
+		let response = r#"{"jsonrpc":"2.0","result":null,"id":1}"#;
+		assert_eq!(Some(response.into()), io.handle_request_sync(request, Default::default()));
+	}
+
+	#[test]
+	fn justification_at_rpc_without_beefy_gadget() {
+		let (io, _, justification_requests) = setup_io_handler();
+		drop(justification_requests);
+
+		let request = "{\"jsonrpc\":\"2.0\",\"method\":\"beefy_justificationAt\",\"params\":[\"0x\
+			4242424242424242424242424242424242424242424242424242424242424242\"],\"id\":1}";
+		let response = r#"{"jsonrpc":"2.0","error":{"code":2,"message":"BEEFY justification request failed"},"id":1}"#;
+		assert_eq!(Some(response.into()), io.handle_request_sync(request, Default::default()));
+	}
 }
@@ -28,4 +28,6 @@ pub enum Error {
 	Keystore(String),
 	#[error("Signature error: {0}")]
 	Signature(String),
+	#[error("Verification error: {0}")]
+	Verification(String),
 }
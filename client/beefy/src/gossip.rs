@@ -16,9 +16,12 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use std::{collections::BTreeMap, time::Duration};
+use std::{
+	collections::{BTreeMap, HashSet},
+	time::Duration,
+};
 
-use sc_network::PeerId;
+use sc_network::{ObservedRole, PeerId};
 use sc_network_gossip::{MessageIntent, ValidationResult, Validator, ValidatorContext};
 use sp_core::hashing::twox_64;
 use sp_runtime::traits::{Block, Hash, Header, NumberFor};
@@ -68,6 +71,7 @@ where
 {
 	topic: B::Hash,
 	known_votes: RwLock<KnownVotes<B>>,
+	known_peers: Mutex<HashSet<PeerId>>,
 	next_rebroadcast: Mutex<Instant>,
 }
 
@@ -79,6 +83,7 @@ where
 		GossipValidator {
 			topic: topic::<B>(),
 			known_votes: RwLock::new(BTreeMap::new()),
+			known_peers: Mutex::new(HashSet::new()),
 			next_rebroadcast: Mutex::new(Instant::now() + REBROADCAST_AFTER),
 		}
 	}
@@ -106,6 +111,11 @@ where
 		}
 	}
 
+	/// Return the peers we are currently connected to over the BEEFY gossip protocol.
+	pub(crate) fn peers(&self) -> Vec<PeerId> {
+		self.known_peers.lock().iter().cloned().collect()
+	}
+
 	fn add_known(known_votes: &mut KnownVotes<B>, round: &NumberFor<B>, hash: MessageHash) {
 		known_votes.get_mut(round).map(|known| known.insert(hash));
 	}
@@ -136,6 +146,14 @@ impl<B> Validator<B> for GossipValidator<B>
 where
 	B: Block,
 {
+	fn new_peer(&self, _context: &mut dyn ValidatorContext<B>, who: &PeerId, _role: ObservedRole) {
+		self.known_peers.lock().insert(*who);
+	}
+
+	fn peer_disconnected(&self, _context: &mut dyn ValidatorContext<B>, who: &PeerId) {
+		self.known_peers.lock().remove(who);
+	}
+
 	fn validate(
 		&self,
 		_context: &mut dyn ValidatorContext<B>,
@@ -226,7 +244,7 @@ where
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
 	use sc_keystore::LocalKeystore;
 	use sc_network_test::Block;
 	use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
@@ -322,7 +340,7 @@ mod tests {
 		assert!(GossipValidator::<Block>::is_live(&live, &10u64));
 	}
 
-	struct TestContext;
+	pub(crate) struct TestContext;
 	impl<B: sp_runtime::traits::Block> ValidatorContext<B> for TestContext {
 		fn broadcast_topic(&mut self, _topic: B::Hash, _force: bool) {
 			todo!()
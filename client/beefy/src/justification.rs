@@ -0,0 +1,186 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Decoding and verification of BEEFY justifications received from peers.
+
+use codec::{Decode, Encode};
+use sp_runtime::traits::{Block, NumberFor};
+
+use beefy_primitives::{crypto::Public, ValidatorSet, VersionedCommitment};
+
+use crate::{error::Error, keystore::BeefyKeystore, notification::SignedCommitment, round};
+
+/// Decode a SCALE-encoded [`VersionedCommitment`] and verify that it is a valid BEEFY
+/// justification of block `target_number`, signed by `validator_set`.
+pub(crate) fn decode_and_verify_commitment<B: Block>(
+	encoded: &[u8],
+	target_number: NumberFor<B>,
+	validator_set: &ValidatorSet<Public>,
+) -> Result<SignedCommitment<B>, Error> {
+	let VersionedCommitment::V1(signed_commitment) =
+		VersionedCommitment::<NumberFor<B>>::decode(&mut &*encoded)
+			.map_err(|e| Error::Verification(format!("failed to decode commitment: {}", e)))?;
+
+	verify_with_validator_set::<B>(target_number, validator_set, &signed_commitment)?;
+
+	Ok(signed_commitment)
+}
+
+/// Verify that `signed_commitment` commits to block `target_number` and carries the
+/// signatures of at least a threshold of `validator_set`.
+fn verify_with_validator_set<B: Block>(
+	target_number: NumberFor<B>,
+	validator_set: &ValidatorSet<Public>,
+	signed_commitment: &SignedCommitment<B>,
+) -> Result<(), Error> {
+	let commitment = &signed_commitment.commitment;
+
+	if commitment.validator_set_id != validator_set.id {
+		return Err(Error::Verification(format!(
+			"expected validator set id {}, got {}",
+			validator_set.id, commitment.validator_set_id,
+		)))
+	}
+
+	if commitment.block_number != target_number {
+		return Err(Error::Verification(format!(
+			"expected commitment for block #{}, got #{}",
+			target_number, commitment.block_number,
+		)))
+	}
+
+	if validator_set.validators.is_empty() {
+		return Err(Error::Verification("empty validator set".into()))
+	}
+
+	if signed_commitment.signatures.len() != validator_set.validators.len() {
+		return Err(Error::Verification("signatures do not match the validator set".into()))
+	}
+
+	let message = commitment.encode();
+	let valid_signatures = validator_set
+		.validators
+		.iter()
+		.zip(signed_commitment.signatures.iter())
+		.filter(|(id, signature)| match signature {
+			Some(signature) => BeefyKeystore::verify(id, signature, &message),
+			None => false,
+		})
+		.count();
+
+	if valid_signatures < round::threshold(validator_set.validators.len()) {
+		return Err(Error::Verification(format!(
+			"not enough valid signatures: {}",
+			valid_signatures,
+		)))
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use sc_network_test::Block;
+
+	use beefy_primitives::{known_payload_ids, Commitment, Payload, SignedCommitment};
+
+	use super::*;
+	use crate::keystore::tests::Keyring;
+
+	fn validator_set(keys: &[Keyring]) -> ValidatorSet<Public> {
+		ValidatorSet { validators: keys.iter().map(|k| k.public()).collect(), id: 0 }
+	}
+
+	fn commitment(block_number: u64, validator_set_id: u64) -> Commitment<u64> {
+		let payload = Payload::new(known_payload_ids::MMR_ROOT_ID, vec![42; 32]);
+		Commitment { payload, block_number, validator_set_id }
+	}
+
+	fn signed_commitment(block_number: u64, keys: &[Keyring]) -> SignedCommitment<u64> {
+		let commitment = commitment(block_number, 0);
+		let signatures = keys.iter().map(|k| Some(k.sign(&commitment.encode()))).collect();
+		SignedCommitment { commitment, signatures }
+	}
+
+	#[test]
+	fn should_verify_signed_commitment() {
+		let keys = &[Keyring::Alice, Keyring::Bob, Keyring::Charlie];
+		let validator_set = validator_set(keys);
+
+		let signed_commitment = signed_commitment(1, keys);
+		let encoded = VersionedCommitment::V1(signed_commitment.clone()).encode();
+
+		assert_eq!(
+			decode_and_verify_commitment::<Block>(&encoded, 1, &validator_set),
+			Ok(signed_commitment),
+		);
+	}
+
+	#[test]
+	fn should_reject_commitment_without_enough_signatures() {
+		let keys = &[Keyring::Alice, Keyring::Bob, Keyring::Charlie];
+		let validator_set = validator_set(keys);
+
+		let mut signed_commitment = signed_commitment(1, keys);
+		signed_commitment.signatures[1] = None;
+		let encoded = VersionedCommitment::V1(signed_commitment).encode();
+
+		assert!(matches!(
+			decode_and_verify_commitment::<Block>(&encoded, 1, &validator_set),
+			Err(Error::Verification(_)),
+		));
+	}
+
+	#[test]
+	fn should_reject_commitment_of_empty_validator_set() {
+		let signed_commitment =
+			SignedCommitment { commitment: commitment(1, 0), signatures: vec![] };
+		let encoded = VersionedCommitment::V1(signed_commitment).encode();
+
+		assert!(matches!(
+			decode_and_verify_commitment::<Block>(&encoded, 1, &ValidatorSet::empty()),
+			Err(Error::Verification(_)),
+		));
+	}
+
+	#[test]
+	fn should_reject_commitment_for_other_block_or_set() {
+		let validator_set = validator_set(&[Keyring::Alice]);
+
+		let signed_commitment =
+			SignedCommitment { commitment: commitment(2, 0), signatures: vec![None] };
+		let encoded = VersionedCommitment::V1(signed_commitment).encode();
+		assert!(matches!(
+			decode_and_verify_commitment::<Block>(&encoded, 1, &validator_set),
+			Err(Error::Verification(_)),
+		));
+
+		let signed_commitment =
+			SignedCommitment { commitment: commitment(1, 1), signatures: vec![None] };
+		let encoded = VersionedCommitment::V1(signed_commitment).encode();
+		assert!(matches!(
+			decode_and_verify_commitment::<Block>(&encoded, 1, &validator_set),
+			Err(Error::Verification(_)),
+		));
+
+		assert!(matches!(
+			decode_and_verify_commitment::<Block>(&[1, 2, 3], 1, &validator_set),
+			Err(Error::Verification(_)),
+		));
+	}
+}
@@ -33,12 +33,17 @@ use beefy_primitives::BeefyApi;
 
 mod error;
 mod gossip;
+mod justification;
 mod keystore;
 mod metrics;
 mod round;
 mod worker;
 
 pub mod notification;
+pub mod request_response;
+pub mod warp_proof;
+
+use request_response::JustificationsNetwork;
 
 pub const BEEFY_PROTOCOL_NAME: &str = "/paritytech/beefy/1";
 
@@ -85,7 +90,7 @@ where
 	BE: Backend<B>,
 	C: Client<B, BE>,
 	C::Api: BeefyApi<B>,
-	N: GossipNetwork<B> + Clone + Send + 'static,
+	N: GossipNetwork<B> + JustificationsNetwork + Clone + Send + 'static,
 {
 	/// BEEFY client
 	pub client: Arc<C>,
@@ -93,10 +98,14 @@ where
 	pub backend: Arc<BE>,
 	/// Local key store
 	pub key_store: Option<SyncCryptoStorePtr>,
-	/// Gossip network
+	/// Gossip network, also used to request justifications from peers
 	pub network: N,
 	/// BEEFY signed commitment sender
 	pub signed_commitment_sender: notification::BeefySignedCommitmentSender<B>,
+	/// BEEFY best block sender
+	pub best_block_sender: notification::BeefyBestBlockSender<B>,
+	/// Requests for justifications to be fetched from peers if missing locally
+	pub justification_requests: request_response::BeefyJustificationRequests<B>,
 	/// Minimal delta between blocks, BEEFY should vote for
 	pub min_block_delta: u32,
 	/// Prometheus metric registry
@@ -112,7 +121,7 @@ where
 	BE: Backend<B>,
 	C: Client<B, BE>,
 	C::Api: BeefyApi<B>,
-	N: GossipNetwork<B> + Clone + Send + 'static,
+	N: GossipNetwork<B> + JustificationsNetwork + Clone + Send + 'static,
 {
 	let BeefyParams {
 		client,
@@ -120,11 +129,18 @@ where
 		key_store,
 		network,
 		signed_commitment_sender,
+		best_block_sender,
+		justification_requests,
 		min_block_delta,
 		prometheus_registry,
 	} = beefy_params;
 
 	let gossip_validator = Arc::new(gossip::GossipValidator::new());
+	let on_demand_justifications =
+		request_response::outgoing_requests_engine::OnDemandJustificationsEngine::new(
+			network.clone(),
+			gossip_validator.clone(),
+		);
 	let gossip_engine =
 		GossipEngine::new(network, BEEFY_PROTOCOL_NAME, gossip_validator.clone(), None);
 
@@ -147,13 +163,16 @@ where
 		backend,
 		key_store: key_store.into(),
 		signed_commitment_sender,
+		best_block_sender,
 		gossip_engine,
 		gossip_validator,
+		on_demand_justifications,
+		justification_requests,
 		min_block_delta,
 		metrics,
 	};
 
-	let worker = worker::BeefyWorker::<_, _, _>::new(worker_params);
+	let worker = worker::BeefyWorker::<_, _, _, _>::new(worker_params);
 
 	worker.run().await
 }
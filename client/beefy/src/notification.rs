@@ -36,6 +36,15 @@ type SignedCommitmentSender<Block> = TracingUnboundedSender<SignedCommitment<Blo
 /// themselves.
 type SharedSignedCommitmentSenders<Block> = Arc<Mutex<Vec<SignedCommitmentSender<Block>>>>;
 
+/// Stream of best BEEFY block hashes returned when subscribing.
+type BestBlockStream<B> = TracingUnboundedReceiver<<B as Block>::Hash>;
+
+/// Sending endpoint for notifying about the best BEEFY block.
+type BestBlockSender<B> = TracingUnboundedSender<<B as Block>::Hash>;
+
+/// Collection of best BEEFY block channel sending endpoints shared with the receiver side.
+type SharedBestBlockSenders<B> = Arc<Mutex<Vec<BestBlockSender<B>>>>;
+
 /// The sending half of the signed commitment channel(s).
 ///
 /// Used to send notifications about signed commitments generated at the end of a BEEFY round.
@@ -110,3 +119,64 @@ where
 		receiver
 	}
 }
+
+/// The sending half of the best BEEFY block channel(s).
+///
+/// Used to send notifications about the hash of the best block finalized by BEEFY.
+#[derive(Clone)]
+pub struct BeefyBestBlockSender<B>
+where
+	B: Block,
+{
+	subscribers: SharedBestBlockSenders<B>,
+}
+
+impl<B> BeefyBestBlockSender<B>
+where
+	B: Block,
+{
+	/// Send out a notification to all subscribers that a new best block has been finalized by
+	/// BEEFY.
+	pub fn notify(&self, best_block: B::Hash) {
+		let mut subscribers = self.subscribers.lock();
+
+		// do an initial prune on closed subscriptions
+		subscribers.retain(|n| !n.is_closed());
+
+		if !subscribers.is_empty() {
+			subscribers.retain(|n| n.unbounded_send(best_block).is_ok());
+		}
+	}
+}
+
+/// The receiving half of the best BEEFY block channel.
+///
+/// Used to receive notifications about the hash of the best block finalized by BEEFY.
+#[derive(Clone)]
+pub struct BeefyBestBlockStream<B>
+where
+	B: Block,
+{
+	subscribers: SharedBestBlockSenders<B>,
+}
+
+impl<B> BeefyBestBlockStream<B>
+where
+	B: Block,
+{
+	/// Creates a new pair of receiver and sender of best BEEFY block notifications.
+	pub fn channel() -> (BeefyBestBlockSender<B>, Self) {
+		let subscribers = Arc::new(Mutex::new(vec![]));
+		let receiver = BeefyBestBlockStream { subscribers: subscribers.clone() };
+		let sender = BeefyBestBlockSender { subscribers };
+		(sender, receiver)
+	}
+
+	/// Subscribe to a channel through which the hash of the best BEEFY block is sent each time
+	/// it changes.
+	pub fn subscribe(&self) -> BestBlockStream<B> {
+		let (sender, receiver) = tracing_unbounded("mpsc_beefy_best_block_notification_stream");
+		self.subscribers.lock().push(sender);
+		receiver
+	}
+}
@@ -0,0 +1,166 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Helper for handling (i.e. answering) BEEFY justification requests from a remote peer.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use codec::Decode;
+use futures::{
+	channel::{mpsc, oneshot},
+	StreamExt,
+};
+use log::debug;
+
+use sc_client_api::BlockBackend;
+use sc_network::config::{IncomingRequest, OutgoingResponse, RequestResponseConfig};
+use sp_runtime::{generic::BlockId, traits::Block};
+
+use beefy_primitives::BEEFY_ENGINE_ID;
+
+use super::{beefy_justifs_protocol_config, JustificationRequest};
+
+/// Handler for incoming BEEFY justification requests from a remote peer.
+pub struct BeefyJustifsRequestHandler<B, Client> {
+	request_receiver: mpsc::Receiver<IncomingRequest>,
+	client: Arc<Client>,
+	_block: PhantomData<B>,
+}
+
+impl<B, Client> BeefyJustifsRequestHandler<B, Client>
+where
+	B: Block,
+	Client: BlockBackend<B> + Send + Sync + 'static,
+{
+	/// Create a new [`BeefyJustifsRequestHandler`]. The returned configuration must be
+	/// registered with the network for the handler to receive requests.
+	pub fn new(client: Arc<Client>) -> (Self, RequestResponseConfig) {
+		let (tx, request_receiver) = mpsc::channel(20);
+
+		let mut request_response_config = beefy_justifs_protocol_config();
+		request_response_config.inbound_queue = Some(tx);
+
+		(Self { request_receiver, client, _block: PhantomData }, request_response_config)
+	}
+
+	fn handle_request(
+		&self,
+		payload: Vec<u8>,
+		pending_response: oneshot::Sender<OutgoingResponse>,
+	) -> Result<(), HandleRequestError> {
+		let request = JustificationRequest::<B>::decode(&mut &payload[..])?;
+
+		// refuse the request if we don't have the justification so that the
+		// remote peer moves on to the next one.
+		let result = self
+			.client
+			.justifications(&BlockId::Number(request.begin))?
+			.and_then(|justifications| justifications.into_justification(BEEFY_ENGINE_ID))
+			.ok_or(());
+
+		pending_response
+			.send(OutgoingResponse { result, reputation_changes: Vec::new(), sent_feedback: None })
+			.map_err(|_| HandleRequestError::SendResponse)
+	}
+
+	/// Run [`BeefyJustifsRequestHandler`].
+	pub async fn run(mut self) {
+		while let Some(request) = self.request_receiver.next().await {
+			let IncomingRequest { peer, payload, pending_response } = request;
+
+			match self.handle_request(payload, pending_response) {
+				Ok(()) => {
+					debug!(target: "beefy", "🥩 Handled BEEFY justification request from {}.", peer)
+				},
+				Err(e) => debug!(
+					target: "beefy",
+					"🥩 Failed to handle BEEFY justification request from {}: {}",
+					peer, e,
+				),
+			}
+		}
+	}
+}
+
+#[derive(Debug, thiserror::Error)]
+enum HandleRequestError {
+	#[error("Failed to decode request: {0}.")]
+	DecodeScale(#[from] codec::Error),
+	#[error(transparent)]
+	Client(#[from] sp_blockchain::Error),
+	#[error("Failed to send response.")]
+	SendResponse,
+}
+
+#[cfg(test)]
+mod tests {
+	use codec::Encode;
+	use futures::executor::block_on;
+
+	use sc_block_builder::BlockBuilderProvider;
+	use sc_network_test::Block;
+	use sp_consensus::BlockOrigin;
+	use sp_runtime::Justifications;
+	use substrate_test_runtime_client::{
+		ClientBlockImportExt, DefaultTestClientBuilderExt, TestClient, TestClientBuilder,
+		TestClientBuilderExt,
+	};
+
+	use super::*;
+
+	type Answer = (Result<(), HandleRequestError>, Option<Result<Vec<u8>, ()>>);
+
+	fn answer(handler: &BeefyJustifsRequestHandler<Block, TestClient>, payload: Vec<u8>) -> Answer {
+		let (tx, mut rx) = oneshot::channel();
+		let result = handler.handle_request(payload, tx);
+		(result, rx.try_recv().ok().flatten().map(|response| response.result))
+	}
+
+	#[test]
+	fn answers_requests_with_stored_justifications() {
+		let mut client = TestClientBuilder::new().build();
+
+		let block = client.new_block(Default::default()).unwrap().build().unwrap().block;
+		let justifications = Justifications::from((BEEFY_ENGINE_ID, vec![1, 2, 3]));
+		block_on(client.import_justified(BlockOrigin::Own, block, justifications)).unwrap();
+
+		let block = client.new_block(Default::default()).unwrap().build().unwrap().block;
+		block_on(client.import(BlockOrigin::Own, block)).unwrap();
+
+		let (handler, _) = BeefyJustifsRequestHandler::<Block, _>::new(Arc::new(client));
+		let request = |begin| JustificationRequest::<Block> { begin }.encode();
+
+		let (result, response) = answer(&handler, request(1));
+		assert!(result.is_ok());
+		assert_eq!(response, Some(Ok(vec![1, 2, 3])));
+
+		// requests for blocks without a justification, or unknown blocks, are refused
+		let (result, response) = answer(&handler, request(2));
+		assert!(result.is_ok());
+		assert_eq!(response, Some(Err(())));
+
+		let (result, response) = answer(&handler, request(3));
+		assert!(result.is_ok());
+		assert_eq!(response, Some(Err(())));
+
+		// invalid requests are not answered
+		let (result, response) = answer(&handler, vec![]);
+		assert!(matches!(result, Err(HandleRequestError::DecodeScale(_))));
+		assert_eq!(response, None);
+	}
+}
@@ -0,0 +1,258 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Request/response protocol for fetching BEEFY justifications from peers.
+//!
+//! BEEFY votes are only gossiped while a round is live, so a node that was offline while a
+//! round concluded never learns the resulting signed commitment. This protocol lets such a
+//! node request the justification of a given finalized block from the peers that have it.
+
+mod incoming_requests_handler;
+pub(crate) mod outgoing_requests_engine;
+
+pub use incoming_requests_handler::BeefyJustifsRequestHandler;
+
+use std::{
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+	time::Duration,
+};
+
+use codec::{Decode, Encode};
+use futures::{channel::oneshot, stream::FusedStream, Stream, StreamExt};
+
+use sc_network::{
+	config::RequestResponseConfig, ExHashT, IfDisconnected, NetworkService, PeerId, RequestFailure,
+};
+use sc_utils::mpsc::{tracing_unbounded, TracingUnboundedReceiver, TracingUnboundedSender};
+use sp_runtime::{
+	traits::{Block, NumberFor},
+	EncodedJustification,
+};
+
+/// Name of the BEEFY justifications request/response protocol.
+pub const BEEFY_SYNC_PROTOCOL_NAME: &str = "/paritytech/beefy/justifications/1";
+
+// Maximum size of a request, i.e. of an encoded block number.
+const MAX_REQUEST_SIZE: u64 = 32;
+
+// Maximum size of a response, i.e. of an encoded signed commitment.
+const MAX_RESPONSE_SIZE: u64 = 1024 * 1024;
+
+// Peers that don't answer in time are skipped in favor of the next one.
+const JUSTIF_REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Returns the configuration value to put in
+/// [`sc_network::config::NetworkConfiguration::request_response_protocols`], refusing
+/// incoming requests. Use [`BeefyJustifsRequestHandler::new`] to answer them.
+pub fn beefy_justifs_protocol_config() -> RequestResponseConfig {
+	RequestResponseConfig {
+		name: BEEFY_SYNC_PROTOCOL_NAME.into(),
+		max_request_size: MAX_REQUEST_SIZE,
+		max_response_size: MAX_RESPONSE_SIZE,
+		request_timeout: JUSTIF_REQUEST_TIMEOUT,
+		inbound_queue: None,
+		inbound_limits: Default::default(),
+	}
+}
+
+/// BEEFY justification request.
+#[derive(Debug, Encode, Decode)]
+pub struct JustificationRequest<B: Block> {
+	/// The number of the finalized block the justification is requested for.
+	pub begin: NumberFor<B>,
+}
+
+/// Network abstraction used to send BEEFY justification requests to peers.
+pub trait JustificationsNetwork: Send + 'static {
+	/// Send `request` to `target` over the BEEFY justifications protocol. The response is sent
+	/// to `pending_response`.
+	fn start_request(
+		&self,
+		target: PeerId,
+		request: Vec<u8>,
+		pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+	);
+}
+
+impl<B: Block, H: ExHashT> JustificationsNetwork for Arc<NetworkService<B, H>> {
+	fn start_request(
+		&self,
+		target: PeerId,
+		request: Vec<u8>,
+		pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+	) {
+		NetworkService::start_request(
+			self,
+			target,
+			BEEFY_SYNC_PROTOCOL_NAME,
+			request,
+			pending_response,
+			IfDisconnected::ImmediateError,
+		)
+	}
+}
+
+/// A request for the justification of a block, alongside the sender of the answer.
+pub type JustificationRequestMessage<B> =
+	(<B as Block>::Hash, oneshot::Sender<Option<EncodedJustification>>);
+
+/// Handle used to get the BEEFY justification of a finalized block. Justifications missing
+/// from the local storage are requested from peers on-demand.
+#[derive(Clone)]
+pub struct BeefyJustificationRequester<B: Block> {
+	sender: TracingUnboundedSender<JustificationRequestMessage<B>>,
+}
+
+impl<B: Block> BeefyJustificationRequester<B> {
+	/// Creates a new pair of requester and receiving end of justification requests, the
+	/// latter to be passed to the BEEFY gadget.
+	pub fn channel() -> (Self, BeefyJustificationRequests<B>) {
+		let (sender, receiver) = tracing_unbounded("mpsc_beefy_justification_requests");
+		(Self { sender }, BeefyJustificationRequests { receiver })
+	}
+
+	/// Get the SCALE-encoded `VersionedCommitment` justifying the given block, fetching it
+	/// from peers if needed. Resolves to `None` if the block isn't finalized or if none of
+	/// our peers could provide a valid justification, and fails if the BEEFY gadget is not
+	/// running or if too many justifications are being fetched from peers already.
+	pub async fn justification(
+		&self,
+		block: B::Hash,
+	) -> Result<Option<EncodedJustification>, oneshot::Canceled> {
+		let (tx, rx) = oneshot::channel();
+		let _ = self.sender.unbounded_send((block, tx));
+		rx.await
+	}
+}
+
+/// The receiving end of the requests sent by a [`BeefyJustificationRequester`].
+pub struct BeefyJustificationRequests<B: Block> {
+	receiver: TracingUnboundedReceiver<JustificationRequestMessage<B>>,
+}
+
+impl<B: Block> Stream for BeefyJustificationRequests<B> {
+	type Item = JustificationRequestMessage<B>;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+		self.receiver.poll_next_unpin(cx)
+	}
+}
+
+impl<B: Block> FusedStream for BeefyJustificationRequests<B> {
+	fn is_terminated(&self) -> bool {
+		self.receiver.is_terminated()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::task::Poll;
+
+	use futures::{executor::block_on, future, FutureExt};
+
+	use sc_client_api::BlockBackend;
+	use sc_network::ObservedRole;
+	use sc_network_gossip::Validator;
+	use sc_network_test::{
+		Block, FullPeerConfig, PeersClient, RequestResponseProtocolBuilder, TestNet, TestNetFactory,
+	};
+	use sp_runtime::generic::BlockId;
+	use substrate_test_runtime_client::ClientExt;
+
+	use beefy_primitives::{
+		crypto::Public, known_payload_ids, Commitment, Payload, SignedCommitment, ValidatorSet,
+		VersionedCommitment, BEEFY_ENGINE_ID,
+	};
+
+	use super::{outgoing_requests_engine::OnDemandJustificationsEngine, *};
+	use crate::{
+		gossip::{tests::TestContext, GossipValidator},
+		keystore::tests::Keyring,
+		worker::append_justification,
+	};
+
+	fn beefy_justifs_request_handler() -> Vec<RequestResponseProtocolBuilder> {
+		vec![Box::new(|client: PeersClient| {
+			let (handler, protocol_config) =
+				BeefyJustifsRequestHandler::<Block, _>::new(client.as_client());
+			(protocol_config, handler.run().boxed())
+		})]
+	}
+
+	#[test]
+	fn offline_node_fetches_mandatory_block_justification() {
+		sp_tracing::try_init_simple();
+		let mut net = TestNet::new(0);
+		for _ in 0..2 {
+			net.add_full_peer_with_config(FullPeerConfig {
+				request_response_protocols: beefy_justifs_request_handler(),
+				..Default::default()
+			});
+		}
+		net.peer(0).push_blocks(4, false);
+		net.block_until_sync();
+
+		// block 2 enacts a new validator set, and the BEEFY round concluding it happens while
+		// peer 1 is offline.
+		let keys = [Keyring::Alice, Keyring::Bob];
+		let validator_set =
+			ValidatorSet::<Public> { validators: keys.iter().map(|k| k.public()).collect(), id: 1 };
+		let payload = Payload::new(known_payload_ids::MMR_ROOT_ID, vec![42; 32]);
+		let commitment = Commitment { payload, block_number: 2, validator_set_id: 1 };
+		let signatures = keys.iter().map(|k| Some(k.sign(&commitment.encode()))).collect();
+		let justification =
+			VersionedCommitment::V1(SignedCommitment { commitment, signatures }).encode();
+		let client = net.peer(0).client().as_client();
+		client
+			.finalize_block(BlockId::Number(2), Some((BEEFY_ENGINE_ID, justification.clone())))
+			.unwrap();
+		client.finalize_block(BlockId::Number(4), None).unwrap();
+
+		let client = net.peer(1).client().as_client();
+		client.finalize_block(BlockId::Number(4), None).unwrap();
+		assert!(client.justifications(&BlockId::Number(2)).unwrap().is_none());
+
+		// back online, peer 1 fetches the justification from peer 0 and stores it.
+		let gossip_validator = Arc::new(GossipValidator::<Block>::new());
+		let peer_0 = *net.peer(0).network_service().local_peer_id();
+		gossip_validator.new_peer(&mut TestContext, &peer_0, ObservedRole::Full);
+		let mut engine = OnDemandJustificationsEngine::new(
+			net.peer(1).network_service().clone(),
+			gossip_validator,
+		);
+		assert!(engine.request(2, validator_set));
+
+		let (number, signed_commitment) = block_on(future::poll_fn(|cx| {
+			net.poll(cx);
+			match engine.next().boxed().poll_unpin(cx) {
+				Poll::Ready(result) => Poll::Ready(result),
+				Poll::Pending => Poll::Pending,
+			}
+		}));
+		assert_eq!(number, 2);
+
+		let fetched = VersionedCommitment::V1(signed_commitment.unwrap()).encode();
+		let backend = net.peer(1).client().as_backend();
+		append_justification::<Block, _>(&*backend, 2, fetched).unwrap();
+
+		let justifications = client.justifications(&BlockId::Number(2)).unwrap().unwrap();
+		assert_eq!(justifications.get(BEEFY_ENGINE_ID), Some(&justification));
+	}
+}
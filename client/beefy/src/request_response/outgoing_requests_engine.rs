@@ -0,0 +1,297 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Sending BEEFY justification requests to our peers, one peer at a time, until one of
+//! them answers with a valid justification.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use codec::Encode;
+use futures::{channel::oneshot, future};
+use log::debug;
+
+use sc_network::{PeerId, RequestFailure};
+use sp_runtime::traits::{Block, NumberFor};
+
+use beefy_primitives::{crypto::Public, ValidatorSet};
+
+use crate::{
+	gossip::GossipValidator, justification::decode_and_verify_commitment,
+	notification::SignedCommitment,
+};
+
+use super::{JustificationRequest, JustificationsNetwork};
+
+struct InFlightRequest<B: Block> {
+	peer: PeerId,
+	block: NumberFor<B>,
+	validator_set: ValidatorSet<Public>,
+	response: oneshot::Receiver<Result<Vec<u8>, RequestFailure>>,
+}
+
+/// Requests the justification of a single block at a time from our peers.
+pub(crate) struct OnDemandJustificationsEngine<B: Block, N> {
+	network: N,
+	gossip_validator: Arc<GossipValidator<B>>,
+	peers_to_query: VecDeque<PeerId>,
+	in_flight: Option<InFlightRequest<B>>,
+}
+
+impl<B, N> OnDemandJustificationsEngine<B, N>
+where
+	B: Block,
+	N: JustificationsNetwork,
+{
+	pub(crate) fn new(network: N, gossip_validator: Arc<GossipValidator<B>>) -> Self {
+		Self { network, gossip_validator, peers_to_query: VecDeque::new(), in_flight: None }
+	}
+
+	/// Return `true` if there is no request in flight.
+	pub(crate) fn is_idle(&self) -> bool {
+		self.in_flight.is_none()
+	}
+
+	/// Start requesting the justification of `block`, which has to be signed by
+	/// `validator_set`, from our peers. A request in flight for another block is abandoned.
+	///
+	/// Returns `false` if there is no peer to request the justification from.
+	pub(crate) fn request(
+		&mut self,
+		block: NumberFor<B>,
+		validator_set: ValidatorSet<Public>,
+	) -> bool {
+		if let Some(in_flight) = self.in_flight.as_ref() {
+			if in_flight.block == block {
+				return true
+			}
+		}
+
+		self.peers_to_query = self.gossip_validator.peers().into();
+		self.try_next_peer(block, validator_set)
+	}
+
+	fn try_next_peer(&mut self, block: NumberFor<B>, validator_set: ValidatorSet<Public>) -> bool {
+		let peer = match self.peers_to_query.pop_front() {
+			Some(peer) => peer,
+			None => {
+				debug!(target: "beefy", "🥩 No more peers to request justification #{:?} from", block);
+				self.in_flight = None;
+				return false
+			},
+		};
+
+		debug!(target: "beefy", "🥩 Requesting justification #{:?} from {:?}", block, peer);
+
+		let (tx, response) = oneshot::channel();
+		self.network
+			.start_request(peer, JustificationRequest::<B> { begin: block }.encode(), tx);
+		self.in_flight = Some(InFlightRequest { peer, block, validator_set, response });
+
+		true
+	}
+
+	/// Wait for the outcome of the request in flight: either the verified justification, or
+	/// `None` once all peers have been queried unsuccessfully. Never resolves while idle.
+	pub(crate) async fn next(&mut self) -> (NumberFor<B>, Option<SignedCommitment<B>>) {
+		loop {
+			let in_flight = match self.in_flight.as_mut() {
+				Some(in_flight) => in_flight,
+				None => return future::pending().await,
+			};
+
+			let response = (&mut in_flight.response).await;
+			let block = in_flight.block;
+
+			let result = match response {
+				Ok(Ok(encoded)) =>
+					decode_and_verify_commitment::<B>(&encoded, block, &in_flight.validator_set)
+						.map_err(|e| e.to_string()),
+				Ok(Err(e)) => Err(e.to_string()),
+				Err(_) => Err("request canceled".into()),
+			};
+
+			match result {
+				Ok(signed_commitment) => {
+					debug!(target: "beefy", "🥩 Received justification #{:?} from {:?}", block, in_flight.peer);
+					self.in_flight = None;
+					return (block, Some(signed_commitment))
+				},
+				Err(e) => {
+					debug!(target: "beefy", "🥩 Failed to get justification #{:?} from {:?}: {}", block, in_flight.peer, e);
+					let validator_set = in_flight.validator_set.clone();
+					if !self.try_next_peer(block, validator_set) {
+						return (block, None)
+					}
+				},
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::task::Poll;
+
+	use futures::{executor::block_on, poll, FutureExt};
+	use parking_lot::Mutex;
+
+	use sc_network::ObservedRole;
+	use sc_network_gossip::Validator;
+	use sc_network_test::Block;
+
+	use beefy_primitives::{known_payload_ids, Commitment, Payload, VersionedCommitment};
+
+	use super::*;
+	use crate::{gossip::tests::TestContext, keystore::tests::Keyring};
+
+	type Request = (PeerId, Vec<u8>, Option<oneshot::Sender<Result<Vec<u8>, RequestFailure>>>);
+
+	#[derive(Clone, Default)]
+	struct TestNetwork(Arc<Mutex<Vec<Request>>>);
+
+	impl JustificationsNetwork for TestNetwork {
+		fn start_request(
+			&self,
+			target: PeerId,
+			request: Vec<u8>,
+			pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+		) {
+			self.0.lock().push((target, request, Some(pending_response)));
+		}
+	}
+
+	impl TestNetwork {
+		fn targets(&self) -> Vec<PeerId> {
+			self.0.lock().iter().map(|(target, _, _)| *target).collect()
+		}
+
+		fn request(&self, index: usize) -> Vec<u8> {
+			self.0.lock()[index].1.clone()
+		}
+
+		fn respond(&self, index: usize, response: Result<Vec<u8>, RequestFailure>) {
+			let pending_response = self.0.lock()[index].2.take().unwrap();
+			let _ = pending_response.send(response);
+		}
+	}
+
+	fn engine(peers: &[PeerId]) -> (OnDemandJustificationsEngine<Block, TestNetwork>, TestNetwork) {
+		let gossip_validator = Arc::new(GossipValidator::new());
+		for peer in peers {
+			gossip_validator.new_peer(&mut TestContext, peer, ObservedRole::Full);
+		}
+
+		let network = TestNetwork::default();
+		(OnDemandJustificationsEngine::new(network.clone(), gossip_validator), network)
+	}
+
+	fn poll_next(
+		engine: &mut OnDemandJustificationsEngine<Block, TestNetwork>,
+	) -> Poll<(u64, Option<SignedCommitment<Block>>)> {
+		block_on(async { poll!(engine.next().boxed()) })
+	}
+
+	fn validator_set() -> ValidatorSet<Public> {
+		ValidatorSet { validators: vec![Keyring::Alice.public()], id: 0 }
+	}
+
+	fn encoded_justification(block_number: u64) -> Vec<u8> {
+		let payload = Payload::new(known_payload_ids::MMR_ROOT_ID, vec![42; 32]);
+		let commitment = Commitment { payload, block_number, validator_set_id: 0 };
+		let signatures = vec![Some(Keyring::Alice.sign(&commitment.encode()))];
+		VersionedCommitment::V1(beefy_primitives::SignedCommitment { commitment, signatures })
+			.encode()
+	}
+
+	#[test]
+	fn requests_peers_in_turn_until_one_answers() {
+		let mut peers = vec![PeerId::random(), PeerId::random(), PeerId::random()];
+		let (mut engine, network) = engine(&peers);
+
+		assert!(engine.is_idle());
+		assert!(engine.request(1, validator_set()));
+		assert!(!engine.is_idle());
+		assert_eq!(network.request(0), JustificationRequest::<Block> { begin: 1 }.encode());
+		assert!(poll_next(&mut engine).is_pending());
+
+		// a refusal or an invalid justification moves on to the next peer
+		network.respond(0, Err(RequestFailure::Refused));
+		assert!(poll_next(&mut engine).is_pending());
+		network.respond(1, Ok(encoded_justification(2)));
+		assert!(poll_next(&mut engine).is_pending());
+		network.respond(2, Ok(encoded_justification(1)));
+		match poll_next(&mut engine) {
+			Poll::Ready((1, Some(signed_commitment))) =>
+				assert_eq!(signed_commitment.commitment.block_number, 1),
+			_ => panic!("expected the justification of block #1"),
+		}
+		assert!(engine.is_idle());
+
+		// each peer was queried once
+		let mut targets = network.targets();
+		targets.sort();
+		peers.sort();
+		assert_eq!(targets, peers);
+	}
+
+	#[test]
+	fn gives_up_once_all_peers_failed() {
+		let (mut engine, network) = engine(&[PeerId::random(), PeerId::random()]);
+
+		assert!(engine.request(1, ValidatorSet::empty()));
+		network.respond(0, Err(RequestFailure::Refused));
+		assert!(poll_next(&mut engine).is_pending());
+		network.respond(1, Err(RequestFailure::Obsolete));
+		assert!(matches!(poll_next(&mut engine), Poll::Ready((1, None))));
+		assert!(engine.is_idle());
+		assert!(poll_next(&mut engine).is_pending());
+
+		let targets = network.targets();
+		assert_eq!(targets.len(), 2);
+		assert_ne!(targets[0], targets[1]);
+	}
+
+	#[test]
+	fn request_without_peers_fails() {
+		let (mut engine, network) = engine(&[]);
+
+		assert!(!engine.request(1, ValidatorSet::empty()));
+		assert!(engine.is_idle());
+		assert!(network.targets().is_empty());
+	}
+
+	#[test]
+	fn new_request_abandons_the_one_in_flight() {
+		let (mut engine, network) = engine(&[PeerId::random(), PeerId::random()]);
+
+		assert!(engine.request(1, validator_set()));
+		// requesting the same block again keeps the request in flight
+		assert!(engine.request(1, validator_set()));
+		assert_eq!(network.targets().len(), 1);
+
+		assert!(engine.request(2, validator_set()));
+		assert_eq!(network.targets().len(), 2);
+		assert_eq!(network.request(1), JustificationRequest::<Block> { begin: 2 }.encode());
+
+		// the response to the abandoned request is ignored
+		network.respond(0, Ok(encoded_justification(1)));
+		assert!(poll_next(&mut engine).is_pending());
+		network.respond(1, Ok(encoded_justification(2)));
+		assert!(matches!(poll_next(&mut engine), Poll::Ready((2, Some(_)))));
+	}
+}
@@ -48,7 +48,7 @@ impl RoundTracker {
 	}
 }
 
-fn threshold(authorities: usize) -> usize {
+pub(crate) fn threshold(authorities: usize) -> usize {
 	let faulty = authorities.saturating_sub(1) / 3;
 	authorities - faulty
 }
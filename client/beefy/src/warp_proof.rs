@@ -0,0 +1,481 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Generation and verification of BEEFY warp sync proofs.
+//!
+//! A validator set hands over to the next one with the last block it justified. The proof carries
+//! the header at the block number of that commitment, and a read proof of the `NextAuthorities`
+//! storage of the BEEFY pallet against the header's state root yields the next validator set.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use codec::{Decode, Encode};
+
+use sc_client_api::{BlockBackend, ProofProvider};
+use sc_network::warp_request_handler::{
+	EncodedAuthoritySet, EncodedProof, VerificationResult, WarpSyncProvider,
+};
+use sp_blockchain::HeaderBackend;
+use sp_core::hashing::twox_128;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block, HashFor, Header, NumberFor, One},
+};
+use sp_state_machine::StorageProof;
+
+use beefy_primitives::{crypto::Public, ValidatorSet, VersionedCommitment, BEEFY_ENGINE_ID};
+
+use crate::{error::Error, justification::decode_and_verify_commitment};
+
+/// The maximum size in bytes of the [`WarpSyncProof`].
+const MAX_WARP_SYNC_PROOF_SIZE: usize = 8 * 1024 * 1024;
+
+/// Storage key of the next validator set of the BEEFY pallet named `pallet_name` in the runtime.
+pub fn next_authorities_storage_key(pallet_name: &str) -> Vec<u8> {
+	let mut key = twox_128(pallet_name.as_bytes()).to_vec();
+	key.extend_from_slice(&twox_128(b"NextAuthorities"));
+	key
+}
+
+/// A justified block of a validator set.
+#[derive(Decode, Encode, Debug, Clone)]
+pub struct WarpSyncFragment<B: Block> {
+	/// The justified block.
+	pub header: B::Header,
+	/// The SCALE-encoded [`VersionedCommitment`] justifying the block.
+	pub justification: Vec<u8>,
+	/// Proof of the next validator set at the state of the block. Only the target block of a
+	/// finished proof goes without it.
+	pub next_validator_set_proof: Option<StorageProof>,
+}
+
+/// An accumulated proof of multiple validator set changes.
+#[derive(Decode, Encode, Debug)]
+pub struct WarpSyncProof<B: Block> {
+	fragments: Vec<WarpSyncFragment<B>>,
+	is_finished: bool,
+}
+
+impl<B: Block> WarpSyncProof<B> {
+	/// Generate a proof of the validator set changes following block `begin`, ending with the
+	/// latest justified block.
+	fn generate<C>(
+		client: &C,
+		begin: B::Hash,
+		next_authorities_key: &[u8],
+	) -> Result<Self, sp_blockchain::Error>
+	where
+		C: HeaderBackend<B> + BlockBackend<B> + ProofProvider<B>,
+	{
+		let begin_number = client
+			.number(begin)?
+			.ok_or_else(|| sp_blockchain::Error::UnknownBlock(format!("{}", begin)))?;
+		let finalized_number = client.info().finalized_number;
+
+		let mut fragments = Vec::new();
+		let mut proof_size = 0;
+		// The last justified block seen, along with its justification and validator set id.
+		let mut last_justified: Option<(B::Header, Vec<u8>, u64)> = None;
+
+		let mut number = begin_number + One::one();
+		while number <= finalized_number {
+			let hash = client
+				.hash(number)?
+				.ok_or_else(|| sp_blockchain::Error::UnknownBlock(format!("{}", number)))?;
+			number += One::one();
+
+			let justification = match client
+				.justifications(&BlockId::Hash(hash))?
+				.and_then(|j| j.into_justification(BEEFY_ENGINE_ID))
+			{
+				Some(justification) => justification,
+				None => continue,
+			};
+			let VersionedCommitment::V1(signed_commitment) =
+				VersionedCommitment::<NumberFor<B>>::decode(&mut &*justification).map_err(|e| {
+					sp_blockchain::Error::Backend(format!("Invalid BEEFY justification: {}", e))
+				})?;
+			let commitment = signed_commitment.commitment;
+
+			if let Some((header, justification, last_set_id)) = last_justified.take() {
+				if last_set_id != commitment.validator_set_id {
+					// `header` is the last block justified by the previous validator set.
+					let proof = client.read_proof(
+						&BlockId::Hash(header.hash()),
+						&mut std::iter::once(next_authorities_key),
+					)?;
+					let fragment = WarpSyncFragment {
+						header,
+						justification,
+						next_validator_set_proof: Some(proof),
+					};
+
+					proof_size += fragment.encoded_size();
+					if proof_size > MAX_WARP_SYNC_PROOF_SIZE && !fragments.is_empty() {
+						return Ok(WarpSyncProof { fragments, is_finished: false })
+					}
+					fragments.push(fragment);
+				}
+			}
+
+			let header =
+				client.header(BlockId::Number(commitment.block_number))?.ok_or_else(|| {
+					sp_blockchain::Error::UnknownBlock(format!("{}", commitment.block_number))
+				})?;
+			last_justified = Some((header, justification, commitment.validator_set_id));
+		}
+
+		if let Some((header, justification, _)) = last_justified {
+			fragments.push(WarpSyncFragment {
+				header,
+				justification,
+				next_validator_set_proof: None,
+			});
+		}
+
+		Ok(WarpSyncProof { fragments, is_finished: true })
+	}
+
+	/// Verify the proof against `validator_set`, returning the validator set of the last fragment.
+	fn verify(
+		&self,
+		validator_set: ValidatorSet<Public>,
+		next_authorities_key: &[u8],
+	) -> Result<ValidatorSet<Public>, Error> {
+		let mut validator_set = validator_set;
+
+		for (index, fragment) in self.fragments.iter().enumerate() {
+			let header = &fragment.header;
+			decode_and_verify_commitment::<B>(
+				&fragment.justification,
+				*header.number(),
+				&validator_set,
+			)?;
+
+			let is_target = self.is_finished && index + 1 == self.fragments.len();
+			match (&fragment.next_validator_set_proof, is_target) {
+				(Some(proof), false) => {
+					let validators = sp_state_machine::read_proof_check::<HashFor<B>, _>(
+						*header.state_root(),
+						proof.clone(),
+						std::iter::once(next_authorities_key),
+					)
+					.map_err(|e| {
+						Error::Verification(format!("invalid next validator set proof: {}", e))
+					})?
+					.remove(next_authorities_key)
+					.flatten()
+					.and_then(|value| Vec::<Public>::decode(&mut &*value).ok())
+					.ok_or_else(|| {
+						Error::Verification(format!(
+							"missing next validator set at block #{}",
+							header.number(),
+						))
+					})?;
+
+					validator_set = ValidatorSet { validators, id: validator_set.id + 1 };
+				},
+				(None, true) => {},
+				(Some(_), true) | (None, false) =>
+					return Err(Error::Verification(format!(
+						"unexpected next validator set proof presence at block #{}",
+						header.number(),
+					))),
+			}
+		}
+
+		Ok(validator_set)
+	}
+}
+
+/// Implements network API for BEEFY warp sync.
+pub struct NetworkProvider<B, C> {
+	client: Arc<C>,
+	genesis_validator_set: ValidatorSet<Public>,
+	next_authorities_key: Vec<u8>,
+	_phantom: PhantomData<B>,
+}
+
+impl<B: Block, C> NetworkProvider<B, C> {
+	/// Create a new instance for the given client, warp syncing from `genesis_validator_set`.
+	///
+	/// `next_authorities_key` is the storage key of the next validator set, see
+	/// [`next_authorities_storage_key`].
+	pub fn new(
+		client: Arc<C>,
+		genesis_validator_set: ValidatorSet<Public>,
+		next_authorities_key: Vec<u8>,
+	) -> Self {
+		NetworkProvider {
+			client,
+			genesis_validator_set,
+			next_authorities_key,
+			_phantom: PhantomData,
+		}
+	}
+}
+
+impl<B, C> WarpSyncProvider<B> for NetworkProvider<B, C>
+where
+	B: Block,
+	C: HeaderBackend<B> + BlockBackend<B> + ProofProvider<B> + Send + Sync,
+{
+	fn generate(
+		&self,
+		start: B::Hash,
+	) -> Result<EncodedProof, Box<dyn std::error::Error + Send + Sync>> {
+		let proof = WarpSyncProof::<B>::generate(&*self.client, start, &self.next_authorities_key)
+			.map_err(Box::new)?;
+		Ok(EncodedProof(proof.encode()))
+	}
+
+	fn verify(
+		&self,
+		proof: &EncodedProof,
+		authorities: EncodedAuthoritySet,
+	) -> Result<VerificationResult<B>, Box<dyn std::error::Error + Send + Sync>> {
+		let EncodedProof(proof) = proof;
+		let proof = WarpSyncProof::<B>::decode(&mut proof.as_slice())
+			.map_err(|e| format!("Proof decoding error: {:?}", e))?;
+		let EncodedAuthoritySet(authorities) = authorities;
+		let validator_set = ValidatorSet::<Public>::decode(&mut authorities.as_slice())
+			.map_err(|e| format!("Validator set decoding error: {:?}", e))?;
+		let last_header = proof
+			.fragments
+			.last()
+			.map(|fragment| fragment.header.clone())
+			.ok_or_else(|| "Empty proof".to_string())?;
+		let next_validator_set =
+			proof.verify(validator_set, &self.next_authorities_key).map_err(Box::new)?;
+		let next_authorities = EncodedAuthoritySet(next_validator_set.encode());
+		if proof.is_finished {
+			Ok(VerificationResult::<B>::Complete(next_authorities, last_header))
+		} else {
+			Ok(VerificationResult::<B>::Partial(next_authorities, last_header.hash()))
+		}
+	}
+
+	fn current_authorities(&self) -> EncodedAuthoritySet {
+		EncodedAuthoritySet(self.genesis_validator_set.encode())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{sync::Arc, task::Poll};
+
+	use futures::executor::block_on;
+
+	use sc_block_builder::BlockBuilderProvider;
+	use sc_network::config::SyncMode;
+	use sc_network_test::{
+		Block, FullPeerConfig, PeersClient, TestNet, TestNetFactory, WarpSyncProviderBuilder,
+	};
+	use sp_consensus::BlockOrigin;
+	use substrate_test_runtime_client::{
+		runtime::Header, BlockBuilderExt, ClientBlockImportExt, ClientExt,
+		DefaultTestClientBuilderExt, TestClient, TestClientBuilder, TestClientBuilderExt,
+	};
+
+	use beefy_primitives::{known_payload_ids, Commitment, Payload, SignedCommitment};
+
+	use super::{Header as _, *};
+	use crate::keystore::tests::Keyring;
+
+	const PALLET_NAME: &str = "Beefy";
+
+	fn validator_set(keys: &[Keyring], id: u64) -> ValidatorSet<Public> {
+		ValidatorSet { validators: keys.iter().map(|k| k.public()).collect(), id }
+	}
+
+	fn validator_sets() -> Vec<ValidatorSet<Public>> {
+		vec![
+			validator_set(&[Keyring::Alice, Keyring::Bob, Keyring::Charlie], 0),
+			validator_set(&[Keyring::Dave, Keyring::Eve, Keyring::Ferdie], 1),
+			validator_set(&[Keyring::One, Keyring::Two], 2),
+		]
+	}
+
+	fn justification(header: &Header, keys: &[Keyring], validator_set_id: u64) -> Vec<u8> {
+		let payload = Payload::new(known_payload_ids::MMR_ROOT_ID, vec![42; 32]);
+		let commitment = Commitment { payload, block_number: *header.number(), validator_set_id };
+		let signatures = keys.iter().map(|k| Some(k.sign(&commitment.encode()))).collect();
+		VersionedCommitment::V1(SignedCommitment { commitment, signatures }).encode()
+	}
+
+	/// Build 12 blocks, queueing the next validator set in blocks 1 and 5. Validator set 0
+	/// justifies blocks 2 and 4, set 1 blocks 6 and 8, and set 2 blocks 10 and 12.
+	fn build_chain(mut client: Arc<TestClient>) {
+		let key = next_authorities_storage_key(PALLET_NAME);
+		let keys: [&[Keyring]; 3] = [
+			&[Keyring::Alice, Keyring::Bob, Keyring::Charlie],
+			&[Keyring::Dave, Keyring::Eve, Keyring::Ferdie],
+			&[Keyring::One, Keyring::Two],
+		];
+		let validator_sets = validator_sets();
+
+		for number in 1..=12u64 {
+			let mut builder = client.new_block(Default::default()).unwrap();
+			match number {
+				1 => builder
+					.push_storage_change(key.clone(), Some(validator_sets[1].validators.encode()))
+					.unwrap(),
+				5 => builder
+					.push_storage_change(key.clone(), Some(validator_sets[2].validators.encode()))
+					.unwrap(),
+				_ => {},
+			}
+			let block = builder.build().unwrap().block;
+			let header = block.header.clone();
+			block_on(client.import(BlockOrigin::Own, block)).unwrap();
+
+			if number % 2 == 0 {
+				let set_id = (number - 1) / 4;
+				let justification = justification(&header, keys[set_id as usize], set_id);
+				client
+					.finalize_block(
+						BlockId::Hash(header.hash()),
+						Some((BEEFY_ENGINE_ID, justification)),
+					)
+					.unwrap();
+			}
+		}
+	}
+
+	fn new_client() -> Arc<TestClient> {
+		let client = Arc::new(TestClientBuilder::new().build());
+		build_chain(client.clone());
+		client
+	}
+
+	#[test]
+	fn warp_proof_follows_validator_set_changes() {
+		let client = new_client();
+		let key = next_authorities_storage_key(PALLET_NAME);
+		let genesis_hash = client.info().genesis_hash;
+
+		let proof = WarpSyncProof::<Block>::generate(&*client, genesis_hash, &key).unwrap();
+		assert!(proof.is_finished);
+		let numbers: Vec<_> = proof.fragments.iter().map(|f| *f.header.number()).collect();
+		assert_eq!(numbers, vec![4, 8, 12]);
+
+		assert_eq!(
+			proof.verify(validator_sets()[0].clone(), &key),
+			Ok(validator_sets()[2].clone())
+		);
+
+		// Nothing was justified after the target.
+		let target = proof.fragments[2].header.hash();
+		let proof = WarpSyncProof::<Block>::generate(&*client, target, &key).unwrap();
+		assert!(proof.fragments.is_empty());
+	}
+
+	#[test]
+	fn warp_proof_resumes_from_the_last_fragment() {
+		let client = new_client();
+		let key = next_authorities_storage_key(PALLET_NAME);
+		let provider = NetworkProvider::new(client.clone(), validator_sets()[0].clone(), key);
+
+		let begin = client.hash(4).unwrap().unwrap();
+		let proof = provider.generate(begin).unwrap();
+		match provider
+			.verify(&proof, EncodedAuthoritySet(validator_sets()[1].encode()))
+			.unwrap()
+		{
+			VerificationResult::Complete(authorities, header) => {
+				assert_eq!(authorities, EncodedAuthoritySet(validator_sets()[2].encode()));
+				assert_eq!(*header.number(), 12);
+			},
+			VerificationResult::Partial(..) => panic!("expected a complete proof"),
+		}
+
+		// The proof is signed by validator set 1, not by the genesis one.
+		assert!(provider.verify(&proof, provider.current_authorities()).is_err());
+	}
+
+	#[test]
+	fn warp_proof_rejects_forged_headers() {
+		let client = new_client();
+		let key = next_authorities_storage_key(PALLET_NAME);
+		let genesis_hash = client.info().genesis_hash;
+		let validator_set = validator_sets()[0].clone();
+
+		// The next validator set proof does not match a header with another state root.
+		let mut proof = WarpSyncProof::<Block>::generate(&*client, genesis_hash, &key).unwrap();
+		proof.fragments[0].header.state_root = Default::default();
+		assert!(matches!(proof.verify(validator_set.clone(), &key), Err(Error::Verification(_))));
+
+		// The next validator set must be proven at the state of the header.
+		let mut proof = WarpSyncProof::<Block>::generate(&*client, genesis_hash, &key).unwrap();
+		let other_proof = proof.fragments[1].next_validator_set_proof.clone();
+		proof.fragments[0].next_validator_set_proof = other_proof;
+		assert!(matches!(proof.verify(validator_set.clone(), &key), Err(Error::Verification(_))));
+
+		// Each validator set must hand over to the next one.
+		let mut proof = WarpSyncProof::<Block>::generate(&*client, genesis_hash, &key).unwrap();
+		proof.fragments[0].next_validator_set_proof = None;
+		assert!(matches!(proof.verify(validator_set, &key), Err(Error::Verification(_))));
+	}
+
+	fn beefy_warp_sync_provider() -> Option<WarpSyncProviderBuilder> {
+		Some(Box::new(|client: PeersClient| {
+			Arc::new(NetworkProvider::new(
+				client.as_client(),
+				validator_sets()[0].clone(),
+				next_authorities_storage_key(PALLET_NAME),
+			))
+		}))
+	}
+
+	#[test]
+	fn warp_sync_with_beefy_proofs() {
+		sp_tracing::try_init_simple();
+		let mut net = TestNet::new(0);
+		// Create 3 synced peers and 1 peer trying to warp sync.
+		for _ in 0..3 {
+			net.add_full_peer_with_config(FullPeerConfig {
+				warp_sync_provider: beefy_warp_sync_provider(),
+				..Default::default()
+			});
+		}
+		net.add_full_peer_with_config(FullPeerConfig {
+			sync_mode: SyncMode::Warp,
+			warp_sync_provider: beefy_warp_sync_provider(),
+			..Default::default()
+		});
+		for peer in 0..3 {
+			build_chain(net.peer(peer).client().as_client());
+		}
+		let gap_end = net.peer(0).client().header(&BlockId::Number(11)).unwrap().unwrap().hash();
+
+		// Wait for peer 3 to sync the state of the last justified block.
+		net.block_until_sync();
+		assert!(!net.peer(3).client().has_state_at(&BlockId::Number(1)));
+		assert!(net.peer(3).client().has_state_at(&BlockId::Number(12)));
+
+		// Wait for peer 3 to download the block history.
+		block_on(futures::future::poll_fn::<(), _>(|cx| {
+			net.poll(cx);
+			if net.peer(3).has_block(&gap_end) {
+				Poll::Ready(())
+			} else {
+				Poll::Pending
+			}
+		}));
+	}
+}
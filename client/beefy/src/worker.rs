@@ -16,10 +16,15 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use std::{collections::BTreeSet, fmt::Debug, marker::PhantomData, sync::Arc};
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::Debug,
+	marker::PhantomData,
+	sync::Arc,
+};
 
 use codec::{Codec, Decode, Encode};
-use futures::{future, FutureExt, StreamExt};
+use futures::{channel::oneshot, future, FutureExt, StreamExt};
 use log::{debug, error, info, trace, warn};
 use parking_lot::Mutex;
 
@@ -28,10 +33,11 @@ use sc_network_gossip::GossipEngine;
 
 use sp_api::BlockId;
 use sp_arithmetic::traits::AtLeast32Bit;
+use sp_blockchain::Backend as BlockchainBackend;
 use sp_runtime::{
 	generic::OpaqueDigestItemId,
 	traits::{Block, Header, NumberFor},
-	SaturatedConversion,
+	EncodedJustification, SaturatedConversion,
 };
 
 use beefy_primitives::{
@@ -46,10 +52,14 @@ use crate::{
 	keystore::BeefyKeystore,
 	metric_inc, metric_set,
 	metrics::Metrics,
-	notification, round, Client,
+	notification, request_response,
+	request_response::{
+		outgoing_requests_engine::OnDemandJustificationsEngine, JustificationsNetwork,
+	},
+	round, Client,
 };
 
-pub(crate) struct WorkerParams<B, BE, C>
+pub(crate) struct WorkerParams<B, BE, C, N>
 where
 	B: Block,
 {
@@ -57,14 +67,88 @@ where
 	pub backend: Arc<BE>,
 	pub key_store: BeefyKeystore,
 	pub signed_commitment_sender: notification::BeefySignedCommitmentSender<B>,
+	pub best_block_sender: notification::BeefyBestBlockSender<B>,
 	pub gossip_engine: GossipEngine<B>,
 	pub gossip_validator: Arc<GossipValidator<B>>,
+	pub on_demand_justifications: OnDemandJustificationsEngine<B, N>,
+	pub justification_requests: request_response::BeefyJustificationRequests<B>,
 	pub min_block_delta: u32,
 	pub metrics: Option<Metrics>,
 }
 
+/// Maximum number of blocks waiting for a justification to be fetched from our peers.
+const MAX_PENDING_JUSTIFICATION_REQUESTS: usize = 64;
+
+/// Requesters waiting for the justification of a block.
+type JustificationResponseSenders = Vec<oneshot::Sender<Option<EncodedJustification>>>;
+
+/// Blocks waiting for a justification to be fetched from our peers, lowest first, along with
+/// the validator set expected to have signed it and the requesters to notify.
+pub(crate) struct PendingJustificationRequests<B: Block> {
+	requests: BTreeMap<NumberFor<B>, (ValidatorSet<Public>, JustificationResponseSenders)>,
+}
+
+impl<B: Block> PendingJustificationRequests<B> {
+	pub(crate) fn new() -> Self {
+		Self { requests: BTreeMap::new() }
+	}
+
+	/// Queue a request for the justification of block `number`. Requests for a new block are
+	/// refused, dropping `response_sender`, once `MAX_PENDING_JUSTIFICATION_REQUESTS` blocks
+	/// are pending.
+	pub(crate) fn push(
+		&mut self,
+		number: NumberFor<B>,
+		validator_set: ValidatorSet<Public>,
+		response_sender: oneshot::Sender<Option<EncodedJustification>>,
+	) -> bool {
+		if !self.requests.contains_key(&number) &&
+			self.requests.len() >= MAX_PENDING_JUSTIFICATION_REQUESTS
+		{
+			// make room by forgetting the blocks nobody waits for anymore.
+			self.requests.retain(|_, (_, senders)| {
+				senders.retain(|sender| !sender.is_canceled());
+				!senders.is_empty()
+			});
+
+			if self.requests.len() >= MAX_PENDING_JUSTIFICATION_REQUESTS {
+				return false
+			}
+		}
+
+		self.requests
+			.entry(number)
+			.or_insert_with(|| (validator_set, Vec::new()))
+			.1
+			.push(response_sender);
+		true
+	}
+
+	/// Return the lowest block waiting for its justification and the validator set expected
+	/// to have signed it.
+	pub(crate) fn next(&self) -> Option<(NumberFor<B>, &ValidatorSet<Public>)> {
+		self.requests
+			.iter()
+			.next()
+			.map(|(number, (validator_set, _))| (*number, validator_set))
+	}
+
+	/// Answer the requests for the justification of block `number`.
+	pub(crate) fn answer(
+		&mut self,
+		number: NumberFor<B>,
+		justification: Option<EncodedJustification>,
+	) {
+		if let Some((_, response_senders)) = self.requests.remove(&number) {
+			for response_sender in response_senders {
+				let _ = response_sender.send(justification.clone());
+			}
+		}
+	}
+}
+
 /// A BEEFY worker plays the BEEFY protocol
-pub(crate) struct BeefyWorker<B, C, BE>
+pub(crate) struct BeefyWorker<B, C, BE, N>
 where
 	B: Block,
 	BE: Backend<B>,
@@ -74,8 +158,12 @@ where
 	backend: Arc<BE>,
 	key_store: BeefyKeystore,
 	signed_commitment_sender: notification::BeefySignedCommitmentSender<B>,
+	best_block_sender: notification::BeefyBestBlockSender<B>,
 	gossip_engine: Arc<Mutex<GossipEngine<B>>>,
 	gossip_validator: Arc<GossipValidator<B>>,
+	on_demand_justifications: OnDemandJustificationsEngine<B, N>,
+	justification_requests: request_response::BeefyJustificationRequests<B>,
+	pending_justification_requests: PendingJustificationRequests<B>,
 	/// Min delta in block numbers between two blocks, BEEFY should vote on
 	min_block_delta: u32,
 	metrics: Option<Metrics>,
@@ -91,12 +179,13 @@ where
 	_backend: PhantomData<BE>,
 }
 
-impl<B, C, BE> BeefyWorker<B, C, BE>
+impl<B, C, BE, N> BeefyWorker<B, C, BE, N>
 where
 	B: Block + Codec,
 	BE: Backend<B>,
 	C: Client<B, BE>,
 	C::Api: BeefyApi<B>,
+	N: JustificationsNetwork,
 {
 	/// Return a new BEEFY worker instance.
 	///
@@ -104,14 +193,17 @@ where
 	/// BEEFY pallet has been deployed on-chain.
 	///
 	/// The BEEFY pallet is needed in order to keep track of the BEEFY authority set.
-	pub(crate) fn new(worker_params: WorkerParams<B, BE, C>) -> Self {
+	pub(crate) fn new(worker_params: WorkerParams<B, BE, C, N>) -> Self {
 		let WorkerParams {
 			client,
 			backend,
 			key_store,
 			signed_commitment_sender,
+			best_block_sender,
 			gossip_engine,
 			gossip_validator,
+			on_demand_justifications,
+			justification_requests,
 			min_block_delta,
 			metrics,
 		} = worker_params;
@@ -121,8 +213,12 @@ where
 			backend,
 			key_store,
 			signed_commitment_sender,
+			best_block_sender,
 			gossip_engine: Arc::new(Mutex::new(gossip_engine)),
 			gossip_validator,
+			on_demand_justifications,
+			justification_requests,
+			pending_justification_requests: PendingJustificationRequests::new(),
 			min_block_delta,
 			metrics,
 			rounds: round::Rounds::new(ValidatorSet::empty()),
@@ -135,12 +231,13 @@ where
 	}
 }
 
-impl<B, C, BE> BeefyWorker<B, C, BE>
+impl<B, C, BE, N> BeefyWorker<B, C, BE, N>
 where
 	B: Block,
 	BE: Backend<B>,
 	C: Client<B, BE>,
 	C::Api: BeefyApi<B>,
+	N: JustificationsNetwork,
 {
 	/// Return `true`, if we should vote on block `number`
 	fn should_vote_on(&self, number: NumberFor<B>) -> bool {
@@ -341,13 +438,115 @@ where
 				}
 
 				self.signed_commitment_sender.notify(signed_commitment);
-				self.best_beefy_block = Some(round.1);
+				self.set_best_beefy_block(round.1);
+			}
+		}
+	}
+
+	/// Update the best block finalized by BEEFY and notify the subscribers.
+	fn set_best_beefy_block(&mut self, number: NumberFor<B>) {
+		self.best_beefy_block = Some(number);
+
+		if let Ok(Some(hash)) = self.client.hash(number) {
+			self.best_block_sender.notify(hash);
+		}
+
+		metric_set!(self, beefy_best_block, number);
+	}
+
+	/// Answer a request for the justification of block `hash` from the local storage, or
+	/// queue it to be fetched from our peers.
+	fn handle_justification_request(
+		&mut self,
+		hash: B::Hash,
+		response_sender: oneshot::Sender<Option<EncodedJustification>>,
+	) {
+		let header = match self.client.header(BlockId::hash(hash)) {
+			Ok(Some(header)) => header,
+			_ => {
+				let _ = response_sender.send(None);
+				return
+			},
+		};
+		let number = *header.number();
+
+		// only blocks finalized by GRANDPA can have a BEEFY justification
+		let finalized = number <= self.best_grandpa_block &&
+			self.client.hash(number).ok().flatten() == Some(hash);
+		if !finalized {
+			let _ = response_sender.send(None);
+			return
+		}
+
+		let justification = self
+			.backend
+			.blockchain()
+			.justifications(BlockId::hash(hash))
+			.ok()
+			.flatten()
+			.and_then(|justifications| justifications.into_justification(BEEFY_ENGINE_ID));
+		if let Some(justification) = justification {
+			let _ = response_sender.send(Some(justification));
+			return
+		}
+
+		let validator_set = match self.validator_set(&header) {
+			Some(validator_set) => validator_set,
+			None => {
+				let _ = response_sender.send(None);
+				return
+			},
+		};
 
-				metric_set!(self, beefy_best_block, round.1);
+		if !self.pending_justification_requests.push(number, validator_set, response_sender) {
+			debug!(target: "beefy", "🥩 Too many pending justification requests, refused #{}", number);
+			return
+		}
+
+		self.request_next_justification();
+	}
+
+	/// Start fetching the lowest pending justification, unless a request is in flight.
+	/// Requests are answered right away while there is no peer to fetch them from.
+	fn request_next_justification(&mut self) {
+		while self.on_demand_justifications.is_idle() {
+			let (number, validator_set) = match self.pending_justification_requests.next() {
+				Some((number, validator_set)) => (number, validator_set.clone()),
+				None => return,
+			};
+
+			if !self.on_demand_justifications.request(number, validator_set) {
+				self.pending_justification_requests.answer(number, None);
 			}
 		}
 	}
 
+	/// Import a justification fetched from our peers and answer the pending requests for it.
+	fn handle_on_demand_justification(
+		&mut self,
+		number: NumberFor<B>,
+		signed_commitment: Option<SignedCommitment<NumberFor<B>>>,
+	) {
+		let justification = signed_commitment.map(|signed_commitment| {
+			info!(target: "beefy", "🥩 Imported justification for block #{} from peers.", number);
+
+			let justification = VersionedCommitment::V1(signed_commitment).encode();
+
+			if let Err(e) = append_justification(&*self.backend, number, justification.clone()) {
+				debug!(target: "beefy", "🥩 Failed to append justification #{}: {:?}", number, e);
+			}
+
+			if self.best_beefy_block.map(|best| number > best).unwrap_or(true) {
+				self.set_best_beefy_block(number);
+			}
+
+			justification
+		});
+
+		self.pending_justification_requests.answer(number, justification);
+		self.request_next_justification();
+	}
+
 	pub(crate) async fn run(mut self) {
 		let mut votes = Box::pin(self.gossip_engine.lock().messages_for(topic::<B>()).filter_map(
 			|notification| async move {
@@ -382,6 +581,14 @@ where
 						return;
 					}
 				},
+				justification = self.on_demand_justifications.next().fuse() => {
+					let (number, signed_commitment) = justification;
+					self.handle_on_demand_justification(number, signed_commitment);
+				},
+				request = self.justification_requests.select_next_some() => {
+					let (hash, response_sender) = request;
+					self.handle_justification_request(hash, response_sender);
+				},
 				_ = gossip_engine.fuse() => {
 					error!(target: "beefy", "🥩 Gossip engine has terminated.");
 					return;
@@ -391,6 +598,19 @@ where
 	}
 }
 
+/// Store the BEEFY `justification` of the finalized block `number`.
+pub(crate) fn append_justification<B, BE>(
+	backend: &BE,
+	number: NumberFor<B>,
+	justification: EncodedJustification,
+) -> Result<(), sp_blockchain::Error>
+where
+	B: Block,
+	BE: Backend<B>,
+{
+	backend.append_justification(BlockId::Number(number), (BEEFY_ENGINE_ID, justification))
+}
+
 /// Extract the MMR root hash from a digest in the given header, if it exists.
 fn find_mmr_root_digest<B, Id>(header: &B::Header) -> Option<MmrRootHash>
 where
@@ -443,7 +663,91 @@ where
 
 #[cfg(test)]
 mod tests {
-	use super::vote_target;
+	use futures::executor::block_on;
+
+	use sc_block_builder::BlockBuilderProvider;
+	use sc_client_api::BlockBackend;
+	use sc_network_test::Block;
+	use sp_consensus::BlockOrigin;
+	use substrate_test_runtime_client::{
+		ClientBlockImportExt, ClientExt, DefaultTestClientBuilderExt, TestClientBuilder,
+		TestClientBuilderExt,
+	};
+
+	use super::*;
+
+	#[test]
+	fn pending_justification_requests_are_answered_lowest_first() {
+		let mut pending = PendingJustificationRequests::<Block>::new();
+		assert!(pending.next().is_none());
+
+		let (tx_5, mut rx_5) = oneshot::channel();
+		let (tx_3, mut rx_3) = oneshot::channel();
+		let (tx_3_again, mut rx_3_again) = oneshot::channel();
+		assert!(pending.push(5, ValidatorSet::empty(), tx_5));
+		assert!(pending.push(3, ValidatorSet::empty(), tx_3));
+		assert!(pending.push(3, ValidatorSet::empty(), tx_3_again));
+		assert_eq!(pending.next().map(|(number, _)| number), Some(3));
+
+		// all the requesters of a block are answered at once
+		pending.answer(3, Some(vec![1, 2, 3]));
+		assert_eq!(rx_3.try_recv(), Ok(Some(Some(vec![1, 2, 3]))));
+		assert_eq!(rx_3_again.try_recv(), Ok(Some(Some(vec![1, 2, 3]))));
+		assert_eq!(pending.next().map(|(number, _)| number), Some(5));
+
+		pending.answer(5, None);
+		assert_eq!(rx_5.try_recv(), Ok(Some(None)));
+		assert!(pending.next().is_none());
+	}
+
+	#[test]
+	fn pending_justification_requests_are_capped() {
+		let mut pending = PendingJustificationRequests::<Block>::new();
+
+		let mut receivers = (0..MAX_PENDING_JUSTIFICATION_REQUESTS as u64)
+			.map(|number| {
+				let (tx, rx) = oneshot::channel();
+				assert!(pending.push(number, ValidatorSet::empty(), tx));
+				rx
+			})
+			.collect::<Vec<_>>();
+
+		// new blocks are refused, pending ones are still accepted
+		let (tx, mut rx) = oneshot::channel();
+		assert!(!pending.push(100, ValidatorSet::empty(), tx));
+		assert!(rx.try_recv().is_err());
+		let (tx, _rx) = oneshot::channel();
+		assert!(pending.push(0, ValidatorSet::empty(), tx));
+
+		// blocks nobody waits for anymore make room for new ones
+		drop(receivers.remove(1));
+		let (tx, _rx) = oneshot::channel();
+		assert!(pending.push(100, ValidatorSet::empty(), tx));
+		assert_eq!(pending.requests.len(), MAX_PENDING_JUSTIFICATION_REQUESTS);
+		assert!(!pending.requests.contains_key(&1));
+	}
+
+	#[test]
+	fn justifications_are_appended_to_finalized_blocks_only() {
+		let builder = TestClientBuilder::new();
+		let backend = builder.backend();
+		let mut client = builder.build();
+
+		for _ in 0..2 {
+			let block = client.new_block(Default::default()).unwrap().build().unwrap().block;
+			block_on(client.import(BlockOrigin::Own, block)).unwrap();
+		}
+		client.finalize_block(BlockId::Number(1), None).unwrap();
+
+		append_justification(&*backend, 1, vec![1, 2, 3]).unwrap();
+		let justifications = client.justifications(&BlockId::Number(1)).unwrap().unwrap();
+		assert_eq!(justifications.get(BEEFY_ENGINE_ID), Some(&vec![1, 2, 3]));
+
+		assert!(matches!(
+			append_justification(&*backend, 2, vec![1, 2, 3]),
+			Err(sp_blockchain::Error::NotInFinalizedChain),
+		));
+	}
 
 	#[test]
 	fn vote_on_min_block_delta() {
@@ -36,6 +36,7 @@ use sp_core::ExecutionContext;
 use sp_runtime::{
 	generic::BlockId,
 	traits::{Block as BlockT, Hash, HashFor, Header as HeaderT, NumberFor, One},
+	transaction_validity::InvalidTransaction,
 	Digest,
 };
 
@@ -140,6 +141,8 @@ pub struct BlockBuilder<'a, Block: BlockT, A: ProvideRuntimeApi<Block>, B> {
 	backend: &'a B,
 	/// The estimated size of the block header.
 	estimated_header_size: usize,
+	/// The maximum estimated size of the compact storage proof.
+	proof_size_limit: Option<usize>,
 }
 
 impl<'a, Block, A, B> BlockBuilder<'a, Block, A, B>
@@ -190,15 +193,25 @@ where
 			block_id,
 			backend,
 			estimated_header_size,
+			proof_size_limit: None,
 		})
 	}
 
+	/// Set the maximum estimated size of the compact storage proof.
+	///
+	/// Extrinsics that would make the proof exceed the limit are rolled back by [`Self::push`].
+	/// This has no effect when proof recording is disabled.
+	pub fn set_proof_size_limit(&mut self, limit: Option<usize>) {
+		self.proof_size_limit = limit;
+	}
+
 	/// Push onto the block's list of extrinsics.
 	///
 	/// This will ensure the extrinsic can be validly executed (by executing it).
 	pub fn push(&mut self, xt: <Block as BlockT>::Extrinsic) -> Result<(), Error> {
 		let block_id = &self.block_id;
 		let extrinsics = &mut self.extrinsics;
+		let proof_size_limit = self.proof_size_limit;
 
 		self.api.execute_in_transaction(|api| {
 			match api.apply_extrinsic_with_context(
@@ -206,6 +219,11 @@ where
 				ExecutionContext::BlockConstruction,
 				xt.clone(),
 			) {
+				Ok(Ok(_)) if exceeds_proof_size_limit(api, proof_size_limit) =>
+					TransactionOutcome::Rollback(Err(ApplyExtrinsicFailed::Validity(
+						InvalidTransaction::ExhaustsResources.into(),
+					)
+					.into())),
 				Ok(Ok(_)) => {
 					extrinsics.push(xt);
 					TransactionOutcome::Commit(Ok(()))
@@ -286,6 +304,24 @@ where
 			size
 		}
 	}
+
+	/// Estimate the size of the storage proof in compact form in the current state.
+	///
+	/// Returns `None` if proof recording is disabled.
+	pub fn estimate_proof_size(&self) -> Option<usize> {
+		self.api.proof_recorder().map(|pr| pr.estimate_compact_encoded_size())
+	}
+}
+
+/// Returns `true` if the compact proof recorded by `api` exceeds `limit`.
+fn exceeds_proof_size_limit<Block: BlockT, Api: ApiExt<Block>>(
+	api: &Api,
+	limit: Option<usize>,
+) -> bool {
+	match (limit, api.proof_recorder()) {
+		(Some(limit), Some(recorder)) => recorder.estimate_compact_encoded_size() > limit,
+		_ => false,
+	}
 }
 
 #[cfg(test)]
@@ -294,7 +330,9 @@ mod tests {
 	use sp_blockchain::HeaderBackend;
 	use sp_core::Blake2Hasher;
 	use sp_state_machine::Backend;
-	use substrate_test_runtime_client::{DefaultTestClientBuilderExt, TestClientBuilderExt};
+	use substrate_test_runtime_client::{
+		runtime::Transfer, AccountKeyring, DefaultTestClientBuilderExt, TestClientBuilderExt,
+	};
 
 	#[test]
 	fn block_building_storage_proof_does_not_include_runtime_by_default() {
@@ -327,4 +365,40 @@ mod tests {
 			.unwrap_err()
 			.contains("Database missing expected key"),);
 	}
+
+	#[test]
+	fn extrinsic_exceeding_proof_size_limit_is_rolled_back() {
+		let builder = substrate_test_runtime_client::TestClientBuilder::new();
+		let backend = builder.backend();
+		let client = builder.build();
+		let transfer = |from: AccountKeyring| {
+			Transfer { amount: 1, nonce: 0, from: from.into(), to: AccountKeyring::Ferdie.into() }
+				.into_signed_tx()
+		};
+
+		let mut block_builder = BlockBuilder::new(
+			&client,
+			client.info().best_hash,
+			client.info().best_number,
+			RecordProof::Yes,
+			Default::default(),
+			&*backend,
+		)
+		.unwrap();
+
+		block_builder.push(transfer(AccountKeyring::Alice)).unwrap();
+		let proof_size = block_builder.estimate_proof_size().unwrap();
+
+		block_builder.set_proof_size_limit(Some(proof_size));
+		let err = block_builder.push(transfer(AccountKeyring::Bob)).unwrap_err();
+		assert!(
+			matches!(err, Error::ApplyExtrinsicFailed(ApplyExtrinsicFailed::Validity(e)) if e.exhausted_resources())
+		);
+		assert_eq!(block_builder.estimate_proof_size(), Some(proof_size));
+
+		block_builder.set_proof_size_limit(None);
+		block_builder.push(transfer(AccountKeyring::Bob)).unwrap();
+		assert!(block_builder.estimate_proof_size().unwrap() > proof_size);
+		assert_eq!(block_builder.build().unwrap().block.extrinsics().len(), 2);
+	}
 }
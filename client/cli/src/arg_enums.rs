@@ -249,6 +249,8 @@ arg_enum! {
 		FastUnsafe,
 		// Prove finality and download the latest state.
 		Warp,
+		// Full sync. Download headers first, then block bodies from many peers in parallel.
+		HeadersFirst,
 	}
 }
 
@@ -261,6 +263,7 @@ impl Into<sc_network::config::SyncMode> for SyncMode {
 			SyncMode::FastUnsafe =>
 				sc_network::config::SyncMode::Fast { skip_proofs: true, storage_chain_mode: false },
 			SyncMode::Warp => sc_network::config::SyncMode::Warp,
+			SyncMode::HeadersFirst => sc_network::config::SyncMode::HeadersFirst,
 		}
 	}
 }
@@ -0,0 +1,68 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+	error,
+	params::{DatabaseParams, PruningParams, SharedParams},
+	CliConfiguration,
+};
+use sc_service::{chain_ops::check_db, TFullBackend};
+use sp_runtime::traits::Block as BlockT;
+use std::sync::Arc;
+use structopt::StructOpt;
+
+/// The `check-db` command used to verify the consistency of the database.
+#[derive(Debug, StructOpt)]
+pub struct CheckDbCmd {
+	/// Rebuild the leaves and children indexes if they are inconsistent.
+	#[structopt(long)]
+	pub repair: bool,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+impl CheckDbCmd {
+	/// Run the check-db command
+	pub async fn run<B: BlockT>(&self, backend: Arc<TFullBackend<B>>) -> error::Result<()> {
+		check_db(backend, self.repair).map_err(Into::into)
+	}
+}
+
+impl CliConfiguration for CheckDbCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		Some(&self.pruning_params)
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		Some(&self.database_params)
+	}
+}
@@ -0,0 +1,86 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{arg_enums::Database, error, params::SharedParams, CliConfiguration};
+use log::info;
+use sc_service::{chain_ops::convert_db, DatabaseSource};
+use sp_runtime::traits::Block as BlockT;
+use structopt::StructOpt;
+
+/// The `convert-db` command used to copy the database to another backend.
+#[derive(Debug, StructOpt)]
+pub struct ConvertDbCmd {
+	/// Database backend to convert the existing database to.
+	///
+	/// The new database is created next to the existing one, which is left untouched. Only the
+	/// columns of a ParityDB database that can be iterated are copied to RocksDB, the conversion
+	/// fails if any column only stores the hashes of its keys.
+	#[structopt(
+		long,
+		value_name = "DB",
+		case_insensitive = true,
+		possible_values = &["rocksdb", "paritydb-experimental"],
+	)]
+	pub to: Database,
+
+	/// Limit the memory the RocksDB cache can use.
+	#[structopt(long = "db-cache", value_name = "MiB")]
+	pub database_cache_size: Option<usize>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+impl ConvertDbCmd {
+	/// Run the convert-db command
+	pub fn run<B: BlockT>(&self, database_config: DatabaseSource) -> error::Result<()> {
+		let (paritydb_path, rocksdb_path, cache_size) = match database_config {
+			DatabaseSource::Auto { paritydb_path, rocksdb_path, cache_size } =>
+				(paritydb_path, rocksdb_path, cache_size),
+			_ => return Err(error::Error::Input("Cannot convert custom database".into())),
+		};
+		let rocksdb = DatabaseSource::RocksDb { path: rocksdb_path, cache_size };
+		let paritydb = DatabaseSource::ParityDb { path: paritydb_path };
+		let (source, target, name) = match self.to {
+			Database::ParityDb => (rocksdb, paritydb, "paritydb-experimental"),
+			Database::RocksDb => (paritydb, rocksdb, "rocksdb"),
+			Database::Auto =>
+				return Err(error::Error::Input("Target database backend must be given".into())),
+		};
+
+		convert_db::<B>(&source, &target)?;
+		info!("Start the node with `--database {}` to use the converted database", name);
+		Ok(())
+	}
+}
+
+impl CliConfiguration for ConvertDbCmd {
+	fn shared_params(&self) -> &SharedParams {
+		&self.shared_params
+	}
+
+	fn database_cache_size(&self) -> error::Result<Option<usize>> {
+		Ok(self.database_cache_size)
+	}
+
+	fn database(&self) -> error::Result<Option<Database>> {
+		// Both paths are needed, the backend to convert to is given by `--to`.
+		Ok(Some(Database::Auto))
+	}
+}
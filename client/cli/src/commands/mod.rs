@@ -17,6 +17,8 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 mod build_spec_cmd;
 mod check_block_cmd;
+mod check_db_cmd;
+mod convert_db_cmd;
 mod export_blocks_cmd;
 mod export_state_cmd;
 mod generate;
@@ -30,15 +32,30 @@ mod purge_chain_cmd;
 mod revert_cmd;
 mod run_cmd;
 mod sign;
+mod snapshot_cmd;
 pub mod utils;
 mod vanity;
 mod verify;
 
 pub use self::{
-	build_spec_cmd::BuildSpecCmd, check_block_cmd::CheckBlockCmd,
-	export_blocks_cmd::ExportBlocksCmd, export_state_cmd::ExportStateCmd, generate::GenerateCmd,
-	generate_node_key::GenerateNodeKeyCmd, import_blocks_cmd::ImportBlocksCmd,
-	insert_key::InsertKeyCmd, inspect_key::InspectKeyCmd, inspect_node_key::InspectNodeKeyCmd,
-	key::KeySubcommand, purge_chain_cmd::PurgeChainCmd, revert_cmd::RevertCmd, run_cmd::RunCmd,
-	sign::SignCmd, vanity::VanityCmd, verify::VerifyCmd,
+	build_spec_cmd::BuildSpecCmd,
+	check_block_cmd::CheckBlockCmd,
+	check_db_cmd::CheckDbCmd,
+	convert_db_cmd::ConvertDbCmd,
+	export_blocks_cmd::ExportBlocksCmd,
+	export_state_cmd::ExportStateCmd,
+	generate::GenerateCmd,
+	generate_node_key::GenerateNodeKeyCmd,
+	import_blocks_cmd::ImportBlocksCmd,
+	insert_key::InsertKeyCmd,
+	inspect_key::InspectKeyCmd,
+	inspect_node_key::InspectNodeKeyCmd,
+	key::KeySubcommand,
+	purge_chain_cmd::PurgeChainCmd,
+	revert_cmd::RevertCmd,
+	run_cmd::RunCmd,
+	sign::SignCmd,
+	snapshot_cmd::{ExportSnapshotCmd, ImportSnapshotCmd, SnapshotCmd},
+	vanity::VanityCmd,
+	verify::VerifyCmd,
 };
@@ -0,0 +1,200 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{
+	error,
+	params::{BlockNumberOrHash, DatabaseParams, ImportParams, PruningParams, SharedParams},
+	CliConfiguration,
+};
+use sc_client_api::{Backend, BlockBackend, HeaderBackend, ProofProvider, UsageProvider};
+use sc_consensus::BlockImport;
+use sc_service::chain_ops::{export_snapshot, import_snapshot};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header as HeaderT},
+};
+use std::{
+	fmt::Debug,
+	fs,
+	io::{self, BufReader, BufWriter, Read, Write},
+	path::PathBuf,
+	str::FromStr,
+	sync::Arc,
+};
+use structopt::StructOpt;
+
+/// The `snapshot` command used to export and import state snapshots.
+#[derive(Debug, StructOpt)]
+pub enum SnapshotCmd {
+	/// Export a snapshot of the state at a finalized block.
+	Export(ExportSnapshotCmd),
+
+	/// Bootstrap the database from a state snapshot.
+	Import(ImportSnapshotCmd),
+}
+
+/// The `snapshot export` command.
+#[derive(Debug, StructOpt)]
+pub struct ExportSnapshotCmd {
+	/// Output file name or stdout if unspecified.
+	#[structopt(parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	/// Block hash or number of a finalized block.
+	///
+	/// Default is the last finalized block.
+	#[structopt(long = "at", value_name = "HASH or NUMBER")]
+	pub at: Option<BlockNumberOrHash>,
+
+	/// Approximate size in bytes of the proof stored in each chunk.
+	#[structopt(long = "chunk-size", value_name = "BYTES", default_value = "2097152")]
+	pub chunk_size: usize,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub pruning_params: PruningParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub database_params: DatabaseParams,
+}
+
+/// The `snapshot import` command.
+#[derive(Debug, StructOpt)]
+pub struct ImportSnapshotCmd {
+	/// Input file or stdin if unspecified.
+	#[structopt(parse(from_os_str))]
+	pub input: Option<PathBuf>,
+
+	/// Hash of the block the snapshot was taken at, obtained from a trusted source.
+	///
+	/// The justifications of the snapshot block can't be verified without syncing the chain up
+	/// to it, so the snapshot is only imported if it matches this hash.
+	#[structopt(long = "trusted-hash", value_name = "HASH")]
+	pub trusted_hash: BlockNumberOrHash,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub import_params: ImportParams,
+}
+
+impl SnapshotCmd {
+	/// Run the snapshot command
+	pub async fn run<B, C, BE>(&self, client: Arc<C>, backend: Arc<BE>) -> error::Result<()>
+	where
+		B: BlockT,
+		C: UsageProvider<B> + HeaderBackend<B> + BlockBackend<B> + ProofProvider<B>,
+		BE: Backend<B>,
+		Arc<C>: BlockImport<B, Error = sp_consensus::Error>,
+		B::Hash: FromStr,
+		<B::Hash as FromStr>::Err: Debug,
+		<<B::Header as HeaderT>::Number as FromStr>::Err: Debug,
+	{
+		match self {
+			SnapshotCmd::Export(cmd) => cmd.run(client).await,
+			SnapshotCmd::Import(cmd) => cmd.run(client, backend).await,
+		}
+	}
+}
+
+impl ExportSnapshotCmd {
+	/// Run the `snapshot export` command
+	pub async fn run<B, C>(&self, client: Arc<C>) -> error::Result<()>
+	where
+		B: BlockT,
+		C: UsageProvider<B> + HeaderBackend<B> + BlockBackend<B> + ProofProvider<B>,
+		B::Hash: FromStr,
+		<B::Hash as FromStr>::Err: Debug,
+		<<B::Header as HeaderT>::Number as FromStr>::Err: Debug,
+	{
+		let block_id = self.at.as_ref().map(|b| b.parse()).transpose()?;
+
+		let file: Box<dyn Write> = match &self.output {
+			Some(filename) => Box::new(fs::File::create(filename)?),
+			None => Box::new(io::stdout()),
+		};
+
+		export_snapshot(client, block_id, BufWriter::new(file), self.chunk_size).map_err(Into::into)
+	}
+}
+
+impl ImportSnapshotCmd {
+	/// Run the `snapshot import` command
+	pub async fn run<B, C, BE>(&self, client: Arc<C>, backend: Arc<BE>) -> error::Result<()>
+	where
+		B: BlockT,
+		C: UsageProvider<B> + ProofProvider<B>,
+		BE: Backend<B>,
+		Arc<C>: BlockImport<B, Error = sp_consensus::Error>,
+		B::Hash: FromStr,
+		<B::Hash as FromStr>::Err: Debug,
+		<<B::Header as HeaderT>::Number as FromStr>::Err: Debug,
+	{
+		let trusted_hash = match self.trusted_hash.parse::<B>()? {
+			BlockId::Hash(hash) => hash,
+			BlockId::Number(_) => return Err("The trusted block must be given by hash".into()),
+		};
+
+		let file: Box<dyn Read> = match &self.input {
+			Some(filename) => Box::new(fs::File::open(filename)?),
+			None => Box::new(io::stdin()),
+		};
+
+		import_snapshot(client.clone(), backend, client, BufReader::new(file), trusted_hash)
+			.await
+			.map_err(Into::into)
+	}
+}
+
+impl CliConfiguration for SnapshotCmd {
+	fn shared_params(&self) -> &SharedParams {
+		match self {
+			SnapshotCmd::Export(cmd) => &cmd.shared_params,
+			SnapshotCmd::Import(cmd) => &cmd.shared_params,
+		}
+	}
+
+	fn import_params(&self) -> Option<&ImportParams> {
+		match self {
+			SnapshotCmd::Export(_) => None,
+			SnapshotCmd::Import(cmd) => Some(&cmd.import_params),
+		}
+	}
+
+	fn pruning_params(&self) -> Option<&PruningParams> {
+		match self {
+			SnapshotCmd::Export(cmd) => Some(&cmd.pruning_params),
+			SnapshotCmd::Import(cmd) => Some(&cmd.import_params.pruning_params),
+		}
+	}
+
+	fn database_params(&self) -> Option<&DatabaseParams> {
+		match self {
+			SnapshotCmd::Export(cmd) => Some(&cmd.database_params),
+			SnapshotCmd::Import(cmd) => Some(&cmd.import_params.database_params),
+		}
+	}
+}
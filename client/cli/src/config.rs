@@ -206,6 +206,14 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 			.unwrap_or(TransactionStorageMode::BlockBody))
 	}
 
+	/// Get whether the keys written by each block should be indexed.
+	///
+	/// By default this is retrieved from `DatabaseParams` if it is available. Otherwise its
+	/// `false`.
+	fn database_state_diff_index(&self) -> Result<bool> {
+		Ok(self.database_params().map(|x| x.state_diff_index()).unwrap_or(false))
+	}
+
 	/// Get the database backend variant.
 	///
 	/// By default this is retrieved from `DatabaseParams` if it is available. Otherwise its `None`.
@@ -236,14 +244,23 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 
 	/// Get the state cache size.
 	///
-	/// By default this is retrieved from `ImportParams` if it is available. Otherwise its `0`.
+	/// Deprecated and ignored, the state cache was replaced by the trie node cache. By default
+	/// this is `0`.
 	fn state_cache_size(&self) -> Result<usize> {
-		Ok(self.import_params().map(|x| x.state_cache_size()).unwrap_or_default())
+		Ok(Default::default())
+	}
+
+	/// Get the trie node cache size.
+	///
+	/// By default this is retrieved from `ImportParams` if it is available. Otherwise its `0`.
+	fn trie_cache_size(&self) -> Result<usize> {
+		Ok(self.import_params().map(|x| x.trie_cache_size()).unwrap_or_default())
 	}
 
 	/// Get the state cache child ratio (if any).
 	///
-	/// By default this is `None`.
+	/// Deprecated and ignored, the trie node cache is shared by all the tries. By default this
+	/// is `None`.
 	fn state_cache_child_ratio(&self) -> Result<Option<usize>> {
 		Ok(Default::default())
 	}
@@ -505,9 +522,11 @@ pub trait CliConfiguration<DCV: DefaultConfigurationValues = ()>: Sized {
 			database: self.database_config(&config_dir, database_cache_size, database, &role)?,
 			state_cache_size: self.state_cache_size()?,
 			state_cache_child_ratio: self.state_cache_child_ratio()?,
+			trie_cache_size: self.trie_cache_size()?,
 			state_pruning: self.state_pruning(unsafe_pruning, &role)?,
 			keep_blocks: self.keep_blocks()?,
 			transaction_storage: self.database_transaction_storage()?,
+			state_diff_index: self.database_state_diff_index()?,
 			wasm_method: self.wasm_method()?,
 			wasm_runtime_overrides: self.wasm_runtime_overrides(),
 			execution_strategies: self.execution_strategies(is_dev, is_validator)?,
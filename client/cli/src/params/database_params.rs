@@ -45,6 +45,14 @@ pub struct DatabaseParams {
 	/// in the block body column.
 	#[structopt(long)]
 	pub storage_chain: bool,
+
+	/// Record the storage keys written by each imported block.
+	///
+	/// This makes `state_queryStorage` and `state_keyHistory` only read the
+	/// blocks in which the requested keys actually changed. Blocks imported
+	/// before the index was enabled are not indexed.
+	#[structopt(long)]
+	pub state_diff_index: bool,
 }
 
 impl DatabaseParams {
@@ -66,4 +74,9 @@ impl DatabaseParams {
 			TransactionStorageMode::BlockBody
 		}
 	}
+
+	/// Whether to index the keys written by each block.
+	pub fn state_diff_index(&self) -> bool {
+		self.state_diff_index
+	}
 }
@@ -73,15 +73,23 @@ pub struct ImportParams {
 	#[structopt(flatten)]
 	pub execution_strategies: ExecutionStrategiesParams,
 
-	/// Specify the state cache size.
-	#[structopt(long = "state-cache-size", value_name = "Bytes", default_value = "67108864")]
-	pub state_cache_size: usize,
+	/// Specify the trie node cache size.
+	///
+	/// The trie node cache is shared by all the forks. `0` disables it. `--state-cache-size` is
+	/// accepted as an alias for compatibility.
+	#[structopt(
+		long = "trie-cache-size",
+		alias = "state-cache-size",
+		value_name = "Bytes",
+		default_value = "67108864"
+	)]
+	pub trie_cache_size: usize,
 }
 
 impl ImportParams {
-	/// Specify the state cache size.
-	pub fn state_cache_size(&self) -> usize {
-		self.state_cache_size
+	/// Specify the trie node cache size.
+	pub fn trie_cache_size(&self) -> usize {
+		self.trie_cache_size
 	}
 
 	/// Get the WASM execution method from the parameters
@@ -19,7 +19,8 @@
 use crate::{arg_enums::SyncMode, params::node_key_params::NodeKeyParams};
 use sc_network::{
 	config::{
-		NetworkConfiguration, NodeKeyConfig, NonReservedPeerMode, SetConfig, TransportConfig,
+		BandwidthLimits, InboundRequestLimits, NetworkConfiguration, NodeKeyConfig,
+		NonReservedPeerMode, RequestQuota, SetConfig, TransportConfig,
 	},
 	multiaddr::Protocol,
 };
@@ -27,7 +28,7 @@ use sc_service::{
 	config::{Multiaddr, MultiaddrWithPeerId},
 	ChainSpec, ChainType,
 };
-use std::{borrow::Cow, path::PathBuf};
+use std::{borrow::Cow, path::PathBuf, time::Duration};
 use structopt::StructOpt;
 
 /// Parameters used to create the network configuration.
@@ -104,6 +105,31 @@ pub struct NetworkParams {
 	#[structopt(long = "max-parallel-downloads", value_name = "COUNT", default_value = "5")]
 	pub max_parallel_downloads: u32,
 
+	/// Maximum upload rate of all the connections combined, in KiB/s.
+	#[structopt(long = "max-upload-rate", value_name = "KIB_PER_SEC")]
+	pub max_upload_rate: Option<u64>,
+
+	/// Maximum download rate of all the connections combined, in KiB/s.
+	#[structopt(long = "max-download-rate", value_name = "KIB_PER_SEC")]
+	pub max_download_rate: Option<u64>,
+
+	/// Maximum rate of the responses to block requests sent to other peers, in KiB/s.
+	///
+	/// Block requests are held back while the limit is exceeded.
+	#[structopt(long = "block-requests-upload-rate", value_name = "KIB_PER_SEC")]
+	pub block_requests_upload_rate: Option<u64>,
+
+	/// Maximum rate of the responses to state requests sent to other peers, in KiB/s.
+	///
+	/// State requests are held back while the limit is exceeded.
+	#[structopt(long = "state-requests-upload-rate", value_name = "KIB_PER_SEC")]
+	pub state_requests_upload_rate: Option<u64>,
+
+	/// Maximum number of block requests and of state requests served to a single peer per
+	/// minute.
+	#[structopt(long = "max-sync-requests-per-peer", value_name = "COUNT")]
+	pub max_sync_requests_per_peer: Option<u32>,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub node_key_params: NodeKeyParams,
@@ -124,9 +150,17 @@ pub struct NetworkParams {
 	pub kademlia_disjoint_query_paths: bool,
 
 	/// Join the IPFS network and serve transactions over bitswap protocol.
+	///
+	/// Also required to fetch stored content from peers with `system_bitswapGet`.
 	#[structopt(long)]
 	pub ipfs_server: bool,
 
+	/// Record all the messages exchanged with the network to the given file.
+	///
+	/// Meant for debugging: the file grows quickly and contains everything sent and received.
+	#[structopt(long = "record-network-messages", value_name = "PATH", parse(from_os_str))]
+	pub record_network_messages: Option<PathBuf>,
+
 	/// Blockchain syncing mode.
 	///
 	/// - `Full`: Download and validate full blockchain history.
@@ -134,6 +168,9 @@ pub struct NetworkParams {
 	/// - `Fast`: Download blocks and the latest state only.
 	///
 	/// - `FastUnsafe`: Same as `Fast`, but skip downloading state proofs.
+	///
+	/// - `HeadersFirst`: Same as `Full`, but while far behind, download the headers first and then
+	///   the block bodies from many peers in parallel.
 	#[structopt(long, value_name = "SYNC_MODE", default_value = "Full")]
 	pub sync: SyncMode,
 }
@@ -199,6 +236,14 @@ impl NetworkParams {
 				is_dev || matches!(chain_type, ChainType::Local | ChainType::Development),
 		};
 
+		let per_peer_quota = self
+			.max_sync_requests_per_peer
+			.map(|max_requests| RequestQuota { max_requests, period: Duration::from_secs(60) });
+		let request_limits = |upload_rate: Option<u64>| InboundRequestLimits {
+			bandwidth: BandwidthLimits { upload: upload_rate.map(kib_to_bytes), download: None },
+			per_peer_quota,
+		};
+
 		NetworkConfiguration {
 			boot_nodes,
 			net_config_path,
@@ -230,6 +275,17 @@ impl NetworkParams {
 			yamux_window_size: None,
 			ipfs_server: self.ipfs_server,
 			sync_mode: self.sync.into(),
+			bandwidth_limits: BandwidthLimits {
+				upload: self.max_upload_rate.map(kib_to_bytes),
+				download: self.max_download_rate.map(kib_to_bytes),
+			},
+			block_request_limits: request_limits(self.block_requests_upload_rate),
+			state_request_limits: request_limits(self.state_requests_upload_rate),
+			message_recording: self.record_network_messages.clone(),
 		}
 	}
 }
+
+fn kib_to_bytes(kib: u64) -> u64 {
+	kib.saturating_mul(1024)
+}
@@ -125,6 +125,8 @@ pub enum StorageChanges<Block: BlockT, Transaction> {
 	Changes(sp_state_machine::StorageChanges<Transaction, HashFor<Block>>),
 	/// Whole new state.
 	Import(ImportedState<Block>),
+	/// Whole new state, already written to the backend with `Backend::write_state_nodes`.
+	Written,
 }
 
 /// Imported state data. A vector of key-value pairs that should form a trie.
@@ -260,6 +262,8 @@ impl<Block: BlockT, Transaction> BlockImportParams<Block, Transaction> {
 		let state_action = match self.state_action {
 			StateAction::ApplyChanges(StorageChanges::Import(state)) =>
 				StateAction::ApplyChanges(StorageChanges::Import(state)),
+			StateAction::ApplyChanges(StorageChanges::Written) =>
+				StateAction::ApplyChanges(StorageChanges::Written),
 			StateAction::ApplyChanges(StorageChanges::Changes(_)) => StateAction::Skip,
 			StateAction::Execute => StateAction::Execute,
 			StateAction::ExecuteIfPossible => StateAction::ExecuteIfPossible,
@@ -312,7 +316,10 @@ impl<Block: BlockT, Transaction> BlockImportParams<Block, Transaction> {
 
 	/// Check if this block contains state import action
 	pub fn with_state(&self) -> bool {
-		matches!(self.state_action, StateAction::ApplyChanges(StorageChanges::Import(_)))
+		matches!(
+			self.state_action,
+			StateAction::ApplyChanges(StorageChanges::Import(_) | StorageChanges::Written)
+		)
 	}
 }
 
@@ -41,4 +41,16 @@ pub trait ConsensusDataProvider<B: BlockT>: Send + Sync {
 		params: &mut BlockImportParams<B, Self::Transaction>,
 		inherents: &InherentData,
 	) -> Result<(), Error>;
+
+	/// Adjust the inherent data of a block sealed at the given `timestamp`, e.g. the slot
+	/// derived from it. The timestamp inherent data is already set.
+	fn set_timestamp(&self, _timestamp: u64, _inherents: &mut InherentData) -> Result<(), Error> {
+		Ok(())
+	}
+
+	/// Check that the given blocks can be reverted, i.e. that no consensus data kept outside
+	/// of the chain depends on them.
+	fn check_revert(&self, _blocks: &[B::Hash]) -> Result<(), Error> {
+		Ok(())
+	}
 }
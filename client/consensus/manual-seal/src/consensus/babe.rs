@@ -310,6 +310,24 @@ where
 
 		Ok(())
 	}
+
+	fn set_timestamp(&self, timestamp: u64, inherents: &mut InherentData) -> Result<(), Error> {
+		inherents.babe_replace_inherent_data(Slot::from(timestamp / self.config.slot_duration));
+		Ok(())
+	}
+
+	fn check_revert(&self, blocks: &[B::Hash]) -> Result<(), Error> {
+		// the epoch changes tree has no way to forget the epochs announced by reverted blocks.
+		let epoch_changes = self.epoch_changes.shared_data();
+		let announced = epoch_changes.tree().iter().find(|(hash, _, _)| blocks.contains(hash));
+		match announced {
+			Some((hash, number, _)) => Err(Error::StringError(format!(
+				"Block #{} ({}) announces a BABE epoch and can't be reverted",
+				number, hash
+			))),
+			None => Ok(()),
+		}
+	}
 }
 
 /// Provide duration since unix epoch in millisecond for timestamp inherent.
@@ -34,6 +34,7 @@ use std::{marker::PhantomData, sync::Arc};
 
 mod error;
 mod finalize_block;
+mod revert_blocks;
 mod seal_block;
 
 pub mod consensus;
@@ -43,8 +44,9 @@ pub use self::{
 	consensus::ConsensusDataProvider,
 	error::Error,
 	finalize_block::{finalize_block, FinalizeBlockParams},
-	rpc::{CreatedBlock, EngineCommand},
-	seal_block::{seal_block, SealBlockParams, MAX_PROPOSAL_DURATION},
+	revert_blocks::{revert_blocks, RevertBlocksParams},
+	rpc::{CreatedBlock, EngineCommand, RevertedBlocks},
+	seal_block::{seal_block, SealBlockParams, TimeOffset, MAX_PROPOSAL_DURATION},
 };
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::{ProvideRuntimeApi, TransactionFor};
@@ -81,7 +83,7 @@ where
 }
 
 /// Params required to start the instant sealing authorship task.
-pub struct ManualSealParams<B: BlockT, BI, E, C: ProvideRuntimeApi<B>, TP, SC, CS, CIDP> {
+pub struct ManualSealParams<B: BlockT, BI, E, C: ProvideRuntimeApi<B>, CB, TP, SC, CS, CIDP> {
 	/// Block import instance for well. importing blocks.
 	pub block_import: BI,
 
@@ -91,6 +93,9 @@ pub struct ManualSealParams<B: BlockT, BI, E, C: ProvideRuntimeApi<B>, TP, SC, C
 	/// Client instance
 	pub client: Arc<C>,
 
+	/// Backend instance, used to revert blocks.
+	pub backend: Arc<CB>,
+
 	/// Shared reference to the transaction pool.
 	pub pool: Arc<TP>,
 
@@ -110,7 +115,7 @@ pub struct ManualSealParams<B: BlockT, BI, E, C: ProvideRuntimeApi<B>, TP, SC, C
 }
 
 /// Params required to start the manual sealing authorship task.
-pub struct InstantSealParams<B: BlockT, BI, E, C: ProvideRuntimeApi<B>, TP, SC, CIDP> {
+pub struct InstantSealParams<B: BlockT, BI, E, C: ProvideRuntimeApi<B>, CB, TP, SC, CIDP> {
 	/// Block import instance for well. importing blocks.
 	pub block_import: BI,
 
@@ -120,6 +125,9 @@ pub struct InstantSealParams<B: BlockT, BI, E, C: ProvideRuntimeApi<B>, TP, SC,
 	/// Client instance
 	pub client: Arc<C>,
 
+	/// Backend instance, used to revert blocks.
+	pub backend: Arc<CB>,
+
 	/// Shared reference to the transaction pool.
 	pub pool: Arc<TP>,
 
@@ -140,12 +148,13 @@ pub async fn run_manual_seal<B, BI, CB, E, C, TP, SC, CS, CIDP>(
 		mut block_import,
 		mut env,
 		client,
+		backend,
 		pool,
 		mut commands_stream,
 		select_chain,
 		consensus_data_provider,
 		create_inherent_data_providers,
-	}: ManualSealParams<B, BI, E, C, TP, SC, CS, CIDP>,
+	}: ManualSealParams<B, BI, E, C, CB, TP, SC, CS, CIDP>,
 ) where
 	B: BlockT + 'static,
 	BI: BlockImport<B, Error = sp_consensus::Error, Transaction = sp_api::TransactionFor<C, B>>
@@ -162,12 +171,15 @@ pub async fn run_manual_seal<B, BI, CB, E, C, TP, SC, CS, CIDP>(
 	TP: TransactionPool<Block = B>,
 	CIDP: CreateInherentDataProviders<B, ()>,
 {
+	let mut time_offset = TimeOffset::default();
 	while let Some(command) = commands_stream.next().await {
 		match command {
 			EngineCommand::SealNewBlock { create_empty, finalize, parent_hash, sender } => {
 				seal_block(SealBlockParams {
 					sender,
 					parent_hash,
+					timestamp: None,
+					time_offset: &mut time_offset,
 					finalize,
 					create_empty,
 					env: &mut env,
@@ -191,6 +203,69 @@ pub async fn run_manual_seal<B, BI, CB, E, C, TP, SC, CS, CIDP>(
 				})
 				.await
 			},
+			EngineCommand::SealNewBlockAt {
+				create_empty,
+				finalize,
+				parent_hash,
+				timestamp,
+				sender,
+			} => {
+				seal_block(SealBlockParams {
+					sender,
+					parent_hash,
+					timestamp: Some(timestamp),
+					time_offset: &mut time_offset,
+					finalize,
+					create_empty,
+					env: &mut env,
+					select_chain: &select_chain,
+					block_import: &mut block_import,
+					consensus_data_provider: consensus_data_provider.as_deref(),
+					pool: pool.clone(),
+					client: client.clone(),
+					create_inherent_data_providers: &create_inherent_data_providers,
+				})
+				.await;
+			},
+			EngineCommand::SealNewBlocks { count, create_empty, finalize, mut sender } => {
+				let mut blocks = Vec::with_capacity(count as usize);
+				let mut result = Ok(());
+				for _ in 0..count {
+					let sealed = seal_block::try_seal_block(SealBlockParams {
+						sender: None,
+						parent_hash: None,
+						timestamp: None,
+						time_offset: &mut time_offset,
+						finalize,
+						create_empty,
+						env: &mut env,
+						select_chain: &select_chain,
+						block_import: &mut block_import,
+						consensus_data_provider: consensus_data_provider.as_deref(),
+						pool: pool.clone(),
+						client: client.clone(),
+						create_inherent_data_providers: &create_inherent_data_providers,
+					})
+					.await;
+
+					match sealed {
+						Ok(block) => blocks.push(block),
+						Err(e) => {
+							result = Err(e);
+							break
+						},
+					}
+				}
+				rpc::send_result(&mut sender, result.map(|_| blocks));
+			},
+			EngineCommand::RevertBlocks { count, sender } =>
+				revert_blocks(RevertBlocksParams {
+					count,
+					sender,
+					backend: backend.clone(),
+					consensus_data_provider: consensus_data_provider.as_deref(),
+				})
+				.await,
 		}
 	}
 }
@@ -203,11 +278,12 @@ pub async fn run_instant_seal<B, BI, CB, E, C, TP, SC, CIDP>(
 		block_import,
 		env,
 		client,
+		backend,
 		pool,
 		select_chain,
 		consensus_data_provider,
 		create_inherent_data_providers,
-	}: InstantSealParams<B, BI, E, C, TP, SC, CIDP>,
+	}: InstantSealParams<B, BI, E, C, CB, TP, SC, CIDP>,
 ) where
 	B: BlockT + 'static,
 	BI: BlockImport<B, Error = sp_consensus::Error, Transaction = sp_api::TransactionFor<C, B>>
@@ -236,6 +312,7 @@ pub async fn run_instant_seal<B, BI, CB, E, C, TP, SC, CIDP>(
 		block_import,
 		env,
 		client,
+		backend,
 		pool,
 		commands_stream,
 		select_chain,
@@ -248,14 +325,21 @@ pub async fn run_instant_seal<B, BI, CB, E, C, TP, SC, CIDP>(
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use codec::Encode;
 	use sc_basic_authorship::ProposerFactory;
 	use sc_client_api::BlockBackend;
-	use sc_consensus::ImportedAux;
+	use sc_consensus::{BlockImportParams, ImportedAux};
 	use sc_transaction_pool::{BasicPool, Options, RevalidationType};
 	use sc_transaction_pool_api::{MaintainedTransactionPool, TransactionPool, TransactionSource};
-	use sp_runtime::generic::BlockId;
+	use sp_consensus_babe::{inherents::BabeInherentData, Slot};
+	use sp_inherents::InherentData;
+	use sp_runtime::{generic::BlockId, traits::Header as _, Digest, DigestItem};
+	use sp_timestamp::TimestampInherentData;
+	use std::time::Duration;
 	use substrate_test_runtime_client::{
-		AccountKeyring::*, DefaultTestClientBuilderExt, TestClientBuilder, TestClientBuilderExt,
+		runtime::{Block, Hash},
+		AccountKeyring::*,
+		DefaultTestClientBuilderExt, TestClient, TestClientBuilder, TestClientBuilderExt,
 	};
 	use substrate_test_runtime_transaction_pool::{uxt, TestApi};
 
@@ -268,6 +352,7 @@ mod tests {
 	#[tokio::test]
 	async fn instant_seal() {
 		let builder = TestClientBuilder::new();
+		let backend = builder.backend();
 		let (client, select_chain) = builder.build_with_longest_chain();
 		let client = Arc::new(client);
 		let spawner = sp_core::testing::TaskExecutor::new();
@@ -301,6 +386,7 @@ mod tests {
 			block_import: client.clone(),
 			env,
 			client: client.clone(),
+			backend,
 			pool: pool.clone(),
 			commands_stream,
 			select_chain,
@@ -338,6 +424,7 @@ mod tests {
 	#[tokio::test]
 	async fn manual_seal_and_finalization() {
 		let builder = TestClientBuilder::new();
+		let backend = builder.backend();
 		let (client, select_chain) = builder.build_with_longest_chain();
 		let client = Arc::new(client);
 		let spawner = sp_core::testing::TaskExecutor::new();
@@ -358,6 +445,7 @@ mod tests {
 			block_import: client.clone(),
 			env,
 			client: client.clone(),
+			backend,
 			pool: pool.clone(),
 			commands_stream,
 			select_chain,
@@ -415,6 +503,7 @@ mod tests {
 	#[tokio::test]
 	async fn manual_seal_fork_blocks() {
 		let builder = TestClientBuilder::new();
+		let backend = builder.backend();
 		let (client, select_chain) = builder.build_with_longest_chain();
 		let client = Arc::new(client);
 		let pool_api = api();
@@ -436,6 +525,7 @@ mod tests {
 			block_import: client.clone(),
 			env,
 			client: client.clone(),
+			backend,
 			pool: pool.clone(),
 			commands_stream,
 			select_chain,
@@ -519,4 +609,233 @@ mod tests {
 		// assert that fork block is in the db
 		assert!(client.header(&BlockId::Hash(imported.hash)).unwrap().is_some())
 	}
+
+	#[tokio::test]
+	async fn manual_seal_multiple_blocks_and_revert() {
+		let builder = TestClientBuilder::new();
+		let backend = builder.backend();
+		let (client, select_chain) = builder.build_with_longest_chain();
+		let client = Arc::new(client);
+		let spawner = sp_core::testing::TaskExecutor::new();
+		let pool = Arc::new(BasicPool::with_revalidation_type(
+			Options::default(),
+			true.into(),
+			api(),
+			None,
+			RevalidationType::Full,
+			spawner.clone(),
+			0,
+		));
+		let env = ProposerFactory::new(spawner.clone(), client.clone(), pool.clone(), None, None);
+		// this test checks that several blocks can be sealed with a single command, and then
+		// reverted.
+		let (mut sink, commands_stream) = futures::channel::mpsc::channel(1024);
+		let future = run_manual_seal(ManualSealParams {
+			block_import: client.clone(),
+			env,
+			client: client.clone(),
+			backend,
+			pool: pool.clone(),
+			commands_stream,
+			select_chain,
+			consensus_data_provider: None,
+			create_inherent_data_providers: |_, _| async { Ok(()) },
+		});
+		std::thread::spawn(|| {
+			let rt = tokio::runtime::Runtime::new().unwrap();
+			// spawn the background authorship task
+			rt.block_on(future);
+		});
+
+		let (tx, rx) = futures::channel::oneshot::channel();
+		sink.send(EngineCommand::SealNewBlocks {
+			count: 5,
+			create_empty: true,
+			finalize: false,
+			sender: Some(tx),
+		})
+		.await
+		.unwrap();
+		let created_blocks = rx.await.unwrap().unwrap();
+		assert_eq!(created_blocks.len(), 5);
+		assert_eq!(client.info().best_number, 5);
+		assert_eq!(client.info().best_hash, created_blocks[4].hash);
+
+		// finalize the first block, it can't be reverted.
+		let (tx, rx) = futures::channel::oneshot::channel();
+		sink.send(EngineCommand::FinalizeBlock {
+			sender: Some(tx),
+			hash: created_blocks[0].hash,
+			justification: None,
+		})
+		.await
+		.unwrap();
+		rx.await.unwrap().unwrap();
+
+		let (tx, rx) = futures::channel::oneshot::channel();
+		sink.send(EngineCommand::RevertBlocks { count: 2, sender: Some(tx) })
+			.await
+			.unwrap();
+		assert_eq!(
+			rx.await.unwrap().unwrap(),
+			RevertedBlocks { reverted: 2, best_hash: created_blocks[2].hash },
+		);
+		assert_eq!(client.info().best_number, 3);
+
+		let (tx, rx) = futures::channel::oneshot::channel();
+		sink.send(EngineCommand::RevertBlocks { count: 10, sender: Some(tx) })
+			.await
+			.unwrap();
+		assert_eq!(
+			rx.await.unwrap().unwrap(),
+			RevertedBlocks { reverted: 2, best_hash: created_blocks[0].hash },
+		);
+		assert_eq!(client.info().best_number, 1);
+	}
+
+	const SLOT_DURATION: u64 = 6000;
+
+	/// Records the timestamp and slot of the inherent data in the digest of the sealed blocks.
+	struct TimestampDigestProvider<T>(PhantomData<fn() -> T>);
+
+	impl<T> ConsensusDataProvider<Block> for TimestampDigestProvider<T> {
+		type Transaction = T;
+
+		fn create_digest(
+			&self,
+			_parent: &<Block as BlockT>::Header,
+			inherents: &InherentData,
+		) -> Result<Digest, Error> {
+			let timestamp = inherents.timestamp_inherent_data()?.map(u64::from);
+			let slot = inherents.babe_inherent_data()?;
+			Ok(Digest { logs: vec![DigestItem::PreRuntime(*b"test", (timestamp, slot).encode())] })
+		}
+
+		fn append_block_import(
+			&self,
+			_parent: &<Block as BlockT>::Header,
+			_params: &mut BlockImportParams<Block, Self::Transaction>,
+			_inherents: &InherentData,
+		) -> Result<(), Error> {
+			Ok(())
+		}
+
+		fn set_timestamp(&self, timestamp: u64, inherents: &mut InherentData) -> Result<(), Error> {
+			inherents.babe_replace_inherent_data(Slot::from(timestamp / SLOT_DURATION));
+			Ok(())
+		}
+	}
+
+	fn sealed_at(client: &TestClient, hash: Hash) -> (u64, Slot) {
+		let header = client.header(&BlockId::Hash(hash)).unwrap().unwrap();
+		let (timestamp, slot): (Option<u64>, Option<Slot>) = header
+			.digest()
+			.convert_first(|log| log.pre_runtime_try_to(b"test"))
+			.unwrap();
+		(timestamp.unwrap(), slot.unwrap())
+	}
+
+	#[tokio::test]
+	async fn manual_seal_at_timestamp() {
+		let builder = TestClientBuilder::new();
+		let backend = builder.backend();
+		let (client, select_chain) = builder.build_with_longest_chain();
+		let client = Arc::new(client);
+		let spawner = sp_core::testing::TaskExecutor::new();
+		let pool = Arc::new(BasicPool::with_revalidation_type(
+			Options::default(),
+			true.into(),
+			api(),
+			None,
+			RevalidationType::Full,
+			spawner.clone(),
+			0,
+		));
+		let env = ProposerFactory::new(spawner.clone(), client.clone(), pool.clone(), None, None);
+		let (mut sink, commands_stream) = futures::channel::mpsc::channel(1024);
+		let future = run_manual_seal(ManualSealParams {
+			block_import: client.clone(),
+			env,
+			client: client.clone(),
+			backend,
+			pool: pool.clone(),
+			commands_stream,
+			select_chain,
+			consensus_data_provider: Some(Box::new(TimestampDigestProvider(PhantomData))),
+			create_inherent_data_providers: |_, _| async {
+				let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
+				let slot =
+					sp_consensus_babe::inherents::InherentDataProvider::from_timestamp_and_duration(
+						*timestamp,
+						Duration::from_millis(SLOT_DURATION),
+					);
+				Ok((timestamp, slot))
+			},
+		});
+		std::thread::spawn(|| {
+			let rt = tokio::runtime::Runtime::new().unwrap();
+			// spawn the background authorship task
+			rt.block_on(future);
+		});
+
+		// jump a day ahead.
+		let timestamp =
+			**sp_timestamp::InherentDataProvider::from_system_time() + 24 * 60 * 60 * 1000;
+		let (tx, rx) = futures::channel::oneshot::channel();
+		sink.send(EngineCommand::SealNewBlockAt {
+			create_empty: true,
+			finalize: false,
+			parent_hash: None,
+			timestamp,
+			sender: Some(tx),
+		})
+		.await
+		.unwrap();
+		let created_block = rx.await.unwrap().unwrap();
+		assert_eq!(client.info().best_hash, created_block.hash);
+		assert_eq!(
+			sealed_at(&client, created_block.hash),
+			(timestamp, Slot::from(timestamp / SLOT_DURATION))
+		);
+
+		// the following blocks keep following the jump.
+		let (tx, rx) = futures::channel::oneshot::channel();
+		sink.send(EngineCommand::SealNewBlock {
+			create_empty: true,
+			finalize: false,
+			parent_hash: None,
+			sender: Some(tx),
+		})
+		.await
+		.unwrap();
+		let created_block = rx.await.unwrap().unwrap();
+		assert_eq!(client.info().best_hash, created_block.hash);
+		let (next_timestamp, next_slot) = sealed_at(&client, created_block.hash);
+		assert!(next_timestamp >= timestamp && next_timestamp < timestamp + 60 * 1000);
+		assert_eq!(next_slot, Slot::from(next_timestamp / SLOT_DURATION));
+	}
+
+	#[test]
+	fn time_offset_follows_the_jump() {
+		// wall clock providers keep being shifted.
+		let mut offset = TimeOffset::default();
+		assert_eq!(offset.apply(Some(1_000), None), Some(1_000));
+		assert_eq!(offset.apply(Some(2_000), Some(10_000)), Some(10_000));
+		assert_eq!(offset.apply(Some(2_500), None), Some(10_500));
+		assert_eq!(offset.apply(Some(3_000), None), Some(11_000));
+
+		// chain relative providers already follow the jump.
+		let mut offset = TimeOffset::default();
+		assert_eq!(offset.apply(Some(2_000), Some(10_000)), Some(10_000));
+		assert_eq!(offset.apply(Some(16_000), None), Some(16_000));
+		assert_eq!(offset.apply(Some(22_000), None), Some(22_000));
+
+		// going back in time.
+		let mut offset = TimeOffset::default();
+		assert_eq!(offset.apply(Some(100_000), Some(1_000)), Some(1_000));
+		assert_eq!(offset.apply(Some(100_500), None), Some(1_500));
+		let mut offset = TimeOffset::default();
+		assert_eq!(offset.apply(Some(100_000), Some(1_000)), Some(1_000));
+		assert_eq!(offset.apply(Some(7_000), None), Some(7_000));
+	}
 }
@@ -0,0 +1,93 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2019-2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Block reversion utilities
+
+use crate::{rpc, ConsensusDataProvider, Error, RevertedBlocks};
+use sc_client_api::backend::Backend as ClientBackend;
+use sp_blockchain::HeaderBackend;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header as HeaderT, UniqueSaturatedInto},
+};
+use std::sync::Arc;
+
+/// params for block reversion.
+pub struct RevertBlocksParams<'a, B: BlockT, CB, T> {
+	/// number of blocks to revert
+	pub count: u32,
+	/// sender to report errors/success to the rpc.
+	pub sender: rpc::Sender<RevertedBlocks<<B as BlockT>::Hash>>,
+	/// Backend to revert the blocks from.
+	pub backend: Arc<CB>,
+	/// Digest provider, checking that the blocks can be reverted.
+	pub consensus_data_provider: Option<&'a dyn ConsensusDataProvider<B, Transaction = T>>,
+}
+
+/// reverts the latest unfinalized blocks of the best chain in the backend with the given
+/// params. Finalized blocks are never reverted, and nothing is reverted if the consensus data
+/// provider refuses it.
+pub async fn revert_blocks<B, CB, T>(params: RevertBlocksParams<'_, B, CB, T>)
+where
+	B: BlockT,
+	CB: ClientBackend<B>,
+{
+	let RevertBlocksParams { count, mut sender, backend, consensus_data_provider } = params;
+
+	let result = match consensus_data_provider {
+		Some(provider) => blocks_to_revert(&*backend, count)
+			.map_err(Error::from)
+			.and_then(|blocks| provider.check_revert(&blocks)),
+		None => Ok(()),
+	}
+	.and_then(|()| backend.revert(count.into(), false).map_err(Error::from));
+
+	match result {
+		Err(e) => {
+			log::warn!("Failed to revert blocks {:?}", e);
+			rpc::send_result(&mut sender, Err(e))
+		},
+		Ok((reverted, _)) => {
+			let best_hash = backend.blockchain().info().best_hash;
+			log::info!("⏪ Successfully reverted {} blocks, best block: {}", reverted, best_hash);
+			rpc::send_result(
+				&mut sender,
+				Ok(RevertedBlocks { reverted: reverted.unique_saturated_into(), best_hash }),
+			)
+		},
+	}
+}
+
+/// Returns the hashes of the blocks reverted by `Backend::revert` for the given `count`.
+fn blocks_to_revert<B: BlockT, CB: ClientBackend<B>>(
+	backend: &CB,
+	count: u32,
+) -> sp_blockchain::Result<Vec<B::Hash>> {
+	let blockchain = backend.blockchain();
+	let info = blockchain.info();
+	let mut blocks = Vec::new();
+	let mut header = blockchain.header(BlockId::Hash(info.best_hash))?;
+	while let Some(current) = header {
+		if blocks.len() >= count as usize || *current.number() <= info.finalized_number {
+			break
+		}
+		blocks.push(current.hash());
+		header = blockchain.header(BlockId::Hash(*current.parent_hash()))?;
+	}
+	Ok(blocks)
+}
@@ -62,6 +62,48 @@ pub enum EngineCommand<Hash> {
 		/// finalization justification
 		justification: Option<EncodedJustification>,
 	},
+	/// Tells the engine to propose a new block with the given timestamp, instead of the one
+	/// of the inherent data providers.
+	///
+	/// This allows to jump forward in time, e.g. to reach the end of a vesting schedule,
+	/// without sealing all the blocks in between.
+	SealNewBlockAt {
+		/// if true, empty blocks(without extrinsics) will be created.
+		/// otherwise, will return Error::EmptyTransactionPool.
+		create_empty: bool,
+		/// instantly finalize this block?
+		finalize: bool,
+		/// specify the parent hash of the about-to-created block
+		parent_hash: Option<Hash>,
+		/// timestamp of the block, in milliseconds.
+		timestamp: u64,
+		/// sender to report errors/success to the rpc.
+		sender: Sender<CreatedBlock<Hash>>,
+	},
+	/// Tells the engine to propose `count` new blocks, each on top of the best block.
+	///
+	/// Stops at the first block that fails to be sealed.
+	SealNewBlocks {
+		/// number of blocks to create.
+		count: u32,
+		/// if true, empty blocks(without extrinsics) will be created.
+		/// otherwise, will return Error::EmptyTransactionPool.
+		create_empty: bool,
+		/// instantly finalize these blocks?
+		finalize: bool,
+		/// sender to report errors/success to the rpc.
+		sender: Sender<Vec<CreatedBlock<Hash>>>,
+	},
+	/// Tells the engine to revert the `count` latest blocks of the best chain.
+	///
+	/// Finalized blocks are never reverted. Nothing is reverted if consensus data kept outside
+	/// of the chain depends on the blocks, e.g. if one of them announces a BABE epoch.
+	RevertBlocks {
+		/// number of blocks to revert.
+		count: u32,
+		/// sender to report errors/success to the rpc.
+		sender: Sender<RevertedBlocks<Hash>>,
+	},
 }
 
 /// RPC trait that provides methods for interacting with the manual-seal authorship task over rpc.
@@ -83,6 +125,32 @@ pub trait ManualSealApi<Hash> {
 		hash: Hash,
 		justification: Option<EncodedJustification>,
 	) -> FutureResult<bool>;
+
+	/// Instructs the manual-seal authorship task to create a new block with the given
+	/// timestamp, in milliseconds
+	#[rpc(name = "engine_createBlockAt")]
+	fn create_block_at(
+		&self,
+		create_empty: bool,
+		finalize: bool,
+		parent_hash: Option<Hash>,
+		timestamp: u64,
+	) -> FutureResult<CreatedBlock<Hash>>;
+
+	/// Instructs the manual-seal authorship task to create `count` new blocks on top of the
+	/// best block
+	#[rpc(name = "engine_createBlocks")]
+	fn create_blocks(
+		&self,
+		count: u32,
+		create_empty: bool,
+		finalize: bool,
+	) -> FutureResult<Vec<CreatedBlock<Hash>>>;
+
+	/// Instructs the manual-seal authorship task to revert the `count` latest unfinalized
+	/// blocks
+	#[rpc(name = "engine_revertBlocks")]
+	fn revert_blocks(&self, count: u32) -> FutureResult<RevertedBlocks<Hash>>;
 }
 
 /// A struct that implements the [`ManualSealApi`].
@@ -99,6 +167,15 @@ pub struct CreatedBlock<Hash> {
 	pub aux: ImportedAux,
 }
 
+/// return type of `engine_revertBlocks`
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RevertedBlocks<Hash> {
+	/// number of blocks that were reverted.
+	pub reverted: u32,
+	/// hash of the best block after the reversion.
+	pub best_hash: Hash,
+}
+
 impl<Hash> ManualSeal<Hash> {
 	/// Create new `ManualSeal` with the given reference to the client.
 	pub fn new(import_block_channel: mpsc::Sender<EngineCommand<Hash>>) -> Self {
@@ -145,6 +222,63 @@ impl<Hash: Send + 'static> ManualSealApi<Hash> for ManualSeal<Hash> {
 		.map_err(Error::from)
 		.boxed()
 	}
+
+	fn create_block_at(
+		&self,
+		create_empty: bool,
+		finalize: bool,
+		parent_hash: Option<Hash>,
+		timestamp: u64,
+	) -> FutureResult<CreatedBlock<Hash>> {
+		let mut sink = self.import_block_channel.clone();
+		async move {
+			let (sender, receiver) = oneshot::channel();
+			let command = EngineCommand::SealNewBlockAt {
+				create_empty,
+				finalize,
+				parent_hash,
+				timestamp,
+				sender: Some(sender),
+			};
+			sink.send(command).await?;
+			receiver.await?
+		}
+		.map_err(Error::from)
+		.boxed()
+	}
+
+	fn create_blocks(
+		&self,
+		count: u32,
+		create_empty: bool,
+		finalize: bool,
+	) -> FutureResult<Vec<CreatedBlock<Hash>>> {
+		let mut sink = self.import_block_channel.clone();
+		async move {
+			let (sender, receiver) = oneshot::channel();
+			let command = EngineCommand::SealNewBlocks {
+				count,
+				create_empty,
+				finalize,
+				sender: Some(sender),
+			};
+			sink.send(command).await?;
+			receiver.await?
+		}
+		.map_err(Error::from)
+		.boxed()
+	}
+
+	fn revert_blocks(&self, count: u32) -> FutureResult<RevertedBlocks<Hash>> {
+		let mut sink = self.import_block_channel.clone();
+		async move {
+			let (sender, receiver) = oneshot::channel();
+			sink.send(EngineCommand::RevertBlocks { count, sender: Some(sender) }).await?;
+			receiver.await?
+		}
+		.map_err(Error::from)
+		.boxed()
+	}
 }
 
 /// report any errors or successes encountered by the authorship task back
@@ -30,11 +30,51 @@ use sp_runtime::{
 	generic::BlockId,
 	traits::{Block as BlockT, Header as HeaderT},
 };
+use sp_timestamp::{InherentType, TimestampInherentData, INHERENT_IDENTIFIER};
 use std::{collections::HashMap, sync::Arc, time::Duration};
 
 /// max duration for creating a proposal in secs
 pub const MAX_PROPOSAL_DURATION: u64 = 10;
 
+/// Shift of the timestamps given by the inherent data providers, so that the blocks sealed after
+/// a block sealed at a given timestamp keep following it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TimeOffset {
+	/// milliseconds added to the timestamps of the inherent data providers.
+	millis: i64,
+	/// timestamp of the block sealed at a given timestamp, until the next block is sealed.
+	jumped_to: Option<u64>,
+}
+
+impl TimeOffset {
+	/// Returns the timestamp of the next block, given the one of the inherent data providers
+	/// and the one requested for the block, if any.
+	pub(crate) fn apply(&mut self, provided: Option<u64>, requested: Option<u64>) -> Option<u64> {
+		match (provided, requested) {
+			(Some(provided), Some(requested)) => {
+				self.millis = requested as i64 - provided as i64;
+				self.jumped_to = Some(requested);
+				Some(requested)
+			},
+			(None, requested) => requested,
+			(Some(provided), None) => {
+				// providers deriving the timestamp from the chain, such as the
+				// `SlotTimestampProvider`, already follow the jump: their timestamp is closer to
+				// the one jumped to than to the one they provided before the jump.
+				if let Some(jumped_to) = self.jumped_to.take() {
+					let before_jump = jumped_to as i64 - self.millis;
+					if (provided as i64 - jumped_to as i64).abs() <
+						(provided as i64 - before_jump).abs()
+					{
+						self.millis = 0;
+					}
+				}
+				Some((provided as i64).saturating_add(self.millis).max(0) as u64)
+			},
+		}
+	}
+}
+
 /// params for sealing a new block
 pub struct SealBlockParams<'a, B: BlockT, BI, SC, C: ProvideRuntimeApi<B>, E, TP, CIDP> {
 	/// if true, empty blocks(without extrinsics) will be created.
@@ -44,6 +84,12 @@ pub struct SealBlockParams<'a, B: BlockT, BI, SC, C: ProvideRuntimeApi<B>, E, TP
 	pub finalize: bool,
 	/// specify the parent hash of the about-to-created block
 	pub parent_hash: Option<<B as BlockT>::Hash>,
+	/// timestamp of the about-to-created block, in milliseconds, overriding the one of the
+	/// inherent data providers.
+	pub timestamp: Option<u64>,
+	/// shift of the timestamps of the inherent data providers, updated when `timestamp` is
+	/// set and applied to the following blocks.
+	pub time_offset: &'a mut TimeOffset,
 	/// sender to report errors/success to the rpc.
 	pub sender: rpc::Sender<CreatedBlock<<B as BlockT>::Hash>>,
 	/// transaction pool
@@ -65,20 +111,47 @@ pub struct SealBlockParams<'a, B: BlockT, BI, SC, C: ProvideRuntimeApi<B>, E, TP
 
 /// seals a new block with the given params
 pub async fn seal_block<B, BI, SC, C, E, TP, CIDP>(
+	mut params: SealBlockParams<'_, B, BI, SC, C, E, TP, CIDP>,
+) where
+	B: BlockT,
+	BI: BlockImport<B, Error = sp_consensus::Error, Transaction = sp_api::TransactionFor<C, B>>
+		+ Send
+		+ Sync
+		+ 'static,
+	C: HeaderBackend<B> + ProvideRuntimeApi<B>,
+	E: Environment<B>,
+	E::Proposer: Proposer<B, Transaction = TransactionFor<C, B>>,
+	TP: TransactionPool<Block = B>,
+	SC: SelectChain<B>,
+	TransactionFor<C, B>: 'static,
+	CIDP: CreateInherentDataProviders<B, ()>,
+{
+	let mut sender = params.sender.take();
+	let result = try_seal_block(params).await;
+
+	rpc::send_result(&mut sender, result)
+}
+
+/// seals a new block with the given params, returning the created block instead of
+/// reporting it to the `sender` of the params.
+pub(crate) async fn try_seal_block<B, BI, SC, C, E, TP, CIDP>(
 	SealBlockParams {
 		create_empty,
 		finalize,
 		pool,
 		parent_hash,
+		timestamp,
+		time_offset,
 		client,
 		select_chain,
 		block_import,
 		env,
 		create_inherent_data_providers,
 		consensus_data_provider: digest_provider,
-		mut sender,
+		sender: _,
 	}: SealBlockParams<'_, B, BI, SC, C, E, TP, CIDP>,
-) where
+) -> Result<CreatedBlock<<B as BlockT>::Hash>, Error>
+where
 	B: BlockT,
 	BI: BlockImport<B, Error = sp_consensus::Error, Transaction = sp_api::TransactionFor<C, B>>
 		+ Send
@@ -92,73 +165,77 @@ pub async fn seal_block<B, BI, SC, C, E, TP, CIDP>(
 	TransactionFor<C, B>: 'static,
 	CIDP: CreateInherentDataProviders<B, ()>,
 {
-	let future = async {
-		if pool.status().ready == 0 && !create_empty {
-			return Err(Error::EmptyTransactionPool)
-		}
+	if pool.status().ready == 0 && !create_empty {
+		return Err(Error::EmptyTransactionPool)
+	}
+
+	// get the header to build this new block on.
+	// use the parent_hash supplied via `EngineCommand`
+	// or fetch the best_block.
+	let parent = match parent_hash {
+		Some(hash) => client
+			.header(BlockId::Hash(hash))?
+			.ok_or_else(|| Error::BlockNotFound(format!("{}", hash)))?,
+		None => select_chain.best_chain().await?,
+	};
 
-		// get the header to build this new block on.
-		// use the parent_hash supplied via `EngineCommand`
-		// or fetch the best_block.
-		let parent = match parent_hash {
-			Some(hash) => client
-				.header(BlockId::Hash(hash))?
-				.ok_or_else(|| Error::BlockNotFound(format!("{}", hash)))?,
-			None => select_chain.best_chain().await?,
-		};
-
-		let inherent_data_providers = create_inherent_data_providers
-			.create_inherent_data_providers(parent.hash(), ())
-			.await
-			.map_err(|e| Error::Other(e))?;
-
-		let inherent_data = inherent_data_providers.create_inherent_data()?;
-
-		let proposer = env
-			.init(&parent)
-			.map_err(|err| Error::StringError(format!("{:?}", err)))
-			.await?;
-		let inherents_len = inherent_data.len();
-
-		let digest = if let Some(digest_provider) = digest_provider {
-			digest_provider.create_digest(&parent, &inherent_data)?
-		} else {
-			Default::default()
-		};
-
-		let proposal = proposer
-			.propose(
-				inherent_data.clone(),
-				digest,
-				Duration::from_secs(MAX_PROPOSAL_DURATION),
-				None,
-			)
-			.map_err(|err| Error::StringError(format!("{:?}", err)))
-			.await?;
-
-		if proposal.block.extrinsics().len() == inherents_len && !create_empty {
-			return Err(Error::EmptyTransactionPool)
-		}
+	let inherent_data_providers = create_inherent_data_providers
+		.create_inherent_data_providers(parent.hash(), ())
+		.await
+		.map_err(|e| Error::Other(e))?;
 
-		let (header, body) = proposal.block.deconstruct();
-		let mut params = BlockImportParams::new(BlockOrigin::Own, header.clone());
-		params.body = Some(body);
-		params.finalized = finalize;
-		params.fork_choice = Some(ForkChoiceStrategy::LongestChain);
-		params.state_action = StateAction::ApplyChanges(sc_consensus::StorageChanges::Changes(
-			proposal.storage_changes,
-		));
+	let mut inherent_data = inherent_data_providers.create_inherent_data()?;
 
-		if let Some(digest_provider) = digest_provider {
-			digest_provider.append_block_import(&parent, &mut params, &inherent_data)?;
-		}
+	// the offset is only kept if the block is sealed.
+	let mut next_time_offset = *time_offset;
+	let provided = inherent_data.timestamp_inherent_data()?.map(u64::from);
+	let timestamp = next_time_offset.apply(provided, timestamp);
+	if let Some(timestamp) = timestamp.filter(|timestamp| Some(*timestamp) != provided) {
+		inherent_data.replace_data(INHERENT_IDENTIFIER, &InherentType::from(timestamp));
 
-		match block_import.import_block(params, HashMap::new()).await? {
-			ImportResult::Imported(aux) =>
-				Ok(CreatedBlock { hash: <B as BlockT>::Header::hash(&header), aux }),
-			other => Err(other.into()),
+		if let Some(digest_provider) = digest_provider {
+			digest_provider.set_timestamp(timestamp, &mut inherent_data)?;
 		}
+	}
+
+	let proposer = env
+		.init(&parent)
+		.map_err(|err| Error::StringError(format!("{:?}", err)))
+		.await?;
+	let inherents_len = inherent_data.len();
+
+	let digest = if let Some(digest_provider) = digest_provider {
+		digest_provider.create_digest(&parent, &inherent_data)?
+	} else {
+		Default::default()
 	};
 
-	rpc::send_result(&mut sender, future.await)
+	let proposal = proposer
+		.propose(inherent_data.clone(), digest, Duration::from_secs(MAX_PROPOSAL_DURATION), None)
+		.map_err(|err| Error::StringError(format!("{:?}", err)))
+		.await?;
+
+	if proposal.block.extrinsics().len() == inherents_len && !create_empty {
+		return Err(Error::EmptyTransactionPool)
+	}
+
+	let (header, body) = proposal.block.deconstruct();
+	let mut params = BlockImportParams::new(BlockOrigin::Own, header.clone());
+	params.body = Some(body);
+	params.finalized = finalize;
+	params.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+	params.state_action =
+		StateAction::ApplyChanges(sc_consensus::StorageChanges::Changes(proposal.storage_changes));
+
+	if let Some(digest_provider) = digest_provider {
+		digest_provider.append_block_import(&parent, &mut params, &inherent_data)?;
+	}
+
+	match block_import.import_block(params, HashMap::new()).await? {
+		ImportResult::Imported(aux) => {
+			*time_offset = next_time_offset;
+			Ok(CreatedBlock { hash: <B as BlockT>::Header::hash(&header), aux })
+		},
+		other => Err(other.into()),
+	}
 }
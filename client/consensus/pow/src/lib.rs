@@ -31,6 +31,13 @@
 //! mining on a standalone thread. Finally, when a seal is found, call
 //! [`MiningHandle::submit`] to build the block.
 //!
+//! Chains can be mined with several algorithms, the algorithm of each block being
+//! selected by a [`POW_ALGORITHM_ENGINE_ID`] pre-runtime digest. Use [`MultiAlgorithm`]
+//! to combine the [`PowAlgorithm`] of each of them, and [`MiningHandle::select_algorithm`]
+//! to choose the algorithm of the blocks built by the mining worker. Each algorithm can be given
+//! a weight, so that the total difficulty of the chain sums comparable amounts of work, and
+//! [`RuntimeDifficulty`] reads its difficulty from the runtime.
+//!
 //! The auxiliary storage for PoW engine only stores the total difficulty.
 //! For other storage requirements for particular PoW algorithm (such as
 //! the actual difficulty for each particular blocks), you can take a client
@@ -39,9 +46,13 @@
 //! as the storage, but it is not recommended as it won't work well with light
 //! clients.
 
+mod multi_algorithm;
 mod worker;
 
-pub use crate::worker::{MiningBuild, MiningHandle, MiningMetadata};
+pub use crate::{
+	multi_algorithm::{MultiAlgorithm, RuntimeDifficulty},
+	worker::{MiningBuild, MiningHandle, MiningMetadata},
+};
 
 use crate::worker::UntilImportedOrTimeout;
 use codec::{Decode, Encode};
@@ -59,7 +70,9 @@ use sp_blockchain::{well_known_cache_keys::Id as CacheKeyId, HeaderBackend};
 use sp_consensus::{
 	CanAuthorWith, Environment, Error as ConsensusError, Proposer, SelectChain, SyncOracle,
 };
-use sp_consensus_pow::{Seal, TotalDifficulty, POW_ENGINE_ID};
+use sp_consensus_pow::{
+	AlgorithmId, Seal, TotalDifficulty, POW_ALGORITHM_ENGINE_ID, POW_ENGINE_ID,
+};
 use sp_core::ExecutionContext;
 use sp_inherents::{CreateInherentDataProviders, InherentDataProvider};
 use sp_runtime::{
@@ -107,6 +120,10 @@ pub enum Error<B: BlockT> {
 	CheckInherentsUnknownError(sp_inherents::InherentIdentifier),
 	#[display(fmt = "Multiple pre-runtime digests")]
 	MultiplePreRuntimeDigests,
+	#[display(fmt = "Multiple algorithm digests")]
+	MultipleAlgorithmDigests,
+	#[display(fmt = "Unknown PoW algorithm {:?}", _0)]
+	UnknownAlgorithm(AlgorithmId),
 	Client(sp_blockchain::Error),
 	Codec(codec::Error),
 	Environment(String),
@@ -206,6 +223,39 @@ pub trait PowAlgorithm<B: BlockT> {
 		seal: &Seal,
 		difficulty: Self::Difficulty,
 	) -> Result<bool, Error<B>>;
+	/// Get the next block's difficulty for blocks mined with `algorithm`.
+	///
+	/// `algorithm` is read from the [`POW_ALGORITHM_ENGINE_ID`] pre-runtime digest of the block,
+	/// and is `None` if there is no such digest. By default it is ignored.
+	fn algorithm_difficulty(
+		&self,
+		parent: B::Hash,
+		_algorithm: Option<AlgorithmId>,
+	) -> Result<Self::Difficulty, Error<B>> {
+		self.difficulty(parent)
+	}
+	/// Verify that the difficulty is valid against given seal, for a block mined with
+	/// `algorithm`. By default `algorithm` is ignored.
+	fn algorithm_verify(
+		&self,
+		parent: &BlockId<B>,
+		pre_hash: &B::Hash,
+		pre_digest: Option<&[u8]>,
+		seal: &Seal,
+		difficulty: Self::Difficulty,
+		_algorithm: Option<AlgorithmId>,
+	) -> Result<bool, Error<B>> {
+		self.verify(parent, pre_hash, pre_digest, seal, difficulty)
+	}
+	/// Get the work done by mining a block with `algorithm` at `difficulty`, which is added to
+	/// the total difficulty of the chain. By default this is the difficulty itself.
+	fn algorithm_work(
+		&self,
+		difficulty: Self::Difficulty,
+		_algorithm: Option<AlgorithmId>,
+	) -> Result<Self::Difficulty, Error<B>> {
+		Ok(difficulty)
+	}
 }
 
 /// A block importer for PoW.
@@ -373,25 +423,29 @@ where
 		let intermediate =
 			block.take_intermediate::<PowIntermediate<Algorithm::Difficulty>>(INTERMEDIATE_KEY)?;
 
+		let algorithm = find_algorithm::<B>(&block.header)?;
+
 		let difficulty = match intermediate.difficulty {
 			Some(difficulty) => difficulty,
-			None => self.algorithm.difficulty(parent_hash)?,
+			None => self.algorithm.algorithm_difficulty(parent_hash, algorithm)?,
 		};
 
 		let pre_hash = block.header.hash();
 		let pre_digest = find_pre_digest::<B>(&block.header)?;
-		if !self.algorithm.verify(
+		if !self.algorithm.algorithm_verify(
 			&BlockId::hash(parent_hash),
 			&pre_hash,
 			pre_digest.as_ref().map(|v| &v[..]),
 			&inner_seal,
 			difficulty,
+			algorithm,
 		)? {
 			return Err(Error::<B>::InvalidSeal.into())
 		}
 
 		aux.difficulty = difficulty;
-		aux.total_difficulty.increment(difficulty);
+		aux.total_difficulty
+			.increment(self.algorithm.algorithm_work(difficulty, algorithm)?);
 
 		let key = aux_key(&block.post_hash());
 		block.auxiliary.push((key, Some(aux.encode())));
@@ -585,7 +639,8 @@ where
 			// The worker is locked for the duration of the whole proposing period. Within this
 			// period, the mining target is outdated and useless anyway.
 
-			let difficulty = match algorithm.difficulty(best_hash) {
+			let algorithm_id = worker.selected_algorithm();
+			let difficulty = match algorithm.algorithm_difficulty(best_hash, algorithm_id) {
 				Ok(x) => x,
 				Err(err) => {
 					warn!(
@@ -631,6 +686,10 @@ where
 			if let Some(pre_runtime) = &pre_runtime {
 				inherent_digest.push(DigestItem::PreRuntime(POW_ENGINE_ID, pre_runtime.to_vec()));
 			}
+			if let Some(algorithm_id) = algorithm_id {
+				inherent_digest
+					.push(DigestItem::PreRuntime(POW_ALGORITHM_ENGINE_ID, algorithm_id.encode()));
+			}
 
 			let pre_runtime = pre_runtime.clone();
 
@@ -669,10 +728,16 @@ where
 					pre_hash: proposal.block.header().hash(),
 					pre_runtime: pre_runtime.clone(),
 					difficulty,
+					algorithm: algorithm_id,
 				},
 				proposal,
 			};
 
+			// Another algorithm was selected while proposing, build again on the next round.
+			if worker.selected_algorithm() != algorithm_id {
+				continue
+			}
+
 			worker.on_build(build);
 		}
 	};
@@ -698,6 +763,23 @@ fn find_pre_digest<B: BlockT>(header: &B::Header) -> Result<Option<Vec<u8>>, Err
 	Ok(pre_digest)
 }
 
+/// Find the mining algorithm selected by the PoW algorithm pre-runtime digest.
+fn find_algorithm<B: BlockT>(header: &B::Header) -> Result<Option<AlgorithmId>, Error<B>> {
+	let mut algorithm: Option<_> = None;
+	for log in header.digest().logs() {
+		match (log, algorithm.is_some()) {
+			(DigestItem::PreRuntime(POW_ALGORITHM_ENGINE_ID, _), true) =>
+				return Err(Error::MultipleAlgorithmDigests),
+			(DigestItem::PreRuntime(POW_ALGORITHM_ENGINE_ID, v), false) => {
+				algorithm = Some(AlgorithmId::decode(&mut &v[..]).map_err(Error::Codec)?);
+			},
+			(_, _) => (),
+		}
+	}
+
+	Ok(algorithm)
+}
+
 /// Fetch PoW seal.
 fn fetch_seal<B: BlockT>(digest: Option<&DigestItem>, hash: B::Hash) -> Result<Vec<u8>, Error<B>> {
 	match digest {
@@ -710,3 +792,41 @@ fn fetch_seal<B: BlockT>(digest: Option<&DigestItem>, hash: B::Hash) -> Result<V
 		_ => return Err(Error::<B>::HeaderUnsealed(hash).into()),
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use substrate_test_runtime_client::runtime::{Block, Header};
+
+	fn header(logs: Vec<DigestItem>) -> Header {
+		Header::new(1, Default::default(), Default::default(), Default::default(), Digest { logs })
+	}
+
+	#[test]
+	fn find_algorithm_reads_the_algorithm_digest() {
+		assert_eq!(find_algorithm::<Block>(&header(vec![])).unwrap(), None);
+
+		let header = header(vec![
+			DigestItem::PreRuntime(POW_ENGINE_ID, vec![1, 2, 3]),
+			DigestItem::PreRuntime(POW_ALGORITHM_ENGINE_ID, b"sha3".encode()),
+		]);
+		assert_eq!(find_algorithm::<Block>(&header).unwrap(), Some(*b"sha3"));
+	}
+
+	#[test]
+	fn find_algorithm_rejects_invalid_digests() {
+		let header_with = |logs| find_algorithm::<Block>(&header(logs));
+
+		assert!(matches!(
+			header_with(vec![
+				DigestItem::PreRuntime(POW_ALGORITHM_ENGINE_ID, b"sha3".encode()),
+				DigestItem::PreRuntime(POW_ALGORITHM_ENGINE_ID, b"sha3".encode()),
+			]),
+			Err(Error::MultipleAlgorithmDigests),
+		));
+		assert!(matches!(
+			header_with(vec![DigestItem::PreRuntime(POW_ALGORITHM_ENGINE_ID, vec![1, 2])]),
+			Err(Error::Codec(_)),
+		));
+	}
+}
@@ -0,0 +1,369 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use codec::{Decode, Encode};
+use sp_api::ProvideRuntimeApi;
+use sp_consensus_pow::{AlgorithmDifficultyApi, AlgorithmId, Seal, TotalDifficulty};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use std::{collections::BTreeMap, marker::PhantomData, ops::Mul, sync::Arc};
+
+use crate::{Error, PowAlgorithm};
+
+type BoxedAlgorithm<B, Difficulty> =
+	Arc<dyn PowAlgorithm<B, Difficulty = Difficulty> + Send + Sync>;
+
+/// A [`PowAlgorithm`] for chains mined with several algorithms, dispatching to the algorithm
+/// selected by the [`POW_ALGORITHM_ENGINE_ID`](sp_consensus_pow::POW_ALGORITHM_ENGINE_ID)
+/// pre-runtime digest of each block. Blocks without such a digest are mined with the default
+/// algorithm, and blocks mined with an unknown algorithm are rejected.
+///
+/// The difficulties of different algorithms are usually not comparable. Each algorithm can be
+/// given a weight, the work of a block being its difficulty multiplied by the weight of its
+/// algorithm. The total difficulty of the chain sums that work.
+pub struct MultiAlgorithm<B: BlockT, Difficulty> {
+	default: AlgorithmId,
+	algorithms: BTreeMap<AlgorithmId, BoxedAlgorithm<B, Difficulty>>,
+	weights: BTreeMap<AlgorithmId, Difficulty>,
+}
+
+impl<B: BlockT, Difficulty> MultiAlgorithm<B, Difficulty> {
+	/// Create a new instance supporting only the `default` algorithm.
+	pub fn new<A>(default: AlgorithmId, algorithm: A) -> Self
+	where
+		A: PowAlgorithm<B, Difficulty = Difficulty> + Send + Sync + 'static,
+	{
+		Self { default, algorithms: BTreeMap::new(), weights: BTreeMap::new() }
+			.with_algorithm(default, algorithm)
+	}
+
+	/// Add support for the algorithm identified by `id`.
+	pub fn with_algorithm<A>(mut self, id: AlgorithmId, algorithm: A) -> Self
+	where
+		A: PowAlgorithm<B, Difficulty = Difficulty> + Send + Sync + 'static,
+	{
+		self.algorithms.insert(id, Arc::new(algorithm));
+		self
+	}
+
+	/// Set the weight of the algorithm identified by `id`. Algorithms without weight have their
+	/// difficulty added as is to the total difficulty.
+	pub fn with_weight(mut self, id: AlgorithmId, weight: Difficulty) -> Self {
+		self.weights.insert(id, weight);
+		self
+	}
+
+	fn algorithm(
+		&self,
+		id: Option<AlgorithmId>,
+	) -> Result<&BoxedAlgorithm<B, Difficulty>, Error<B>> {
+		let id = id.unwrap_or(self.default);
+		self.algorithms.get(&id).ok_or(Error::UnknownAlgorithm(id))
+	}
+}
+
+impl<B: BlockT, Difficulty: Clone> Clone for MultiAlgorithm<B, Difficulty> {
+	fn clone(&self) -> Self {
+		Self {
+			default: self.default,
+			algorithms: self.algorithms.clone(),
+			weights: self.weights.clone(),
+		}
+	}
+}
+
+impl<B, Difficulty> PowAlgorithm<B> for MultiAlgorithm<B, Difficulty>
+where
+	B: BlockT,
+	Difficulty:
+		TotalDifficulty + Default + Encode + Decode + Ord + Clone + Copy + Mul<Output = Difficulty>,
+{
+	type Difficulty = Difficulty;
+
+	fn difficulty(&self, parent: B::Hash) -> Result<Difficulty, Error<B>> {
+		self.algorithm_difficulty(parent, None)
+	}
+
+	fn verify(
+		&self,
+		parent: &BlockId<B>,
+		pre_hash: &B::Hash,
+		pre_digest: Option<&[u8]>,
+		seal: &Seal,
+		difficulty: Difficulty,
+	) -> Result<bool, Error<B>> {
+		self.algorithm_verify(parent, pre_hash, pre_digest, seal, difficulty, None)
+	}
+
+	fn algorithm_difficulty(
+		&self,
+		parent: B::Hash,
+		algorithm: Option<AlgorithmId>,
+	) -> Result<Difficulty, Error<B>> {
+		self.algorithm(algorithm)?.difficulty(parent)
+	}
+
+	fn algorithm_verify(
+		&self,
+		parent: &BlockId<B>,
+		pre_hash: &B::Hash,
+		pre_digest: Option<&[u8]>,
+		seal: &Seal,
+		difficulty: Difficulty,
+		algorithm: Option<AlgorithmId>,
+	) -> Result<bool, Error<B>> {
+		self.algorithm(algorithm)?
+			.verify(parent, pre_hash, pre_digest, seal, difficulty)
+	}
+
+	fn algorithm_work(
+		&self,
+		difficulty: Difficulty,
+		algorithm: Option<AlgorithmId>,
+	) -> Result<Difficulty, Error<B>> {
+		let id = algorithm.unwrap_or(self.default);
+		let work = self.algorithm(Some(id))?.algorithm_work(difficulty, Some(id))?;
+
+		Ok(match self.weights.get(&id) {
+			Some(weight) => work * *weight,
+			None => work,
+		})
+	}
+}
+
+/// A [`PowAlgorithm`] reading the difficulty of the algorithm identified by `id` from the
+/// runtime through [`AlgorithmDifficultyApi`], and verifying seals with the wrapped algorithm.
+/// The difficulty is an [`Error::UnknownAlgorithm`] if the runtime does not support `id`.
+///
+/// This is meant to be added to a [`MultiAlgorithm`] under the same `id`.
+pub struct RuntimeDifficulty<B: BlockT, C, A> {
+	client: Arc<C>,
+	id: AlgorithmId,
+	inner: A,
+	_marker: PhantomData<B>,
+}
+
+impl<B: BlockT, C, A> RuntimeDifficulty<B, C, A> {
+	/// Create a new instance reading the difficulty of `id` from the runtime of `client`.
+	pub fn new(client: Arc<C>, id: AlgorithmId, inner: A) -> Self {
+		Self { client, id, inner, _marker: PhantomData }
+	}
+}
+
+impl<B: BlockT, C, A: Clone> Clone for RuntimeDifficulty<B, C, A> {
+	fn clone(&self) -> Self {
+		Self::new(self.client.clone(), self.id, self.inner.clone())
+	}
+}
+
+impl<B, C, A> PowAlgorithm<B> for RuntimeDifficulty<B, C, A>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B>,
+	C::Api: AlgorithmDifficultyApi<B, A::Difficulty>,
+	A: PowAlgorithm<B>,
+{
+	type Difficulty = A::Difficulty;
+
+	fn difficulty(&self, parent: B::Hash) -> Result<Self::Difficulty, Error<B>> {
+		self.client
+			.runtime_api()
+			.algorithm_difficulty(&BlockId::hash(parent), self.id)
+			.map_err(|e| Error::Client(e.into()))?
+			.ok_or(Error::UnknownAlgorithm(self.id))
+	}
+
+	fn preliminary_verify(
+		&self,
+		pre_hash: &B::Hash,
+		seal: &Seal,
+	) -> Result<Option<bool>, Error<B>> {
+		self.inner.preliminary_verify(pre_hash, seal)
+	}
+
+	fn break_tie(&self, own_seal: &Seal, new_seal: &Seal) -> bool {
+		self.inner.break_tie(own_seal, new_seal)
+	}
+
+	fn verify(
+		&self,
+		parent: &BlockId<B>,
+		pre_hash: &B::Hash,
+		pre_digest: Option<&[u8]>,
+		seal: &Seal,
+		difficulty: Self::Difficulty,
+	) -> Result<bool, Error<B>> {
+		self.inner.verify(parent, pre_hash, pre_digest, seal, difficulty)
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use sp_api::ApiRef;
+	use substrate_test_runtime_client::runtime::{Block, Hash};
+
+	const SHA3: AlgorithmId = *b"sha3";
+	const BLAKE: AlgorithmId = *b"blak";
+	const UNKNOWN: AlgorithmId = *b"none";
+
+	/// Algorithm with a fixed difficulty, accepting only the seal made of its `tag`.
+	#[derive(Clone)]
+	pub(crate) struct Fixed {
+		pub(crate) difficulty: u128,
+		pub(crate) tag: u8,
+	}
+
+	impl PowAlgorithm<Block> for Fixed {
+		type Difficulty = u128;
+
+		fn difficulty(&self, _parent: Hash) -> Result<u128, Error<Block>> {
+			Ok(self.difficulty)
+		}
+
+		fn verify(
+			&self,
+			_parent: &BlockId<Block>,
+			_pre_hash: &Hash,
+			_pre_digest: Option<&[u8]>,
+			seal: &Seal,
+			_difficulty: u128,
+		) -> Result<bool, Error<Block>> {
+			Ok(seal[..] == [self.tag])
+		}
+	}
+
+	fn multi_algorithm() -> MultiAlgorithm<Block, u128> {
+		MultiAlgorithm::new(SHA3, Fixed { difficulty: 100, tag: 1 })
+			.with_algorithm(BLAKE, Fixed { difficulty: 10, tag: 2 })
+	}
+
+	fn verify(
+		algorithm: &impl PowAlgorithm<Block, Difficulty = u128>,
+		seal: u8,
+		id: Option<AlgorithmId>,
+	) -> Result<bool, Error<Block>> {
+		algorithm.algorithm_verify(
+			&BlockId::hash(Default::default()),
+			&Default::default(),
+			None,
+			&vec![seal],
+			0,
+			id,
+		)
+	}
+
+	#[test]
+	fn dispatches_to_the_selected_algorithm() {
+		let algorithm = multi_algorithm();
+		let parent = Hash::default();
+
+		assert_eq!(algorithm.difficulty(parent).unwrap(), 100);
+		assert_eq!(algorithm.algorithm_difficulty(parent, Some(SHA3)).unwrap(), 100);
+		assert_eq!(algorithm.algorithm_difficulty(parent, Some(BLAKE)).unwrap(), 10);
+
+		assert!(verify(&algorithm, 1, None).unwrap());
+		assert!(verify(&algorithm, 1, Some(SHA3)).unwrap());
+		assert!(!verify(&algorithm, 2, Some(SHA3)).unwrap());
+		assert!(verify(&algorithm, 2, Some(BLAKE)).unwrap());
+		assert!(!verify(&algorithm, 1, Some(BLAKE)).unwrap());
+	}
+
+	#[test]
+	fn rejects_unknown_algorithms() {
+		let algorithm = multi_algorithm();
+
+		assert!(matches!(
+			algorithm.algorithm_difficulty(Default::default(), Some(UNKNOWN)),
+			Err(Error::UnknownAlgorithm(UNKNOWN)),
+		));
+		assert!(matches!(
+			verify(&algorithm, 1, Some(UNKNOWN)),
+			Err(Error::UnknownAlgorithm(UNKNOWN)),
+		));
+		assert!(matches!(
+			algorithm.algorithm_work(100, Some(UNKNOWN)),
+			Err(Error::UnknownAlgorithm(UNKNOWN)),
+		));
+	}
+
+	#[test]
+	fn work_is_weighted_by_algorithm() {
+		let algorithm = multi_algorithm();
+		assert_eq!(algorithm.algorithm_work(10, Some(BLAKE)).unwrap(), 10);
+
+		let algorithm = algorithm.with_weight(BLAKE, 10);
+		assert_eq!(algorithm.algorithm_work(10, Some(BLAKE)).unwrap(), 100);
+		assert_eq!(algorithm.algorithm_work(100, Some(SHA3)).unwrap(), 100);
+		assert_eq!(algorithm.algorithm_work(100, None).unwrap(), 100);
+
+		let algorithm = algorithm.with_weight(SHA3, 2);
+		assert_eq!(algorithm.algorithm_work(100, None).unwrap(), 200);
+	}
+
+	struct TestApi;
+
+	impl ProvideRuntimeApi<Block> for TestApi {
+		type Api = RuntimeApi;
+
+		fn runtime_api<'a>(&'a self) -> ApiRef<'a, Self::Api> {
+			RuntimeApi.into()
+		}
+	}
+
+	struct RuntimeApi;
+
+	sp_api::mock_impl_runtime_apis! {
+		impl AlgorithmDifficultyApi<Block, u128> for RuntimeApi {
+			fn algorithm_difficulty(algorithm: AlgorithmId) -> Option<u128> {
+				match algorithm {
+					SHA3 => Some(1_000),
+					BLAKE => Some(20),
+					_ => None,
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn runtime_difficulty_reads_the_runtime() {
+		let client = Arc::new(TestApi);
+		let algorithm = MultiAlgorithm::new(
+			SHA3,
+			RuntimeDifficulty::new(client.clone(), SHA3, Fixed { difficulty: 100, tag: 1 }),
+		)
+		.with_algorithm(
+			BLAKE,
+			RuntimeDifficulty::new(client.clone(), BLAKE, Fixed { difficulty: 10, tag: 2 }),
+		)
+		.with_algorithm(
+			UNKNOWN,
+			RuntimeDifficulty::new(client, UNKNOWN, Fixed { difficulty: 1, tag: 3 }),
+		);
+		let parent = Hash::default();
+
+		assert_eq!(algorithm.difficulty(parent).unwrap(), 1_000);
+		assert_eq!(algorithm.algorithm_difficulty(parent, Some(BLAKE)).unwrap(), 20);
+		assert!(matches!(
+			algorithm.algorithm_difficulty(parent, Some(UNKNOWN)),
+			Err(Error::UnknownAlgorithm(UNKNOWN)),
+		));
+
+		assert!(verify(&algorithm, 2, Some(BLAKE)).unwrap());
+		assert!(!verify(&algorithm, 1, Some(BLAKE)).unwrap());
+	}
+}
@@ -26,6 +26,7 @@ use parking_lot::Mutex;
 use sc_client_api::ImportNotifications;
 use sc_consensus::{BlockImportParams, BoxBlockImport, StateAction, StorageChanges};
 use sp_consensus::{BlockOrigin, Proposal};
+use sp_consensus_pow::AlgorithmId;
 use sp_runtime::{
 	generic::BlockId,
 	traits::{Block as BlockT, Header as HeaderT},
@@ -55,6 +56,9 @@ pub struct MiningMetadata<H, D> {
 	pub pre_runtime: Option<Vec<u8>>,
 	/// Mining target difficulty.
 	pub difficulty: D,
+	/// Algorithm to mine the block with, as selected by its algorithm digest. `None` if the
+	/// block has no algorithm digest.
+	pub algorithm: Option<AlgorithmId>,
 }
 
 /// A build of mining, containing the metadata and the block proposal.
@@ -84,6 +88,7 @@ pub struct MiningHandle<
 > {
 	version: Arc<AtomicUsize>,
 	algorithm: Arc<Algorithm>,
+	selected_algorithm: Arc<Mutex<Option<AlgorithmId>>>,
 	justification_sync_link: Arc<L>,
 	build: Arc<Mutex<Option<MiningBuild<Block, Algorithm, C, Proof>>>>,
 	block_import: Arc<Mutex<BoxBlockImport<Block, sp_api::TransactionFor<C, Block>>>>,
@@ -110,6 +115,7 @@ where
 		Self {
 			version: Arc::new(AtomicUsize::new(0)),
 			algorithm: Arc::new(algorithm),
+			selected_algorithm: Arc::new(Mutex::new(None)),
 			justification_sync_link: Arc::new(justification_sync_link),
 			build: Arc::new(Mutex::new(None)),
 			block_import: Arc::new(Mutex::new(block_import)),
@@ -147,16 +153,38 @@ where
 		self.build.lock().as_ref().map(|b| b.metadata.clone())
 	}
 
+	/// Get the algorithm the next blocks are built for. `None` means that the blocks are built
+	/// without algorithm digest.
+	pub fn selected_algorithm(&self) -> Option<AlgorithmId> {
+		*self.selected_algorithm.lock()
+	}
+
+	/// Select the algorithm to build the next blocks for, recorded in their algorithm digest.
+	/// `None` builds the blocks without algorithm digest.
+	///
+	/// The current build is discarded if it was made for another algorithm, and a new one is
+	/// created on the next round of the mining worker.
+	pub fn select_algorithm(&self, algorithm: Option<AlgorithmId>) {
+		*self.selected_algorithm.lock() = algorithm;
+
+		let mut build = self.build.lock();
+		if matches!(build.as_ref(), Some(b) if b.metadata.algorithm != algorithm) {
+			*build = None;
+			self.increment_version();
+		}
+	}
+
 	/// Submit a mined seal. The seal will be validated again. Returns true if the submission is
 	/// successful.
 	pub async fn submit(&self, seal: Seal) -> bool {
 		if let Some(metadata) = self.metadata() {
-			match self.algorithm.verify(
+			match self.algorithm.algorithm_verify(
 				&BlockId::Hash(metadata.best_hash),
 				&metadata.pre_hash,
 				metadata.pre_runtime.as_ref().map(|v| &v[..]),
 				&seal,
 				metadata.difficulty,
+				metadata.algorithm,
 			) {
 				Ok(true) => (),
 				Ok(false) => {
@@ -258,6 +286,7 @@ where
 		Self {
 			version: self.version.clone(),
 			algorithm: self.algorithm.clone(),
+			selected_algorithm: self.selected_algorithm.clone(),
 			justification_sync_link: self.justification_sync_link.clone(),
 			build: self.build.clone(),
 			block_import: self.block_import.clone(),
@@ -313,3 +342,113 @@ impl<Block: BlockT> Stream for UntilImportedOrTimeout<Block> {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::multi_algorithm::tests::Fixed;
+	use sc_consensus::{BlockCheckParams, BlockImport, ImportResult};
+	use sp_blockchain::well_known_cache_keys::Id as CacheKeyId;
+	use sp_consensus::Error as ConsensusError;
+	use substrate_test_runtime_client::{
+		runtime::{Block, Header},
+		TestClient,
+	};
+
+	type Transaction = sp_api::TransactionFor<TestClient, Block>;
+
+	struct NoImport;
+
+	#[async_trait::async_trait]
+	impl BlockImport<Block> for NoImport {
+		type Error = ConsensusError;
+		type Transaction = Transaction;
+
+		async fn check_block(
+			&mut self,
+			_block: BlockCheckParams<Block>,
+		) -> Result<ImportResult, Self::Error> {
+			Ok(ImportResult::imported(false))
+		}
+
+		async fn import_block(
+			&mut self,
+			_block: BlockImportParams<Block, Self::Transaction>,
+			_cache: HashMap<CacheKeyId, Vec<u8>>,
+		) -> Result<ImportResult, Self::Error> {
+			Ok(ImportResult::imported(false))
+		}
+	}
+
+	fn build(algorithm: Option<AlgorithmId>) -> MiningBuild<Block, Fixed, TestClient, ()> {
+		let header = Header::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+
+		MiningBuild {
+			metadata: MiningMetadata {
+				best_hash: Default::default(),
+				pre_hash: header.hash(),
+				pre_runtime: None,
+				difficulty: 1,
+				algorithm,
+			},
+			proposal: Proposal {
+				block: Block { header, extrinsics: Vec::new() },
+				proof: (),
+				storage_changes: Default::default(),
+			},
+		}
+	}
+
+	fn handle() -> MiningHandle<Block, Fixed, TestClient, (), ()> {
+		MiningHandle::new(Fixed { difficulty: 1, tag: 1 }, Box::new(NoImport), ())
+	}
+
+	#[test]
+	fn selecting_another_algorithm_discards_the_build() {
+		let handle = handle();
+		assert_eq!(handle.selected_algorithm(), None);
+
+		handle.on_build(build(None));
+		let version = handle.version();
+
+		handle.select_algorithm(Some(*b"sha3"));
+		assert_eq!(handle.selected_algorithm(), Some(*b"sha3"));
+		assert!(handle.metadata().is_none());
+		assert!(handle.version() != version);
+
+		handle.on_build(build(Some(*b"sha3")));
+		assert_eq!(handle.metadata().unwrap().algorithm, Some(*b"sha3"));
+
+		handle.select_algorithm(None);
+		assert!(handle.metadata().is_none());
+	}
+
+	#[test]
+	fn selecting_the_same_algorithm_keeps_the_build() {
+		let handle = handle();
+		handle.select_algorithm(Some(*b"sha3"));
+		handle.on_build(build(Some(*b"sha3")));
+		let version = handle.version();
+
+		handle.select_algorithm(Some(*b"sha3"));
+		assert!(handle.version() == version);
+		assert_eq!(handle.metadata().unwrap().algorithm, Some(*b"sha3"));
+	}
+
+	#[test]
+	fn selection_is_shared_by_clones() {
+		let handle = handle();
+		let clone = handle.clone();
+		handle.on_build(build(None));
+
+		clone.select_algorithm(Some(*b"blak"));
+		assert_eq!(handle.selected_algorithm(), Some(*b"blak"));
+		assert!(handle.metadata().is_none());
+	}
+}
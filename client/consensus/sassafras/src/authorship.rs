@@ -0,0 +1,226 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Sassafras slot claiming and ticket generation.
+
+use super::Epoch;
+use codec::Encode;
+use sp_application_crypto::AppKey;
+use sp_consensus_sassafras::{
+	digests::PreDigest, make_slot_transcript_data, make_ticket_transcript_data, AuthorityId,
+	AuthorityIndex, SassafrasAuthorityWeight, Slot, Ticket, TicketAux,
+};
+use sp_consensus_vrf::schnorrkel::{VRFOutput, VRFProof};
+use sp_core::{blake2_256, crypto::Public, U256};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use std::collections::BTreeMap;
+
+/// Get the expected fallback author for the given slot and with given
+/// authorities. This should always assign the slot to some authority unless the
+/// authorities list is empty.
+pub(super) fn fallback_slot_author(
+	slot: Slot,
+	authorities: &[(AuthorityId, SassafrasAuthorityWeight)],
+	randomness: [u8; 32],
+) -> Option<(&AuthorityId, AuthorityIndex)> {
+	if authorities.is_empty() {
+		return None
+	}
+
+	let rand = U256::from((randomness, slot).using_encoded(blake2_256));
+
+	let authorities_len = U256::from(authorities.len());
+	let idx = (rand % authorities_len).as_u32();
+
+	let expected_author = authorities.get(idx as usize).expect(
+		"authorities not empty; index constrained to list length; \
+				this is a valid index; qed",
+	);
+
+	Some((&expected_author.0, idx))
+}
+
+/// Tries to claim the given slot number.
+///
+/// If the slot is assigned to a `ticket` generated by one of our keys for the `epoch`, the slot
+/// is claimed with the proof of the ticket. A slot assigned to a ticket of another authority is
+/// never claimed. A slot without a ticket is claimed if we are its fallback author.
+pub fn claim_slot(
+	slot: Slot,
+	epoch: &Epoch,
+	ticket: Option<Ticket>,
+	keystore: &SyncCryptoStorePtr,
+) -> Option<(PreDigest, AuthorityId)> {
+	let (authority_index, ticket_aux) = match ticket {
+		Some(ticket) => {
+			let (authority_index, ticket_aux) = epoch.tickets_aux.get(&ticket)?;
+			(*authority_index, Some(ticket_aux.clone()))
+		},
+		None => (fallback_slot_author(slot, &epoch.authorities, epoch.randomness)?.1, None),
+	};
+	let authority_id = &epoch.authorities.get(authority_index as usize)?.0;
+
+	let transcript_data = make_slot_transcript_data(&epoch.randomness, slot, epoch.epoch_index);
+	let signature = SyncCryptoStore::sr25519_vrf_sign(
+		&**keystore,
+		AuthorityId::ID,
+		authority_id.as_ref(),
+		transcript_data,
+	)
+	.ok()??;
+
+	let pre_digest = PreDigest {
+		authority_index,
+		slot,
+		vrf_output: VRFOutput(signature.output),
+		vrf_proof: VRFProof(signature.proof),
+		ticket_aux,
+	};
+
+	Some((pre_digest, authority_id.clone()))
+}
+
+/// Generates the tickets of our keys for the given epoch, keeping the ones below the ticket
+/// threshold of the epoch along with the data needed to claim their slots.
+pub fn generate_epoch_tickets(
+	epoch: &Epoch,
+	keystore: &SyncCryptoStorePtr,
+) -> BTreeMap<Ticket, (AuthorityIndex, TicketAux)> {
+	let threshold = epoch.ticket_threshold();
+	let mut tickets = BTreeMap::new();
+
+	for (authority_index, (authority_id, _)) in epoch.authorities.iter().enumerate() {
+		if !SyncCryptoStore::has_keys(&**keystore, &[(authority_id.to_raw_vec(), AuthorityId::ID)])
+		{
+			continue
+		}
+
+		for attempt in 0..epoch.config.attempts_number {
+			let transcript_data =
+				make_ticket_transcript_data(&epoch.randomness, attempt, epoch.epoch_index);
+			let signature = match SyncCryptoStore::sr25519_vrf_sign(
+				&**keystore,
+				AuthorityId::ID,
+				authority_id.as_ref(),
+				transcript_data,
+			) {
+				Ok(Some(signature)) => signature,
+				_ => break,
+			};
+
+			let ticket = signature.output.to_bytes();
+			if sp_consensus_sassafras::check_threshold(&ticket, threshold) {
+				let ticket_aux = TicketAux { attempt, proof: VRFProof(signature.proof) };
+				tickets.insert(ticket, (authority_index as AuthorityIndex, ticket_aux));
+			}
+		}
+	}
+
+	tickets
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sc_keystore::LocalKeystore;
+	use sp_consensus_sassafras::SassafrasEpochConfiguration;
+	use sp_core::{crypto::Pair as _, sr25519::Pair};
+	use std::sync::Arc;
+
+	fn epoch(authorities: Vec<(AuthorityId, SassafrasAuthorityWeight)>) -> Epoch {
+		Epoch {
+			epoch_index: 10,
+			start_slot: 0.into(),
+			duration: 20,
+			authorities,
+			randomness: Default::default(),
+			config: SassafrasEpochConfiguration { redundancy_factor: 1, attempts_number: 8 },
+			tickets_aux: Default::default(),
+		}
+	}
+
+	#[test]
+	fn claim_fallback_slot_works() {
+		let keystore: SyncCryptoStorePtr = Arc::new(LocalKeystore::in_memory());
+		let valid_public_key = SyncCryptoStore::sr25519_generate_new(
+			&*keystore,
+			AuthorityId::ID,
+			Some(sp_core::crypto::DEV_PHRASE),
+		)
+		.unwrap();
+
+		let mut epoch = epoch(vec![
+			(AuthorityId::from(Pair::generate().0.public()), 1),
+			(AuthorityId::from(Pair::generate().0.public()), 1),
+		]);
+		assert!((0..20).all(|slot| claim_slot(slot.into(), &epoch, None, &keystore).is_none()));
+
+		epoch.authorities.push((valid_public_key.into(), 1));
+		let slot = (0..20)
+			.map(Slot::from)
+			.find(|slot| {
+				fallback_slot_author(*slot, &epoch.authorities, epoch.randomness).unwrap().1 == 2
+			})
+			.unwrap();
+		let (pre_digest, author) = claim_slot(slot, &epoch, None, &keystore).unwrap();
+		assert_eq!(author, valid_public_key.into());
+		assert_eq!(pre_digest.authority_index, 2);
+		assert!(pre_digest.ticket_aux.is_none());
+	}
+
+	#[test]
+	fn claim_ticket_slot_works() {
+		let keystore: SyncCryptoStorePtr = Arc::new(LocalKeystore::in_memory());
+		let valid_public_key = SyncCryptoStore::sr25519_generate_new(
+			&*keystore,
+			AuthorityId::ID,
+			Some(sp_core::crypto::DEV_PHRASE),
+		)
+		.unwrap();
+
+		let mut epoch = epoch(vec![
+			(AuthorityId::from(Pair::generate().0.public()), 1),
+			(valid_public_key.into(), 1),
+		]);
+
+		// there are fewer attempts than slots, so every ticket is valid.
+		let tickets = generate_epoch_tickets(&epoch, &keystore);
+		assert_eq!(tickets.len(), 8);
+		assert!(tickets.values().all(|(authority_index, _)| *authority_index == 1));
+
+		let fallback_author =
+			|slot: u64| fallback_slot_author(slot.into(), &epoch.authorities, epoch.randomness);
+		let other_slot = (0..20).find(|slot| fallback_author(*slot).unwrap().1 == 0).unwrap();
+		let our_slot = (0..20).find(|slot| fallback_author(*slot).unwrap().1 == 1).unwrap();
+
+		// the slot of an unknown ticket is never claimed, even if we are its fallback author.
+		let ticket = *tickets.keys().next().unwrap();
+		assert!(claim_slot(other_slot.into(), &epoch, Some(ticket), &keystore).is_none());
+		assert!(claim_slot(our_slot.into(), &epoch, Some(ticket), &keystore).is_none());
+
+		// the slot of our ticket is claimed with its proof, whoever is the fallback author.
+		epoch.tickets_aux = tickets;
+		for slot in [other_slot, our_slot] {
+			let (pre_digest, author) =
+				claim_slot(slot.into(), &epoch, Some(ticket), &keystore).unwrap();
+			assert_eq!(author, valid_public_key.into());
+			assert_eq!(pre_digest.authority_index, 1);
+			assert_eq!(pre_digest.ticket_aux.as_ref(), Some(&epoch.tickets_aux[&ticket].1));
+		}
+	}
+}
@@ -0,0 +1,1588 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! # Sassafras (Semi Anonymous Sortition of Staked Assignees For Fixed-time Rhythmic Assignment
+//! of Slots)
+//!
+//! Sassafras is a slot-based block production mechanism which elects a single leader per slot.
+//! Instead of every authority drawing a lottery on every slot, as done by BABE, the authorities
+//! draw the lottery once per epoch: during epoch `N`, every authority computes a number of VRF
+//! outputs over the randomness of epoch `N + 1`, the *tickets*, and submits on-chain the ones
+//! that fall below a threshold. The runtime keeps the lowest tickets, at most one per slot, and
+//! assigns them to the slots of epoch `N + 1` in a deterministic order.
+//!
+//! When authoring, the node asks the runtime for the ticket assigned to the slot. If it owns
+//! the ticket, it claims the slot by revealing the VRF proof of the ticket in the pre-runtime
+//! digest of the block. Every slot also has a fallback author, the authority at index:
+//!
+//! `blake2_256(epoch_randomness ++ slot_number) % authorities_len`.
+//!
+//! The fallback author only claims the slots without a ticket: a slot assigned to a ticket can
+//! only be claimed by the owner of the ticket, and stays empty if the owner is offline.
+//!
+//! In both cases the author also computes a VRF over the slot, whose output is accumulated
+//! on-chain to build the randomness of the following epochs.
+//!
+//! The fork choice rule is weight-based, where weight equals the number of blocks claimed with
+//! a ticket in the chain. We will pick the heaviest chain and will go with the longest one in
+//! case of a tie.
+//!
+//! The tickets are plain VRF outputs rather than ring-VRF outputs: they are submitted with
+//! their proof and the index of their owner, so that the runtime can reject forged tickets.
+//!
+//! An in-depth description of the protocol can be found here:
+//! <https://research.web3.foundation/en/latest/polkadot/block-production/SASSAFRAS.html>
+
+#![forbid(unsafe_code)]
+#![warn(missing_docs)]
+
+use std::{
+	borrow::Cow,
+	collections::{BTreeMap, HashMap},
+	convert::TryInto,
+	pin::Pin,
+	sync::Arc,
+	time::Duration,
+};
+
+use codec::{Decode, Encode};
+use futures::prelude::*;
+use log::{debug, info, log, trace, warn};
+use prometheus_endpoint::Registry;
+use schnorrkel::SignatureError;
+
+use sc_client_api::{backend::AuxStore, BlockchainEvents, UsageProvider};
+use sc_consensus::{
+	block_import::{
+		BlockCheckParams, BlockImport, BlockImportParams, ForkChoiceStrategy, ImportResult,
+		StateAction,
+	},
+	import_queue::{BasicQueue, BoxJustificationImport, DefaultImportQueue, Verifier},
+};
+use sc_consensus_epochs::{
+	descendent_query, Epoch as EpochT, EpochChangesFor, SharedEpochChanges, ViableEpochDescriptor,
+};
+use sc_consensus_slots::{
+	BackoffAuthoringBlocksStrategy, CheckedHeader, InherentDataProviderExt, SlotInfo,
+	StorageChanges,
+};
+use sc_telemetry::{telemetry, TelemetryHandle, CONSENSUS_DEBUG, CONSENSUS_TRACE};
+use sp_api::{ApiExt, NumberFor, ProvideRuntimeApi};
+use sp_application_crypto::AppKey;
+use sp_block_builder::BlockBuilder as BlockBuilderApi;
+use sp_blockchain::{Error as ClientError, HeaderBackend, HeaderMetadata, Result as ClientResult};
+use sp_consensus::{
+	BlockOrigin, CacheKeyId, CanAuthorWith, Environment, Error as ConsensusError, Proposer,
+	SelectChain, SlotData,
+};
+use sp_consensus_sassafras::inherents::SassafrasInherentData;
+use sp_consensus_slots::Slot;
+use sp_core::{crypto::Public, ExecutionContext};
+use sp_inherents::{CreateInherentDataProviders, InherentData, InherentDataProvider};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_runtime::{
+	generic::{BlockId, OpaqueDigestItemId},
+	traits::{Block as BlockT, Header, Zero},
+	DigestItem,
+};
+
+pub use sc_consensus_slots::SlotProportion;
+pub use sp_consensus::SyncOracle;
+pub use sp_consensus_sassafras::{
+	digests::{CompatibleDigestItem, NextEpochDescriptor, PreDigest},
+	AuthorityId, AuthorityIndex, AuthorityPair, AuthoritySignature, ConsensusLog, Randomness,
+	SassafrasApi, SassafrasAuthorityWeight, SassafrasBlockWeight, SassafrasConfiguration,
+	SassafrasEpochConfiguration, Ticket, TicketAux, TicketEnvelope, SASSAFRAS_ENGINE_ID,
+};
+
+pub use aux_schema::load_block_weight as block_weight;
+
+mod verification;
+
+pub mod authorship;
+pub mod aux_schema;
+
+/// Sassafras epoch information
+#[derive(Decode, Encode, PartialEq, Eq, Clone, Debug)]
+pub struct Epoch {
+	/// The epoch index.
+	pub epoch_index: u64,
+	/// The starting slot of the epoch.
+	pub start_slot: Slot,
+	/// The duration of this epoch.
+	pub duration: u64,
+	/// The authorities and their weights.
+	pub authorities: Vec<(AuthorityId, SassafrasAuthorityWeight)>,
+	/// Randomness for this epoch.
+	pub randomness: Randomness,
+	/// Configuration of the epoch.
+	pub config: SassafrasEpochConfiguration,
+	/// Tickets generated by our keys for this epoch, with the data needed to claim their
+	/// slots. This is local data, filled once the epoch is announced.
+	pub tickets_aux: BTreeMap<Ticket, (AuthorityIndex, TicketAux)>,
+}
+
+impl EpochT for Epoch {
+	type NextEpochDescriptor = NextEpochDescriptor;
+	type Slot = Slot;
+
+	fn increment(&self, descriptor: NextEpochDescriptor) -> Epoch {
+		Epoch {
+			epoch_index: self.epoch_index + 1,
+			start_slot: self.start_slot + self.duration,
+			duration: self.duration,
+			authorities: descriptor.authorities,
+			randomness: descriptor.randomness,
+			config: self.config.clone(),
+			tickets_aux: BTreeMap::new(),
+		}
+	}
+
+	fn start_slot(&self) -> Slot {
+		self.start_slot
+	}
+
+	fn end_slot(&self) -> Slot {
+		self.start_slot + self.duration
+	}
+}
+
+impl From<sp_consensus_sassafras::Epoch> for Epoch {
+	fn from(epoch: sp_consensus_sassafras::Epoch) -> Self {
+		Epoch {
+			epoch_index: epoch.epoch_index,
+			start_slot: epoch.start_slot,
+			duration: epoch.duration,
+			authorities: epoch.authorities,
+			randomness: epoch.randomness,
+			config: epoch.config,
+			tickets_aux: BTreeMap::new(),
+		}
+	}
+}
+
+impl Epoch {
+	/// Create the genesis epoch (epoch #0). This is defined to start at the slot of
+	/// the first block, so that has to be provided.
+	pub fn genesis(config: &SassafrasConfiguration, slot: Slot) -> Epoch {
+		Epoch {
+			epoch_index: 0,
+			start_slot: slot,
+			duration: config.epoch_duration,
+			authorities: config.authorities.clone(),
+			randomness: config.randomness,
+			config: config.threshold_params.clone(),
+			tickets_aux: BTreeMap::new(),
+		}
+	}
+
+	/// The threshold below which the tickets for this epoch are valid.
+	pub fn ticket_threshold(&self) -> u128 {
+		sp_consensus_sassafras::compute_threshold(
+			self.config.redundancy_factor,
+			self.duration,
+			self.config.attempts_number,
+			self.authorities.len() as u32,
+		)
+	}
+}
+
+/// Errors encountered by the sassafras authorship task.
+#[derive(derive_more::Display, Debug)]
+pub enum Error<B: BlockT> {
+	/// Multiple Sassafras pre-runtime digests
+	#[display(fmt = "Multiple Sassafras pre-runtime digests, rejecting!")]
+	MultiplePreRuntimeDigests,
+	/// No Sassafras pre-runtime digest found
+	#[display(fmt = "No Sassafras pre-runtime digest found")]
+	NoPreRuntimeDigest,
+	/// Multiple Sassafras epoch change digests
+	#[display(fmt = "Multiple Sassafras epoch change digests, rejecting!")]
+	MultipleEpochChangeDigests,
+	/// Could not fetch epoch
+	#[display(fmt = "Could not fetch epoch at {:?}", _0)]
+	FetchEpoch(B::Hash),
+	/// Header rejected: too far in the future
+	#[display(fmt = "Header {:?} rejected: too far in the future", _0)]
+	TooFarInFuture(B::Hash),
+	/// Parent unavailable. Cannot import
+	#[display(fmt = "Parent ({}) of {} unavailable. Cannot import", _0, _1)]
+	ParentUnavailable(B::Hash, B::Hash),
+	/// Slot number must increase
+	#[display(fmt = "Slot number must increase: parent slot: {}, this slot: {}", _0, _1)]
+	SlotMustIncrease(Slot, Slot),
+	/// Header has a bad seal
+	#[display(fmt = "Header {:?} has a bad seal", _0)]
+	HeaderBadSeal(B::Hash),
+	/// Header is unsealed
+	#[display(fmt = "Header {:?} is unsealed", _0)]
+	HeaderUnsealed(B::Hash),
+	/// Slot author not found
+	#[display(fmt = "Slot author not found")]
+	SlotAuthorNotFound,
+	/// Bad signature
+	#[display(fmt = "Bad signature on {:?}", _0)]
+	BadSignature(B::Hash),
+	/// Invalid author: Expected fallback author
+	#[display(fmt = "Invalid author: Expected fallback author: {:?}, got: {:?}.", _0, _1)]
+	InvalidAuthor(AuthorityId, AuthorityId),
+	/// Slot not assigned to a ticket claimed with a ticket
+	#[display(fmt = "Slot {} is not assigned to a ticket, but was claimed with one", _0)]
+	UnexpectedTicketClaim(Slot),
+	/// Slot assigned to a ticket claimed without a ticket
+	#[display(fmt = "Slot {} is assigned to a ticket, but was claimed without one", _0)]
+	MissingTicketClaim(Slot),
+	/// Ticket VRF verification failed
+	#[display(fmt = "Ticket VRF verification failed: {:?}", _0)]
+	TicketVerificationFailed(SignatureError),
+	/// VRF verification failed
+	#[display(fmt = "VRF verification failed: {:?}", _0)]
+	VRFVerificationFailed(SignatureError),
+	/// Could not fetch parent header
+	#[display(fmt = "Could not fetch parent header: {:?}", _0)]
+	FetchParentHeader(sp_blockchain::Error),
+	/// Expected epoch change to happen.
+	#[display(fmt = "Expected epoch change to happen at {:?}, s{}", _0, _1)]
+	ExpectedEpochChange(B::Hash, Slot),
+	/// Unexpected epoch change
+	#[display(fmt = "Unexpected epoch change")]
+	UnexpectedEpochChange,
+	/// Parent block has no associated weight
+	#[display(fmt = "Parent block of {} has no associated weight", _0)]
+	ParentBlockNoAssociatedWeight(B::Hash),
+	/// Check inherents error
+	#[display(fmt = "Checking inherents failed: {}", _0)]
+	CheckInherents(sp_inherents::Error),
+	/// Unhandled check inherents error
+	#[display(fmt = "Checking inherents unhandled error: {}", "String::from_utf8_lossy(_0)")]
+	CheckInherentsUnhandled(sp_inherents::InherentIdentifier),
+	/// Create inherents error.
+	#[display(fmt = "Creating inherents failed: {}", _0)]
+	CreateInherents(sp_inherents::Error),
+	/// Client error
+	Client(sp_blockchain::Error),
+	/// Runtime Api error.
+	RuntimeApi(sp_api::ApiError),
+	/// Fork tree error
+	ForkTree(Box<fork_tree::Error<sp_blockchain::Error>>),
+}
+
+impl<B: BlockT> std::convert::From<Error<B>> for String {
+	fn from(error: Error<B>) -> String {
+		error.to_string()
+	}
+}
+
+fn sassafras_err<B: BlockT>(error: Error<B>) -> Error<B> {
+	debug!(target: "sassafras", "{}", error);
+	error
+}
+
+/// Intermediate value passed to block importer.
+pub struct SassafrasIntermediate<B: BlockT> {
+	/// The epoch descriptor.
+	pub epoch_descriptor: ViableEpochDescriptor<B::Hash, NumberFor<B>, Epoch>,
+}
+
+/// Intermediate key for Sassafras engine.
+pub static INTERMEDIATE_KEY: &[u8] = b"sass1";
+
+/// A slot duration. Create with `get_or_compute`.
+#[derive(Clone)]
+pub struct Config(sc_consensus_slots::SlotDuration<SassafrasConfiguration>);
+
+impl Config {
+	/// Either fetch the slot duration from disk or compute it from the genesis
+	/// state.
+	pub fn get_or_compute<B: BlockT, C>(client: &C) -> ClientResult<Self>
+	where
+		C: AuxStore + ProvideRuntimeApi<B> + UsageProvider<B>,
+		C::Api: SassafrasApi<B>,
+	{
+		trace!(target: "sassafras", "Getting slot duration");
+		match sc_consensus_slots::SlotDuration::get_or_compute(client, |a, b| {
+			a.configuration(b).map_err(Into::into)
+		})
+		.map(Self)
+		{
+			Ok(s) => Ok(s),
+			Err(s) => {
+				warn!(target: "sassafras", "Failed to get slot duration");
+				Err(s)
+			},
+		}
+	}
+
+	/// Get the inner slot duration
+	pub fn slot_duration(&self) -> Duration {
+		self.0.slot_duration()
+	}
+}
+
+impl std::ops::Deref for Config {
+	type Target = SassafrasConfiguration;
+
+	fn deref(&self) -> &SassafrasConfiguration {
+		&self.0
+	}
+}
+
+/// Parameters for Sassafras.
+pub struct SassafrasParams<B: BlockT, C, SC, E, I, SO, L, CIDP, BS, CAW> {
+	/// The keystore that manages the keys of the node.
+	pub keystore: SyncCryptoStorePtr,
+
+	/// The client to use
+	pub client: Arc<C>,
+
+	/// The SelectChain Strategy
+	pub select_chain: SC,
+
+	/// The environment we are producing blocks for.
+	pub env: E,
+
+	/// The underlying block-import object to supply our produced blocks to.
+	/// This must be a `SassafrasBlockImport` or a wrapper of it, otherwise
+	/// critical consensus logic will be omitted.
+	pub block_import: I,
+
+	/// A sync oracle
+	pub sync_oracle: SO,
+
+	/// Hook into the sync module to control the justification sync process.
+	pub justification_sync_link: L,
+
+	/// Something that can create the inherent data providers.
+	pub create_inherent_data_providers: CIDP,
+
+	/// Force authoring of blocks even if we are offline
+	pub force_authoring: bool,
+
+	/// Strategy and parameters for backing off block production.
+	pub backoff_authoring_blocks: Option<BS>,
+
+	/// The source of timestamps for relative slots
+	pub sassafras_link: SassafrasLink<B>,
+
+	/// Checks if the current native implementation can author with a runtime at a given block.
+	pub can_author_with: CAW,
+
+	/// The proportion of the slot dedicated to proposing.
+	///
+	/// The block proposing will be limited to this proportion of the slot from the starting of the
+	/// slot. However, the proposing can still take longer when there is some lenience factor
+	/// applied, because there were no blocks produced for some slots.
+	pub block_proposal_slot_portion: SlotProportion,
+
+	/// The maximum proportion of the slot dedicated to proposing with any lenience factor applied
+	/// due to no blocks being produced.
+	pub max_block_proposal_slot_portion: Option<SlotProportion>,
+
+	/// Handle use to report telemetries.
+	pub telemetry: Option<TelemetryHandle>,
+}
+
+/// Start the sassafras worker.
+///
+/// Besides authoring blocks, the worker generates and submits our tickets for the next epoch
+/// whenever a new epoch is announced on the best chain.
+pub fn start_sassafras<B, C, SC, E, I, SO, CIDP, BS, CAW, L, Error>(
+	SassafrasParams {
+		keystore,
+		client,
+		select_chain,
+		env,
+		block_import,
+		sync_oracle,
+		justification_sync_link,
+		create_inherent_data_providers,
+		force_authoring,
+		backoff_authoring_blocks,
+		sassafras_link,
+		can_author_with,
+		block_proposal_slot_portion,
+		max_block_proposal_slot_portion,
+		telemetry,
+	}: SassafrasParams<B, C, SC, E, I, SO, L, CIDP, BS, CAW>,
+) -> Result<SassafrasWorker, sp_consensus::Error>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B>
+		+ BlockchainEvents<B>
+		+ HeaderBackend<B>
+		+ HeaderMetadata<B, Error = ClientError>
+		+ AuxStore
+		+ Send
+		+ Sync
+		+ 'static,
+	C::Api: SassafrasApi<B>,
+	SC: SelectChain<B> + 'static,
+	E: Environment<B, Error = Error> + Send + Sync + 'static,
+	E::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
+	I: BlockImport<B, Error = ConsensusError, Transaction = sp_api::TransactionFor<C, B>>
+		+ Send
+		+ Sync
+		+ 'static,
+	SO: SyncOracle + Send + Sync + Clone + 'static,
+	L: sc_consensus::JustificationSyncLink<B> + 'static,
+	CIDP: CreateInherentDataProviders<B, ()> + Send + Sync + 'static,
+	CIDP::InherentDataProviders: InherentDataProviderExt + Send,
+	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Send + Sync + 'static,
+	CAW: CanAuthorWith<B> + Send + Sync + 'static,
+	Error: std::error::Error + Send + From<ConsensusError> + From<I::Error> + 'static,
+{
+	let config = sassafras_link.config;
+
+	let worker = SassafrasSlotWorker {
+		client: client.clone(),
+		block_import,
+		env,
+		sync_oracle: sync_oracle.clone(),
+		justification_sync_link,
+		force_authoring,
+		backoff_authoring_blocks,
+		keystore: keystore.clone(),
+		epoch_changes: sassafras_link.epoch_changes.clone(),
+		config: config.clone(),
+		block_proposal_slot_portion,
+		max_block_proposal_slot_portion,
+		telemetry,
+	};
+
+	info!(target: "sassafras", "🌳 Starting Sassafras Authorship worker");
+	let inner = sc_consensus_slots::start_slot_worker(
+		config.0.clone(),
+		select_chain,
+		worker,
+		sync_oracle,
+		create_inherent_data_providers,
+		can_author_with,
+	);
+
+	let tickets_worker = tickets_worker(client, keystore, sassafras_link.epoch_changes.clone());
+
+	Ok(SassafrasWorker { inner: Box::pin(future::join(inner, tickets_worker).map(|_| ())) })
+}
+
+/// Generates and submits our tickets for every epoch announced on the best chain.
+///
+/// The tickets are kept in the announced epoch of the epoch changes tree, to be able to claim
+/// the slots they are assigned to.
+async fn tickets_worker<B, C>(
+	client: Arc<C>,
+	keystore: SyncCryptoStorePtr,
+	epoch_changes: SharedEpochChanges<B, Epoch>,
+) where
+	B: BlockT,
+	C: ProvideRuntimeApi<B>
+		+ BlockchainEvents<B>
+		+ HeaderBackend<B>
+		+ HeaderMetadata<B, Error = ClientError>
+		+ AuxStore,
+	C::Api: SassafrasApi<B>,
+{
+	let mut notifications = client.import_notification_stream();
+
+	while let Some(notification) = notifications.next().await {
+		// tickets are only useful for the best chain, and are not submitted for stale epochs.
+		if !notification.is_new_best || notification.origin == BlockOrigin::NetworkInitialSync {
+			continue
+		}
+
+		match find_next_epoch_digest::<B>(&notification.header) {
+			Ok(Some(_)) => {},
+			_ => continue,
+		}
+
+		let hash = notification.hash;
+		let number = *notification.header.number();
+		let slot = match find_pre_digest::<B>(&notification.header) {
+			Ok(pre_digest) => pre_digest.slot,
+			Err(_) => continue,
+		};
+
+		let tickets = {
+			let mut epoch_changes = epoch_changes.shared_data();
+
+			// the epoch announced by this block starts at the end of the current one.
+			let next_epoch_descriptor = epoch_changes
+				.epoch_descriptor_for_child_of(descendent_query(&*client), &hash, number, slot)
+				.ok()
+				.flatten()
+				.and_then(|current| match current {
+					ViableEpochDescriptor::Signaled(_, header) => epoch_changes
+						.epoch_descriptor_for_child_of(
+							descendent_query(&*client),
+							&hash,
+							number,
+							header.end_slot,
+						)
+						.ok()
+						.flatten(),
+					ViableEpochDescriptor::UnimportedGenesis(_) => None,
+				});
+
+			let next_epoch = match next_epoch_descriptor {
+				Some(ViableEpochDescriptor::Signaled(identifier, _)) =>
+					epoch_changes.epoch_mut(&identifier),
+				_ => None,
+			};
+
+			let next_epoch = match next_epoch {
+				Some(epoch) => epoch,
+				None => {
+					warn!(target: "sassafras", "🌳 Unable to find the epoch announced at {:?}", hash);
+					continue
+				},
+			};
+
+			let tickets = authorship::generate_epoch_tickets(next_epoch, &keystore);
+			if tickets.is_empty() {
+				continue
+			}
+
+			debug!(
+				target: "sassafras",
+				"🌳 Generated {} tickets for epoch {}",
+				tickets.len(),
+				next_epoch.epoch_index,
+			);
+
+			let mut envelopes = BTreeMap::<_, Vec<_>>::new();
+			for (ticket, (authority_index, ticket_aux)) in &tickets {
+				envelopes.entry(*authority_index).or_default().push(TicketEnvelope {
+					ticket: *ticket,
+					attempt: ticket_aux.attempt,
+					proof: ticket_aux.proof.to_bytes(),
+				});
+			}
+			next_epoch.tickets_aux.extend(tickets);
+
+			if let Err(e) = aux_schema::write_epoch_changes::<B, _, _>(&*epoch_changes, |insert| {
+				client.insert_aux(insert, [])
+			}) {
+				warn!(target: "sassafras", "🌳 Failed to persist the epoch tickets: {:?}", e);
+			}
+
+			envelopes
+		};
+
+		// the runtime accepts the tickets of each authority in a separate extrinsic.
+		for (authority_index, envelopes) in tickets {
+			match client.runtime_api().submit_tickets_unsigned_extrinsic(
+				&BlockId::Hash(hash),
+				authority_index,
+				envelopes,
+			) {
+				Ok(true) => debug!(target: "sassafras", "🌳 Submitted epoch tickets at {:?}", hash),
+				Ok(false) =>
+					warn!(target: "sassafras", "🌳 Epoch tickets were rejected at {:?}", hash),
+				Err(e) => warn!(target: "sassafras", "🌳 Failed to submit epoch tickets: {:?}", e),
+			}
+		}
+	}
+}
+
+/// Worker for Sassafras which implements `Future<Output=()>`. This must be polled.
+#[must_use]
+pub struct SassafrasWorker {
+	inner: Pin<Box<dyn futures::Future<Output = ()> + Send + 'static>>,
+}
+
+impl futures::Future for SassafrasWorker {
+	type Output = ();
+
+	fn poll(
+		mut self: Pin<&mut Self>,
+		cx: &mut futures::task::Context,
+	) -> futures::task::Poll<Self::Output> {
+		self.inner.as_mut().poll(cx)
+	}
+}
+
+struct SassafrasSlotWorker<B: BlockT, C, E, I, SO, L, BS> {
+	client: Arc<C>,
+	block_import: I,
+	env: E,
+	sync_oracle: SO,
+	justification_sync_link: L,
+	force_authoring: bool,
+	backoff_authoring_blocks: Option<BS>,
+	keystore: SyncCryptoStorePtr,
+	epoch_changes: SharedEpochChanges<B, Epoch>,
+	config: Config,
+	block_proposal_slot_portion: SlotProportion,
+	max_block_proposal_slot_portion: Option<SlotProportion>,
+	telemetry: Option<TelemetryHandle>,
+}
+
+#[async_trait::async_trait]
+impl<B, C, E, I, Error, SO, L, BS> sc_consensus_slots::SimpleSlotWorker<B>
+	for SassafrasSlotWorker<B, C, E, I, SO, L, BS>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B> + HeaderMetadata<B, Error = ClientError>,
+	C::Api: SassafrasApi<B>,
+	E: Environment<B, Error = Error> + Sync,
+	E::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
+	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync + 'static,
+	SO: SyncOracle + Send + Clone + Sync,
+	L: sc_consensus::JustificationSyncLink<B>,
+	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Sync,
+	Error: std::error::Error + Send + From<ConsensusError> + From<I::Error> + 'static,
+{
+	type EpochData = ViableEpochDescriptor<B::Hash, NumberFor<B>, Epoch>;
+	type Claim = (PreDigest, AuthorityId);
+	type SyncOracle = SO;
+	type JustificationSyncLink = L;
+	type CreateProposer =
+		Pin<Box<dyn Future<Output = Result<E::Proposer, sp_consensus::Error>> + Send + 'static>>;
+	type Proposer = E::Proposer;
+	type BlockImport = I;
+
+	fn logging_target(&self) -> &'static str {
+		"sassafras"
+	}
+
+	fn block_import(&mut self) -> &mut Self::BlockImport {
+		&mut self.block_import
+	}
+
+	fn epoch_data(
+		&self,
+		parent: &B::Header,
+		slot: Slot,
+	) -> Result<Self::EpochData, ConsensusError> {
+		self.epoch_changes
+			.shared_data()
+			.epoch_descriptor_for_child_of(
+				descendent_query(&*self.client),
+				&parent.hash(),
+				*parent.number(),
+				slot,
+			)
+			.map_err(|e| ConsensusError::ChainLookup(format!("{:?}", e)))?
+			.ok_or(sp_consensus::Error::InvalidAuthoritiesSet)
+	}
+
+	fn authorities_len(&self, epoch_descriptor: &Self::EpochData) -> Option<usize> {
+		self.epoch_changes
+			.shared_data()
+			.viable_epoch(epoch_descriptor, |slot| Epoch::genesis(&self.config, slot))
+			.map(|epoch| epoch.as_ref().authorities.len())
+	}
+
+	async fn claim_slot(
+		&self,
+		parent_header: &B::Header,
+		slot: Slot,
+		epoch_descriptor: &ViableEpochDescriptor<B::Hash, NumberFor<B>, Epoch>,
+	) -> Option<Self::Claim> {
+		debug!(target: "sassafras", "Attempting to claim slot {}", slot);
+
+		// the ticket assigned to the slot, if any, as known by the runtime.
+		let ticket = self
+			.client
+			.runtime_api()
+			.slot_ticket(&BlockId::Hash(parent_header.hash()), slot)
+			.map_err(|e| warn!(target: "sassafras", "Unable to fetch the slot ticket: {:?}", e))
+			.ok()?;
+
+		let s = authorship::claim_slot(
+			slot,
+			self.epoch_changes
+				.shared_data()
+				.viable_epoch(epoch_descriptor, |slot| Epoch::genesis(&self.config, slot))?
+				.as_ref(),
+			ticket,
+			&self.keystore,
+		);
+
+		if s.is_some() {
+			debug!(target: "sassafras", "Claimed slot {}", slot);
+		}
+
+		s
+	}
+
+	fn pre_digest_data(&self, _slot: Slot, claim: &Self::Claim) -> Vec<sp_runtime::DigestItem> {
+		vec![<DigestItem as CompatibleDigestItem>::sassafras_pre_digest(claim.0.clone())]
+	}
+
+	fn block_import_params(
+		&self,
+	) -> Box<
+		dyn Fn(
+				B::Header,
+				&B::Hash,
+				Vec<B::Extrinsic>,
+				StorageChanges<I::Transaction, B>,
+				Self::Claim,
+				Self::EpochData,
+			)
+				-> Result<sc_consensus::BlockImportParams<B, I::Transaction>, sp_consensus::Error>
+			+ Send
+			+ 'static,
+	> {
+		let keystore = self.keystore.clone();
+		Box::new(
+			move |header, header_hash, body, storage_changes, (_, public), epoch_descriptor| {
+				// sign the pre-sealed hash of the block and then
+				// add it to a digest item.
+				let public_type_pair = public.clone().into();
+				let public = public.to_raw_vec();
+				let signature = SyncCryptoStore::sign_with(
+					&*keystore,
+					<AuthorityId as AppKey>::ID,
+					&public_type_pair,
+					header_hash.as_ref(),
+				)
+				.map_err(|e| sp_consensus::Error::CannotSign(public.clone(), e.to_string()))?
+				.ok_or_else(|| {
+					sp_consensus::Error::CannotSign(
+						public.clone(),
+						"Could not find key in keystore.".into(),
+					)
+				})?;
+				let signature: AuthoritySignature = signature
+					.clone()
+					.try_into()
+					.map_err(|_| sp_consensus::Error::InvalidSignature(signature, public))?;
+				let digest_item = <DigestItem as CompatibleDigestItem>::sassafras_seal(signature);
+
+				let mut import_block = BlockImportParams::new(BlockOrigin::Own, header);
+				import_block.post_digests.push(digest_item);
+				import_block.body = Some(body);
+				import_block.state_action = StateAction::ApplyChanges(
+					sc_consensus::StorageChanges::Changes(storage_changes),
+				);
+				import_block.intermediates.insert(
+					Cow::from(INTERMEDIATE_KEY),
+					Box::new(SassafrasIntermediate::<B> { epoch_descriptor }) as Box<_>,
+				);
+
+				Ok(import_block)
+			},
+		)
+	}
+
+	fn force_authoring(&self) -> bool {
+		self.force_authoring
+	}
+
+	fn should_backoff(&self, slot: Slot, chain_head: &B::Header) -> bool {
+		if let Some(ref strategy) = self.backoff_authoring_blocks {
+			if let Ok(chain_head_slot) = find_slot::<B>(chain_head) {
+				return strategy.should_backoff(
+					*chain_head.number(),
+					chain_head_slot,
+					self.client.info().finalized_number,
+					slot,
+					self.logging_target(),
+				)
+			}
+		}
+		false
+	}
+
+	fn sync_oracle(&mut self) -> &mut Self::SyncOracle {
+		&mut self.sync_oracle
+	}
+
+	fn justification_sync_link(&mut self) -> &mut Self::JustificationSyncLink {
+		&mut self.justification_sync_link
+	}
+
+	fn proposer(&mut self, block: &B::Header) -> Self::CreateProposer {
+		Box::pin(
+			self.env
+				.init(block)
+				.map_err(|e| sp_consensus::Error::ClientImport(format!("{:?}", e))),
+		)
+	}
+
+	fn telemetry(&self) -> Option<TelemetryHandle> {
+		self.telemetry.clone()
+	}
+
+	fn proposing_remaining_duration(&self, slot_info: &SlotInfo<B>) -> std::time::Duration {
+		let parent_slot = find_slot::<B>(&slot_info.chain_head).ok();
+
+		sc_consensus_slots::proposing_remaining_duration(
+			parent_slot,
+			slot_info,
+			&self.block_proposal_slot_portion,
+			self.max_block_proposal_slot_portion.as_ref(),
+			sc_consensus_slots::SlotLenienceType::Exponential,
+			self.logging_target(),
+		)
+	}
+}
+
+/// Extract the Sassafras pre digest from the given header. Pre-runtime digests are
+/// mandatory, the function will return `Err` if none is found.
+///
+/// The genesis block doesn't contain a pre digest, use [`find_slot`] to get the slot of any
+/// block.
+pub fn find_pre_digest<B: BlockT>(header: &B::Header) -> Result<PreDigest, Error<B>> {
+	let mut pre_digest: Option<_> = None;
+	for log in header.digest().logs() {
+		trace!(target: "sassafras", "Checking log {:?}, looking for pre runtime digest", log);
+		match (log.as_sassafras_pre_digest(), pre_digest.is_some()) {
+			(Some(_), true) => return Err(sassafras_err(Error::MultiplePreRuntimeDigests)),
+			(None, _) => trace!(target: "sassafras", "Ignoring digest not meant for us"),
+			(s, false) => pre_digest = s,
+		}
+	}
+	pre_digest.ok_or_else(|| sassafras_err(Error::NoPreRuntimeDigest))
+}
+
+/// Extract the slot of the given header, which is zero for the genesis block.
+pub fn find_slot<B: BlockT>(header: &B::Header) -> Result<Slot, Error<B>> {
+	if header.number().is_zero() {
+		return Ok(0.into())
+	}
+
+	find_pre_digest::<B>(header).map(|pre_digest| pre_digest.slot)
+}
+
+/// Extract the Sassafras epoch change digest from the given header, if it exists.
+fn find_next_epoch_digest<B: BlockT>(
+	header: &B::Header,
+) -> Result<Option<NextEpochDescriptor>, Error<B>> {
+	let mut epoch_digest: Option<_> = None;
+	for log in header.digest().logs() {
+		trace!(target: "sassafras", "Checking log {:?}, looking for epoch change digest.", log);
+		let log = log.try_to::<ConsensusLog>(OpaqueDigestItemId::Consensus(&SASSAFRAS_ENGINE_ID));
+		match (log, epoch_digest.is_some()) {
+			(Some(ConsensusLog::NextEpochData(_)), true) =>
+				return Err(sassafras_err(Error::MultipleEpochChangeDigests)),
+			(Some(ConsensusLog::NextEpochData(epoch)), false) => epoch_digest = Some(epoch),
+			_ => trace!(target: "sassafras", "Ignoring digest not meant for us"),
+		}
+	}
+
+	Ok(epoch_digest)
+}
+
+/// State that must be shared between the import queue and the authoring logic.
+#[derive(Clone)]
+pub struct SassafrasLink<Block: BlockT> {
+	epoch_changes: SharedEpochChanges<Block, Epoch>,
+	config: Config,
+}
+
+impl<Block: BlockT> SassafrasLink<Block> {
+	/// Get the epoch changes of this link.
+	pub fn epoch_changes(&self) -> &SharedEpochChanges<Block, Epoch> {
+		&self.epoch_changes
+	}
+
+	/// Get the config of this link.
+	pub fn config(&self) -> &Config {
+		&self.config
+	}
+}
+
+/// A verifier for Sassafras blocks.
+pub struct SassafrasVerifier<Block: BlockT, Client, CAW, CIDP> {
+	client: Arc<Client>,
+	create_inherent_data_providers: CIDP,
+	config: Config,
+	epoch_changes: SharedEpochChanges<Block, Epoch>,
+	can_author_with: CAW,
+	telemetry: Option<TelemetryHandle>,
+}
+
+impl<Block, Client, CAW, CIDP> SassafrasVerifier<Block, Client, CAW, CIDP>
+where
+	Block: BlockT,
+	Client: AuxStore + HeaderBackend<Block> + HeaderMetadata<Block> + ProvideRuntimeApi<Block>,
+	Client::Api: BlockBuilderApi<Block> + SassafrasApi<Block>,
+	CAW: CanAuthorWith<Block>,
+	CIDP: CreateInherentDataProviders<Block, ()>,
+{
+	async fn check_inherents(
+		&self,
+		block: Block,
+		block_id: BlockId<Block>,
+		inherent_data: InherentData,
+		create_inherent_data_providers: CIDP::InherentDataProviders,
+		execution_context: ExecutionContext,
+	) -> Result<(), Error<Block>> {
+		if let Err(e) = self.can_author_with.can_author_with(&block_id) {
+			debug!(
+				target: "sassafras",
+				"Skipping `check_inherents` as authoring version is not compatible: {}",
+				e,
+			);
+
+			return Ok(())
+		}
+
+		let inherent_res = self
+			.client
+			.runtime_api()
+			.check_inherents_with_context(&block_id, execution_context, block, inherent_data)
+			.map_err(Error::RuntimeApi)?;
+
+		if !inherent_res.ok() {
+			for (i, e) in inherent_res.into_errors() {
+				match create_inherent_data_providers.try_handle_error(&i, &e).await {
+					Some(res) => res.map_err(Error::CheckInherents)?,
+					None => return Err(Error::CheckInherentsUnhandled(i)),
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+type BlockVerificationResult<Block> =
+	Result<(BlockImportParams<Block, ()>, Option<Vec<(CacheKeyId, Vec<u8>)>>), String>;
+
+#[async_trait::async_trait]
+impl<Block, Client, CAW, CIDP> Verifier<Block> for SassafrasVerifier<Block, Client, CAW, CIDP>
+where
+	Block: BlockT,
+	Client: HeaderMetadata<Block, Error = sp_blockchain::Error>
+		+ HeaderBackend<Block>
+		+ ProvideRuntimeApi<Block>
+		+ Send
+		+ Sync
+		+ AuxStore,
+	Client::Api: BlockBuilderApi<Block> + SassafrasApi<Block>,
+	CAW: CanAuthorWith<Block> + Send + Sync,
+	CIDP: CreateInherentDataProviders<Block, ()> + Send + Sync,
+	CIDP::InherentDataProviders: InherentDataProviderExt + Send + Sync,
+{
+	async fn verify(
+		&mut self,
+		mut block: BlockImportParams<Block, ()>,
+	) -> BlockVerificationResult<Block> {
+		trace!(
+			target: "sassafras",
+			"Verifying origin: {:?} header: {:?} justification(s): {:?} body: {:?}",
+			block.origin,
+			block.header,
+			block.justifications,
+			block.body,
+		);
+
+		let hash = block.header.hash();
+		let parent_hash = *block.header.parent_hash();
+
+		if block.with_state() {
+			// When importing whole state we don't calculate epoch descriptor, but rather
+			// read it from the state after import. We also skip all verifications
+			// because there's no parent state and we trust the sync module to verify
+			// that the state is correct and finalized.
+			return Ok((block, Default::default()))
+		}
+
+		debug!(target: "sassafras", "We have {:?} logs in this header", block.header.digest().logs().len());
+
+		let create_inherent_data_providers = self
+			.create_inherent_data_providers
+			.create_inherent_data_providers(parent_hash, ())
+			.await
+			.map_err(|e| Error::<Block>::Client(sp_consensus::Error::from(e).into()))?;
+
+		let slot_now = create_inherent_data_providers.slot();
+
+		let parent_header_metadata = self
+			.client
+			.header_metadata(parent_hash)
+			.map_err(Error::<Block>::FetchParentHeader)?;
+
+		let pre_digest = find_pre_digest::<Block>(&block.header)?;
+
+		// the ticket assigned to the slot of the block, as known by the runtime at its parent.
+		let ticket = self
+			.client
+			.runtime_api()
+			.slot_ticket(&BlockId::Hash(parent_hash), pre_digest.slot)
+			.map_err(Error::<Block>::RuntimeApi)?;
+
+		let (check_header, epoch_descriptor) = {
+			let epoch_changes = self.epoch_changes.shared_data();
+			let epoch_descriptor = epoch_changes
+				.epoch_descriptor_for_child_of(
+					descendent_query(&*self.client),
+					&parent_hash,
+					parent_header_metadata.number,
+					pre_digest.slot,
+				)
+				.map_err(|e| Error::<Block>::ForkTree(Box::new(e)))?
+				.ok_or(Error::<Block>::FetchEpoch(parent_hash))?;
+			let viable_epoch = epoch_changes
+				.viable_epoch(&epoch_descriptor, |slot| Epoch::genesis(&self.config, slot))
+				.ok_or(Error::<Block>::FetchEpoch(parent_hash))?;
+
+			// We add one to the current slot to allow for some small drift.
+			let v_params = verification::VerificationParams {
+				header: block.header.clone(),
+				pre_digest: Some(pre_digest),
+				slot_now: slot_now + 1,
+				epoch: viable_epoch.as_ref(),
+				ticket,
+			};
+
+			(verification::check_header::<Block>(v_params)?, epoch_descriptor)
+		};
+
+		match check_header {
+			CheckedHeader::Checked(pre_header, verified_info) => {
+				let slot = verified_info
+					.pre_digest
+					.as_sassafras_pre_digest()
+					.expect("check_header always returns a pre-digest digest item; qed")
+					.slot;
+
+				// if the body is passed through, we need to use the runtime
+				// to check that the internally-set timestamp in the inherents
+				// actually matches the slot set in the seal.
+				if let Some(inner_body) = block.body {
+					let mut inherent_data = create_inherent_data_providers
+						.create_inherent_data()
+						.map_err(Error::<Block>::CreateInherents)?;
+					inherent_data.sassafras_replace_inherent_data(slot);
+					let new_block = Block::new(pre_header.clone(), inner_body);
+
+					self.check_inherents(
+						new_block.clone(),
+						BlockId::Hash(parent_hash),
+						inherent_data,
+						create_inherent_data_providers,
+						block.origin.into(),
+					)
+					.await?;
+
+					let (_, inner_body) = new_block.deconstruct();
+					block.body = Some(inner_body);
+				}
+
+				trace!(target: "sassafras", "Checked {:?}; importing.", pre_header);
+				telemetry!(
+					self.telemetry;
+					CONSENSUS_TRACE;
+					"sassafras.checked_and_importing";
+					"pre_header" => ?pre_header,
+				);
+
+				block.header = pre_header;
+				block.post_digests.push(verified_info.seal);
+				block.intermediates.insert(
+					Cow::from(INTERMEDIATE_KEY),
+					Box::new(SassafrasIntermediate::<Block> { epoch_descriptor }) as Box<_>,
+				);
+				block.post_hash = Some(hash);
+
+				Ok((block, Default::default()))
+			},
+			CheckedHeader::Deferred(a, b) => {
+				debug!(target: "sassafras", "Checking {:?} failed; {:?}, {:?}.", hash, a, b);
+				telemetry!(
+					self.telemetry;
+					CONSENSUS_DEBUG;
+					"sassafras.header_too_far_in_future";
+					"hash" => ?hash, "a" => ?a, "b" => ?b
+				);
+				Err(Error::<Block>::TooFarInFuture(hash).into())
+			},
+		}
+	}
+}
+
+/// A block-import handler for Sassafras.
+///
+/// This scans each imported block for epoch change signals. The signals are
+/// tracked in a tree (of all forks), and the import logic validates all epoch
+/// change transitions, i.e. whether a given epoch change is expected or whether
+/// it is missing.
+///
+/// The epoch change tree should be pruned as blocks are finalized.
+pub struct SassafrasBlockImport<Block: BlockT, Client, I> {
+	inner: I,
+	client: Arc<Client>,
+	epoch_changes: SharedEpochChanges<Block, Epoch>,
+	config: Config,
+}
+
+impl<Block: BlockT, I: Clone, Client> Clone for SassafrasBlockImport<Block, Client, I> {
+	fn clone(&self) -> Self {
+		SassafrasBlockImport {
+			inner: self.inner.clone(),
+			client: self.client.clone(),
+			epoch_changes: self.epoch_changes.clone(),
+			config: self.config.clone(),
+		}
+	}
+}
+
+impl<Block: BlockT, Client, I> SassafrasBlockImport<Block, Client, I> {
+	fn new(
+		client: Arc<Client>,
+		epoch_changes: SharedEpochChanges<Block, Epoch>,
+		block_import: I,
+		config: Config,
+	) -> Self {
+		SassafrasBlockImport { client, inner: block_import, epoch_changes, config }
+	}
+}
+
+impl<Block, Client, Inner> SassafrasBlockImport<Block, Client, Inner>
+where
+	Block: BlockT,
+	Inner: BlockImport<Block, Transaction = sp_api::TransactionFor<Client, Block>> + Send + Sync,
+	Inner::Error: Into<ConsensusError>,
+	Client: HeaderBackend<Block>
+		+ HeaderMetadata<Block, Error = sp_blockchain::Error>
+		+ AuxStore
+		+ ProvideRuntimeApi<Block>
+		+ Send
+		+ Sync,
+	Client::Api: SassafrasApi<Block> + ApiExt<Block>,
+{
+	/// Import whole state after warp sync.
+	// This function makes multiple transactions to the DB. If one of them fails we may
+	// end up in an inconsistent state and have to resync.
+	async fn import_state(
+		&mut self,
+		mut block: BlockImportParams<Block, sp_api::TransactionFor<Client, Block>>,
+		new_cache: HashMap<CacheKeyId, Vec<u8>>,
+	) -> Result<ImportResult, ConsensusError> {
+		let hash = block.post_hash();
+		let parent_hash = *block.header.parent_hash();
+		let number = *block.header.number();
+
+		block.fork_choice = Some(ForkChoiceStrategy::Custom(true));
+		// Reset block weight.
+		aux_schema::write_block_weight(hash, 0, |values| {
+			block
+				.auxiliary
+				.extend(values.iter().map(|(k, v)| (k.to_vec(), Some(v.to_vec()))))
+		});
+
+		// First make the client import the state.
+		let import_result = self.inner.import_block(block, new_cache).await;
+		let aux = match import_result {
+			Ok(ImportResult::Imported(aux)) => aux,
+			Ok(r) =>
+				return Err(ConsensusError::ClientImport(format!(
+					"Unexpected import result: {:?}",
+					r
+				))),
+			Err(r) => return Err(r.into()),
+		};
+
+		// Read epoch info from the imported state.
+		let block_id = BlockId::hash(hash);
+		let current_epoch = self.client.runtime_api().current_epoch(&block_id).map_err(|e| {
+			ConsensusError::ClientImport(sassafras_err::<Block>(Error::RuntimeApi(e)).into())
+		})?;
+		let next_epoch = self.client.runtime_api().next_epoch(&block_id).map_err(|e| {
+			ConsensusError::ClientImport(sassafras_err::<Block>(Error::RuntimeApi(e)).into())
+		})?;
+
+		let mut epoch_changes = self.epoch_changes.shared_data_locked();
+		epoch_changes.reset(parent_hash, hash, number, current_epoch.into(), next_epoch.into());
+		aux_schema::write_epoch_changes::<Block, _, _>(&*epoch_changes, |insert| {
+			self.client.insert_aux(insert, [])
+		})
+		.map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+
+		Ok(ImportResult::Imported(aux))
+	}
+}
+
+#[async_trait::async_trait]
+impl<Block, Client, Inner> BlockImport<Block> for SassafrasBlockImport<Block, Client, Inner>
+where
+	Block: BlockT,
+	Inner: BlockImport<Block, Transaction = sp_api::TransactionFor<Client, Block>> + Send + Sync,
+	Inner::Error: Into<ConsensusError>,
+	Client: HeaderBackend<Block>
+		+ HeaderMetadata<Block, Error = sp_blockchain::Error>
+		+ AuxStore
+		+ ProvideRuntimeApi<Block>
+		+ Send
+		+ Sync,
+	Client::Api: SassafrasApi<Block> + ApiExt<Block>,
+{
+	type Error = ConsensusError;
+	type Transaction = sp_api::TransactionFor<Client, Block>;
+
+	async fn import_block(
+		&mut self,
+		mut block: BlockImportParams<Block, Self::Transaction>,
+		new_cache: HashMap<CacheKeyId, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		let hash = block.post_hash();
+		let number = *block.header.number();
+
+		// early exit if block already in chain, otherwise the check for
+		// epoch changes will error when trying to re-import an epoch change
+		match self.client.status(BlockId::Hash(hash)) {
+			Ok(sp_blockchain::BlockStatus::InChain) => {
+				// When re-importing existing block strip away intermediates.
+				let _ = block.take_intermediate::<SassafrasIntermediate<Block>>(INTERMEDIATE_KEY);
+				block.fork_choice = Some(ForkChoiceStrategy::Custom(false));
+				return self.inner.import_block(block, new_cache).await.map_err(Into::into)
+			},
+			Ok(sp_blockchain::BlockStatus::Unknown) => {},
+			Err(e) => return Err(ConsensusError::ClientImport(e.to_string())),
+		}
+
+		if block.with_state() {
+			return self.import_state(block, new_cache).await
+		}
+
+		let pre_digest = find_pre_digest::<Block>(&block.header).expect(
+			"valid sassafras headers must contain a predigest; header has been already verified; qed",
+		);
+		let slot = pre_digest.slot;
+
+		let parent_hash = *block.header.parent_hash();
+		let parent_header = self
+			.client
+			.header(BlockId::Hash(parent_hash))
+			.map_err(|e| ConsensusError::ChainLookup(e.to_string()))?
+			.ok_or_else(|| {
+				ConsensusError::ChainLookup(
+					sassafras_err(Error::<Block>::ParentUnavailable(parent_hash, hash)).into(),
+				)
+			})?;
+
+		let parent_slot = find_slot::<Block>(&parent_header).expect(
+			"valid Sassafras headers contain a pre-digest; parent header has already been \
+			 verified; qed",
+		);
+
+		// make sure that slot number is strictly increasing
+		if slot <= parent_slot {
+			return Err(ConsensusError::ClientImport(
+				sassafras_err(Error::<Block>::SlotMustIncrease(parent_slot, slot)).into(),
+			))
+		}
+
+		// if there's a pending epoch we'll save the previous epoch changes here
+		// this way we can revert it if there's any error
+		let mut old_epoch_changes = None;
+
+		// Use an extra scope to make the compiler happy, because otherwise he complains about the
+		// mutex, even if we dropped it...
+		let mut epoch_changes = {
+			let mut epoch_changes = self.epoch_changes.shared_data_locked();
+
+			// check if there's any epoch change expected to happen at this slot.
+			// `epoch` is the epoch to verify the block under, and `first_in_epoch` is true
+			// if this is the first block in its chain for that epoch.
+			//
+			// also provides the total weight of the chain, including the imported block.
+			let (epoch_descriptor, first_in_epoch, parent_weight) = {
+				let parent_weight = if *parent_header.number() == Zero::zero() {
+					0
+				} else {
+					aux_schema::load_block_weight(&*self.client, parent_hash)
+						.map_err(|e| ConsensusError::ClientImport(e.to_string()))?
+						.ok_or_else(|| {
+							ConsensusError::ClientImport(
+								sassafras_err(Error::<Block>::ParentBlockNoAssociatedWeight(hash))
+									.into(),
+							)
+						})?
+				};
+
+				let intermediate =
+					block.take_intermediate::<SassafrasIntermediate<Block>>(INTERMEDIATE_KEY)?;
+
+				let epoch_descriptor = intermediate.epoch_descriptor;
+				let first_in_epoch = parent_slot < epoch_descriptor.start_slot();
+				(epoch_descriptor, first_in_epoch, parent_weight)
+			};
+
+			// only the blocks claimed with a ticket add weight to the chain.
+			let total_weight = parent_weight + pre_digest.is_ticket_claim() as SassafrasBlockWeight;
+
+			// search for this all the time so we can reject unexpected announcements.
+			let next_epoch_digest = find_next_epoch_digest::<Block>(&block.header)
+				.map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+
+			match (first_in_epoch, next_epoch_digest.is_some()) {
+				(true, true) => {},
+				(false, false) => {},
+				(true, false) =>
+					return Err(ConsensusError::ClientImport(
+						sassafras_err(Error::<Block>::ExpectedEpochChange(hash, slot)).into(),
+					)),
+				(false, true) =>
+					return Err(ConsensusError::ClientImport(
+						sassafras_err(Error::<Block>::UnexpectedEpochChange).into(),
+					)),
+			}
+
+			let info = self.client.info();
+
+			if let Some(next_epoch_descriptor) = next_epoch_digest {
+				old_epoch_changes = Some((*epoch_changes).clone());
+
+				let viable_epoch = epoch_changes
+					.viable_epoch(&epoch_descriptor, |slot| Epoch::genesis(&self.config, slot))
+					.ok_or_else(|| {
+						ConsensusError::ClientImport(Error::<Block>::FetchEpoch(parent_hash).into())
+					})?;
+
+				// restrict info logging during initial sync to avoid spam
+				let log_level = if block.origin == BlockOrigin::NetworkInitialSync {
+					log::Level::Debug
+				} else {
+					log::Level::Info
+				};
+
+				log!(target: "sassafras",
+					 log_level,
+					 "🌳 New epoch {} launching at block {} (block slot {} >= start slot {}).",
+					 viable_epoch.as_ref().epoch_index,
+					 hash,
+					 slot,
+					 viable_epoch.as_ref().start_slot,
+				);
+
+				let next_epoch = viable_epoch.increment(next_epoch_descriptor);
+
+				log!(target: "sassafras",
+					 log_level,
+					 "🌳 Next epoch starts at slot {}",
+					 next_epoch.as_ref().start_slot,
+				);
+
+				// prune the tree of epochs not part of the finalized chain or
+				// that are not live anymore, and then track the given epoch change
+				// in the tree.
+				// NOTE: it is important that these operations are done in this
+				// order, otherwise if pruning after import the `is_descendent_of`
+				// used by pruning may not know about the block that is being
+				// imported.
+				let prune_and_import = || {
+					prune_finalized(self.client.clone(), &mut epoch_changes)?;
+
+					epoch_changes
+						.import(
+							descendent_query(&*self.client),
+							hash,
+							number,
+							*block.header.parent_hash(),
+							next_epoch,
+						)
+						.map_err(|e| {
+							ConsensusError::ClientImport(format!(
+								"Error importing epoch changes: {:?}",
+								e
+							))
+						})?;
+					Ok(())
+				};
+
+				if let Err(e) = prune_and_import() {
+					debug!(target: "sassafras", "Failed to launch next epoch: {:?}", e);
+					*epoch_changes =
+						old_epoch_changes.expect("set `Some` above and not taken; qed");
+					return Err(e)
+				}
+
+				aux_schema::write_epoch_changes::<Block, _, _>(&*epoch_changes, |insert| {
+					block
+						.auxiliary
+						.extend(insert.iter().map(|(k, v)| (k.to_vec(), Some(v.to_vec()))))
+				});
+			}
+
+			aux_schema::write_block_weight(hash, total_weight, |values| {
+				block
+					.auxiliary
+					.extend(values.iter().map(|(k, v)| (k.to_vec(), Some(v.to_vec()))))
+			});
+
+			// The fork choice rule is that we pick the heaviest chain (i.e.
+			// more blocks claimed with a ticket), if there's a tie we go with
+			// the longest chain.
+			block.fork_choice = {
+				let (last_best, last_best_number) = (info.best_hash, info.best_number);
+
+				let last_best_weight = if &last_best == block.header.parent_hash() {
+					// the parent=genesis case is already covered for loading parent weight,
+					// so we don't need to cover again here.
+					parent_weight
+				} else {
+					aux_schema::load_block_weight(&*self.client, last_best)
+						.map_err(|e| ConsensusError::ChainLookup(format!("{:?}", e)))?
+						.ok_or_else(|| {
+							ConsensusError::ChainLookup(
+								"No block weight for parent header.".to_string(),
+							)
+						})?
+				};
+
+				Some(ForkChoiceStrategy::Custom(if total_weight > last_best_weight {
+					true
+				} else if total_weight == last_best_weight {
+					number > last_best_number
+				} else {
+					false
+				}))
+			};
+
+			// Release the mutex, but it stays locked
+			epoch_changes.release_mutex()
+		};
+
+		let import_result = self.inner.import_block(block, new_cache).await;
+
+		// revert to the original epoch changes in case there's an error
+		// importing the block
+		if import_result.is_err() {
+			if let Some(old_epoch_changes) = old_epoch_changes {
+				*epoch_changes.upgrade() = old_epoch_changes;
+			}
+		}
+
+		import_result.map_err(Into::into)
+	}
+
+	async fn check_block(
+		&mut self,
+		block: BlockCheckParams<Block>,
+	) -> Result<ImportResult, Self::Error> {
+		self.inner.check_block(block).await.map_err(Into::into)
+	}
+}
+
+/// Gets the best finalized block and its slot, and prunes the given epoch tree.
+fn prune_finalized<Block, Client>(
+	client: Arc<Client>,
+	epoch_changes: &mut EpochChangesFor<Block, Epoch>,
+) -> Result<(), ConsensusError>
+where
+	Block: BlockT,
+	Client: HeaderBackend<Block> + HeaderMetadata<Block, Error = sp_blockchain::Error>,
+{
+	let info = client.info();
+	if info.block_gap.is_none() {
+		epoch_changes.clear_gap();
+	}
+
+	let finalized_slot = {
+		let finalized_header = client
+			.header(BlockId::Hash(info.finalized_hash))
+			.map_err(|e| ConsensusError::ClientImport(format!("{:?}", e)))?
+			.expect(
+				"best finalized hash was given by client; finalized headers must exist in db; qed",
+			);
+
+		find_slot::<Block>(&finalized_header)
+			.expect("finalized header must be valid; valid blocks have a pre-digest; qed")
+	};
+
+	epoch_changes
+		.prune_finalized(
+			descendent_query(&*client),
+			&info.finalized_hash,
+			info.finalized_number,
+			finalized_slot,
+		)
+		.map_err(|e| ConsensusError::ClientImport(format!("{:?}", e)))?;
+
+	Ok(())
+}
+
+/// Produce a Sassafras block-import object to be used later on in the construction of
+/// an import-queue.
+///
+/// Also returns a link object used to correctly instantiate the import queue
+/// and background worker.
+pub fn block_import<Client, Block: BlockT, I>(
+	config: Config,
+	wrapped_block_import: I,
+	client: Arc<Client>,
+) -> ClientResult<(SassafrasBlockImport<Block, Client, I>, SassafrasLink<Block>)>
+where
+	Client: AuxStore + HeaderBackend<Block> + HeaderMetadata<Block, Error = sp_blockchain::Error>,
+{
+	let epoch_changes = aux_schema::load_epoch_changes::<Block, _>(&*client)?;
+	let link = SassafrasLink { epoch_changes: epoch_changes.clone(), config: config.clone() };
+
+	prune_finalized(client.clone(), &mut epoch_changes.shared_data())?;
+
+	let import = SassafrasBlockImport::new(client, epoch_changes, wrapped_block_import, config);
+
+	Ok((import, link))
+}
+
+/// Start an import queue for the Sassafras consensus algorithm.
+///
+/// This method returns the import queue, some data that needs to be passed to the block
+/// authoring logic (`SassafrasLink`), and a future that must be run to
+/// completion and is responsible for listening to finality notifications and
+/// pruning the epoch changes tree.
+///
+/// The block import object provided must be the `SassafrasBlockImport` or a wrapper
+/// of it, otherwise crucial import logic will be omitted.
+pub fn import_queue<Block: BlockT, Client, Inner, CAW, CIDP>(
+	sassafras_link: SassafrasLink<Block>,
+	block_import: Inner,
+	justification_import: Option<BoxJustificationImport<Block>>,
+	client: Arc<Client>,
+	create_inherent_data_providers: CIDP,
+	spawner: &impl sp_core::traits::SpawnEssentialNamed,
+	registry: Option<&Registry>,
+	can_author_with: CAW,
+	telemetry: Option<TelemetryHandle>,
+) -> ClientResult<DefaultImportQueue<Block, Client>>
+where
+	Inner: BlockImport<
+			Block,
+			Error = ConsensusError,
+			Transaction = sp_api::TransactionFor<Client, Block>,
+		> + Send
+		+ Sync
+		+ 'static,
+	Client: ProvideRuntimeApi<Block>
+		+ HeaderBackend<Block>
+		+ HeaderMetadata<Block, Error = sp_blockchain::Error>
+		+ AuxStore
+		+ Send
+		+ Sync
+		+ 'static,
+	Client::Api: BlockBuilderApi<Block> + SassafrasApi<Block> + ApiExt<Block>,
+	CAW: CanAuthorWith<Block> + Send + Sync + 'static,
+	CIDP: CreateInherentDataProviders<Block, ()> + Send + Sync + 'static,
+	CIDP::InherentDataProviders: InherentDataProviderExt + Send + Sync,
+{
+	let verifier = SassafrasVerifier {
+		create_inherent_data_providers,
+		config: sassafras_link.config,
+		epoch_changes: sassafras_link.epoch_changes,
+		can_author_with,
+		telemetry,
+		client,
+	};
+
+	Ok(BasicQueue::new(verifier, Box::new(block_import), justification_import, spawner, registry))
+}
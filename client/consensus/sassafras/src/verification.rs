@@ -0,0 +1,163 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Verification for Sassafras headers.
+use super::{
+	authorship::fallback_slot_author, find_pre_digest, sassafras_err, BlockT, Epoch, Error,
+};
+use log::{debug, trace};
+use sc_consensus_slots::CheckedHeader;
+use sp_consensus_sassafras::{
+	digests::{CompatibleDigestItem, PreDigest},
+	make_slot_transcript, make_ticket_transcript, AuthorityId, AuthorityPair, Ticket, TicketAux,
+};
+use sp_consensus_slots::Slot;
+use sp_core::{Pair, Public};
+use sp_runtime::{traits::Header, DigestItem};
+
+/// Sassafras verification parameters
+pub(super) struct VerificationParams<'a, B: 'a + BlockT> {
+	/// The header being verified.
+	pub(super) header: B::Header,
+	/// The pre-digest of the header being verified. this is optional - if prior
+	/// verification code had to read it, it can be included here to avoid duplicate
+	/// work.
+	pub(super) pre_digest: Option<PreDigest>,
+	/// The slot number of the current time.
+	pub(super) slot_now: Slot,
+	/// Epoch descriptor of the epoch this block _should_ be under, if it's valid.
+	pub(super) epoch: &'a Epoch,
+	/// The ticket the slot of the header is assigned to, as known by the runtime at the
+	/// parent block.
+	pub(super) ticket: Option<Ticket>,
+}
+
+/// Check a header has been signed by the right key. If the slot is too far in
+/// the future, an error will be returned. If successful, returns the pre-header
+/// and the digest item containing the seal.
+///
+/// The seal must be the last digest.  Otherwise, the whole header is considered
+/// unsigned.  This is required for security and must not be changed.
+///
+/// This digest item will always return `Some` when used with `as_sassafras_pre_digest`.
+///
+/// A slot assigned to a ticket must be claimed with this ticket, and a slot without a ticket
+/// must be claimed by its fallback author.
+pub(super) fn check_header<B: BlockT + Sized>(
+	params: VerificationParams<B>,
+) -> Result<CheckedHeader<B::Header, VerifiedHeaderInfo>, Error<B>> {
+	let VerificationParams { mut header, pre_digest, slot_now, epoch, ticket } = params;
+
+	let pre_digest = pre_digest.map(Ok).unwrap_or_else(|| find_pre_digest::<B>(&header))?;
+
+	trace!(target: "sassafras", "Checking header");
+	let seal = header
+		.digest_mut()
+		.pop()
+		.ok_or_else(|| sassafras_err(Error::HeaderUnsealed(header.hash())))?;
+
+	let sig = seal
+		.as_sassafras_seal()
+		.ok_or_else(|| sassafras_err(Error::HeaderBadSeal(header.hash())))?;
+
+	// the pre-hash of the header doesn't include the seal
+	// and that's what we sign
+	let pre_hash = header.hash();
+
+	if pre_digest.slot > slot_now {
+		header.digest_mut().push(seal);
+		return Ok(CheckedHeader::Deferred(header, pre_digest.slot))
+	}
+
+	let author = match epoch.authorities.get(pre_digest.authority_index as usize) {
+		Some(author) => author.0.clone(),
+		None => return Err(sassafras_err(Error::SlotAuthorNotFound)),
+	};
+
+	if !AuthorityPair::verify(&sig, pre_hash, &author) {
+		return Err(sassafras_err(Error::BadSignature(pre_hash)))
+	}
+
+	match (ticket, &pre_digest.ticket_aux) {
+		(Some(ticket), Some(ticket_aux)) => {
+			debug!(target: "sassafras",
+				"Verifying ticket block #{} at slot: {}",
+				header.number(),
+				pre_digest.slot,
+			);
+
+			check_ticket::<B>(&ticket, ticket_aux, &author, epoch)?;
+		},
+		(None, None) => {
+			debug!(target: "sassafras",
+				"Verifying fallback block #{} at slot: {}",
+				header.number(),
+				pre_digest.slot,
+			);
+
+			let (expected_author, _) =
+				fallback_slot_author(pre_digest.slot, &epoch.authorities, epoch.randomness)
+					.ok_or_else(|| sassafras_err(Error::SlotAuthorNotFound))?;
+
+			if expected_author != &author {
+				return Err(sassafras_err(Error::InvalidAuthor(expected_author.clone(), author)))
+			}
+		},
+		(Some(_), None) => return Err(sassafras_err(Error::MissingTicketClaim(pre_digest.slot))),
+		(None, Some(_)) => return Err(sassafras_err(Error::UnexpectedTicketClaim(pre_digest.slot))),
+	}
+
+	let transcript = make_slot_transcript(&epoch.randomness, pre_digest.slot, epoch.epoch_index);
+	schnorrkel::PublicKey::from_bytes(author.as_slice())
+		.and_then(|p| p.vrf_verify(transcript, &pre_digest.vrf_output, &pre_digest.vrf_proof))
+		.map_err(|s| sassafras_err(Error::VRFVerificationFailed(s)))?;
+
+	let info = VerifiedHeaderInfo {
+		pre_digest: CompatibleDigestItem::sassafras_pre_digest(pre_digest),
+		seal,
+	};
+	Ok(CheckedHeader::Checked(header, info))
+}
+
+pub(super) struct VerifiedHeaderInfo {
+	pub(super) pre_digest: DigestItem,
+	pub(super) seal: DigestItem,
+}
+
+/// Check that `ticket` is the output of the ticket VRF of `author` for the epoch.
+fn check_ticket<B: BlockT>(
+	ticket: &Ticket,
+	ticket_aux: &TicketAux,
+	author: &AuthorityId,
+	epoch: &Epoch,
+) -> Result<(), Error<B>> {
+	let transcript =
+		make_ticket_transcript(&epoch.randomness, ticket_aux.attempt, epoch.epoch_index);
+
+	schnorrkel::vrf::VRFOutput::from_bytes(ticket)
+		.and_then(|output| {
+			schnorrkel::PublicKey::from_bytes(author.as_slice())?.vrf_verify(
+				transcript,
+				&output,
+				&ticket_aux.proof,
+			)
+		})
+		.map_err(|s| sassafras_err(Error::TicketVerificationFailed(s)))?;
+
+	Ok(())
+}
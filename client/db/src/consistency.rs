@@ -0,0 +1,918 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Database consistency checks.
+
+use crate::{
+	children, columns,
+	utils::{self, meta_keys},
+	Backend, DbState, StorageDb,
+};
+use codec::Decode;
+use hash_db::{HashDBRef, Prefix};
+use sc_client_api::leaves::LeafSet;
+use sp_blockchain::{Backend as _, HeaderBackend, Info, Result as ClientResult};
+use sp_core::storage::{well_known_keys, ChildInfo};
+use sp_database::Transaction;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{
+		Block as BlockT, HashFor, Header as HeaderT, NumberFor, One, SaturatedConversion, Zero,
+	},
+};
+use sp_state_machine::DBValue;
+use sp_trie::{prefixed_key, KeySpacedDB, LayoutV1, TrieLayout};
+use std::{
+	collections::{HashMap, HashSet},
+	sync::Arc,
+};
+use trie_db::{
+	node::{decode_hash, Node, NodeHandle, Value},
+	NibbleVec, NodeCodec as _,
+};
+
+/// Trie layout used to read the state. Both state versions decode the same way.
+type Layout<Block> = LayoutV1<HashFor<Block>>;
+
+/// State storage reading the database rather than the trie cache.
+struct UncachedStorage<Block: BlockT>(Arc<StorageDb<Block>>);
+
+impl<Block: BlockT> sp_state_machine::Storage<HashFor<Block>> for UncachedStorage<Block> {
+	fn get(&self, key: &Block::Hash, prefix: Prefix) -> Result<Option<DBValue>, String> {
+		self.0.get_uncached(key, prefix)
+	}
+}
+
+/// A problem found by [`Backend::check_consistency`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inconsistency<Block: BlockT> {
+	/// A meta entry is missing or does not match the canonical chain.
+	InvalidMeta(&'static str),
+	/// There is no canonical block with the given number.
+	MissingCanonicalBlock(NumberFor<Block>),
+	/// The header of a block is missing.
+	MissingHeader(Block::Hash),
+	/// An entry of the given column can't be decoded.
+	CorruptedEntry {
+		/// Column name.
+		column: &'static str,
+		/// Block the entry belongs to.
+		block: Block::Hash,
+		/// Decoding error.
+		error: String,
+	},
+	/// A canonical block can't be looked up by hash.
+	MissingHashLookup(Block::Hash),
+	/// A canonical block is not a child of the previous canonical block.
+	BrokenParentLink {
+		/// Block number.
+		number: NumberFor<Block>,
+		/// Block hash.
+		hash: Block::Hash,
+	},
+	/// The body of a block that is not finalized yet is missing.
+	MissingBody(Block::Hash),
+	/// A trie node reachable from the state root of the block is missing.
+	MissingTrieNode {
+		/// Block whose state was checked.
+		block: Block::Hash,
+		/// Hash of the missing node.
+		node: Block::Hash,
+	},
+	/// The leaf set contains a block that is unknown or has children.
+	DanglingLeaf(Block::Hash),
+	/// A block without children is missing from the leaf set.
+	MissingLeaf(Block::Hash),
+	/// The children index lists a block that is unknown or has another parent.
+	DanglingChild {
+		/// Parent block.
+		parent: Block::Hash,
+		/// Listed child.
+		child: Block::Hash,
+	},
+	/// The children index of a block does not list one of its children.
+	MissingChild {
+		/// Parent block.
+		parent: Block::Hash,
+		/// Unlisted child.
+		child: Block::Hash,
+	},
+	/// The state-db journal contains a block that is unknown.
+	DanglingJournalEntry {
+		/// Block number.
+		number: u64,
+		/// Block hash.
+		hash: Block::Hash,
+	},
+}
+
+impl<Block: BlockT> Inconsistency<Block> {
+	/// Returns `true` if the inconsistency is fixed by rebuilding the leaves and children indexes.
+	pub fn is_repairable(&self) -> bool {
+		matches!(
+			self,
+			Inconsistency::DanglingLeaf(_) |
+				Inconsistency::MissingLeaf(_) |
+				Inconsistency::DanglingChild { .. } |
+				Inconsistency::MissingChild { .. }
+		)
+	}
+}
+
+impl<Block: BlockT> std::fmt::Display for Inconsistency<Block> {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Inconsistency::InvalidMeta(entry) => write!(f, "Invalid {} meta entry", entry),
+			Inconsistency::MissingCanonicalBlock(number) =>
+				write!(f, "Missing canonical block #{}", number),
+			Inconsistency::MissingHeader(hash) => write!(f, "Missing header of {}", hash),
+			Inconsistency::CorruptedEntry { column, block, error } =>
+				write!(f, "Corrupted {} entry of {}: {}", column, block, error),
+			Inconsistency::MissingHashLookup(hash) =>
+				write!(f, "Missing lookup key of canonical block {}", hash),
+			Inconsistency::BrokenParentLink { number, hash } =>
+				write!(f, "Canonical block #{} ({}) has a non-canonical parent", number, hash),
+			Inconsistency::MissingBody(hash) => write!(f, "Missing body of {}", hash),
+			Inconsistency::MissingTrieNode { block, node } =>
+				write!(f, "Missing trie node {} in the state of {}", node, block),
+			Inconsistency::DanglingLeaf(hash) => write!(f, "Dangling leaf {}", hash),
+			Inconsistency::MissingLeaf(hash) => write!(f, "Missing leaf {}", hash),
+			Inconsistency::DanglingChild { parent, child } =>
+				write!(f, "Dangling child {} of {}", child, parent),
+			Inconsistency::MissingChild { parent, child } =>
+				write!(f, "Missing child {} of {}", child, parent),
+			Inconsistency::DanglingJournalEntry { number, hash } =>
+				write!(f, "Dangling state-db journal entry #{} ({})", number, hash),
+		}
+	}
+}
+
+/// Result of [`Backend::check_consistency`].
+#[derive(Debug)]
+pub struct ConsistencyReport<Block: BlockT> {
+	/// Number of canonical blocks that were checked.
+	pub canonical_blocks: u64,
+	/// Number of blocks in the leaves and children indexes that were checked.
+	pub indexed_blocks: u64,
+	/// Number of state keys that were checked. Keys shared by several states are only counted
+	/// once.
+	pub state_keys: u64,
+	/// Problems that were found.
+	pub inconsistencies: Vec<Inconsistency<Block>>,
+	/// Whether the leaves and children indexes were rebuilt.
+	pub repaired: bool,
+}
+
+impl<Block: BlockT> ConsistencyReport<Block> {
+	fn push(&mut self, inconsistency: Inconsistency<Block>) {
+		self.inconsistencies.push(inconsistency);
+	}
+}
+
+/// Leaves and children indexes rebuilt from the block headers.
+struct BlockIndexes<Block: BlockT> {
+	leaves: Vec<(Block::Hash, NumberFor<Block>, Block::Hash)>,
+	children: HashMap<Block::Hash, Vec<Block::Hash>>,
+}
+
+impl<Block: BlockT> Backend<Block> {
+	/// Check the database for inconsistencies.
+	///
+	/// This walks the canonical chain, the leaves and children indexes of blocks that are not
+	/// finalized, the state-db journal and every state kept by the state pruning: the states of
+	/// the canonical blocks in the pruning window and of the blocks that are not finalized.
+	/// When `repair` is set, the leaves and children indexes are rebuilt from the block headers.
+	pub fn check_consistency(&self, repair: bool) -> ClientResult<ConsistencyReport<Block>> {
+		let _import_lock = self.import_lock.write();
+		let info = self.blockchain.info();
+		let mut report = ConsistencyReport {
+			canonical_blocks: 0,
+			indexed_blocks: 0,
+			state_keys: 0,
+			inconsistencies: Vec::new(),
+			repaired: false,
+		};
+
+		self.check_meta(&info, &mut report)?;
+		self.check_canonical_chain(&info, &mut report)?;
+		let indexes = self.check_block_indexes(&info, &mut report)?;
+		self.check_journal(&info, &mut report);
+		self.check_states(&info, &mut report)?;
+
+		if repair && report.inconsistencies.iter().any(Inconsistency::is_repairable) {
+			self.write_block_indexes(indexes)?;
+			report.repaired = true;
+		}
+		Ok(report)
+	}
+
+	fn check_meta(
+		&self,
+		info: &Info<Block>,
+		report: &mut ConsistencyReport<Block>,
+	) -> ClientResult<()> {
+		let db = &*self.storage.db;
+		let has_header = |key: &[u8]| {
+			matches!(db.get(columns::META, key), Some(lookup_key)
+				if db.get(columns::HEADER, &lookup_key).is_some())
+		};
+
+		if !has_header(meta_keys::BEST_BLOCK) ||
+			self.blockchain.hash(info.best_number)? != Some(info.best_hash)
+		{
+			report.push(Inconsistency::InvalidMeta("best block"));
+		}
+		if !has_header(meta_keys::FINALIZED_BLOCK) ||
+			info.finalized_number > info.best_number ||
+			self.blockchain.hash(info.finalized_number)? != Some(info.finalized_hash)
+		{
+			report.push(Inconsistency::InvalidMeta("finalized block"));
+		}
+		if db.get(columns::META, meta_keys::FINALIZED_STATE).is_some() &&
+			!has_header(meta_keys::FINALIZED_STATE)
+		{
+			report.push(Inconsistency::InvalidMeta("finalized state"));
+		}
+		if self.blockchain.hash(Zero::zero())? != Some(info.genesis_hash) {
+			report.push(Inconsistency::InvalidMeta("genesis hash"));
+		}
+		Ok(())
+	}
+
+	fn check_canonical_chain(
+		&self,
+		info: &Info<Block>,
+		report: &mut ConsistencyReport<Block>,
+	) -> ClientResult<()> {
+		let mut parent_hash = None;
+		let mut number = Zero::zero();
+		while number <= info.best_number {
+			match info.block_gap {
+				// Headers in the gap are not downloaded yet.
+				Some((start, end)) if number >= start && number <= end => {
+					parent_hash = None;
+					number = end + One::one();
+				},
+				_ => {
+					parent_hash = self.check_canonical_block(number, parent_hash, info, report)?;
+					number += One::one();
+				},
+			}
+		}
+		Ok(())
+	}
+
+	fn check_canonical_block(
+		&self,
+		number: NumberFor<Block>,
+		parent_hash: Option<Block::Hash>,
+		info: &Info<Block>,
+		report: &mut ConsistencyReport<Block>,
+	) -> ClientResult<Option<Block::Hash>> {
+		let db = &*self.storage.db;
+		report.canonical_blocks += 1;
+
+		let lookup_key =
+			match db.get(columns::KEY_LOOKUP, utils::number_index_key(number)?.as_ref()) {
+				Some(lookup_key) => lookup_key,
+				None => {
+					report.push(Inconsistency::MissingCanonicalBlock(number));
+					return Ok(None)
+				},
+			};
+		let header = match db.get(columns::HEADER, &lookup_key) {
+			Some(header) => header,
+			None => {
+				report.push(Inconsistency::MissingHeader(lookup_key_hash::<Block>(&lookup_key)));
+				return Ok(None)
+			},
+		};
+		let header = match Block::Header::decode(&mut &header[..]) {
+			Ok(header) => header,
+			Err(error) => {
+				report.push(Inconsistency::CorruptedEntry {
+					column: "header",
+					block: lookup_key_hash::<Block>(&lookup_key),
+					error: error.to_string(),
+				});
+				return Ok(None)
+			},
+		};
+
+		let hash = header.hash();
+		if db.get(columns::KEY_LOOKUP, hash.as_ref()).as_ref() != Some(&lookup_key) {
+			report.push(Inconsistency::MissingHashLookup(hash));
+		}
+		if matches!(parent_hash, Some(parent_hash) if parent_hash != *header.parent_hash()) {
+			report.push(Inconsistency::BrokenParentLink { number, hash });
+		}
+
+		let id = BlockId::Number(number);
+		match self.blockchain.body(id) {
+			Ok(None) if number > info.finalized_number =>
+				report.push(Inconsistency::MissingBody(hash)),
+			Ok(_) => {},
+			Err(error) => report.push(Inconsistency::CorruptedEntry {
+				column: "body",
+				block: hash,
+				error: error.to_string(),
+			}),
+		}
+		if let Err(error) = self.blockchain.justifications(id) {
+			report.push(Inconsistency::CorruptedEntry {
+				column: "justifications",
+				block: hash,
+				error: error.to_string(),
+			});
+		}
+		Ok(Some(hash))
+	}
+
+	/// Check the leaves and children indexes against the block headers and return the rebuilt
+	/// indexes.
+	///
+	/// The checked blocks are the descendants of the last finalized block and the blocks of
+	/// forks that still have a leaf.
+	fn check_block_indexes(
+		&self,
+		info: &Info<Block>,
+		report: &mut ConsistencyReport<Block>,
+	) -> ClientResult<BlockIndexes<Block>> {
+		// Number and parent hash of every known block.
+		let mut blocks = HashMap::new();
+		let mut header = |hash: Block::Hash, report: &mut ConsistencyReport<Block>| match self
+			.blockchain
+			.header(BlockId::Hash(hash))
+		{
+			Ok(Some(header)) => {
+				let entry = (*header.number(), *header.parent_hash());
+				blocks.insert(hash, entry);
+				Some(entry)
+			},
+			Ok(None) => None,
+			Err(error) => {
+				report.push(Inconsistency::CorruptedEntry {
+					column: "header",
+					block: hash,
+					error: error.to_string(),
+				});
+				None
+			},
+		};
+
+		// Walk every leaf back to the finalized chain.
+		let stored_leaves = self.blockchain.leaves.read().hashes();
+		let mut queue = vec![info.finalized_hash];
+		for leaf in &stored_leaves {
+			let mut hash = *leaf;
+			while let Some((number, parent_hash)) = header(hash, report) {
+				queue.push(hash);
+				if number <= info.finalized_number {
+					break
+				}
+				hash = parent_hash;
+			}
+		}
+
+		// Collect all the descendants of these blocks through the children index.
+		let mut children = HashMap::<Block::Hash, Vec<Block::Hash>>::new();
+		while let Some(parent) = queue.pop() {
+			if children.contains_key(&parent) {
+				continue
+			}
+			let stored: Vec<Block::Hash> = match children::read_children(
+				&*self.storage.db,
+				columns::META,
+				meta_keys::CHILDREN_PREFIX,
+				parent,
+			) {
+				Ok(stored) => stored,
+				Err(error) => {
+					report.push(Inconsistency::CorruptedEntry {
+						column: "children",
+						block: parent,
+						error: error.to_string(),
+					});
+					Vec::new()
+				},
+			};
+			let mut valid = Vec::with_capacity(stored.len());
+			for child in stored {
+				match header(child, report) {
+					Some((_, parent_hash)) if parent_hash == parent => {
+						queue.push(child);
+						valid.push(child);
+					},
+					_ => report.push(Inconsistency::DanglingChild { parent, child }),
+				}
+			}
+			children.insert(parent, valid);
+		}
+		report.indexed_blocks = blocks.len() as u64;
+
+		// Every block whose parent index was read must be listed by it.
+		let mut has_children = HashSet::new();
+		for (hash, (_, parent_hash)) in &blocks {
+			if let Some(siblings) = children.get_mut(parent_hash) {
+				has_children.insert(*parent_hash);
+				if !siblings.contains(hash) {
+					report.push(Inconsistency::MissingChild { parent: *parent_hash, child: *hash });
+					siblings.push(*hash);
+				}
+			}
+		}
+
+		// Leaves are blocks without children. Leaves at the finalized height are only displaced
+		// by the next finalization.
+		let mut leaves = Vec::new();
+		for leaf in &stored_leaves {
+			match blocks.get(leaf) {
+				Some((number, parent_hash))
+					if *number >= info.finalized_number && !has_children.contains(leaf) =>
+					leaves.push((*leaf, *number, *parent_hash)),
+				_ => report.push(Inconsistency::DanglingLeaf(*leaf)),
+			}
+		}
+		for hash in children.keys() {
+			let (number, parent_hash) = blocks[hash];
+			if number >= info.finalized_number &&
+				!has_children.contains(hash) &&
+				!stored_leaves.contains(hash)
+			{
+				report.push(Inconsistency::MissingLeaf(*hash));
+				leaves.push((*hash, number, parent_hash));
+			}
+		}
+
+		Ok(BlockIndexes { leaves, children })
+	}
+
+	fn write_block_indexes(&self, mut indexes: BlockIndexes<Block>) -> ClientResult<()> {
+		let mut transaction = Transaction::new();
+		for (parent, children) in indexes.children {
+			if children.is_empty() {
+				children::remove_children(
+					&mut transaction,
+					columns::META,
+					meta_keys::CHILDREN_PREFIX,
+					parent,
+				);
+			} else {
+				children::write_children(
+					&mut transaction,
+					columns::META,
+					meta_keys::CHILDREN_PREFIX,
+					parent,
+					children,
+				);
+			}
+		}
+
+		let mut leaves = self.blockchain.leaves.write();
+		let mut new_leaves = LeafSet::new();
+		indexes.leaves.sort_by_key(|(_, number, _)| *number);
+		for (hash, number, parent_hash) in indexes.leaves {
+			new_leaves.import(hash, number, parent_hash);
+		}
+		new_leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
+
+		self.storage.db.commit(transaction)?;
+		*leaves = new_leaves;
+		Ok(())
+	}
+
+	fn check_journal(&self, info: &Info<Block>, report: &mut ConsistencyReport<Block>) {
+		let best_number = info.best_number.saturated_into::<u64>();
+		if matches!(self.storage.state_db.best_canonical(), Some(number) if number > best_number) {
+			report.push(Inconsistency::InvalidMeta("state-db last canonical block"));
+		}
+		for (hash, number, _) in self.storage.state_db.non_canonical_blocks() {
+			let known = match self.blockchain.header(BlockId::Hash(hash)) {
+				Ok(Some(header)) => (*header.number()).saturated_into::<u64>() == number,
+				_ => false,
+			};
+			if !known {
+				report.push(Inconsistency::DanglingJournalEntry { number, hash });
+			}
+		}
+	}
+
+	fn check_states(
+		&self,
+		info: &Info<Block>,
+		report: &mut ConsistencyReport<Block>,
+	) -> ClientResult<()> {
+		let have_state_at =
+			|hash: &Block::Hash, number| sc_client_api::Backend::have_state_at(self, hash, number);
+
+		// Canonical blocks, from the last finalized one back to the start of the pruning window.
+		let mut blocks = Vec::new();
+		let mut number = info.finalized_number;
+		loop {
+			match self.blockchain.hash(number)? {
+				Some(hash) if have_state_at(&hash, number) => blocks.push(hash),
+				_ => break,
+			}
+			if number.is_zero() {
+				break
+			}
+			number -= One::one();
+		}
+
+		// Blocks that are not finalized yet, whose states are kept until they are canonicalized.
+		blocks.extend(
+			self.storage
+				.state_db
+				.non_canonical_blocks()
+				.into_iter()
+				.map(|(hash, _, _)| hash),
+		);
+		for leaf in self.blockchain.leaves.read().hashes() {
+			if let Ok(Some(header)) = self.blockchain.header(BlockId::Hash(leaf)) {
+				if have_state_at(&leaf, *header.number()) {
+					blocks.push(leaf);
+				}
+			}
+		}
+
+		// Consecutive states share most of their nodes, which are only checked once.
+		let storage = Arc::new(UncachedStorage(self.storage.clone()));
+		let mut visited = HashSet::new();
+		for block in blocks {
+			let root = match self.blockchain.header(BlockId::Hash(block))? {
+				Some(header) => *header.state_root(),
+				None => continue,
+			};
+			let state = DbState::<Block>::new(storage.clone(), root);
+			let mut child_roots = Vec::new();
+			let mut trie = TrieCheck { block, keyspace: &[], visited: &mut visited, report };
+			trie.check(state.essence(), root, Some(&mut child_roots));
+			for (storage_key, child_root) in child_roots {
+				let child_info = ChildInfo::new_default(&storage_key);
+				let db = KeySpacedDB::new(state.essence(), child_info.keyspace());
+				let mut trie = TrieCheck {
+					block,
+					keyspace: child_info.keyspace(),
+					visited: &mut visited,
+					report,
+				};
+				trie.check(&db, child_root, None);
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Extract the block hash from a lookup key, which is the block number followed by the hash.
+fn lookup_key_hash<Block: BlockT>(lookup_key: &[u8]) -> Block::Hash {
+	lookup_key
+		.get(4..)
+		.and_then(|mut hash| Block::Hash::decode(&mut hash).ok())
+		.unwrap_or_default()
+}
+
+/// A node of a trie, referenced by its hash or inlined in its parent.
+enum NodeRef<Hash> {
+	Hash(Hash),
+	Inline(Vec<u8>),
+}
+
+/// Walk over the tries of the states of several blocks, reporting every missing or corrupted node.
+struct TrieCheck<'a, Block: BlockT> {
+	/// Block whose state is checked.
+	block: Block::Hash,
+	/// Keyspace of the child trie being checked, empty for the top trie.
+	keyspace: &'a [u8],
+	/// Keyspaced database keys of the nodes and values checked so far.
+	visited: &'a mut HashSet<Vec<u8>>,
+	report: &'a mut ConsistencyReport<Block>,
+}
+
+impl<'a, Block: BlockT> TrieCheck<'a, Block> {
+	/// Check the nodes of the trie with the given root that were not visited yet. The subtrees of
+	/// visited nodes are skipped, so the nodes shared with a previously checked trie are only
+	/// read once. The roots of default child tries found along the way are added to
+	/// `child_roots`.
+	fn check(
+		&mut self,
+		db: &dyn HashDBRef<HashFor<Block>, DBValue>,
+		root: Block::Hash,
+		mut child_roots: Option<&mut Vec<(Vec<u8>, Block::Hash)>>,
+	) {
+		let mut stack = vec![(NibbleVec::new(), NodeRef::Hash(root))];
+		while let Some((mut key, node_ref)) = stack.pop() {
+			let data = match node_ref {
+				NodeRef::Hash(hash) => match self.fetch(db, hash, key.as_prefix()) {
+					Some(data) => data,
+					None => continue,
+				},
+				NodeRef::Inline(data) => data,
+			};
+			let node = match <Layout<Block> as TrieLayout>::Codec::decode(&data) {
+				Ok(node) => node,
+				Err(error) => {
+					self.push_corrupted(format!("Invalid trie node: {}", error));
+					continue
+				},
+			};
+
+			let mut children = Vec::new();
+			let value = match node {
+				Node::Empty => None,
+				Node::Leaf(partial, value) => {
+					key.append_partial(partial.right());
+					Some(value)
+				},
+				Node::Extension(partial, child) => {
+					key.append_partial(partial.right());
+					children.push((None, child));
+					None
+				},
+				Node::Branch(nodes, value) => {
+					children.extend(nodes.iter().enumerate().filter_map(|(index, child)| {
+						child.map(|child| (Some(index as u8), child))
+					}));
+					value
+				},
+				Node::NibbledBranch(partial, nodes, value) => {
+					key.append_partial(partial.right());
+					children.extend(nodes.iter().enumerate().filter_map(|(index, child)| {
+						child.map(|child| (Some(index as u8), child))
+					}));
+					value
+				},
+			};
+
+			for (index, child) in children {
+				let mut child_key = key.clone();
+				if let Some(index) = index {
+					child_key.push(index);
+				}
+				let child = match child {
+					NodeHandle::Hash(hash) => match decode_hash::<HashFor<Block>>(hash) {
+						Some(hash) => NodeRef::Hash(hash),
+						None => {
+							self.push_corrupted(format!("Invalid trie node hash: {:?}", hash));
+							continue
+						},
+					},
+					NodeHandle::Inline(data) => NodeRef::Inline(data.to_vec()),
+				};
+				stack.push((child_key, child));
+			}
+
+			if value.is_some() && key.len() % 2 != 0 {
+				self.push_corrupted("Trie value at incomplete key".into());
+				continue
+			}
+			let value = match value {
+				Some(Value::Inline(value)) => value.to_vec(),
+				Some(Value::Node(hash, _)) => match decode_hash::<HashFor<Block>>(hash) {
+					Some(hash) => match self.fetch(db, hash, (key.inner(), None)) {
+						Some(value) => value,
+						None => continue,
+					},
+					None => {
+						self.push_corrupted(format!("Invalid trie value hash: {:?}", hash));
+						continue
+					},
+				},
+				None => continue,
+			};
+			self.report.state_keys += 1;
+			let (child_roots, storage_key) = match (
+				child_roots.as_mut(),
+				key.inner().strip_prefix(well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX),
+			) {
+				(Some(child_roots), Some(storage_key)) => (child_roots, storage_key),
+				_ => continue,
+			};
+			match Block::Hash::decode(&mut &value[..]) {
+				Ok(child_root) => child_roots.push((storage_key.to_vec(), child_root)),
+				Err(error) => self.push_corrupted(format!("Invalid child trie root: {}", error)),
+			}
+		}
+	}
+
+	/// Read a node or a value that was not visited yet, reporting it if it is missing.
+	fn fetch(
+		&mut self,
+		db: &dyn HashDBRef<HashFor<Block>, DBValue>,
+		hash: Block::Hash,
+		prefix: Prefix,
+	) -> Option<DBValue> {
+		let mut db_key = self.keyspace.to_vec();
+		db_key.extend(prefixed_key::<HashFor<Block>>(&hash, prefix));
+		if !self.visited.insert(db_key) {
+			return None
+		}
+		let data = db.get(&hash, prefix);
+		if data.is_none() {
+			self.report
+				.push(Inconsistency::MissingTrieNode { block: self.block, node: hash });
+		}
+		data
+	}
+
+	fn push_corrupted(&mut self, error: String) {
+		self.report.push(Inconsistency::CorruptedEntry {
+			column: "state",
+			block: self.block,
+			error,
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::{insert_header, Block};
+	use sc_client_api::backend::{Backend as _, BlockImportOperation as _, NewBlockState};
+	use sp_core::{Hasher, H256};
+	use sp_runtime::{testing::Header, StateVersion, Storage};
+	use sp_state_machine::Backend as _;
+
+	fn check(backend: &Backend<Block>, repair: bool) -> Vec<Inconsistency<Block>> {
+		let mut inconsistencies = backend.check_consistency(repair).unwrap().inconsistencies;
+		inconsistencies.sort_by_key(|i| i.to_string());
+		inconsistencies
+	}
+
+	#[test]
+	fn consistent_database_has_no_inconsistencies() {
+		let backend = Backend::<Block>::new_test(1000, 100);
+		let block0 = insert_header(&backend, 0, Default::default(), None, Default::default());
+		let block1 = insert_header(&backend, 1, block0, None, Default::default());
+		let _fork1 = insert_header(&backend, 1, block0, None, [1; 32].into());
+		let _block2 = insert_header(&backend, 2, block1, None, Default::default());
+
+		let report = backend.check_consistency(false).unwrap();
+		assert_eq!(report.inconsistencies, Vec::new());
+		assert_eq!(report.canonical_blocks, 3);
+		assert_eq!(report.indexed_blocks, 4);
+		assert!(!report.repaired);
+	}
+
+	/// Insert a finalized block setting `key` to `value` on top of `parent`. The genesis state also
+	/// holds two large values, stored apart from their trie nodes.
+	fn insert_state(
+		backend: &Backend<Block>,
+		number: u64,
+		parent: H256,
+		value: u8,
+	) -> (H256, H256) {
+		let mut op = backend.begin_operation().unwrap();
+		let state_root = if number == 0 {
+			let storage = Storage {
+				top: vec![
+					(b"a".to_vec(), vec![1; 40]),
+					(b"b".to_vec(), vec![2; 40]),
+					(b"key".to_vec(), vec![value]),
+				]
+				.into_iter()
+				.collect(),
+				children_default: Default::default(),
+			};
+			op.reset_storage(storage, StateVersion::V1).unwrap()
+		} else {
+			backend.begin_state_operation(&mut op, BlockId::Hash(parent)).unwrap();
+			let (root, overlay) = op
+				.old_state
+				.storage_root(std::iter::once((&b"key"[..], Some(&[value][..]))), StateVersion::V1);
+			op.update_db_storage(overlay).unwrap();
+			root
+		};
+		let header = Header {
+			number,
+			parent_hash: parent,
+			state_root,
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let hash = header.hash();
+		op.set_block_data(header, Some(vec![]), None, None, NewBlockState::Final)
+			.unwrap();
+		backend.commit_operation(op).unwrap();
+		(hash, state_root)
+	}
+
+	#[test]
+	fn checks_every_state_in_the_pruning_window() {
+		let backend = Backend::<Block>::new_test(1000, 100);
+		let (block0, _) = insert_state(&backend, 0, Default::default(), 0);
+		let (block1, root1) = insert_state(&backend, 1, block0, 1);
+		let (_block2, _) = insert_state(&backend, 2, block1, 2);
+		assert_eq!(check(&backend, false), Vec::new());
+
+		// The state of `block1` is neither the best nor the finalized state, but is still kept.
+		let mut transaction = Transaction::new();
+		transaction.remove(columns::STATE, root1.as_ref());
+		backend.storage.db.commit(transaction).unwrap();
+
+		assert_eq!(
+			check(&backend, false),
+			vec![Inconsistency::MissingTrieNode { block: block1, node: root1 }],
+		);
+	}
+
+	#[test]
+	fn checks_shared_nodes_once() {
+		let backend = Backend::<Block>::new_test(1000, 100);
+		let (block0, _) = insert_state(&backend, 0, Default::default(), 0);
+		let (block1, _) = insert_state(&backend, 1, block0, 1);
+		let (block2, _) = insert_state(&backend, 2, block1, 2);
+
+		let report = backend.check_consistency(false).unwrap();
+		assert_eq!(report.inconsistencies, Vec::new());
+		// The large values are only read with the first state.
+		assert_eq!(report.state_keys, 5);
+
+		let value = HashFor::<Block>::hash(&[1; 40]);
+		let mut transaction = Transaction::new();
+		transaction.remove(columns::STATE, &[&b"a"[..], value.as_ref()].concat());
+		backend.storage.db.commit(transaction).unwrap();
+
+		// The missing value is shared by the three states, it is reported once.
+		assert_eq!(
+			check(&backend, false),
+			vec![Inconsistency::MissingTrieNode { block: block2, node: value }],
+		);
+	}
+
+	#[test]
+	fn repairs_leaves_and_children() {
+		let backend = Backend::<Block>::new_test(1000, 100);
+		let block0 = insert_header(&backend, 0, Default::default(), None, Default::default());
+		let block1 = insert_header(&backend, 1, block0, None, Default::default());
+		let fork1 = insert_header(&backend, 1, block0, None, [1; 32].into());
+		let block2 = insert_header(&backend, 2, block1, None, Default::default());
+		let unknown = [2; 32].into();
+
+		// Forget about `fork1` and list an unknown child of `block1`.
+		let mut transaction = Transaction::new();
+		children::write_children(
+			&mut transaction,
+			columns::META,
+			meta_keys::CHILDREN_PREFIX,
+			block0,
+			vec![block1],
+		);
+		children::write_children(
+			&mut transaction,
+			columns::META,
+			meta_keys::CHILDREN_PREFIX,
+			block1,
+			vec![block2, unknown],
+		);
+		let mut leaves = LeafSet::new();
+		leaves.import(block2, 2, block1);
+		leaves.import(unknown, 2, block1);
+		leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
+		backend.storage.db.commit(transaction).unwrap();
+		*backend.blockchain.leaves.write() = leaves;
+
+		assert_eq!(
+			check(&backend, false),
+			vec![
+				Inconsistency::DanglingChild { parent: block1, child: unknown },
+				Inconsistency::DanglingLeaf(unknown),
+			],
+		);
+
+		// `fork1` is not reachable anymore, add it back as a leaf to let it be found.
+		let mut leaves = backend.blockchain.leaves.write();
+		leaves.import(fork1, 1, block0);
+		drop(leaves);
+		assert_eq!(
+			check(&backend, true),
+			vec![
+				Inconsistency::DanglingChild { parent: block1, child: unknown },
+				Inconsistency::DanglingLeaf(unknown),
+				Inconsistency::MissingChild { parent: block0, child: fork1 },
+			],
+		);
+
+		assert_eq!(check(&backend, false), Vec::new());
+		let mut leaves = backend.blockchain().leaves().unwrap();
+		leaves.sort();
+		let mut expected = vec![block2, fork1];
+		expected.sort();
+		assert_eq!(leaves, expected);
+		assert_eq!(backend.blockchain().children(block1).unwrap(), vec![block2]);
+	}
+}
@@ -0,0 +1,354 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Conversion of the database to another backend.
+
+use crate::{
+	columns,
+	utils::{self, DatabaseType, NUM_COLUMNS},
+	DatabaseSource, DbHash, StateMetaDb, DB_HASH_LEN,
+};
+use log::info;
+use sp_blockchain::{Error as ClientError, Result as ClientResult};
+use sp_database::{Database, Transaction};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, HashFor, Header as HeaderT},
+};
+use sp_trie::prefixed_key;
+
+/// Maximum size of the keys and values committed to the target database at once.
+const BATCH_SIZE: usize = 64 * 1024 * 1024;
+
+/// Copy the database at `source` to the empty database at `target`.
+///
+/// All the columns are copied as is, except when the target counts references and the source
+/// doesn't, e.g. from RocksDB to ParityDB. In that case the prefix is removed from the state keys
+/// and the state-db journals are rewritten to match. The metadata of both databases is compared
+/// once the copy is done.
+pub fn convert_database<Block: BlockT>(
+	source: &DatabaseSource,
+	target: &DatabaseSource,
+) -> ClientResult<()> {
+	if source.path().is_some() && source.path() == target.path() {
+		return Err(backend_err("Source and target databases must be different"))
+	}
+
+	let source_db = utils::open_database_at::<Block>(source, DatabaseType::Full)?;
+	if utils::read_genesis_hash::<Block::Hash>(&*source_db)?.is_none() {
+		return Err(backend_err(format!("{} database is empty", source)))
+	}
+	let hashed_columns = (0..NUM_COLUMNS)
+		.filter(|col| source_db.iter(*col).is_none())
+		.collect::<Vec<_>>();
+	if !hashed_columns.is_empty() {
+		return Err(backend_err(format!(
+			"Columns {:?} of the {} database can't be iterated, their keys are only stored hashed",
+			hashed_columns, source
+		)))
+	}
+
+	let target_db = utils::open_database_at::<Block>(target, DatabaseType::Full)?;
+	if utils::read_genesis_hash::<Block::Hash>(&*target_db)?.is_some() {
+		return Err(backend_err(format!("{} database is not empty", target)))
+	}
+
+	let strip_prefix = match (source_db.supports_ref_counting(), target_db.supports_ref_counting())
+	{
+		(true, false) =>
+			return Err(backend_err(format!(
+				"Converting {} database to {} is not supported, the trie prefixes of the state \
+				keys can't be restored",
+				source, target
+			))),
+		(source, target) => !source && target,
+	};
+
+	info!("Converting {} database to {}", source, target);
+	for col in 0..NUM_COLUMNS {
+		let map_key = |mut key: Vec<u8>| {
+			if strip_prefix && col == columns::STATE {
+				key.drain(0..key.len() - DB_HASH_LEN);
+			}
+			key
+		};
+		let count = copy_column(&*source_db, &*target_db, col, map_key)?;
+		info!("Copied {} entries of column {}", count, col);
+	}
+
+	if strip_prefix {
+		let journals = sc_state_db::convert_journals::<Block::Hash, Vec<u8>, _>(
+			&StateMetaDb(&*source_db),
+			|mut key| {
+				key.drain(0..key.len() - DB_HASH_LEN);
+				key
+			},
+		)
+		.map_err(ClientError::from_state_db)?;
+		info!("Rewriting {} state-db journal records", journals.inserted.len());
+		let mut transaction = Transaction::new();
+		for (key, value) in journals.inserted {
+			transaction.set_from_vec(columns::STATE_META, &key, value);
+		}
+		for key in journals.deleted {
+			transaction.remove(columns::STATE_META, &key);
+		}
+		target_db.commit(transaction)?;
+	}
+
+	verify::<Block>(&*source_db, &*target_db, strip_prefix)?;
+	info!("Database converted");
+	Ok(())
+}
+
+/// Copy all the entries of column `col`, applying `map_key` to the keys. Returns the number of
+/// entries copied.
+fn copy_column(
+	source: &dyn Database<DbHash>,
+	target: &dyn Database<DbHash>,
+	col: u32,
+	map_key: impl Fn(Vec<u8>) -> Vec<u8>,
+) -> ClientResult<u64> {
+	let entries = source
+		.iter(col)
+		.ok_or_else(|| backend_err(format!("Column {} can't be iterated", col)))?;
+	let mut transaction = Transaction::new();
+	let mut size = 0;
+	let mut count = 0;
+	for (key, value) in entries {
+		size += key.len() + value.len();
+		count += 1;
+		transaction.set_from_vec(col, &map_key(key), value);
+		if size >= BATCH_SIZE {
+			target.commit(std::mem::take(&mut transaction))?;
+			size = 0;
+		}
+	}
+	target.commit(transaction)?;
+	Ok(count)
+}
+
+/// Check that the metadata was copied and that the finalized state can be found.
+fn verify<Block: BlockT>(
+	source: &dyn Database<DbHash>,
+	target: &dyn Database<DbHash>,
+	strip_prefix: bool,
+) -> ClientResult<()> {
+	let source_meta = utils::read_meta::<Block>(source, columns::HEADER)?;
+	let target_meta = utils::read_meta::<Block>(target, columns::HEADER)?;
+	if source_meta != target_meta {
+		return Err(backend_err(format!(
+			"Metadata mismatch after conversion. Expected {:?}, found {:?}",
+			source_meta, target_meta
+		)))
+	}
+
+	if let Some((hash, number)) = target_meta.finalized_state {
+		let header = utils::read_header::<Block>(
+			target,
+			columns::KEY_LOOKUP,
+			columns::HEADER,
+			BlockId::Hash(hash),
+		)?
+		.ok_or_else(|| backend_err(format!("Missing header of the finalized state #{}", number)))?;
+		let root = *header.state_root();
+		let key = if strip_prefix {
+			root.as_ref().to_vec()
+		} else {
+			prefixed_key::<HashFor<Block>>(&root, hash_db::EMPTY_PREFIX)
+		};
+		if !target.contains(columns::STATE, &key) {
+			return Err(backend_err(format!("Missing state root of finalized block #{}", number)))
+		}
+	}
+
+	info!(
+		"Verified best block #{} ({}) and finalized block #{} ({})",
+		target_meta.best_number,
+		target_meta.best_hash,
+		target_meta.finalized_number,
+		target_meta.finalized_hash,
+	);
+	Ok(())
+}
+
+fn backend_err(message: impl Into<String>) -> ClientError {
+	ClientError::Backend(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		tests::Block, Backend, DatabaseSettings, KeepBlocks, PruningMode, TransactionStorageMode,
+	};
+	use sc_client_api::backend::{Backend as _, BlockImportOperation as _, NewBlockState};
+	use sp_blockchain::HeaderBackend;
+	use sp_core::H256;
+	use sp_database::{ColumnIter, MemDb};
+	use sp_runtime::{testing::Header, StateVersion, Storage};
+	use sp_state_machine::Backend as _;
+	use std::sync::Arc;
+
+	/// In-memory database that counts references itself, like ParityDB.
+	struct RefCountingDb(MemDb);
+
+	impl Database<DbHash> for RefCountingDb {
+		fn commit(&self, transaction: Transaction<DbHash>) -> sp_database::error::Result<()> {
+			self.0.commit(transaction)
+		}
+
+		fn get(&self, col: u32, key: &[u8]) -> Option<Vec<u8>> {
+			Database::<DbHash>::get(&self.0, col, key)
+		}
+
+		fn supports_ref_counting(&self) -> bool {
+			true
+		}
+
+		fn iter<'a>(&'a self, col: u32) -> Option<ColumnIter<'a>> {
+			Database::<DbHash>::iter(&self.0, col)
+		}
+	}
+
+	/// In-memory database that can't iterate the state column.
+	struct HashedStateDb(MemDb);
+
+	impl Database<DbHash> for HashedStateDb {
+		fn commit(&self, transaction: Transaction<DbHash>) -> sp_database::error::Result<()> {
+			self.0.commit(transaction)
+		}
+
+		fn get(&self, col: u32, key: &[u8]) -> Option<Vec<u8>> {
+			Database::<DbHash>::get(&self.0, col, key)
+		}
+
+		fn iter<'a>(&'a self, col: u32) -> Option<ColumnIter<'a>> {
+			if col == columns::STATE {
+				return None
+			}
+			Database::<DbHash>::iter(&self.0, col)
+		}
+	}
+
+	fn open(db: Arc<dyn Database<DbHash>>) -> Backend<Block> {
+		let settings = DatabaseSettings {
+			state_cache_size: 16777216,
+			state_cache_child_ratio: Some((50, 100)),
+			trie_cache_size: 16777216,
+			state_pruning: PruningMode::keep_blocks(2),
+			source: DatabaseSource::Custom(db),
+			keep_blocks: KeepBlocks::All,
+			transaction_storage: TransactionStorageMode::BlockBody,
+			state_diff_index: false,
+		};
+		Backend::new(settings, 10).unwrap()
+	}
+
+	fn insert(
+		backend: &Backend<Block>,
+		number: u64,
+		parent_hash: H256,
+		storage: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+		state: NewBlockState,
+	) -> H256 {
+		let mut op = backend.begin_operation().unwrap();
+		let parent = BlockId::Hash(parent_hash);
+		backend.begin_state_operation(&mut op, parent).unwrap();
+		let (root, overlay) = op.old_state.storage_root(
+			storage.iter().map(|(k, v)| (&k[..], v.as_ref().map(|v| &v[..]))),
+			StateVersion::V1,
+		);
+		let header = Header {
+			number,
+			parent_hash,
+			state_root: root,
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		if number == 0 {
+			let top = storage.into_iter().map(|(k, v)| (k, v.unwrap())).collect();
+			op.reset_storage(
+				Storage { top, children_default: Default::default() },
+				StateVersion::V1,
+			)
+			.unwrap();
+		} else {
+			op.update_db_storage(overlay).unwrap();
+			op.update_storage(storage, Vec::new()).unwrap();
+		}
+		let hash = header.hash();
+		op.set_block_data(header, Some(vec![]), None, None, state).unwrap();
+		backend.commit_operation(op).unwrap();
+		hash
+	}
+
+	#[test]
+	fn converts_to_ref_counting_database() {
+		let source = sp_database::as_database(kvdb_memorydb::create(NUM_COLUMNS));
+		let (block0, block1) = {
+			let backend = open(source.clone());
+			let storage = vec![(vec![1], Some(vec![1])), (vec![2], Some(vec![2]))];
+			let block0 = insert(&backend, 0, Default::default(), storage, NewBlockState::Final);
+			let storage = vec![(vec![1], None), (vec![3], Some(vec![3]))];
+			let block1 = insert(&backend, 1, block0, storage, NewBlockState::Best);
+			(block0, block1)
+		};
+
+		let target: Arc<dyn Database<DbHash>> = Arc::new(RefCountingDb(MemDb::new()));
+		convert_database::<Block>(
+			&DatabaseSource::Custom(source.clone()),
+			&DatabaseSource::Custom(target.clone()),
+		)
+		.unwrap();
+
+		let backend = open(target.clone());
+		let info = backend.blockchain().info();
+		assert_eq!(info.best_hash, block1);
+		assert_eq!(info.finalized_hash, block0);
+		let state = backend.state_at(BlockId::Hash(block0)).unwrap();
+		assert_eq!(state.storage(&[1]).unwrap(), Some(vec![1]));
+		assert_eq!(state.storage(&[2]).unwrap(), Some(vec![2]));
+		// The state of the non-canonical block is read from the converted journal.
+		let state = backend.state_at(BlockId::Hash(block1)).unwrap();
+		assert_eq!(state.storage(&[1]).unwrap(), None);
+		assert_eq!(state.storage(&[3]).unwrap(), Some(vec![3]));
+		drop(backend);
+
+		assert!(convert_database::<Block>(
+			&DatabaseSource::Custom(source),
+			&DatabaseSource::Custom(target),
+		)
+		.is_err());
+	}
+
+	#[test]
+	fn rejects_columns_that_cant_be_iterated() {
+		let source: Arc<dyn Database<DbHash>> = Arc::new(HashedStateDb(MemDb::new()));
+		insert(&open(source.clone()), 0, Default::default(), vec![], NewBlockState::Final);
+
+		let target = sp_database::as_database(kvdb_memorydb::create(NUM_COLUMNS));
+		let err = convert_database::<Block>(
+			&DatabaseSource::Custom(source),
+			&DatabaseSource::Custom(target.clone()),
+		)
+		.unwrap_err();
+		assert!(err.to_string().contains("Columns [1]"));
+		assert!(utils::read_genesis_hash::<H256>(&*target).unwrap().is_none());
+	}
+}
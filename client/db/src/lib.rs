@@ -34,16 +34,19 @@ pub mod offchain;
 pub mod bench;
 
 mod children;
+mod consistency;
+mod convert;
 #[cfg(feature = "with-parity-db")]
 mod parity_db;
+mod record_stats_state;
 mod stats;
-mod storage_cache;
+mod trie_cache;
 #[cfg(any(feature = "with-kvdb-rocksdb", test))]
 mod upgrade;
 mod utils;
 
 use linked_hash_map::LinkedHashMap;
-use log::{debug, trace, warn};
+use log::{debug, info, trace, warn};
 use parking_lot::{Mutex, RwLock};
 use std::{
 	collections::{HashMap, HashSet},
@@ -53,19 +56,20 @@ use std::{
 };
 
 use crate::{
+	record_stats_state::RecordStatsState,
 	stats::StateUsageStats,
-	storage_cache::{new_shared_cache, CachingState, SharedCache, SyncingCachingState},
+	trie_cache::SharedTrieCache,
 	utils::{meta_keys, read_db, read_meta, DatabaseType, Meta},
 };
 use codec::{Decode, Encode};
 use hash_db::Prefix;
 use sc_client_api::{
-	backend::NewBlockState,
+	backend::{NewBlockState, StateDiff},
 	leaves::{FinalizationDisplaced, LeafSet},
 	utils::is_descendent_of,
 	IoInfo, MemoryInfo, MemorySize, UsageInfo,
 };
-use sc_state_db::StateDb;
+use sc_state_db::{IsPruned, StateDb};
 use sp_arithmetic::traits::Saturating;
 use sp_blockchain::{
 	well_known_cache_keys, Backend as _, CachedHeaderMetadata, Error as ClientError, HeaderBackend,
@@ -82,7 +86,7 @@ use sp_runtime::{
 		Block as BlockT, Hash, HashFor, Header as HeaderT, NumberFor, One, SaturatedConversion,
 		Zero,
 	},
-	Justification, Justifications, Storage,
+	Justification, Justifications, StateVersion, Storage,
 };
 use sp_state_machine::{
 	backend::Backend as StateBackend, ChildStorageCollection, DBValue, IndexOperation,
@@ -94,14 +98,14 @@ use sp_trie::{prefixed_key, MemoryDB, PrefixedMemoryDB};
 pub use sc_state_db::PruningMode;
 pub use sp_database::Database;
 
+pub use consistency::{ConsistencyReport, Inconsistency};
+pub use convert::convert_database;
+
 #[cfg(any(feature = "with-kvdb-rocksdb", test))]
 pub use bench::BenchmarkingState;
 
 const CACHE_HEADERS: usize = 8;
 
-/// Default value for storage cache child ratio.
-const DEFAULT_CHILD_RATIO: (usize, usize) = (1, 10);
-
 /// DB-backed patricia trie state, transaction type is an overlay of changes to commit.
 pub type DbState<B> =
 	sp_state_machine::TrieBackend<Arc<dyn sp_state_machine::Storage<HashFor<B>>>, HashFor<B>>;
@@ -235,22 +239,24 @@ impl<B: BlockT> StateBackend<HashFor<B>> for RefTrackingState<B> {
 	fn storage_root<'a>(
 		&self,
 		delta: impl Iterator<Item = (&'a [u8], Option<&'a [u8]>)>,
+		state_version: StateVersion,
 	) -> (B::Hash, Self::Transaction)
 	where
 		B::Hash: Ord,
 	{
-		self.state.storage_root(delta)
+		self.state.storage_root(delta, state_version)
 	}
 
 	fn child_storage_root<'a>(
 		&self,
 		child_info: &ChildInfo,
 		delta: impl Iterator<Item = (&'a [u8], Option<&'a [u8]>)>,
+		state_version: StateVersion,
 	) -> (B::Hash, bool, Self::Transaction)
 	where
 		B::Hash: Ord,
 	{
-		self.state.child_storage_root(child_info, delta)
+		self.state.child_storage_root(child_info, delta, state_version)
 	}
 
 	fn pairs(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
@@ -282,10 +288,12 @@ impl<B: BlockT> StateBackend<HashFor<B>> for RefTrackingState<B> {
 
 /// Database settings.
 pub struct DatabaseSettings {
-	/// State cache size.
+	/// State cache size. Deprecated and ignored, use `trie_cache_size`.
 	pub state_cache_size: usize,
-	/// Ratio of cache size dedicated to child tries.
+	/// Ratio of cache size dedicated to child tries. Deprecated and ignored.
 	pub state_cache_child_ratio: Option<(usize, usize)>,
+	/// Trie node cache size, shared by all the forks. `0` disables the cache.
+	pub trie_cache_size: usize,
 	/// State pruning mode.
 	pub state_pruning: PruningMode,
 	/// Where to find the database.
@@ -294,6 +302,8 @@ pub struct DatabaseSettings {
 	pub keep_blocks: KeepBlocks,
 	/// Block body/Transaction storage scheme.
 	pub transaction_storage: TransactionStorageMode,
+	/// Record the keys written by each imported block.
+	pub state_diff_index: bool,
 }
 
 /// Block pruning settings.
@@ -401,6 +411,8 @@ pub(crate) mod columns {
 	pub const AUX: u32 = 8;
 	/// Offchain workers local storage
 	pub const OFFCHAIN: u32 = 9;
+	/// Keys written by each block, when the state diff index is enabled.
+	pub const STATE_DIFF: u32 = 10;
 	/// Transactions
 	pub const TRANSACTION: u32 = 11;
 }
@@ -720,7 +732,7 @@ impl<Block: BlockT> HeaderMetadata<Block> for BlockchainDb<Block> {
 
 /// Database transaction
 pub struct BlockImportOperation<Block: BlockT> {
-	old_state: SyncingCachingState<RefTrackingState<Block>, Block>,
+	old_state: RecordStatsState<RefTrackingState<Block>, Block>,
 	db_updates: PrefixedMemoryDB<HashFor<Block>>,
 	storage_updates: StorageCollection,
 	child_storage_updates: ChildStorageCollection,
@@ -730,6 +742,10 @@ pub struct BlockImportOperation<Block: BlockT> {
 	finalized_blocks: Vec<(BlockId<Block>, Option<Justification>)>,
 	set_head: Option<BlockId<Block>>,
 	commit_state: bool,
+	// The state was written in full rather than as changes on top of the parent state.
+	full_state: bool,
+	// The state was written beforehand with `Backend::write_state_nodes`.
+	written_state: bool,
 	index_ops: Vec<IndexOperation>,
 }
 
@@ -760,7 +776,11 @@ impl<Block: BlockT> BlockImportOperation<Block> {
 		}
 	}
 
-	fn apply_new_state(&mut self, storage: Storage) -> ClientResult<Block::Hash> {
+	fn apply_new_state(
+		&mut self,
+		storage: Storage,
+		state_version: StateVersion,
+	) -> ClientResult<Block::Hash> {
 		if storage.top.keys().any(|k| well_known_keys::is_child_storage_key(&k)) {
 			return Err(sp_blockchain::Error::InvalidState.into())
 		}
@@ -775,9 +795,11 @@ impl<Block: BlockT> BlockImportOperation<Block> {
 		let (root, transaction) = self.old_state.full_storage_root(
 			storage.top.iter().map(|(k, v)| (&k[..], Some(&v[..]))),
 			child_delta,
+			state_version,
 		);
 
 		self.db_updates = transaction;
+		self.full_state = true;
 		Ok(root)
 	}
 }
@@ -785,7 +807,7 @@ impl<Block: BlockT> BlockImportOperation<Block> {
 impl<Block: BlockT> sc_client_api::backend::BlockImportOperation<Block>
 	for BlockImportOperation<Block>
 {
-	type State = SyncingCachingState<RefTrackingState<Block>, Block>;
+	type State = RecordStatsState<RefTrackingState<Block>, Block>;
 
 	fn state(&self) -> ClientResult<Option<&Self::State>> {
 		Ok(Some(&self.old_state))
@@ -814,14 +836,23 @@ impl<Block: BlockT> sc_client_api::backend::BlockImportOperation<Block>
 		Ok(())
 	}
 
-	fn reset_storage(&mut self, storage: Storage) -> ClientResult<Block::Hash> {
-		let root = self.apply_new_state(storage)?;
+	fn reset_storage(
+		&mut self,
+		storage: Storage,
+		state_version: StateVersion,
+	) -> ClientResult<Block::Hash> {
+		let root = self.apply_new_state(storage, state_version)?;
 		self.commit_state = true;
 		Ok(root)
 	}
 
-	fn set_genesis_state(&mut self, storage: Storage, commit: bool) -> ClientResult<Block::Hash> {
-		let root = self.apply_new_state(storage)?;
+	fn set_genesis_state(
+		&mut self,
+		storage: Storage,
+		commit: bool,
+		state_version: StateVersion,
+	) -> ClientResult<Block::Hash> {
+		let root = self.apply_new_state(storage, state_version)?;
 		self.commit_state = commit;
 		Ok(root)
 	}
@@ -871,16 +902,59 @@ impl<Block: BlockT> sc_client_api::backend::BlockImportOperation<Block>
 		self.index_ops = index_ops;
 		Ok(())
 	}
+
+	fn set_written_state(&mut self) -> ClientResult<()> {
+		self.commit_state = true;
+		self.full_state = true;
+		self.written_state = true;
+		Ok(())
+	}
 }
 
 struct StorageDb<Block: BlockT> {
 	pub db: Arc<dyn Database<DbHash>>,
 	pub state_db: StateDb<Block::Hash, Vec<u8>>,
 	prefix_keys: bool,
+	trie_cache: Option<SharedTrieCache<Block::Hash>>,
 }
 
 impl<Block: BlockT> sp_state_machine::Storage<HashFor<Block>> for StorageDb<Block> {
 	fn get(&self, key: &Block::Hash, prefix: Prefix) -> Result<Option<DBValue>, String> {
+		if let Some(node) = self.trie_cache.as_ref().and_then(|cache| cache.get(key)) {
+			return Ok(Some(node))
+		}
+		let node = self.get_uncached(key, prefix)?;
+		if let (Some(cache), Some(node)) = (self.trie_cache.as_ref(), node.as_ref()) {
+			cache.insert(*key, node.clone());
+		}
+		Ok(node)
+	}
+}
+
+impl<Block: BlockT> StorageDb<Block> {
+	/// Add a state-db commit to the transaction, evicting the deleted trie nodes from the trie
+	/// cache.
+	fn apply_state_commit(
+		&self,
+		transaction: &mut Transaction<DbHash>,
+		commit: sc_state_db::CommitSet<Vec<u8>>,
+	) {
+		if let Some(cache) = &self.trie_cache {
+			for key in &commit.data.deleted {
+				// The node hash is the suffix of prefixed keys.
+				let mut hash = Block::Hash::default();
+				let len = hash.as_ref().len();
+				if let Some(suffix) = key.len().checked_sub(len).map(|start| &key[start..]) {
+					hash.as_mut().copy_from_slice(suffix);
+					cache.remove(&hash);
+				}
+			}
+		}
+		apply_state_commit(transaction, commit);
+	}
+
+	/// Read a trie node from the state-db, bypassing the trie cache.
+	fn get_uncached(&self, key: &Block::Hash, prefix: Prefix) -> Result<Option<DBValue>, String> {
 		if self.prefix_keys {
 			let key = prefixed_key::<HashFor<Block>>(key, prefix);
 			self.state_db.get(&key, self)
@@ -984,11 +1058,11 @@ pub struct Backend<Block: BlockT> {
 	offchain_storage: offchain::LocalStorage,
 	blockchain: BlockchainDb<Block>,
 	canonicalization_delay: u64,
-	shared_cache: SharedCache<Block>,
 	import_lock: Arc<RwLock<()>>,
 	is_archive: bool,
 	keep_blocks: KeepBlocks,
 	transaction_storage: TransactionStorageMode,
+	state_diff_index: bool,
 	io_stats: FrozenForDuration<(kvdb::IoStats, StateUsageInfo)>,
 	state_usage: Arc<StateUsageStats>,
 	genesis_state: RwLock<Option<Arc<DbGenesisStorage<Block>>>>,
@@ -1025,10 +1099,12 @@ impl<Block: BlockT> Backend<Block> {
 		let db_setting = DatabaseSettings {
 			state_cache_size: 16777216,
 			state_cache_child_ratio: Some((50, 100)),
+			trie_cache_size: 16777216,
 			state_pruning: PruningMode::keep_blocks(keep_blocks),
 			source: DatabaseSource::Custom(db),
 			keep_blocks: KeepBlocks::Some(keep_blocks),
 			transaction_storage,
+			state_diff_index: true,
 		};
 
 		Self::new(db_setting, canonicalization_delay).expect("failed to create test-db")
@@ -1042,14 +1118,35 @@ impl<Block: BlockT> Backend<Block> {
 		let is_archive_pruning = config.state_pruning.is_archive();
 		let blockchain = BlockchainDb::new(db.clone(), config.transaction_storage.clone())?;
 		let map_e = |e: sc_state_db::Error<io::Error>| sp_blockchain::Error::from_state_db(e);
+		if !is_archive_pruning && !db.supports_ref_counting() {
+			let meta = sc_state_db::index_journals::<Block::Hash, Vec<u8>, _>(&StateMetaDb(&*db))
+				.map_err(map_e)?;
+			if !meta.inserted.is_empty() {
+				info!("Indexing state pruning journal");
+				let mut transaction = Transaction::new();
+				apply_state_commit(
+					&mut transaction,
+					sc_state_db::CommitSet { data: Default::default(), meta },
+				);
+				db.commit(transaction)?;
+			}
+		}
 		let state_db: StateDb<_, _> = StateDb::new(
 			config.state_pruning.clone(),
 			!db.supports_ref_counting(),
 			&StateMetaDb(&*db),
 		)
 		.map_err(map_e)?;
-		let storage_db =
-			StorageDb { db: db.clone(), state_db, prefix_keys: !db.supports_ref_counting() };
+		let trie_cache = match config.trie_cache_size {
+			0 => None,
+			size => Some(SharedTrieCache::new(size)),
+		};
+		let storage_db = StorageDb {
+			db: db.clone(),
+			state_db,
+			prefix_keys: !db.supports_ref_counting(),
+			trie_cache,
+		};
 		let offchain_storage = offchain::LocalStorage::new(db.clone());
 
 		let backend = Backend {
@@ -1057,16 +1154,13 @@ impl<Block: BlockT> Backend<Block> {
 			offchain_storage,
 			blockchain,
 			canonicalization_delay,
-			shared_cache: new_shared_cache(
-				config.state_cache_size,
-				config.state_cache_child_ratio.unwrap_or(DEFAULT_CHILD_RATIO),
-			),
 			import_lock: Default::default(),
 			is_archive: is_archive_pruning,
 			io_stats: FrozenForDuration::new(std::time::Duration::from_secs(1)),
 			state_usage: Arc::new(StateUsageStats::new()),
 			keep_blocks: config.keep_blocks.clone(),
 			transaction_storage: config.transaction_storage.clone(),
+			state_diff_index: config.state_diff_index,
 			genesis_state: RwLock::new(None),
 		};
 
@@ -1102,10 +1196,7 @@ impl<Block: BlockT> Backend<Block> {
 		transaction: &mut Transaction<DbHash>,
 		route_to: Block::Hash,
 		best_to: (NumberFor<Block>, Block::Hash),
-	) -> ClientResult<(Vec<Block::Hash>, Vec<Block::Hash>)> {
-		let mut enacted = Vec::default();
-		let mut retracted = Vec::default();
-
+	) -> ClientResult<()> {
 		let (best_number, best_hash) = best_to;
 
 		let meta = self.blockchain.meta.read();
@@ -1136,13 +1227,11 @@ impl<Block: BlockT> Backend<Block> {
 					return Err(::sp_blockchain::Error::NotInFinalizedChain.into())
 				}
 
-				retracted.push(r.hash.clone());
 				utils::remove_number_to_key_mapping(transaction, columns::KEY_LOOKUP, r.number)?;
 			}
 
 			// canonicalize: set the number lookup to map to this block's hash.
 			for e in tree_route.enacted() {
-				enacted.push(e.hash.clone());
 				utils::insert_number_to_key_mapping(
 					transaction,
 					columns::KEY_LOOKUP,
@@ -1161,7 +1250,7 @@ impl<Block: BlockT> Backend<Block> {
 			best_hash,
 		)?;
 
-		Ok((enacted, retracted))
+		Ok(())
 	}
 
 	fn ensure_sequential_finalization(
@@ -1243,10 +1332,14 @@ impl<Block: BlockT> Backend<Block> {
 			}
 
 			trace!(target: "db", "Canonicalize block #{} ({:?})", new_canonical, hash);
-			let commit = self.storage.state_db.canonicalize_block(&hash).map_err(
-				|e: sc_state_db::Error<io::Error>| sp_blockchain::Error::from_state_db(e),
-			)?;
-			apply_state_commit(transaction, commit);
+			let commit = self
+				.storage
+				.state_db
+				.canonicalize_block(&hash, &StateMetaDb(&*self.storage.db))
+				.map_err(|e: sc_state_db::Error<io::Error>| {
+					sp_blockchain::Error::from_state_db(e)
+				})?;
+			self.storage.apply_state_commit(transaction, commit);
 		}
 		Ok(())
 	}
@@ -1290,10 +1383,8 @@ impl<Block: BlockT> Backend<Block> {
 			// blocks are keyed by number + hash.
 			let lookup_key = utils::number_and_hash_to_lookup_key(number, hash)?;
 
-			let (enacted, retracted) = if pending_block.leaf_state.is_best() {
-				self.set_head_with_transaction(&mut transaction, parent_hash, (number, hash))?
-			} else {
-				(Default::default(), Default::default())
+			if pending_block.leaf_state.is_best() {
+				self.set_head_with_transaction(&mut transaction, parent_hash, (number, hash))?;
 			};
 
 			utils::insert_hash_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash)?;
@@ -1350,6 +1441,19 @@ impl<Block: BlockT> Backend<Block> {
 				}
 			}
 
+			if operation.written_state {
+				let state_root = pending_block.header.state_root();
+				let root_node =
+					sp_state_machine::Storage::get(self.storage.as_ref(), state_root, (&[], None))
+						.map_err(sp_blockchain::Error::Backend)?;
+				if root_node.is_none() {
+					return Err(sp_blockchain::Error::Backend(format!(
+						"State root {:?} of block {} was not written",
+						state_root, hash,
+					)))
+				}
+			}
+
 			let finalized = if operation.commit_state {
 				let mut changeset: sc_state_db::ChangeSet<Vec<u8>> =
 					sc_state_db::ChangeSet::default();
@@ -1402,6 +1506,13 @@ impl<Block: BlockT> Backend<Block> {
 					}
 				}
 				self.state_usage.tally_writes(ops, bytes);
+				if self.state_diff_index && !operation.full_state {
+					let diff = StateDiff::from_changes(
+						&operation.storage_updates,
+						&operation.child_storage_updates,
+					);
+					transaction.set_from_vec(columns::STATE_DIFF, hash.as_ref(), diff.encode());
+				}
 				let number_u64 = number.saturated_into::<u64>();
 				let commit = self
 					.storage
@@ -1410,13 +1521,17 @@ impl<Block: BlockT> Backend<Block> {
 					.map_err(|e: sc_state_db::Error<io::Error>| {
 						sp_blockchain::Error::from_state_db(e)
 					})?;
-				apply_state_commit(&mut transaction, commit);
+				self.storage.apply_state_commit(&mut transaction, commit);
 				if number <= last_finalized_num {
 					// Canonicalize in the db when re-importing existing blocks with state.
-					let commit = self.storage.state_db.canonicalize_block(&hash).map_err(
-						|e: sc_state_db::Error<io::Error>| sp_blockchain::Error::from_state_db(e),
-					)?;
-					apply_state_commit(&mut transaction, commit);
+					let commit = self
+						.storage
+						.state_db
+						.canonicalize_block(&hash, &StateMetaDb(&*self.storage.db))
+						.map_err(|e: sc_state_db::Error<io::Error>| {
+							sp_blockchain::Error::from_state_db(e)
+						})?;
+					self.storage.apply_state_commit(&mut transaction, commit);
 					meta_updates.push(MetaUpdate {
 						hash,
 						number,
@@ -1442,7 +1557,7 @@ impl<Block: BlockT> Backend<Block> {
 
 			self.state_usage.merge_sm(operation.old_state.usage_info());
 			// release state reference so that it can be finalized
-			let cache = operation.old_state.into_cache_changes();
+			drop(operation.old_state);
 
 			if finalized {
 				// TODO: ensure best chain contains this block.
@@ -1511,10 +1626,8 @@ impl<Block: BlockT> Backend<Block> {
 						);
 					}
 				} else if number > best_num + One::one() &&
-					number > One::one() && self
-					.blockchain
-					.header(BlockId::hash(parent_hash))?
-					.is_none()
+					number > One::one() &&
+					self.blockchain.header(BlockId::hash(parent_hash))?.is_none()
 				{
 					let gap = (best_num + One::one(), number - One::one());
 					transaction.set(columns::META, meta_keys::BLOCK_GAP, &gap.encode());
@@ -1530,19 +1643,19 @@ impl<Block: BlockT> Backend<Block> {
 				is_finalized: finalized,
 				with_state: operation.commit_state,
 			});
-			Some((pending_block.header, number, hash, enacted, retracted, is_best, cache))
+			Some((pending_block.header, hash))
 		} else {
 			None
 		};
 
-		let cache_update = if let Some(set_head) = operation.set_head {
+		if let Some(set_head) = operation.set_head {
 			if let Some(header) =
 				sc_client_api::blockchain::HeaderBackend::header(&self.blockchain, set_head)?
 			{
 				let number = header.number();
 				let hash = header.hash();
 
-				let (enacted, retracted) = self.set_head_with_transaction(
+				self.set_head_with_transaction(
 					&mut transaction,
 					hash.clone(),
 					(number.clone(), hash.clone()),
@@ -1554,40 +1667,24 @@ impl<Block: BlockT> Backend<Block> {
 					is_finalized: false,
 					with_state: false,
 				});
-				Some((enacted, retracted))
 			} else {
 				return Err(sp_blockchain::Error::UnknownBlock(format!(
 					"Cannot set head {:?}",
 					set_head
 				)))
 			}
-		} else {
-			None
-		};
+		}
 
 		self.storage.db.commit(transaction)?;
 
 		// Apply all in-memory state changes.
 		// Code beyond this point can't fail.
 
-		if let Some((header, number, hash, enacted, retracted, is_best, mut cache)) = imported {
+		if let Some((header, hash)) = imported {
 			trace!(target: "db", "DB Commit done {:?}", hash);
 			let header_metadata = CachedHeaderMetadata::from(&header);
 			self.blockchain.insert_header_metadata(header_metadata.hash, header_metadata);
 			cache_header(&mut self.blockchain.header_cache.lock(), hash, Some(header));
-			cache.sync_cache(
-				&enacted,
-				&retracted,
-				operation.storage_updates,
-				operation.child_storage_updates,
-				Some(hash),
-				Some(number),
-				is_best,
-			);
-		}
-
-		if let Some((enacted, retracted)) = cache_update {
-			self.shared_cache.write().sync(&enacted, &retracted);
 		}
 
 		for m in meta_updates {
@@ -1624,10 +1721,14 @@ impl<Block: BlockT> Backend<Block> {
 				.map(|c| f_num.saturated_into::<u64>() > c)
 				.unwrap_or(true)
 		{
-			let commit = self.storage.state_db.canonicalize_block(&f_hash).map_err(
-				|e: sc_state_db::Error<io::Error>| sp_blockchain::Error::from_state_db(e),
-			)?;
-			apply_state_commit(transaction, commit);
+			let commit = self
+				.storage
+				.state_db
+				.canonicalize_block(&f_hash, &StateMetaDb(&*self.storage.db))
+				.map_err(|e: sc_state_db::Error<io::Error>| {
+					sp_blockchain::Error::from_state_db(e)
+				})?;
+			self.storage.apply_state_commit(transaction, commit);
 		}
 
 		let new_displaced = self.blockchain.leaves.write().finalize_height(f_num);
@@ -1667,6 +1768,7 @@ impl<Block: BlockT> Backend<Block> {
 					match self.blockchain.header(id)? {
 						Some(header) => {
 							self.prune_block(transaction, id)?;
+							transaction.remove(columns::STATE_DIFF, hash.as_ref());
 							number = header.number().saturating_sub(One::one());
 							hash = header.parent_hash().clone();
 						},
@@ -1717,17 +1819,11 @@ impl<Block: BlockT> Backend<Block> {
 		Ok(())
 	}
 
-	fn empty_state(&self) -> ClientResult<SyncingCachingState<RefTrackingState<Block>, Block>> {
+	fn empty_state(&self) -> ClientResult<RecordStatsState<RefTrackingState<Block>, Block>> {
 		let root = EmptyStorage::<Block>::new().0; // Empty trie
 		let db_state = DbState::<Block>::new(self.storage.clone(), root);
 		let state = RefTrackingState::new(db_state, self.storage.clone(), None);
-		let caching_state = CachingState::new(state, self.shared_cache.clone(), None);
-		Ok(SyncingCachingState::new(
-			caching_state,
-			self.state_usage.clone(),
-			self.blockchain.meta.clone(),
-			self.import_lock.clone(),
-		))
+		Ok(RecordStatsState::new(state, None, self.state_usage.clone()))
 	}
 }
 
@@ -1842,12 +1938,11 @@ where
 impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 	type BlockImportOperation = BlockImportOperation<Block>;
 	type Blockchain = BlockchainDb<Block>;
-	type State = SyncingCachingState<RefTrackingState<Block>, Block>;
+	type State = RecordStatsState<RefTrackingState<Block>, Block>;
 	type OffchainStorage = offchain::LocalStorage;
 
 	fn begin_operation(&self) -> ClientResult<Self::BlockImportOperation> {
-		let mut old_state = self.empty_state()?;
-		old_state.disable_syncing();
+		let old_state = self.empty_state()?;
 
 		Ok(BlockImportOperation {
 			pending_block: None,
@@ -1860,6 +1955,8 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 			finalized_blocks: Vec::new(),
 			set_head: None,
 			commit_state: false,
+			full_state: false,
+			written_state: false,
 			index_ops: Default::default(),
 		})
 	}
@@ -1874,7 +1971,6 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 		} else {
 			operation.old_state = self.state_at(block)?;
 		}
-		operation.old_state.disable_syncing();
 
 		operation.commit_state = true;
 		Ok(())
@@ -1975,12 +2071,15 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 			)
 		});
 		let database_cache = MemorySize::from_bytes(0);
-		let state_cache =
-			MemorySize::from_bytes((*&self.shared_cache).read().used_storage_cache_size());
 		let state_db = self.storage.state_db.memory_info();
+		let (trie_cache, trie_cache_hits, trie_cache_misses) = match &self.storage.trie_cache {
+			Some(cache) =>
+				(MemorySize::from_bytes(cache.used_size()), cache.hits(), cache.misses()),
+			None => Default::default(),
+		};
 
 		Some(UsageInfo {
-			memory: MemoryInfo { state_cache, database_cache, state_db },
+			memory: MemoryInfo { trie_cache, database_cache, state_db },
 			io: IoInfo {
 				transactions: io_stats.transactions,
 				bytes_read: io_stats.bytes_read,
@@ -1993,6 +2092,8 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 				state_writes_cache: state_stats.overlay_writes.ops,
 				state_reads_cache: state_stats.cache_reads.ops,
 				state_writes_nodes: state_stats.nodes_writes.ops,
+				trie_cache_hits,
+				trie_cache_misses,
 			},
 		})
 	}
@@ -2041,7 +2142,7 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 
 				match self.storage.state_db.revert_one() {
 					Some(commit) => {
-						apply_state_commit(&mut transaction, commit);
+						self.storage.apply_state_commit(&mut transaction, commit);
 
 						best_number = prev_number;
 						best_hash = prev_hash;
@@ -2081,6 +2182,7 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 						}
 						transaction.set_from_vec(columns::META, meta_keys::BEST_BLOCK, key);
 						transaction.remove(columns::KEY_LOOKUP, removed.hash().as_ref());
+						transaction.remove(columns::STATE_DIFF, removed.hash().as_ref());
 						children::remove_children(
 							&mut transaction,
 							columns::META,
@@ -2146,9 +2248,10 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 
 		let mut transaction = Transaction::new();
 		if let Some(commit) = self.storage.state_db.remove(hash) {
-			apply_state_commit(&mut transaction, commit);
+			self.storage.apply_state_commit(&mut transaction, commit);
 		}
 		transaction.remove(columns::KEY_LOOKUP, hash.as_ref());
+		transaction.remove(columns::STATE_DIFF, hash.as_ref());
 		leaves.revert(hash.clone(), hdr.number);
 		leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
 		self.storage.db.commit(transaction)?;
@@ -2160,6 +2263,28 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 		&self.blockchain
 	}
 
+	fn state_diff(&self, hash: &Block::Hash) -> ClientResult<Option<StateDiff>> {
+		match self.storage.db.get(columns::STATE_DIFF, hash.as_ref()) {
+			Some(diff) => StateDiff::decode(&mut &diff[..]).map(Some).map_err(|e| {
+				sp_blockchain::Error::Backend(format!("Error decoding state diff: {}", e))
+			}),
+			None => Ok(None),
+		}
+	}
+
+	fn write_state_nodes(&self, nodes: Vec<(Vec<u8>, Vec<u8>)>) -> ClientResult<()> {
+		let mut transaction = Transaction::new();
+		for (mut key, value) in nodes {
+			if !self.storage.prefix_keys {
+				// Strip prefix
+				key.drain(0..key.len() - DB_HASH_LEN);
+			}
+			transaction.set_from_vec(columns::STATE, &key, value);
+		}
+		self.storage.db.commit(transaction)?;
+		Ok(())
+	}
+
 	fn state_at(&self, block: BlockId<Block>) -> ClientResult<Self::State> {
 		use sc_client_api::blockchain::HeaderBackend as BcHeaderBackend;
 
@@ -2173,15 +2298,7 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 				let root = genesis_state.root.clone();
 				let db_state = DbState::<Block>::new(genesis_state.clone(), root);
 				let state = RefTrackingState::new(db_state, self.storage.clone(), None);
-				let caching_state = CachingState::new(state, self.shared_cache.clone(), None);
-				let mut state = SyncingCachingState::new(
-					caching_state,
-					self.state_usage.clone(),
-					self.blockchain.meta.clone(),
-					self.import_lock.clone(),
-				);
-				state.disable_syncing();
-				return Ok(state)
+				return Ok(RecordStatsState::new(state, None, self.state_usage.clone()))
 			}
 		}
 
@@ -2200,19 +2317,22 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 						block
 					)))
 				}
-				if let Ok(()) = self.storage.state_db.pin(&hash) {
+				// The state-db is locked while the hint is called, so the backing database
+				// has to be queried directly.
+				let hint = || {
+					let key = if self.storage.prefix_keys {
+						prefixed_key::<HashFor<Block>>(&hdr.state_root, (&[], None))
+					} else {
+						hdr.state_root.as_ref().to_vec()
+					};
+					self.storage.db.contains(columns::STATE, &key)
+				};
+				if let Ok(()) = self.storage.state_db.pin(&hash, hint) {
 					let root = hdr.state_root;
 					let db_state = DbState::<Block>::new(self.storage.clone(), root);
 					let state =
 						RefTrackingState::new(db_state, self.storage.clone(), Some(hash.clone()));
-					let caching_state =
-						CachingState::new(state, self.shared_cache.clone(), Some(hash));
-					Ok(SyncingCachingState::new(
-						caching_state,
-						self.state_usage.clone(),
-						self.blockchain.meta.clone(),
-						self.import_lock.clone(),
-					))
+					Ok(RecordStatsState::new(state, Some(hash), self.state_usage.clone()))
 				} else {
 					Err(sp_blockchain::Error::UnknownBlock(format!(
 						"State already discarded for {:?}",
@@ -2237,7 +2357,20 @@ impl<Block: BlockT> sc_client_api::backend::Backend<Block> for Backend<Block> {
 				_ => false,
 			}
 		} else {
-			!self.storage.state_db.is_pruned(hash, number.saturated_into::<u64>())
+			match self.storage.state_db.is_pruned(hash, number.saturated_into::<u64>()) {
+				IsPruned::Pruned => false,
+				IsPruned::NotPruned => true,
+				IsPruned::MaybePruned => match self.blockchain.header_metadata(hash.clone()) {
+					Ok(header) => sp_state_machine::Storage::get(
+						self.storage.as_ref(),
+						&header.state_root,
+						(&[], None),
+					)
+					.unwrap_or(None)
+					.is_some(),
+					_ => false,
+				},
+			}
 		}
 	}
 
@@ -2359,10 +2492,12 @@ pub(crate) mod tests {
 			DatabaseSettings {
 				state_cache_size: 16777216,
 				state_cache_child_ratio: Some((50, 100)),
+				trie_cache_size: 16777216,
 				state_pruning: PruningMode::keep_blocks(1),
 				source: DatabaseSource::Custom(backing),
 				keep_blocks: KeepBlocks::All,
 				transaction_storage: TransactionStorageMode::BlockBody,
+				state_diff_index: false,
 			},
 			0,
 		)
@@ -2375,6 +2510,11 @@ pub(crate) mod tests {
 
 	#[test]
 	fn set_state_data() {
+		set_state_data_inner(StateVersion::V0);
+		set_state_data_inner(StateVersion::V1);
+	}
+
+	fn set_state_data_inner(state_version: StateVersion) {
 		let db = Backend::<Block>::new_test(2, 0);
 		let hash = {
 			let mut op = db.begin_operation().unwrap();
@@ -2390,15 +2530,18 @@ pub(crate) mod tests {
 
 			header.state_root = op
 				.old_state
-				.storage_root(storage.iter().map(|(x, y)| (&x[..], Some(&y[..]))))
+				.storage_root(storage.iter().map(|(x, y)| (&x[..], Some(&y[..]))), state_version)
 				.0
 				.into();
 			let hash = header.hash();
 
-			op.reset_storage(Storage {
-				top: storage.into_iter().collect(),
-				children_default: Default::default(),
-			})
+			op.reset_storage(
+				Storage {
+					top: storage.into_iter().collect(),
+					children_default: Default::default(),
+				},
+				state_version,
+			)
 			.unwrap();
 			op.set_block_data(header.clone(), Some(vec![]), None, None, NewBlockState::Best)
 				.unwrap();
@@ -2427,9 +2570,10 @@ pub(crate) mod tests {
 
 			let storage = vec![(vec![1, 3, 5], None), (vec![5, 5, 5], Some(vec![4, 5, 6]))];
 
-			let (root, overlay) = op
-				.old_state
-				.storage_root(storage.iter().map(|(k, v)| (&k[..], v.as_ref().map(|v| &v[..]))));
+			let (root, overlay) = op.old_state.storage_root(
+				storage.iter().map(|(k, v)| (&k[..], v.as_ref().map(|v| &v[..]))),
+				state_version,
+			);
 			op.update_db_storage(overlay).unwrap();
 			header.state_root = root.into();
 
@@ -2447,8 +2591,180 @@ pub(crate) mod tests {
 		}
 	}
 
+	#[test]
+	fn state_diff_index_records_written_keys() {
+		let db = Backend::<Block>::new_test(2, 10);
+		let child_info = ChildInfo::new_default(b"child");
+		let hash0 = {
+			let mut op = db.begin_operation().unwrap();
+			let mut header = Header {
+				number: 0,
+				parent_hash: Default::default(),
+				state_root: Default::default(),
+				digest: Default::default(),
+				extrinsics_root: Default::default(),
+			};
+			header.state_root =
+				op.old_state.storage_root(std::iter::empty(), StateVersion::V1).0.into();
+			let hash = header.hash();
+			op.reset_storage(Default::default(), StateVersion::V1).unwrap();
+			op.set_block_data(header, Some(vec![]), None, None, NewBlockState::Final)
+				.unwrap();
+			db.commit_operation(op).unwrap();
+			hash
+		};
+
+		let hash1 = {
+			let mut op = db.begin_operation().unwrap();
+			db.begin_state_operation(&mut op, BlockId::Hash(hash0)).unwrap();
+			let mut header = Header {
+				number: 1,
+				parent_hash: hash0,
+				state_root: Default::default(),
+				digest: Default::default(),
+				extrinsics_root: Default::default(),
+			};
+			let storage = vec![(vec![5, 5, 5], Some(vec![4, 5, 6])), (vec![1, 3, 5], None)];
+			let (root, overlay) = op.old_state.storage_root(
+				storage.iter().map(|(k, v)| (&k[..], v.as_ref().map(|v| &v[..]))),
+				StateVersion::V1,
+			);
+			op.update_db_storage(overlay).unwrap();
+			header.state_root = root.into();
+			let child_storage = vec![(b"child".to_vec(), vec![(vec![7], Some(vec![7]))])];
+			op.update_storage(storage, child_storage).unwrap();
+			let hash = header.hash();
+			op.set_block_data(header, Some(vec![]), None, None, NewBlockState::Best)
+				.unwrap();
+			db.commit_operation(op).unwrap();
+			hash
+		};
+
+		// full state imports are not indexed.
+		assert_eq!(db.state_diff(&hash0).unwrap(), None);
+
+		let diff = db.state_diff(&hash1).unwrap().unwrap();
+		assert_eq!(
+			diff,
+			StateDiff {
+				top: vec![vec![1, 3, 5], vec![5, 5, 5]],
+				children: vec![(b"child".to_vec(), vec![vec![7]])],
+			}
+		);
+		assert!(diff.contains(None, &[1, 3, 5]));
+		assert!(!diff.contains(None, &[7]));
+		assert!(diff.contains(Some(&child_info), &[7]));
+		assert!(!diff.contains(Some(&ChildInfo::new_default(b"other")), &[7]));
+
+		assert_eq!(db.revert(1, false).unwrap().0, 1);
+		assert_eq!(db.state_diff(&hash1).unwrap(), None);
+	}
+
+	#[test]
+	fn trie_cache_is_shared_by_forks() {
+		let backend = Backend::<Block>::new(
+			DatabaseSettings {
+				state_cache_size: 0,
+				state_cache_child_ratio: None,
+				trie_cache_size: 16777216,
+				state_pruning: PruningMode::keep_blocks(2),
+				source: DatabaseSource::Custom(sp_database::as_database(kvdb_memorydb::create(
+					crate::utils::NUM_COLUMNS,
+				))),
+				keep_blocks: KeepBlocks::All,
+				transaction_storage: TransactionStorageMode::BlockBody,
+				state_diff_index: false,
+			},
+			10,
+		)
+		.unwrap();
+		let insert = |number, parent_hash, storage: Vec<(Vec<u8>, Option<Vec<u8>>)>| {
+			let mut op = backend.begin_operation().unwrap();
+			backend.begin_state_operation(&mut op, BlockId::Hash(parent_hash)).unwrap();
+			let (root, overlay) = op.old_state.storage_root(
+				storage.iter().map(|(k, v)| (&k[..], v.as_ref().map(|v| &v[..]))),
+				StateVersion::V1,
+			);
+			let header = Header {
+				number,
+				parent_hash,
+				state_root: root,
+				digest: Default::default(),
+				extrinsics_root: Default::default(),
+			};
+			if number == 0 {
+				let top = storage.into_iter().map(|(k, v)| (k, v.unwrap())).collect();
+				op.reset_storage(
+					Storage { top, children_default: Default::default() },
+					StateVersion::V1,
+				)
+				.unwrap();
+			} else {
+				op.update_db_storage(overlay).unwrap();
+				op.update_storage(storage, Vec::new()).unwrap();
+			}
+			let hash = header.hash();
+			op.set_block_data(header, Some(vec![]), None, None, NewBlockState::Best)
+				.unwrap();
+			backend.commit_operation(op).unwrap();
+			hash
+		};
+		let cache_stats = || {
+			let usage = backend.usage_info().unwrap();
+			(usage.io.trie_cache_hits, usage.io.trie_cache_misses)
+		};
+
+		let storage = vec![
+			(vec![1, 2, 3], Some(vec![1; 40])),
+			(vec![1, 3, 5], Some(vec![2; 40])),
+			(vec![4], Some(vec![3; 40])),
+		];
+		let hash0 = insert(0, Default::default(), storage);
+		let hash1a = insert(1, hash0, vec![(vec![4], Some(vec![4; 40]))]);
+		let hash1b = insert(1, hash0, vec![(vec![4], Some(vec![5; 40]))]);
+
+		let state = backend.state_at(BlockId::Hash(hash1a)).unwrap();
+		assert_eq!(state.storage(&[1, 2, 3]).unwrap(), Some(vec![1; 40]));
+		assert_eq!(state.storage(&[4]).unwrap(), Some(vec![4; 40]));
+		let (hits, misses) = cache_stats();
+		assert!(misses > 0);
+		assert!(backend.usage_info().unwrap().memory.trie_cache.as_bytes() > 0);
+
+		drop(state);
+
+		// Only the root, the changed leaf and its value node of the other fork are read from the
+		// database.
+		let state = backend.state_at(BlockId::Hash(hash1b)).unwrap();
+		assert_eq!(state.storage(&[1, 2, 3]).unwrap(), Some(vec![1; 40]));
+		assert_eq!(state.storage(&[4]).unwrap(), Some(vec![5; 40]));
+		let (fork_hits, fork_misses) = cache_stats();
+		assert!(fork_hits > hits);
+		assert_eq!(fork_misses, misses + 3);
+
+		drop(state);
+
+		// Nodes deleted by the state pruning are evicted.
+		let root1a =
+			backend.blockchain().header(BlockId::Hash(hash1a)).unwrap().unwrap().state_root;
+		let cache = backend.storage.trie_cache.clone().unwrap();
+		assert!(cache.get(&root1a).is_some());
+		backend.finalize_block(BlockId::Hash(hash1a), None).unwrap();
+		let mut parent = hash1a;
+		for number in 2..8 {
+			parent = insert(number, parent, vec![(vec![4], Some(vec![number as u8; 40]))]);
+			backend.finalize_block(BlockId::Hash(parent), None).unwrap();
+		}
+		assert!(!backend.have_state_at(&hash1a, 1));
+		assert!(cache.get(&root1a).is_none());
+	}
+
 	#[test]
 	fn delete_only_when_negative_rc() {
+		delete_only_when_negative_rc_inner(StateVersion::V0);
+		delete_only_when_negative_rc_inner(StateVersion::V1);
+	}
+
+	fn delete_only_when_negative_rc_inner(state_version: StateVersion) {
 		sp_tracing::try_init_simple();
 		let key;
 		let backend = Backend::<Block>::new_test(1, 0);
@@ -2466,13 +2782,14 @@ pub(crate) mod tests {
 				extrinsics_root: Default::default(),
 			};
 
-			header.state_root = op.old_state.storage_root(std::iter::empty()).0.into();
+			header.state_root =
+				op.old_state.storage_root(std::iter::empty(), state_version).0.into();
 			let hash = header.hash();
 
-			op.reset_storage(Storage {
-				top: Default::default(),
-				children_default: Default::default(),
-			})
+			op.reset_storage(
+				Storage { top: Default::default(), children_default: Default::default() },
+				state_version,
+			)
 			.unwrap();
 
 			key = op.db_updates.insert(EMPTY_PREFIX, b"hello");
@@ -2506,7 +2823,7 @@ pub(crate) mod tests {
 
 			header.state_root = op
 				.old_state
-				.storage_root(storage.iter().cloned().map(|(x, y)| (x, Some(y))))
+				.storage_root(storage.iter().cloned().map(|(x, y)| (x, Some(y))), state_version)
 				.0
 				.into();
 			let hash = header.hash();
@@ -2543,7 +2860,7 @@ pub(crate) mod tests {
 
 			header.state_root = op
 				.old_state
-				.storage_root(storage.iter().cloned().map(|(x, y)| (x, Some(y))))
+				.storage_root(storage.iter().cloned().map(|(x, y)| (x, Some(y))), state_version)
 				.0
 				.into();
 			let hash = header.hash();
@@ -2577,7 +2894,7 @@ pub(crate) mod tests {
 
 			header.state_root = op
 				.old_state
-				.storage_root(storage.iter().cloned().map(|(x, y)| (x, Some(y))))
+				.storage_root(storage.iter().cloned().map(|(x, y)| (x, Some(y))), state_version)
 				.0
 				.into();
 
@@ -2912,6 +3229,11 @@ pub(crate) mod tests {
 
 	#[test]
 	fn storage_hash_is_cached_correctly() {
+		storage_hash_is_cached_correctly_inner(StateVersion::V0);
+		storage_hash_is_cached_correctly_inner(StateVersion::V1);
+	}
+
+	fn storage_hash_is_cached_correctly_inner(state_version: StateVersion) {
 		let backend = Backend::<Block>::new_test(10, 10);
 
 		let hash0 = {
@@ -2931,15 +3253,18 @@ pub(crate) mod tests {
 
 			header.state_root = op
 				.old_state
-				.storage_root(storage.iter().map(|(x, y)| (&x[..], Some(&y[..]))))
+				.storage_root(storage.iter().map(|(x, y)| (&x[..], Some(&y[..]))), state_version)
 				.0
 				.into();
 			let hash = header.hash();
 
-			op.reset_storage(Storage {
-				top: storage.into_iter().collect(),
-				children_default: Default::default(),
-			})
+			op.reset_storage(
+				Storage {
+					top: storage.into_iter().collect(),
+					children_default: Default::default(),
+				},
+				state_version,
+			)
 			.unwrap();
 			op.set_block_data(header.clone(), Some(vec![]), None, None, NewBlockState::Best)
 				.unwrap();
@@ -2968,9 +3293,10 @@ pub(crate) mod tests {
 
 			let storage = vec![(b"test".to_vec(), Some(b"test2".to_vec()))];
 
-			let (root, overlay) = op
-				.old_state
-				.storage_root(storage.iter().map(|(k, v)| (&k[..], v.as_ref().map(|v| &v[..]))));
+			let (root, overlay) = op.old_state.storage_root(
+				storage.iter().map(|(k, v)| (&k[..], v.as_ref().map(|v| &v[..]))),
+				state_version,
+			);
 			op.update_db_storage(overlay).unwrap();
 			header.state_root = root.into();
 			let hash = header.hash();
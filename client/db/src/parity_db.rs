@@ -20,7 +20,7 @@ use crate::{
 	utils::{DatabaseType, NUM_COLUMNS},
 };
 /// A `Database` adapter for parity-db.
-use sp_database::{error::DatabaseError, Change, ColumnId, Database, Transaction};
+use sp_database::{error::DatabaseError, Change, ColumnId, ColumnIter, Database, Transaction};
 
 struct DbAdapter(parity_db::Db);
 
@@ -98,4 +98,9 @@ impl<H: Clone + AsRef<[u8]>> Database<H> for DbAdapter {
 	fn supports_ref_counting(&self) -> bool {
 		true
 	}
+
+	// Columns are indexed by the hashes of the keys, the keys themselves are not stored.
+	fn iter<'a>(&'a self, _col: ColumnId) -> Option<ColumnIter<'a>> {
+		None
+	}
 }
@@ -0,0 +1,237 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Global trie node cache. Maintains recently read trie nodes, keyed by their hash.
+//!
+//! A node is identified by the hash of its encoding, so a cached node is valid for every
+//! state that references it. The cache is shared by all the forks and never has to be
+//! invalidated when the best chain changes. Nodes deleted by the state pruning are evicted.
+//!
+//! The cache is split into shards, each with its own lock and an equal part of the size limit,
+//! so that concurrent lookups of different nodes rarely contend.
+
+use linked_hash_map::LinkedHashMap;
+use parking_lot::Mutex;
+use sp_state_machine::DBValue;
+use std::{hash::Hash as StdHash, mem, sync::Arc};
+
+/// Number of shards of the shared cache. Nodes are assigned to a shard by the first byte of
+/// their hash.
+const SHARDS: usize = 16;
+
+/// Shared trie node cache.
+pub struct SharedTrieCache<H> {
+	shards: Arc<[Mutex<TrieNodeCache<H>>]>,
+}
+
+impl<H> Clone for SharedTrieCache<H> {
+	fn clone(&self) -> Self {
+		SharedTrieCache { shards: self.shards.clone() }
+	}
+}
+
+impl<H: AsRef<[u8]> + StdHash + Eq> SharedTrieCache<H> {
+	/// Create an empty cache, using at most `size_limit` bytes.
+	pub fn new(size_limit: usize) -> Self {
+		let shards = (0..SHARDS).map(|_| Mutex::new(TrieNodeCache::new(size_limit / SHARDS)));
+		SharedTrieCache { shards: shards.collect() }
+	}
+
+	fn shard(&self, hash: &H) -> &Mutex<TrieNodeCache<H>> {
+		let byte = hash.as_ref().first().copied().unwrap_or_default();
+		&self.shards[byte as usize % SHARDS]
+	}
+
+	/// Look up the node with the given hash.
+	pub fn get(&self, hash: &H) -> Option<DBValue> {
+		self.shard(hash).lock().get(hash)
+	}
+
+	/// Add a node read from the database.
+	pub fn insert(&self, hash: H, node: DBValue) {
+		self.shard(&hash).lock().insert(hash, node)
+	}
+
+	/// Evict the node with the given hash, if it is cached.
+	pub fn remove(&self, hash: &H) {
+		self.shard(hash).lock().remove(hash)
+	}
+
+	/// Memory used by the cached nodes of all the shards, in bytes.
+	pub fn used_size(&self) -> usize {
+		self.shards.iter().map(|shard| shard.lock().used_size()).sum()
+	}
+
+	/// Number of lookups that found the node in the cache.
+	pub fn hits(&self) -> u64 {
+		self.shards.iter().map(|shard| shard.lock().hits()).sum()
+	}
+
+	/// Number of lookups that had to go to the database.
+	pub fn misses(&self) -> u64 {
+		self.shards.iter().map(|shard| shard.lock().misses()).sum()
+	}
+}
+
+/// Least recently used cache of encoded trie nodes.
+pub struct TrieNodeCache<H> {
+	nodes: LinkedHashMap<H, DBValue>,
+	used_size: usize,
+	size_limit: usize,
+	hits: u64,
+	misses: u64,
+}
+
+impl<H: StdHash + Eq> TrieNodeCache<H> {
+	/// Create an empty cache, using at most `size_limit` bytes.
+	pub fn new(size_limit: usize) -> Self {
+		TrieNodeCache { nodes: LinkedHashMap::new(), used_size: 0, size_limit, hits: 0, misses: 0 }
+	}
+
+	/// Memory used by the cached nodes, in bytes.
+	///
+	/// This accounts for the keys, the node encodings and the bookkeeping of the map.
+	pub fn used_size(&self) -> usize {
+		self.used_size
+	}
+
+	/// Number of lookups that found the node in the cache.
+	pub fn hits(&self) -> u64 {
+		self.hits
+	}
+
+	/// Number of lookups that had to go to the database.
+	pub fn misses(&self) -> u64 {
+		self.misses
+	}
+
+	/// Look up the node with the given hash, marking it as recently used.
+	pub fn get(&mut self, hash: &H) -> Option<DBValue> {
+		match self.nodes.get_refresh(hash) {
+			Some(node) => {
+				self.hits += 1;
+				Some(node.clone())
+			},
+			None => {
+				self.misses += 1;
+				None
+			},
+		}
+	}
+
+	/// Add a node read from the database, evicting the least recently used nodes if the cache
+	/// is full. Nodes that don't fit in the cache at all are ignored.
+	pub fn insert(&mut self, hash: H, node: DBValue) {
+		let size = entry_size::<H>(&node);
+		if size > self.size_limit {
+			return
+		}
+		if let Some(old) = self.nodes.insert(hash, node) {
+			self.used_size -= entry_size::<H>(&old);
+		}
+		self.used_size += size;
+		while self.used_size > self.size_limit {
+			match self.nodes.pop_front() {
+				Some((_, evicted)) => self.used_size -= entry_size::<H>(&evicted),
+				None => break,
+			}
+		}
+	}
+
+	/// Evict the node with the given hash, if it is cached.
+	pub fn remove(&mut self, hash: &H) {
+		if let Some(node) = self.nodes.remove(hash) {
+			self.used_size -= entry_size::<H>(&node);
+		}
+	}
+}
+
+/// Memory used by a cache entry: the heap allocated list node holding the key, the value and the
+/// two list links, the map slot pointing to it, and the node encoding itself.
+fn entry_size<H>(node: &DBValue) -> usize {
+	mem::size_of::<H>() + mem::size_of::<DBValue>() + 4 * mem::size_of::<usize>() + node.capacity()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn node(len: usize) -> DBValue {
+		vec![0; len]
+	}
+
+	#[test]
+	fn size_is_accounted_per_byte() {
+		let mut cache = TrieNodeCache::<u64>::new(1024);
+		cache.insert(1, node(10));
+		cache.insert(2, node(20));
+		assert_eq!(cache.used_size(), entry_size::<u64>(&node(10)) + entry_size::<u64>(&node(20)));
+
+		cache.insert(1, node(30));
+		assert_eq!(cache.used_size(), entry_size::<u64>(&node(30)) + entry_size::<u64>(&node(20)));
+	}
+
+	#[test]
+	fn least_recently_used_nodes_are_evicted() {
+		let limit = 3 * entry_size::<u64>(&node(100));
+		let mut cache = TrieNodeCache::<u64>::new(limit);
+		cache.insert(1, node(100));
+		cache.insert(2, node(100));
+		cache.insert(3, node(100));
+		assert!(cache.get(&1).is_some());
+
+		cache.insert(4, node(100));
+		assert!(cache.used_size() <= limit);
+		assert!(cache.get(&2).is_none());
+		assert!(cache.get(&1).is_some());
+		assert!(cache.get(&3).is_some());
+		assert!(cache.get(&4).is_some());
+		assert_eq!((cache.hits(), cache.misses()), (4, 1));
+
+		// Too large to be cached at all.
+		cache.insert(5, node(limit));
+		assert!(cache.get(&5).is_none());
+		assert!(cache.get(&4).is_some());
+	}
+
+	#[test]
+	fn removed_nodes_are_not_accounted() {
+		let mut cache = TrieNodeCache::<u64>::new(1024);
+		cache.insert(1, node(10));
+		cache.insert(2, node(20));
+		cache.remove(&1);
+		cache.remove(&3);
+		assert!(cache.get(&1).is_none());
+		assert_eq!(cache.used_size(), entry_size::<u64>(&node(20)));
+	}
+
+	#[test]
+	fn shared_cache_spreads_nodes_over_shards() {
+		let cache = SharedTrieCache::<[u8; 32]>::new(SHARDS * 4096);
+		for byte in 0..=255u8 {
+			cache.insert([byte; 32], node(10));
+		}
+		assert_eq!(cache.used_size(), 256 * entry_size::<[u8; 32]>(&node(10)));
+		assert!(cache.shards.iter().all(|shard| shard.lock().used_size() > 0));
+
+		assert!(cache.get(&[7; 32]).is_some());
+		cache.remove(&[7; 32]);
+		assert!(cache.get(&[7; 32]).is_none());
+		assert_eq!((cache.hits(), cache.misses()), (1, 1));
+	}
+}
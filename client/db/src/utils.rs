@@ -34,12 +34,6 @@ use sp_trie::DBValue;
 
 /// Number of columns in the db. Must be the same for both full && light dbs.
 /// Otherwise RocksDb will fail to open database && check its type.
-#[cfg(any(
-	feature = "with-kvdb-rocksdb",
-	feature = "with-parity-db",
-	feature = "test-helpers",
-	test
-))]
 pub const NUM_COLUMNS: u32 = 12;
 /// Meta column. The set of keys in the column is shared by full && light storages.
 pub const COLUMN_META: u32 = 0;
@@ -65,7 +59,7 @@ pub mod meta_keys {
 }
 
 /// Database metadata.
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct Meta<N, H> {
 	/// Hash of the best known block.
 	pub best_hash: H,
@@ -196,7 +190,7 @@ pub fn open_database<Block: BlockT>(
 	open_database_at::<Block>(&config.source, db_type)
 }
 
-fn open_database_at<Block: BlockT>(
+pub(crate) fn open_database_at<Block: BlockT>(
 	source: &DatabaseSource,
 	db_type: DatabaseType,
 ) -> sp_blockchain::Result<Arc<dyn Database<DbHash>>> {
@@ -686,10 +680,12 @@ mod tests {
 		DatabaseSettings {
 			state_cache_size: 0,
 			state_cache_child_ratio: None,
+			trie_cache_size: 0,
 			state_pruning: PruningMode::ArchiveAll,
 			source,
 			keep_blocks: KeepBlocks::All,
 			transaction_storage: TransactionStorageMode::BlockBody,
+			state_diff_index: false,
 		}
 	}
 
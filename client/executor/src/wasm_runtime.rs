@@ -380,12 +380,24 @@ pub fn read_embedded_version(blob: &RuntimeBlob) -> Result<Option<RuntimeVersion
 		// of a special API in the `apis` field to treat the input as a non-legacy version. However
 		// the structure found in the `runtime_version` always contain an empty `apis` field.
 		// Therefore the version read will be mistakenly treated as an legacy one.
-		let mut decoded_version = sp_api::RuntimeVersion::decode(&mut version_section)
-			.map_err(|_| WasmError::Instantiation("failed to decode version section".into()))?;
-
-		// Don't stop on this and check if there is a special section that encodes all runtime APIs.
-		if let Some(apis_section) = blob.custom_section_contents("runtime_apis") {
-			decoded_version.apis = decode_runtime_apis(apis_section)?.into();
+		//
+		// The fields present in the version section depend on the version of the `Core` api, so
+		// we need to read the special section that encodes all runtime APIs first. The `apis`
+		// field of the version section itself is always empty.
+		let apis = blob
+			.custom_section_contents("runtime_apis")
+			.map(decode_runtime_apis)
+			.transpose()?
+			.map(Into::into);
+
+		// Without the runtime apis section we assume the latest layout of the version section.
+		let core_version = apis.as_ref().and_then(sp_version::core_version_from_apis).or(Some(4));
+		let mut decoded_version =
+			sp_api::RuntimeVersion::decode_with_version_hint(&mut version_section, core_version)
+				.map_err(|_| WasmError::Instantiation("failed to decode version section".into()))?;
+
+		if let Some(apis) = apis {
+			decoded_version.apis = apis;
 		}
 
 		Ok(Some(decoded_version))
@@ -507,10 +519,27 @@ mod tests {
 			impl_version: 1,
 			apis: sp_api::create_apis_vec!([(<dyn Core::<Block>>::ID, 3)]),
 			transaction_version: 3,
+			state_version: 4,
+		};
+
+		let version = decode_version(&old_runtime_version.encode()).unwrap();
+		assert_eq!(3, version.transaction_version);
+		assert_eq!(0, version.state_version);
+
+		let old_runtime_version = sp_api::RuntimeVersion {
+			spec_name: "test".into(),
+			impl_name: "test".into(),
+			authoring_version: 1,
+			spec_version: 1,
+			impl_version: 1,
+			apis: sp_api::create_apis_vec!([(<dyn Core::<Block>>::ID, 4)]),
+			transaction_version: 3,
+			state_version: 4,
 		};
 
 		let version = decode_version(&old_runtime_version.encode()).unwrap();
 		assert_eq!(3, version.transaction_version);
+		assert_eq!(4, version.state_version);
 	}
 
 	#[test]
@@ -527,8 +556,9 @@ mod tests {
 			authoring_version: 100,
 			spec_version: 100,
 			impl_version: 100,
-			apis: sp_api::create_apis_vec!([(<dyn Core::<Block>>::ID, 3)]),
+			apis: sp_api::create_apis_vec!([(<dyn Core::<Block>>::ID, 4)]),
 			transaction_version: 100,
+			state_version: 1,
 		};
 
 		let embedded = sp_version::embed::embed_runtime_version(&wasm, runtime_version.clone())
@@ -30,12 +30,15 @@ mod finality;
 mod notification;
 mod report;
 
-use sc_finality_grandpa::GrandpaJustificationStream;
+use sc_finality_grandpa::{GrandpaEquivocationStream, GrandpaJustificationStream};
 use sp_runtime::traits::{Block as BlockT, NumberFor};
 
 use finality::{EncodedFinalityProof, RpcFinalityProofProvider};
-use notification::JustificationNotification;
-use report::{ReportAuthoritySet, ReportVoterState, ReportedRoundStates};
+use notification::{EquivocationNotification, JustificationNotification};
+use report::{
+	ReportAuthoritySet, ReportRoundHistory, ReportVoterState, ReportedRoundParticipation,
+	ReportedRoundStates,
+};
 
 type FutureResult<T> = jsonrpc_core::BoxFuture<Result<T, jsonrpc_core::Error>>;
 
@@ -50,6 +53,12 @@ pub trait GrandpaApi<Notification, Hash, Number> {
 	#[rpc(name = "grandpa_roundState")]
 	fn round_state(&self) -> FutureResult<ReportedRoundStates>;
 
+	/// Returns the prevote and precommit participation of each authority in the
+	/// most recently completed rounds, oldest round first. Authorities are listed
+	/// in the order of the set, i.e. by the index labelling their vote latency metric.
+	#[rpc(name = "grandpa_roundHistory")]
+	fn round_history(&self) -> FutureResult<Vec<ReportedRoundParticipation>>;
+
 	/// Returns the block most recently finalized by Grandpa, alongside
 	/// side its justification.
 	#[pubsub(
@@ -75,6 +84,31 @@ pub trait GrandpaApi<Notification, Hash, Number> {
 		id: SubscriptionId,
 	) -> jsonrpc_core::Result<bool>;
 
+	/// Returns the equivocations detected by the local voter as they happen,
+	/// whether or not they could be reported on-chain.
+	#[pubsub(
+		subscription = "grandpa_equivocations",
+		subscribe,
+		name = "grandpa_subscribeEquivocations"
+	)]
+	fn subscribe_equivocations(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<EquivocationNotification>,
+	);
+
+	/// Unsubscribe from receiving notifications about detected equivocations.
+	#[pubsub(
+		subscription = "grandpa_equivocations",
+		unsubscribe,
+		name = "grandpa_unsubscribeEquivocations"
+	)]
+	fn unsubscribe_equivocations(
+		&self,
+		metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> jsonrpc_core::Result<bool>;
+
 	/// Prove finality for the given block number by returning the Justification for the last block
 	/// in the set and all the intermediary headers to link them together.
 	#[rpc(name = "grandpa_proveFinality")]
@@ -82,22 +116,26 @@ pub trait GrandpaApi<Notification, Hash, Number> {
 }
 
 /// Implements the GrandpaApi RPC trait for interacting with GRANDPA.
-pub struct GrandpaRpcHandler<AuthoritySet, VoterState, Block: BlockT, ProofProvider> {
+pub struct GrandpaRpcHandler<AuthoritySet, VoterState, RoundHistory, Block: BlockT, ProofProvider> {
 	authority_set: AuthoritySet,
 	voter_state: VoterState,
+	round_history: RoundHistory,
 	justification_stream: GrandpaJustificationStream<Block>,
+	equivocation_stream: GrandpaEquivocationStream<Block>,
 	manager: SubscriptionManager,
 	finality_proof_provider: Arc<ProofProvider>,
 }
 
-impl<AuthoritySet, VoterState, Block: BlockT, ProofProvider>
-	GrandpaRpcHandler<AuthoritySet, VoterState, Block, ProofProvider>
+impl<AuthoritySet, VoterState, RoundHistory, Block: BlockT, ProofProvider>
+	GrandpaRpcHandler<AuthoritySet, VoterState, RoundHistory, Block, ProofProvider>
 {
 	/// Creates a new GrandpaRpcHandler instance.
 	pub fn new<E>(
 		authority_set: AuthoritySet,
 		voter_state: VoterState,
+		round_history: RoundHistory,
 		justification_stream: GrandpaJustificationStream<Block>,
+		equivocation_stream: GrandpaEquivocationStream<Block>,
 		executor: E,
 		finality_proof_provider: Arc<ProofProvider>,
 	) -> Self
@@ -105,16 +143,25 @@ impl<AuthoritySet, VoterState, Block: BlockT, ProofProvider>
 		E: Spawn + Sync + Send + 'static,
 	{
 		let manager = SubscriptionManager::new(Arc::new(executor));
-		Self { authority_set, voter_state, justification_stream, manager, finality_proof_provider }
+		Self {
+			authority_set,
+			voter_state,
+			round_history,
+			justification_stream,
+			equivocation_stream,
+			manager,
+			finality_proof_provider,
+		}
 	}
 }
 
-impl<AuthoritySet, VoterState, Block, ProofProvider>
+impl<AuthoritySet, VoterState, RoundHistory, Block, ProofProvider>
 	GrandpaApi<JustificationNotification, Block::Hash, NumberFor<Block>>
-	for GrandpaRpcHandler<AuthoritySet, VoterState, Block, ProofProvider>
+	for GrandpaRpcHandler<AuthoritySet, VoterState, RoundHistory, Block, ProofProvider>
 where
 	VoterState: ReportVoterState + Send + Sync + 'static,
 	AuthoritySet: ReportAuthoritySet + Send + Sync + 'static,
+	RoundHistory: ReportRoundHistory + Send + Sync + 'static,
 	Block: BlockT,
 	ProofProvider: RpcFinalityProofProvider<Block> + Send + Sync + 'static,
 {
@@ -126,6 +173,11 @@ where
 		future.map_err(jsonrpc_core::Error::from).boxed()
 	}
 
+	fn round_history(&self) -> FutureResult<Vec<ReportedRoundParticipation>> {
+		let round_history = ReportedRoundParticipation::history(&self.round_history);
+		async move { Ok(round_history) }.boxed()
+	}
+
 	fn subscribe_justifications(
 		&self,
 		_metadata: Self::Metadata,
@@ -151,6 +203,31 @@ where
 		Ok(self.manager.cancel(id))
 	}
 
+	fn subscribe_equivocations(
+		&self,
+		_metadata: Self::Metadata,
+		subscriber: Subscriber<EquivocationNotification>,
+	) {
+		let stream = self
+			.equivocation_stream
+			.subscribe()
+			.map(|x| Ok(Ok::<_, jsonrpc_core::Error>(EquivocationNotification::from(x))));
+
+		self.manager.add(subscriber, |sink| {
+			stream
+				.forward(sink.sink_map_err(|e| warn!("Error sending notifications: {:?}", e)))
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_equivocations(
+		&self,
+		_metadata: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> jsonrpc_core::Result<bool> {
+		Ok(self.manager.cancel(id))
+	}
+
 	fn prove_finality(
 		&self,
 		block: NumberFor<Block>,
@@ -176,7 +253,8 @@ mod tests {
 	use parity_scale_codec::{Decode, Encode};
 	use sc_block_builder::{BlockBuilder, RecordProof};
 	use sc_finality_grandpa::{
-		report, AuthorityId, FinalityProof, GrandpaJustification, GrandpaJustificationSender,
+		report, AuthorityId, FinalityProof, GrandpaEquivocationSender, GrandpaJustification,
+		GrandpaJustificationSender, RoundParticipation,
 	};
 	use sp_blockchain::HeaderBackend;
 	use sp_core::crypto::Public;
@@ -190,6 +268,7 @@ mod tests {
 	struct TestAuthoritySet;
 	struct TestVoterState;
 	struct EmptyVoterState;
+	struct TestRoundHistory;
 
 	struct TestFinalityProofProvider {
 		finality_proof: Option<FinalityProof<Header>>,
@@ -214,6 +293,21 @@ mod tests {
 		}
 	}
 
+	impl ReportRoundHistory for TestRoundHistory {
+		fn get(&self) -> Vec<RoundParticipation> {
+			let voter_id_1 = AuthorityId::from_slice(&[1; 32]);
+			let voter_id_2 = AuthorityId::from_slice(&[2; 32]);
+
+			vec![RoundParticipation {
+				set_id: 1,
+				round: 5,
+				voters: vec![voter_id_1.clone(), voter_id_2],
+				prevotes: vec![voter_id_1.clone()].into_iter().collect(),
+				precommits: vec![voter_id_1].into_iter().collect(),
+			}]
+		}
+	}
+
 	fn header(number: u64) -> Header {
 		let parent_hash = match number {
 			0 => Default::default(),
@@ -274,7 +368,11 @@ mod tests {
 
 	fn setup_io_handler<VoterState>(
 		voter_state: VoterState,
-	) -> (jsonrpc_core::MetaIoHandler<sc_rpc::Metadata>, GrandpaJustificationSender<Block>)
+	) -> (
+		jsonrpc_core::MetaIoHandler<sc_rpc::Metadata>,
+		GrandpaJustificationSender<Block>,
+		GrandpaEquivocationSender<Block>,
+	)
 	where
 		VoterState: ReportVoterState + Send + Sync + 'static,
 	{
@@ -284,17 +382,24 @@ mod tests {
 	fn setup_io_handler_with_finality_proofs<VoterState>(
 		voter_state: VoterState,
 		finality_proof: Option<FinalityProof<Header>>,
-	) -> (jsonrpc_core::MetaIoHandler<sc_rpc::Metadata>, GrandpaJustificationSender<Block>)
+	) -> (
+		jsonrpc_core::MetaIoHandler<sc_rpc::Metadata>,
+		GrandpaJustificationSender<Block>,
+		GrandpaEquivocationSender<Block>,
+	)
 	where
 		VoterState: ReportVoterState + Send + Sync + 'static,
 	{
 		let (justification_sender, justification_stream) = GrandpaJustificationStream::channel();
+		let (equivocation_sender, equivocation_stream) = GrandpaEquivocationStream::channel();
 		let finality_proof_provider = Arc::new(TestFinalityProofProvider { finality_proof });
 
 		let handler = GrandpaRpcHandler::new(
 			TestAuthoritySet,
 			voter_state,
+			TestRoundHistory,
 			justification_stream,
+			equivocation_stream,
 			sc_rpc::testing::TaskExecutor,
 			finality_proof_provider,
 		);
@@ -302,12 +407,12 @@ mod tests {
 		let mut io = jsonrpc_core::MetaIoHandler::default();
 		io.extend_with(GrandpaApi::to_delegate(handler));
 
-		(io, justification_sender)
+		(io, justification_sender, equivocation_sender)
 	}
 
 	#[test]
 	fn uninitialized_rpc_handler() {
-		let (io, _) = setup_io_handler(EmptyVoterState);
+		let (io, _, _) = setup_io_handler(EmptyVoterState);
 
 		let request = r#"{"jsonrpc":"2.0","method":"grandpa_roundState","params":[],"id":1}"#;
 		let response = r#"{"jsonrpc":"2.0","error":{"code":1,"message":"GRANDPA RPC endpoint not ready"},"id":1}"#;
@@ -318,7 +423,7 @@ mod tests {
 
 	#[test]
 	fn working_rpc_handler() {
-		let (io, _) = setup_io_handler(TestVoterState);
+		let (io, _, _) = setup_io_handler(TestVoterState);
 
 		let request = r#"{"jsonrpc":"2.0","method":"grandpa_roundState","params":[],"id":1}"#;
 		let response = "{\"jsonrpc\":\"2.0\",\"result\":{\
@@ -339,6 +444,23 @@ mod tests {
 		assert_eq!(io.handle_request_sync(request, meta), Some(response.into()));
 	}
 
+	#[test]
+	fn round_history_rpc_handler() {
+		let (io, _, _) = setup_io_handler(TestVoterState);
+
+		let request = r#"{"jsonrpc":"2.0","method":"grandpa_roundHistory","params":[],"id":1}"#;
+		let response = "{\"jsonrpc\":\"2.0\",\"result\":[{\
+			\"authorities\":[\
+				{\"id\":\"5C62Ck4UrFPiBtoCmeSrgF7x9yv9mn38446dhCpsi2mLHiFT\",\"precommitted\":true,\"prevoted\":true},\
+				{\"id\":\"5C7LYpP2ZH3tpKbvVvwiVe54AapxErdPBbvkYhe6y9ZBkqWt\",\"precommitted\":false,\"prevoted\":false}\
+			],\
+			\"round\":5,\"setId\":1\
+		}],\"id\":1}";
+
+		let meta = sc_rpc::Metadata::default();
+		assert_eq!(io.handle_request_sync(request, meta), Some(response.into()));
+	}
+
 	fn setup_session() -> (sc_rpc::Metadata, futures::channel::mpsc::UnboundedReceiver<String>) {
 		let (tx, rx) = futures::channel::mpsc::unbounded();
 		let meta = sc_rpc::Metadata::new(tx);
@@ -347,7 +469,7 @@ mod tests {
 
 	#[test]
 	fn subscribe_and_unsubscribe_to_justifications() {
-		let (io, _) = setup_io_handler(TestVoterState);
+		let (io, _, _) = setup_io_handler(TestVoterState);
 		let (meta, _) = setup_session();
 
 		// Subscribe
@@ -380,7 +502,7 @@ mod tests {
 
 	#[test]
 	fn subscribe_and_unsubscribe_with_wrong_id() {
-		let (io, _) = setup_io_handler(TestVoterState);
+		let (io, _, _) = setup_io_handler(TestVoterState);
 		let (meta, _) = setup_session();
 
 		// Subscribe
@@ -456,7 +578,7 @@ mod tests {
 
 	#[test]
 	fn subscribe_and_listen_to_one_justification() {
-		let (io, justification_sender) = setup_io_handler(TestVoterState);
+		let (io, justification_sender, _) = setup_io_handler(TestVoterState);
 		let (meta, receiver) = setup_session();
 
 		// Subscribe
@@ -497,7 +619,7 @@ mod tests {
 			justification: create_justification().encode(),
 			unknown_headers: vec![header(2)],
 		};
-		let (io, _) =
+		let (io, _, _) =
 			setup_io_handler_with_finality_proofs(TestVoterState, Some(finality_proof.clone()));
 
 		let request =
@@ -510,4 +632,61 @@ mod tests {
 		let finality_proof_rpc: FinalityProof<Header> = Decode::decode(&mut &result[..]).unwrap();
 		assert_eq!(finality_proof_rpc, finality_proof);
 	}
+
+	#[test]
+	fn subscribe_and_listen_to_one_equivocation() {
+		let (io, _, equivocation_sender) = setup_io_handler(TestVoterState);
+		let (meta, receiver) = setup_session();
+
+		// Subscribe
+		let sub_request =
+			r#"{"jsonrpc":"2.0","method":"grandpa_subscribeEquivocations","params":[],"id":1}"#;
+
+		let resp = io.handle_request_sync(sub_request, meta.clone());
+		let mut resp: serde_json::Value = serde_json::from_str(&resp.unwrap()).unwrap();
+		let sub_id: String = serde_json::from_value(resp["result"].take()).unwrap();
+
+		// Notify with an equivocation
+		let alice = Ed25519Keyring::Alice;
+		let prevote = |n| {
+			let prevote =
+				finality_grandpa::Prevote { target_hash: H256::repeat_byte(n), target_number: 1 };
+			(prevote, alice.sign(&[n]).into())
+		};
+		let equivocation = sp_finality_grandpa::EquivocationProof::new(
+			2,
+			sp_finality_grandpa::Equivocation::Prevote(finality_grandpa::Equivocation {
+				round_number: 7,
+				identity: alice.public().into(),
+				first: prevote(1),
+				second: prevote(2),
+			}),
+		);
+		equivocation_sender.notify(equivocation.clone());
+
+		// Inspect what we received
+		let recv = futures::executor::block_on(receiver.take(1).collect::<Vec<_>>());
+		let recv: Notification = serde_json::from_str(&recv[0]).unwrap();
+		let mut json_map = match recv.params {
+			Params::Map(json_map) => json_map,
+			_ => panic!(),
+		};
+
+		let recv_sub_id: String = serde_json::from_value(json_map["subscription"].take()).unwrap();
+		let mut recv_equivocation = json_map["result"].take();
+
+		assert_eq!(recv.method, "grandpa_equivocations");
+		assert_eq!(recv_sub_id, sub_id);
+		assert_eq!(recv_equivocation["setId"], 2);
+		assert_eq!(recv_equivocation["round"], 7);
+		assert_eq!(recv_equivocation["stage"], "prevote");
+		assert_eq!(
+			serde_json::from_value::<AuthorityId>(recv_equivocation["offender"].take()).unwrap(),
+			alice.public().into(),
+		);
+
+		let recv_proof: sp_core::Bytes =
+			serde_json::from_value(recv_equivocation["proof"].take()).unwrap();
+		assert_eq!(Decode::decode(&mut &recv_proof[..]), Ok(equivocation));
+	}
 }
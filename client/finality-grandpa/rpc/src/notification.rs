@@ -17,8 +17,9 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use parity_scale_codec::Encode;
-use sc_finality_grandpa::GrandpaJustification;
+use sc_finality_grandpa::{AuthorityId, GrandpaJustification};
 use serde::{Deserialize, Serialize};
+use sp_finality_grandpa::{Equivocation, EquivocationProof};
 use sp_runtime::traits::Block as BlockT;
 
 /// An encoded justification proving that the given header has been finalized
@@ -30,3 +31,41 @@ impl<Block: BlockT> From<GrandpaJustification<Block>> for JustificationNotificat
 		JustificationNotification(notification.encode().into())
 	}
 }
+
+/// The voting stage at which an equivocation happened.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EquivocationStage {
+	/// The offender cast two different prevotes.
+	Prevote,
+	/// The offender cast two different precommits.
+	Precommit,
+}
+
+/// An equivocation detected by the local voter, alongside its encoded proof.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EquivocationNotification {
+	set_id: u64,
+	round: u64,
+	offender: AuthorityId,
+	stage: EquivocationStage,
+	proof: sp_core::Bytes,
+}
+
+impl<H: Encode, N: Encode> From<EquivocationProof<H, N>> for EquivocationNotification {
+	fn from(proof: EquivocationProof<H, N>) -> Self {
+		let stage = match proof.equivocation() {
+			Equivocation::Prevote(_) => EquivocationStage::Prevote,
+			Equivocation::Precommit(_) => EquivocationStage::Precommit,
+		};
+
+		EquivocationNotification {
+			set_id: proof.set_id(),
+			round: proof.round(),
+			offender: proof.offender().clone(),
+			stage,
+			proof: proof.encode().into(),
+		}
+	}
+}
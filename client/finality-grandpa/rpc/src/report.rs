@@ -24,7 +24,10 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
-use sc_finality_grandpa::{report, AuthorityId, SharedAuthoritySet, SharedVoterState};
+use sc_finality_grandpa::{
+	report, AuthorityId, RoundParticipation, SharedAuthoritySet, SharedRoundHistory,
+	SharedVoterState,
+};
 
 use crate::error::Error;
 
@@ -38,6 +41,11 @@ pub trait ReportVoterState {
 	fn get(&self) -> Option<report::VoterState<AuthorityId>>;
 }
 
+/// Utility trait to get the participation in the recently completed GRANDPA rounds.
+pub trait ReportRoundHistory {
+	fn get(&self) -> Vec<RoundParticipation>;
+}
+
 impl<H, N> ReportAuthoritySet for SharedAuthoritySet<H, N>
 where
 	N: Add<Output = N> + Ord + Clone + Debug,
@@ -57,6 +65,12 @@ impl ReportVoterState for SharedVoterState {
 	}
 }
 
+impl ReportRoundHistory for SharedRoundHistory {
+	fn get(&self) -> Vec<RoundParticipation> {
+		self.rounds()
+	}
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Prevotes {
@@ -148,3 +162,46 @@ impl ReportedRoundStates {
 		Ok(Self { set_id, best, background })
 	}
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AuthorityParticipation {
+	id: AuthorityId,
+	prevoted: bool,
+	precommitted: bool,
+}
+
+/// The participation of each authority of the set in a completed round, in a
+/// form suitable for serialization.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportedRoundParticipation {
+	set_id: u64,
+	round: u64,
+	authorities: Vec<AuthorityParticipation>,
+}
+
+impl ReportedRoundParticipation {
+	/// Get the participation in the rounds reported by the given history,
+	/// oldest round first.
+	pub fn history<RoundHistory>(round_history: &RoundHistory) -> Vec<Self>
+	where
+		RoundHistory: ReportRoundHistory,
+	{
+		round_history.get().iter().map(Self::from).collect()
+	}
+
+	fn from(participation: &RoundParticipation) -> Self {
+		let authorities = participation
+			.voters
+			.iter()
+			.map(|id| AuthorityParticipation {
+				id: id.clone(),
+				prevoted: participation.prevotes.contains(id),
+				precommitted: participation.precommits.contains(id),
+			})
+			.collect();
+
+		Self { set_id: participation.set_id, round: participation.round, authorities }
+	}
+}
@@ -86,7 +86,7 @@
 
 use parity_scale_codec::{Decode, Encode};
 use sc_network::{ObservedRole, PeerId, ReputationChange};
-use sc_network_gossip::{MessageIntent, ValidatorContext};
+use sc_network_gossip::{MessageIntent, TopicPolicy, ValidatorContext};
 use sp_finality_grandpa::AuthorityId;
 use sp_runtime::traits::{Block as BlockT, NumberFor, Zero};
 
@@ -1656,6 +1656,16 @@ impl<Block: BlockT> sc_network_gossip::Validator<Block> for GossipValidator<Bloc
 			}
 		})
 	}
+
+	fn topic_policy(&self, topic: &Block::Hash) -> TopicPolicy {
+		// Only named for the metrics, rounds and sets expire their messages themselves.
+		let name = match self.inner.read().live_topics.topic_info(topic) {
+			Some((Some(_), _)) => "grandpa-round",
+			Some((None, _)) => "grandpa-set",
+			None => return TopicPolicy::default(),
+		};
+		TopicPolicy { name, ..Default::default() }
+	}
 }
 
 /// Report specifying a reputation change for a given peer.
@@ -1699,6 +1709,20 @@ mod tests {
 		set_state.into()
 	}
 
+	#[test]
+	fn topic_policy_names_round_and_set_topics() {
+		let (val, _) = GossipValidator::<Block>::new(config(), voter_set_state(), None, None);
+		val.note_set(SetId(1), Vec::new(), |_, _| {});
+		val.note_round(Round(3), |_, _| {});
+
+		let policy = |topic| GossipValidatorT::topic_policy(&val, &topic).name;
+		assert_eq!(policy(crate::communication::round_topic::<Block>(3, 1)), "grandpa-round");
+		assert_eq!(policy(crate::communication::round_topic::<Block>(4, 1)), "grandpa-round");
+		assert_eq!(policy(crate::communication::global_topic::<Block>(1)), "grandpa-set");
+		// Topics of past sets are no longer tracked.
+		assert_eq!(policy(crate::communication::global_topic::<Block>(0)), "default");
+	}
+
 	#[test]
 	fn view_vote_rules() {
 		let view = View { round: Round(100), set_id: SetId(1), last_commit: Some(1000u64) };
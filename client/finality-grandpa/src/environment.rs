@@ -17,12 +17,12 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use std::{
-	collections::{BTreeMap, HashMap},
+	collections::{BTreeMap, HashMap, HashSet},
 	iter::FromIterator,
 	marker::PhantomData,
 	pin::Pin,
 	sync::Arc,
-	time::Duration,
+	time::{Duration, Instant},
 };
 
 use finality_grandpa::{
@@ -33,7 +33,9 @@ use futures_timer::Delay;
 use log::{debug, warn};
 use parity_scale_codec::{Decode, Encode};
 use parking_lot::RwLock;
-use prometheus_endpoint::{register, Counter, Gauge, PrometheusError, U64};
+use prometheus_endpoint::{
+	register, Counter, CounterVec, Gauge, HistogramOpts, HistogramVec, Opts, PrometheusError, U64,
+};
 
 use sc_client_api::{
 	backend::{apply_aux, Backend as BackendT},
@@ -56,7 +58,8 @@ use crate::{
 	communication::Network as NetworkT,
 	justification::GrandpaJustification,
 	local_authority_id,
-	notification::GrandpaJustificationSender,
+	notification::{GrandpaEquivocationSender, GrandpaJustificationSender},
+	round_history::SharedRoundHistory,
 	until_imported::UntilVoteTargetImported,
 	voting_rule::VotingRule as VotingRuleT,
 	ClientForGrandpa, CommandOrError, Commit, Config, Error, NewAuthoritySet, Precommit, Prevote,
@@ -396,6 +399,8 @@ pub(crate) struct Metrics {
 	finality_grandpa_round: Gauge<U64>,
 	finality_grandpa_prevotes: Counter<U64>,
 	finality_grandpa_precommits: Counter<U64>,
+	finality_grandpa_vote_latency: HistogramVec,
+	finality_grandpa_equivocations: CounterVec<U64>,
 }
 
 impl Metrics {
@@ -421,6 +426,29 @@ impl Metrics {
 				)?,
 				registry,
 			)?,
+			finality_grandpa_vote_latency: register(
+				HistogramVec::new(
+					HistogramOpts::new(
+						"finality_grandpa_vote_latency_seconds",
+						"Time between the start of a round and receiving the vote of an authority, \
+						by index of the authority in the current set.",
+					)
+					.buckets(vec![0.1, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0]),
+					&["authority_index", "stage"],
+				)?,
+				registry,
+			)?,
+			finality_grandpa_equivocations: register(
+				CounterVec::new(
+					Opts::new(
+						"finality_grandpa_equivocations_total",
+						"Total number of GRANDPA equivocations detected locally, by index of the \
+						offender in the current set.",
+					),
+					&["authority_index", "stage"],
+				)?,
+				registry,
+			)?,
 		})
 	}
 }
@@ -438,6 +466,8 @@ pub(crate) struct Environment<Backend, Block: BlockT, C, N: NetworkT<Block>, SC,
 	pub(crate) voting_rule: VR,
 	pub(crate) metrics: Option<Metrics>,
 	pub(crate) justification_sender: Option<GrandpaJustificationSender<Block>>,
+	pub(crate) equivocation_sender: Option<GrandpaEquivocationSender<Block>>,
+	pub(crate) round_history: SharedRoundHistory,
 	pub(crate) telemetry: Option<TelemetryHandle>,
 	pub(crate) _phantom: PhantomData<Backend>,
 }
@@ -470,6 +500,30 @@ impl<BE, Block: BlockT, C, N: NetworkT<Block>, SC, VR> Environment<BE, Block, C,
 			Ok(())
 		})
 	}
+
+	/// Notes an equivocation detected by the voter, notifying any subscribers
+	/// and updating the metrics.
+	fn note_equivocation(&self, equivocation: &Equivocation<Block::Hash, NumberFor<Block>>) {
+		if let Some(metrics) = self.metrics.as_ref() {
+			let stage = match equivocation {
+				Equivocation::Prevote(_) => "prevote",
+				Equivocation::Precommit(_) => "precommit",
+			};
+
+			// the full key of the offender is only part of the notification, labelling the
+			// metric with it would create a new series for every authority ever seen.
+			if let Some(info) = self.voters.get(equivocation.offender()) {
+				metrics
+					.finality_grandpa_equivocations
+					.with_label_values(&[&info.position().to_string(), stage])
+					.inc();
+			}
+		}
+
+		if let Some(sender) = self.equivocation_sender.as_ref() {
+			sender.notify(EquivocationProof::new(self.set_id, equivocation.clone()));
+		}
+	}
 }
 
 impl<BE, Block, C, N, SC, VR> Environment<BE, Block, C, N, SC, VR>
@@ -742,6 +796,35 @@ where
 			has_voted,
 		);
 
+		// keep track of the time it takes to receive the first prevote and
+		// precommit of each voter since the start of the round. voters are
+		// labelled by their index in the set to keep the number of series bounded.
+		let round_start = Instant::now();
+		let mut received_votes = HashSet::new();
+		let metrics = self.metrics.clone();
+		let voters = self.voters.clone();
+		let incoming = incoming.inspect(move |vote| {
+			if let Some(metrics) = metrics.as_ref() {
+				let stage = match vote.message {
+					finality_grandpa::Message::Prevote(_) => "prevote",
+					finality_grandpa::Message::Precommit(_) => "precommit",
+					finality_grandpa::Message::PrimaryPropose(_) => return,
+				};
+
+				let index = match voters.get(&vote.id) {
+					Some(info) => info.position(),
+					None => return,
+				};
+
+				if received_votes.insert((index, stage)) {
+					metrics
+						.finality_grandpa_vote_latency
+						.with_label_values(&[&index.to_string(), stage])
+						.observe(round_start.elapsed().as_secs_f64());
+				}
+			}
+		});
+
 		// schedule incoming messages from the network to be held until
 		// corresponding blocks are imported.
 		let incoming = Box::pin(
@@ -976,6 +1059,8 @@ where
 			// TODO: Future integration will store the prevote and precommit index. See #2611.
 			let votes = historical_votes.seen().to_vec();
 
+			self.round_history.note_round(self.set_id, round, &self.voters, &votes);
+
 			completed_rounds.push(CompletedRound {
 				number: round,
 				state: state.clone(),
@@ -1046,6 +1131,13 @@ where
 					.votes
 					.extend(historical_votes.seen().iter().skip(n_existing_votes).cloned());
 				already_completed.state = state;
+
+				self.round_history.note_round(
+					self.set_id,
+					round,
+					&self.voters,
+					&already_completed.votes,
+				);
 				crate::aux_schema::write_concluded_round(&*self.client, &already_completed)?;
 			}
 
@@ -1097,7 +1189,9 @@ where
 		equivocation: finality_grandpa::Equivocation<Self::Id, Prevote<Block>, Self::Signature>,
 	) {
 		warn!(target: "afg", "Detected prevote equivocation in the finality worker: {:?}", equivocation);
-		if let Err(err) = self.report_equivocation(equivocation.into()) {
+		let equivocation = equivocation.into();
+		self.note_equivocation(&equivocation);
+		if let Err(err) = self.report_equivocation(equivocation) {
 			warn!(target: "afg", "Error reporting prevote equivocation: {:?}", err);
 		}
 	}
@@ -1108,7 +1202,9 @@ where
 		equivocation: finality_grandpa::Equivocation<Self::Id, Precommit<Block>, Self::Signature>,
 	) {
 		warn!(target: "afg", "Detected precommit equivocation in the finality worker: {:?}", equivocation);
-		if let Err(err) = self.report_equivocation(equivocation.into()) {
+		let equivocation = equivocation.into();
+		self.note_equivocation(&equivocation);
+		if let Err(err) = self.report_equivocation(equivocation) {
 			warn!(target: "afg", "Error reporting precommit equivocation: {:?}", err);
 		}
 	}
@@ -117,6 +117,7 @@ mod import;
 mod justification;
 mod notification;
 mod observer;
+mod round_history;
 mod until_imported;
 mod voting_rule;
 pub mod warp_proof;
@@ -127,8 +128,12 @@ pub use finality_grandpa::voter::report;
 pub use finality_proof::{FinalityProof, FinalityProofError, FinalityProofProvider};
 pub use import::{find_forced_change, find_scheduled_change, GrandpaBlockImport};
 pub use justification::GrandpaJustification;
-pub use notification::{GrandpaJustificationSender, GrandpaJustificationStream};
+pub use notification::{
+	GrandpaEquivocationSender, GrandpaEquivocationStream, GrandpaJustificationSender,
+	GrandpaJustificationStream,
+};
 pub use observer::run_grandpa_observer;
+pub use round_history::{RoundParticipation, SharedRoundHistory, MAX_ROUND_HISTORY};
 pub use voting_rule::{
 	BeforeBestBlockBy, ThreeQuartersOfTheUnfinalizedChain, VotingRule, VotingRuleResult,
 	VotingRulesBuilder,
@@ -470,6 +475,9 @@ pub struct LinkHalf<Block: BlockT, C, SC> {
 	voter_commands_rx: TracingUnboundedReceiver<VoterCommand<Block::Hash, NumberFor<Block>>>,
 	justification_sender: GrandpaJustificationSender<Block>,
 	justification_stream: GrandpaJustificationStream<Block>,
+	equivocation_sender: GrandpaEquivocationSender<Block>,
+	equivocation_stream: GrandpaEquivocationStream<Block>,
+	round_history: SharedRoundHistory,
 	telemetry: Option<TelemetryHandle>,
 }
 
@@ -483,6 +491,16 @@ impl<Block: BlockT, C, SC> LinkHalf<Block, C, SC> {
 	pub fn justification_stream(&self) -> GrandpaJustificationStream<Block> {
 		self.justification_stream.clone()
 	}
+
+	/// Get the receiving end of equivocation notifications.
+	pub fn equivocation_stream(&self) -> GrandpaEquivocationStream<Block> {
+		self.equivocation_stream.clone()
+	}
+
+	/// Get the participation history of the recently completed rounds.
+	pub fn round_history(&self) -> SharedRoundHistory {
+		self.round_history.clone()
+	}
 }
 
 /// Provider for the Grandpa authority set configured on the genesis block.
@@ -579,6 +597,7 @@ where
 	let (voter_commands_tx, voter_commands_rx) = tracing_unbounded("mpsc_grandpa_voter_command");
 
 	let (justification_sender, justification_stream) = GrandpaJustificationStream::channel();
+	let (equivocation_sender, equivocation_stream) = GrandpaEquivocationStream::channel();
 
 	// create pending change objects with 0 delay and enacted on finality
 	// (i.e. standard changes) for each authority set hard fork.
@@ -615,6 +634,9 @@ where
 			voter_commands_rx,
 			justification_sender,
 			justification_stream,
+			equivocation_sender,
+			equivocation_stream,
+			round_history: SharedRoundHistory::empty(),
 			telemetry,
 		},
 	))
@@ -691,12 +713,16 @@ pub struct GrandpaParams<Block: BlockT, C, N, SC, VR> {
 
 /// Returns the configuration value to put in
 /// [`sc_network::config::NetworkConfiguration::extra_sets`].
+///
+/// The set only accepts reserved peers. Authorities can have them maintained by the authority
+/// discovery worker, by passing the set's protocol name in its `authority_peer_sets`.
 pub fn grandpa_peers_set_config() -> sc_network::config::NonDefaultSetConfig {
 	sc_network::config::NonDefaultSetConfig {
 		notifications_protocol: communication::GRANDPA_PROTOCOL_NAME.into(),
 		fallback_names: Vec::new(),
 		// Notifications reach ~256kiB in size at the time of writing on Kusama and Polkadot.
 		max_notification_size: 1024 * 1024,
+		bandwidth_limits: Default::default(),
 		set_config: sc_network::config::SetConfig {
 			in_peers: 0,
 			out_peers: 0,
@@ -744,6 +770,9 @@ where
 		voter_commands_rx,
 		justification_sender,
 		justification_stream: _,
+		equivocation_sender,
+		equivocation_stream: _,
+		round_history,
 		telemetry: _,
 	} = link;
 
@@ -801,6 +830,8 @@ where
 		prometheus_registry,
 		shared_voter_state,
 		justification_sender,
+		equivocation_sender,
+		round_history,
 		telemetry,
 	);
 
@@ -868,6 +899,8 @@ where
 		prometheus_registry: Option<prometheus_endpoint::Registry>,
 		shared_voter_state: SharedVoterState,
 		justification_sender: GrandpaJustificationSender<Block>,
+		equivocation_sender: GrandpaEquivocationSender<Block>,
+		round_history: SharedRoundHistory,
 		telemetry: Option<TelemetryHandle>,
 	) -> Self {
 		let metrics = match prometheus_registry.as_ref().map(Metrics::register) {
@@ -892,6 +925,8 @@ where
 			voter_set_state: persistent_data.set_state,
 			metrics: metrics.as_ref().map(|m| m.environment.clone()),
 			justification_sender: Some(justification_sender),
+			equivocation_sender: Some(equivocation_sender),
+			round_history,
 			telemetry: telemetry.clone(),
 			_phantom: PhantomData,
 		});
@@ -1036,6 +1071,8 @@ where
 					voting_rule: self.env.voting_rule.clone(),
 					metrics: self.env.metrics.clone(),
 					justification_sender: self.env.justification_sender.clone(),
+					equivocation_sender: self.env.equivocation_sender.clone(),
+					round_history: self.env.round_history.clone(),
 					telemetry: self.telemetry.clone(),
 					_phantom: PhantomData,
 				});
@@ -20,7 +20,8 @@ use parking_lot::Mutex;
 use std::sync::Arc;
 
 use sc_utils::mpsc::{tracing_unbounded, TracingUnboundedReceiver, TracingUnboundedSender};
-use sp_runtime::traits::Block as BlockT;
+use sp_finality_grandpa::EquivocationProof;
+use sp_runtime::traits::{Block as BlockT, NumberFor};
 
 use crate::{justification::GrandpaJustification, Error};
 
@@ -34,6 +35,18 @@ type JustificationSender<Block> = TracingUnboundedSender<GrandpaJustification<Bl
 // themselves.
 type SharedJustificationSenders<Block> = Arc<Mutex<Vec<JustificationSender<Block>>>>;
 
+// Proof of an equivocation detected by the local voter.
+type EquivocationNotification<Block> = EquivocationProof<<Block as BlockT>::Hash, NumberFor<Block>>;
+
+// Stream of equivocations returned when subscribing.
+type EquivocationStream<Block> = TracingUnboundedReceiver<EquivocationNotification<Block>>;
+
+// Sending endpoint for notifying about equivocations.
+type EquivocationSender<Block> = TracingUnboundedSender<EquivocationNotification<Block>>;
+
+// Collection of equivocation channel sending endpoints shared with the receiver side.
+type SharedEquivocationSenders<Block> = Arc<Mutex<Vec<EquivocationSender<Block>>>>;
+
 /// The sending half of the Grandpa justification channel(s).
 ///
 /// Used to send notifications about justifications generated
@@ -108,3 +121,49 @@ impl<Block: BlockT> GrandpaJustificationStream<Block> {
 		receiver
 	}
 }
+
+/// The sending half of the Grandpa equivocation channel(s).
+///
+/// Used to send notifications about equivocations detected by the
+/// local voter, regardless of whether they could be reported on-chain.
+#[derive(Clone)]
+pub struct GrandpaEquivocationSender<Block: BlockT> {
+	subscribers: SharedEquivocationSenders<Block>,
+}
+
+impl<Block: BlockT> GrandpaEquivocationSender<Block> {
+	/// Send out a notification to all subscribers that an equivocation
+	/// has been detected.
+	pub fn notify(&self, equivocation: EquivocationNotification<Block>) {
+		self.subscribers
+			.lock()
+			.retain(|n| n.unbounded_send(equivocation.clone()).is_ok());
+	}
+}
+
+/// The receiving half of the Grandpa equivocation channel.
+///
+/// Used to receive notifications about equivocations detected by the
+/// local voter.
+#[derive(Clone)]
+pub struct GrandpaEquivocationStream<Block: BlockT> {
+	subscribers: SharedEquivocationSenders<Block>,
+}
+
+impl<Block: BlockT> GrandpaEquivocationStream<Block> {
+	/// Creates a new pair of receiver and sender of equivocation notifications.
+	pub fn channel() -> (GrandpaEquivocationSender<Block>, Self) {
+		let subscribers = Arc::new(Mutex::new(vec![]));
+		let receiver = GrandpaEquivocationStream { subscribers: subscribers.clone() };
+		let sender = GrandpaEquivocationSender { subscribers };
+		(sender, receiver)
+	}
+
+	/// Subscribe to a channel through which equivocations are sent
+	/// as soon as they are detected.
+	pub fn subscribe(&self) -> EquivocationStream<Block> {
+		let (sender, receiver) = tracing_unbounded("mpsc_equivocation_notification_stream");
+		self.subscribers.lock().push(sender);
+		receiver
+	}
+}
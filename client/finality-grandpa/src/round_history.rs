@@ -0,0 +1,178 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Bounded history of the participation of the authorities in the most
+//! recently completed GRANDPA rounds.
+
+use parking_lot::RwLock;
+use std::{
+	collections::{BTreeSet, VecDeque},
+	sync::Arc,
+};
+
+use finality_grandpa::{voter_set::VoterSet, Message, SignedMessage};
+use sp_finality_grandpa::{AuthorityId, AuthoritySignature, RoundNumber, SetId};
+
+/// The maximum number of completed rounds kept in the history.
+pub const MAX_ROUND_HISTORY: usize = 64;
+
+/// The participation of the authorities in a completed round.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundParticipation {
+	/// The set id the round belongs to.
+	pub set_id: SetId,
+	/// The round number.
+	pub round: RoundNumber,
+	/// All the voters of the set.
+	pub voters: Vec<AuthorityId>,
+	/// The voters we have seen a prevote from.
+	pub prevotes: BTreeSet<AuthorityId>,
+	/// The voters we have seen a precommit from.
+	pub precommits: BTreeSet<AuthorityId>,
+}
+
+/// Shared history of the participation in the last `MAX_ROUND_HISTORY`
+/// completed rounds, ordered from oldest to newest.
+#[derive(Clone)]
+pub struct SharedRoundHistory {
+	inner: Arc<RwLock<VecDeque<RoundParticipation>>>,
+}
+
+impl SharedRoundHistory {
+	/// Create a new empty `SharedRoundHistory` instance.
+	pub fn empty() -> Self {
+		Self { inner: Arc::new(RwLock::new(VecDeque::with_capacity(MAX_ROUND_HISTORY))) }
+	}
+
+	/// Get the participation in the recently completed rounds, oldest first.
+	pub fn rounds(&self) -> Vec<RoundParticipation> {
+		self.inner.read().iter().cloned().collect()
+	}
+
+	/// Note the votes observed in the given round. If the round is already
+	/// part of the history (e.g. it is concluded after being completed) its
+	/// entry is replaced, otherwise the oldest round is evicted if needed.
+	pub(crate) fn note_round<H, N>(
+		&self,
+		set_id: SetId,
+		round: RoundNumber,
+		voters: &VoterSet<AuthorityId>,
+		votes: &[SignedMessage<H, N, AuthoritySignature, AuthorityId>],
+	) {
+		let mut participation = RoundParticipation {
+			set_id,
+			round,
+			voters: voters.iter().map(|(id, _)| id.clone()).collect(),
+			prevotes: BTreeSet::new(),
+			precommits: BTreeSet::new(),
+		};
+
+		for vote in votes {
+			match vote.message {
+				Message::Prevote(_) => {
+					participation.prevotes.insert(vote.id.clone());
+				},
+				Message::Precommit(_) => {
+					participation.precommits.insert(vote.id.clone());
+				},
+				Message::PrimaryPropose(_) => {},
+			}
+		}
+
+		let mut rounds = self.inner.write();
+		if let Some(existing) =
+			rounds.iter_mut().rev().find(|r| r.set_id == set_id && r.round == round)
+		{
+			*existing = participation;
+			return
+		}
+
+		if rounds.len() >= MAX_ROUND_HISTORY {
+			rounds.pop_front();
+		}
+		rounds.push_back(participation);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::{crypto::Public, ed25519};
+	use substrate_test_runtime_client::runtime::{BlockNumber, Hash};
+
+	type Vote = SignedMessage<Hash, BlockNumber, AuthoritySignature, AuthorityId>;
+
+	fn voter(n: u8) -> AuthorityId {
+		AuthorityId::from_slice(&[n; 32])
+	}
+
+	fn vote(n: u8, prevote: bool) -> Vote {
+		let message = if prevote {
+			Message::Prevote(finality_grandpa::Prevote::new(Default::default(), 1))
+		} else {
+			Message::Precommit(finality_grandpa::Precommit::new(Default::default(), 1))
+		};
+
+		SignedMessage {
+			message,
+			signature: ed25519::Signature::from_raw([0; 64]).into(),
+			id: voter(n),
+		}
+	}
+
+	#[test]
+	fn notes_participation_and_replaces_concluded_rounds() {
+		let history = SharedRoundHistory::empty();
+		let voters = VoterSet::new((1..=3).map(|n| (voter(n), 1))).unwrap();
+
+		history.note_round(0, 1, &voters, &[vote(1, true), vote(2, true), vote(1, false)]);
+
+		let rounds = history.rounds();
+		assert_eq!(rounds.len(), 1);
+		assert_eq!(rounds[0].voters.len(), 3);
+		assert_eq!(rounds[0].prevotes, vec![voter(1), voter(2)].into_iter().collect());
+		assert_eq!(rounds[0].precommits, vec![voter(1)].into_iter().collect());
+
+		// concluding the round updates the existing entry
+		history.note_round(
+			0,
+			1,
+			&voters,
+			&[vote(1, true), vote(2, true), vote(1, false), vote(2, false)],
+		);
+
+		let rounds = history.rounds();
+		assert_eq!(rounds.len(), 1);
+		assert_eq!(rounds[0].precommits, vec![voter(1), voter(2)].into_iter().collect());
+	}
+
+	#[test]
+	fn evicts_oldest_rounds() {
+		let history = SharedRoundHistory::empty();
+		let voters = VoterSet::new((1..=3).map(|n| (voter(n), 1))).unwrap();
+
+		for round in 1..=(MAX_ROUND_HISTORY as u64 + 2) {
+			history.note_round::<Hash, BlockNumber>(0, round, &voters, &[]);
+		}
+
+		let rounds = history.rounds();
+		assert_eq!(rounds.len(), MAX_ROUND_HISTORY);
+		assert_eq!(rounds.first().map(|r| r.round), Some(3));
+		assert_eq!(rounds.last().map(|r| r.round), Some(MAX_ROUND_HISTORY as u64 + 2));
+	}
+}
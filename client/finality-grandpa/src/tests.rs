@@ -1393,6 +1393,8 @@ where
 		voting_rule,
 		metrics: None,
 		justification_sender: None,
+		equivocation_sender: None,
+		round_history: SharedRoundHistory::empty(),
 		telemetry: None,
 		_phantom: PhantomData,
 	}
@@ -1675,3 +1677,52 @@ fn grandpa_environment_doesnt_send_equivocation_reports_for_itself() {
 	let equivocation_proof = sp_finality_grandpa::Equivocation::Prevote(equivocation);
 	assert!(environment.report_equivocation(equivocation_proof).is_ok());
 }
+
+#[test]
+fn grandpa_environment_notifies_equivocations() {
+	use finality_grandpa::voter::Environment;
+
+	let alice = Ed25519Keyring::Alice;
+	let voters = make_ids(&[alice]);
+
+	let (equivocation_sender, equivocation_stream) = GrandpaEquivocationStream::channel();
+	let mut equivocations = equivocation_stream.subscribe();
+
+	let environment = {
+		let mut net = GrandpaTestNet::new(TestApi::new(voters), 1, 0);
+		let peer = net.peer(0);
+		let network_service = peer.network_service().clone();
+		let link = peer.data.lock().take().unwrap();
+		let mut environment = test_environment(&link, None, network_service.clone(), ());
+		environment.equivocation_sender = Some(equivocation_sender);
+		environment
+	};
+
+	let signed_precommit = {
+		let precommit =
+			finality_grandpa::Precommit { target_hash: H256::random(), target_number: 1 };
+
+		let signed = alice.sign(&[]).into();
+		(precommit, signed)
+	};
+
+	let equivocation = finality_grandpa::Equivocation {
+		round_number: 3,
+		identity: alice.public().into(),
+		first: signed_precommit.clone(),
+		second: signed_precommit,
+	};
+
+	// subscribers are notified of the equivocation even though we are not
+	// an authority and therefore the report isn't submitted on-chain
+	environment.precommit_equivocation(3, equivocation.clone());
+
+	let notification = equivocations.try_next().unwrap().unwrap();
+	assert_eq!(notification.set_id(), 0);
+	assert_eq!(notification.round(), 3);
+	assert_eq!(notification.offender(), &AuthorityId::from(alice.public()));
+	assert_eq!(
+		notification,
+		EquivocationProof::new(0, sp_finality_grandpa::Equivocation::Precommit(equivocation)),
+	);
+}
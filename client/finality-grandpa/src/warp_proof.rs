@@ -23,7 +23,9 @@ use crate::{
 	GrandpaJustification, SharedAuthoritySet,
 };
 use sc_client_api::Backend as ClientBackend;
-use sc_network::warp_request_handler::{EncodedProof, VerificationResult, WarpSyncProvider};
+use sc_network::warp_request_handler::{
+	EncodedAuthoritySet, EncodedProof, VerificationResult, WarpSyncProvider,
+};
 use sp_blockchain::{Backend as BlockchainBackend, HeaderBackend};
 use sp_finality_grandpa::{AuthorityList, SetId, GRANDPA_ENGINE_ID};
 use sp_runtime::{
@@ -286,12 +288,15 @@ where
 	fn verify(
 		&self,
 		proof: &EncodedProof,
-		set_id: SetId,
-		authorities: AuthorityList,
+		authorities: EncodedAuthoritySet,
 	) -> Result<VerificationResult<Block>, Box<dyn std::error::Error + Send + Sync>> {
 		let EncodedProof(proof) = proof;
 		let proof = WarpSyncProof::<Block>::decode(&mut proof.as_slice())
 			.map_err(|e| format!("Proof decoding error: {:?}", e))?;
+		let EncodedAuthoritySet(authorities) = authorities;
+		let (set_id, authorities) =
+			<(SetId, AuthorityList)>::decode(&mut authorities.as_slice())
+				.map_err(|e| format!("Authority set decoding error: {:?}", e))?;
 		let last_header = proof
 			.proofs
 			.last()
@@ -299,19 +304,17 @@ where
 			.ok_or_else(|| "Empty proof".to_string())?;
 		let (next_set_id, next_authorities) =
 			proof.verify(set_id, authorities, &self.hard_forks).map_err(Box::new)?;
+		let next_authorities = EncodedAuthoritySet((next_set_id, next_authorities).encode());
 		if proof.is_finished {
-			Ok(VerificationResult::<Block>::Complete(next_set_id, next_authorities, last_header))
+			Ok(VerificationResult::<Block>::Complete(next_authorities, last_header))
 		} else {
-			Ok(VerificationResult::<Block>::Partial(
-				next_set_id,
-				next_authorities,
-				last_header.hash(),
-			))
+			Ok(VerificationResult::<Block>::Partial(next_authorities, last_header.hash()))
 		}
 	}
 
-	fn current_authorities(&self) -> AuthorityList {
-		self.authority_set.inner().current_authorities.clone()
+	fn current_authorities(&self) -> EncodedAuthoritySet {
+		let authority_set = self.authority_set.inner();
+		EncodedAuthoritySet((authority_set.set_id, &authority_set.current_authorities).encode())
 	}
 }
 
@@ -60,11 +60,18 @@
 //! opens the door for neighbor status packets to be baked into the gossip protocol.
 //! These status packets will typically contain light pieces of information
 //! used to inform peers of a current view of protocol state.
+//!
+//! Validators can also return a `TopicPolicy` for each topic. It names the topic in the
+//! Prometheus metrics, and can give it its own deduplication window, an expiry time for its
+//! messages and a fan-out limit, so that high-volume topics are only broadcast to a random subset
+//! of the peers.
 
 pub use self::{
 	bridge::GossipEngine,
 	state_machine::TopicNotification,
-	validator::{DiscardAll, MessageIntent, ValidationResult, Validator, ValidatorContext},
+	validator::{
+		DiscardAll, MessageIntent, TopicPolicy, ValidationResult, Validator, ValidatorContext,
+	},
 };
 
 use futures::prelude::*;
@@ -16,11 +16,12 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{MessageIntent, Network, ValidationResult, Validator, ValidatorContext};
+use crate::{MessageIntent, Network, TopicPolicy, ValidationResult, Validator, ValidatorContext};
 
 use libp2p::PeerId;
 use lru::LruCache;
-use prometheus_endpoint::{register, Counter, PrometheusError, Registry, U64};
+use prometheus_endpoint::{register, Counter, CounterVec, Opts, PrometheusError, Registry, U64};
+use rand::seq::SliceRandom;
 use sc_network::ObservedRole;
 use sp_runtime::traits::{Block as BlockT, Hash, HashFor};
 use std::{
@@ -73,6 +74,8 @@ struct MessageEntry<B: BlockT> {
 	topic: B::Hash,
 	message: Vec<u8>,
 	sender: Option<PeerId>,
+	policy: TopicPolicy,
+	registered_at: Instant,
 }
 
 /// Local implementation of `ValidatorContext`.
@@ -111,15 +114,33 @@ fn propagate<'a, B: BlockT, I>(
 	intent: MessageIntent,
 	peers: &mut HashMap<PeerId, PeerConsensus<B::Hash>>,
 	validator: &Arc<dyn Validator<B>>,
+	metrics: Option<&Metrics>,
 )
-// (msg_hash, topic, message)
+// (msg_hash, topic, message, policy)
 where
-	I: Clone + IntoIterator<Item = (&'a B::Hash, &'a B::Hash, &'a Vec<u8>)>,
+	I: IntoIterator<Item = (&'a B::Hash, &'a B::Hash, &'a Vec<u8>, &'a TopicPolicy)>,
 {
 	let mut message_allowed = validator.message_allowed();
+	let mut peer_ids = peers.keys().cloned().collect::<Vec<_>>();
 
-	for (id, ref mut peer) in peers.iter_mut() {
-		for (message_hash, topic, message) in messages.clone() {
+	for (message_hash, topic, message, policy) in messages {
+		let fan_out = match intent {
+			MessageIntent::ForcedBroadcast => None,
+			_ => policy.fan_out,
+		};
+		if fan_out.is_some() {
+			peer_ids.shuffle(&mut rand::thread_rng());
+		}
+		let mut sent = 0;
+
+		for id in &peer_ids {
+			if matches!(fan_out, Some(fan_out) if sent >= fan_out) {
+				break
+			}
+			let peer = match peers.get_mut(id) {
+				Some(peer) => peer,
+				None => continue,
+			};
 			let intent = match intent {
 				MessageIntent::Broadcast { .. } =>
 					if peer.known_messages.contains(&message_hash) {
@@ -144,6 +165,7 @@ where
 			}
 
 			peer.known_messages.insert(message_hash.clone());
+			sent += 1;
 
 			tracing::trace!(
 				target: "gossip",
@@ -152,6 +174,9 @@ where
 				?message,
 				"Propagating message",
 			);
+			if let Some(metrics) = metrics {
+				metrics.on_message(policy, "out", message.len());
+			}
 			network.write_notification(id.clone(), protocol.clone(), message.clone());
 		}
 	}
@@ -162,6 +187,8 @@ pub struct ConsensusGossip<B: BlockT> {
 	peers: HashMap<PeerId, PeerConsensus<B::Hash>>,
 	messages: Vec<MessageEntry<B>>,
 	known_messages: LruCache<B::Hash, ()>,
+	/// Deduplication windows of the topics with a dedicated one, by topic name.
+	topic_known_messages: HashMap<&'static str, LruCache<B::Hash, ()>>,
 	protocol: Cow<'static, str>,
 	validator: Arc<dyn Validator<B>>,
 	next_broadcast: Instant,
@@ -188,6 +215,7 @@ impl<B: BlockT> ConsensusGossip<B> {
 			peers: HashMap::new(),
 			messages: Default::default(),
 			known_messages: LruCache::new(KNOWN_MESSAGES_CACHE_SIZE),
+			topic_known_messages: HashMap::new(),
 			protocol,
 			validator,
 			next_broadcast: Instant::now() + REBROADCAST_INTERVAL,
@@ -217,14 +245,38 @@ impl<B: BlockT> ConsensusGossip<B> {
 		topic: B::Hash,
 		message: Vec<u8>,
 		sender: Option<PeerId>,
-	) {
-		if self.known_messages.put(message_hash.clone(), ()).is_none() {
-			self.messages.push(MessageEntry { message_hash, topic, message, sender });
+	) -> TopicPolicy {
+		let policy = self.validator.topic_policy(&topic);
+		let is_new = !self.is_known(&message_hash);
+		let known_messages = match policy.dedup_window {
+			Some(window) => self
+				.topic_known_messages
+				.entry(policy.name)
+				.or_insert_with(|| LruCache::new(window.max(1))),
+			None => &mut self.known_messages,
+		};
+		known_messages.put(message_hash, ());
+
+		if is_new {
+			self.messages.push(MessageEntry {
+				message_hash,
+				topic,
+				message,
+				sender,
+				policy,
+				registered_at: Instant::now(),
+			});
 
 			if let Some(ref metrics) = self.metrics {
 				metrics.registered_messages.inc();
 			}
 		}
+		policy
+	}
+
+	/// Whether the message is in one of the deduplication windows.
+	fn is_known(&self, message_hash: &B::Hash) -> bool {
+		is_known(&self.known_messages, &self.topic_known_messages, message_hash)
 	}
 
 	/// Registers a message without propagating it to any peers. The message
@@ -234,7 +286,7 @@ impl<B: BlockT> ConsensusGossip<B> {
 	/// collection.
 	pub fn register_message(&mut self, topic: B::Hash, message: Vec<u8>) {
 		let message_hash = HashFor::<B>::hash(&message[..]);
-		self.register_message_hashed(message_hash, topic, message, None);
+		let _ = self.register_message_hashed(message_hash, topic, message, None);
 	}
 
 	/// Call when a peer has been disconnected to stop tracking gossip status.
@@ -259,7 +311,7 @@ impl<B: BlockT> ConsensusGossip<B> {
 		let messages = self
 			.messages
 			.iter()
-			.map(|entry| (&entry.message_hash, &entry.topic, &entry.message));
+			.map(|entry| (&entry.message_hash, &entry.topic, &entry.message, &entry.policy));
 		propagate(
 			network,
 			self.protocol.clone(),
@@ -267,6 +319,7 @@ impl<B: BlockT> ConsensusGossip<B> {
 			MessageIntent::PeriodicRebroadcast,
 			&mut self.peers,
 			&self.validator,
+			self.metrics.as_ref(),
 		);
 	}
 
@@ -274,7 +327,7 @@ impl<B: BlockT> ConsensusGossip<B> {
 	pub fn broadcast_topic(&mut self, network: &mut dyn Network<B>, topic: B::Hash, force: bool) {
 		let messages = self.messages.iter().filter_map(|entry| {
 			if entry.topic == topic {
-				Some((&entry.message_hash, &entry.topic, &entry.message))
+				Some((&entry.message_hash, &entry.topic, &entry.message, &entry.policy))
 			} else {
 				None
 			}
@@ -287,17 +340,22 @@ impl<B: BlockT> ConsensusGossip<B> {
 			intent,
 			&mut self.peers,
 			&self.validator,
+			self.metrics.as_ref(),
 		);
 	}
 
 	/// Prune old or no longer relevant consensus messages. Provide a predicate
 	/// for pruning, which returns `false` when the items with a given topic should be pruned.
 	pub fn collect_garbage(&mut self) {
-		let known_messages = &mut self.known_messages;
+		let known_messages = &self.known_messages;
+		let topic_known_messages = &self.topic_known_messages;
 		let before = self.messages.len();
 
 		let mut message_expired = self.validator.message_expired();
-		self.messages.retain(|entry| !message_expired(entry.topic, &entry.message));
+		self.messages.retain(|entry| {
+			!message_expired(entry.topic, &entry.message) &&
+				!matches!(entry.policy.expiry, Some(expiry) if entry.registered_at.elapsed() >= expiry)
+		});
 
 		let expired_messages = before - self.messages.len();
 
@@ -315,7 +373,8 @@ impl<B: BlockT> ConsensusGossip<B> {
 		);
 
 		for (_, ref mut peer) in self.peers.iter_mut() {
-			peer.known_messages.retain(|h| known_messages.contains(h));
+			peer.known_messages
+				.retain(|h| is_known(known_messages, topic_known_messages, h));
 		}
 	}
 
@@ -353,7 +412,7 @@ impl<B: BlockT> ConsensusGossip<B> {
 		for message in messages {
 			let message_hash = HashFor::<B>::hash(&message[..]);
 
-			if self.known_messages.contains(&message_hash) {
+			if self.is_known(&message_hash) {
 				tracing::trace!(
 					target: "gossip",
 					%who,
@@ -361,6 +420,9 @@ impl<B: BlockT> ConsensusGossip<B> {
 					"Ignored already known message",
 				);
 				network.report_peer(who.clone(), rep::DUPLICATE_GOSSIP);
+				if let Some(ref metrics) = self.metrics {
+					metrics.duplicate_messages.inc();
+				}
 				continue
 			}
 
@@ -400,13 +462,19 @@ impl<B: BlockT> ConsensusGossip<B> {
 
 			network.report_peer(who.clone(), rep::GOSSIP_SUCCESS);
 			peer.known_messages.insert(message_hash);
+			let message_len = message.len();
 			to_forward.push((
 				topic,
 				TopicNotification { message: message.clone(), sender: Some(who.clone()) },
 			));
 
-			if keep {
-				self.register_message_hashed(message_hash, topic, message, Some(who.clone()));
+			let policy = if keep {
+				self.register_message_hashed(message_hash, topic, message, Some(who))
+			} else {
+				self.validator.topic_policy(&topic)
+			};
+			if let Some(ref metrics) = self.metrics {
+				metrics.on_message(&policy, "in", message_len);
 			}
 		}
 
@@ -445,6 +513,9 @@ impl<B: BlockT> ConsensusGossip<B> {
 					?entry.message,
 					"Sending topic message",
 				);
+				if let Some(ref metrics) = self.metrics {
+					metrics.on_message(&entry.policy, "out", entry.message.len());
+				}
 				network.write_notification(
 					who.clone(),
 					self.protocol.clone(),
@@ -463,15 +534,16 @@ impl<B: BlockT> ConsensusGossip<B> {
 		force: bool,
 	) {
 		let message_hash = HashFor::<B>::hash(&message);
-		self.register_message_hashed(message_hash, topic, message.clone(), None);
+		let policy = self.register_message_hashed(message_hash, topic, message.clone(), None);
 		let intent = if force { MessageIntent::ForcedBroadcast } else { MessageIntent::Broadcast };
 		propagate(
 			network,
 			self.protocol.clone(),
-			iter::once((&message_hash, &topic, &message)),
+			iter::once((&message_hash, &topic, &message, &policy)),
 			intent,
 			&mut self.peers,
 			&self.validator,
+			self.metrics.as_ref(),
 		);
 	}
 
@@ -498,9 +570,21 @@ impl<B: BlockT> ConsensusGossip<B> {
 	}
 }
 
+fn is_known<H: std::hash::Hash + Eq>(
+	known_messages: &LruCache<H, ()>,
+	topic_known_messages: &HashMap<&'static str, LruCache<H, ()>>,
+	message_hash: &H,
+) -> bool {
+	known_messages.contains(message_hash) ||
+		topic_known_messages.values().any(|known| known.contains(message_hash))
+}
+
 struct Metrics {
 	registered_messages: Counter<U64>,
 	expired_messages: Counter<U64>,
+	duplicate_messages: Counter<U64>,
+	topic_messages: CounterVec<U64>,
+	topic_bytes: CounterVec<U64>,
 }
 
 impl Metrics {
@@ -520,8 +604,40 @@ impl Metrics {
 				)?,
 				registry,
 			)?,
+			duplicate_messages: register(
+				Counter::new(
+					"network_gossip_duplicate_messages_total",
+					"Number of received messages ignored because they were already known.",
+				)?,
+				registry,
+			)?,
+			topic_messages: register(
+				CounterVec::new(
+					Opts::new(
+						"network_gossip_topic_messages_total",
+						"Number of messages received and sent by the gossip service, by topic.",
+					),
+					&["topic", "direction"],
+				)?,
+				registry,
+			)?,
+			topic_bytes: register(
+				CounterVec::new(
+					Opts::new(
+						"network_gossip_topic_bytes_total",
+						"Size of the messages received and sent by the gossip service, by topic.",
+					),
+					&["topic", "direction"],
+				)?,
+				registry,
+			)?,
 		})
 	}
+
+	fn on_message(&self, policy: &TopicPolicy, direction: &str, len: usize) {
+		self.topic_messages.with_label_values(&[policy.name, direction]).inc();
+		self.topic_bytes.with_label_values(&[policy.name, direction]).inc_by(len as u64);
+	}
 }
 
 #[cfg(test)]
@@ -546,6 +662,8 @@ mod tests {
 					topic: $topic,
 					message: $m,
 					sender: None,
+					policy: TopicPolicy::default(),
+					registered_at: Instant::now(),
 				});
 			}
 		};
@@ -583,6 +701,7 @@ mod tests {
 	#[derive(Clone, Default)]
 	struct NoOpNetworkInner {
 		peer_reports: Vec<(PeerId, ReputationChange)>,
+		notifications: Vec<(PeerId, Vec<u8>)>,
 	}
 
 	impl<B: BlockT> Network<B> for NoOpNetwork {
@@ -602,8 +721,8 @@ mod tests {
 
 		fn remove_set_reserved(&self, _: PeerId, _: Cow<'static, str>) {}
 
-		fn write_notification(&self, _: PeerId, _: Cow<'static, str>, _: Vec<u8>) {
-			unimplemented!();
+		fn write_notification(&self, who: PeerId, _: Cow<'static, str>, message: Vec<u8>) {
+			self.inner.lock().unwrap().notifications.push((who, message));
 		}
 
 		fn announce(&self, _: B::Hash, _: Option<Vec<u8>>) {
@@ -734,4 +853,106 @@ mod tests {
 			to_forward,
 		);
 	}
+
+	/// Validator giving all the topics the same policy.
+	struct WithPolicy(TopicPolicy);
+	impl Validator<Block> for WithPolicy {
+		fn validate(
+			&self,
+			_context: &mut dyn ValidatorContext<Block>,
+			_sender: &PeerId,
+			_data: &[u8],
+		) -> ValidationResult<H256> {
+			ValidationResult::ProcessAndKeep(H256::default())
+		}
+
+		fn topic_policy(&self, _topic: &H256) -> TopicPolicy {
+			self.0
+		}
+	}
+
+	#[test]
+	fn broadcast_is_limited_to_fan_out() {
+		let policy = TopicPolicy { name: "fan-out", fan_out: Some(3), ..Default::default() };
+		let mut consensus =
+			ConsensusGossip::<Block>::new(Arc::new(WithPolicy(policy)), "/foo".into(), None);
+		let mut network = NoOpNetwork::default();
+		for _ in 0..10 {
+			consensus.new_peer(&mut network, PeerId::random(), ObservedRole::Full);
+		}
+
+		let topic = H256::default();
+		consensus.multicast(&mut network, topic, vec![1, 2, 3], false);
+		assert_eq!(network.inner.lock().unwrap().notifications.len(), 3);
+
+		// Peers that already have the message are not counted.
+		consensus.broadcast_topic(&mut network, topic, false);
+		assert_eq!(network.inner.lock().unwrap().notifications.len(), 6);
+
+		consensus.broadcast_topic(&mut network, topic, true);
+		assert_eq!(network.inner.lock().unwrap().notifications.len(), 16);
+	}
+
+	#[test]
+	fn messages_expire_after_policy_expiry() {
+		let policy = TopicPolicy {
+			name: "short-lived",
+			expiry: Some(std::time::Duration::from_millis(10)),
+			..Default::default()
+		};
+		let mut consensus =
+			ConsensusGossip::<Block>::new(Arc::new(WithPolicy(policy)), "/foo".into(), None);
+		consensus.register_message(H256::default(), vec![1, 2, 3]);
+
+		consensus.collect_garbage();
+		assert_eq!(consensus.messages.len(), 1);
+
+		std::thread::sleep(std::time::Duration::from_millis(20));
+		consensus.collect_garbage();
+		assert!(consensus.messages.is_empty());
+	}
+
+	#[test]
+	fn topics_use_their_own_dedup_window() {
+		let policy = TopicPolicy { name: "windowed", dedup_window: Some(2), ..Default::default() };
+		let mut consensus =
+			ConsensusGossip::<Block>::new(Arc::new(WithPolicy(policy)), "/foo".into(), None);
+		let mut network = NoOpNetwork::default();
+		let peer = PeerId::random();
+		consensus.new_peer(&mut network, peer, ObservedRole::Full);
+
+		let messages = vec![vec![1], vec![2], vec![3]];
+		assert_eq!(consensus.on_incoming(&mut network, peer, messages).len(), 3);
+		assert!(consensus.known_messages.is_empty());
+
+		// The first message was evicted from the window and is no longer a duplicate.
+		let duplicates = vec![vec![1], vec![3]];
+		assert_eq!(consensus.on_incoming(&mut network, peer, duplicates).len(), 1);
+		let reports = &network.inner.lock().unwrap().peer_reports;
+		assert_eq!(reports.last().unwrap().1, rep::DUPLICATE_GOSSIP);
+	}
+
+	#[test]
+	fn metrics_are_labelled_with_the_topic_policy_name() {
+		let registry = Registry::new();
+		let policy = TopicPolicy { name: "named", ..Default::default() };
+		let mut consensus = ConsensusGossip::<Block>::new(
+			Arc::new(WithPolicy(policy)),
+			"/foo".into(),
+			Some(&registry),
+		);
+		let mut network = NoOpNetwork::default();
+		let peer = PeerId::random();
+		consensus.new_peer(&mut network, peer, ObservedRole::Full);
+
+		consensus.on_incoming(&mut network, peer, vec![vec![1, 2, 3]]);
+		consensus.multicast(&mut network, H256::default(), vec![4, 5], false);
+
+		let metrics = consensus.metrics.as_ref().unwrap();
+		assert_eq!(metrics.topic_messages.with_label_values(&["named", "in"]).get(), 1);
+		assert_eq!(metrics.topic_bytes.with_label_values(&["named", "in"]).get(), 3);
+		assert_eq!(metrics.topic_messages.with_label_values(&["named", "out"]).get(), 1);
+		assert_eq!(metrics.topic_bytes.with_label_values(&["named", "out"]).get(), 2);
+		assert_eq!(metrics.topic_messages.with_label_values(&["default", "in"]).get(), 0);
+	}
 }
@@ -18,6 +18,7 @@
 
 use sc_network::{ObservedRole, PeerId};
 use sp_runtime::traits::Block as BlockT;
+use std::time::Duration;
 
 /// Validates consensus messages.
 pub trait Validator<B: BlockT>: Send + Sync {
@@ -47,6 +48,35 @@ pub trait Validator<B: BlockT>: Send + Sync {
 	) -> Box<dyn FnMut(&PeerId, MessageIntent, &B::Hash, &[u8]) -> bool + 'a> {
 		Box::new(move |_who, _intent, _topic, _data| true)
 	}
+
+	/// Return the gossip policy of a topic. Called when a message of the topic is registered.
+	fn topic_policy(&self, _topic: &B::Hash) -> TopicPolicy {
+		TopicPolicy::default()
+	}
+}
+
+/// How the messages of a topic are deduplicated, expired and propagated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TopicPolicy {
+	/// Name of the topic in the metrics. Topics with the same name share their metrics and
+	/// their deduplication window.
+	pub name: &'static str,
+	/// Number of recent messages remembered to ignore duplicates, in a window dedicated to the
+	/// topics with this name. `None` to use the window shared by all the other topics. The first
+	/// registered topic with a given name decides the size of the window.
+	pub dedup_window: Option<usize>,
+	/// Time after which the registered messages of the topic are dropped, even if the validator
+	/// doesn't consider them expired.
+	pub expiry: Option<Duration>,
+	/// Maximum number of peers, picked at random, a message is sent to per broadcast. `None` to
+	/// send to all peers. Forced broadcasts always go to all peers.
+	pub fan_out: Option<usize>,
+}
+
+impl Default for TopicPolicy {
+	fn default() -> Self {
+		TopicPolicy { name: "default", dedup_window: None, expiry: None, fan_out: None }
+	}
 }
 
 /// Validation context. Allows reacting to incoming messages by sending out further messages.
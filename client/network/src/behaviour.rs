@@ -17,11 +17,12 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{
-	bitswap::Bitswap,
+	bitswap::{Bitswap, BitswapError, Cid},
 	config::ProtocolId,
 	discovery::{DiscoveryBehaviour, DiscoveryConfig, DiscoveryOut},
 	peer_info,
 	protocol::{message::Roles, CustomMessageOutcome, NotificationsSink, Protocol},
+	recorder::Recorder,
 	request_responses, DhtEvent, ObservedRole,
 };
 
@@ -57,6 +58,7 @@ use std::{
 
 pub use crate::request_responses::{
 	IfDisconnected, InboundFailure, OutboundFailure, RequestFailure, RequestId, ResponseFailure,
+	ThrottleReason,
 };
 
 /// General behaviour of the network. Combines all protocols together.
@@ -70,7 +72,7 @@ pub struct Behaviour<B: BlockT> {
 	peer_info: peer_info::PeerInfoBehaviour,
 	/// Discovers nodes of the network.
 	discovery: DiscoveryBehaviour,
-	/// Bitswap server for blockchain data.
+	/// Bitswap client and server for blockchain data.
 	bitswap: Toggle<Bitswap<B>>,
 	/// Generic request-reponse protocols.
 	request_responses: request_responses::RequestResponsesBehaviour,
@@ -206,6 +208,7 @@ impl<B: BlockT> Behaviour<B> {
 		// All remaining request protocol configs.
 		mut request_response_protocols: Vec<request_responses::ProtocolConfig>,
 		peerset: PeersetHandle,
+		recorder: Option<Recorder>,
 	) -> Result<Self, request_responses::RegisterError> {
 		// Extract protocol name and add to `request_response_protocols`.
 		let block_request_protocol_name = block_request_protocol_config.name.to_string();
@@ -230,6 +233,7 @@ impl<B: BlockT> Behaviour<B> {
 			request_responses: request_responses::RequestResponsesBehaviour::new(
 				request_response_protocols.into_iter(),
 				peerset,
+				recorder,
 			)?,
 			events: VecDeque::new(),
 			block_request_protocol_name,
@@ -292,6 +296,20 @@ impl<B: BlockT> Behaviour<B> {
 			.send_request(target, protocol, request, pending_response, connect)
 	}
 
+	/// Starts fetching the data with the given CID over Bitswap.
+	pub fn bitswap_get(
+		&mut self,
+		cid: Cid,
+		pending_response: oneshot::Sender<Result<Vec<u8>, BitswapError>>,
+	) {
+		match self.bitswap.as_mut() {
+			Some(bitswap) => bitswap.get(cid, pending_response),
+			None => {
+				let _ = pending_response.send(Err(BitswapError::Disabled));
+			},
+		}
+	}
+
 	/// Returns a shared reference to the user protocol.
 	pub fn user_protocol(&self) -> &Protocol<B> {
 		&self.substrate
@@ -14,25 +14,38 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-//! Bitswap server for substrate.
+//! Bitswap client and server for substrate.
 //!
 //! Allows querying transactions by hash over standard bitswap protocol
 //! Only supports bitswap 1.2.0.
 //! CID is expected to reference 256-bit Blake2b transaction hash.
+//!
+//! The client side fetches blocks by CID from the connected peers, which can be other substrate
+//! nodes or IPFS nodes. The wanted CIDs are sent to a few peers at a time, and to more peers as
+//! they answer that they don't have the data. Received data is checked against the CID before
+//! being returned.
 
 use crate::{
 	chain::Client,
 	schema::bitswap::{
-		message::{wantlist::WantType, Block as MessageBlock, BlockPresence, BlockPresenceType},
+		message::{
+			wantlist::{Entry, WantType},
+			Block as MessageBlock, BlockPresence, BlockPresenceType, Wantlist,
+		},
 		Message as BitswapMessage,
 	},
 };
-use cid::Version;
+use cid::{
+	multihash::{Code, MultihashDigest},
+	Version,
+};
 use core::pin::Pin;
 use futures::{
+	channel::oneshot,
 	io::{AsyncRead, AsyncWrite},
-	Future,
+	Future, FutureExt,
 };
+use futures_timer::Delay;
 use libp2p::{
 	core::{
 		connection::ConnectionId, upgrade, InboundUpgrade, Multiaddr, OutboundUpgrade, PeerId,
@@ -46,12 +59,16 @@ use log::{debug, error, trace};
 use prost::Message;
 use sp_runtime::traits::Block as BlockT;
 use std::{
-	collections::VecDeque,
+	collections::{HashMap, HashSet, VecDeque},
+	convert::TryFrom,
 	io,
 	sync::Arc,
 	task::{Context, Poll},
+	time::{Duration, Instant},
 };
-use unsigned_varint::encode as varint_encode;
+use unsigned_varint::{decode as varint_decode, encode as varint_encode};
+
+pub use cid::Cid;
 
 const LOG_TARGET: &str = "bitswap";
 
@@ -65,6 +82,10 @@ const MAX_PACKET_SIZE: usize = 16 * 1024 * 1024;
 const MAX_RESPONSE_QUEUE: usize = 20;
 // Max number of blocks per wantlist
 const MAX_WANTED_BLOCKS: usize = 16;
+// Max number of peers a CID is wanted from at the same time.
+const MAX_PEERS_PER_WANT: usize = 4;
+// Time after which a CID that wasn't received is reported as not found.
+const WANT_TIMEOUT: Duration = Duration::from_secs(30);
 
 const PROTOCOL_NAME: &'static [u8] = b"/ipfs/bitswap/1.2.0";
 
@@ -178,18 +199,226 @@ impl Prefix {
 		res.extend_from_slice(mh_len);
 		res
 	}
+
+	/// Decode a prefix encoded with [`Prefix::to_bytes`].
+	pub fn from_bytes(data: &[u8]) -> Result<Self, BitswapError> {
+		let (version, rest) = varint_decode::u64(data)?;
+		let (codec, rest) = varint_decode::u64(rest)?;
+		let (mh_type, rest) = varint_decode::u64(rest)?;
+		let (mh_len, _) = varint_decode::u64(rest)?;
+		Ok(Self {
+			version: Version::try_from(version)?,
+			codec,
+			mh_type,
+			mh_len: u8::try_from(mh_len).map_err(|_| BitswapError::UnsupportedCid)?,
+		})
+	}
+
+	/// Compute the CID of `data`.
+	pub fn to_cid(&self, data: &[u8]) -> Result<Cid, BitswapError> {
+		let code = Code::try_from(self.mh_type).map_err(|_| BitswapError::UnsupportedCid)?;
+		let hash = code.digest(data);
+		if hash.size() != self.mh_len {
+			return Err(BitswapError::UnsupportedCid)
+		}
+		Ok(Cid::new(self.version, self.codec, hash)?)
+	}
+}
+
+/// A CID wanted by the local node.
+struct Want {
+	/// Senders waiting for the data.
+	pending: Vec<oneshot::Sender<Result<Vec<u8>, BitswapError>>>,
+	/// Peers the CID was sent to, that haven't answered yet.
+	asked: HashSet<PeerId>,
+	/// Peers that answered they don't have the data.
+	dont_have: HashSet<PeerId>,
+	/// When the want fails if the data wasn't received.
+	deadline: Instant,
+}
+
+impl Want {
+	fn complete(self, result: impl Fn() -> Result<Vec<u8>, BitswapError>) {
+		for sender in self.pending {
+			let _ = sender.send(result());
+		}
+	}
 }
 
 /// Network behaviour that handles sending and receiving IPFS blocks.
 pub struct Bitswap<B> {
 	client: Arc<dyn Client<B>>,
 	ready_blocks: VecDeque<(PeerId, BitswapMessage)>,
+	/// Wantlists to send to other peers.
+	wantlists: VecDeque<(PeerId, BitswapMessage)>,
+	/// CIDs wanted by the local node.
+	wants: HashMap<Cid, Want>,
+	/// Peers we are connected to.
+	peers: HashSet<PeerId>,
+	/// Fires when the earliest want times out.
+	timeout: Option<Delay>,
 }
 
 impl<B: BlockT> Bitswap<B> {
 	/// Create a new instance of the bitswap protocol handler.
 	pub fn new(client: Arc<dyn Client<B>>) -> Self {
-		Self { client, ready_blocks: Default::default() }
+		Self {
+			client,
+			ready_blocks: Default::default(),
+			wantlists: Default::default(),
+			wants: Default::default(),
+			peers: Default::default(),
+			timeout: None,
+		}
+	}
+
+	/// Fetch the data with the given CID from the connected peers. The result is sent to
+	/// `pending_response`.
+	pub fn get(
+		&mut self,
+		cid: Cid,
+		pending_response: oneshot::Sender<Result<Vec<u8>, BitswapError>>,
+	) {
+		if Code::try_from(cid.hash().code()).is_err() {
+			let _ = pending_response.send(Err(BitswapError::UnsupportedCid));
+			return
+		}
+		if let Some(want) = self.wants.get_mut(&cid) {
+			want.pending.push(pending_response);
+			return
+		}
+		debug!(target: LOG_TARGET, "Fetching {}", cid);
+		let deadline = Instant::now() + WANT_TIMEOUT;
+		if self.timeout.is_none() {
+			self.timeout = Some(Delay::new(WANT_TIMEOUT));
+		}
+		self.wants.insert(
+			cid,
+			Want {
+				pending: vec![pending_response],
+				asked: HashSet::new(),
+				dont_have: HashSet::new(),
+				deadline,
+			},
+		);
+		self.ask_more_peers(&cid);
+	}
+
+	/// Send the wanted CID to peers that haven't been asked yet, up to [`MAX_PEERS_PER_WANT`].
+	/// Fails the want if all the peers answered they don't have the data.
+	fn ask_more_peers(&mut self, cid: &Cid) {
+		let want = match self.wants.get_mut(cid) {
+			Some(want) => want,
+			None => return,
+		};
+		let candidates = self
+			.peers
+			.iter()
+			.filter(|peer| !want.asked.contains(*peer) && !want.dont_have.contains(*peer))
+			.take(MAX_PEERS_PER_WANT.saturating_sub(want.asked.len()))
+			.cloned()
+			.collect::<Vec<_>>();
+		for peer in candidates {
+			want.asked.insert(peer);
+			self.wantlists
+				.push_back((peer, wantlist_message(vec![wantlist_entry(cid, false)])));
+		}
+		if want.asked.is_empty() && !want.dont_have.is_empty() {
+			debug!(target: LOG_TARGET, "None of the peers has {}", cid);
+			if let Some(want) = self.wants.remove(cid) {
+				want.complete(|| Err(BitswapError::NotFound));
+			}
+		}
+	}
+
+	/// Handle the blocks and block presences received from `peer`.
+	fn on_response(&mut self, peer: PeerId, message: &BitswapMessage) {
+		for block in &message.payload {
+			let cid = match Prefix::from_bytes(&block.prefix).and_then(|p| p.to_cid(&block.data)) {
+				Ok(cid) => cid,
+				Err(e) => {
+					debug!(target: LOG_TARGET, "Bad block from {}: {}", peer, e);
+					continue
+				},
+			};
+			let want = match self.wants.remove(&cid) {
+				Some(want) => want,
+				None => {
+					trace!(target: LOG_TARGET, "Unexpected block {} from {}", cid, peer);
+					continue
+				},
+			};
+			trace!(target: LOG_TARGET, "Received {} from {}", cid, peer);
+			for other in want.asked.iter().filter(|other| **other != peer) {
+				self.wantlists
+					.push_back((*other, wantlist_message(vec![wantlist_entry(&cid, true)])));
+			}
+			want.complete(|| Ok(block.data.clone()));
+		}
+		for presence in &message.block_presences {
+			if presence.r#type != BlockPresenceType::DontHave as i32 {
+				continue
+			}
+			let cid = match Cid::read_bytes(presence.cid.as_slice()) {
+				Ok(cid) => cid,
+				Err(_) => continue,
+			};
+			if let Some(want) = self.wants.get_mut(&cid) {
+				if want.asked.remove(&peer) {
+					trace!(target: LOG_TARGET, "{} doesn't have {}", peer, cid);
+					want.dont_have.insert(peer);
+					self.ask_more_peers(&cid);
+				}
+			}
+		}
+	}
+
+	/// Fail the wants that timed out or that nobody waits for any more. Returns the next deadline.
+	fn expire_wants(&mut self) -> Option<Instant> {
+		let now = Instant::now();
+		let expired = self
+			.wants
+			.iter_mut()
+			.filter_map(|(cid, want)| {
+				want.pending.retain(|sender| !sender.is_canceled());
+				if want.pending.is_empty() || want.deadline <= now {
+					Some(*cid)
+				} else {
+					None
+				}
+			})
+			.collect::<Vec<_>>();
+		for cid in expired {
+			if let Some(want) = self.wants.remove(&cid) {
+				debug!(target: LOG_TARGET, "Stopped fetching {}", cid);
+				for peer in &want.asked {
+					self.wantlists
+						.push_back((*peer, wantlist_message(vec![wantlist_entry(&cid, true)])));
+				}
+				want.complete(|| Err(BitswapError::Timeout));
+			}
+		}
+		self.wants.values().map(|want| want.deadline).min()
+	}
+}
+
+fn wantlist_entry(cid: &Cid, cancel: bool) -> Entry {
+	Entry {
+		block: cid.to_bytes(),
+		priority: 1,
+		cancel,
+		want_type: WantType::Block as i32,
+		send_dont_have: true,
+	}
+}
+
+fn wantlist_message(entries: Vec<Entry>) -> BitswapMessage {
+	BitswapMessage {
+		wantlist: Some(Wantlist { entries, full: false }),
+		blocks: Default::default(),
+		payload: Default::default(),
+		block_presences: Default::default(),
+		pending_bytes: 0,
 	}
 }
 
@@ -205,9 +434,33 @@ impl<B: BlockT> NetworkBehaviour for Bitswap<B> {
 		Vec::new()
 	}
 
-	fn inject_connected(&mut self, _peer: &PeerId) {}
+	fn inject_connected(&mut self, peer: &PeerId) {
+		self.peers.insert(*peer);
+		let wanted = self
+			.wants
+			.iter_mut()
+			.filter(|(_, want)| want.asked.len() < MAX_PEERS_PER_WANT)
+			.map(|(cid, want)| {
+				want.asked.insert(*peer);
+				wantlist_entry(cid, false)
+			})
+			.collect::<Vec<_>>();
+		for entries in wanted.chunks(MAX_WANTED_BLOCKS) {
+			self.wantlists.push_back((*peer, wantlist_message(entries.to_vec())));
+		}
+	}
 
-	fn inject_disconnected(&mut self, _peer: &PeerId) {}
+	fn inject_disconnected(&mut self, peer: &PeerId) {
+		self.peers.remove(peer);
+		let asked = self
+			.wants
+			.iter_mut()
+			.filter_map(|(cid, want)| if want.asked.remove(peer) { Some(*cid) } else { None })
+			.collect::<Vec<_>>();
+		for cid in asked {
+			self.ask_more_peers(&cid);
+		}
+	}
 
 	fn inject_event(&mut self, peer: PeerId, _connection: ConnectionId, message: HandlerEvent) {
 		let request = match message {
@@ -215,6 +468,16 @@ impl<B: BlockT> NetworkBehaviour for Bitswap<B> {
 			HandlerEvent::Request(msg) => msg,
 		};
 		trace!(target: LOG_TARGET, "Received request: {:?} from {}", request, peer);
+		self.on_response(peer, &request);
+		let wantlist = match request.wantlist {
+			Some(wantlist) => wantlist,
+			None => {
+				if request.payload.is_empty() && request.block_presences.is_empty() {
+					debug!(target: LOG_TARGET, "Unexpected bitswap message from {}", peer);
+				}
+				return
+			},
+		};
 		if self.ready_blocks.len() > MAX_RESPONSE_QUEUE {
 			debug!(target: LOG_TARGET, "Ignored request: queue is full");
 			return
@@ -226,18 +489,11 @@ impl<B: BlockT> NetworkBehaviour for Bitswap<B> {
 			block_presences: Default::default(),
 			pending_bytes: 0,
 		};
-		let wantlist = match request.wantlist {
-			Some(wantlist) => wantlist,
-			None => {
-				debug!(target: LOG_TARGET, "Unexpected bitswap message from {}", peer);
-				return
-			},
-		};
 		if wantlist.entries.len() > MAX_WANTED_BLOCKS {
 			trace!(target: LOG_TARGET, "Ignored request: too many entries");
 			return
 		}
-		for entry in wantlist.entries {
+		for entry in wantlist.entries.into_iter().filter(|entry| !entry.cancel) {
 			let cid = match cid::Cid::read_bytes(entry.block.as_slice()) {
 				Ok(cid) => cid,
 				Err(e) => {
@@ -298,10 +554,20 @@ impl<B: BlockT> NetworkBehaviour for Bitswap<B> {
 
 	fn poll(
 		&mut self,
-		_ctx: &mut Context,
+		cx: &mut Context,
 		_: &mut impl PollParameters,
 	) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ProtocolsHandler>> {
-		if let Some((peer_id, message)) = self.ready_blocks.pop_front() {
+		while let Some(timeout) = self.timeout.as_mut() {
+			if timeout.poll_unpin(cx).is_pending() {
+				break
+			}
+			self.timeout = self
+				.expire_wants()
+				.map(|deadline| Delay::new(deadline.saturating_duration_since(Instant::now())));
+		}
+		if let Some((peer_id, message)) =
+			self.ready_blocks.pop_front().or_else(|| self.wantlists.pop_front())
+		{
 			return Poll::Ready(NetworkBehaviourAction::NotifyHandler {
 				peer_id,
 				handler: NotifyHandler::Any,
@@ -313,7 +579,7 @@ impl<B: BlockT> NetworkBehaviour for Bitswap<B> {
 }
 
 /// Bitswap protocol error.
-#[derive(derive_more::Display, derive_more::From)]
+#[derive(Debug, derive_more::Display, derive_more::From)]
 pub enum BitswapError {
 	/// Protobuf decoding error.
 	#[display(fmt = "Failed to decode request: {}.", _0)]
@@ -330,4 +596,206 @@ pub enum BitswapError {
 	/// Error sending response.
 	#[display(fmt = "Failed to send response.")]
 	SendResponse,
+	/// Error decoding a CID prefix.
+	#[display(fmt = "Failed to decode CID prefix: {}.", _0)]
+	BadPrefix(varint_decode::Error),
+	/// The CID uses an unsupported hash algorithm.
+	#[display(fmt = "Unsupported CID.")]
+	UnsupportedCid,
+	/// None of the peers has the data.
+	#[display(fmt = "Data not found.")]
+	NotFound,
+	/// The data wasn't received in time.
+	#[display(fmt = "Timed out fetching data.")]
+	Timeout,
+	/// Bitswap is not enabled.
+	#[display(fmt = "Bitswap is not enabled.")]
+	Disabled,
+	/// The network worker is no longer running.
+	#[display(fmt = "Network is not running.")]
+	NetworkUnavailable,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use substrate_test_runtime_client::{
+		runtime::Block, DefaultTestClientBuilderExt, TestClientBuilder, TestClientBuilderExt,
+	};
+
+	fn bitswap_with_peers(peers: usize) -> (Bitswap<Block>, Vec<PeerId>) {
+		let mut bitswap = Bitswap::<Block>::new(Arc::new(TestClientBuilder::new().build()));
+		let peers = (0..peers).map(|_| PeerId::random()).collect::<Vec<_>>();
+		for peer in &peers {
+			bitswap.inject_connected(peer);
+		}
+		(bitswap, peers)
+	}
+
+	fn cid_of(data: &[u8]) -> Cid {
+		Cid::new_v1(0x55, Code::Blake2b256.digest(data))
+	}
+
+	fn get(
+		bitswap: &mut Bitswap<Block>,
+		cid: Cid,
+	) -> oneshot::Receiver<Result<Vec<u8>, BitswapError>> {
+		let (tx, rx) = oneshot::channel();
+		bitswap.get(cid, tx);
+		rx
+	}
+
+	/// Remove the queued wantlists with `cid`, returning the peers and whether the entries are
+	/// cancellations.
+	fn sent_wantlists(bitswap: &mut Bitswap<Block>, cid: &Cid) -> Vec<(PeerId, bool)> {
+		let mut sent = Vec::new();
+		bitswap.wantlists.retain(|(peer, message)| {
+			let entries = &message.wantlist.as_ref().unwrap().entries;
+			match entries.iter().find(|entry| entry.block == cid.to_bytes()) {
+				Some(entry) => {
+					sent.push((*peer, entry.cancel));
+					false
+				},
+				None => true,
+			}
+		});
+		sent
+	}
+
+	fn dont_have(cid: &Cid) -> BitswapMessage {
+		BitswapMessage {
+			wantlist: None,
+			blocks: Default::default(),
+			payload: Default::default(),
+			block_presences: vec![BlockPresence {
+				r#type: BlockPresenceType::DontHave as i32,
+				cid: cid.to_bytes(),
+			}],
+			pending_bytes: 0,
+		}
+	}
+
+	#[test]
+	fn wants_are_sent_to_more_peers_on_dont_have() {
+		let (mut bitswap, peers) = bitswap_with_peers(MAX_PEERS_PER_WANT + 1);
+		let cid = cid_of(b"data");
+		let mut rx = get(&mut bitswap, cid);
+
+		let asked = sent_wantlists(&mut bitswap, &cid);
+		assert_eq!(asked.len(), MAX_PEERS_PER_WANT);
+		assert!(asked.iter().all(|(_, cancel)| !cancel));
+		let not_asked = peers.iter().find(|p| !asked.iter().any(|(a, _)| a == *p)).unwrap();
+
+		// The peer that doesn't have the data is replaced by the one not asked yet.
+		bitswap.on_response(asked[0].0, &dont_have(&cid));
+		assert_eq!(sent_wantlists(&mut bitswap, &cid), vec![(*not_asked, false)]);
+		assert_eq!(bitswap.wants[&cid].asked.len(), MAX_PEERS_PER_WANT);
+		assert!(bitswap.wants[&cid].dont_have.contains(&asked[0].0));
+
+		// A peer answering for a CID it wasn't asked for is ignored.
+		bitswap.on_response(asked[0].0, &dont_have(&cid));
+		assert_eq!(bitswap.wants[&cid].dont_have.len(), 1);
+
+		// The want fails once nobody has the data.
+		for peer in peers.iter().filter(|p| **p != asked[0].0) {
+			bitswap.on_response(*peer, &dont_have(&cid));
+		}
+		assert!(bitswap.wants.is_empty());
+		assert!(matches!(rx.try_recv(), Ok(Some(Err(BitswapError::NotFound)))));
+	}
+
+	#[test]
+	fn received_data_completes_wants_and_cancels_them_at_other_peers() {
+		let (mut bitswap, _) = bitswap_with_peers(2);
+		let data = b"indexed transaction".to_vec();
+		let cid = cid_of(&data);
+		let mut rx1 = get(&mut bitswap, cid);
+		let mut rx2 = get(&mut bitswap, cid);
+		let asked = sent_wantlists(&mut bitswap, &cid);
+		assert_eq!(asked.len(), 2);
+
+		let prefix = Prefix {
+			version: cid.version(),
+			codec: cid.codec(),
+			mh_type: cid.hash().code(),
+			mh_len: cid.hash().size(),
+		};
+		let mut message = dont_have(&cid);
+		message.block_presences.clear();
+		// Data not matching the CID is ignored.
+		message.payload = vec![MessageBlock { prefix: prefix.to_bytes(), data: b"bad".to_vec() }];
+		bitswap.on_response(asked[0].0, &message);
+		assert!(bitswap.wants.contains_key(&cid));
+
+		message.payload = vec![MessageBlock { prefix: prefix.to_bytes(), data: data.clone() }];
+		bitswap.on_response(asked[0].0, &message);
+		assert!(bitswap.wants.is_empty());
+		assert_eq!(rx1.try_recv().unwrap().unwrap().unwrap(), data);
+		assert_eq!(rx2.try_recv().unwrap().unwrap().unwrap(), data);
+		assert_eq!(sent_wantlists(&mut bitswap, &cid), vec![(asked[1].0, true)]);
+	}
+
+	#[test]
+	fn expire_wants_fails_timed_out_and_abandoned_wants() {
+		let (mut bitswap, peers) = bitswap_with_peers(1);
+		let timed_out = cid_of(b"timed out");
+		let abandoned = cid_of(b"abandoned");
+		let pending = cid_of(b"pending");
+		let mut timed_out_rx = get(&mut bitswap, timed_out);
+		drop(get(&mut bitswap, abandoned));
+		let _pending_rx = get(&mut bitswap, pending);
+		bitswap.wantlists.clear();
+
+		bitswap.wants.get_mut(&timed_out).unwrap().deadline = Instant::now();
+		let next_deadline = bitswap.expire_wants();
+
+		assert_eq!(bitswap.wants.keys().collect::<Vec<_>>(), vec![&pending]);
+		assert_eq!(next_deadline, Some(bitswap.wants[&pending].deadline));
+		assert!(matches!(timed_out_rx.try_recv(), Ok(Some(Err(BitswapError::Timeout)))));
+		assert_eq!(sent_wantlists(&mut bitswap, &timed_out), vec![(peers[0], true)]);
+		assert_eq!(sent_wantlists(&mut bitswap, &abandoned), vec![(peers[0], true)]);
+		assert!(bitswap.wantlists.is_empty());
+	}
+
+	#[test]
+	fn wants_move_to_other_peers_on_disconnection() {
+		let (mut bitswap, peers) = bitswap_with_peers(MAX_PEERS_PER_WANT + 1);
+		let cid = cid_of(b"data");
+		let mut rx = get(&mut bitswap, cid);
+		let asked = sent_wantlists(&mut bitswap, &cid);
+		let not_asked = peers.iter().find(|p| !asked.iter().any(|(a, _)| a == *p)).unwrap();
+
+		bitswap.inject_disconnected(&asked[0].0);
+		assert_eq!(sent_wantlists(&mut bitswap, &cid), vec![(*not_asked, false)]);
+		assert!(!bitswap.wants[&cid].asked.contains(&asked[0].0));
+
+		// Without any peer left, the want waits for new connections.
+		for peer in &peers {
+			bitswap.inject_disconnected(peer);
+		}
+		assert!(bitswap.wants[&cid].asked.is_empty());
+		assert!(matches!(rx.try_recv(), Ok(None)));
+
+		let peer = PeerId::random();
+		bitswap.inject_connected(&peer);
+		assert_eq!(sent_wantlists(&mut bitswap, &cid), vec![(peer, false)]);
+	}
+
+	#[test]
+	fn prefix_round_trip_and_cid_check() {
+		let data = b"indexed transaction".to_vec();
+		let cid = Cid::new_v1(0x70, Code::Blake2b256.digest(&data));
+		let prefix = Prefix {
+			version: cid.version(),
+			codec: cid.codec(),
+			mh_type: cid.hash().code(),
+			mh_len: cid.hash().size(),
+		};
+		let decoded = Prefix::from_bytes(&prefix.to_bytes()).unwrap();
+		assert_eq!(decoded.to_cid(&data).unwrap(), cid);
+		assert_ne!(decoded.to_cid(b"other data").unwrap(), cid);
+
+		let unsupported = Prefix { mh_type: 0xffff, ..decoded };
+		assert!(matches!(unsupported.to_cid(&data), Err(BitswapError::UnsupportedCid)));
+	}
 }
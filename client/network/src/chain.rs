@@ -18,7 +18,7 @@
 
 //! Blockchain access trait
 
-use sc_client_api::{BlockBackend, ProofProvider};
+use sc_client_api::{AuxStore, BlockBackend, ProofProvider};
 pub use sc_client_api::{StorageData, StorageKey};
 pub use sc_consensus::ImportedState;
 use sp_blockchain::{Error, HeaderBackend, HeaderMetadata};
@@ -31,6 +31,7 @@ pub trait Client<Block: BlockT>:
 	+ BlockIdTo<Block, Error = Error>
 	+ BlockBackend<Block>
 	+ HeaderMetadata<Block, Error = Error>
+	+ AuxDataStore
 	+ Send
 	+ Sync
 {
@@ -42,7 +43,27 @@ impl<Block: BlockT, T> Client<Block> for T where
 		+ BlockIdTo<Block, Error = Error>
 		+ BlockBackend<Block>
 		+ HeaderMetadata<Block, Error = Error>
+		+ AuxStore
 		+ Send
 		+ Sync
 {
 }
+
+/// Object safe access to the auxiliary data of the client. Used to persist sync progress.
+pub trait AuxDataStore {
+	/// Insert auxiliary data. Deletions occur after insertions.
+	fn insert_aux_data(&self, insert: &[(&[u8], &[u8])], delete: &[&[u8]]) -> Result<(), Error>;
+
+	/// Query auxiliary data.
+	fn get_aux_data(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error>;
+}
+
+impl<T: AuxStore> AuxDataStore for T {
+	fn insert_aux_data(&self, insert: &[(&[u8], &[u8])], delete: &[&[u8]]) -> Result<(), Error> {
+		self.insert_aux(insert, delete)
+	}
+
+	fn get_aux_data(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+		self.get_aux(key)
+	}
+}
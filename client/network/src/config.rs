@@ -24,7 +24,8 @@
 pub use crate::{
 	chain::Client,
 	request_responses::{
-		IncomingRequest, OutgoingResponse, ProtocolConfig as RequestResponseConfig,
+		InboundRequestLimits, IncomingRequest, OutgoingResponse,
+		ProtocolConfig as RequestResponseConfig, RequestQuota,
 	},
 	warp_request_handler::WarpSyncProvider,
 };
@@ -391,6 +392,9 @@ pub enum SyncMode {
 	},
 	/// Warp sync - verify authority set transitions and the latest state.
 	Warp,
+	/// Full block download and verification. While far behind, headers are downloaded and
+	/// verified first, then block bodies are downloaded from many peers in parallel.
+	HeadersFirst,
 }
 
 impl Default for SyncMode {
@@ -428,6 +432,14 @@ pub struct NetworkConfiguration {
 	pub max_parallel_downloads: u32,
 	/// Initial syncing mode.
 	pub sync_mode: SyncMode,
+	/// Bandwidth limits of all the connections combined.
+	pub bandwidth_limits: BandwidthLimits,
+	/// Limits on the block requests served to other peers. Replaces the limits of
+	/// [`Params::block_request_protocol_config`].
+	pub block_request_limits: InboundRequestLimits,
+	/// Limits on the state requests served to other peers. Replaces the limits of
+	/// [`Params::state_request_protocol_config`].
+	pub state_request_limits: InboundRequestLimits,
 
 	/// True if Kademlia random discovery should be enabled.
 	///
@@ -440,7 +452,7 @@ pub struct NetworkConfiguration {
 	/// Require iterative Kademlia DHT queries to use disjoint paths for increased resiliency in
 	/// the presence of potentially adversarial nodes.
 	pub kademlia_disjoint_query_paths: bool,
-	/// Enable serving block data over IPFS bitswap.
+	/// Enable serving block data over IPFS bitswap, and fetching data from other peers.
 	pub ipfs_server: bool,
 
 	/// Size of Yamux receive window of all substreams. `None` for the default (256kiB).
@@ -463,6 +475,10 @@ pub struct NetworkConfiguration {
 	/// a modification of the way the implementation works. Different nodes with different
 	/// configured values remain compatible with each other.
 	pub yamux_window_size: Option<u32>,
+
+	/// File to record all the notifications, requests and responses exchanged with the network
+	/// to, for debugging purposes. See the [`recorder`](crate::recorder) module.
+	pub message_recording: Option<PathBuf>,
 }
 
 impl NetworkConfiguration {
@@ -487,11 +503,15 @@ impl NetworkConfiguration {
 			transport: TransportConfig::Normal { enable_mdns: false, allow_private_ipv4: true },
 			max_parallel_downloads: 5,
 			sync_mode: SyncMode::Full,
+			bandwidth_limits: Default::default(),
+			block_request_limits: Default::default(),
+			state_request_limits: Default::default(),
 			enable_dht_random_walk: true,
 			allow_non_globals_in_dht: false,
 			kademlia_disjoint_query_paths: false,
 			yamux_window_size: None,
 			ipfs_server: false,
+			message_recording: None,
 		}
 	}
 
@@ -572,6 +592,8 @@ pub struct NonDefaultSetConfig {
 	pub fallback_names: Vec<Cow<'static, str>>,
 	/// Maximum allowed size of single notifications.
 	pub max_notification_size: u64,
+	/// Bandwidth limits of the notifications protocol, for all the peers combined.
+	pub bandwidth_limits: BandwidthLimits,
 	/// Base configuration.
 	pub set_config: SetConfig,
 }
@@ -583,6 +605,7 @@ impl NonDefaultSetConfig {
 			notifications_protocol,
 			max_notification_size,
 			fallback_names: Vec::new(),
+			bandwidth_limits: Default::default(),
 			set_config: SetConfig {
 				in_peers: 0,
 				out_peers: 0,
@@ -605,6 +628,15 @@ impl NonDefaultSetConfig {
 	}
 }
 
+/// Limits of the bandwidth used, in bytes per second.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BandwidthLimits {
+	/// Maximum number of bytes sent per second. `None` for no limit.
+	pub upload: Option<u64>,
+	/// Maximum number of bytes received per second. `None` for no limit.
+	pub download: Option<u64>,
+}
+
 /// Configuration for the transport layer.
 #[derive(Clone, Debug)]
 pub enum TransportConfig {
@@ -248,7 +248,9 @@ mod behaviour;
 mod chain;
 mod discovery;
 mod peer_info;
+mod peer_store;
 mod protocol;
+mod rate_limit;
 mod request_responses;
 mod schema;
 mod service;
@@ -261,6 +263,7 @@ pub mod config;
 pub mod error;
 pub mod light_client_requests;
 pub mod network_state;
+pub mod recorder;
 pub mod state_request_handler;
 pub mod transactions;
 pub mod warp_request_handler;
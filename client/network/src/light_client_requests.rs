@@ -43,5 +43,6 @@ pub fn generate_protocol_config(protocol_id: &ProtocolId) -> ProtocolConfig {
 		max_response_size: 16 * 1024 * 1024,
 		request_timeout: Duration::from_secs(15),
 		inbound_queue: None,
+		inbound_limits: Default::default(),
 	}
 }
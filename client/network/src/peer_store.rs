@@ -0,0 +1,399 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Persistent store of the peers we managed to connect to.
+//!
+//! The peerset and the discovery mechanism only keep their state in memory, so a restarting
+//! node has to find its peers again through the boot nodes and Kademlia. The [`PeerStore`]
+//! records, for each peer we connected to, the addresses we reached it at, when we last saw it,
+//! its reputation and how many times we connected to it. It is saved in the network
+//! configuration directory and reloaded on startup, where its content is used to prioritize
+//! dialing.
+
+use libp2p::{Multiaddr, PeerId};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+	cmp::Reverse,
+	collections::HashMap,
+	fs, io,
+	path::PathBuf,
+	time::{Duration, SystemTime},
+};
+
+/// Name of the file of the peer store, in the network configuration directory.
+pub const PEER_STORE_FILE: &str = "peers.json";
+/// Interval at which the peer store is saved.
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Maximum number of peers in the store.
+const MAX_PEERS: usize = 1000;
+/// Maximum number of addresses kept for each peer.
+const MAX_ADDRESSES_PER_PEER: usize = 8;
+/// Peers that we haven't seen for this long are removed from the store.
+const FORGET_AFTER: Duration = Duration::from_secs(30 * 24 * 3600);
+/// Reputation granted on startup for each connection to the peer that succeeded in the past.
+const CONNECTION_REPUTATION_BONUS: i32 = 64;
+/// Maximum number of past connections rewarded by [`CONNECTION_REPUTATION_BONUS`].
+const MAX_REWARDED_CONNECTIONS: u32 = 16;
+/// Addresses that failed this many more times than they succeeded are removed from the store.
+const MAX_ADDRESS_FAILURES: u32 = 5;
+
+/// Returns the current time as a number of seconds since the UNIX epoch.
+pub fn unix_time() -> u64 {
+	SystemTime::now()
+		.duration_since(SystemTime::UNIX_EPOCH)
+		.map(|d| d.as_secs())
+		.unwrap_or_default()
+}
+
+/// Known peers, saved to and loaded from a file.
+#[derive(Debug)]
+pub struct PeerStore {
+	path: PathBuf,
+	peers: HashMap<PeerId, PeerRecord>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct PeerRecord {
+	addresses: Vec<AddressRecord>,
+	last_seen: u64,
+	reputation: i32,
+	successful_connections: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct AddressRecord {
+	address: Multiaddr,
+	successes: u32,
+	failures: u32,
+	last_success: u64,
+}
+
+impl AddressRecord {
+	fn score(&self) -> (i64, u64) {
+		(i64::from(self.successes) - i64::from(self.failures), self.last_success)
+	}
+}
+
+impl PeerRecord {
+	/// Reputation to give to the peer on startup.
+	///
+	/// Peers we connected to in the past are rewarded, so that the peerset dials them first.
+	/// Peers with a negative reputation keep it as is. The reward isn't added to the saved
+	/// reputation, which may already contain it if the node was restarted right away.
+	fn initial_reputation(&self) -> i32 {
+		if self.reputation < 0 {
+			return self.reputation
+		}
+		let bonus = CONNECTION_REPUTATION_BONUS
+			.saturating_mul(self.successful_connections.min(MAX_REWARDED_CONNECTIONS) as i32);
+		self.reputation.max(bonus)
+	}
+
+	fn priority(&self) -> (i32, u64) {
+		(self.initial_reputation(), self.last_seen)
+	}
+}
+
+/// A peer of the store, as returned by [`PeerStore::known_peers`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct KnownPeer {
+	/// Identity of the peer.
+	pub peer_id: PeerId,
+	/// Addresses the peer was reached at, best first.
+	pub addresses: Vec<Multiaddr>,
+	/// Reputation to give to the peer.
+	pub reputation: i32,
+}
+
+/// On-disk representation of a peer.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredPeer {
+	peer_id: String,
+	addresses: Vec<StoredAddress>,
+	last_seen: u64,
+	reputation: i32,
+	successful_connections: u32,
+}
+
+/// On-disk representation of an address.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoredAddress {
+	address: String,
+	successes: u32,
+	failures: u32,
+	last_success: u64,
+}
+
+impl PeerStore {
+	/// Load the store saved at `path`.
+	///
+	/// Starts with an empty store if the file doesn't exist or can't be read.
+	pub fn load(path: PathBuf) -> Self {
+		let peers = match fs::read(&path) {
+			Ok(content) => match serde_json::from_slice::<Vec<StoredPeer>>(&content) {
+				Ok(stored) => stored.into_iter().filter_map(decode_peer).collect(),
+				Err(e) => {
+					warn!(target: "sub-libp2p", "Ignoring invalid peer store {:?}: {}", path, e);
+					HashMap::new()
+				},
+			},
+			Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+			Err(e) => {
+				warn!(target: "sub-libp2p", "Failed to read peer store {:?}: {}", path, e);
+				HashMap::new()
+			},
+		};
+		debug!(target: "sub-libp2p", "Loaded {} peers from {:?}", peers.len(), path);
+		PeerStore { path, peers }
+	}
+
+	/// Number of peers in the store.
+	pub fn num_peers(&self) -> usize {
+		self.peers.len()
+	}
+
+	/// Returns the peers of the store, in the order in which they should be dialed.
+	pub fn known_peers(&self) -> Vec<KnownPeer> {
+		let mut peers = self.peers.iter().collect::<Vec<_>>();
+		peers.sort_by_key(|(_, record)| Reverse(record.priority()));
+		peers
+			.into_iter()
+			.map(|(peer_id, record)| KnownPeer {
+				peer_id: *peer_id,
+				addresses: record.addresses.iter().map(|a| a.address.clone()).collect(),
+				reputation: record.initial_reputation(),
+			})
+			.collect()
+	}
+
+	/// Returns the identities of the peers of the store.
+	pub fn peer_ids(&self) -> impl Iterator<Item = &PeerId> {
+		self.peers.keys()
+	}
+
+	/// Record a connection to `peer_id`. `address` is the address we dialed, if the connection is
+	/// an outgoing one.
+	pub fn on_connected(&mut self, peer_id: PeerId, address: Option<&Multiaddr>, now: u64) {
+		let record = self.peers.entry(peer_id).or_default();
+		record.last_seen = now;
+		record.successful_connections = record.successful_connections.saturating_add(1);
+		if let Some(address) = address {
+			match record.addresses.iter_mut().find(|a| a.address == *address) {
+				Some(entry) => {
+					entry.successes = entry.successes.saturating_add(1);
+					entry.last_success = now;
+				},
+				None => record.addresses.push(AddressRecord {
+					address: address.clone(),
+					successes: 1,
+					failures: 0,
+					last_success: now,
+				}),
+			}
+			record.addresses.sort_by_key(|a| Reverse(a.score()));
+		}
+	}
+
+	/// Record a failure to reach a known peer at the given address.
+	pub fn on_dial_failure(&mut self, peer_id: &PeerId, address: &Multiaddr) {
+		if let Some(record) = self.peers.get_mut(peer_id) {
+			if let Some(entry) = record.addresses.iter_mut().find(|a| a.address == *address) {
+				entry.failures = entry.failures.saturating_add(1);
+				record.addresses.sort_by_key(|a| Reverse(a.score()));
+			}
+		}
+	}
+
+	/// Update the reputation of a known peer.
+	pub fn set_reputation(&mut self, peer_id: &PeerId, reputation: i32) {
+		if let Some(record) = self.peers.get_mut(peer_id) {
+			record.reputation = reputation;
+		}
+	}
+
+	/// Remove the stale peers and addresses, then write the store to disk.
+	pub fn save(&mut self, now: u64) {
+		self.prune(now);
+		let stored = self.peers.iter().map(|(peer_id, record)| encode_peer(peer_id, record));
+		let result = serde_json::to_vec(&stored.collect::<Vec<_>>())
+			.map_err(io::Error::from)
+			.and_then(|content| {
+				// Write to a temporary file first, so that a crash never leaves a truncated store.
+				let tmp_path = self.path.with_extension("json.tmp");
+				fs::write(&tmp_path, content)?;
+				fs::rename(&tmp_path, &self.path)
+			});
+		match result {
+			Ok(()) =>
+				debug!(target: "sub-libp2p", "Saved {} peers to {:?}", self.num_peers(), self.path),
+			Err(e) =>
+				warn!(target: "sub-libp2p", "Failed to save peer store {:?}: {}", self.path, e),
+		}
+	}
+
+	fn prune(&mut self, now: u64) {
+		let forget_before = now.saturating_sub(FORGET_AFTER.as_secs());
+		self.peers.retain(|_, record| {
+			record
+				.addresses
+				.retain(|a| a.failures < a.successes.saturating_add(MAX_ADDRESS_FAILURES));
+			record.addresses.truncate(MAX_ADDRESSES_PER_PEER);
+			record.last_seen >= forget_before
+		});
+
+		if self.peers.len() > MAX_PEERS {
+			let mut priorities =
+				self.peers.values().map(|record| Reverse(record.priority())).collect::<Vec<_>>();
+			priorities.sort();
+			let lowest_kept = priorities[MAX_PEERS - 1];
+			self.peers.retain(|_, record| Reverse(record.priority()) <= lowest_kept);
+		}
+	}
+}
+
+fn encode_peer(peer_id: &PeerId, record: &PeerRecord) -> StoredPeer {
+	StoredPeer {
+		peer_id: peer_id.to_base58(),
+		addresses: record
+			.addresses
+			.iter()
+			.map(|a| StoredAddress {
+				address: a.address.to_string(),
+				successes: a.successes,
+				failures: a.failures,
+				last_success: a.last_success,
+			})
+			.collect(),
+		last_seen: record.last_seen,
+		reputation: record.reputation,
+		successful_connections: record.successful_connections,
+	}
+}
+
+fn decode_peer(stored: StoredPeer) -> Option<(PeerId, PeerRecord)> {
+	let peer_id = stored.peer_id.parse().ok()?;
+	let addresses = stored
+		.addresses
+		.into_iter()
+		.filter_map(|a| {
+			Some(AddressRecord {
+				address: a.address.parse().ok()?,
+				successes: a.successes,
+				failures: a.failures,
+				last_success: a.last_success,
+			})
+		})
+		.collect();
+	let record = PeerRecord {
+		addresses,
+		last_seen: stored.last_seen,
+		reputation: stored.reputation,
+		successful_connections: stored.successful_connections,
+	};
+	Some((peer_id, record))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const NOW: u64 = 1_000_000_000;
+
+	fn address(port: u16) -> Multiaddr {
+		format!("/ip4/127.0.0.1/tcp/{}", port).parse().unwrap()
+	}
+
+	#[test]
+	fn peers_are_saved_and_reloaded() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join(PEER_STORE_FILE);
+		let (peer1, peer2) = (PeerId::random(), PeerId::random());
+
+		let mut store = PeerStore::load(path.clone());
+		assert_eq!(store.num_peers(), 0);
+		store.on_connected(peer1, Some(&address(1)), NOW);
+		store.on_connected(peer2, None, NOW);
+		store.set_reputation(&peer2, -100);
+		store.save(NOW);
+
+		let reloaded = PeerStore::load(path);
+		assert_eq!(reloaded.peers, store.peers);
+		assert_eq!(
+			reloaded.known_peers(),
+			vec![
+				KnownPeer {
+					peer_id: peer1,
+					addresses: vec![address(1)],
+					reputation: CONNECTION_REPUTATION_BONUS,
+				},
+				KnownPeer { peer_id: peer2, addresses: vec![], reputation: -100 },
+			],
+		);
+	}
+
+	#[test]
+	fn invalid_store_is_ignored() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join(PEER_STORE_FILE);
+		fs::write(&path, b"not json").unwrap();
+		assert_eq!(PeerStore::load(path).num_peers(), 0);
+	}
+
+	#[test]
+	fn peers_and_addresses_are_ordered_by_score() {
+		let dir = tempfile::tempdir().unwrap();
+		let mut store = PeerStore::load(dir.path().join(PEER_STORE_FILE));
+		let (peer1, peer2, peer3) = (PeerId::random(), PeerId::random(), PeerId::random());
+
+		store.on_connected(peer1, Some(&address(1)), NOW);
+		store.on_connected(peer1, Some(&address(2)), NOW + 1);
+		store.on_connected(peer2, Some(&address(3)), NOW);
+		store.on_connected(peer2, Some(&address(3)), NOW + 1);
+		store.on_connected(peer2, Some(&address(3)), NOW + 2);
+		store.on_connected(peer3, Some(&address(4)), NOW + 3);
+		store.on_dial_failure(&peer1, &address(2));
+
+		let known = store.known_peers();
+		assert_eq!(known.iter().map(|p| p.peer_id).collect::<Vec<_>>(), vec![peer2, peer1, peer3]);
+		assert_eq!(known[1].addresses, vec![address(1), address(2)]);
+	}
+
+	#[test]
+	fn stale_peers_and_failing_addresses_are_pruned() {
+		let dir = tempfile::tempdir().unwrap();
+		let mut store = PeerStore::load(dir.path().join(PEER_STORE_FILE));
+		let (peer1, peer2) = (PeerId::random(), PeerId::random());
+
+		store.on_connected(peer1, Some(&address(1)), NOW);
+		store.on_connected(peer1, Some(&address(2)), NOW);
+		for _ in 0..MAX_ADDRESS_FAILURES + 1 {
+			store.on_dial_failure(&peer1, &address(2));
+		}
+		store.on_connected(peer2, Some(&address(3)), NOW - FORGET_AFTER.as_secs() - 1);
+		// Unknown peers are not added on failures.
+		store.on_dial_failure(&PeerId::random(), &address(4));
+
+		store.save(NOW);
+		assert_eq!(store.num_peers(), 1);
+		assert_eq!(store.known_peers()[0].addresses, vec![address(1)]);
+	}
+}
@@ -20,6 +20,7 @@ use crate::{
 	chain::Client,
 	config::{self, ProtocolId, WarpSyncProvider},
 	error,
+	recorder::Recorder,
 	request_responses::RequestFailure,
 	schema::v1::StateResponse,
 	utils::{interval, LruHashSet},
@@ -230,6 +231,8 @@ pub struct ProtocolConfig {
 	pub max_parallel_downloads: u32,
 	/// Enable state sync.
 	pub sync_mode: config::SyncMode,
+	/// Records the notifications sent and received, if enabled.
+	pub recorder: Option<Recorder>,
 }
 
 impl ProtocolConfig {
@@ -242,6 +245,7 @@ impl ProtocolConfig {
 				config::SyncMode::Fast { skip_proofs, storage_chain_mode } =>
 					sync::SyncMode::LightState { skip_proofs, storage_chain_mode },
 				config::SyncMode::Warp => sync::SyncMode::Warp,
+				config::SyncMode::HeadersFirst => sync::SyncMode::HeadersFirst,
 			}
 		}
 	}
@@ -249,7 +253,12 @@ impl ProtocolConfig {
 
 impl Default for ProtocolConfig {
 	fn default() -> ProtocolConfig {
-		Self { roles: Roles::FULL, max_parallel_downloads: 5, sync_mode: config::SyncMode::Full }
+		Self {
+			roles: Roles::FULL,
+			max_parallel_downloads: 5,
+			sync_mode: config::SyncMode::Full,
+			recorder: None,
+		}
 	}
 }
 
@@ -396,6 +405,7 @@ impl<B: BlockT> Protocol<B> {
 				fallback_names: Vec::new(),
 				handshake: block_announces_handshake,
 				max_notification_size: MAX_BLOCK_ANNOUNCE_SIZE,
+				bandwidth_limits: Default::default(),
 			};
 
 			Notifications::new(
@@ -407,9 +417,11 @@ impl<B: BlockT> Protocol<B> {
 							fallback_names: s.fallback_names.clone(),
 							handshake: hs,
 							max_notification_size: s.max_notification_size,
+							bandwidth_limits: s.bandwidth_limits,
 						},
 					),
 				),
+				config.recorder.clone(),
 			)
 		};
 
@@ -463,6 +475,11 @@ impl<B: BlockT> Protocol<B> {
 		self.behaviour.num_discovered_peers()
 	}
 
+	/// Returns the reputation of the given peer.
+	pub fn peer_reputation(&mut self, peer_id: PeerId) -> i32 {
+		self.behaviour.peer_reputation(peer_id)
+	}
+
 	/// Disconnects the given peer if we are connected to it.
 	pub fn disconnect_peer(&mut self, peer_id: &PeerId, protocol_name: &str) {
 		if let Some(position) = self.notification_protocols.iter().position(|p| *p == protocol_name)
@@ -1460,7 +1477,9 @@ impl<B: BlockT> NetworkBehaviour for Protocol<B> {
 						}
 					},
 					Poll::Ready(Ok(Err(e))) => {
-						peer.request.take();
+						if let Some((PeerRequest::State, _)) = peer.request.take() {
+							self.sync.on_state_request_failed(id);
+						}
 						debug!(target: "sync", "Request to peer {:?} failed: {:?}.", id, e);
 
 						match e {
@@ -1499,7 +1518,9 @@ impl<B: BlockT> NetworkBehaviour for Protocol<B> {
 						}
 					},
 					Poll::Ready(Err(oneshot::Canceled)) => {
-						peer.request.take();
+						if let Some((PeerRequest::State, _)) = peer.request.take() {
+							self.sync.on_state_request_failed(id);
+						}
 						trace!(
 							target: "sync",
 							"Request to peer {:?} failed due to oneshot being canceled.",
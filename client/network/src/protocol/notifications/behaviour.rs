@@ -16,8 +16,13 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use crate::protocol::notifications::handler::{
-	self, NotificationsSink, NotifsHandlerIn, NotifsHandlerOut, NotifsHandlerProto,
+use crate::{
+	config::BandwidthLimits,
+	protocol::notifications::handler::{
+		self, NotificationsSink, NotifsHandlerIn, NotifsHandlerOut, NotifsHandlerProto,
+	},
+	rate_limit::RateLimiter,
+	recorder::Recorder,
 };
 
 use bytes::BytesMut;
@@ -147,6 +152,8 @@ pub struct ProtocolConfig {
 	pub handshake: Vec<u8>,
 	/// Maximum allowed size for a notification.
 	pub max_notification_size: u64,
+	/// Bandwidth limits of the protocol, for all the peers combined.
+	pub bandwidth_limits: BandwidthLimits,
 }
 
 /// Identifier for a delay firing.
@@ -368,6 +375,7 @@ impl Notifications {
 	pub fn new(
 		peerset: sc_peerset::Peerset,
 		notif_protocols: impl Iterator<Item = ProtocolConfig>,
+		recorder: Option<Recorder>,
 	) -> Self {
 		let notif_protocols = notif_protocols
 			.map(|cfg| handler::ProtocolConfig {
@@ -375,6 +383,9 @@ impl Notifications {
 				fallback_names: cfg.fallback_names,
 				handshake: Arc::new(RwLock::new(cfg.handshake)),
 				max_notification_size: cfg.max_notification_size,
+				upload: cfg.bandwidth_limits.upload.map(RateLimiter::new),
+				download: cfg.bandwidth_limits.download.map(RateLimiter::new),
+				recorder: recorder.clone(),
 			})
 			.collect::<Vec<_>>();
 
@@ -411,6 +422,11 @@ impl Notifications {
 		self.peerset.num_discovered_peers()
 	}
 
+	/// Returns the reputation of the given peer in the peerset.
+	pub fn peer_reputation(&mut self, peer_id: PeerId) -> i32 {
+		self.peerset.peer_reputation(peer_id)
+	}
+
 	/// Returns the list of all the peers we have an open channel to.
 	pub fn open_peers<'a>(&'a self) -> impl Iterator<Item = &'a PeerId> + 'a {
 		self.peers.iter().filter(|(_, state)| state.is_open()).map(|((id, _), _)| id)
@@ -57,9 +57,13 @@
 //! It is illegal to send a [`NotifsHandlerIn::Open`] before a previously-emitted
 //! [`NotifsHandlerIn::Open`] has gotten an answer.
 
-use crate::protocol::notifications::upgrade::{
-	NotificationsHandshakeError, NotificationsIn, NotificationsInSubstream, NotificationsOut,
-	NotificationsOutSubstream, UpgradeCollec,
+use crate::{
+	protocol::notifications::upgrade::{
+		NotificationsHandshakeError, NotificationsIn, NotificationsInSubstream, NotificationsOut,
+		NotificationsOutSubstream, UpgradeCollec,
+	},
+	rate_limit::{RateLimiter, Throttle},
+	recorder::{Direction, MessageKind, Recorder},
 };
 
 use bytes::BytesMut;
@@ -153,6 +157,12 @@ pub struct ProtocolConfig {
 	pub handshake: Arc<RwLock<Vec<u8>>>,
 	/// Maximum allowed size for a notification.
 	pub max_notification_size: u64,
+	/// Limiter of the notifications sent, shared by all the connections.
+	pub upload: Option<RateLimiter>,
+	/// Limiter of the notifications received, shared by all the connections.
+	pub download: Option<RateLimiter>,
+	/// Records the notifications sent and received, if enabled.
+	pub recorder: Option<Recorder>,
 }
 
 /// Fields specific for each individual protocol.
@@ -163,6 +173,13 @@ struct Protocol {
 	/// Prototype for the inbound upgrade.
 	in_upgrade: NotificationsIn,
 
+	/// Throttles the notifications sent on this connection, if the protocol has an upload limit.
+	upload: Option<Throttle>,
+
+	/// Throttles the notifications received on this connection, if the protocol has a download
+	/// limit. Once exceeded, the inbound substream isn't read until the limiter is out of debt.
+	download: Option<Throttle>,
+
 	/// Current state of the substreams for this protocol.
 	state: State,
 }
@@ -253,7 +270,15 @@ impl IntoProtocolsHandler for NotifsHandlerProto {
 						config.max_notification_size,
 					);
 
-					Protocol { config, in_upgrade, state: State::Closed { pending_opening: false } }
+					let upload = config.upload.clone().map(Throttle::new);
+					let download = config.download.clone().map(Throttle::new);
+					Protocol {
+						config,
+						in_upgrade,
+						upload,
+						download,
+						state: State::Closed { pending_opening: false },
+					}
 				})
 				.collect(),
 			peer_id: *peer_id,
@@ -730,10 +755,10 @@ impl ProtocolsHandler for NotifsHandler {
 
 		// For each open substream, try send messages from `notifications_sink_rx` to the
 		// substream.
-		for protocol_index in 0..self.protocols.len() {
+		for protocol in &mut self.protocols {
 			if let State::Open {
 				notifications_sink_rx, out_substream: Some(out_substream), ..
-			} = &mut self.protocols[protocol_index].state
+			} = &mut protocol.state
 			{
 				loop {
 					// Only proceed with `out_substream.poll_ready_unpin` if there is an element
@@ -748,6 +773,13 @@ impl ProtocolsHandler for NotifsHandler {
 						Poll::Ready(None) | Poll::Pending => break,
 					}
 
+					// Wait for the upload limit of the protocol, if any.
+					if let Some(upload) = protocol.upload.as_mut() {
+						if upload.poll_ready(cx).is_pending() {
+							break
+						}
+					}
+
 					// Before we extract the element from `notifications_sink_rx`, check that the
 					// substream is ready to accept a message.
 					match out_substream.poll_ready_unpin(cx) {
@@ -768,6 +800,18 @@ impl ProtocolsHandler for NotifsHandler {
 						},
 					};
 
+					if let Some(upload) = protocol.upload.as_ref() {
+						upload.consume(message.len());
+					}
+					if let Some(recorder) = protocol.config.recorder.as_ref() {
+						recorder.record(
+							&self.peer_id,
+							&protocol.config.name,
+							Direction::Outbound,
+							MessageKind::Notification,
+							&message,
+						);
+					}
 					let _ = out_substream.start_send_unpin(message);
 					// Note that flushing is performed later down this function.
 				}
@@ -805,28 +849,46 @@ impl ProtocolsHandler for NotifsHandler {
 		for protocol_index in 0..self.protocols.len() {
 			// Inbound substreams being closed is always tolerated, except for the
 			// `OpenDesiredByRemote` state which might need to be switched back to `Closed`.
-			match &mut self.protocols[protocol_index].state {
+			let protocol = &mut self.protocols[protocol_index];
+			match &mut protocol.state {
 				State::Closed { .. } |
 				State::Open { in_substream: None, .. } |
 				State::Opening { in_substream: None } => {},
 
-				State::Open { in_substream: in_substream @ Some(_), .. } =>
+				State::Open { in_substream: in_substream @ Some(_), .. } => {
+					if let Some(download) = protocol.download.as_mut() {
+						if download.poll_ready(cx).is_pending() {
+							continue
+						}
+					}
 					match Stream::poll_next(Pin::new(in_substream.as_mut().unwrap()), cx) {
 						Poll::Pending => {},
 						Poll::Ready(Some(Ok(message))) => {
+							if let Some(download) = protocol.download.as_ref() {
+								download.consume(message.len());
+							}
+							if let Some(recorder) = protocol.config.recorder.as_ref() {
+								recorder.record(
+									&self.peer_id,
+									&protocol.config.name,
+									Direction::Inbound,
+									MessageKind::Notification,
+									&message,
+								);
+							}
 							let event = NotifsHandlerOut::Notification { protocol_index, message };
 							return Poll::Ready(ProtocolsHandlerEvent::Custom(event))
 						},
 						Poll::Ready(None) | Poll::Ready(Some(Err(_))) => *in_substream = None,
-					},
+					}
+				},
 
 				State::OpenDesiredByRemote { in_substream, pending_opening } =>
 					match NotificationsInSubstream::poll_process(Pin::new(in_substream), cx) {
 						Poll::Pending => {},
 						Poll::Ready(Ok(void)) => match void {},
 						Poll::Ready(Err(_)) => {
-							self.protocols[protocol_index].state =
-								State::Closed { pending_opening: *pending_opening };
+							protocol.state = State::Closed { pending_opening: *pending_opening };
 							return Poll::Ready(ProtocolsHandlerEvent::Custom(
 								NotifsHandlerOut::CloseDesired { protocol_index },
 							))
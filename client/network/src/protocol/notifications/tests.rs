@@ -85,7 +85,9 @@ fn build_nodes() -> (Swarm<CustomProtoWithAddr>, Swarm<CustomProtoWithAddr>) {
 					fallback_names: Vec::new(),
 					handshake: Vec::new(),
 					max_notification_size: 1024 * 1024,
+					bandwidth_limits: Default::default(),
 				}),
+				None,
 			),
 			addrs: addrs
 				.iter()
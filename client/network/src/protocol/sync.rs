@@ -37,6 +37,7 @@ use codec::Encode;
 use either::Either;
 use extra_requests::ExtraRequests;
 use futures::{stream::FuturesUnordered, task::Poll, Future, FutureExt, StreamExt};
+use headers::HeaderSync;
 use libp2p::PeerId;
 use log::{debug, error, info, trace, warn};
 use sc_consensus::{BlockImportError, BlockImportStatus, IncomingBlock};
@@ -66,6 +67,7 @@ use warp::{WarpProofRequest, WarpSync, WarpSyncProvider};
 
 mod blocks;
 mod extra_requests;
+mod headers;
 mod state;
 mod warp;
 
@@ -96,6 +98,14 @@ const MAX_CONCURRENT_BLOCK_ANNOUNCE_VALIDATIONS_PER_PEER: usize = 4;
 /// Pick the state to sync as the latest finalized number minus this.
 const STATE_SYNC_FINALITY_THRESHOLD: u32 = 8;
 
+/// State sync targets resumed from the database that are further than this behind the best block
+/// of the peers may have been pruned by the peers (the default pruning window).
+const STATE_SYNC_PRUNING_WINDOW: u32 = 256;
+
+/// Number of consecutive state requests the peers may fail to serve before a resumed state sync
+/// target that may have been pruned is given up.
+const MAX_RESUMED_STATE_SYNC_FAILURES: u32 = 3;
+
 /// We use a heuristic that with a high likelihood, by the time
 /// `MAJOR_SYNC_BLOCKS` have been imported we'll be on the same
 /// chain as (or at least closer to) the peer so we want to delay
@@ -225,6 +235,9 @@ pub struct ChainSync<B: BlockT> {
 	block_announce_validation_per_peer_stats: HashMap<PeerId, usize>,
 	/// State sync in progress, if any.
 	state_sync: Option<StateSync<B>>,
+	/// Number of consecutive state requests that failed, if the state sync in progress was resumed
+	/// from the database.
+	resumed_state_sync_failures: Option<u32>,
 	/// Warp sync in progress, if any.
 	warp_sync: Option<WarpSync<B>>,
 	/// Warp sync provider.
@@ -234,6 +247,8 @@ pub struct ChainSync<B: BlockT> {
 	import_existing: bool,
 	/// Gap download process.
 	gap_sync: Option<GapSync<B>>,
+	/// Header-first download process, if any.
+	header_sync: Option<HeaderSync<B>>,
 }
 
 /// All the data we have about a Peer that we are trying to sync with
@@ -308,6 +323,10 @@ pub enum PeerSyncState<B: BlockT> {
 	DownloadingWarpProof,
 	/// Actively downloading block history after warp sync.
 	DownloadingGap(NumberFor<B>),
+	/// Downloading headers in header-first sync, starting from the given Number.
+	DownloadingHeaders(NumberFor<B>),
+	/// Downloading block bodies in header-first sync, starting from the given Number.
+	DownloadingBodies(NumberFor<B>),
 }
 
 impl<B: BlockT> PeerSyncState<B> {
@@ -518,6 +537,8 @@ pub enum SyncMode {
 	LightState { storage_chain_mode: bool, skip_proofs: bool },
 	// Warp sync mode.
 	Warp,
+	// Sync headers first, then block bodies from many peers in parallel
+	HeadersFirst,
 }
 
 /// Result of [`ChainSync::has_slot_for_block_announce_validation`].
@@ -556,18 +577,33 @@ impl<B: BlockT> ChainSync<B> {
 			block_announce_validation: Default::default(),
 			block_announce_validation_per_peer_stats: Default::default(),
 			state_sync: None,
+			resumed_state_sync_failures: None,
 			warp_sync: None,
 			warp_sync_provider,
 			import_existing: false,
 			gap_sync: None,
+			header_sync: None,
 		};
 		sync.reset_sync_start_point()?;
+		if let SyncMode::LightState { skip_proofs, .. } = sync.mode {
+			sync.state_sync = StateSync::resume(sync.client.clone(), skip_proofs);
+			if let Some(state_sync) = &sync.state_sync {
+				sync.resumed_state_sync_failures = Some(0);
+				info!(
+					target: "sync",
+					"Resuming state sync for #{} ({}), {} MiB downloaded",
+					state_sync.target_block_num(),
+					state_sync.target(),
+					state_sync.progress().size / (1024 * 1024),
+				);
+			}
+		}
 		Ok(sync)
 	}
 
 	fn required_block_attributes(&self) -> BlockAttributes {
 		match self.mode {
-			SyncMode::Full =>
+			SyncMode::Full | SyncMode::HeadersFirst =>
 				BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION | BlockAttributes::BODY,
 			SyncMode::Light => BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION,
 			SyncMode::LightState { storage_chain_mode: false, .. } | SyncMode::Warp =>
@@ -581,7 +617,7 @@ impl<B: BlockT> ChainSync<B> {
 
 	fn skip_execution(&self) -> bool {
 		match self.mode {
-			SyncMode::Full => false,
+			SyncMode::Full | SyncMode::HeadersFirst => false,
 			SyncMode::Light => true,
 			SyncMode::LightState { .. } => true,
 			SyncMode::Warp => true,
@@ -890,7 +926,14 @@ impl<B: BlockT> ChainSync<B> {
 			return Either::Left(std::iter::empty())
 		}
 		let major_sync = self.status().state == SyncState::Downloading;
+		if major_sync && self.mode == SyncMode::HeadersFirst {
+			let (number, hash) = (self.best_queued_number, self.best_queued_hash);
+			self.header_sync
+				.get_or_insert_with(|| HeaderSync::new(number, hash))
+				.on_block_queued(number, hash);
+		}
 		let attrs = self.required_block_attributes();
+		let mut header_sync = if major_sync { self.header_sync.as_mut() } else { None };
 		let blocks = &mut self.blocks;
 		let fork_targets = &mut self.fork_targets;
 		let last_finalized =
@@ -930,6 +973,19 @@ impl<B: BlockT> ChainSync<B> {
 					state: AncestorSearchState::ExponentialBackoff(One::one()),
 				};
 				Some((id, ancestry_request::<B>(current)))
+			} else if let Some(header_sync) = header_sync.as_mut() {
+				// Header-first sync downloads the new blocks while we are far behind.
+				let (state, req) = header_sync.next_request(id, peer)?;
+				trace!(
+					target: "sync",
+					"New header-first request for {}, (best:{}, common:{}) {:?}",
+					id,
+					peer.best_number,
+					peer.common_number,
+					req,
+				);
+				peer.state = state;
+				Some((id, req))
 			} else if let Some((range, req)) = peer_block_request(
 				id,
 				peer,
@@ -997,6 +1053,24 @@ impl<B: BlockT> ChainSync<B> {
 			if sync.is_complete() {
 				return None
 			}
+			let target = sync.target_block_num();
+			if matches!(
+				self.resumed_state_sync_failures,
+				Some(failures) if failures >= MAX_RESUMED_STATE_SYNC_FAILURES
+			) && target + STATE_SYNC_PRUNING_WINDOW.saturated_into() <
+				self.median_peer_best_number()
+			{
+				// Another target is picked on the next finalized block.
+				info!(
+					target: "sync",
+					"State sync target #{} ({}) is no longer served by the peers, restarting state sync",
+					target,
+					sync.target(),
+				);
+				sync.clear_saved_progress();
+				self.state_sync = None;
+				return None
+			}
 			for (id, peer) in self.peers.iter_mut() {
 				if peer.state.is_available() && peer.common_number >= sync.target_block_num() {
 					peer.state = PeerSyncState::DownloadingState;
@@ -1095,6 +1169,30 @@ impl<B: BlockT> ChainSync<B> {
 						}
 						self.drain_blocks()
 					},
+					PeerSyncState::DownloadingHeaders(_) => {
+						peer.state = PeerSyncState::Available;
+						if let Some(header_sync) = &mut self.header_sync {
+							let start_block = validate_blocks::<B>(&blocks, who, Some(request))?;
+							header_sync.on_headers(who, start_block, blocks)?;
+							trace!(
+								target: "sync",
+								"Verified headers up to #{}, {} waiting for block bodies",
+								header_sync.tip_number(),
+								header_sync.num_verified(),
+							);
+							// Bodies of the new verified headers may be requested from any peer.
+							self.pending_requests.set_all();
+						}
+						Vec::new()
+					},
+					PeerSyncState::DownloadingBodies(start_block) => {
+						let start_block = *start_block;
+						peer.state = PeerSyncState::Available;
+						if let Some(header_sync) = &mut self.header_sync {
+							header_sync.on_bodies(who, start_block, request, blocks)?;
+						}
+						self.drain_header_sync()
+					},
 					PeerSyncState::DownloadingGap(start_block) => {
 						let start_block = *start_block;
 						peer.state = PeerSyncState::Available;
@@ -1307,7 +1405,14 @@ impl<B: BlockT> ChainSync<B> {
 				response.entries.len(),
 				response.proof.len(),
 			);
-			sync.import(response)
+			let result = sync.import(response);
+			if let Some(failures) = &mut self.resumed_state_sync_failures {
+				match result {
+					state::ImportResult::BadResponse => *failures += 1,
+					_ => *failures = 0,
+				}
+			}
+			result
 		} else if let Some(sync) = &mut self.warp_sync {
 			debug!(
 				target: "sync",
@@ -1348,6 +1453,18 @@ impl<B: BlockT> ChainSync<B> {
 		}
 	}
 
+	/// Call when a state request that we made failed.
+	pub fn on_state_request_failed(&mut self, who: &PeerId) {
+		if let Some(peer) = self.peers.get_mut(who) {
+			if let PeerSyncState::DownloadingState = peer.state {
+				peer.state = PeerSyncState::Available;
+			}
+		}
+		if let Some(failures) = &mut self.resumed_state_sync_failures {
+			*failures += 1;
+		}
+	}
+
 	/// Handle a response from the remote to a warp proof request that we made.
 	///
 	/// Returns next request.
@@ -1554,7 +1671,9 @@ impl<B: BlockT> ChainSync<B> {
 							"State sync is complete ({} MiB), restarting block sync.",
 							self.state_sync.as_ref().map_or(0, |s| s.progress().size / (1024 * 1024)),
 						);
-						self.state_sync = None;
+						if let Some(state_sync) = self.state_sync.take() {
+							state_sync.clear_saved_progress();
+						}
 						self.mode = SyncMode::Full;
 						output.extend(self.restart());
 					}
@@ -1652,10 +1771,7 @@ impl<B: BlockT> ChainSync<B> {
 		if let SyncMode::LightState { skip_proofs, .. } = &self.mode {
 			if self.state_sync.is_none() && !self.peers.is_empty() && self.queue_blocks.is_empty() {
 				// Finalized a recent block.
-				let mut heads: Vec<_> =
-					self.peers.iter().map(|(_, peer)| peer.best_number).collect();
-				heads.sort();
-				let median = heads[heads.len() / 2];
+				let median = self.median_peer_best_number();
 				if number + STATE_SYNC_FINALITY_THRESHOLD.saturated_into() >= median {
 					if let Ok(Some(header)) = self.client.header(BlockId::hash(hash.clone())) {
 						log::debug!(
@@ -1664,8 +1780,12 @@ impl<B: BlockT> ChainSync<B> {
 							number,
 							hash,
 						);
-						self.state_sync =
-							Some(StateSync::new(self.client.clone(), header, *skip_proofs));
+						self.state_sync = Some(StateSync::new_resumable(
+							self.client.clone(),
+							header,
+							*skip_proofs,
+						));
+						self.resumed_state_sync_failures = None;
 					}
 				}
 			}
@@ -1680,6 +1800,13 @@ impl<B: BlockT> ChainSync<B> {
 		}
 	}
 
+	/// Median of the best block numbers of the peers, or zero without peers.
+	fn median_peer_best_number(&self) -> NumberFor<B> {
+		let mut heads: Vec<_> = self.peers.values().map(|peer| peer.best_number).collect();
+		heads.sort();
+		heads.get(heads.len() / 2).copied().unwrap_or_else(Zero::zero)
+	}
+
 	/// Called when a block has been queued for import.
 	///
 	/// Updates our internal state for best queued block and then goes
@@ -2049,6 +2176,9 @@ impl<B: BlockT> ChainSync<B> {
 		if let Some(gap_sync) = &mut self.gap_sync {
 			gap_sync.blocks.clear_peer_download(who)
 		}
+		if let Some(header_sync) = &mut self.header_sync {
+			header_sync.peer_disconnected(who)
+		}
 		self.peers.remove(who);
 		self.extra_justifications.peer_disconnected(who);
 		self.pending_requests.set_all();
@@ -2071,6 +2201,7 @@ impl<B: BlockT> ChainSync<B> {
 		&'a mut self,
 	) -> impl Iterator<Item = Result<(PeerId, BlockRequest<B>), BadPeer>> + 'a {
 		self.blocks.clear();
+		self.header_sync = None;
 		if let Err(e) = self.reset_sync_start_point() {
 			warn!(target: "sync", "💔  Unable to restart sync. :{:?}", e);
 		}
@@ -2121,21 +2252,20 @@ impl<B: BlockT> ChainSync<B> {
 		self.import_existing = false;
 		self.best_queued_hash = info.best_hash;
 		self.best_queued_number = info.best_number;
-		if self.mode == SyncMode::Full {
-			if self.client.block_status(&BlockId::hash(info.best_hash))? !=
+		if matches!(self.mode, SyncMode::Full | SyncMode::HeadersFirst) &&
+			self.client.block_status(&BlockId::hash(info.best_hash))? !=
 				BlockStatus::InChainWithState
-			{
-				self.import_existing = true;
-				// Latest state is missing, start with the last finalized state or genesis instead.
-				if let Some((hash, number)) = info.finalized_state {
-					debug!(target: "sync", "Starting from finalized state #{}", number);
-					self.best_queued_hash = hash;
-					self.best_queued_number = number;
-				} else {
-					debug!(target: "sync", "Restarting from genesis");
-					self.best_queued_hash = Default::default();
-					self.best_queued_number = Zero::zero();
-				}
+		{
+			self.import_existing = true;
+			// Latest state is missing, start with the last finalized state or genesis instead.
+			if let Some((hash, number)) = info.finalized_state {
+				debug!(target: "sync", "Starting from finalized state #{}", number);
+				self.best_queued_hash = hash;
+				self.best_queued_number = number;
+			} else {
+				debug!(target: "sync", "Restarting from genesis");
+				self.best_queued_hash = Default::default();
+				self.best_queued_number = Zero::zero();
 			}
 		}
 		if let Some((start, end)) = info.block_gap {
@@ -2182,8 +2312,18 @@ impl<B: BlockT> ChainSync<B> {
 
 	/// Drain the downloaded block set up to the first gap.
 	fn drain_blocks(&mut self) -> Vec<IncomingBlock<B>> {
-		self.blocks
-			.drain(self.best_queued_number + One::one())
+		let blocks = self.blocks.drain(self.best_queued_number + One::one());
+		self.incoming_blocks(blocks)
+	}
+
+	/// Drain the blocks downloaded by header-first sync up to the first gap.
+	fn drain_header_sync(&mut self) -> Vec<IncomingBlock<B>> {
+		let blocks = self.header_sync.as_mut().map(|sync| sync.drain()).unwrap_or_default();
+		self.incoming_blocks(blocks)
+	}
+
+	fn incoming_blocks(&self, blocks: Vec<blocks::BlockData<B>>) -> Vec<IncomingBlock<B>> {
+		blocks
 			.into_iter()
 			.map(|block_data| {
 				let justifications = block_data
@@ -2882,6 +3022,78 @@ mod test {
 		assert!(matches!(res, OnBlockData::Import(_, blocks) if blocks.is_empty()));
 	}
 
+	#[test]
+	fn headers_first_sync_downloads_bodies_from_different_peers() {
+		sp_tracing::try_init_simple();
+
+		let mut client = Arc::new(TestClientBuilder::new().build());
+		let blocks = (0..100).map(|_| build_block(&mut client, None, false)).collect::<Vec<_>>();
+		let best_block = blocks.last().unwrap().clone();
+
+		let empty_client = Arc::new(TestClientBuilder::new().build());
+		let mut sync = ChainSync::new(
+			SyncMode::HeadersFirst,
+			empty_client,
+			Box::new(DefaultBlockAnnounceValidator),
+			5,
+			None,
+		)
+		.unwrap();
+
+		let peer_id1 = PeerId::random();
+		let peer_id2 = PeerId::random();
+		sync.new_peer(peer_id1, best_block.hash(), 100).unwrap();
+		sync.new_peer(peer_id2, best_block.hash(), 100).unwrap();
+
+		// All the headers fit in a single request, so only one peer is busy.
+		let requests = sync.block_requests().map(|(p, r)| (*p, r)).collect::<Vec<_>>();
+		assert_eq!(requests.len(), 1);
+		let (peer, request) = requests[0].clone();
+		assert_eq!(request.fields, BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION);
+		assert_eq!(request.from, FromBlock::Hash(best_block.hash()));
+		assert_eq!(request.max, Some(100));
+
+		let mut response = create_block_response(blocks.iter().rev().cloned().collect());
+		response.blocks.iter_mut().for_each(|b| b.body = None);
+		let res = sync.on_block_data(&peer, Some(request), response).unwrap();
+		assert!(matches!(res, OnBlockData::Import(_, blocks) if blocks.is_empty()));
+
+		// Bodies are downloaded from both peers in disjoint ranges.
+		let mut requests = sync.block_requests().map(|(p, r)| (*p, r)).collect::<Vec<_>>();
+		assert_eq!(requests.len(), 2);
+		assert_ne!(requests[0].0, requests[1].0);
+		requests.sort_by_key(|(_, r)| r.max);
+		let (peer1, request1) = requests[1].clone();
+		let (peer2, request2) = requests[0].clone();
+		assert_eq!(request1.fields, BlockAttributes::BODY);
+		assert_eq!(request1.direction, message::Direction::Ascending);
+		assert_eq!(request1.from, FromBlock::Hash(blocks[0].hash()));
+		assert_eq!(request1.max, Some(64));
+		assert_eq!(request2.from, FromBlock::Hash(blocks[64].hash()));
+		assert_eq!(request2.max, Some(36));
+
+		let bodies = |blocks: &[Block]| {
+			let mut response = create_block_response(blocks.to_vec());
+			response.blocks.iter_mut().for_each(|b| b.header = None);
+			response
+		};
+
+		// The second range waits for the first one to be imported.
+		let res = sync.on_block_data(&peer2, Some(request2), bodies(&blocks[64..])).unwrap();
+		assert!(matches!(res, OnBlockData::Import(_, blocks) if blocks.is_empty()));
+
+		let res = sync.on_block_data(&peer1, Some(request1), bodies(&blocks[..64])).unwrap();
+		match res {
+			OnBlockData::Import(_, imported) => {
+				assert_eq!(imported.len(), 100);
+				assert!(imported.iter().zip(&blocks).all(|(i, b)| i.hash == b.hash() &&
+					i.header.as_ref() == Some(b.header()) &&
+					i.body.as_ref() == Some(&b.extrinsics().to_vec())));
+			},
+			_ => panic!("Expected blocks to import"),
+		}
+	}
+
 	fn unwrap_from_block_number(from: FromBlock<Hash, u64>) -> u64 {
 		if let FromBlock::Number(from) = from {
 			from
@@ -3167,4 +3379,50 @@ mod test {
 		sync.peer_disconnected(&peer_id1);
 		assert!(sync.fork_targets.len() == 0);
 	}
+
+	#[test]
+	fn gives_up_resumed_state_sync_of_pruned_target() {
+		let client = Arc::new(TestClientBuilder::new().set_no_genesis().build());
+		let target = client.header(&BlockId::Number(0)).unwrap().unwrap();
+		let mut state_sync = StateSync::new_resumable(client.clone(), target, true);
+		let response = StateResponse {
+			entries: vec![crate::schema::v1::KeyValueStateEntry {
+				state_root: Vec::new(),
+				entries: vec![crate::schema::v1::StateEntry { key: vec![1], value: vec![1] }],
+				complete: false,
+			}],
+			proof: Vec::new(),
+		};
+		assert!(matches!(state_sync.import(response), state::ImportResult::Continue));
+
+		let new_sync = || {
+			let mode = SyncMode::LightState { storage_chain_mode: false, skip_proofs: true };
+			let validator = Box::new(DefaultBlockAnnounceValidator);
+			ChainSync::new(mode, client.clone(), validator, 1, None).unwrap()
+		};
+
+		// A peer that may still have the state of the target is asked for it.
+		let mut sync = new_sync();
+		assert!(sync.status().state_sync.is_some());
+		sync.new_peer(PeerId::random(), Hash::random(), STATE_SYNC_PRUNING_WINDOW.into())
+			.unwrap();
+		assert_eq!(sync.state_request().unwrap().1.start, vec![vec![1]]);
+
+		// Peers that may have pruned it are asked too, until they repeatedly fail to serve it. The
+		// saved progress is then discarded.
+		let mut sync = new_sync();
+		assert!(sync.status().state_sync.is_some());
+		for _ in 0..3 {
+			let best_number = (STATE_SYNC_PRUNING_WINDOW + 1).into();
+			sync.new_peer(PeerId::random(), Hash::random(), best_number).unwrap();
+		}
+		for _ in 0..MAX_RESUMED_STATE_SYNC_FAILURES {
+			let (peer, request) = sync.state_request().unwrap();
+			assert_eq!(request.start, vec![vec![1]]);
+			sync.on_state_request_failed(&peer);
+		}
+		assert!(sync.state_request().is_none());
+		assert!(sync.status().state_sync.is_none());
+		assert!(StateSync::resume(client, true).is_none());
+	}
 }
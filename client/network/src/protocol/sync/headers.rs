@@ -0,0 +1,564 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Header-first sync support.
+//!
+//! Headers are downloaded in disjoint ranges from different peers and appended to a chain of
+//! verified headers once they link to it by parent hash. Block bodies are then downloaded in
+//! disjoint ranges from different peers, checked against the verified headers and handed to the
+//! import queue as soon as they are contiguous, so that import overlaps the download.
+
+use super::{
+	blocks::{BlockCollection, BlockData},
+	rep, BadPeer, PeerSync, PeerSyncState, MAX_BLOCKS_TO_REQUEST, MAX_DOWNLOAD_AHEAD,
+};
+use crate::protocol::message::{self, BlockAttributes, BlockRequest};
+use codec::Encode;
+use libp2p::PeerId;
+use log::{debug, trace};
+use sp_runtime::traits::{
+	Block as BlockT, Hash, HashFor, Header, NumberFor, One, SaturatedConversion,
+};
+use std::{cmp, collections::BTreeMap};
+
+/// Maximum headers to request in a single packet.
+const MAX_HEADERS_TO_REQUEST: usize = 128;
+
+/// Maximum number of verified headers to keep ahead of the blocks queued for import.
+const MAX_HEADERS_AHEAD: u32 = 8 * MAX_DOWNLOAD_AHEAD;
+
+/// Number of consecutive header ranges that may fail to link to the verified chain before it is
+/// discarded and downloaded again from the last block queued for import.
+const MAX_LINK_FAILURES: u32 = 3;
+
+/// Header-first sync state machine.
+pub struct HeaderSync<B: BlockT> {
+	/// Header ranges being downloaded.
+	headers: BlockCollection<B>,
+	/// Block body ranges being downloaded.
+	bodies: BlockCollection<B>,
+	/// Verified headers and justifications of the blocks that are not queued for import yet.
+	verified: BTreeMap<NumberFor<B>, message::BlockData<B>>,
+	/// Number of the last verified header.
+	tip_number: NumberFor<B>,
+	/// Hash of the last verified header.
+	tip_hash: B::Hash,
+	/// Number of the last block queued for import.
+	queued_number: NumberFor<B>,
+	/// Hash of the last block queued for import.
+	queued_hash: B::Hash,
+	/// Number of consecutive header ranges that did not link to the verified chain.
+	link_failures: u32,
+}
+
+impl<B: BlockT> HeaderSync<B> {
+	/// Create a new instance, starting after the given block.
+	pub fn new(number: NumberFor<B>, hash: B::Hash) -> Self {
+		Self {
+			headers: BlockCollection::new(),
+			bodies: BlockCollection::new(),
+			verified: BTreeMap::new(),
+			tip_number: number,
+			tip_hash: hash,
+			queued_number: number,
+			queued_hash: hash,
+			link_failures: 0,
+		}
+	}
+
+	/// Number of the last verified header.
+	pub fn tip_number(&self) -> NumberFor<B> {
+		self.tip_number
+	}
+
+	/// Number of verified headers waiting for their block body.
+	pub fn num_verified(&self) -> usize {
+		self.verified.len()
+	}
+
+	/// Update the last block queued for import, when it was not queued by header-first sync.
+	///
+	/// Verified headers up to this block are dropped. If the block is not part of the verified
+	/// chain, the sync restarts from it.
+	pub fn on_block_queued(&mut self, number: NumberFor<B>, hash: B::Hash) {
+		if number <= self.queued_number {
+			return
+		}
+		if self.verified.get(&number).map(|b| b.hash) == Some(hash) {
+			self.verified = self.verified.split_off(&(number + One::one()));
+			self.queued_number = number;
+			self.queued_hash = hash;
+		} else {
+			trace!(target: "sync", "Restarting header-first sync from #{} ({})", number, hash);
+			self.queued_number = number;
+			self.queued_hash = hash;
+			self.reset();
+		}
+	}
+
+	/// Get the next request for the given peer, if any.
+	///
+	/// Block bodies of verified headers are requested first. Otherwise the next range of
+	/// headers is requested, if the verified chain is not too far ahead already.
+	pub fn next_request(
+		&mut self,
+		who: &PeerId,
+		peer: &PeerSync<B>,
+	) -> Option<(PeerSyncState<B>, BlockRequest<B>)> {
+		self.body_request(who, peer).or_else(|| self.header_request(who, peer))
+	}
+
+	fn body_request(
+		&mut self,
+		who: &PeerId,
+		peer: &PeerSync<B>,
+	) -> Option<(PeerSyncState<B>, BlockRequest<B>)> {
+		let range = self.bodies.needed_blocks(
+			*who,
+			MAX_BLOCKS_TO_REQUEST,
+			cmp::min(peer.best_number, self.tip_number),
+			self.queued_number,
+			1,
+			MAX_DOWNLOAD_AHEAD,
+		)?;
+		let first = match self.verified.get(&range.start) {
+			Some(first) => first.hash,
+			None => {
+				// Stale range left from before a restart.
+				self.bodies.clear_peer_download(who);
+				return None
+			},
+		};
+		let request = message::generic::BlockRequest {
+			id: 0,
+			fields: BlockAttributes::BODY,
+			from: message::FromBlock::Hash(first),
+			to: None,
+			direction: message::Direction::Ascending,
+			max: Some((range.end - range.start).saturated_into::<u32>()),
+		};
+		Some((PeerSyncState::DownloadingBodies(range.start), request))
+	}
+
+	fn header_request(
+		&mut self,
+		who: &PeerId,
+		peer: &PeerSync<B>,
+	) -> Option<(PeerSyncState<B>, BlockRequest<B>)> {
+		if self.tip_number >= self.queued_number + MAX_HEADERS_AHEAD.into() {
+			trace!(target: "sync", "Too many headers ahead of the import queue");
+			return None
+		}
+		let range = self.headers.needed_blocks(
+			*who,
+			MAX_HEADERS_TO_REQUEST,
+			peer.best_number,
+			self.tip_number,
+			1,
+			MAX_HEADERS_AHEAD,
+		)?;
+
+		// The end is not part of the range.
+		let last = range.end - One::one();
+		let from = if peer.best_number == last {
+			message::FromBlock::Hash(peer.best_hash)
+		} else {
+			message::FromBlock::Number(last)
+		};
+		let request = message::generic::BlockRequest {
+			id: 0,
+			fields: BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION,
+			from,
+			to: None,
+			direction: message::Direction::Descending,
+			max: Some((range.end - range.start).saturated_into::<u32>()),
+		};
+		Some((PeerSyncState::DownloadingHeaders(range.start), request))
+	}
+
+	/// Handle a response to a header request, in ascending order.
+	///
+	/// The headers are expected to be validated against the request already. Headers that link
+	/// to the verified chain are appended to it.
+	pub fn on_headers(
+		&mut self,
+		who: &PeerId,
+		start: Option<NumberFor<B>>,
+		headers: Vec<message::BlockData<B>>,
+	) -> Result<(), BadPeer> {
+		self.headers.clear_peer_download(who);
+		for pair in headers.windows(2) {
+			let links = match (&pair[0].header, &pair[1].header) {
+				(Some(parent), Some(child)) =>
+					child.parent_hash() == &pair[0].hash &&
+						*child.number() == *parent.number() + One::one(),
+				_ => false,
+			};
+			if !links {
+				debug!(target: "sync", "Received a broken header chain from {}", who);
+				return Err(BadPeer(*who, rep::BAD_BLOCK))
+			}
+		}
+		if let Some(start) = start {
+			self.headers.insert(start, headers, *who);
+		}
+		self.verify_headers();
+		Ok(())
+	}
+
+	/// Append the downloaded headers that link to the verified chain.
+	fn verify_headers(&mut self) {
+		for data in self.headers.drain(self.tip_number + One::one()) {
+			let (number, parent_hash) = match &data.block.header {
+				Some(header) => (*header.number(), *header.parent_hash()),
+				None => break,
+			};
+			if number <= self.tip_number {
+				continue
+			}
+			if number != self.tip_number + One::one() || parent_hash != self.tip_hash {
+				debug!(
+					target: "sync",
+					"Header #{} ({}) received from {:?} does not extend the verified chain at #{} ({})",
+					number,
+					data.block.hash,
+					data.origin,
+					self.tip_number,
+					self.tip_hash,
+				);
+				self.link_failures += 1;
+				if self.link_failures >= MAX_LINK_FAILURES {
+					debug!(
+						target: "sync",
+						"Discarding verified headers after #{} ({})",
+						self.queued_number,
+						self.queued_hash,
+					);
+					self.reset();
+				}
+				// The following headers can't be verified either.
+				break
+			}
+			self.link_failures = 0;
+			self.tip_number = number;
+			self.tip_hash = data.block.hash;
+			self.verified.insert(number, data.block);
+		}
+	}
+
+	/// Handle a response to a body request made with the given starting block.
+	///
+	/// Bodies of blocks that are no longer part of the verified chain are ignored.
+	pub fn on_bodies(
+		&mut self,
+		who: &PeerId,
+		start: NumberFor<B>,
+		request: BlockRequest<B>,
+		blocks: Vec<message::BlockData<B>>,
+	) -> Result<(), BadPeer> {
+		self.bodies.clear_peer_download(who);
+		if Some(blocks.len() as u32) > request.max {
+			debug!(
+				target: "sync",
+				"Received more blocks than requested from {}. Expected in maximum {:?}, got {}.",
+				who,
+				request.max,
+				blocks.len(),
+			);
+			return Err(BadPeer(*who, rep::NOT_REQUESTED))
+		}
+		let requested = self.verified.get(&start).map(|b| message::FromBlock::Hash(b.hash));
+		if requested != Some(request.from) {
+			trace!(target: "sync", "Ignored obsolete block bodies from {}", who);
+			return Ok(())
+		}
+		let mut number = start;
+		for block in &blocks {
+			let header = match self.verified.get(&number) {
+				Some(verified) if verified.hash == block.hash => verified.header.as_ref(),
+				_ => {
+					debug!(
+						target: "sync",
+						"Received block that was not requested from {}. Expected #{}, got {}.",
+						who,
+						number,
+						block.hash,
+					);
+					return Err(BadPeer(*who, rep::NOT_REQUESTED))
+				},
+			};
+			let body = match &block.body {
+				Some(body) => body,
+				None => {
+					trace!(target: "sync", "Missing requested body for a block in response from {}.", who);
+					return Err(BadPeer(*who, rep::BAD_RESPONSE))
+				},
+			};
+			if let Some(header) = header {
+				let expected = *header.extrinsics_root();
+				let got =
+					HashFor::<B>::ordered_trie_root(body.iter().map(Encode::encode).collect());
+				if expected != got {
+					debug!(
+						target: "sync",
+						"Bad extrinsic root for a block {} received from {}. Expected {:?}, got {:?}",
+						block.hash,
+						who,
+						expected,
+						got,
+					);
+					return Err(BadPeer(*who, rep::BAD_BLOCK))
+				}
+			}
+			number += One::one();
+		}
+		self.bodies.insert(start, blocks, *who);
+		Ok(())
+	}
+
+	/// Take the blocks that are ready for import: the downloaded bodies that follow the last
+	/// queued block, combined with their verified headers and justifications.
+	pub fn drain(&mut self) -> Vec<BlockData<B>> {
+		let mut drained = Vec::new();
+		for data in self.bodies.drain(self.queued_number + One::one()) {
+			let next = self.queued_number + One::one();
+			let mut block = match self.verified.remove(&next) {
+				Some(verified) if verified.hash == data.block.hash => verified,
+				verified => {
+					if let Some(verified) = verified {
+						self.verified.insert(next, verified);
+					}
+					trace!(target: "sync", "Ignored obsolete block body {}", data.block.hash);
+					continue
+				},
+			};
+			block.body = data.block.body;
+			self.queued_number = next;
+			self.queued_hash = block.hash;
+			drained.push(BlockData { block, origin: data.origin });
+		}
+		drained
+	}
+
+	/// Cancel the requests made to a disconnected peer.
+	pub fn peer_disconnected(&mut self, who: &PeerId) {
+		self.headers.clear_peer_download(who);
+		self.bodies.clear_peer_download(who);
+	}
+
+	/// Discard all verified headers and downloads, and start again from the last queued block.
+	fn reset(&mut self) {
+		self.headers.clear();
+		self.bodies.clear();
+		self.verified.clear();
+		self.tip_number = self.queued_number;
+		self.tip_hash = self.queued_hash;
+		self.link_failures = 0;
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::{Block as RawBlock, ExtrinsicWrapper, Header as TestHeader},
+		traits::BlakeTwo256,
+	};
+
+	type Block = RawBlock<ExtrinsicWrapper<u64>>;
+
+	fn chain(parent: H256, from: u64, len: u64) -> Vec<(TestHeader, Vec<ExtrinsicWrapper<u64>>)> {
+		let mut parent_hash = parent;
+		(from..from + len)
+			.map(|number| {
+				let body = vec![ExtrinsicWrapper::from(number)];
+				let header = TestHeader {
+					number,
+					parent_hash,
+					state_root: Default::default(),
+					extrinsics_root: BlakeTwo256::ordered_trie_root(
+						body.iter().map(Encode::encode).collect(),
+					),
+					digest: Default::default(),
+				};
+				parent_hash = header.hash();
+				(header, body)
+			})
+			.collect()
+	}
+
+	fn block_data(
+		header: Option<&TestHeader>,
+		hash: H256,
+		body: Option<Vec<ExtrinsicWrapper<u64>>>,
+	) -> message::BlockData<Block> {
+		message::generic::BlockData {
+			hash,
+			header: header.cloned(),
+			body,
+			indexed_body: None,
+			receipt: None,
+			message_queue: None,
+			justification: None,
+			justifications: None,
+		}
+	}
+
+	fn headers(
+		blocks: &[(TestHeader, Vec<ExtrinsicWrapper<u64>>)],
+	) -> Vec<message::BlockData<Block>> {
+		blocks.iter().map(|(h, _)| block_data(Some(h), h.hash(), None)).collect()
+	}
+
+	fn bodies(
+		blocks: &[(TestHeader, Vec<ExtrinsicWrapper<u64>>)],
+	) -> Vec<message::BlockData<Block>> {
+		blocks
+			.iter()
+			.map(|(h, b)| block_data(None, h.hash(), Some(b.clone())))
+			.collect()
+	}
+
+	fn peer(best: &TestHeader) -> PeerSync<Block> {
+		PeerSync {
+			peer_id: PeerId::random(),
+			common_number: 0,
+			best_hash: best.hash(),
+			best_number: best.number,
+			state: PeerSyncState::Available,
+		}
+	}
+
+	#[test]
+	fn downloads_disjoint_ranges_and_imports_contiguous_blocks() {
+		let genesis = H256::random();
+		let blocks = chain(genesis, 1, 300);
+		let mut sync = HeaderSync::<Block>::new(0, genesis);
+		let peer1 = peer(&blocks[299].0);
+		let peer2 = peer(&blocks[299].0);
+		let (id1, id2) = (peer1.peer_id, peer2.peer_id);
+
+		// Nothing verified yet, so headers are requested from both peers in disjoint ranges.
+		let (state1, request1) = sync.next_request(&id1, &peer1).unwrap();
+		let (state2, request2) = sync.next_request(&id2, &peer2).unwrap();
+		assert_eq!(state1, PeerSyncState::DownloadingHeaders(1));
+		assert_eq!(state2, PeerSyncState::DownloadingHeaders(129));
+		assert_eq!(request1.from, message::FromBlock::Number(128));
+		assert_eq!(request2.from, message::FromBlock::Number(256));
+		assert_eq!(request1.fields, BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION);
+
+		// The second range can't be verified before the first one.
+		sync.on_headers(&id2, Some(129), headers(&blocks[128..256])).unwrap();
+		assert_eq!(sync.tip_number(), 0);
+		sync.on_headers(&id1, Some(1), headers(&blocks[..128])).unwrap();
+		assert_eq!(sync.tip_number(), 256);
+		assert_eq!(sync.num_verified(), 256);
+
+		// Bodies of verified headers are requested before more headers.
+		let (state1, request1) = sync.next_request(&id1, &peer1).unwrap();
+		let (state2, request2) = sync.next_request(&id2, &peer2).unwrap();
+		assert_eq!(state1, PeerSyncState::DownloadingBodies(1));
+		assert_eq!(state2, PeerSyncState::DownloadingBodies(65));
+		assert_eq!(request1.from, message::FromBlock::Hash(blocks[0].0.hash()));
+		assert_eq!(request2.from, message::FromBlock::Hash(blocks[64].0.hash()));
+		assert_eq!(request1.fields, BlockAttributes::BODY);
+
+		sync.on_bodies(&id2, 65, request2, bodies(&blocks[64..128])).unwrap();
+		assert!(sync.drain().is_empty());
+		sync.on_bodies(&id1, 1, request1, bodies(&blocks[..64])).unwrap();
+		let drained = sync.drain();
+		assert_eq!(drained.len(), 128);
+		assert!(drained
+			.iter()
+			.zip(&blocks)
+			.all(|(d, (h, b))| d.block.header.as_ref() == Some(h) &&
+				d.block.body.as_ref() == Some(b)));
+		assert_eq!(sync.num_verified(), 128);
+	}
+
+	#[test]
+	fn rejects_bad_bodies() {
+		let genesis = H256::random();
+		let blocks = chain(genesis, 1, 10);
+		let mut sync = HeaderSync::<Block>::new(0, genesis);
+		let peer = peer(&blocks[9].0);
+		let id = peer.peer_id;
+		sync.next_request(&id, &peer).unwrap();
+		sync.on_headers(&id, Some(1), headers(&blocks)).unwrap();
+
+		let (_, request) = sync.next_request(&id, &peer).unwrap();
+		let mut bad = bodies(&blocks);
+		bad[3].body = Some(vec![ExtrinsicWrapper::from(42)]);
+		assert_eq!(sync.on_bodies(&id, 1, request, bad).unwrap_err().1, rep::BAD_BLOCK);
+
+		let (_, request) = sync.next_request(&id, &peer).unwrap();
+		let mut missing = bodies(&blocks);
+		missing[5].body = None;
+		assert_eq!(sync.on_bodies(&id, 1, request, missing).unwrap_err().1, rep::BAD_RESPONSE);
+
+		let (_, request) = sync.next_request(&id, &peer).unwrap();
+		let mut other = bodies(&blocks);
+		other[2].hash = H256::random();
+		assert_eq!(sync.on_bodies(&id, 1, request, other).unwrap_err().1, rep::NOT_REQUESTED);
+		assert!(sync.drain().is_empty());
+	}
+
+	#[test]
+	fn discards_headers_that_do_not_link() {
+		let genesis = H256::random();
+		let blocks = chain(genesis, 1, 10);
+		let fork = chain(H256::random(), 1, 10);
+		let mut sync = HeaderSync::<Block>::new(0, genesis);
+		let peer = peer(&blocks[9].0);
+		let id = peer.peer_id;
+
+		let mut broken = headers(&blocks);
+		broken.swap(3, 4);
+		sync.next_request(&id, &peer).unwrap();
+		assert_eq!(sync.on_headers(&id, Some(1), broken).unwrap_err().1, rep::BAD_BLOCK);
+
+		sync.next_request(&id, &peer).unwrap();
+		sync.on_headers(&id, Some(1), headers(&fork)).unwrap();
+		assert_eq!(sync.tip_number(), 0);
+
+		let (state, _) = sync.next_request(&id, &peer).unwrap();
+		assert_eq!(state, PeerSyncState::DownloadingHeaders(1));
+		sync.on_headers(&id, Some(1), headers(&blocks)).unwrap();
+		assert_eq!(sync.tip_number(), 10);
+	}
+
+	#[test]
+	fn restarts_when_another_block_is_queued() {
+		let genesis = H256::random();
+		let blocks = chain(genesis, 1, 10);
+		let mut sync = HeaderSync::<Block>::new(0, genesis);
+		let peer = peer(&blocks[9].0);
+		let id = peer.peer_id;
+		sync.next_request(&id, &peer).unwrap();
+		sync.on_headers(&id, Some(1), headers(&blocks)).unwrap();
+
+		sync.on_block_queued(4, blocks[3].0.hash());
+		assert_eq!(sync.num_verified(), 6);
+		assert_eq!(sync.tip_number(), 10);
+
+		sync.on_block_queued(5, H256::random());
+		assert_eq!(sync.num_verified(), 0);
+		assert_eq!(sync.tip_number(), 5);
+	}
+}
@@ -22,7 +22,8 @@ use crate::{
 	schema::v1::{StateEntry, StateRequest, StateResponse},
 };
 use codec::{Decode, Encode};
-use log::debug;
+use log::{debug, warn};
+use prost::Message;
 use sc_client_api::CompactProof;
 use smallvec::SmallVec;
 use sp_core::storage::well_known_keys;
@@ -31,6 +32,27 @@ use std::{collections::HashMap, sync::Arc};
 
 /// State sync support.
 
+/// Aux store key of the saved state sync progress.
+const PROGRESS_KEY: &[u8] = b"state_sync_progress";
+
+/// Aux store key prefix of the saved state responses.
+const CHUNK_KEY_PREFIX: &[u8] = b"state_sync_chunk";
+
+/// Key values and child trie storage keys, by trie root. The top trie has an empty root.
+type State = HashMap<Vec<u8>, (Vec<(Vec<u8>, Vec<u8>)>, Vec<Vec<u8>>)>;
+
+/// State sync progress, saved after each verified response so that the download can resume
+/// after a restart. The responses themselves, i.e. the trie nodes of the range proofs, are saved
+/// in chunks, one per response.
+#[derive(Encode, Decode)]
+struct Progress<H> {
+	target_header: H,
+	last_key: Vec<Vec<u8>>,
+	skip_proof: bool,
+	imported_bytes: u64,
+	chunks: u32,
+}
+
 /// State sync state machine. Accumulates partial state data until it
 /// is ready to be imported.
 pub struct StateSync<B: BlockT> {
@@ -38,11 +60,14 @@ pub struct StateSync<B: BlockT> {
 	target_header: B::Header,
 	target_root: B::Hash,
 	last_key: SmallVec<[Vec<u8>; 2]>,
-	state: HashMap<Vec<u8>, (Vec<(Vec<u8>, Vec<u8>)>, Vec<Vec<u8>>)>,
+	state: State,
 	complete: bool,
 	client: Arc<dyn Client<B>>,
 	imported_bytes: u64,
 	skip_proof: bool,
+	/// Number of responses saved to the database, if the progress is saved. The state is then
+	/// only assembled from the saved responses once complete, instead of being kept in memory.
+	saved_chunks: Option<u32>,
 }
 
 /// Import state chunk result.
@@ -68,11 +93,131 @@ impl<B: BlockT> StateSync<B> {
 			complete: false,
 			imported_bytes: 0,
 			skip_proof,
+			saved_chunks: None,
+		}
+	}
+
+	/// Create a new instance that saves its progress to the database, discarding any progress
+	/// saved previously.
+	pub fn new_resumable(client: Arc<dyn Client<B>>, target: B::Header, skip_proof: bool) -> Self {
+		clear_progress::<B>(&*client);
+		let mut sync = Self::new(client, target, skip_proof);
+		sync.saved_chunks = Some(0);
+		sync
+	}
+
+	/// Resume the state sync from the progress saved to the database, if any.
+	///
+	/// Progress saved with a different `skip_proof` setting is discarded. The saved responses are
+	/// only read back, and verified again, once the state is complete.
+	pub fn resume(client: Arc<dyn Client<B>>, skip_proof: bool) -> Option<Self> {
+		let progress = match load_progress::<B>(&*client) {
+			Ok(Some(progress)) if progress.skip_proof == skip_proof => progress,
+			Ok(Some(_)) => {
+				debug!(target: "sync", "Discarding state sync progress saved with other settings");
+				clear_progress::<B>(&*client);
+				return None
+			},
+			Ok(None) => return None,
+			Err(e) => {
+				warn!(target: "sync", "Discarding saved state sync progress: {}", e);
+				clear_progress::<B>(&*client);
+				return None
+			},
+		};
+		let mut sync = Self::new(client, progress.target_header, skip_proof);
+		sync.last_key = progress.last_key.into_iter().collect();
+		sync.imported_bytes = progress.imported_bytes;
+		sync.saved_chunks = Some(progress.chunks);
+		Some(sync)
+	}
+
+	/// Delete the progress saved to the database, once the state is imported or the target is
+	/// abandoned.
+	pub fn clear_saved_progress(&self) {
+		if self.saved_chunks.is_some() {
+			clear_progress::<B>(&*self.client);
 		}
 	}
 
+	/// Save a verified response and the new cursor.
+	fn save_progress(&mut self, index: u32, response: &[u8]) -> Result<(), String> {
+		let progress = Progress {
+			target_header: self.target_header.clone(),
+			last_key: self.last_key.to_vec(),
+			skip_proof: self.skip_proof,
+			imported_bytes: self.imported_bytes,
+			chunks: index + 1,
+		}
+		.encode();
+		let chunk_key = chunk_key(index);
+		let insert = [(&chunk_key[..], response), (PROGRESS_KEY, &progress[..])];
+		self.client
+			.insert_aux_data(&insert, &[])
+			.map_err(|e| format!("Error saving chunk {}: {:?}", index, e))?;
+		self.saved_chunks = Some(index + 1);
+		Ok(())
+	}
+
+	/// Assemble the state from the saved responses, followed by the last `response`, verifying
+	/// them again.
+	fn assemble_saved_state(
+		&self,
+		chunks: u32,
+		response: &[u8],
+	) -> Result<ImportResult<B>, String> {
+		let mut sync = Self::new(self.client.clone(), self.target_header.clone(), self.skip_proof);
+		for index in 0..chunks {
+			if !matches!(
+				sync.import_response(load_chunk(&*self.client, index)?),
+				ImportResult::Continue
+			) {
+				return Err(format!("Chunk {} is not a valid partial state", index))
+			}
+		}
+		let response = StateResponse::decode(response)
+			.map_err(|e| format!("Error decoding last response: {:?}", e))?;
+		match sync.import_response(response) {
+			result @ ImportResult::Import(..) => Ok(result),
+			_ => Err("Saved state is not complete".into()),
+		}
+	}
+
+	/// Discard the saved progress and download the state again from the start, keeping it in
+	/// memory.
+	fn restart(&mut self) {
+		clear_progress::<B>(&*self.client);
+		self.last_key.clear();
+		self.state.clear();
+		self.complete = false;
+		self.imported_bytes = 0;
+		self.saved_chunks = None;
+	}
+
 	///  Validate and import a state reponse.
 	pub fn import(&mut self, response: StateResponse) -> ImportResult<B> {
+		let index = match self.saved_chunks {
+			Some(index) => index,
+			None => return self.import_response(response),
+		};
+		// The verified responses are kept in the database rather than in memory.
+		let encoded = response.encode_to_vec();
+		let result = match self.import_response(response) {
+			ImportResult::Continue => {
+				self.state.clear();
+				self.save_progress(index, &encoded).map(|()| ImportResult::Continue)
+			},
+			ImportResult::Import(..) => self.assemble_saved_state(index, &encoded),
+			ImportResult::BadResponse => return ImportResult::BadResponse,
+		};
+		result.unwrap_or_else(|e| {
+			warn!(target: "sync", "Restarting state sync, saved progress is unusable: {}", e);
+			self.restart();
+			ImportResult::Continue
+		})
+	}
+
+	fn import_response(&mut self, response: StateResponse) -> ImportResult<B> {
 		if response.entries.is_empty() && response.proof.is_empty() {
 			debug!(target: "sync", "Bad state response");
 			return ImportResult::BadResponse
@@ -135,7 +280,7 @@ impl<B: BlockT> StateSync<B> {
 				} else {
 					values.key_values
 				};
-				let mut entry = self.state.entry(values.state_root).or_default();
+				let entry = self.state.entry(values.state_root).or_default();
 				if entry.0.len() > 0 && entry.1.len() > 1 {
 					// Already imported child_trie with same root.
 					// Warning this will not work with parallel download.
@@ -250,3 +395,131 @@ impl<B: BlockT> StateSync<B> {
 		StateDownloadProgress { percentage: percent_done, size: self.imported_bytes }
 	}
 }
+
+fn chunk_key(index: u32) -> Vec<u8> {
+	(CHUNK_KEY_PREFIX, index).encode()
+}
+
+fn load_progress<B: BlockT>(client: &dyn Client<B>) -> Result<Option<Progress<B::Header>>, String> {
+	match client.get_aux_data(PROGRESS_KEY) {
+		Ok(Some(encoded)) => Progress::decode(&mut encoded.as_slice())
+			.map(Some)
+			.map_err(|e| format!("Error decoding progress: {:?}", e)),
+		Ok(None) => Ok(None),
+		Err(e) => Err(format!("Error reading progress: {:?}", e)),
+	}
+}
+
+fn load_chunk<B: BlockT>(client: &dyn Client<B>, index: u32) -> Result<StateResponse, String> {
+	match client.get_aux_data(&chunk_key(index)) {
+		Ok(Some(encoded)) => StateResponse::decode(encoded.as_slice())
+			.map_err(|e| format!("Error decoding chunk {}: {:?}", index, e)),
+		Ok(None) => Err(format!("Missing chunk {}", index)),
+		Err(e) => Err(format!("Error reading chunk {}: {:?}", index, e)),
+	}
+}
+
+/// Delete the saved state sync progress, if any.
+fn clear_progress<B: BlockT>(client: &dyn Client<B>) {
+	let chunks = match load_progress::<B>(client) {
+		Ok(Some(progress)) => progress.chunks,
+		Ok(None) => return,
+		// Chunks that can't be found are left behind, but are never read without progress.
+		Err(_) => 0,
+	};
+	let keys = (0..chunks).map(chunk_key).collect::<Vec<_>>();
+	let mut delete = keys.iter().map(|k| &k[..]).collect::<Vec<_>>();
+	delete.push(PROGRESS_KEY);
+	if let Err(e) = client.insert_aux_data(&[], &delete) {
+		warn!(target: "sync", "Failed to clear state sync progress: {:?}", e);
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::schema::v1::KeyValueStateEntry;
+	use sp_runtime::generic::BlockId;
+	use substrate_test_runtime_client::{
+		runtime::Block, DefaultTestClientBuilderExt, TestClientBuilder, TestClientBuilderExt,
+	};
+
+	fn response(entries: Vec<(u8, u8)>, complete: bool) -> StateResponse {
+		StateResponse {
+			entries: vec![KeyValueStateEntry {
+				state_root: Vec::new(),
+				entries: entries
+					.into_iter()
+					.map(|(key, value)| StateEntry { key: vec![key], value: vec![value] })
+					.collect(),
+				complete,
+			}],
+			proof: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn resumes_from_saved_progress() {
+		let client: Arc<dyn Client<Block>> = Arc::new(TestClientBuilder::new().build());
+		let header = client.header(BlockId::Number(0)).unwrap().unwrap();
+
+		let mut sync = StateSync::new_resumable(client.clone(), header.clone(), true);
+		assert!(matches!(
+			sync.import(response(vec![(1, 1), (2, 2)], false)),
+			ImportResult::Continue
+		));
+		assert!(matches!(sync.import(response(vec![(3, 3)], false)), ImportResult::Continue));
+		drop(sync);
+
+		let mut sync = StateSync::<Block>::resume(client.clone(), true).unwrap();
+		assert_eq!(sync.target(), header.hash());
+		assert_eq!(sync.next_request().start, vec![vec![3]]);
+		assert_eq!(sync.progress().size, 3);
+		match sync.import(response(vec![(4, 4)], true)) {
+			ImportResult::Import(hash, _, imported) => {
+				assert_eq!(hash, header.hash());
+				assert_eq!(
+					imported.state.0[0].key_values,
+					(1..=4).map(|i| (vec![i], vec![i])).collect::<Vec<_>>(),
+				);
+			},
+			_ => panic!("Expected the state to be complete"),
+		}
+		sync.clear_saved_progress();
+		assert!(StateSync::<Block>::resume(client.clone(), true).is_none());
+	}
+
+	#[test]
+	fn discards_progress_saved_with_other_settings() {
+		let client: Arc<dyn Client<Block>> = Arc::new(TestClientBuilder::new().build());
+		let header = client.header(BlockId::Number(0)).unwrap().unwrap();
+
+		let mut sync = StateSync::new_resumable(client.clone(), header.clone(), true);
+		assert!(matches!(sync.import(response(vec![(1, 1)], false)), ImportResult::Continue));
+		assert!(StateSync::<Block>::resume(client.clone(), false).is_none());
+		assert!(StateSync::<Block>::resume(client.clone(), true).is_none());
+
+		// Progress of a state sync that is not resumable is not saved.
+		let mut sync = StateSync::new(client.clone(), header, true);
+		assert!(matches!(sync.import(response(vec![(1, 1)], false)), ImportResult::Continue));
+		assert!(StateSync::<Block>::resume(client, true).is_none());
+	}
+
+	#[test]
+	fn restarts_when_saved_progress_is_unusable() {
+		let client: Arc<dyn Client<Block>> = Arc::new(TestClientBuilder::new().build());
+		let header = client.header(BlockId::Number(0)).unwrap().unwrap();
+
+		let mut sync = StateSync::new_resumable(client.clone(), header, true);
+		assert!(matches!(sync.import(response(vec![(1, 1)], false)), ImportResult::Continue));
+		assert!(matches!(sync.import(response(vec![(2, 2)], false)), ImportResult::Continue));
+		client.insert_aux_data(&[], &[&chunk_key(0)]).unwrap();
+
+		// The state can't be assembled without the first response, so it is downloaded again.
+		assert!(matches!(sync.import(response(vec![(3, 3)], true)), ImportResult::Continue));
+		assert!(!sync.is_complete());
+		assert!(sync.next_request().start.is_empty());
+		assert_eq!(sync.progress().size, 0);
+		assert!(StateSync::<Block>::resume(client, true).is_none());
+	}
+}
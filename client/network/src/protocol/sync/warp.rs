@@ -20,19 +20,19 @@
 pub use super::state::ImportResult;
 use super::state::StateSync;
 pub use crate::warp_request_handler::{
-	EncodedProof, Request as WarpProofRequest, VerificationResult, WarpSyncProvider,
+	EncodedAuthoritySet, EncodedProof, Request as WarpProofRequest, VerificationResult,
+	WarpSyncProvider,
 };
 use crate::{
 	chain::Client,
 	schema::v1::{StateRequest, StateResponse},
 	WarpSyncPhase, WarpSyncProgress,
 };
-use sp_finality_grandpa::{AuthorityList, SetId};
-use sp_runtime::traits::{Block as BlockT, NumberFor, Zero};
+use sp_runtime::traits::{Block as BlockT, Header, NumberFor, Zero};
 use std::sync::Arc;
 
 enum Phase<B: BlockT> {
-	WarpProof { set_id: SetId, authorities: AuthorityList, last_hash: B::Hash },
+	WarpProof { authorities: EncodedAuthoritySet, last_hash: B::Hash },
 	State(StateSync<B>),
 }
 
@@ -59,11 +59,8 @@ impl<B: BlockT> WarpSync<B> {
 		warp_sync_provider: Arc<dyn WarpSyncProvider<B>>,
 	) -> Self {
 		let last_hash = client.hash(Zero::zero()).unwrap().expect("Genesis header always exists");
-		let phase = Phase::WarpProof {
-			set_id: 0,
-			authorities: warp_sync_provider.current_authorities(),
-			last_hash,
-		};
+		let phase =
+			Phase::WarpProof { authorities: warp_sync_provider.current_authorities(), last_hash };
 		Self { client, warp_sync_provider, phase, total_proof_bytes: 0 }
 	}
 
@@ -85,22 +82,21 @@ impl<B: BlockT> WarpSync<B> {
 				log::debug!(target: "sync", "Unexpected warp proof response");
 				WarpProofImportResult::BadResponse
 			},
-			Phase::WarpProof { set_id, authorities, last_hash } => {
-				match self.warp_sync_provider.verify(&response, *set_id, authorities.clone()) {
+			Phase::WarpProof { authorities, last_hash } => {
+				match self.warp_sync_provider.verify(&response, authorities.clone()) {
 					Err(e) => {
 						log::debug!(target: "sync", "Bad warp proof response: {:?}", e);
 						return WarpProofImportResult::BadResponse
 					},
-					Ok(VerificationResult::Partial(new_set_id, new_authorities, new_last_hash)) => {
-						log::debug!(target: "sync", "Verified partial proof, set_id={:?}", new_set_id);
-						*set_id = new_set_id;
+					Ok(VerificationResult::Partial(new_authorities, new_last_hash)) => {
+						log::debug!(target: "sync", "Verified partial proof, last_hash={:?}", new_last_hash);
 						*authorities = new_authorities;
 						*last_hash = new_last_hash.clone();
 						self.total_proof_bytes += response.0.len() as u64;
 						WarpProofImportResult::Success
 					},
-					Ok(VerificationResult::Complete(new_set_id, _, header)) => {
-						log::debug!(target: "sync", "Verified complete proof, set_id={:?}", new_set_id);
+					Ok(VerificationResult::Complete(_, header)) => {
+						log::debug!(target: "sync", "Verified complete proof, target={:?}", header.hash());
 						self.total_proof_bytes += response.0.len() as u64;
 						let state_sync = StateSync::new(self.client.clone(), header, false);
 						self.phase = Phase::State(state_sync);
@@ -0,0 +1,244 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Rate limiting of the network traffic.
+//!
+//! A [`RateLimiter`] is a token bucket shared by everything it limits, e.g. all the connections
+//! or all the substreams of a protocol. Bytes are accounted once they have been transferred, so
+//! the bucket can go into debt. Transfers then wait until the debt is paid back, which keeps the
+//! average rate at the limit while allowing bursts of up to one second worth of traffic.
+
+use futures::{
+	io::{AsyncRead, AsyncWrite},
+	prelude::*,
+	ready,
+};
+use futures_timer::Delay;
+use parking_lot::Mutex;
+use std::{
+	fmt, io,
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+	time::{Duration, Instant},
+};
+
+/// Token bucket limiting the number of bytes transferred per second.
+#[derive(Clone)]
+pub struct RateLimiter {
+	bucket: Arc<Mutex<Bucket>>,
+}
+
+struct Bucket {
+	/// Maximum number of bytes per second, which is also the size of the bucket.
+	bytes_per_sec: u64,
+	/// Number of bytes that can be transferred right away. Negative when in debt.
+	available: i128,
+	/// When `available` was last updated.
+	last_refill: Instant,
+}
+
+impl Bucket {
+	fn refill(&mut self, now: Instant) {
+		let elapsed = now.saturating_duration_since(self.last_refill);
+		let refill = elapsed.as_nanos() * self.bytes_per_sec as u128 / 1_000_000_000;
+		// Don't move `last_refill` forward if the elapsed time isn't worth a single byte, so that
+		// frequent calls still refill the bucket.
+		if refill > 0 {
+			self.available = (self.available + refill as i128).min(self.bytes_per_sec as i128);
+			self.last_refill = now;
+		}
+	}
+}
+
+impl RateLimiter {
+	/// Create a limiter allowing `bytes_per_sec` bytes per second. A limit of zero is treated as
+	/// one byte per second.
+	pub fn new(bytes_per_sec: u64) -> Self {
+		let bytes_per_sec = bytes_per_sec.max(1);
+		RateLimiter {
+			bucket: Arc::new(Mutex::new(Bucket {
+				bytes_per_sec,
+				available: bytes_per_sec as i128,
+				last_refill: Instant::now(),
+			})),
+		}
+	}
+
+	/// Account for `bytes` transferred.
+	pub fn consume(&self, bytes: usize) {
+		let mut bucket = self.bucket.lock();
+		bucket.refill(Instant::now());
+		bucket.available -= bytes as i128;
+	}
+
+	/// Time to wait until the limiter is out of debt, or `None` if more bytes can be transferred
+	/// right away.
+	pub fn delay(&self) -> Option<Duration> {
+		let mut bucket = self.bucket.lock();
+		bucket.refill(Instant::now());
+		if bucket.available >= 0 {
+			return None
+		}
+		let nanos = (-bucket.available) as u128 * 1_000_000_000 / bucket.bytes_per_sec as u128;
+		Some(Duration::from_nanos(nanos.min(u64::MAX as u128) as u64 + 1))
+	}
+}
+
+impl fmt::Debug for RateLimiter {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("RateLimiter")
+			.field("bytes_per_sec", &self.bucket.lock().bytes_per_sec)
+			.finish()
+	}
+}
+
+/// Waits on a [`RateLimiter`] on behalf of a single stream.
+pub struct Throttle {
+	limiter: RateLimiter,
+	delay: Option<Delay>,
+}
+
+impl Throttle {
+	/// Create a throttle waiting on `limiter`.
+	pub fn new(limiter: RateLimiter) -> Self {
+		Throttle { limiter, delay: None }
+	}
+
+	/// Returns `Ready` once more bytes can be transferred. Otherwise, the task is woken up when
+	/// the limiter is expected to be out of debt.
+	pub fn poll_ready(&mut self, cx: &mut Context) -> Poll<()> {
+		loop {
+			let wait = match self.limiter.delay() {
+				Some(wait) => wait,
+				None => {
+					self.delay = None;
+					return Poll::Ready(())
+				},
+			};
+			let delay = self.delay.get_or_insert_with(|| Delay::new(wait));
+			ready!(delay.poll_unpin(cx));
+			// Other streams sharing the limiter might have used the bytes in the meantime.
+			self.delay = None;
+		}
+	}
+
+	/// Account for `bytes` transferred.
+	pub fn consume(&self, bytes: usize) {
+		self.limiter.consume(bytes)
+	}
+}
+
+/// Connection whose reads and writes are limited by rate limiters.
+#[pin_project::pin_project]
+pub struct RateLimited<C> {
+	#[pin]
+	inner: C,
+	read: Option<Throttle>,
+	write: Option<Throttle>,
+}
+
+impl<C> RateLimited<C> {
+	/// Wrap `inner`, limiting reads with `read` and writes with `write`.
+	pub fn new(inner: C, read: Option<RateLimiter>, write: Option<RateLimiter>) -> Self {
+		RateLimited { inner, read: read.map(Throttle::new), write: write.map(Throttle::new) }
+	}
+}
+
+impl<C: AsyncRead> AsyncRead for RateLimited<C> {
+	fn poll_read(
+		self: Pin<&mut Self>,
+		cx: &mut Context,
+		buf: &mut [u8],
+	) -> Poll<io::Result<usize>> {
+		let this = self.project();
+		if let Some(throttle) = this.read.as_mut() {
+			ready!(throttle.poll_ready(cx));
+		}
+		let read = ready!(this.inner.poll_read(cx, buf))?;
+		if let Some(throttle) = this.read.as_ref() {
+			throttle.consume(read);
+		}
+		Poll::Ready(Ok(read))
+	}
+}
+
+impl<C: AsyncWrite> AsyncWrite for RateLimited<C> {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+		let this = self.project();
+		if let Some(throttle) = this.write.as_mut() {
+			ready!(throttle.poll_ready(cx));
+		}
+		let written = ready!(this.inner.poll_write(cx, buf))?;
+		if let Some(throttle) = this.write.as_ref() {
+			throttle.consume(written);
+		}
+		Poll::Ready(Ok(written))
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+		self.project().inner.poll_flush(cx)
+	}
+
+	fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+		self.project().inner.poll_close(cx)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{executor::block_on, io::Cursor};
+
+	#[test]
+	fn bursts_are_limited_to_one_second() {
+		let limiter = RateLimiter::new(1000);
+		assert!(limiter.delay().is_none());
+		limiter.consume(1000);
+		assert!(limiter.delay().is_none());
+
+		limiter.consume(500);
+		let delay = limiter.delay().unwrap();
+		assert!(delay > Duration::from_millis(400) && delay <= Duration::from_millis(501));
+	}
+
+	#[test]
+	fn limiter_refills_over_time() {
+		let limiter = RateLimiter::new(1_000_000);
+		limiter.consume(1_010_000);
+		assert!(limiter.delay().is_some());
+		std::thread::sleep(Duration::from_millis(20));
+		assert!(limiter.delay().is_none());
+	}
+
+	#[test]
+	fn connection_reads_wait_for_the_limiter() {
+		let limiter = RateLimiter::new(10_000);
+		let mut conn = RateLimited::new(Cursor::new(vec![0u8; 15_000]), Some(limiter), None);
+		let started = Instant::now();
+		let mut buf = [0; 1000];
+		let mut total = 0;
+		while let Ok(read @ 1..=1000) = block_on(conn.read(&mut buf)) {
+			total += read;
+		}
+		assert_eq!(total, 15_000);
+		// The first 10_000 bytes are a burst, the last 1_000 are read without waiting once the
+		// debt of the previous ones is paid back.
+		assert!(started.elapsed() >= Duration::from_millis(350));
+	}
+}
@@ -0,0 +1,243 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Recording of the messages exchanged with the network, for debugging purposes.
+//!
+//! When [`NetworkConfiguration::message_recording`](crate::config::NetworkConfiguration) is set,
+//! every notification, request and response sent or received by the node is appended to the
+//! given file as a SCALE-encoded [`Record`]. A capture can be read back with a [`RecordReader`],
+//! for example to replay it against a local node.
+//!
+//! The file is written by a dedicated thread, so that the networking code never waits for the
+//! disk. The messages are handed to it through an unbounded channel.
+
+use codec::{Decode, Encode};
+use futures::{executor::block_on, StreamExt};
+use libp2p::PeerId;
+use sc_utils::mpsc::{tracing_unbounded, TracingUnboundedReceiver, TracingUnboundedSender};
+use std::{
+	fmt,
+	fs::File,
+	io::{self, BufRead, BufReader, BufWriter, Write},
+	path::Path,
+	sync::Arc,
+	thread::{self, JoinHandle},
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Whether a message was sent or received by the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum Direction {
+	/// Received from the remote.
+	Inbound,
+	/// Sent to the remote.
+	Outbound,
+}
+
+/// Kind of a recorded message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum MessageKind {
+	/// Notification sent on a notifications substream.
+	Notification,
+	/// Request of a request-response protocol.
+	Request,
+	/// Response of a request-response protocol.
+	Response,
+}
+
+/// A message exchanged with a peer.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct Record {
+	/// Milliseconds since the UNIX epoch at which the message was sent or received.
+	pub timestamp: u64,
+	/// Bytes of the [`PeerId`] of the remote.
+	pub peer: Vec<u8>,
+	/// Name of the protocol the message was exchanged on.
+	pub protocol: String,
+	/// Whether the message was sent or received.
+	pub direction: Direction,
+	/// Kind of the message.
+	pub kind: MessageKind,
+	/// Content of the message.
+	pub payload: Vec<u8>,
+}
+
+impl Record {
+	/// Returns the [`PeerId`] of the remote, if valid.
+	pub fn peer_id(&self) -> Option<PeerId> {
+		PeerId::from_bytes(&self.peer).ok()
+	}
+}
+
+/// Appends the messages exchanged with the network to a file.
+///
+/// Cloning a `Recorder` gives another handle to the same file. The file is flushed whenever the
+/// writer thread has no message left to write, and is complete once all the handles are dropped.
+#[derive(Clone)]
+pub struct Recorder {
+	writer: Arc<Writer>,
+}
+
+/// Sends the records to the writer thread, and waits for it to finish when dropped.
+struct Writer {
+	to_thread: TracingUnboundedSender<Record>,
+	thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Writer {
+	fn drop(&mut self) {
+		self.to_thread.close_channel();
+		if let Some(thread) = self.thread.take() {
+			let _ = thread.join();
+		}
+	}
+}
+
+impl Recorder {
+	/// Creates the file at `path`, truncating it if it already exists, and starts the thread
+	/// writing to it.
+	pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+		let file = BufWriter::new(File::create(path)?);
+		let (to_thread, from_recorder) = tracing_unbounded("mpsc_network_recorder");
+		let thread = thread::Builder::new()
+			.name("network-recorder".into())
+			.spawn(move || write_records(file, from_recorder))?;
+		Ok(Self { writer: Arc::new(Writer { to_thread, thread: Some(thread) }) })
+	}
+
+	/// Queues a message to be appended to the file.
+	///
+	/// Failing to write only logs an error, as the recording must not disturb the node.
+	pub fn record(
+		&self,
+		peer: &PeerId,
+		protocol: &str,
+		direction: Direction,
+		kind: MessageKind,
+		payload: &[u8],
+	) {
+		let timestamp = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|elapsed| elapsed.as_millis() as u64)
+			.unwrap_or_default();
+		let record = Record {
+			timestamp,
+			peer: peer.to_bytes(),
+			protocol: protocol.to_owned(),
+			direction,
+			kind,
+			payload: payload.to_vec(),
+		};
+
+		// Only fails if the writer thread has stopped after an error, which it has logged.
+		let _ = self.writer.to_thread.unbounded_send(record);
+	}
+}
+
+/// Writes the records received from `records` to `file` until the channel is closed.
+fn write_records(file: BufWriter<File>, records: TracingUnboundedReceiver<Record>) {
+	if let Err(err) = try_write_records(file, records) {
+		log::error!(target: "sub-libp2p", "Failed to record network messages: {}", err);
+	}
+}
+
+fn try_write_records(
+	mut file: BufWriter<File>,
+	mut records: TracingUnboundedReceiver<Record>,
+) -> io::Result<()> {
+	loop {
+		let record = match records.try_next() {
+			Ok(Some(record)) => record,
+			Ok(None) => break,
+			Err(_) => {
+				// No message is waiting, flush the file before waiting for the next one.
+				file.flush()?;
+				match block_on(records.next()) {
+					Some(record) => record,
+					None => break,
+				}
+			},
+		};
+		file.write_all(&record.encode())?;
+	}
+	file.flush()
+}
+
+impl fmt::Debug for Recorder {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("Recorder").finish()
+	}
+}
+
+/// Iterates over the [`Record`]s of a file written by a [`Recorder`].
+pub struct RecordReader {
+	reader: BufReader<File>,
+}
+
+impl RecordReader {
+	/// Opens the file at `path`.
+	pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+		Ok(Self { reader: BufReader::new(File::open(path)?) })
+	}
+}
+
+impl Iterator for RecordReader {
+	type Item = Result<Record, codec::Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		match self.reader.fill_buf() {
+			Ok([]) => return None,
+			Ok(_) => {},
+			Err(err) => return Some(Err(err.into())),
+		}
+		Some(Record::decode(&mut codec::IoReader(&mut self.reader)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn records_are_read_back() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("messages");
+		let peer = PeerId::random();
+
+		let recorder = Recorder::create(&path).unwrap();
+		recorder.record(&peer, "/foo", Direction::Outbound, MessageKind::Request, b"ping");
+		recorder
+			.clone()
+			.record(&peer, "/foo", Direction::Inbound, MessageKind::Response, b"pong");
+		drop(recorder);
+
+		let records = RecordReader::open(&path).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+		assert_eq!(records.len(), 2);
+		assert_eq!(records[0].peer_id(), Some(peer));
+		assert_eq!(records[0].protocol, "/foo");
+		assert_eq!(
+			(records[0].direction, records[0].kind, &records[0].payload[..]),
+			(Direction::Outbound, MessageKind::Request, &b"ping"[..]),
+		);
+		assert_eq!(
+			(records[1].direction, records[1].kind, &records[1].payload[..]),
+			(Direction::Inbound, MessageKind::Response, &b"pong"[..]),
+		);
+		assert!(records[1].timestamp >= records[0].timestamp);
+	}
+}
@@ -33,12 +33,20 @@
 //!
 //! - If provided, a ["requests processing"](ProtocolConfig::inbound_queue) channel
 //! is used to handle incoming requests.
+//!
+//! - Incoming requests exceeding the [limits](ProtocolConfig::inbound_limits) wait to be served.
 
-use crate::ReputationChange;
+use crate::{
+	config::BandwidthLimits,
+	rate_limit::RateLimiter,
+	recorder::{Direction, MessageKind, Recorder},
+	ReputationChange,
+};
 use futures::{
 	channel::{mpsc, oneshot},
 	prelude::*,
 };
+use futures_timer::Delay;
 use libp2p::{
 	core::{
 		connection::{ConnectionId, ListenerId},
@@ -55,7 +63,7 @@ use libp2p::{
 };
 use std::{
 	borrow::Cow,
-	collections::{hash_map::Entry, HashMap},
+	collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
 	convert::TryFrom as _,
 	io, iter,
 	pin::Pin,
@@ -66,6 +74,10 @@ use std::{
 pub use libp2p::request_response::{InboundFailure, OutboundFailure, RequestId};
 use sc_peerset::{PeersetHandle, BANNED_THRESHOLD};
 
+/// Maximum number of incoming requests of a protocol held back by its limits. Further requests
+/// exceeding the limits are refused.
+const MAX_DEFERRED_REQUESTS: usize = 64;
+
 /// Configuration for a single request-response protocol.
 #[derive(Debug, Clone)]
 pub struct ProtocolConfig {
@@ -110,6 +122,45 @@ pub struct ProtocolConfig {
 	/// advertise support for this protocol, but any incoming request will lead to an error being
 	/// sent back.
 	pub inbound_queue: Option<mpsc::Sender<IncomingRequest>>,
+
+	/// Limits on the incoming requests. Requests exceeding them wait until they can be served.
+	pub inbound_limits: InboundRequestLimits,
+}
+
+/// Limits on the requests served to other peers on a request-response protocol.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InboundRequestLimits {
+	/// Bandwidth used by the requests received (download) and the responses sent (upload), for
+	/// all the peers combined. Once a limit is exceeded, new requests wait until the average
+	/// bandwidth is back under the limit.
+	pub bandwidth: BandwidthLimits,
+
+	/// Maximum number of requests served to every single peer.
+	pub per_peer_quota: Option<RequestQuota>,
+}
+
+/// Maximum number of requests a peer can make in a given period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestQuota {
+	/// Number of requests accepted in each period.
+	pub max_requests: u32,
+	/// Duration of a period. The count of a peer is reset once the period of its first request
+	/// is over.
+	pub period: Duration,
+}
+
+/// Limit of [`InboundRequestLimits`] that caused a request to be held back or refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+pub enum ThrottleReason {
+	/// The peer made too many requests.
+	#[display(fmt = "peer quota exceeded")]
+	PeerQuota,
+	/// Too many bytes of responses were sent.
+	#[display(fmt = "upload bandwidth exceeded")]
+	Upload,
+	/// Too many bytes of requests were received.
+	#[display(fmt = "download bandwidth exceeded")]
+	Download,
 }
 
 /// A single request received by a peer on a request-response protocol.
@@ -264,6 +315,139 @@ pub struct RequestResponsesBehaviour {
 	/// Pending message request, holds `MessageRequest` as a Future state to poll it
 	/// until we get a response from `Peerset`
 	message_request: Option<MessageRequest>,
+
+	/// Limiters of the incoming requests, by protocol. Protocols without limits aren't present.
+	inbound_limiters: HashMap<Cow<'static, str>, InboundLimiter>,
+
+	/// Incoming requests refused by the limiters because too many were held back already. Their
+	/// failure has already been reported and the `InboundFailure` emitted by the
+	/// [`RequestResponse`] behaviour is ignored.
+	throttled_requests: HashSet<ProtocolRequestId>,
+
+	/// Records the requests and responses sent and received, if enabled.
+	recorder: Option<Recorder>,
+}
+
+/// Enforces the [`InboundRequestLimits`] of a protocol.
+struct InboundLimiter {
+	upload: Option<RateLimiter>,
+	download: Option<RateLimiter>,
+	quota: Option<RequestQuota>,
+	/// Start of the current quota period and number of requests accepted since, by peer.
+	requests: HashMap<PeerId, (Instant, u32)>,
+	/// Requests exceeding the limits, oldest first. At most [`MAX_DEFERRED_REQUESTS`].
+	deferred: VecDeque<DeferredRequest>,
+	/// Wakes up the task once a deferred request may be served.
+	timer: Option<Delay>,
+}
+
+/// Incoming request held back by an [`InboundLimiter`].
+struct DeferredRequest {
+	peer: PeerId,
+	request_id: RequestId,
+	request: Vec<u8>,
+	channel: ResponseChannel<Result<Vec<u8>, ()>>,
+	received: Instant,
+}
+
+impl InboundLimiter {
+	/// Returns `None` if there are no limits to enforce.
+	fn new(limits: &InboundRequestLimits) -> Option<Self> {
+		if limits == &InboundRequestLimits::default() {
+			return None
+		}
+		Some(Self {
+			upload: limits.bandwidth.upload.map(RateLimiter::new),
+			download: limits.bandwidth.download.map(RateLimiter::new),
+			quota: limits.per_peer_quota,
+			requests: HashMap::new(),
+			deferred: VecDeque::new(),
+			timer: None,
+		})
+	}
+
+	/// Account for a request of `size` bytes received from `peer`, unless it has to be refused.
+	fn on_request(&mut self, peer: &PeerId, size: usize) -> Result<(), ThrottleReason> {
+		if self.upload.as_ref().and_then(|upload| upload.delay()).is_some() {
+			return Err(ThrottleReason::Upload)
+		}
+		if self.download.as_ref().and_then(|download| download.delay()).is_some() {
+			return Err(ThrottleReason::Download)
+		}
+		if let Some(quota) = self.quota {
+			let now = Instant::now();
+			if !self.requests.contains_key(peer) {
+				// Forget the peers whose period is over before tracking a new one.
+				self.requests.retain(|_, (start, _)| now.duration_since(*start) < quota.period);
+			}
+			let (start, count) = self.requests.entry(*peer).or_insert((now, 0));
+			if now.duration_since(*start) >= quota.period {
+				*start = now;
+				*count = 0;
+			}
+			if *count >= quota.max_requests {
+				return Err(ThrottleReason::PeerQuota)
+			}
+			*count += 1;
+		}
+		if let Some(download) = &self.download {
+			download.consume(size);
+		}
+		Ok(())
+	}
+
+	/// Account for a response of `size` bytes sent.
+	fn on_response(&self, size: usize) {
+		if let Some(upload) = &self.upload {
+			upload.consume(size);
+		}
+	}
+
+	/// Time to wait until a request from `peer` no longer exceeds the limits.
+	fn delay(&self, peer: &PeerId) -> Duration {
+		let bandwidth = [&self.upload, &self.download]
+			.iter()
+			.filter_map(|limiter| limiter.as_ref()?.delay())
+			.max();
+		let quota = self.quota.and_then(|quota| match self.requests.get(peer) {
+			Some((start, count)) if *count >= quota.max_requests =>
+				Some(quota.period.saturating_sub(start.elapsed())),
+			_ => None,
+		});
+		bandwidth.max(quota).unwrap_or_default()
+	}
+
+	/// Forget a deferred request that failed, e.g. because it timed out.
+	fn remove_deferred(&mut self, request_id: RequestId) {
+		self.deferred.retain(|request| request.request_id != request_id);
+	}
+
+	/// Take the oldest deferred request the limits allow serving, if any. Otherwise, the task is
+	/// woken up once one may be served.
+	fn poll_deferred(&mut self, cx: &mut Context) -> Option<DeferredRequest> {
+		loop {
+			let mut index = 0;
+			while let Some(request) = self.deferred.get(index) {
+				let (peer, size) = (request.peer, request.request.len());
+				match self.on_request(&peer, size) {
+					Ok(()) => {
+						self.timer = None;
+						return self.deferred.remove(index)
+					},
+					// Other peers may still be served.
+					Err(ThrottleReason::PeerQuota) => index += 1,
+					Err(ThrottleReason::Upload | ThrottleReason::Download) => break,
+				}
+			}
+
+			let wait = self.deferred.iter().map(|request| self.delay(&request.peer)).min()?;
+			let timer = self.timer.get_or_insert_with(|| Delay::new(wait));
+			match timer.poll_unpin(cx) {
+				Poll::Ready(()) => self.timer = None,
+				Poll::Pending => return None,
+			}
+		}
+	}
 }
 
 // This is a state of processing incoming request Message.
@@ -295,8 +479,10 @@ impl RequestResponsesBehaviour {
 	pub fn new(
 		list: impl Iterator<Item = ProtocolConfig>,
 		peerset: PeersetHandle,
+		recorder: Option<Recorder>,
 	) -> Result<Self, RegisterError> {
 		let mut protocols = HashMap::new();
+		let mut inbound_limiters = HashMap::new();
 		for protocol in list {
 			let mut cfg = RequestResponseConfig::default();
 			cfg.set_connection_keep_alive(Duration::from_secs(10));
@@ -317,6 +503,10 @@ impl RequestResponsesBehaviour {
 				cfg,
 			);
 
+			if let Some(limiter) = InboundLimiter::new(&protocol.inbound_limits) {
+				inbound_limiters.insert(protocol.name.clone(), limiter);
+			}
+
 			match protocols.entry(protocol.name) {
 				Entry::Vacant(e) => e.insert((rq_rp, protocol.inbound_queue)),
 				Entry::Occupied(e) => return Err(RegisterError::DuplicateProtocol(e.key().clone())),
@@ -331,6 +521,9 @@ impl RequestResponsesBehaviour {
 			send_feedback: Default::default(),
 			peerset,
 			message_request: None,
+			inbound_limiters,
+			throttled_requests: Default::default(),
+			recorder,
 		})
 	}
 
@@ -350,6 +543,15 @@ impl RequestResponsesBehaviour {
 	) {
 		if let Some((protocol, _)) = self.protocols.get_mut(protocol_name) {
 			if protocol.is_connected(target) || connect.should_connect() {
+				if let Some(recorder) = &self.recorder {
+					recorder.record(
+						target,
+						protocol_name,
+						Direction::Outbound,
+						MessageKind::Request,
+						&request,
+					);
+				}
 				let request_id = protocol.send_request(target, request);
 				let prev_req_id = self.pending_requests.insert(
 					(protocol_name.to_string().into(), request_id).into(),
@@ -642,6 +844,18 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 				};
 
 				if let Ok(payload) = result {
+					if let Some(limiter) = self.inbound_limiters.get(&protocol_name) {
+						limiter.on_response(payload.len());
+					}
+					if let Some(recorder) = &self.recorder {
+						recorder.record(
+							&peer,
+							&protocol_name,
+							Direction::Outbound,
+							MessageKind::Response,
+							&payload,
+						);
+					}
 					if let Some((protocol, _)) = self.protocols.get_mut(&*protocol_name) {
 						if let Err(_) = protocol.send_response(inner_channel, Ok(payload)) {
 							// Note: Failure is handled further below when receiving
@@ -669,6 +883,28 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 				}
 			}
 
+			// Serve the held back requests that the limits now allow.
+			for (protocol, limiter) in &mut self.inbound_limiters {
+				if let Some(deferred) = limiter.poll_deferred(cx) {
+					let DeferredRequest { peer, request_id, request, channel, received } = deferred;
+					self.pending_responses_arrival_time
+						.insert((protocol.clone(), request_id).into(), received);
+					let resp_builder =
+						self.protocols.get(protocol).and_then(|(_, builder)| builder.clone());
+					let get_peer_reputation = Box::pin(self.peerset.clone().peer_reputation(peer));
+					self.message_request = Some(MessageRequest {
+						peer,
+						request_id,
+						request,
+						channel,
+						protocol: protocol.to_string(),
+						resp_builder,
+						get_peer_reputation,
+					});
+					continue 'poll_all
+				}
+			}
+
 			// Poll request-responses protocols.
 			for (protocol, (behaviour, resp_builder)) in &mut self.protocols {
 				while let Poll::Ready(ev) = behaviour.poll(cx, params) {
@@ -723,6 +959,58 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 							message:
 								RequestResponseMessage::Request { request_id, request, channel, .. },
 						} => {
+							if let Some(recorder) = &self.recorder {
+								recorder.record(
+									&peer,
+									protocol,
+									Direction::Inbound,
+									MessageKind::Request,
+									&request,
+								);
+							}
+							if let Some(limiter) = self.inbound_limiters.get_mut(protocol) {
+								match limiter.on_request(&peer, request.len()) {
+									Ok(()) => {},
+									Err(reason)
+										if limiter.deferred.len() < MAX_DEFERRED_REQUESTS =>
+									{
+										log::debug!(
+											target: "sub-libp2p",
+											"Deferring request {:?} on protocol {:?} from {}: {}",
+											request_id, protocol, peer, reason,
+										);
+										limiter.deferred.push_back(DeferredRequest {
+											peer,
+											request_id,
+											request,
+											channel,
+											received: Instant::now(),
+										});
+										// Makes sure that the limiter wakes the task up.
+										continue 'poll_all
+									},
+									Err(reason) => {
+										log::debug!(
+											target: "sub-libp2p",
+											"Refusing request {:?} on protocol {:?} from {}: {}",
+											request_id, protocol, peer, reason,
+										);
+										// Dropping the channel refuses the request.
+										drop(channel);
+										self.throttled_requests
+											.insert((protocol.clone(), request_id).into());
+										let out = Event::InboundRequest {
+											peer,
+											protocol: protocol.clone(),
+											result: Err(ResponseFailure::Throttled(reason)),
+										};
+										return Poll::Ready(NetworkBehaviourAction::GenerateEvent(
+											out,
+										))
+									},
+								}
+							}
+
 							self.pending_responses_arrival_time.insert(
 								(protocol.clone(), request_id.clone()).into(),
 								Instant::now(),
@@ -756,6 +1044,15 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 							message: RequestResponseMessage::Response { request_id, response },
 							..
 						} => {
+							if let (Some(recorder), Ok(response)) = (&self.recorder, &response) {
+								recorder.record(
+									&peer,
+									protocol,
+									Direction::Inbound,
+									MessageKind::Response,
+									response,
+								);
+							}
 							let (started, delivered) = match self
 								.pending_requests
 								.remove(&(protocol.clone(), request_id).into())
@@ -835,6 +1132,15 @@ impl NetworkBehaviour for RequestResponsesBehaviour {
 						RequestResponseEvent::InboundFailure {
 							request_id, peer, error, ..
 						} => {
+							if self
+								.throttled_requests
+								.remove(&(protocol.clone(), request_id).into())
+							{
+								continue
+							}
+							if let Some(limiter) = self.inbound_limiters.get_mut(protocol) {
+								limiter.remove_deferred(request_id);
+							}
 							self.pending_responses_arrival_time
 								.remove(&(protocol.clone(), request_id).into());
 							self.send_feedback.remove(&(protocol.clone(), request_id).into());
@@ -913,6 +1219,10 @@ pub enum ResponseFailure {
 	/// Problem on the network.
 	#[display(fmt = "Problem on the network: {}", _0)]
 	Network(InboundFailure),
+	/// The request exceeded the limits of the protocol while too many requests were held back
+	/// already, and was refused.
+	#[display(fmt = "Request throttled: {}", _0)]
+	Throttled(#[error(ignore)] ThrottleReason),
 }
 
 /// Implements the libp2p [`RequestResponseCodec`] trait. Defines how streams of bytes are turned
@@ -1088,7 +1398,7 @@ mod tests {
 
 		let (peerset, handle) = Peerset::from_config(config);
 
-		let behaviour = RequestResponsesBehaviour::new(list, handle).unwrap();
+		let behaviour = RequestResponsesBehaviour::new(list, handle, None).unwrap();
 
 		let mut swarm = Swarm::new(transport, behaviour, keypair.public().to_peer_id());
 		let listen_addr: Multiaddr = format!("/memory/{}", rand::random::<u64>()).parse().unwrap();
@@ -1101,6 +1411,27 @@ mod tests {
 		let _: Vec<_> = peerset.collect().await;
 	}
 
+	#[test]
+	fn inbound_limiter_enforces_limits() {
+		let limits = InboundRequestLimits {
+			bandwidth: BandwidthLimits { upload: Some(1000), download: None },
+			per_peer_quota: Some(RequestQuota { max_requests: 2, period: Duration::from_secs(60) }),
+		};
+		let mut limiter = InboundLimiter::new(&limits).unwrap();
+		let (peer_a, peer_b) = (PeerId::random(), PeerId::random());
+
+		assert_eq!(limiter.on_request(&peer_a, 10), Ok(()));
+		assert_eq!(limiter.on_request(&peer_a, 10), Ok(()));
+		assert_eq!(limiter.on_request(&peer_a, 10), Err(ThrottleReason::PeerQuota));
+		assert_eq!(limiter.on_request(&peer_b, 10), Ok(()));
+
+		// Responses are accounted once sent, new requests are held back until the debt is paid.
+		limiter.on_response(2000);
+		assert_eq!(limiter.on_request(&peer_b, 10), Err(ThrottleReason::Upload));
+
+		assert!(InboundLimiter::new(&Default::default()).is_none());
+	}
+
 	#[test]
 	fn basic_request_response_works() {
 		let protocol_name = "/test/req-resp/1";
@@ -1136,6 +1467,7 @@ mod tests {
 					max_response_size: 1024 * 1024,
 					request_timeout: Duration::from_secs(30),
 					inbound_queue: Some(tx),
+					inbound_limits: Default::default(),
 				};
 
 				build_swarm(iter::once(protocol_config))
@@ -1203,6 +1535,117 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn requests_exceeding_limits_are_served_later() {
+		let protocol_name = "/test/req-resp/1";
+		let mut pool = LocalPool::new();
+
+		// Build swarms whose behaviour is `RequestResponsesBehaviour`.
+		let mut swarms = (0..2)
+			.map(|_| {
+				let (tx, mut rx) = mpsc::channel::<IncomingRequest>(64);
+
+				pool.spawner()
+					.spawn_obj(
+						async move {
+							while let Some(rq) = rx.next().await {
+								let _ = rq.pending_response.send(super::OutgoingResponse {
+									result: Ok(b"this is a response".to_vec()),
+									reputation_changes: Vec::new(),
+									sent_feedback: None,
+								});
+							}
+						}
+						.boxed()
+						.into(),
+					)
+					.unwrap();
+
+				let protocol_config = ProtocolConfig {
+					name: From::from(protocol_name),
+					max_request_size: 1024,
+					max_response_size: 1024 * 1024,
+					request_timeout: Duration::from_secs(30),
+					inbound_queue: Some(tx),
+					inbound_limits: InboundRequestLimits {
+						bandwidth: Default::default(),
+						per_peer_quota: Some(RequestQuota {
+							max_requests: 1,
+							period: Duration::from_millis(500),
+						}),
+					},
+				};
+
+				build_swarm(iter::once(protocol_config))
+			})
+			.collect::<Vec<_>>();
+
+		// Ask `swarm[0]` to dial `swarm[1]`. There isn't any discovery mechanism in place in
+		// this test, so they wouldn't connect to each other.
+		{
+			let dial_addr = swarms[1].1.clone();
+			Swarm::dial_addr(&mut swarms[0].0, dial_addr).unwrap();
+		}
+
+		let (mut swarm, _, peerset) = swarms.remove(0);
+		// Process every peerset event in the background.
+		pool.spawner().spawn_obj(loop_peerset(peerset).boxed().into()).unwrap();
+		// Running `swarm[0]` in the background. The requests exceeding the quota are not refused.
+		pool.spawner()
+			.spawn_obj({
+				async move {
+					loop {
+						if let SwarmEvent::Behaviour(Event::InboundRequest { result, .. }) =
+							swarm.select_next_some().await
+						{
+							result.unwrap();
+						}
+					}
+				}
+				.boxed()
+				.into()
+			})
+			.unwrap();
+
+		// Remove and run the remaining swarm.
+		let (mut swarm, _, peerset) = swarms.remove(0);
+		// Process every peerset event in the background.
+		pool.spawner().spawn_obj(loop_peerset(peerset).boxed().into()).unwrap();
+		pool.run_until(async move {
+			let mut response_receivers = Vec::new();
+			let mut num_finished = 0;
+
+			loop {
+				match swarm.select_next_some().await {
+					SwarmEvent::ConnectionEstablished { peer_id, .. } =>
+						for _ in 0..3 {
+							let (sender, receiver) = oneshot::channel();
+							swarm.behaviour_mut().send_request(
+								&peer_id,
+								protocol_name,
+								b"this is a request".to_vec(),
+								sender,
+								IfDisconnected::ImmediateError,
+							);
+							response_receivers.push(receiver);
+						},
+					SwarmEvent::Behaviour(Event::RequestFinished { result, .. }) => {
+						result.unwrap();
+						num_finished += 1;
+						if num_finished == 3 {
+							break
+						}
+					},
+					_ => {},
+				}
+			}
+
+			for receiver in response_receivers {
+				assert_eq!(receiver.await.unwrap().unwrap(), b"this is a response");
+			}
+		});
+	}
+
 	#[test]
 	fn max_response_size_exceeded() {
 		let protocol_name = "/test/req-resp/1";
@@ -1236,6 +1679,7 @@ mod tests {
 					max_response_size: 8, // <-- important for the test
 					request_timeout: Duration::from_secs(30),
 					inbound_queue: Some(tx),
+					inbound_limits: Default::default(),
 				};
 
 				build_swarm(iter::once(protocol_config))
@@ -1332,6 +1776,7 @@ mod tests {
 					max_response_size: 1024 * 1024,
 					request_timeout: Duration::from_secs(30),
 					inbound_queue: None,
+					inbound_limits: Default::default(),
 				},
 				ProtocolConfig {
 					name: From::from(protocol_name_2),
@@ -1339,6 +1784,7 @@ mod tests {
 					max_response_size: 1024 * 1024,
 					request_timeout: Duration::from_secs(30),
 					inbound_queue: None,
+					inbound_limits: Default::default(),
 				},
 			];
 
@@ -1356,6 +1802,7 @@ mod tests {
 					max_response_size: 1024 * 1024,
 					request_timeout: Duration::from_secs(30),
 					inbound_queue: Some(tx_1),
+					inbound_limits: Default::default(),
 				},
 				ProtocolConfig {
 					name: From::from(protocol_name_2),
@@ -1363,6 +1810,7 @@ mod tests {
 					max_response_size: 1024 * 1024,
 					request_timeout: Duration::from_secs(30),
 					inbound_queue: Some(tx_2),
+					inbound_limits: Default::default(),
 				},
 			];
 
@@ -29,13 +29,14 @@
 
 use crate::{
 	behaviour::{self, Behaviour, BehaviourOut},
-	bitswap::Bitswap,
+	bitswap::{Bitswap, BitswapError, Cid},
 	config::{parse_str_addr, Params, TransportConfig},
 	discovery::DiscoveryConfig,
 	error::Error,
 	network_state::{
 		NetworkState, NotConnectedPeer as NetworkStateNotConnectedPeer, Peer as NetworkStatePeer,
 	},
+	peer_store::{self, PeerStore},
 	protocol::{
 		self,
 		event::Event,
@@ -43,11 +44,13 @@ use crate::{
 		sync::{Status as SyncStatus, SyncState},
 		NotificationsSink, NotifsHandlerError, PeerInfo, Protocol, Ready,
 	},
+	recorder::Recorder,
 	transactions, transport, DhtEvent, ExHashT, NetworkStateInfo, NetworkStatus, ReputationChange,
 };
 
 use codec::Encode as _;
 use futures::{channel::oneshot, prelude::*};
+use futures_timer::Delay;
 use libp2p::{
 	core::{
 		connection::{ConnectionError, ConnectionLimits, PendingConnectionError},
@@ -89,6 +92,7 @@ use std::{
 
 pub use behaviour::{
 	IfDisconnected, InboundFailure, OutboundFailure, RequestFailure, ResponseFailure,
+	ThrottleReason,
 };
 
 mod metrics;
@@ -166,6 +170,14 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			fs::create_dir_all(path)?;
 		}
 
+		// Peers we connected to before the node was last stopped.
+		let peer_store = params
+			.network_config
+			.net_config_path
+			.as_ref()
+			.map(|path| PeerStore::load(path.join(peer_store::PEER_STORE_FILE)));
+		let known_peers = peer_store.as_ref().map(|s| s.known_peers()).unwrap_or_default();
+
 		let transactions_handler_proto =
 			transactions::TransactionsHandlerPrototype::new(params.protocol_id.clone());
 		params
@@ -185,16 +197,29 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 
 		let default_notif_handshake_message = Roles::from(&params.role).encode();
 
+		params.block_request_protocol_config.inbound_limits =
+			params.network_config.block_request_limits.clone();
+		params.state_request_protocol_config.inbound_limits =
+			params.network_config.state_request_limits.clone();
+
 		let (warp_sync_provider, warp_sync_protocol_config) = match params.warp_sync {
 			Some((p, c)) => (Some(p), Some(c)),
 			None => (None, None),
 		};
 
+		let recorder = params
+			.network_config
+			.message_recording
+			.as_ref()
+			.map(Recorder::create)
+			.transpose()?;
+
 		let (protocol, peerset_handle, mut known_addresses) = Protocol::new(
 			protocol::ProtocolConfig {
 				roles: From::from(&params.role),
 				max_parallel_downloads: params.network_config.max_parallel_downloads,
 				sync_mode: params.network_config.sync_mode.clone(),
+				recorder: recorder.clone(),
 			},
 			params.chain.clone(),
 			params.protocol_id.clone(),
@@ -210,6 +235,17 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			warp_sync_provider,
 		)?;
 
+		// Restore the reputations before the peers are discovered, so that the peerset dials the
+		// best ones first.
+		for peer in &known_peers {
+			if peer.reputation != 0 {
+				peerset_handle.report_peer(
+					peer.peer_id,
+					ReputationChange::new(peer.reputation, "Restored from the peer store"),
+				);
+			}
+		}
+
 		// List of multiaddresses that we know in the network.
 		let mut bootnodes = Vec::new();
 		let mut boot_node_ids = HashSet::new();
@@ -322,6 +358,7 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 					config_mem,
 					params.network_config.yamux_window_size,
 					yamux_maximum_buffer_size,
+					params.network_config.bandwidth_limits,
 				)
 			};
 
@@ -339,6 +376,7 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 					params.light_client_request_protocol_config,
 					params.network_config.request_response_protocols,
 					peerset_handle.clone(),
+					recorder,
 				);
 
 				match result {
@@ -396,6 +434,16 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			Swarm::<B>::add_external_address(&mut swarm, addr.clone(), AddressScore::Infinite);
 		}
 
+		// Add the addresses of the stored peers, which also makes them discovered.
+		if !known_peers.is_empty() {
+			info!(target: "sub-libp2p", "📒 Loaded {} peers from the peer store", known_peers.len());
+		}
+		for peer in known_peers {
+			for addr in peer.addresses {
+				swarm.behaviour_mut().add_known_address(peer.peer_id, addr);
+			}
+		}
+
 		let external_addresses = Arc::new(Mutex::new(Vec::new()));
 		let peers_notifications_sinks = Arc::new(Mutex::new(HashMap::new()));
 
@@ -435,9 +483,23 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkWorker<B, H> {
 			tx_handler_controller,
 			metrics,
 			boot_node_ids,
+			peer_store,
+			next_peer_store_save: Delay::new(peer_store::SAVE_INTERVAL),
 		})
 	}
 
+	/// Updates the reputations of the peer store from the peerset and writes it to disk.
+	fn save_peer_store(&mut self) {
+		if let Some(peer_store) = self.peer_store.as_mut() {
+			let protocol = self.network_service.behaviour_mut().user_protocol_mut();
+			for peer_id in peer_store.peer_ids().cloned().collect::<Vec<_>>() {
+				let reputation = protocol.peer_reputation(peer_id);
+				peer_store.set_reputation(&peer_id, reputation);
+			}
+			peer_store.save(peer_store::unix_time());
+		}
+	}
+
 	/// High-level network status information.
 	pub fn status(&self) -> NetworkStatus<B> {
 		let status = self.sync_state();
@@ -920,6 +982,28 @@ impl<B: BlockT + 'static, H: ExHashT> NetworkService<B, H> {
 		});
 	}
 
+	/// Fetches the data with the given CID from the connected peers over Bitswap.
+	///
+	/// The data is checked against the CID before being returned. Bitswap must be enabled with
+	/// [`NetworkConfiguration::ipfs_server`](crate::config::NetworkConfiguration::ipfs_server).
+	pub async fn bitswap_get(&self, cid: Cid) -> Result<Vec<u8>, BitswapError> {
+		let (tx, rx) = oneshot::channel();
+
+		self.start_bitswap_get(cid, tx);
+
+		match rx.await {
+			Ok(v) => v,
+			Err(_) => Err(BitswapError::NetworkUnavailable),
+		}
+	}
+
+	/// Variation of `bitswap_get` which delivers the data on a provided channel.
+	pub fn start_bitswap_get(&self, cid: Cid, tx: oneshot::Sender<Result<Vec<u8>, BitswapError>>) {
+		let _ = self
+			.to_worker
+			.unbounded_send(ServiceToWorkerMsg::BitswapGet { cid, pending_response: tx });
+	}
+
 	/// High-level network status information.
 	///
 	/// Returns an error if the `NetworkWorker` is no longer running.
@@ -1414,6 +1498,10 @@ enum ServiceToWorkerMsg<B: BlockT, H: ExHashT> {
 		pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
 		connect: IfDisconnected,
 	},
+	BitswapGet {
+		cid: Cid,
+		pending_response: oneshot::Sender<Result<Vec<u8>, BitswapError>>,
+	},
 	NetworkStatus {
 		pending_response: oneshot::Sender<Result<NetworkStatus<B>, RequestFailure>>,
 	},
@@ -1454,6 +1542,10 @@ pub struct NetworkWorker<B: BlockT + 'static, H: ExHashT> {
 	peers_notifications_sinks: Arc<Mutex<HashMap<(PeerId, Cow<'static, str>), NotificationsSink>>>,
 	/// Controller for the handler of incoming and outgoing transactions.
 	tx_handler_controller: transactions::TransactionsHandlerController<H>,
+	/// Peers we connected to, saved in the network configuration directory.
+	peer_store: Option<PeerStore>,
+	/// When to save the peer store next.
+	next_peer_store_save: Delay,
 }
 
 impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
@@ -1581,6 +1673,9 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 						connect,
 					);
 				},
+				ServiceToWorkerMsg::BitswapGet { cid, pending_response } => {
+					this.network_service.behaviour_mut().bitswap_get(cid, pending_response);
+				},
 				ServiceToWorkerMsg::NetworkStatus { pending_response } => {
 					let _ = pending_response.send(Ok(this.status()));
 				},
@@ -1662,6 +1757,18 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 										"busy-omitted",
 									ResponseFailure::Network(InboundFailure::ConnectionClosed) =>
 										"connection-closed",
+									ResponseFailure::Throttled(reason) => {
+										let reason = match reason {
+											ThrottleReason::PeerQuota => "peer-quota",
+											ThrottleReason::Upload => "upload",
+											ThrottleReason::Download => "download",
+										};
+										metrics
+											.requests_in_throttled_total
+											.with_label_values(&[&protocol, reason])
+											.inc();
+										continue
+									},
 								};
 
 								metrics
@@ -1850,6 +1957,14 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 						debug!(target: "sub-libp2p", "Libp2p => Connected({:?})", peer_id);
 					}
 
+					if let Some(peer_store) = this.peer_store.as_mut() {
+						let address = match &endpoint {
+							ConnectedPoint::Dialer { address } => Some(address),
+							ConnectedPoint::Listener { .. } => None,
+						};
+						peer_store.on_connected(peer_id, address, peer_store::unix_time());
+					}
+
 					if let Some(metrics) = this.metrics.as_ref() {
 						let direction = match endpoint {
 							ConnectedPoint::Dialer { .. } => "out",
@@ -1924,6 +2039,14 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 							peer_id, error,
 						);
 
+						if let (Some(peer_store), DialError::Transport(errors)) =
+							(this.peer_store.as_mut(), &error)
+						{
+							for (address, _) in errors {
+								peer_store.on_dial_failure(&peer_id, address);
+							}
+						}
+
 						if this.boot_node_ids.contains(&peer_id) {
 							if let DialError::InvalidPeerId = error {
 								error!(
@@ -2096,10 +2219,21 @@ impl<B: BlockT + 'static, H: ExHashT> Future for NetworkWorker<B, H> {
 			);
 		}
 
+		while this.next_peer_store_save.poll_unpin(cx).is_ready() {
+			this.next_peer_store_save = Delay::new(peer_store::SAVE_INTERVAL);
+			this.save_peer_store();
+		}
+
 		Poll::Pending
 	}
 }
 
+impl<B: BlockT + 'static, H: ExHashT> Drop for NetworkWorker<B, H> {
+	fn drop(&mut self) {
+		self.save_peer_store();
+	}
+}
+
 impl<B: BlockT + 'static, H: ExHashT> Unpin for NetworkWorker<B, H> {}
 
 /// The libp2p swarm, customized for our needs.
@@ -73,6 +73,7 @@ pub struct Metrics {
 	pub pending_connections: Gauge<U64>,
 	pub pending_connections_errors_total: CounterVec<U64>,
 	pub requests_in_failure_total: CounterVec<U64>,
+	pub requests_in_throttled_total: CounterVec<U64>,
 	pub requests_in_success_total: HistogramVec,
 	pub requests_out_failure_total: CounterVec<U64>,
 	pub requests_out_success_total: HistogramVec,
@@ -223,6 +224,13 @@ impl Metrics {
 				),
 				&["protocol", "reason"]
 			)?, registry)?,
+			requests_in_throttled_total: prometheus::register(CounterVec::new(
+				Opts::new(
+					"sub_libp2p_requests_in_throttled_total",
+					"Total number of incoming requests refused because of the limits of the protocol"
+				),
+				&["protocol", "reason"]
+			)?, registry)?,
 			requests_in_success_total: prometheus::register(HistogramVec::new(
 				HistogramOpts {
 					common_opts: Opts::new(
@@ -158,6 +158,7 @@ fn build_nodes_one_proto() -> (
 			notifications_protocol: PROTOCOL_NAME,
 			fallback_names: Vec::new(),
 			max_notification_size: 1024 * 1024,
+			bandwidth_limits: Default::default(),
 			set_config: Default::default(),
 		}],
 		listen_addresses: vec![listen_addr.clone()],
@@ -170,6 +171,7 @@ fn build_nodes_one_proto() -> (
 			notifications_protocol: PROTOCOL_NAME,
 			fallback_names: Vec::new(),
 			max_notification_size: 1024 * 1024,
+			bandwidth_limits: Default::default(),
 			set_config: config::SetConfig {
 				reserved_nodes: vec![config::MultiaddrWithPeerId {
 					multiaddr: listen_addr,
@@ -349,6 +351,7 @@ fn lots_of_incoming_peers_works() {
 			notifications_protocol: PROTOCOL_NAME,
 			fallback_names: Vec::new(),
 			max_notification_size: 1024 * 1024,
+			bandwidth_limits: Default::default(),
 			set_config: config::SetConfig { in_peers: u32::MAX, ..Default::default() },
 		}],
 		transport: config::TransportConfig::MemoryOnly,
@@ -368,6 +371,7 @@ fn lots_of_incoming_peers_works() {
 				notifications_protocol: PROTOCOL_NAME,
 				fallback_names: Vec::new(),
 				max_notification_size: 1024 * 1024,
+				bandwidth_limits: Default::default(),
 				set_config: config::SetConfig {
 					reserved_nodes: vec![config::MultiaddrWithPeerId {
 						multiaddr: listen_addr.clone(),
@@ -481,6 +485,7 @@ fn fallback_name_working() {
 			notifications_protocol: NEW_PROTOCOL_NAME.clone(),
 			fallback_names: vec![PROTOCOL_NAME],
 			max_notification_size: 1024 * 1024,
+			bandwidth_limits: Default::default(),
 			set_config: Default::default(),
 		}],
 		listen_addresses: vec![listen_addr.clone()],
@@ -493,6 +498,7 @@ fn fallback_name_working() {
 			notifications_protocol: PROTOCOL_NAME,
 			fallback_names: Vec::new(),
 			max_notification_size: 1024 * 1024,
+			bandwidth_limits: Default::default(),
 			set_config: config::SetConfig {
 				reserved_nodes: vec![config::MultiaddrWithPeerId {
 					multiaddr: listen_addr,
@@ -150,6 +150,7 @@ impl TransactionsHandlerPrototype {
 			notifications_protocol: self.protocol_name.clone(),
 			fallback_names: Vec::new(),
 			max_notification_size: MAX_TRANSACTIONS_SIZE,
+			bandwidth_limits: Default::default(),
 			set_config: config::SetConfig {
 				in_peers: 0,
 				out_peers: 0,
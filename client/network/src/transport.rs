@@ -16,6 +16,10 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+use crate::{
+	config::BandwidthLimits,
+	rate_limit::{RateLimited, RateLimiter},
+};
 use libp2p::{
 	bandwidth,
 	core::{
@@ -44,6 +48,8 @@ pub use self::bandwidth::BandwidthSinks;
 /// high-level protocols combined, or to some generously high value if you are sure that a maximum
 /// size is enforced on all high-level protocols.
 ///
+/// `bandwidth_limits` are shared by all the connections spawned with this transport.
+///
 /// Returns a `BandwidthSinks` object that allows querying the average bandwidth produced by all
 /// the connections spawned with this transport.
 pub fn build_transport(
@@ -51,6 +57,7 @@ pub fn build_transport(
 	memory_only: bool,
 	yamux_window_size: Option<u32>,
 	yamux_maximum_buffer_size: usize,
+	bandwidth_limits: BandwidthLimits,
 ) -> (Boxed<(PeerId, StreamMuxerBox)>, Arc<BandwidthSinks>) {
 	// Build the base layer of the transport.
 	let transport = if !memory_only {
@@ -69,6 +76,14 @@ pub fn build_transport(
 		))
 	};
 
+	let transport = {
+		let download = bandwidth_limits.download.map(RateLimiter::new);
+		let upload = bandwidth_limits.upload.map(RateLimiter::new);
+		transport.map(move |connection, _| {
+			RateLimited::new(connection, download.clone(), upload.clone())
+		})
+	};
+
 	let (transport, bandwidth) = bandwidth::BandwidthLogging::new(transport);
 
 	let authentication_config =
@@ -14,7 +14,11 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-//! Helper for handling (i.e. answering) grandpa warp sync requests from a remote peer.
+//! Helper for handling (i.e. answering) warp sync requests from a remote peer.
+//!
+//! Warp proofs are produced and verified by a [`WarpSyncProvider`], which is implemented by the
+//! finality gadget of the chain, e.g. GRANDPA. Any chain with a provable history of authority
+//! set changes can warp sync.
 
 use crate::config::{IncomingRequest, OutgoingResponse, ProtocolId, RequestResponseConfig};
 use codec::{Decode, Encode};
@@ -26,11 +30,14 @@ use log::debug;
 use sp_runtime::traits::Block as BlockT;
 use std::{sync::Arc, time::Duration};
 
-pub use sp_finality_grandpa::{AuthorityList, SetId};
-
 /// Scale-encoded warp sync proof response.
 pub struct EncodedProof(pub Vec<u8>);
 
+/// Scale-encoded authority set, as defined by the [`WarpSyncProvider`]. This is the state that
+/// is carried from one warp proof to the next one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EncodedAuthoritySet(pub Vec<u8>);
+
 /// Warp sync request
 #[derive(Encode, Decode, Debug)]
 pub struct Request<B: BlockT> {
@@ -42,13 +49,17 @@ const MAX_RESPONSE_SIZE: u64 = 16 * 1024 * 1024;
 
 /// Proof verification result.
 pub enum VerificationResult<Block: BlockT> {
-	/// Proof is valid, but the target was not reached.
-	Partial(SetId, AuthorityList, Block::Hash),
+	/// Proof is valid, but the target was not reached. The next proof starts at the given block
+	/// and is verified against the given authority set.
+	Partial(EncodedAuthoritySet, Block::Hash),
 	/// Target finality is proved.
-	Complete(SetId, AuthorityList, Block::Header),
+	Complete(EncodedAuthoritySet, Block::Header),
 }
 
 /// Warp sync backend. Handles retrieveing and verifying warp sync proofs.
+///
+/// The network doesn't interpret the proofs or the authority sets, so that any finality gadget
+/// can provide warp sync support by proving the history of its authority set changes.
 pub trait WarpSyncProvider<B: BlockT>: Send + Sync {
 	/// Generate proof starting at given block hash. The proof is accumulated until maximum proof
 	/// size is reached.
@@ -60,15 +71,14 @@ pub trait WarpSyncProvider<B: BlockT>: Send + Sync {
 	fn verify(
 		&self,
 		proof: &EncodedProof,
-		set_id: SetId,
-		authorities: AuthorityList,
+		authorities: EncodedAuthoritySet,
 	) -> Result<VerificationResult<B>, Box<dyn std::error::Error + Send + Sync>>;
-	/// Get current list of authorities. This is supposed to be genesis authorities when starting
+	/// Get current set of authorities. This is supposed to be genesis authorities when starting
 	/// sync.
-	fn current_authorities(&self) -> AuthorityList;
+	fn current_authorities(&self) -> EncodedAuthoritySet;
 }
 
-/// Generates a [`RequestResponseConfig`] for the grandpa warp sync request protocol, refusing
+/// Generates a [`RequestResponseConfig`] for the warp sync request protocol, refusing
 /// incoming requests.
 pub fn generate_request_response_config(protocol_id: ProtocolId) -> RequestResponseConfig {
 	RequestResponseConfig {
@@ -77,10 +87,11 @@ pub fn generate_request_response_config(protocol_id: ProtocolId) -> RequestRespo
 		max_response_size: MAX_RESPONSE_SIZE,
 		request_timeout: Duration::from_secs(10),
 		inbound_queue: None,
+		inbound_limits: Default::default(),
 	}
 }
 
-/// Generate the grandpa warp sync protocol name from chain specific protocol identifier.
+/// Generate the warp sync protocol name from chain specific protocol identifier.
 fn generate_protocol_name(protocol_id: ProtocolId) -> String {
 	let mut s = String::new();
 	s.push_str("/");
@@ -89,7 +100,7 @@ fn generate_protocol_name(protocol_id: ProtocolId) -> String {
 	s
 }
 
-/// Handler for incoming grandpa warp sync requests from a remote peer.
+/// Handler for incoming warp sync requests from a remote peer.
 pub struct RequestHandler<TBlock: BlockT> {
 	backend: Arc<dyn WarpSyncProvider<TBlock>>,
 	request_receiver: mpsc::Receiver<IncomingRequest>,
@@ -137,11 +148,11 @@ impl<TBlock: BlockT> RequestHandler<TBlock> {
 
 			match self.handle_request(payload, pending_response) {
 				Ok(()) => {
-					debug!(target: "sync", "Handled grandpa warp sync request from {}.", peer)
+					debug!(target: "sync", "Handled warp sync request from {}.", peer)
 				},
 				Err(e) => debug!(
 					target: "sync",
-					"Failed to handle grandpa warp sync request from {}: {}",
+					"Failed to handle warp sync request from {}: {}",
 					peer, e,
 				),
 			}
@@ -22,9 +22,12 @@ mod block_import;
 #[cfg(test)]
 mod sync;
 
+pub mod replay;
+
 use std::{
 	borrow::Cow,
 	collections::HashMap,
+	path::PathBuf,
 	pin::Pin,
 	sync::Arc,
 	task::{Context as FutureContext, Poll},
@@ -51,7 +54,7 @@ use sc_network::{
 	block_request_handler::BlockRequestHandler,
 	config::{
 		MultiaddrWithPeerId, NetworkConfiguration, NonDefaultSetConfig, NonReservedPeerMode,
-		ProtocolConfig, ProtocolId, Role, SyncMode, TransportConfig,
+		ProtocolConfig, ProtocolId, RequestResponseConfig, Role, SyncMode, TransportConfig,
 	},
 	light_client_requests::handler::LightClientRequestHandler,
 	state_request_handler::StateRequestHandler,
@@ -625,15 +628,14 @@ impl<B: BlockT> warp_request_handler::WarpSyncProvider<B> for TestWarpSyncProvid
 	fn verify(
 		&self,
 		proof: &warp_request_handler::EncodedProof,
-		_set_id: warp_request_handler::SetId,
-		_authorities: warp_request_handler::AuthorityList,
+		_authorities: warp_request_handler::EncodedAuthoritySet,
 	) -> Result<warp_request_handler::VerificationResult<B>, Box<dyn std::error::Error + Send + Sync>>
 	{
 		let warp_request_handler::EncodedProof(encoded) = proof;
 		let header = B::Header::decode(&mut encoded.as_slice()).unwrap();
-		Ok(warp_request_handler::VerificationResult::Complete(0, Default::default(), header))
+		Ok(warp_request_handler::VerificationResult::Complete(Default::default(), header))
 	}
-	fn current_authorities(&self) -> warp_request_handler::AuthorityList {
+	fn current_authorities(&self) -> warp_request_handler::EncodedAuthoritySet {
 		Default::default()
 	}
 }
@@ -659,8 +661,25 @@ pub struct FullPeerConfig {
 	pub extra_storage: Option<sp_core::storage::Storage>,
 	/// Enable transaction indexing.
 	pub storage_chain: bool,
+	/// File to record the messages exchanged with the network to.
+	pub message_recording: Option<PathBuf>,
+	/// Warp sync provider of the peer, built from its client.
+	///
+	/// If `None`, a provider that warps straight to the best block is used.
+	pub warp_sync_provider: Option<WarpSyncProviderBuilder>,
+	/// Extra request-response protocols served by the peer.
+	pub request_response_protocols: Vec<RequestResponseProtocolBuilder>,
 }
 
+/// Builds the warp sync provider of a peer from its client.
+pub type WarpSyncProviderBuilder =
+	Box<dyn FnOnce(PeersClient) -> Arc<dyn warp_request_handler::WarpSyncProvider<Block>>>;
+
+/// Builds a request-response protocol of a peer from its client: the protocol configuration
+/// and the task answering the incoming requests.
+pub type RequestResponseProtocolBuilder =
+	Box<dyn FnOnce(PeersClient) -> (RequestResponseConfig, BoxFuture<'static, ()>)>;
+
 pub trait TestNetFactory: Sized
 where
 	<Self::BlockImport as BlockImport<Block>>::Transaction: Send,
@@ -763,6 +782,7 @@ where
 		network_config.transport = TransportConfig::MemoryOnly;
 		network_config.listen_addresses = vec![listen_addr.clone()];
 		network_config.allow_non_globals_in_dht = true;
+		network_config.message_recording = config.message_recording;
 		network_config.extra_sets = config
 			.notifications_protocols
 			.into_iter()
@@ -770,6 +790,7 @@ where
 				notifications_protocol: p,
 				fallback_names: Vec::new(),
 				max_notification_size: 1024 * 1024,
+				bandwidth_limits: Default::default(),
 				set_config: Default::default(),
 			})
 			.collect();
@@ -786,6 +807,13 @@ where
 			network_config.default_peers_set.non_reserved_mode = NonReservedPeerMode::Deny;
 		}
 
+		for build in config.request_response_protocols {
+			let (protocol_config, handler) =
+				build(PeersClient { client: client.clone(), backend: backend.clone() });
+			self.spawn_task(handler);
+			network_config.request_response_protocols.push(protocol_config);
+		}
+
 		let protocol_id = ProtocolId::from("test-protocol-name");
 
 		let block_request_protocol_config = {
@@ -809,7 +837,10 @@ where
 			protocol_config
 		};
 
-		let warp_sync = Arc::new(TestWarpSyncProvider(client.clone()));
+		let warp_sync = match config.warp_sync_provider {
+			Some(build) => build(PeersClient { client: client.clone(), backend: backend.clone() }),
+			None => Arc::new(TestWarpSyncProvider(client.clone())),
+		};
 
 		let warp_protocol_config = {
 			let (handler, protocol_config) =
@@ -0,0 +1,136 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Replaying of the messages captured by the network recorder.
+//!
+//! The notifications and requests that a node has received, as written by
+//! [`sc_network::recorder::Recorder`], are sent again by a peer of a test network to a local
+//! node, in the same order. Requests are sent one at a time, and the responses of the local node
+//! are collected so that they can be compared with the ones of the recorded node.
+//!
+//! The protocol names of the capture must match the ones of the test network. Notifications are
+//! only delivered if the corresponding substream is open between the two peers.
+
+use futures::{channel::oneshot, prelude::*};
+use sc_network::{
+	recorder::{Direction, MessageKind, Record, RecordReader},
+	IfDisconnected, NetworkService, OutboundFailure, PeerId, RequestFailure,
+};
+use std::{
+	collections::VecDeque,
+	io,
+	path::Path,
+	task::{Context as FutureContext, Poll},
+};
+use substrate_test_runtime_client::runtime::{Block, Hash};
+
+/// A replayed message and, for requests, the response of the local node.
+#[derive(Debug)]
+pub struct ReplayOutcome {
+	/// The message, as recorded.
+	pub record: Record,
+	/// Response of the local node. `None` for notifications.
+	pub response: Option<Result<Vec<u8>, RequestFailure>>,
+}
+
+/// A request sent to the local node, with the receiver of its response.
+type PendingRequest = (Record, oneshot::Receiver<Result<Vec<u8>, RequestFailure>>);
+
+/// Sends the messages received by a recorded node to a local node.
+pub struct Replayer {
+	/// Messages left to send.
+	records: VecDeque<Record>,
+	/// Request sent and waiting for a response.
+	pending_request: Option<PendingRequest>,
+	/// Messages sent so far.
+	outcomes: Vec<ReplayOutcome>,
+}
+
+impl Replayer {
+	/// Creates a replayer of the inbound notifications and requests among `records`.
+	pub fn new(records: impl IntoIterator<Item = Record>) -> Self {
+		let records = records
+			.into_iter()
+			.filter(|record| {
+				record.direction == Direction::Inbound &&
+					matches!(record.kind, MessageKind::Notification | MessageKind::Request)
+			})
+			.collect();
+		Self { records, pending_request: None, outcomes: Vec::new() }
+	}
+
+	/// Creates a replayer of the capture at `path`.
+	pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+		let records = RecordReader::open(path)?
+			.collect::<Result<Vec<_>, _>>()
+			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+		Ok(Self::new(records))
+	}
+
+	/// Sends the messages through `network` to `target`.
+	///
+	/// Must be polled along with the test network. Returns the outcomes of all the messages once
+	/// the last one has been sent and, if it is a request, answered.
+	pub fn poll(
+		&mut self,
+		cx: &mut FutureContext,
+		network: &NetworkService<Block, Hash>,
+		target: PeerId,
+	) -> Poll<Vec<ReplayOutcome>> {
+		loop {
+			if let Some((_, response)) = self.pending_request.as_mut() {
+				let response = match response.poll_unpin(cx) {
+					Poll::Ready(Ok(response)) => response,
+					// The network worker no longer exists.
+					Poll::Ready(Err(_)) =>
+						Err(RequestFailure::Network(OutboundFailure::ConnectionClosed)),
+					Poll::Pending => return Poll::Pending,
+				};
+				let (record, _) = self.pending_request.take().expect("Checked above; qed");
+				self.outcomes.push(ReplayOutcome { record, response: Some(response) });
+			}
+
+			let record = match self.records.pop_front() {
+				Some(record) => record,
+				None => return Poll::Ready(std::mem::take(&mut self.outcomes)),
+			};
+
+			match record.kind {
+				MessageKind::Notification => {
+					network.write_notification(
+						target,
+						record.protocol.clone().into(),
+						record.payload.clone(),
+					);
+					self.outcomes.push(ReplayOutcome { record, response: None });
+				},
+				MessageKind::Request | MessageKind::Response => {
+					let (tx, rx) = oneshot::channel();
+					network.start_request(
+						target,
+						record.protocol.clone(),
+						record.payload.clone(),
+						tx,
+						IfDisconnected::ImmediateError,
+					);
+					self.pending_request = Some((record, rx));
+				},
+			}
+		}
+	}
+}
@@ -1196,3 +1196,141 @@ fn syncs_huge_blocks() {
 	assert_eq!(net.peer(0).client.info().best_number, 33);
 	assert_eq!(net.peer(1).client.info().best_number, 33);
 }
+
+/// Polls `net` until `events` reports a notification substream opened on `protocol`.
+fn wait_for_notification_substream(
+	net: &mut TestNet,
+	events: &mut (impl Stream<Item = sc_network::Event> + Unpin),
+	protocol: &str,
+) {
+	block_on(futures::future::poll_fn(|cx| {
+		net.poll(cx);
+		while let Poll::Ready(Some(event)) = events.poll_next_unpin(cx) {
+			if matches!(
+				event,
+				sc_network::Event::NotificationStreamOpened { protocol: ref p, .. } if p == protocol
+			) {
+				return Poll::Ready(())
+			}
+		}
+		Poll::Pending
+	}));
+}
+
+#[test]
+fn replayed_messages_get_the_recorded_responses() {
+	use sc_network::recorder::{Direction, MessageKind, Record};
+
+	sp_tracing::try_init_simple();
+	const PROTOCOL: &str = "/replay-test/1";
+	let dir = tempfile::tempdir().unwrap();
+	let capture = dir.path().join("messages");
+
+	// Record the messages exchanged by peer 0 while peer 1 syncs from it and then sends it a
+	// notification.
+	let mut net = TestNet::new(0);
+	net.add_full_peer_with_config(FullPeerConfig {
+		notifications_protocols: vec![PROTOCOL.into()],
+		message_recording: Some(capture.clone()),
+		..Default::default()
+	});
+	net.add_full_peer_with_config(FullPeerConfig {
+		notifications_protocols: vec![PROTOCOL.into()],
+		..Default::default()
+	});
+	let mut events = net.peer(1).network_service().event_stream("test");
+	net.peer(0).push_blocks(20, false);
+	net.block_until_sync();
+	assert_eq!(net.peer(1).client.info().best_number, 20);
+	wait_for_notification_substream(&mut net, &mut events, PROTOCOL);
+	let recorded_peer = *net.peer(0).network_service().local_peer_id();
+	net.peer(1).network_service().write_notification(
+		recorded_peer,
+		PROTOCOL.into(),
+		b"hello".to_vec(),
+	);
+
+	let is_test_notification = |record: &Record| {
+		record.protocol == PROTOCOL &&
+			record.direction == Direction::Inbound &&
+			record.kind == MessageKind::Notification &&
+			record.payload == b"hello"
+	};
+	// The capture is written in the background, the notification shows up after the responses.
+	let records = block_on(futures::future::poll_fn(|cx| {
+		net.poll(cx);
+		// The last record may only be partially written.
+		let records = sc_network::recorder::RecordReader::open(&capture)
+			.unwrap()
+			.map_while(Result::ok)
+			.collect::<Vec<_>>();
+		if records.iter().any(is_test_notification) {
+			Poll::Ready(records)
+		} else {
+			cx.waker().wake_by_ref();
+			Poll::Pending
+		}
+	}));
+	drop(net);
+
+	let block_responses = |records: &[Record]| {
+		let mut responses = records
+			.iter()
+			.filter(|record| {
+				record.protocol.ends_with("/sync/2") &&
+					record.direction == Direction::Outbound &&
+					record.kind == MessageKind::Response
+			})
+			.map(|record| record.payload.clone())
+			.collect::<Vec<_>>();
+		responses.sort();
+		responses
+	};
+	let recorded = block_responses(&records);
+	assert!(!recorded.is_empty());
+
+	// Replay them against a fresh node with the same chain.
+	let mut replayer = replay::Replayer::new(records);
+	let mut net = TestNet::new(0);
+	for _ in 0..2 {
+		net.add_full_peer_with_config(FullPeerConfig {
+			notifications_protocols: vec![PROTOCOL.into()],
+			..Default::default()
+		});
+	}
+	let mut replayer_events = net.peer(1).network_service().event_stream("test");
+	let mut target_events = net.peer(0).network_service().event_stream("test");
+	net.peer(0).push_blocks(20, false);
+	net.block_until_connected();
+	wait_for_notification_substream(&mut net, &mut replayer_events, PROTOCOL);
+	let target = *net.peer(0).network_service().local_peer_id();
+	let outcomes = block_on(futures::future::poll_fn(|cx| {
+		net.poll(cx);
+		replayer.poll(cx, net.peer(1).network_service(), target)
+	}));
+
+	assert!(outcomes.iter().any(|outcome| is_test_notification(&outcome.record)));
+	let mut replayed = outcomes
+		.into_iter()
+		.filter(|outcome| outcome.record.protocol.ends_with("/sync/2"))
+		.map(|outcome| outcome.response.unwrap().unwrap())
+		.collect::<Vec<_>>();
+	replayed.sort();
+	assert_eq!(replayed, recorded);
+
+	// The replayed notification reaches the local node.
+	let replayer_peer = *net.peer(1).network_service().local_peer_id();
+	block_on(futures::future::poll_fn(|cx| {
+		net.poll(cx);
+		while let Poll::Ready(Some(event)) = target_events.poll_next_unpin(cx) {
+			if let sc_network::Event::NotificationsReceived { remote, messages } = event {
+				if remote == replayer_peer &&
+					messages.iter().any(|(p, m)| p == PROTOCOL && &m[..] == b"hello")
+				{
+					return Poll::Ready(())
+				}
+			}
+		}
+		Poll::Pending
+	}));
+}
@@ -719,6 +719,12 @@ impl Peerset {
 	pub fn num_discovered_peers(&self) -> usize {
 		self.data.peers().len()
 	}
+
+	/// Returns the current reputation of the given peer.
+	pub fn peer_reputation(&mut self, peer_id: PeerId) -> i32 {
+		self.update_time();
+		self.data.peer_reputation(peer_id).reputation()
+	}
 }
 
 impl Stream for Peerset {
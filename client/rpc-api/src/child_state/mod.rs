@@ -93,6 +93,19 @@ pub trait ChildStateApi<Hash> {
 		hash: Option<Hash>,
 	) -> FutureResult<Option<u64>>;
 
+	/// Returns the hashes of the blocks, from the block given as the third parameter up to
+	/// `hash` (or best), that wrote the given child storage key.
+	///
+	/// Requires the node to keep a state diff index for the whole range.
+	#[rpc(name = "childstate_keyHistory")]
+	fn key_history(
+		&self,
+		child_storage_key: PrefixedStorageKey,
+		key: StorageKey,
+		block: Hash,
+		hash: Option<Hash>,
+	) -> FutureResult<Vec<Hash>>;
+
 	/// Returns proof of storage for child key entries at a specific block's state.
 	#[rpc(name = "state_getChildReadProof")]
 	fn read_child_proof(
@@ -51,6 +51,9 @@ pub enum Error {
 		/// Maximum allowed value
 		max: u32,
 	},
+	/// The state diff index has no entry for a block of the requested range.
+	#[error("State diff is not indexed for block {}", .0)]
+	StateDiffUnavailable(String),
 	/// Call to an unsafe RPC was denied.
 	#[error(transparent)]
 	UnsafeRpcCalled(#[from] crate::policy::UnsafeRpcError),
@@ -72,6 +75,11 @@ impl From<Error> for rpc::Error {
 				message: format!("{}", e),
 				data: None,
 			},
+			Error::StateDiffUnavailable(_) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 3),
+				message: format!("{}", e),
+				data: None,
+			},
 			e => errors::internal(e),
 		}
 	}
@@ -30,3 +30,13 @@ pub struct ReadProof<Hash> {
 	/// A proof used to prove that storage entries are included in the storage trie
 	pub proof: Vec<Bytes>,
 }
+
+/// ReadProofRange struct returned by the RPC
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadProofRange<Hash> {
+	/// Hashes of the blocks whose state is proven, in order
+	pub blocks: Vec<Hash>,
+	/// SCALE encoded compact proof of the storage entries at the state of all the blocks
+	pub proof: Bytes,
+}
@@ -31,7 +31,10 @@ use sp_core::{
 };
 use sp_version::RuntimeVersion;
 
-pub use self::{gen_client::Client as StateClient, helpers::ReadProof};
+pub use self::{
+	gen_client::Client as StateClient,
+	helpers::{ReadProof, ReadProofRange},
+};
 
 /// Substrate state API
 #[rpc]
@@ -110,6 +113,18 @@ pub trait StateApi<Hash> {
 		at: Option<Hash>,
 	) -> FutureResult<Vec<StorageChangeSet<Hash>>>;
 
+	/// Returns the hashes of the blocks, from the block given as the second parameter up to
+	/// `hash` (or best), that wrote the given storage key.
+	///
+	/// Requires the node to keep a state diff index for the whole range.
+	#[rpc(name = "state_keyHistory")]
+	fn key_history(
+		&self,
+		key: StorageKey,
+		block: Hash,
+		hash: Option<Hash>,
+	) -> FutureResult<Vec<Hash>>;
+
 	/// Returns proof of storage entries at a specific block's state.
 	#[rpc(name = "state_getReadProof")]
 	fn read_proof(
@@ -118,6 +133,20 @@ pub trait StateApi<Hash> {
 		hash: Option<Hash>,
 	) -> FutureResult<ReadProof<Hash>>;
 
+	/// Returns a single proof of storage entries at the state of each block, from the block
+	/// given as the second parameter up to `hash` (or best).
+	///
+	/// Trie nodes shared by the states are only included once, and the trie nodes of each state
+	/// are encoded with the state version of the runtime of its block. At most 256 blocks can be
+	/// proven at once.
+	#[rpc(name = "state_getReadProofRange")]
+	fn read_proof_range(
+		&self,
+		keys: Vec<StorageKey>,
+		block: Hash,
+		hash: Option<Hash>,
+	) -> FutureResult<ReadProofRange<Hash>>;
+
 	/// New runtime version subscription
 	#[pubsub(
 		subscription = "state_runtimeVersion",
@@ -184,7 +213,7 @@ pub trait StateApi<Hash> {
 	/// ## Node recommendations
 	///
 	/// - Use fast SSD disk storage.
-	/// - Run node flags to increase DB read speed (i.e. `--state-cache-size`, `--db-cache`).
+	/// - Run node flags to increase DB read speed (i.e. `--trie-cache-size`, `--db-cache`).
 	///
 	/// ## Creating tracing enabled WASM runtimes
 	///
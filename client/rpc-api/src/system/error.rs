@@ -33,6 +33,12 @@ pub enum Error {
 	/// Peer argument is malformatted.
 	#[error("{0}")]
 	MalformattedPeerArg(String),
+	/// CID argument is malformatted.
+	#[error("{0}")]
+	MalformattedCid(String),
+	/// Fetching data over Bitswap failed.
+	#[error("Bitswap request failed: {0}")]
+	BitswapFailed(String),
 }
 
 /// Base code for all system errors.
@@ -51,6 +57,16 @@ impl From<Error> for rpc::Error {
 				message: e.clone(),
 				data: None,
 			},
+			Error::MalformattedCid(ref e) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 3),
+				message: e.clone(),
+				data: None,
+			},
+			Error::BitswapFailed(_) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 4),
+				message: format!("{}", e),
+				data: None,
+			},
 		}
 	}
 }
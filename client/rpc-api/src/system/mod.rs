@@ -24,6 +24,7 @@ pub mod helpers;
 use crate::helpers::Receiver;
 use jsonrpc_core::BoxFuture;
 use jsonrpc_derive::rpc;
+use sp_core::Bytes;
 
 use self::error::Result as SystemResult;
 
@@ -103,6 +104,11 @@ pub trait SystemApi<Hash, Number> {
 		peer_id: String,
 	) -> BoxFuture<Result<(), jsonrpc_core::Error>>;
 
+	/// Fetches the data with the given CID from the connected peers over Bitswap, e.g. an
+	/// indexed transaction. The CID is checked against the received data.
+	#[rpc(name = "system_bitswapGet", returns = "Bytes")]
+	fn system_bitswap_get(&self, cid: String) -> BoxFuture<Result<Bytes, jsonrpc_core::Error>>;
+
 	/// Returns the list of reserved peers
 	#[rpc(name = "system_reservedPeers", returns = "Vec<String>")]
 	fn system_reserved_peers(&self) -> Receiver<Vec<String>>;
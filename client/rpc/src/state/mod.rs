@@ -28,7 +28,10 @@ use jsonrpc_pubsub::{manager::SubscriptionManager, typed::Subscriber, Subscripti
 use rpc::Result as RpcResult;
 use std::sync::Arc;
 
-use sc_rpc_api::{state::ReadProof, DenyUnsafe};
+use sc_rpc_api::{
+	state::{ReadProof, ReadProofRange},
+	DenyUnsafe,
+};
 use sp_core::{
 	storage::{PrefixedStorageKey, StorageChangeSet, StorageData, StorageKey},
 	Bytes,
@@ -48,6 +51,9 @@ use sp_blockchain::{HeaderBackend, HeaderMetadata};
 
 const STORAGE_KEYS_PAGED_MAX_COUNT: u32 = 1000;
 
+/// Maximum number of blocks whose state can be proven by a single `state_getReadProofRange` call.
+const READ_PROOF_RANGE_MAX_BLOCKS: u32 = 256;
+
 /// State backend API.
 pub trait StateBackend<Block: BlockT, Client>: Send + Sync + 'static
 where
@@ -134,6 +140,14 @@ where
 		at: Option<Block::Hash>,
 	) -> FutureResult<Vec<StorageChangeSet<Block::Hash>>>;
 
+	/// Returns the blocks from `from` up to `to` (or best) that wrote the given key.
+	fn key_history(
+		&self,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+		key: StorageKey,
+	) -> FutureResult<Vec<Block::Hash>>;
+
 	/// Returns proof of storage entries at a specific block's state.
 	fn read_proof(
 		&self,
@@ -141,6 +155,15 @@ where
 		keys: Vec<StorageKey>,
 	) -> FutureResult<ReadProof<Block::Hash>>;
 
+	/// Returns a single proof of storage entries at the state of each block from `from` up to
+	/// `to` (or best), at most [`READ_PROOF_RANGE_MAX_BLOCKS`] blocks.
+	fn read_proof_range(
+		&self,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+		keys: Vec<StorageKey>,
+	) -> FutureResult<ReadProofRange<Block::Hash>>;
+
 	/// New runtime version subscription
 	fn subscribe_runtime_version(
 		&self,
@@ -212,7 +235,7 @@ where
 	));
 	let backend =
 		Box::new(self::state_full::FullState::new(client, subscriptions, rpc_max_payload));
-	(State { backend, deny_unsafe }, ChildState { backend: child_backend })
+	(State { backend, deny_unsafe }, ChildState { backend: child_backend, deny_unsafe })
 }
 
 /// State API with subscriptions support.
@@ -318,6 +341,19 @@ where
 		self.backend.query_storage_at(keys, at)
 	}
 
+	fn key_history(
+		&self,
+		key: StorageKey,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+	) -> FutureResult<Vec<Block::Hash>> {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return async move { Err(err.into()) }.boxed()
+		}
+
+		self.backend.key_history(from, to, key)
+	}
+
 	fn read_proof(
 		&self,
 		keys: Vec<StorageKey>,
@@ -326,6 +362,19 @@ where
 		self.backend.read_proof(block, keys)
 	}
 
+	fn read_proof_range(
+		&self,
+		keys: Vec<StorageKey>,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+	) -> FutureResult<ReadProofRange<Block::Hash>> {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return async move { Err(err.into()) }.boxed()
+		}
+
+		self.backend.read_proof_range(from, to, keys)
+	}
+
 	fn subscribe_storage(
 		&self,
 		meta: Self::Metadata,
@@ -440,6 +489,15 @@ where
 		key: StorageKey,
 	) -> FutureResult<Option<Block::Hash>>;
 
+	/// Returns the blocks from `from` up to `to` (or best) that wrote the given child key.
+	fn key_history(
+		&self,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+		storage_key: PrefixedStorageKey,
+		key: StorageKey,
+	) -> FutureResult<Vec<Block::Hash>>;
+
 	/// Returns the size of a child storage entry at a block's state.
 	fn storage_size(
 		&self,
@@ -456,6 +514,8 @@ where
 /// Child state API with subscriptions support.
 pub struct ChildState<Block, Client> {
 	backend: Box<dyn ChildStateBackend<Block, Client>>,
+	/// Whether to deny unsafe calls
+	deny_unsafe: DenyUnsafe,
 }
 
 impl<Block, Client> ChildStateApi<Block::Hash> for ChildState<Block, Client>
@@ -529,6 +589,20 @@ where
 	) -> FutureResult<Option<u64>> {
 		self.backend.storage_size(block, storage_key, key)
 	}
+
+	fn key_history(
+		&self,
+		storage_key: PrefixedStorageKey,
+		key: StorageKey,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+	) -> FutureResult<Vec<Block::Hash>> {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return async move { Err(err.into()) }.boxed()
+		}
+
+		self.backend.key_history(from, to, storage_key, key)
+	}
 }
 
 fn client_err(err: sp_blockchain::Error) -> Error {
@@ -18,6 +18,7 @@
 
 //! State API backend for full nodes.
 
+use codec::Encode;
 use futures::{
 	future,
 	future::{err, try_join_all},
@@ -28,7 +29,7 @@ use log::warn;
 use rpc::Result as RpcResult;
 use std::{collections::HashMap, sync::Arc};
 
-use sc_rpc_api::state::ReadProof;
+use sc_rpc_api::state::{ReadProof, ReadProofRange};
 use sp_blockchain::{
 	CachedHeaderMetadata, Error as ClientError, HeaderBackend, HeaderMetadata,
 	Result as ClientResult,
@@ -39,7 +40,10 @@ use sp_core::{
 	},
 	Bytes,
 };
-use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, HashFor, NumberFor, Saturating},
+};
 use sp_version::RuntimeVersion;
 
 use sp_api::{CallApiAt, Metadata, ProvideRuntimeApi};
@@ -143,6 +147,8 @@ where
 	}
 
 	/// Iterates through range.unfiltered_range and check each block for changes of keys' values.
+	///
+	/// Blocks covered by the state diff index are only read for the keys they wrote.
 	fn query_storage_unfiltered(
 		&self,
 		range: &QueryStorageRange<Block>,
@@ -154,8 +160,13 @@ where
 			let block_hash = block_hash.clone();
 			let mut block_changes =
 				StorageChangeSet { block: block_hash.clone(), changes: Vec::new() };
+			let diff = self.client.state_diff(&block_hash).map_err(client_err)?;
 			let id = BlockId::hash(block_hash);
 			for key in keys {
+				let unchanged = matches!(&diff, Some(diff) if !diff.contains(None, &key.0));
+				if unchanged && last_values.contains_key(key) {
+					continue
+				}
 				let (has_changed, data) = {
 					let curr_data = self.client.storage(&id, key).map_err(client_err)?;
 					match last_values.get(key) {
@@ -174,6 +185,38 @@ where
 		}
 		Ok(())
 	}
+
+	/// Returns the blocks of the range that wrote `key`, according to the state diff index.
+	///
+	/// The genesis block is never indexed, it counts as writing every key it holds.
+	fn key_history_unfiltered(
+		&self,
+		range: &QueryStorageRange<Block>,
+		child_info: Option<&ChildInfo>,
+		key: &StorageKey,
+	) -> Result<Vec<Block::Hash>> {
+		let genesis_hash = self.client.info().genesis_hash;
+		let mut blocks = Vec::new();
+		for block_hash in &range.hashes {
+			let written = match self.client.state_diff(block_hash).map_err(client_err)? {
+				Some(diff) => diff.contains(child_info, &key.0),
+				None if *block_hash == genesis_hash => {
+					let id = BlockId::hash(genesis_hash);
+					match child_info {
+						Some(child_info) => self.client.child_storage(&id, child_info, key),
+						None => self.client.storage(&id, key),
+					}
+					.map_err(client_err)?
+					.is_some()
+				},
+				None => return Err(Error::StateDiffUnavailable(format!("{:?}", block_hash))),
+			};
+			if written {
+				blocks.push(*block_hash);
+			}
+		}
+		Ok(blocks)
+	}
 }
 
 impl<BE, Block, Client> StateBackend<Block, Client> for FullState<BE, Block, Client>
@@ -366,6 +409,18 @@ where
 		self.query_storage(at, Some(at), keys)
 	}
 
+	fn key_history(
+		&self,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+		key: StorageKey,
+	) -> FutureResult<Vec<Block::Hash>> {
+		let r = self
+			.query_storage_range(from, to)
+			.and_then(|range| self.key_history_unfiltered(&range, None, &key));
+		async move { r }.boxed()
+	}
+
 	fn read_proof(
 		&self,
 		block: Option<Block::Hash>,
@@ -383,6 +438,57 @@ where
 		async move { r }.boxed()
 	}
 
+	fn read_proof_range(
+		&self,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+		keys: Vec<StorageKey>,
+	) -> FutureResult<ReadProofRange<Block::Hash>> {
+		let check_range_size = || {
+			let to = self
+				.block_or_best(to)
+				.map_err(|e| invalid_block::<Block>(from, to, e.to_string()))?;
+			let invalid_block_err =
+				|e: ClientError| invalid_block::<Block>(from, Some(to), e.to_string());
+			let from_meta = self.client.header_metadata(from).map_err(invalid_block_err)?;
+			let to_meta = self.client.header_metadata(to).map_err(invalid_block_err)?;
+			if to_meta.number.saturating_sub(from_meta.number) >=
+				NumberFor::<Block>::from(super::READ_PROOF_RANGE_MAX_BLOCKS)
+			{
+				return Err(invalid_block_range(
+					&from_meta,
+					&to_meta,
+					format!("more than {} blocks", super::READ_PROOF_RANGE_MAX_BLOCKS),
+				))
+			}
+			Ok(())
+		};
+
+		let r =
+			check_range_size()
+				.and_then(|_| self.query_storage_range(from, to))
+				.and_then(|range| {
+					let proofs = range
+						.hashes
+						.iter()
+						.map(|hash| {
+							let id = BlockId::Hash(*hash);
+							let state_version =
+								self.client.runtime_version_at(&id)?.state_version();
+							let proof = self
+								.client
+								.read_proof(&id, &mut keys.iter().map(|key| key.0.as_ref()))?;
+							Ok((proof, state_version))
+						})
+						.collect::<ClientResult<Vec<_>>>()
+						.map_err(client_err)?;
+					let proof = sp_trie::encode_compact_multi::<HashFor<Block>, _>(proofs)
+						.map_err(|e| client_err(ClientError::Backend(e.to_string())))?;
+					Ok(ReadProofRange { blocks: range.hashes, proof: proof.encode().into() })
+				});
+		async move { r }.boxed()
+	}
+
 	fn subscribe_runtime_version(
 		&self,
 		_meta: crate::Metadata,
@@ -681,6 +787,22 @@ where
 
 		async move { r }.boxed()
 	}
+	fn key_history(
+		&self,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+		storage_key: PrefixedStorageKey,
+		key: StorageKey,
+	) -> FutureResult<Vec<Block::Hash>> {
+		let child_info = match ChildType::from_prefixed_key(&storage_key) {
+			Some((ChildType::ParentKeyId, storage_key)) => ChildInfo::new_default(storage_key),
+			None => return err(client_err(sp_blockchain::Error::InvalidChildStorageKey)).boxed(),
+		};
+		let r = self
+			.query_storage_range(from, to)
+			.and_then(|range| self.key_history_unfiltered(&range, Some(&child_info), &key));
+		async move { r }.boxed()
+	}
 }
 
 fn invalid_block_range<B: BlockT>(
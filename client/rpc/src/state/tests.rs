@@ -20,12 +20,14 @@ use self::error::Error;
 use super::*;
 use crate::testing::TaskExecutor;
 use assert_matches::assert_matches;
+use codec::Decode;
 use futures::{executor, StreamExt};
 use sc_block_builder::BlockBuilderProvider;
 use sc_rpc_api::DenyUnsafe;
 use sp_consensus::BlockOrigin;
 use sp_core::{hash::H256, storage::ChildInfo};
 use sp_io::hashing::blake2_256;
+use sp_runtime::traits::BlakeTwo256;
 use std::sync::Arc;
 use substrate_test_runtime_client::{prelude::*, runtime};
 
@@ -515,6 +517,107 @@ fn should_query_storage() {
 	run_tests(Arc::new(TestClientBuilder::new().build()));
 }
 
+#[test]
+fn should_return_key_history() {
+	let mut client = Arc::new(substrate_test_runtime_client::new());
+	let (api, _child) = new_full(
+		client.clone(),
+		SubscriptionManager::new(Arc::new(TaskExecutor)),
+		DenyUnsafe::No,
+		None,
+	);
+
+	let mut add_block = |key: u8| {
+		let mut builder = client.new_block(Default::default()).unwrap();
+		builder.push_storage_change(vec![key], Some(vec![key])).unwrap();
+		let block = builder.build().unwrap().block;
+		let hash = block.header.hash();
+		executor::block_on(client.import(BlockOrigin::Own, block)).unwrap();
+		hash
+	};
+	let block1_hash = add_block(1);
+	let block2_hash = add_block(2);
+	let block3_hash = add_block(1);
+	let genesis_hash = client.genesis_hash();
+
+	let result = api.key_history(StorageKey(vec![1]), genesis_hash, None);
+	assert_eq!(executor::block_on(result).unwrap(), vec![block1_hash, block3_hash]);
+
+	let result = api.key_history(StorageKey(vec![2]), block2_hash, Some(block3_hash));
+	assert_eq!(executor::block_on(result).unwrap(), vec![block2_hash]);
+
+	let result = api.key_history(StorageKey(vec![3]), genesis_hash, None);
+	assert_eq!(executor::block_on(result).unwrap(), vec![]);
+
+	// genesis is not indexed, but counts as writing all of its keys.
+	let code = StorageKey(sp_core::storage::well_known_keys::CODE.to_vec());
+	let result = api.key_history(code, genesis_hash, Some(block1_hash));
+	assert_eq!(executor::block_on(result).unwrap(), vec![genesis_hash]);
+
+	let result = api.key_history(StorageKey(vec![1]), block3_hash, Some(block1_hash));
+	assert_matches!(executor::block_on(result), Err(Error::InvalidBlockRange { .. }));
+}
+
+#[test]
+fn should_return_read_proof_range() {
+	let mut client = Arc::new(substrate_test_runtime_client::new());
+	let (api, _child) = new_full(
+		client.clone(),
+		SubscriptionManager::new(Arc::new(TaskExecutor)),
+		DenyUnsafe::No,
+		None,
+	);
+
+	let mut add_block = |key: u8, value: u8| {
+		let mut builder = client.new_block(Default::default()).unwrap();
+		builder.push_storage_change(vec![key], Some(vec![value])).unwrap();
+		let block = builder.build().unwrap().block;
+		let (hash, state_root) = (block.header.hash(), block.header.state_root);
+		executor::block_on(client.import(BlockOrigin::Own, block)).unwrap();
+		(hash, state_root)
+	};
+	let (block1_hash, root1) = add_block(1, 1);
+	let (block2_hash, root2) = add_block(2, 2);
+	let (block3_hash, root3) = add_block(1, 3);
+
+	let keys = vec![StorageKey(vec![1]), StorageKey(vec![2])];
+	let result = api.read_proof_range(keys.clone(), block1_hash, Some(block3_hash));
+	let result = executor::block_on(result).unwrap();
+	assert_eq!(result.blocks, vec![block1_hash, block2_hash, block3_hash]);
+
+	let proof = sp_trie::CompactMultiProof::decode(&mut &result.proof[..]).unwrap();
+	let state_version = executor::block_on(api.runtime_version(Some(block1_hash)))
+		.unwrap()
+		.state_version();
+	let results = sp_state_machine::read_proof_check_range::<BlakeTwo256, _>(
+		&[(root1, state_version), (root2, state_version), (root3, state_version)],
+		proof,
+		keys.iter().map(|key| &key.0),
+	)
+	.unwrap();
+	let values = results
+		.into_iter()
+		.map(|result| (result[&vec![1]].clone(), result[&vec![2]].clone()))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		values,
+		vec![(Some(vec![1]), None), (Some(vec![1]), Some(vec![2])), (Some(vec![3]), Some(vec![2]))],
+	);
+
+	let result = api.read_proof_range(keys.clone(), block3_hash, Some(block1_hash));
+	assert_matches!(executor::block_on(result), Err(Error::InvalidBlockRange { .. }));
+
+	// The number of blocks is limited, block #257 is 256 blocks after block #1.
+	let mut last = block3_hash;
+	for _ in 0..READ_PROOF_RANGE_MAX_BLOCKS - 2 {
+		last = add_block(3, 3).0;
+	}
+	let result = api.read_proof_range(keys.clone(), block1_hash, Some(last));
+	assert_matches!(executor::block_on(result), Err(Error::InvalidBlockRange { .. }));
+	let result = api.read_proof_range(keys, block2_hash, Some(last));
+	assert!(executor::block_on(result).is_ok());
+}
+
 #[test]
 fn should_return_runtime_version() {
 	let client = Arc::new(substrate_test_runtime_client::new());
@@ -23,6 +23,7 @@ use futures::{channel::oneshot, FutureExt};
 use sc_rpc_api::{DenyUnsafe, Receiver};
 use sc_tracing::logging;
 use sc_utils::mpsc::TracingUnboundedSender;
+use sp_core::Bytes;
 use sp_runtime::traits::{self, Header as HeaderT};
 
 pub use self::{
@@ -67,6 +68,8 @@ pub enum Request<B: traits::Block> {
 	NetworkAddReservedPeer(String, oneshot::Sender<Result<()>>),
 	/// Must return any potential parse error.
 	NetworkRemoveReservedPeer(String, oneshot::Sender<Result<()>>),
+	/// Must return the data with the given CID, or any potential parse or fetch error.
+	BitswapGet(String, oneshot::Sender<Result<Vec<u8>>>),
 	/// Must return the list of reserved peers
 	NetworkReservedPeers(oneshot::Sender<Vec<String>>),
 	/// Must return the node role.
@@ -178,6 +181,21 @@ impl<B: traits::Block> SystemApi<B::Hash, <B::Header as HeaderT>::Number> for Sy
 		.boxed()
 	}
 
+	fn system_bitswap_get(&self, cid: String) -> rpc::BoxFuture<rpc::Result<Bytes>> {
+		bail_if_unsafe!(self.deny_unsafe);
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::BitswapGet(cid, tx));
+		async move {
+			match rx.await {
+				Ok(Ok(data)) => Ok(data.into()),
+				Ok(Err(e)) => Err(rpc::Error::from(e)),
+				Err(_) => Err(rpc::Error::internal_error()),
+			}
+		}
+		.boxed()
+	}
+
 	fn system_reserved_peers(&self) -> Receiver<Vec<String>> {
 		let (tx, rx) = oneshot::channel();
 		let _ = self.send_back.unbounded_send(Request::NetworkReservedPeers(tx));
@@ -106,6 +106,12 @@ fn api<T: Into<Option<Status>>>(sync: T) -> System<Block> {
 							sender.send(Err(error::Error::MalformattedPeerArg(s.to_string()))),
 					};
 				},
+				Request::BitswapGet(cid, sender) => {
+					let _ = match cid.parse::<sc_network::bitswap::Cid>() {
+						Ok(_) => sender.send(Ok(vec![1, 2, 3])),
+						Err(e) => sender.send(Err(error::Error::MalformattedCid(e.to_string()))),
+					};
+				},
 				Request::NetworkReservedPeers(sender) => {
 					let _ = sender
 						.send(vec!["QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV".to_string()]);
@@ -292,6 +298,17 @@ fn system_network_remove_reserved() {
 	assert!(executor::block_on(bad_fut).is_err());
 }
 
+#[test]
+fn system_bitswap_get() {
+	let good_cid = "bafk2bzacebcsmtbgfa3lfhx2uvhvdosnqowvkq3zsuqbkl6ir6jbfsxd3bdmw";
+	let bad_cid = "QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV/1";
+
+	let good_fut = api(None).system_bitswap_get(good_cid.into());
+	let bad_fut = api(None).system_bitswap_get(bad_cid.into());
+	assert_eq!(executor::block_on(good_fut), Ok(vec![1, 2, 3].into()));
+	assert!(executor::block_on(bad_fut).is_err());
+}
+
 #[test]
 fn system_network_reserved_peers() {
 	assert_eq!(
@@ -30,7 +30,7 @@ use log::info;
 use prometheus_endpoint::Registry;
 use sc_chain_spec::get_extension;
 use sc_client_api::{
-	execution_extensions::ExecutionExtensions, proof_provider::ProofProvider, BadBlocks,
+	execution_extensions::ExecutionExtensions, proof_provider::ProofProvider, AuxStore, BadBlocks,
 	BlockBackend, BlockchainEvents, ExecutorProvider, ForkBlocks, StorageProvider, UsageProvider,
 };
 use sc_client_db::{Backend, DatabaseSettings};
@@ -263,10 +263,12 @@ where
 		let db_config = sc_client_db::DatabaseSettings {
 			state_cache_size: config.state_cache_size,
 			state_cache_child_ratio: config.state_cache_child_ratio.map(|v| (v, 100)),
+			trie_cache_size: config.trie_cache_size,
 			state_pruning: config.state_pruning.clone(),
 			source: config.database.clone(),
 			keep_blocks: config.keep_blocks.clone(),
 			transaction_storage: config.transaction_storage.clone(),
+			state_diff_index: config.state_diff_index,
 		};
 
 		let backend = new_db_backend(db_config)?;
@@ -729,7 +731,8 @@ pub struct BuildNetworkParams<'a, TBl: BlockT, TExPool, TImpQu, TCl> {
 	/// A block announce validator builder.
 	pub block_announce_validator_builder:
 		Option<Box<dyn FnOnce(Arc<TCl>) -> Box<dyn BlockAnnounceValidator<TBl> + Send> + Send>>,
-	/// An optional warp sync provider.
+	/// An optional warp sync provider, implemented by the finality gadget of the chain.
+	/// Required to use the warp sync mode.
 	pub warp_sync: Option<Arc<dyn WarpSyncProvider<TBl>>>,
 }
 
@@ -754,6 +757,7 @@ where
 		+ ProofProvider<TBl>
 		+ HeaderBackend<TBl>
 		+ BlockchainEvents<TBl>
+		+ AuxStore
 		+ 'static,
 	TExPool: MaintainedTransactionPool<Block = TBl, Hash = <TBl as BlockT>::Hash> + 'static,
 	TImpQu: ImportQueue<TBl> + 'static,
@@ -768,6 +772,13 @@ where
 		warp_sync,
 	} = params;
 
+	if !matches!(config.role, Role::Light) &&
+		matches!(config.network.sync_mode, SyncMode::Warp) &&
+		warp_sync.is_none()
+	{
+		return Err(Error::WarpSyncProviderRequired)
+	}
+
 	let transaction_pool_adapter = Arc::new(TransactionPoolAdapter {
 		imports_external_transactions: !matches!(config.role, Role::Light),
 		pool: transaction_pool,
@@ -0,0 +1,54 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::Error;
+use log::{info, warn};
+use sc_client_db::Backend;
+use sp_runtime::traits::Block as BlockT;
+use std::sync::Arc;
+
+/// Check the database for inconsistencies, optionally repairing the leaves and children
+/// indexes.
+///
+/// Returns an error if inconsistencies remain after the check.
+pub fn check_db<B: BlockT>(backend: Arc<Backend<B>>, repair: bool) -> Result<(), Error> {
+	info!("Checking database consistency...");
+	let report = backend.check_consistency(repair)?;
+
+	for inconsistency in &report.inconsistencies {
+		warn!("{}", inconsistency);
+	}
+	info!(
+		"Checked {} canonical blocks, {} indexed blocks and {} state keys",
+		report.canonical_blocks, report.indexed_blocks, report.state_keys,
+	);
+
+	let remaining = report
+		.inconsistencies
+		.iter()
+		.filter(|inconsistency| !(report.repaired && inconsistency.is_repairable()))
+		.count();
+	if report.repaired {
+		info!("Rebuilt the leaves and children indexes");
+	}
+	if remaining > 0 {
+		return Err(format!("Found {} database inconsistencies", remaining).into())
+	}
+	info!("Database is consistent");
+	Ok(())
+}
@@ -0,0 +1,32 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::Error;
+use sc_client_db::DatabaseSource;
+use sp_runtime::traits::Block as BlockT;
+
+/// Copy the database at `source` to the empty database at `target`, which may use another
+/// backend.
+///
+/// The best and finalized blocks of both databases are compared once the copy is done.
+pub fn convert_db<B: BlockT>(
+	source: &DatabaseSource,
+	target: &DatabaseSource,
+) -> Result<(), Error> {
+	sc_client_db::convert_database::<B>(source, target).map_err(Into::into)
+}
@@ -0,0 +1,403 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! State snapshots.
+//!
+//! A snapshot is a SCALE encoded stream made of a [`SnapshotHeader`] followed by a sequence of
+//! chunks, terminated by an empty chunk. Every chunk is a (possibly compressed) compact range
+//! proof of the state at the snapshot block, so each one can be checked against the state root
+//! of the snapshot header as soon as it is read.
+
+use crate::error::Error;
+use codec::{Decode, Encode, IoReader};
+use log::info;
+use sc_client_api::{
+	Backend, BlockBackend, CompactProof, HeaderBackend, ProofProvider, UsageProvider,
+};
+use sc_consensus::{BlockImport, BlockImportParams, ImportResult, StateAction, StorageChanges};
+use smallvec::SmallVec;
+use sp_consensus::BlockOrigin;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, HashFor, Header as HeaderT, Zero},
+	Justifications,
+};
+use sp_state_machine::Layout;
+use sp_trie::PrefixedMemoryDB;
+use std::{
+	collections::{HashMap, HashSet},
+	io::{Read, Write},
+	sync::Arc,
+};
+
+/// Bytes every snapshot starts with.
+const SNAPSHOT_MAGIC: &[u8; 4] = b"snap";
+
+/// Version of the snapshot format.
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Maximum size of a decompressed chunk.
+const CHUNK_BOMB_LIMIT: usize = 256 * 1024 * 1024;
+
+/// Description of the block a snapshot was taken at.
+#[derive(Debug, Encode, Decode)]
+pub struct SnapshotHeader<B: BlockT> {
+	/// Version of the snapshot format.
+	pub version: u32,
+	/// Genesis hash of the chain the snapshot belongs to.
+	pub genesis_hash: B::Hash,
+	/// Header of the snapshot block.
+	pub header: B::Header,
+	/// Justifications of the snapshot block, if any.
+	pub justifications: Option<Justifications>,
+}
+
+/// Export a snapshot of the state at the given finalized `block` into `output`.
+///
+/// If `block` is `None`, the last finalized block is used. `chunk_size` is the approximate
+/// size in bytes of the proof stored in every chunk.
+pub fn export_snapshot<B, C>(
+	client: Arc<C>,
+	block: Option<BlockId<B>>,
+	mut output: impl Write,
+	chunk_size: usize,
+) -> Result<(), Error>
+where
+	C: UsageProvider<B> + HeaderBackend<B> + BlockBackend<B> + ProofProvider<B>,
+	B: BlockT,
+{
+	let info = client.usage_info().chain;
+	let block = block.unwrap_or(BlockId::Hash(info.finalized_hash));
+	let header = client.header(block)?.ok_or_else(|| format!("Unknown block {}", block))?;
+	let hash = header.hash();
+
+	if *header.number() > info.finalized_number || client.hash(*header.number())? != Some(hash) {
+		return Err(format!("Block {} is not finalized", hash).into())
+	}
+
+	let id = BlockId::Hash(hash);
+	let snapshot_header = SnapshotHeader::<B> {
+		version: SNAPSHOT_VERSION,
+		genesis_hash: info.genesis_hash,
+		justifications: client.justifications(&id)?,
+		header,
+	};
+	let state_root = *snapshot_header.header.state_root();
+
+	info!("Exporting state snapshot at #{} ({})", snapshot_header.header.number(), hash);
+
+	output.write_all(SNAPSHOT_MAGIC)?;
+	output.write_all(&snapshot_header.encode())?;
+
+	let mut last_key = SmallVec::<[Vec<u8>; 2]>::new();
+	let mut chunks = 0u64;
+	let mut keys = 0u64;
+	loop {
+		let (proof, _) = client.read_proof_collection(&id, last_key.as_slice(), chunk_size)?;
+		let encoded = proof.encode();
+		// Verifying our own proof is the simplest way to know where the next chunk starts.
+		let (values, completed) =
+			client.verify_range_proof(state_root, proof, last_key.as_slice())?;
+
+		let chunk =
+			sp_maybe_compressed_blob::compress(&encoded, CHUNK_BOMB_LIMIT).unwrap_or(encoded);
+		output.write_all(&chunk.encode())?;
+
+		chunks += 1;
+		keys += values.len() as u64;
+		if chunks % 100 == 0 {
+			info!("#{} chunks, {} keys exported", chunks, keys);
+		}
+
+		if completed == 0 {
+			break
+		}
+		if !values.update_last_key(completed, &mut last_key) {
+			return Err(format!("Error updating key cursor, depth: {}", completed).into())
+		}
+	}
+	// An empty chunk marks the end of the snapshot.
+	output.write_all(&Vec::<u8>::new().encode())?;
+	output.flush()?;
+
+	info!("Exported {} chunks, {} keys", chunks, keys);
+	Ok(())
+}
+
+/// Import a snapshot read from `input`, taken at the block `trusted_hash`.
+///
+/// The snapshot block must be known beforehand to be part of the chain, which is checked against
+/// `trusted_hash`: its justifications can't be checked without the authority sets leading to it.
+/// Every chunk is checked against the state root of the snapshot block and written to `backend`
+/// as soon as it is read, before the block is imported as finalized with `block_import`. This
+/// leaves a gap in the block history that can be filled by syncing.
+///
+/// Snapshots can only be imported into a database that holds nothing but the genesis block.
+pub async fn import_snapshot<B, C, BE, BI>(
+	client: Arc<C>,
+	backend: Arc<BE>,
+	mut block_import: BI,
+	input: impl Read,
+	trusted_hash: B::Hash,
+) -> Result<(), Error>
+where
+	C: UsageProvider<B> + ProofProvider<B>,
+	B: BlockT,
+	BE: Backend<B>,
+	BI: BlockImport<B, Error = sp_consensus::Error>,
+{
+	let mut input = IoReader(input);
+
+	let info = client.usage_info().chain;
+	if !info.best_number.is_zero() {
+		return Err("Snapshots can only be imported into an empty database".into())
+	}
+
+	let mut magic = [0u8; 4];
+	input.0.read_exact(&mut magic)?;
+	if &magic != SNAPSHOT_MAGIC {
+		return Err("Input is not a state snapshot".into())
+	}
+
+	let snapshot_header = SnapshotHeader::<B>::decode(&mut input)
+		.map_err(|e| format!("Error decoding snapshot header: {}", e))?;
+	if snapshot_header.version != SNAPSHOT_VERSION {
+		return Err(format!("Unsupported snapshot version {}", snapshot_header.version).into())
+	}
+	if snapshot_header.genesis_hash != info.genesis_hash {
+		return Err(format!(
+			"Snapshot genesis {} does not match the chain genesis {}",
+			snapshot_header.genesis_hash, info.genesis_hash,
+		)
+		.into())
+	}
+
+	let header = snapshot_header.header;
+	let hash = header.hash();
+	if hash != trusted_hash {
+		return Err(
+			format!("Snapshot block {} is not the trusted block {}", hash, trusted_hash).into()
+		)
+	}
+	let state_root = *header.state_root();
+
+	info!("Importing state snapshot at #{} ({})", header.number(), hash);
+
+	let mut last_key = SmallVec::<[Vec<u8>; 2]>::new();
+	// Nodes on the boundary of two chunks are in both proofs. They are only written once, so
+	// that backends counting references don't count them twice.
+	let mut previous_nodes = HashSet::new();
+	let mut complete = false;
+	let mut chunks = 0u64;
+	let mut keys = 0u64;
+	loop {
+		let chunk = Vec::<u8>::decode(&mut input)
+			.map_err(|e| format!("Error decoding chunk #{}: {}", chunks, e))?;
+		if chunk.is_empty() {
+			break
+		}
+		if complete {
+			return Err("Unexpected chunk after the end of the state".into())
+		}
+
+		let chunk = sp_maybe_compressed_blob::decompress(&chunk, CHUNK_BOMB_LIMIT)
+			.map_err(|e| format!("Error decompressing chunk #{}: {}", chunks, e))?;
+		let proof = CompactProof::decode(&mut chunk.as_ref())
+			.map_err(|e| format!("Error decoding chunk #{}: {}", chunks, e))?;
+		let mut nodes = PrefixedMemoryDB::<HashFor<B>>::default();
+		sp_trie::decode_compact_with_key_spaces::<Layout<HashFor<B>>, _, _>(
+			&mut nodes,
+			proof.iter_compact_encoded_nodes(),
+			Some(&state_root),
+		)
+		.map_err(|e| format!("Chunk #{} failed proof verification: {}", chunks, e))?;
+		let (values, completed) = client
+			.verify_range_proof(state_root, proof, last_key.as_slice())
+			.map_err(|e| format!("Chunk #{} failed proof verification: {}", chunks, e))?;
+
+		let nodes: Vec<_> = nodes
+			.drain()
+			.into_iter()
+			.filter(|(_, (_, rc))| *rc > 0)
+			.map(|(key, (value, _))| (key, value))
+			.collect();
+		let new_nodes =
+			nodes.iter().filter(|(key, _)| !previous_nodes.contains(key)).cloned().collect();
+		backend.write_state_nodes(new_nodes)?;
+		previous_nodes = nodes.into_iter().map(|(key, _)| key).collect();
+
+		chunks += 1;
+		keys += values.len() as u64;
+		if chunks % 100 == 0 {
+			info!("#{} chunks, {} keys imported", chunks, keys);
+		}
+
+		complete = completed == 0;
+		if !complete && !values.update_last_key(completed, &mut last_key) {
+			return Err(format!("Error updating key cursor, depth: {}", completed).into())
+		}
+	}
+
+	if !complete {
+		return Err("Snapshot ended before the state was complete".into())
+	}
+
+	info!("Imported {} chunks, {} keys. Importing block", chunks, keys);
+
+	let mut import_block = BlockImportParams::new(BlockOrigin::File, header);
+	import_block.justifications = snapshot_header.justifications;
+	import_block.finalized = true;
+	import_block.fork_choice = Some(sc_consensus::ForkChoiceStrategy::Custom(true));
+	import_block.state_action = StateAction::ApplyChanges(StorageChanges::Written);
+
+	match block_import.import_block(import_block, HashMap::new()).await? {
+		ImportResult::Imported(_) => {
+			info!("Imported state snapshot at {}", hash);
+			Ok(())
+		},
+		ImportResult::AlreadyInChain => Err(format!("Block {} is already in chain", hash).into()),
+		r => Err(format!("Error importing snapshot block {}: {:?}", hash, r).into()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::block_on;
+	use sc_block_builder::BlockBuilderProvider;
+	use sc_client_api::StorageProvider;
+	use sp_core::storage::{well_known_keys::CODE, ChildInfo, StorageData, StorageKey};
+	use substrate_test_runtime_client::{prelude::*, runtime::Block};
+
+	type Hash = <Block as BlockT>::Hash;
+
+	fn child_info() -> ChildInfo {
+		ChildInfo::new_default(b"child")
+	}
+
+	fn builder() -> TestClientBuilder<ExecutorDispatch, substrate_test_runtime_client::Backend> {
+		TestClientBuilder::new().add_child_storage(&child_info(), b"key", b"value")
+	}
+
+	/// Build a few finalized blocks and export a snapshot of the last one in small chunks.
+	fn export() -> (Hash, Vec<u8>) {
+		let mut client = Arc::new(builder().build());
+		for _ in 0..3 {
+			let block = client.new_block(Default::default()).unwrap().build().unwrap().block;
+			block_on(client.import(BlockOrigin::Own, block)).unwrap();
+		}
+		let hash = client.chain_info().best_hash;
+		client.finalize_block(BlockId::Hash(hash), None).unwrap();
+
+		let mut snapshot = Vec::new();
+		export_snapshot(client, None, &mut snapshot, 1024).unwrap();
+		(hash, snapshot)
+	}
+
+	fn import(snapshot: &[u8], trusted_hash: Hash) -> Result<Arc<TestClient>, Error> {
+		let builder = builder();
+		let backend = builder.backend();
+		let client = Arc::new(builder.build());
+		block_on(import_snapshot(client.clone(), backend, client.clone(), snapshot, trusted_hash))?;
+		Ok(client)
+	}
+
+	/// Split a snapshot into its magic and header, and its chunks.
+	fn split(snapshot: &[u8]) -> (Vec<u8>, Vec<Vec<u8>>) {
+		let mut input = &snapshot[SNAPSHOT_MAGIC.len()..];
+		SnapshotHeader::<Block>::decode(&mut input).unwrap();
+		let header = snapshot[..snapshot.len() - input.len()].to_vec();
+		let mut chunks = Vec::new();
+		loop {
+			let chunk = Vec::<u8>::decode(&mut input).unwrap();
+			if chunk.is_empty() {
+				break
+			}
+			chunks.push(chunk);
+		}
+		(header, chunks)
+	}
+
+	fn join(header: &[u8], chunks: &[Vec<u8>]) -> Vec<u8> {
+		let mut snapshot = header.to_vec();
+		chunks.iter().for_each(|chunk| chunk.encode_to(&mut snapshot));
+		Vec::<u8>::new().encode_to(&mut snapshot);
+		snapshot
+	}
+
+	#[test]
+	fn export_import_round_trip() {
+		let (hash, snapshot) = export();
+		assert!(split(&snapshot).1.len() > 1);
+
+		let client = import(&snapshot, hash).unwrap();
+
+		let info = client.chain_info();
+		assert_eq!(info.best_hash, hash);
+		assert_eq!(info.finalized_hash, hash);
+		let id = BlockId::Hash(hash);
+		assert!(client.storage(&id, &StorageKey(CODE.to_vec())).unwrap().is_some());
+		assert_eq!(
+			client.child_storage(&id, &child_info(), &StorageKey(b"key".to_vec())).unwrap(),
+			Some(StorageData(b"value".to_vec())),
+		);
+	}
+
+	#[test]
+	fn corrupted_chunk_is_rejected() {
+		let (hash, snapshot) = export();
+		let (header, mut chunks) = split(&snapshot);
+
+		let chunk = sp_maybe_compressed_blob::decompress(&chunks[1], CHUNK_BOMB_LIMIT).unwrap();
+		let mut nodes = CompactProof::decode(&mut chunk.as_ref()).unwrap().encoded_nodes;
+		let node = nodes.last_mut().unwrap();
+		*node.last_mut().unwrap() ^= 1;
+		chunks[1] = CompactProof { encoded_nodes: nodes }.encode();
+
+		assert!(import(&join(&header, &chunks), hash).is_err());
+	}
+
+	#[test]
+	fn truncated_snapshot_is_rejected() {
+		let (hash, snapshot) = export();
+		let (header, mut chunks) = split(&snapshot);
+
+		assert!(import(&snapshot[..snapshot.len() - 1], hash).is_err());
+
+		chunks.pop();
+		assert!(import(&join(&header, &chunks), hash).is_err());
+	}
+
+	#[test]
+	fn wrong_genesis_is_rejected() {
+		let (hash, snapshot) = export();
+
+		let builder = TestClientBuilder::new();
+		let backend = builder.backend();
+		let client = Arc::new(builder.build());
+		assert!(block_on(import_snapshot(client.clone(), backend, client, &snapshot[..], hash))
+			.is_err());
+	}
+
+	#[test]
+	fn untrusted_block_is_rejected() {
+		let (_, snapshot) = export();
+
+		assert!(import(&snapshot, Default::default()).is_err());
+	}
+}
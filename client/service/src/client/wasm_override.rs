@@ -49,18 +49,18 @@ use std::{
 
 #[derive(Clone, Debug, PartialEq)]
 /// Auxiliary structure that holds a wasm blob and its hash.
-struct WasmBlob {
+pub(crate) struct WasmBlob {
 	code: Vec<u8>,
 	hash: Vec<u8>,
 }
 
 impl WasmBlob {
-	fn new(code: Vec<u8>) -> Self {
+	pub(crate) fn new(code: Vec<u8>) -> Self {
 		let hash = make_hash(&code);
 		Self { code, hash }
 	}
 
-	fn runtime_code(&self, heap_pages: Option<u64>) -> RuntimeCode {
+	pub(crate) fn runtime_code(&self, heap_pages: Option<u64>) -> RuntimeCode {
 		RuntimeCode { code_fetcher: self, hash: self.hash.clone(), heap_pages }
 	}
 }
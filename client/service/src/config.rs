@@ -63,16 +63,20 @@ pub struct Configuration {
 	pub keystore_remote: Option<String>,
 	/// Configuration for the database.
 	pub database: DatabaseSource,
-	/// Size of internal state cache in Bytes
+	/// Size of internal state cache in Bytes. Deprecated and ignored, use `trie_cache_size`.
 	pub state_cache_size: usize,
-	/// Size in percent of cache size dedicated to child tries
+	/// Size in percent of cache size dedicated to child tries. Deprecated and ignored.
 	pub state_cache_child_ratio: Option<usize>,
+	/// Size of the trie node cache in Bytes, shared by all the forks
+	pub trie_cache_size: usize,
 	/// State pruning settings.
 	pub state_pruning: PruningMode,
 	/// Number of blocks to keep in the db.
 	pub keep_blocks: KeepBlocks,
 	/// Transaction storage scheme.
 	pub transaction_storage: TransactionStorageMode,
+	/// Record the keys written by each imported block.
+	pub state_diff_index: bool,
 	/// Chain configuration.
 	pub chain_spec: Box<dyn ChainSpec>,
 	/// Wasm execution method.
@@ -55,6 +55,9 @@ pub enum Error {
 	#[error("Tasks executor hasn't been provided.")]
 	TaskExecutorRequired,
 
+	#[error("Warp sync provider is required to use the warp sync mode.")]
+	WarpSyncProviderRequired,
+
 	#[error("Prometheus metrics error")]
 	Prometheus(#[from] prometheus_endpoint::PrometheusError),
 
@@ -37,7 +37,10 @@ mod task_manager;
 use std::{collections::HashMap, io, net::SocketAddr, pin::Pin, task::Poll};
 
 use codec::{Decode, Encode};
-use futures::{stream, Future, FutureExt, Stream, StreamExt};
+use futures::{
+	stream::{self, FuturesUnordered},
+	Future, FutureExt, Stream, StreamExt,
+};
 use log::{debug, error, warn};
 use sc_network::PeerId;
 use sc_utils::mpsc::TracingUnboundedReceiver;
@@ -173,6 +176,9 @@ async fn build_network_future<
 		.fuse()
 	};
 
+	// Bitswap requests started by the RPC layer.
+	let mut bitswap_requests = FuturesUnordered::new();
+
 	loop {
 		futures::select! {
 			// List of blocks that the client has imported.
@@ -253,6 +259,24 @@ async fn build_network_future<
 							))),
 						};
 					}
+					sc_rpc::system::Request::BitswapGet(cid, sender) => {
+						let cid = match cid.parse::<sc_network::bitswap::Cid>() {
+							Ok(cid) => cid,
+							Err(e) => {
+								let _ = sender.send(Err(
+									sc_rpc::system::error::Error::MalformattedCid(e.to_string()),
+								));
+								continue
+							},
+						};
+						let service = network.service().clone();
+						bitswap_requests.push(async move {
+							let result = service.bitswap_get(cid).await.map_err(|e| {
+								sc_rpc::system::error::Error::BitswapFailed(e.to_string())
+							});
+							let _ = sender.send(result);
+						});
+					}
 					sc_rpc::system::Request::NetworkReservedPeers(sender) => {
 						let reserved_peers = network.reserved_peers();
 						let reserved_peers = reserved_peers
@@ -284,6 +308,9 @@ async fn build_network_future<
 				}
 			}
 
+			// A Bitswap request started by the RPC layer has completed.
+			_ = bitswap_requests.select_next_some() => {}
+
 			// The network worker has done something. Nothing special to do, but could be
 			// used in the future to perform actions in response of things that happened on
 			// the network.
@@ -20,7 +20,9 @@ use std::{convert::TryFrom, time::SystemTime};
 
 use crate::config::Configuration;
 use futures_timer::Delay;
-use prometheus_endpoint::{register, Gauge, GaugeVec, Opts, PrometheusError, Registry, U64};
+use prometheus_endpoint::{
+	register, CounterVec, Gauge, GaugeVec, Opts, PrometheusError, Registry, U64,
+};
 use sc_client_api::{ClientInfo, UsageProvider};
 use sc_network::{config::Role, NetworkService, NetworkStatus};
 use sc_telemetry::{telemetry, TelemetryHandle, SUBSTRATE_INFO};
@@ -41,7 +43,8 @@ struct PrometheusMetrics {
 
 	// I/O
 	database_cache: Gauge<U64>,
-	state_cache: Gauge<U64>,
+	trie_cache: Gauge<U64>,
+	trie_cache_reads: CounterVec<U64>,
 	state_db: GaugeVec<U64>,
 }
 
@@ -109,8 +112,15 @@ impl PrometheusMetrics {
 				Gauge::new("database_cache_bytes", "RocksDB cache size in bytes")?,
 				registry,
 			)?,
-			state_cache: register(
-				Gauge::new("state_cache_bytes", "State cache size in bytes")?,
+			trie_cache: register(
+				Gauge::new("trie_cache_bytes", "Trie node cache size in bytes")?,
+				registry,
+			)?,
+			trie_cache_reads: register(
+				CounterVec::new(
+					Opts::new("trie_cache_reads_total", "Trie node reads from the trie node cache"),
+					&["result"],
+				)?,
 				registry,
 			)?,
 			state_db: register(
@@ -232,7 +242,7 @@ impl MetricsService {
 			"finalized_height" => finalized_number,
 			"finalized_hash" => ?info.chain.finalized_hash,
 			"used_state_cache_size" => info.usage.as_ref()
-				.map(|usage| usage.memory.state_cache.as_bytes())
+				.map(|usage| usage.memory.trie_cache.as_bytes())
 				.unwrap_or(0),
 		);
 
@@ -248,7 +258,14 @@ impl MetricsService {
 
 			if let Some(info) = info.usage.as_ref() {
 				metrics.database_cache.set(info.memory.database_cache.as_bytes() as u64);
-				metrics.state_cache.set(info.memory.state_cache.as_bytes() as u64);
+				metrics.trie_cache.set(info.memory.trie_cache.as_bytes() as u64);
+
+				for (result, total) in
+					[("hit", info.io.trie_cache_hits), ("miss", info.io.trie_cache_misses)]
+				{
+					let counter = metrics.trie_cache_reads.with_label_values(&[result]);
+					counter.inc_by(total.saturating_sub(counter.get()));
+				}
 
 				metrics
 					.state_db
@@ -51,7 +51,7 @@ use log::trace;
 use noncanonical::NonCanonicalOverlay;
 use parity_util_mem::{malloc_size, MallocSizeOf};
 use parking_lot::RwLock;
-use pruning::RefWindow;
+use pruning::{HaveBlock, RefWindow};
 use sc_client_api::{MemorySize, StateDbMemoryInfo};
 use std::{
 	collections::{hash_map::Entry, HashMap},
@@ -130,6 +130,22 @@ pub enum Error<E: fmt::Debug> {
 	InvalidPruningMode(String),
 	/// Too many unfinalized sibling blocks inserted.
 	TooManySiblingBlocks,
+	/// The pruning journal record of the given block is missing from the database.
+	MissingJournalRecord(u64),
+	/// The pruning journal was written without its index, see [`index_journals`].
+	UnindexedJournal,
+}
+
+/// Pruning status of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsPruned {
+	/// The state of the block is pruned.
+	Pruned,
+	/// The state of the block is available.
+	NotPruned,
+	/// The block is in the part of the pruning window that is not kept in memory. The caller
+	/// should check whether the state is still in the database.
+	MaybePruned,
 }
 
 /// Pinning error type.
@@ -154,6 +170,8 @@ impl<E: fmt::Debug> fmt::Debug for Error<E> {
 			Error::InvalidParent => write!(f, "Trying to insert block with unknown parent"),
 			Error::InvalidPruningMode(e) => write!(f, "Expected pruning mode: {}", e),
 			Error::TooManySiblingBlocks => write!(f, "Too many sibling blocks inserted"),
+			Error::MissingJournalRecord(n) => write!(f, "Missing pruning journal record #{}", n),
+			Error::UnindexedJournal => write!(f, "The pruning journal is not indexed"),
 		}
 	}
 }
@@ -254,7 +272,8 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 		let non_canonical: NonCanonicalOverlay<BlockHash, Key> = NonCanonicalOverlay::new(db)?;
 		let pruning: Option<RefWindow<BlockHash, Key>> = match mode {
 			PruningMode::Constrained(Constraints { max_mem: Some(_), .. }) => unimplemented!(),
-			PruningMode::Constrained(_) => Some(RefWindow::new(db, ref_counting)?),
+			PruningMode::Constrained(_) =>
+				Some(RefWindow::new(db, ref_counting, pruning::DEFAULT_PAGE_SIZE)?),
 			PruningMode::ArchiveAll | PruningMode::ArchiveCanonical => None,
 		};
 
@@ -303,10 +322,11 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 		}
 	}
 
-	fn canonicalize_block<E: fmt::Debug>(
+	fn canonicalize_block<D: MetaDb>(
 		&mut self,
 		hash: &BlockHash,
-	) -> Result<CommitSet<Key>, Error<E>> {
+		db: &D,
+	) -> Result<CommitSet<Key>, Error<D::Error>> {
 		let mut commit = CommitSet::default();
 		if self.mode == PruningMode::ArchiveAll {
 			return Ok(commit)
@@ -319,32 +339,50 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 			Err(e) => return Err(e),
 		};
 		if let Some(ref mut pruning) = self.pruning {
-			pruning.note_canonical(&hash, &mut commit);
+			pruning.note_canonical(hash, &mut commit, db)?;
 		}
-		self.prune(&mut commit);
+		self.prune(&mut commit, db)?;
 		Ok(commit)
 	}
 
+	fn non_canonical_blocks(&self) -> Vec<(BlockHash, u64, BlockHash)> {
+		self.non_canonical.blocks()
+	}
+
 	fn best_canonical(&self) -> Option<u64> {
 		return self.non_canonical.last_canonicalized_block_number()
 	}
 
-	fn is_pruned(&self, hash: &BlockHash, number: u64) -> bool {
+	fn is_pruned(&self, hash: &BlockHash, number: u64) -> IsPruned {
 		match self.mode {
-			PruningMode::ArchiveAll => false,
+			PruningMode::ArchiveAll => IsPruned::NotPruned,
 			PruningMode::ArchiveCanonical | PruningMode::Constrained(_) => {
 				if self.best_canonical().map(|c| number > c).unwrap_or(true) {
-					!self.non_canonical.have_block(hash)
+					if self.non_canonical.have_block(hash) {
+						IsPruned::NotPruned
+					} else {
+						IsPruned::Pruned
+					}
 				} else {
-					self.pruning.as_ref().map_or(false, |pruning| {
-						number < pruning.pending() || !pruning.have_block(hash)
-					})
+					match self.pruning.as_ref() {
+						None => IsPruned::NotPruned,
+						Some(pruning) if number < pruning.pending() => IsPruned::Pruned,
+						Some(pruning) => match pruning.have_block(hash) {
+							HaveBlock::Yes => IsPruned::NotPruned,
+							HaveBlock::No => IsPruned::Pruned,
+							HaveBlock::Maybe => IsPruned::MaybePruned,
+						},
+					}
 				}
 			},
 		}
 	}
 
-	fn prune(&mut self, commit: &mut CommitSet<Key>) {
+	fn prune<D: MetaDb>(
+		&mut self,
+		commit: &mut CommitSet<Key>,
+		db: &D,
+	) -> Result<(), Error<D::Error>> {
 		if let (&mut Some(ref mut pruning), &PruningMode::Constrained(ref constraints)) =
 			(&mut self.pruning, &self.mode)
 		{
@@ -358,12 +396,13 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 				}
 
 				let pinned = &self.pinned;
-				if pruning.next_hash().map_or(false, |h| pinned.contains_key(&h)) {
+				if pruning.next_hash(db)?.map_or(false, |h| pinned.contains_key(&h)) {
 					break
 				}
-				pruning.prune_one(commit);
+				pruning.prune_one(commit, db)?;
 			}
 		}
+		Ok(())
 	}
 
 	/// Revert all non-canonical blocks with the best block number.
@@ -385,13 +424,22 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 		}
 	}
 
-	fn pin(&mut self, hash: &BlockHash) -> Result<(), PinError> {
+	fn pin<F>(&mut self, hash: &BlockHash, hint: F) -> Result<(), PinError>
+	where
+		F: Fn() -> bool,
+	{
 		match self.mode {
 			PruningMode::ArchiveAll => Ok(()),
 			PruningMode::ArchiveCanonical | PruningMode::Constrained(_) => {
-				if self.non_canonical.have_block(hash) ||
-					self.pruning.as_ref().map_or(false, |pruning| pruning.have_block(hash))
-				{
+				let have_block = self.non_canonical.have_block(hash) ||
+					self.pruning.as_ref().map_or(false, |pruning| {
+						match pruning.have_block(hash) {
+							HaveBlock::Yes => true,
+							HaveBlock::No => false,
+							HaveBlock::Maybe => hint(),
+						}
+					});
+				if have_block {
 					let refs = self.pinned.entry(hash.clone()).or_default();
 					if *refs == 0 {
 						trace!(target: "state-db-pin", "Pinned block: {:?}", hash);
@@ -445,8 +493,7 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDbSync<Block
 		}
 		trace!(
 			target: "forks",
-			"First available: {:?} ({}), Last canon: {:?} ({}), Best forks: {:?}",
-			self.pruning.as_ref().and_then(|p| p.next_hash()),
+			"First available: {}, Last canon: {:?} ({}), Best forks: {:?}",
 			self.pruning.as_ref().map(|p| p.pending()).unwrap_or(0),
 			self.non_canonical.last_canonicalized_hash(),
 			self.non_canonical.last_canonicalized_block_number().unwrap_or(0),
@@ -498,16 +545,25 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDb<BlockHash
 	}
 
 	/// Finalize a previously inserted block.
-	pub fn canonicalize_block<E: fmt::Debug>(
+	///
+	/// The database is used to read the pruning journal when it isn't kept in memory.
+	pub fn canonicalize_block<D: MetaDb>(
 		&self,
 		hash: &BlockHash,
-	) -> Result<CommitSet<Key>, Error<E>> {
-		self.db.write().canonicalize_block(hash)
+		db: &D,
+	) -> Result<CommitSet<Key>, Error<D::Error>> {
+		self.db.write().canonicalize_block(hash, db)
 	}
 
 	/// Prevents pruning of specified block and its descendants.
-	pub fn pin(&self, hash: &BlockHash) -> Result<(), PinError> {
-		self.db.write().pin(hash)
+	///
+	/// `hint` is called to check whether the state of the block is still in the database, when it
+	/// can't be decided from the part of the pruning window kept in memory.
+	pub fn pin<F>(&self, hash: &BlockHash, hint: F) -> Result<(), PinError>
+	where
+		F: Fn() -> bool,
+	{
+		self.db.write().pin(hash, hint)
 	}
 
 	/// Allows pruning of specified block.
@@ -542,13 +598,18 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDb<BlockHash
 		self.db.write().remove(hash)
 	}
 
+	/// Returns hash, number and parent hash of every block that is not canonicalized yet.
+	pub fn non_canonical_blocks(&self) -> Vec<(BlockHash, u64, BlockHash)> {
+		self.db.read().non_canonical_blocks()
+	}
+
 	/// Returns last finalized block number.
 	pub fn best_canonical(&self) -> Option<u64> {
 		return self.db.read().best_canonical()
 	}
 
 	/// Check if block is pruned away.
-	pub fn is_pruned(&self, hash: &BlockHash, number: u64) -> bool {
+	pub fn is_pruned(&self, hash: &BlockHash, number: u64) -> IsPruned {
 		return self.db.read().is_pruned(hash, number)
 	}
 
@@ -568,11 +629,40 @@ impl<BlockHash: Hash + MallocSizeOf, Key: Hash + MallocSizeOf> StateDb<BlockHash
 	}
 }
 
+/// Index the pruning journal of a state database opened with `ref_counting`, if it was written by
+/// a version that didn't index it or converted from a backend that counts references itself.
+///
+/// Returns the changes to write to the database before opening it, which are empty if the
+/// journal is already indexed.
+pub fn index_journals<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+) -> Result<ChangeSet<Vec<u8>>, Error<D::Error>> {
+	let mut meta = ChangeSet::default();
+	pruning::index_journal::<BlockHash, Key, D>(db, &mut meta)?;
+	Ok(meta)
+}
+
+/// Rewrite the journals of a state database opened with `ref_counting`, so that it can be opened
+/// without it once the nodes are moved to a backend that counts references itself.
+///
+/// `map_key` is applied to every node key, e.g. to remove the key prefix. Returns the journal
+/// records to write to the new database.
+pub fn convert_journals<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+	map_key: impl Fn(Key) -> Key,
+) -> Result<ChangeSet<Vec<u8>>, Error<D::Error>> {
+	let mut meta = ChangeSet::default();
+	noncanonical::convert_journal::<BlockHash, Key, D>(db, &map_key, &mut meta)?;
+	pruning::convert_journal::<BlockHash, Key, D>(db, &map_key, &mut meta)?;
+	Ok(meta)
+}
+
 #[cfg(test)]
 mod tests {
 	use crate::{
+		convert_journals,
 		test::{make_changeset, make_db, TestDb},
-		Constraints, PruningMode, StateDb,
+		Constraints, IsPruned, PruningMode, StateDb,
 	};
 	use sp_core::H256;
 	use std::io;
@@ -622,7 +712,7 @@ mod tests {
 				.unwrap(),
 		);
 		state_db.apply_pending();
-		db.commit(&state_db.canonicalize_block::<io::Error>(&H256::from_low_u64_be(1)).unwrap());
+		db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(1), &db).unwrap());
 		state_db.apply_pending();
 		db.commit(
 			&state_db
@@ -635,9 +725,9 @@ mod tests {
 				.unwrap(),
 		);
 		state_db.apply_pending();
-		db.commit(&state_db.canonicalize_block::<io::Error>(&H256::from_low_u64_be(21)).unwrap());
+		db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(21), &db).unwrap());
 		state_db.apply_pending();
-		db.commit(&state_db.canonicalize_block::<io::Error>(&H256::from_low_u64_be(3)).unwrap());
+		db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(3), &db).unwrap());
 		state_db.apply_pending();
 
 		(db, state_db)
@@ -647,7 +737,7 @@ mod tests {
 	fn full_archive_keeps_everything() {
 		let (db, sdb) = make_test_db(PruningMode::ArchiveAll);
 		assert!(db.data_eq(&make_db(&[1, 21, 22, 3, 4, 91, 921, 922, 93, 94])));
-		assert!(!sdb.is_pruned(&H256::from_low_u64_be(0), 0));
+		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(0), 0), IsPruned::NotPruned);
 	}
 
 	#[test]
@@ -671,10 +761,10 @@ mod tests {
 			max_blocks: Some(1),
 			max_mem: None,
 		}));
-		assert!(sdb.is_pruned(&H256::from_low_u64_be(0), 0));
-		assert!(sdb.is_pruned(&H256::from_low_u64_be(1), 1));
-		assert!(sdb.is_pruned(&H256::from_low_u64_be(21), 2));
-		assert!(sdb.is_pruned(&H256::from_low_u64_be(22), 2));
+		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(0), 0), IsPruned::Pruned);
+		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(1), 1), IsPruned::Pruned);
+		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(21), 2), IsPruned::Pruned);
+		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(22), 2), IsPruned::Pruned);
 		assert!(db.data_eq(&make_db(&[21, 3, 922, 93, 94])));
 	}
 
@@ -684,10 +774,10 @@ mod tests {
 			max_blocks: Some(2),
 			max_mem: None,
 		}));
-		assert!(sdb.is_pruned(&H256::from_low_u64_be(0), 0));
-		assert!(sdb.is_pruned(&H256::from_low_u64_be(1), 1));
-		assert!(!sdb.is_pruned(&H256::from_low_u64_be(21), 2));
-		assert!(sdb.is_pruned(&H256::from_low_u64_be(22), 2));
+		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(0), 0), IsPruned::Pruned);
+		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(1), 1), IsPruned::Pruned);
+		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(21), 2), IsPruned::NotPruned);
+		assert_eq!(sdb.is_pruned(&H256::from_low_u64_be(22), 2), IsPruned::Pruned);
 		assert!(db.data_eq(&make_db(&[1, 21, 3, 921, 922, 93, 94])));
 	}
 
@@ -709,4 +799,41 @@ mod tests {
 		let state_db: Result<StateDb<H256, H256>, _> = StateDb::new(new_mode, false, &db);
 		assert!(state_db.is_err());
 	}
+
+	#[test]
+	fn converted_journals_keep_reinserted_keys() {
+		let mut db = make_db(&[1]);
+		let state_db = StateDb::new(PruningMode::keep_blocks(10), true, &db).unwrap();
+		let insert = |db: &mut TestDb, hash: u64, number: u64, parent: u64, changeset| {
+			db.commit(
+				&state_db
+					.insert_block::<io::Error>(
+						&H256::from_low_u64_be(hash),
+						number,
+						&H256::from_low_u64_be(parent),
+						changeset,
+					)
+					.unwrap(),
+			);
+			state_db.apply_pending();
+		};
+		insert(&mut db, 1, 1, 0, make_changeset(&[2], &[1]));
+		insert(&mut db, 2, 2, 1, make_changeset(&[1], &[]));
+		insert(&mut db, 3, 3, 2, make_changeset(&[3], &[2]));
+		for hash in [1, 2] {
+			db.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(hash), &db).unwrap());
+			state_db.apply_pending();
+		}
+
+		let mut converted = db.clone();
+		let journals = convert_journals::<H256, H256, _>(&db, |key| key).unwrap();
+		converted.meta.extend(journals.inserted);
+
+		let state_db: StateDb<H256, H256> =
+			StateDb::new(PruningMode::keep_blocks(0), false, &converted).unwrap();
+		converted
+			.commit(&state_db.canonicalize_block(&H256::from_low_u64_be(3), &converted).unwrap());
+		state_db.apply_pending();
+		assert!(converted.data_eq(&make_db(&[1, 3])));
+	}
 }
@@ -87,6 +87,40 @@ fn to_journal_key(block: u64, index: u64) -> Vec<u8> {
 	to_meta_key(NON_CANONICAL_JOURNAL, &(block, index))
 }
 
+/// Rewrite the keys of every journal record with `map_key`.
+pub(crate) fn convert_journal<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+	map_key: &dyn Fn(Key) -> Key,
+	meta: &mut ChangeSet<Vec<u8>>,
+) -> Result<(), Error<D::Error>> {
+	let last_canonicalized = db.get_meta(&to_meta_key(LAST_CANONICAL, &())).map_err(Error::Db)?;
+	let mut block = match last_canonicalized {
+		Some(buffer) => <(BlockHash, u64)>::decode(&mut buffer.as_slice())?.1 + 1,
+		None => return Ok(()),
+	};
+	loop {
+		let mut found = false;
+		for index in 0..MAX_BLOCKS_PER_LEVEL {
+			let journal_key = to_journal_key(block, index);
+			if let Some(record) = db.get_meta(&journal_key).map_err(Error::Db)? {
+				let record: JournalRecord<BlockHash, Key> = Decode::decode(&mut record.as_slice())?;
+				let record = JournalRecord::<BlockHash, Key> {
+					hash: record.hash,
+					parent_hash: record.parent_hash,
+					inserted: record.inserted.into_iter().map(|(k, v)| (map_key(k), v)).collect(),
+					deleted: record.deleted.into_iter().map(map_key).collect(),
+				};
+				meta.inserted.push((journal_key, record.encode()));
+				found = true;
+			}
+		}
+		if !found {
+			return Ok(())
+		}
+		block += 1;
+	}
+}
+
 #[cfg_attr(test, derive(PartialEq, Debug))]
 #[derive(parity_util_mem_derive::MallocSizeOf)]
 struct BlockOverlay<BlockHash: Hash, Key: Hash> {
@@ -377,6 +411,22 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 			.unwrap_or_default()
 	}
 
+	/// Returns hash, number and parent hash of every block in the overlay.
+	pub fn blocks(&self) -> Vec<(BlockHash, u64, BlockHash)> {
+		let front_block_number = self.front_block_number();
+		self.levels
+			.iter()
+			.enumerate()
+			.flat_map(|(index, level)| {
+				level.blocks.iter().map(move |overlay| (overlay.hash.clone(), index as u64))
+			})
+			.filter_map(|(hash, index)| {
+				let parent_hash = self.parents.get(&hash)?.clone();
+				Some((hash, front_block_number + index, parent_hash))
+			})
+			.collect()
+	}
+
 	/// Select a top-level root and canonicalized it. Discards all sibling subtrees and the root.
 	/// Returns a set of changes that need to be added to the DB.
 	pub fn canonicalize<E: fmt::Debug>(
@@ -793,6 +843,31 @@ mod tests {
 		assert_eq!(overlay.last_canonicalized, overlay2.last_canonicalized);
 	}
 
+	#[test]
+	fn lists_overlay_blocks() {
+		let h1 = H256::random();
+		let h2 = H256::random();
+		let h3 = H256::random();
+		let mut db = make_db(&[1, 2]);
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		db.commit(
+			&overlay
+				.insert::<io::Error>(&h1, 10, &H256::default(), make_changeset(&[3], &[]))
+				.unwrap(),
+		);
+		db.commit(&overlay.insert::<io::Error>(&h2, 11, &h1, make_changeset(&[4], &[])).unwrap());
+		db.commit(&overlay.insert::<io::Error>(&h3, 11, &h1, make_changeset(&[5], &[])).unwrap());
+		let expected = vec![(h1, 10, H256::default()), (h2, 11, h1), (h3, 11, h1)];
+		assert_eq!(overlay.blocks(), expected);
+
+		let mut commit = CommitSet::default();
+		overlay.canonicalize::<io::Error>(&h1, &mut commit).unwrap();
+		db.commit(&commit);
+		overlay.apply_pending();
+		let overlay2 = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		assert_eq!(overlay2.blocks(), vec![(h2, 11, h1), (h3, 11, h1)]);
+	}
+
 	#[test]
 	fn insert_canonicalize_two() {
 		let h1 = H256::random();
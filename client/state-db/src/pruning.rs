@@ -23,34 +23,129 @@
 //! If a node is re-inserted into the window it gets removed from
 //! the death list.
 //! The changes are journaled in the DB.
+//!
+//! Both the death rows and the index are kept in the database. Only a page of the death rows and
+//! the changes that are not committed yet are kept in memory, so the memory usage doesn't depend on
+//! the size of the window. When the backend counts references itself, re-inserted nodes don't need
+//! to be tracked and the index is not kept at all.
+//!
+//! Otherwise a fingerprint of every key in the death rows is kept in memory, updated when the
+//! journal is written, so that canonicalizing a block only reads the index for the inserted keys
+//! that may have been deleted in the window.
 
-use crate::{to_meta_key, CommitSet, Error, Hash, MetaDb};
+use crate::{to_meta_key, ChangeSet, CommitSet, Error, Hash, MetaDb};
 use codec::{Decode, Encode};
 use log::{trace, warn};
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::{
+	collections::{
+		hash_map::{DefaultHasher, Entry},
+		BTreeSet, HashMap, HashSet, VecDeque,
+	},
+	hash::Hasher as _,
+};
 
 const LAST_PRUNED: &[u8] = b"last_pruned";
 const PRUNING_JOURNAL: &[u8] = b"pruning_journal";
+const DEATH_INDEX: &[u8] = b"death_index";
+const INDEXED_JOURNAL: &[u8] = b"indexed_journal";
+
+/// Default number of death rows loaded from the journal at once.
+pub(crate) const DEFAULT_PAGE_SIZE: usize = 256;
 
 /// See module documentation.
 #[derive(parity_util_mem_derive::MallocSizeOf)]
 pub struct RefWindow<BlockHash: Hash, Key: Hash> {
-	/// A queue of keys that should be deleted for each block in the pruning window.
-	death_rows: VecDeque<DeathRow<BlockHash, Key>>,
-	/// An index that maps each key from `death_rows` to block number.
-	death_index: HashMap<Key, u64>,
-	/// Block number that corresponds to the front of `death_rows`.
+	/// Death rows of the first blocks of the window that are not pruned yet.
+	cache: VecDeque<DeathRow<BlockHash, Key>>,
+	/// Death rows that are not in `cache` and are not committed to the database yet, or were
+	/// changed since, indexed by block number.
+	uncommitted: HashMap<u64, DeathRow<BlockHash, Key>>,
+	/// In-memory part of the death index. `None` if re-inserted keys are not tracked.
+	death_index: Option<DeathIndex<Key>>,
+	/// Maximum number of death rows loaded into `cache` at once.
+	page_size: usize,
+	/// Block number that corresponds to the front of the queue.
 	pending_number: u64,
+	/// Block number following the last block of the window.
+	end: u64,
 	/// Number of call of `note_canonical` after
 	/// last call `apply_pending` or `revert_pending`
 	pending_canonicalizations: usize,
 	/// Number of calls of `prune_one` after
 	/// last call `apply_pending` or `revert_pending`
 	pending_prunings: usize,
-	/// Keep track of re-inserted keys and do not delete them when pruning.
-	/// Setting this to false requires backend that supports reference
-	/// counting.
-	count_insertions: bool,
+}
+
+/// Result of looking up a block in the pruning window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaveBlock {
+	/// Definitely not in the window.
+	No,
+	/// The block may be in the part of the window that is not loaded in memory.
+	Maybe,
+	/// Definitely in the window.
+	Yes,
+}
+
+/// In-memory part of the index of the keys of the death rows.
+#[derive(parity_util_mem_derive::MallocSizeOf)]
+struct DeathIndex<Key: Hash> {
+	/// Changes to the index since the last call of `apply_pending` or `revert_pending`, `None`
+	/// for removed keys.
+	pending: HashMap<Key, Option<u64>>,
+	/// Number of keys in the committed death rows, by fingerprint.
+	fingerprints: HashMap<u64, u32>,
+	/// Changes to `fingerprints` since the last call of `apply_pending` or `revert_pending`.
+	pending_fingerprints: HashMap<u64, i64>,
+}
+
+impl<Key: Hash> DeathIndex<Key> {
+	fn new() -> Self {
+		DeathIndex {
+			pending: Default::default(),
+			fingerprints: Default::default(),
+			pending_fingerprints: Default::default(),
+		}
+	}
+
+	/// Whether `key` may be in a death row. Keys for which this is `false` are not in the index.
+	fn may_contain(&self, key: &Key) -> bool {
+		let fingerprint = fingerprint(key);
+		let committed = self.fingerprints.get(&fingerprint).map_or(0, |count| *count as i64);
+		committed + self.pending_fingerprints.get(&fingerprint).copied().unwrap_or(0) > 0
+	}
+
+	/// Note that `key` was added to a death row.
+	fn add(&mut self, key: &Key) {
+		*self.pending_fingerprints.entry(fingerprint(key)).or_default() += 1;
+	}
+
+	/// Note that `key` was removed from a death row.
+	fn remove(&mut self, key: &Key) {
+		*self.pending_fingerprints.entry(fingerprint(key)).or_default() -= 1;
+	}
+
+	fn apply_pending(&mut self) {
+		self.pending.clear();
+		for (fingerprint, change) in self.pending_fingerprints.drain() {
+			let entry = self.fingerprints.entry(fingerprint).or_default();
+			*entry = (*entry as i64 + change).max(0) as u32;
+			if *entry == 0 {
+				self.fingerprints.remove(&fingerprint);
+			}
+		}
+	}
+
+	fn revert_pending(&mut self) {
+		self.pending.clear();
+		self.pending_fingerprints.clear();
+	}
+}
+
+fn fingerprint<Key: Hash>(key: &Key) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	key.hash(&mut hasher);
+	hasher.finish()
 }
 
 #[derive(Debug, PartialEq, Eq, parity_util_mem_derive::MallocSizeOf)]
@@ -60,6 +155,26 @@ struct DeathRow<BlockHash: Hash, Key: Hash> {
 	deleted: HashSet<Key>,
 }
 
+impl<BlockHash: Hash, Key: Hash> DeathRow<BlockHash, Key> {
+	/// Add the deletions of this row to `commit`, as the pruning of block `number`.
+	fn prune(&self, number: u64, commit: &mut CommitSet<Key>) {
+		trace!(target: "state-db", "Pruning {:?} ({} deleted)", self.hash, self.deleted.len());
+		commit.data.deleted.extend(self.deleted.iter().cloned());
+		commit.meta.inserted.push((to_meta_key(LAST_PRUNED, &()), number.encode()));
+		commit.meta.deleted.push(self.journal_key.clone());
+	}
+
+	/// The journal record of this row. Re-inserted keys are already removed from the row, so the
+	/// record doesn't need to list them.
+	fn journal_record(&self) -> JournalRecord<BlockHash, Key> {
+		JournalRecord {
+			hash: self.hash.clone(),
+			inserted: Vec::new(),
+			deleted: self.deleted.iter().cloned().collect(),
+		}
+	}
+}
+
 #[derive(Encode, Decode)]
 struct JournalRecord<BlockHash: Hash, Key: Hash> {
 	hash: BlockHash,
@@ -71,82 +186,254 @@ fn to_journal_key(block: u64) -> Vec<u8> {
 	to_meta_key(PRUNING_JOURNAL, &block)
 }
 
+fn to_death_index_key<Key: Hash>(key: &Key) -> Vec<u8> {
+	to_meta_key(DEATH_INDEX, key)
+}
+
+fn last_pruned<D: MetaDb>(db: &D) -> Result<Option<u64>, Error<D::Error>> {
+	match db.get_meta(&to_meta_key(LAST_PRUNED, &())).map_err(Error::Db)? {
+		Some(buffer) => Ok(Some(u64::decode(&mut buffer.as_slice())?)),
+		None => Ok(None),
+	}
+}
+
+fn read_row<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+	block: u64,
+) -> Result<DeathRow<BlockHash, Key>, Error<D::Error>> {
+	let journal_key = to_journal_key(block);
+	let record = db
+		.get_meta(&journal_key)
+		.map_err(Error::Db)?
+		.ok_or(Error::MissingJournalRecord(block))?;
+	let record: JournalRecord<BlockHash, Key> = Decode::decode(&mut record.as_slice())?;
+	Ok(DeathRow { hash: record.hash, deleted: record.deleted.into_iter().collect(), journal_key })
+}
+
+/// Find the block number following the last journal record, given the number of the first one.
+///
+/// Records are always added at the back and removed at the front of the journal, so there are no
+/// gaps and a binary search can be used instead of reading the whole journal.
+fn journal_end<D: MetaDb>(db: &D, first: u64) -> Result<u64, Error<D::Error>> {
+	let exists = |block: u64| -> Result<bool, Error<D::Error>> {
+		Ok(db.get_meta(&to_journal_key(block)).map_err(Error::Db)?.is_some())
+	};
+	if !exists(first)? {
+		return Ok(first)
+	}
+	let mut present = first;
+	let mut step = 1u64;
+	let mut missing = loop {
+		let probe = present.saturating_add(step);
+		if !exists(probe)? {
+			break probe
+		}
+		present = probe;
+		step = step.saturating_mul(2);
+	};
+	while missing - present > 1 {
+		let middle = present + (missing - present) / 2;
+		if exists(middle)? {
+			present = middle;
+		} else {
+			missing = middle;
+		}
+	}
+	Ok(missing)
+}
+
+/// Read the whole journal starting at block `first`, removing the keys re-inserted later in the
+/// window from the death rows.
+///
+/// This loads the whole window in memory and is only meant for one-off migrations.
+fn replay_journal<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+	first: u64,
+) -> Result<Vec<DeathRow<BlockHash, Key>>, Error<D::Error>> {
+	let mut rows: Vec<DeathRow<BlockHash, Key>> = Vec::new();
+	let mut death_index: HashMap<Key, usize> = HashMap::new();
+	loop {
+		let journal_key = to_journal_key(first + rows.len() as u64);
+		let record = match db.get_meta(&journal_key).map_err(Error::Db)? {
+			Some(record) => record,
+			None => break,
+		};
+		let record: JournalRecord<BlockHash, Key> = Decode::decode(&mut record.as_slice())?;
+		for k in &record.inserted {
+			if let Some(index) = death_index.remove(k) {
+				rows[index].deleted.remove(k);
+			}
+		}
+		for k in &record.deleted {
+			death_index.insert(k.clone(), rows.len());
+		}
+		rows.push(DeathRow {
+			hash: record.hash,
+			deleted: record.deleted.into_iter().collect(),
+			journal_key,
+		});
+	}
+	Ok(rows)
+}
+
+/// Rewrite the journal of a window that counts insertions so that it can be used by a window
+/// backed by a reference counting database.
+///
+/// Keys re-inserted later in the window are removed from the death rows before `map_key` is
+/// applied, since reference counting keeps these keys alive by itself. The death index is
+/// removed.
+pub(crate) fn convert_journal<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+	map_key: &dyn Fn(Key) -> Key,
+	meta: &mut ChangeSet<Vec<u8>>,
+) -> Result<(), Error<D::Error>> {
+	let first = last_pruned(db)?.map_or(0, |block| block + 1);
+	let rows = replay_journal::<BlockHash, Key, D>(db, first)?;
+	for row in rows {
+		meta.deleted.extend(row.deleted.iter().map(to_death_index_key));
+		let record = JournalRecord::<BlockHash, Key> {
+			hash: row.hash,
+			inserted: Vec::new(),
+			deleted: row.deleted.into_iter().map(map_key).collect(),
+		};
+		meta.inserted.push((row.journal_key, record.encode()));
+	}
+	meta.deleted.push(to_meta_key(INDEXED_JOURNAL, &()));
+	Ok(())
+}
+
+/// Build the death index of a journal written without it, e.g. by an older version or for a
+/// reference counting database.
+///
+/// Does nothing if the journal is already indexed.
+pub(crate) fn index_journal<BlockHash: Hash, Key: Hash, D: MetaDb>(
+	db: &D,
+	meta: &mut ChangeSet<Vec<u8>>,
+) -> Result<(), Error<D::Error>> {
+	let marker = to_meta_key(INDEXED_JOURNAL, &());
+	if db.get_meta(&marker).map_err(Error::Db)?.is_some() {
+		return Ok(())
+	}
+	let first = last_pruned(db)?.map_or(0, |block| block + 1);
+	let rows = replay_journal::<BlockHash, Key, D>(db, first)?;
+	trace!(target: "state-db", "Indexing {} pruning journal entries from #{}", rows.len(), first);
+	for (index, row) in rows.into_iter().enumerate() {
+		let block = first + index as u64;
+		meta.inserted
+			.extend(row.deleted.iter().map(|k| (to_death_index_key(k), block.encode())));
+		meta.inserted.push((row.journal_key.clone(), row.journal_record().encode()));
+	}
+	meta.inserted.push((marker, Vec::new()));
+	Ok(())
+}
+
 impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
+	/// Create the window from the journal. Only the bounds of the journal are read, the records
+	/// are read `page_size` at a time, when the blocks need to be pruned.
+	///
+	/// When `count_insertions` is set the journal must have been indexed with `index_journal`, and
+	/// it is read once to fingerprint the keys of the death rows.
 	pub fn new<D: MetaDb>(
 		db: &D,
 		count_insertions: bool,
+		page_size: usize,
 	) -> Result<RefWindow<BlockHash, Key>, Error<D::Error>> {
-		let last_pruned = db.get_meta(&to_meta_key(LAST_PRUNED, &())).map_err(|e| Error::Db(e))?;
-		let pending_number: u64 = match last_pruned {
-			Some(buffer) => u64::decode(&mut buffer.as_slice())? + 1,
-			None => 0,
+		let pending_number = last_pruned(db)?.map_or(0, |block| block + 1);
+		let end = journal_end(db, pending_number)?;
+		if count_insertions &&
+			end > pending_number &&
+			db.get_meta(&to_meta_key(INDEXED_JOURNAL, &())).map_err(Error::Db)?.is_none()
+		{
+			return Err(Error::UnindexedJournal)
+		}
+		trace!(target: "state-db", "Reading pruning journal. Pending #{}, end #{}", pending_number, end);
+		let death_index = if count_insertions {
+			let mut death_index = DeathIndex::new();
+			for block in pending_number..end {
+				let row: DeathRow<BlockHash, Key> = read_row(db, block)?;
+				for k in &row.deleted {
+					*death_index.fingerprints.entry(fingerprint(k)).or_default() += 1;
+				}
+			}
+			Some(death_index)
+		} else {
+			None
 		};
-		let mut block = pending_number;
-		let mut pruning = RefWindow {
-			death_rows: Default::default(),
-			death_index: Default::default(),
+		Ok(RefWindow {
+			cache: Default::default(),
+			uncommitted: Default::default(),
+			death_index,
+			page_size: page_size.max(1),
 			pending_number,
+			end,
 			pending_canonicalizations: 0,
 			pending_prunings: 0,
-			count_insertions,
-		};
-		// read the journal
-		trace!(target: "state-db", "Reading pruning journal. Pending #{}", pending_number);
-		loop {
-			let journal_key = to_journal_key(block);
-			match db.get_meta(&journal_key).map_err(|e| Error::Db(e))? {
-				Some(record) => {
-					let record: JournalRecord<BlockHash, Key> =
-						Decode::decode(&mut record.as_slice())?;
-					trace!(target: "state-db", "Pruning journal entry {} ({} inserted, {} deleted)", block, record.inserted.len(), record.deleted.len());
-					pruning.import(
-						&record.hash,
-						journal_key,
-						record.inserted.into_iter(),
-						record.deleted,
-					);
-				},
-				None => break,
-			}
+		})
+	}
+
+	fn import(&mut self, row: DeathRow<BlockHash, Key>) {
+		// The cache must hold consecutive blocks starting at the front of the window.
+		if self.pending() + self.cache.len() as u64 == self.end && self.cache.len() < self.page_size
+		{
+			self.cache.push_back(row);
+		} else {
+			self.uncommitted.insert(self.end, row);
+		}
+		self.end += 1;
+	}
+
+	/// Load the next page of death rows if the cache is empty.
+	fn load_page<D: MetaDb>(&mut self, db: &D) -> Result<(), Error<D::Error>> {
+		if !self.cache.is_empty() {
+			return Ok(())
+		}
+		let mut block = self.pending();
+		while block < self.end && self.cache.len() < self.page_size {
+			let row = match self.uncommitted.remove(&block) {
+				Some(row) => row,
+				None => read_row(db, block)?,
+			};
+			self.cache.push_back(row);
 			block += 1;
 		}
-		Ok(pruning)
+		trace!(target: "state-db", "Loaded {} pruning journal entries up to #{}", self.cache.len(), block);
+		Ok(())
 	}
 
-	fn import<I: IntoIterator<Item = Key>>(
+	/// The death row of `block`, read from the journal if it is not in memory yet.
+	fn row_mut<D: MetaDb>(
 		&mut self,
-		hash: &BlockHash,
-		journal_key: Vec<u8>,
-		inserted: I,
-		deleted: Vec<Key>,
-	) {
-		if self.count_insertions {
-			// remove all re-inserted keys from death rows
-			for k in inserted {
-				if let Some(block) = self.death_index.remove(&k) {
-					self.death_rows[(block - self.pending_number) as usize].deleted.remove(&k);
-				}
-			}
+		block: u64,
+		db: &D,
+	) -> Result<&mut DeathRow<BlockHash, Key>, Error<D::Error>> {
+		let front = self.pending();
+		if block < front + self.cache.len() as u64 {
+			return Ok(&mut self.cache[(block - front) as usize])
+		}
+		Ok(match self.uncommitted.entry(block) {
+			Entry::Occupied(entry) => entry.into_mut(),
+			Entry::Vacant(entry) => entry.insert(read_row(db, block)?),
+		})
+	}
 
-			// add new keys
-			let imported_block = self.pending_number + self.death_rows.len() as u64;
-			for k in deleted.iter() {
-				self.death_index.insert(k.clone(), imported_block);
-			}
+	/// The block `key` is scheduled to be deleted with.
+	fn death_block<D: MetaDb>(&self, key: &Key, db: &D) -> Result<Option<u64>, Error<D::Error>> {
+		if let Some(block) = self.death_index.as_ref().and_then(|index| index.pending.get(key)) {
+			return Ok(*block)
+		}
+		match db.get_meta(&to_death_index_key(key)).map_err(Error::Db)? {
+			Some(block) => Ok(Some(u64::decode(&mut block.as_slice())?)),
+			None => Ok(None),
 		}
-		self.death_rows.push_back(DeathRow {
-			hash: hash.clone(),
-			deleted: deleted.into_iter().collect(),
-			journal_key,
-		});
 	}
 
 	pub fn window_size(&self) -> u64 {
-		(self.death_rows.len() - self.pending_prunings) as u64
+		self.end - self.pending()
 	}
 
-	pub fn next_hash(&self) -> Option<BlockHash> {
-		self.death_rows.get(self.pending_prunings).map(|r| r.hash.clone())
+	pub fn next_hash<D: MetaDb>(&mut self, db: &D) -> Result<Option<BlockHash>, Error<D::Error>> {
+		self.load_page(db)?;
+		Ok(self.cache.front().map(|r| r.hash.clone()))
 	}
 
 	pub fn mem_used(&self) -> usize {
@@ -157,77 +444,130 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 		self.pending_number + self.pending_prunings as u64
 	}
 
-	pub fn have_block(&self, hash: &BlockHash) -> bool {
-		self.death_rows.iter().skip(self.pending_prunings).any(|r| r.hash == *hash)
+	pub fn have_block(&self, hash: &BlockHash) -> HaveBlock {
+		if self.cache.iter().chain(self.uncommitted.values()).any(|r| r.hash == *hash) {
+			HaveBlock::Yes
+		} else if (self.cache.len() + self.uncommitted.len()) as u64 == self.window_size() {
+			// The whole window is in memory.
+			HaveBlock::No
+		} else {
+			HaveBlock::Maybe
+		}
 	}
 
 	/// Prune next block. Expects at least one block in the window. Adds changes to `commit`.
-	pub fn prune_one(&mut self, commit: &mut CommitSet<Key>) {
-		if let Some(pruned) = self.death_rows.get(self.pending_prunings) {
-			trace!(target: "state-db", "Pruning {:?} ({} deleted)", pruned.hash, pruned.deleted.len());
-			let index = self.pending_number + self.pending_prunings as u64;
-			commit.data.deleted.extend(pruned.deleted.iter().cloned());
-			commit.meta.inserted.push((to_meta_key(LAST_PRUNED, &()), index.encode()));
-			commit.meta.deleted.push(pruned.journal_key.clone());
-			self.pending_prunings += 1;
-		} else {
-			warn!(target: "state-db", "Trying to prune when there's nothing to prune");
+	pub fn prune_one<D: MetaDb>(
+		&mut self,
+		commit: &mut CommitSet<Key>,
+		db: &D,
+	) -> Result<(), Error<D::Error>> {
+		self.load_page(db)?;
+		let row = match self.cache.pop_front() {
+			Some(row) => row,
+			None => {
+				warn!(target: "state-db", "Trying to prune when there's nothing to prune");
+				return Ok(())
+			},
+		};
+		row.prune(self.pending(), commit);
+		if let Some(death_index) = &mut self.death_index {
+			for k in row.deleted {
+				commit.meta.deleted.push(to_death_index_key(&k));
+				death_index.remove(&k);
+				death_index.pending.insert(k, None);
+			}
 		}
+		self.pending_prunings += 1;
+		Ok(())
 	}
 
 	/// Add a change set to the window. Creates a journal record and pushes it to `commit`
-	pub fn note_canonical(&mut self, hash: &BlockHash, commit: &mut CommitSet<Key>) {
+	pub fn note_canonical<D: MetaDb>(
+		&mut self,
+		hash: &BlockHash,
+		commit: &mut CommitSet<Key>,
+		db: &D,
+	) -> Result<(), Error<D::Error>> {
 		trace!(target: "state-db", "Adding to pruning window: {:?} ({} inserted, {} deleted)", hash, commit.data.inserted.len(), commit.data.deleted.len());
-		let inserted = if self.count_insertions {
-			commit.data.inserted.iter().map(|(k, _)| k.clone()).collect()
-		} else {
-			Default::default()
-		};
-		let deleted = ::std::mem::take(&mut commit.data.deleted);
-		let journal_record = JournalRecord { hash: hash.clone(), inserted, deleted };
-		let block = self.pending_number + self.death_rows.len() as u64;
-		let journal_key = to_journal_key(block);
-		commit.meta.inserted.push((journal_key.clone(), journal_record.encode()));
-		self.import(
-			&journal_record.hash,
-			journal_key,
-			journal_record.inserted.into_iter(),
-			journal_record.deleted,
-		);
+		let block = self.end;
+		let deleted: HashSet<Key> = std::mem::take(&mut commit.data.deleted).into_iter().collect();
+		if self.death_index.is_some() {
+			if self.window_size() == 0 {
+				// An empty journal is trivially indexed.
+				commit.meta.inserted.push((to_meta_key(INDEXED_JOURNAL, &()), Vec::new()));
+			}
+			// Remove all re-inserted keys from death rows. The database applies the deletions of
+			// a commit after the insertions, so keys deleted again by this block must not be
+			// removed from the index. Only the keys that may be in the window are looked up.
+			let mut unindexed = HashSet::new();
+			let mut changed_rows = BTreeSet::new();
+			for (k, _) in &commit.data.inserted {
+				if !self.death_index.as_ref().expect("checked above; qed").may_contain(k) {
+					continue
+				}
+				if let Some(death_block) = self.death_block(k, db)? {
+					if death_block >= self.pending() &&
+						self.row_mut(death_block, db)?.deleted.remove(k)
+					{
+						self.death_index.as_mut().expect("checked above; qed").remove(k);
+						changed_rows.insert(death_block);
+					}
+					unindexed.insert(k.clone());
+				}
+			}
+			for death_block in changed_rows {
+				let row = self.row_mut(death_block, db)?;
+				commit
+					.meta
+					.inserted
+					.push((row.journal_key.clone(), row.journal_record().encode()));
+			}
+			let death_index = self.death_index.as_mut().expect("checked above; qed");
+			for k in &deleted {
+				unindexed.remove(k);
+				commit.meta.inserted.push((to_death_index_key(k), block.encode()));
+				death_index.add(k);
+				death_index.pending.insert(k.clone(), Some(block));
+			}
+			for k in unindexed {
+				commit.meta.deleted.push(to_death_index_key(&k));
+				death_index.pending.insert(k, None);
+			}
+		}
+		let row = DeathRow { hash: hash.clone(), journal_key: to_journal_key(block), deleted };
+		commit
+			.meta
+			.inserted
+			.push((row.journal_key.clone(), row.journal_record().encode()));
+		self.import(row);
 		self.pending_canonicalizations += 1;
+		Ok(())
 	}
 
 	/// Apply all pending changes
 	pub fn apply_pending(&mut self) {
-		self.pending_canonicalizations = 0;
-		for _ in 0..self.pending_prunings {
-			let pruned = self
-				.death_rows
-				.pop_front()
-				.expect("pending_prunings is always < death_rows.len()");
-			trace!(target: "state-db", "Applying pruning {:?} ({} deleted)", pruned.hash, pruned.deleted.len());
-			if self.count_insertions {
-				for k in pruned.deleted.iter() {
-					self.death_index.remove(&k);
-				}
-			}
-			self.pending_number += 1;
+		// Pruned rows are already removed from the cache and the other changes are now in the
+		// database.
+		trace!(target: "state-db", "Applying {} prunings", self.pending_prunings);
+		self.uncommitted.clear();
+		if let Some(death_index) = &mut self.death_index {
+			death_index.apply_pending();
 		}
+		self.pending_number += self.pending_prunings as u64;
+		self.pending_canonicalizations = 0;
 		self.pending_prunings = 0;
 	}
 
 	/// Revert all pending changes
 	pub fn revert_pending(&mut self) {
-		// Revert pending deletions.
-		// Note that pending insertions might cause some existing deletions to be removed from
-		// `death_index` We don't bother to track and revert that for now. This means that a few
-		// nodes might end up no being deleted in case transaction fails and `revert_pending` is
-		// called.
-		self.death_rows.truncate(self.death_rows.len() - self.pending_canonicalizations);
-		if self.count_insertions {
-			let new_max_block = self.death_rows.len() as u64 + self.pending_number;
-			self.death_index.retain(|_, block| *block < new_max_block);
+		// The journal and the index in the database are unchanged, the cache is reloaded from
+		// them when needed.
+		self.cache.clear();
+		self.uncommitted.clear();
+		if let Some(death_index) = &mut self.death_index {
+			death_index.revert_pending();
 		}
+		self.end -= self.pending_canonicalizations as u64;
 		self.pending_canonicalizations = 0;
 		self.pending_prunings = 0;
 	}
@@ -235,38 +575,82 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 
 #[cfg(test)]
 mod tests {
-	use super::RefWindow;
+	use super::{
+		index_journal, read_row, to_journal_key, DeathRow, HaveBlock, JournalRecord, RefWindow,
+		DEATH_INDEX, DEFAULT_PAGE_SIZE,
+	};
 	use crate::{
 		test::{make_commit, make_db, TestDb},
-		CommitSet,
+		ChangeSet, CommitSet, DBValue, Error, MetaDb,
 	};
+	use codec::{Decode, Encode};
 	use sp_core::H256;
+	use std::{
+		cell::Cell,
+		collections::{HashMap, VecDeque},
+	};
+
+	/// Counts the reads of the death index.
+	struct IndexReads<'a>(&'a TestDb, Cell<usize>);
+
+	impl MetaDb for IndexReads<'_> {
+		type Error = ();
+
+		fn get_meta(&self, key: &[u8]) -> Result<Option<DBValue>, ()> {
+			if key.ends_with(DEATH_INDEX) {
+				self.1.set(self.1.get() + 1);
+			}
+			self.0.get_meta(key)
+		}
+	}
+
+	fn death_rows(pruning: &RefWindow<H256, H256>) -> &VecDeque<DeathRow<H256, H256>> {
+		&pruning.cache
+	}
+
+	fn death_index(db: &TestDb) -> HashMap<H256, u64> {
+		db.meta
+			.iter()
+			.filter(|(k, _)| k.ends_with(DEATH_INDEX))
+			.map(|(k, v)| (H256::from_slice(&k[..32]), u64::decode(&mut v.as_slice()).unwrap()))
+			.collect()
+	}
 
 	fn check_journal(pruning: &RefWindow<H256, H256>, db: &TestDb) {
-		let restored: RefWindow<H256, H256> = RefWindow::new(db, pruning.count_insertions).unwrap();
+		let count_insertions = pruning.death_index.is_some();
+		let restored: RefWindow<H256, H256> =
+			RefWindow::new(db, count_insertions, DEFAULT_PAGE_SIZE).unwrap();
 		assert_eq!(pruning.pending_number, restored.pending_number);
-		assert_eq!(pruning.death_rows, restored.death_rows);
-		assert_eq!(pruning.death_index, restored.death_index);
+		assert_eq!(pruning.window_size(), restored.window_size());
+		assert_eq!(
+			pruning.death_index.as_ref().map(|index| &index.fingerprints),
+			restored.death_index.as_ref().map(|index| &index.fingerprints),
+		);
+		for (index, row) in pruning.cache.iter().enumerate() {
+			let block = pruning.pending() + index as u64;
+			assert_eq!(&read_row::<H256, H256, _>(db, block).unwrap(), row);
+		}
 	}
 
 	#[test]
 	fn created_from_empty_db() {
 		let db = make_db(&[]);
-		let pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let pruning: RefWindow<H256, H256> = RefWindow::new(&db, true, DEFAULT_PAGE_SIZE).unwrap();
 		assert_eq!(pruning.pending_number, 0);
-		assert!(pruning.death_rows.is_empty());
-		assert!(pruning.death_index.is_empty());
+		assert!(death_rows(&pruning).is_empty());
+		assert!(death_index(&db).is_empty());
 	}
 
 	#[test]
 	fn prune_empty() {
 		let db = make_db(&[]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let mut pruning: RefWindow<H256, H256> =
+			RefWindow::new(&db, true, DEFAULT_PAGE_SIZE).unwrap();
 		let mut commit = CommitSet::default();
-		pruning.prune_one(&mut commit);
+		pruning.prune_one(&mut commit, &db).unwrap();
 		assert_eq!(pruning.pending_number, 0);
-		assert!(pruning.death_rows.is_empty());
-		assert!(pruning.death_index.is_empty());
+		assert!(death_rows(&pruning).is_empty());
+		assert!(death_index(&db).is_empty());
 		assert!(pruning.pending_prunings == 0);
 		assert!(pruning.pending_canonicalizations == 0);
 	}
@@ -274,41 +658,43 @@ mod tests {
 	#[test]
 	fn prune_one() {
 		let mut db = make_db(&[1, 2, 3]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let mut pruning: RefWindow<H256, H256> =
+			RefWindow::new(&db, true, DEFAULT_PAGE_SIZE).unwrap();
 		let mut commit = make_commit(&[4, 5], &[1, 3]);
 		let h = H256::random();
-		pruning.note_canonical(&h, &mut commit);
+		pruning.note_canonical(&h, &mut commit, &db).unwrap();
 		db.commit(&commit);
-		assert!(pruning.have_block(&h));
+		assert_eq!(pruning.have_block(&h), HaveBlock::Yes);
 		pruning.apply_pending();
-		assert!(pruning.have_block(&h));
+		assert_eq!(pruning.have_block(&h), HaveBlock::Yes);
 		assert!(commit.data.deleted.is_empty());
-		assert_eq!(pruning.death_rows.len(), 1);
-		assert_eq!(pruning.death_index.len(), 2);
+		assert_eq!(death_rows(&pruning).len(), 1);
+		assert_eq!(death_index(&db).len(), 2);
 		assert!(db.data_eq(&make_db(&[1, 2, 3, 4, 5])));
 		check_journal(&pruning, &db);
 
 		let mut commit = CommitSet::default();
-		pruning.prune_one(&mut commit);
-		assert!(!pruning.have_block(&h));
+		pruning.prune_one(&mut commit, &db).unwrap();
+		assert_eq!(pruning.have_block(&h), HaveBlock::No);
 		db.commit(&commit);
 		pruning.apply_pending();
-		assert!(!pruning.have_block(&h));
+		assert_eq!(pruning.have_block(&h), HaveBlock::No);
 		assert!(db.data_eq(&make_db(&[2, 4, 5])));
-		assert!(pruning.death_rows.is_empty());
-		assert!(pruning.death_index.is_empty());
+		assert!(death_rows(&pruning).is_empty());
+		assert!(death_index(&db).is_empty());
 		assert_eq!(pruning.pending_number, 1);
 	}
 
 	#[test]
 	fn prune_two() {
 		let mut db = make_db(&[1, 2, 3]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let mut pruning: RefWindow<H256, H256> =
+			RefWindow::new(&db, true, DEFAULT_PAGE_SIZE).unwrap();
 		let mut commit = make_commit(&[4], &[1]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		let mut commit = make_commit(&[5], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		pruning.apply_pending();
 		assert!(db.data_eq(&make_db(&[1, 2, 3, 4, 5])));
@@ -316,12 +702,12 @@ mod tests {
 		check_journal(&pruning, &db);
 
 		let mut commit = CommitSet::default();
-		pruning.prune_one(&mut commit);
+		pruning.prune_one(&mut commit, &db).unwrap();
 		db.commit(&commit);
 		pruning.apply_pending();
 		assert!(db.data_eq(&make_db(&[2, 3, 4, 5])));
 		let mut commit = CommitSet::default();
-		pruning.prune_one(&mut commit);
+		pruning.prune_one(&mut commit, &db).unwrap();
 		db.commit(&commit);
 		pruning.apply_pending();
 		assert!(db.data_eq(&make_db(&[3, 4, 5])));
@@ -331,20 +717,21 @@ mod tests {
 	#[test]
 	fn prune_two_pending() {
 		let mut db = make_db(&[1, 2, 3]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let mut pruning: RefWindow<H256, H256> =
+			RefWindow::new(&db, true, DEFAULT_PAGE_SIZE).unwrap();
 		let mut commit = make_commit(&[4], &[1]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		let mut commit = make_commit(&[5], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 2, 3, 4, 5])));
 		let mut commit = CommitSet::default();
-		pruning.prune_one(&mut commit);
+		pruning.prune_one(&mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[2, 3, 4, 5])));
 		let mut commit = CommitSet::default();
-		pruning.prune_one(&mut commit);
+		pruning.prune_one(&mut commit, &db).unwrap();
 		db.commit(&commit);
 		pruning.apply_pending();
 		assert!(db.data_eq(&make_db(&[3, 4, 5])));
@@ -354,15 +741,16 @@ mod tests {
 	#[test]
 	fn reinserted_survives() {
 		let mut db = make_db(&[1, 2, 3]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let mut pruning: RefWindow<H256, H256> =
+			RefWindow::new(&db, true, DEFAULT_PAGE_SIZE).unwrap();
 		let mut commit = make_commit(&[], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		let mut commit = make_commit(&[2], &[]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		let mut commit = make_commit(&[], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 2, 3])));
 		pruning.apply_pending();
@@ -370,14 +758,14 @@ mod tests {
 		check_journal(&pruning, &db);
 
 		let mut commit = CommitSet::default();
-		pruning.prune_one(&mut commit);
+		pruning.prune_one(&mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 2, 3])));
 		let mut commit = CommitSet::default();
-		pruning.prune_one(&mut commit);
+		pruning.prune_one(&mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 2, 3])));
-		pruning.prune_one(&mut commit);
+		pruning.prune_one(&mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 3])));
 		pruning.apply_pending();
@@ -387,27 +775,28 @@ mod tests {
 	#[test]
 	fn reinserted_survive_pending() {
 		let mut db = make_db(&[1, 2, 3]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true).unwrap();
+		let mut pruning: RefWindow<H256, H256> =
+			RefWindow::new(&db, true, DEFAULT_PAGE_SIZE).unwrap();
 		let mut commit = make_commit(&[], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		let mut commit = make_commit(&[2], &[]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		let mut commit = make_commit(&[], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 2, 3])));
 
 		let mut commit = CommitSet::default();
-		pruning.prune_one(&mut commit);
+		pruning.prune_one(&mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 2, 3])));
 		let mut commit = CommitSet::default();
-		pruning.prune_one(&mut commit);
+		pruning.prune_one(&mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 2, 3])));
-		pruning.prune_one(&mut commit);
+		pruning.prune_one(&mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 3])));
 		pruning.apply_pending();
@@ -417,15 +806,16 @@ mod tests {
 	#[test]
 	fn reinserted_ignores() {
 		let mut db = make_db(&[1, 2, 3]);
-		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, false).unwrap();
+		let mut pruning: RefWindow<H256, H256> =
+			RefWindow::new(&db, false, DEFAULT_PAGE_SIZE).unwrap();
 		let mut commit = make_commit(&[], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		let mut commit = make_commit(&[2], &[]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		let mut commit = make_commit(&[], &[2]);
-		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 2, 3])));
 		pruning.apply_pending();
@@ -433,9 +823,252 @@ mod tests {
 		check_journal(&pruning, &db);
 
 		let mut commit = CommitSet::default();
-		pruning.prune_one(&mut commit);
+		pruning.prune_one(&mut commit, &db).unwrap();
 		db.commit(&commit);
 		assert!(db.data_eq(&make_db(&[1, 3])));
-		assert!(pruning.death_index.is_empty());
+		assert!(death_index(&db).is_empty());
+	}
+
+	#[test]
+	fn db_backed_loads_journal_in_pages() {
+		let mut db = make_db(&[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, false, 2).unwrap();
+		let mut hashes = Vec::new();
+		for i in 1..=7 {
+			let mut commit = make_commit(&[], &[i]);
+			let h = H256::random();
+			pruning.note_canonical(&h, &mut commit, &db).unwrap();
+			db.commit(&commit);
+			hashes.push(h);
+		}
+		pruning.apply_pending();
+		assert!(death_rows(&pruning).len() <= 2);
+		check_journal(&pruning, &db);
+
+		// only the journal bounds are read on startup.
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, false, 2).unwrap();
+		assert_eq!(pruning.window_size(), 7);
+		assert!(death_rows(&pruning).is_empty());
+		assert_eq!(pruning.have_block(&hashes[3]), HaveBlock::Maybe);
+
+		for i in 0..5 {
+			assert_eq!(pruning.next_hash(&db).unwrap(), Some(hashes[i]));
+			let mut commit = CommitSet::default();
+			pruning.prune_one(&mut commit, &db).unwrap();
+			db.commit(&commit);
+			pruning.apply_pending();
+			assert!(death_rows(&pruning).len() <= 2);
+		}
+		assert!(db.data_eq(&make_db(&[6, 7, 8, 9])));
+		assert_eq!(pruning.pending_number, 5);
+		assert_eq!(pruning.have_block(&hashes[5]), HaveBlock::Yes);
+		assert_eq!(pruning.have_block(&hashes[6]), HaveBlock::Maybe);
+		assert_eq!(pruning.have_block(&H256::random()), HaveBlock::Maybe);
+		check_journal(&pruning, &db);
+
+		let mut commit = CommitSet::default();
+		pruning.prune_one(&mut commit, &db).unwrap();
+		pruning.prune_one(&mut commit, &db).unwrap();
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert!(db.data_eq(&make_db(&[8, 9])));
+		assert_eq!(pruning.window_size(), 0);
+		assert_eq!(pruning.have_block(&hashes[6]), HaveBlock::No);
+		check_journal(&pruning, &db);
+	}
+
+	#[test]
+	fn db_backed_prunes_uncommitted() {
+		let mut db = make_db(&[1, 2, 3, 4]);
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, false, 1).unwrap();
+		let mut commit = CommitSet::default();
+		for i in 1..=3 {
+			let mut block_commit = make_commit(&[], &[i]);
+			pruning.note_canonical(&H256::random(), &mut block_commit, &db).unwrap();
+			commit.meta.inserted.extend(block_commit.meta.inserted);
+		}
+		// the journal records are not in the database yet.
+		for _ in 0..3 {
+			pruning.prune_one(&mut commit, &db).unwrap();
+		}
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert!(db.data_eq(&make_db(&[4])));
+		assert_eq!(pruning.pending_number, 3);
+		assert_eq!(pruning.window_size(), 0);
+		assert!(db.meta.keys().all(|k| !k.ends_with(super::PRUNING_JOURNAL)));
+		check_journal(&pruning, &db);
+	}
+
+	#[test]
+	fn db_backed_revert_pending() {
+		let mut db = make_db(&[1, 2, 3]);
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, false, 1).unwrap();
+		for i in 1..=2 {
+			let mut commit = make_commit(&[], &[i]);
+			pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
+			db.commit(&commit);
+		}
+		pruning.apply_pending();
+
+		let mut commit = make_commit(&[], &[3]);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
+		pruning.prune_one(&mut commit, &db).unwrap();
+		assert_eq!(pruning.window_size(), 2);
+		pruning.revert_pending();
+		assert_eq!(pruning.window_size(), 2);
+		assert_eq!(pruning.pending_number, 0);
+
+		let mut commit = CommitSet::default();
+		pruning.prune_one(&mut commit, &db).unwrap();
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert!(db.data_eq(&make_db(&[2, 3])));
+		check_journal(&pruning, &db);
+	}
+
+	#[test]
+	fn reinserted_survives_out_of_page() {
+		let mut db = make_db(&[1, 2, 3]);
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true, 1).unwrap();
+		for (inserted, deleted) in [(&[][..], &[1][..]), (&[], &[2]), (&[], &[3])] {
+			let mut commit = make_commit(inserted, deleted);
+			pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
+			db.commit(&commit);
+			pruning.apply_pending();
+		}
+		// only the first row is kept in memory, the index is in the database.
+		assert_eq!(death_rows(&pruning).len(), 1);
+		assert_eq!(death_index(&db).len(), 3);
+
+		let mut commit = make_commit(&[2], &[]);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert_eq!(death_rows(&pruning).len(), 1);
+		assert_eq!(death_index(&db).len(), 2);
+		check_journal(&pruning, &db);
+
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true, 1).unwrap();
+		for _ in 0..4 {
+			let mut commit = CommitSet::default();
+			pruning.prune_one(&mut commit, &db).unwrap();
+			db.commit(&commit);
+			pruning.apply_pending();
+			assert!(death_rows(&pruning).len() <= 1);
+		}
+		assert!(db.data_eq(&make_db(&[2])));
+		assert!(death_index(&db).is_empty());
+		assert_eq!(pruning.window_size(), 0);
+	}
+
+	#[test]
+	fn only_keys_in_the_window_are_looked_up() {
+		let mut db = make_db(&[1, 2, 3]);
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true, 1).unwrap();
+		for (inserted, deleted) in [(&[4, 5][..], &[1][..]), (&[6], &[2])] {
+			let mut commit = make_commit(inserted, deleted);
+			let reads = IndexReads(&db, Cell::new(0));
+			pruning.note_canonical(&H256::random(), &mut commit, &reads).unwrap();
+			assert_eq!(reads.1.get(), 0);
+			db.commit(&commit);
+			pruning.apply_pending();
+		}
+
+		// fingerprints are restored from the journal.
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db, true, 1).unwrap();
+		let mut commit = make_commit(&[2, 7], &[]);
+		let reads = IndexReads(&db, Cell::new(0));
+		pruning.note_canonical(&H256::random(), &mut commit, &reads).unwrap();
+		assert_eq!(reads.1.get(), 1);
+		db.commit(&commit);
+		pruning.apply_pending();
+		check_journal(&pruning, &db);
+
+		// keys removed from the rows are no longer looked up.
+		let mut commit = make_commit(&[2], &[]);
+		let reads = IndexReads(&db, Cell::new(0));
+		pruning.note_canonical(&H256::random(), &mut commit, &reads).unwrap();
+		assert_eq!(reads.1.get(), 0);
+		pruning.revert_pending();
+
+		let mut commit = CommitSet::default();
+		pruning.prune_one(&mut commit, &db).unwrap();
+		db.commit(&commit);
+		pruning.apply_pending();
+		let mut commit = make_commit(&[1], &[]);
+		let reads = IndexReads(&db, Cell::new(0));
+		pruning.note_canonical(&H256::random(), &mut commit, &reads).unwrap();
+		assert_eq!(reads.1.get(), 0);
+		pruning.revert_pending();
+		check_journal(&pruning, &db);
+	}
+
+	#[test]
+	fn reinserted_and_deleted_again_in_same_block() {
+		let mut db = make_db(&[1, 2]);
+		let mut pruning: RefWindow<H256, H256> =
+			RefWindow::new(&db, true, DEFAULT_PAGE_SIZE).unwrap();
+		let mut commit = make_commit(&[], &[1]);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
+		db.commit(&commit);
+		pruning.apply_pending();
+
+		let mut commit = make_commit(&[1], &[1]);
+		pruning.note_canonical(&H256::random(), &mut commit, &db).unwrap();
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert_eq!(death_index(&db), [(H256::from_low_u64_be(1), 1)].into_iter().collect());
+
+		let mut commit = CommitSet::default();
+		pruning.prune_one(&mut commit, &db).unwrap();
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert!(db.data_eq(&make_db(&[1, 2])));
+		let mut commit = CommitSet::default();
+		pruning.prune_one(&mut commit, &db).unwrap();
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert!(db.data_eq(&make_db(&[2])));
+	}
+
+	#[test]
+	fn unindexed_journal_is_indexed() {
+		let mut db = make_db(&[1, 2, 3]);
+		for (block, inserted, deleted) in [(0u64, vec![], vec![1, 2]), (1, vec![2], vec![3])] {
+			let record = JournalRecord::<H256, H256> {
+				hash: H256::from_low_u64_be(block),
+				inserted: inserted.into_iter().map(H256::from_low_u64_be).collect(),
+				deleted: deleted.into_iter().map(H256::from_low_u64_be).collect(),
+			};
+			db.meta.insert(to_journal_key(block), record.encode());
+		}
+		assert!(matches!(
+			RefWindow::<H256, H256>::new(&db, true, DEFAULT_PAGE_SIZE),
+			Err(Error::UnindexedJournal)
+		));
+
+		let mut meta = ChangeSet::default();
+		index_journal::<H256, H256, _>(&db, &mut meta).unwrap();
+		db.commit(&CommitSet { data: Default::default(), meta });
+		let mut meta = ChangeSet::default();
+		index_journal::<H256, H256, _>(&db, &mut meta).unwrap();
+		assert!(meta.inserted.is_empty());
+		assert_eq!(
+			death_index(&db),
+			[(H256::from_low_u64_be(1), 0), (H256::from_low_u64_be(3), 1)]
+				.into_iter()
+				.collect()
+		);
+
+		let mut pruning: RefWindow<H256, H256> =
+			RefWindow::new(&db, true, DEFAULT_PAGE_SIZE).unwrap();
+		let mut commit = CommitSet::default();
+		pruning.prune_one(&mut commit, &db).unwrap();
+		pruning.prune_one(&mut commit, &db).unwrap();
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert!(db.data_eq(&make_db(&[2])));
+		assert!(death_index(&db).is_empty());
 	}
 }
@@ -0,0 +1,114 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Difficulty adjustment algorithms.
+
+use codec::{Decode, Encode, MaxEncodedLen};
+use frame_support::traits::Get;
+use scale_info::TypeInfo;
+use sp_core::U256;
+use sp_runtime::RuntimeDebug;
+use sp_std::marker::PhantomData;
+
+/// Difficulty and timestamp of a block, as recorded in the history of its mining algorithm.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq, RuntimeDebug, TypeInfo, MaxEncodedLen)]
+pub struct BlockDifficulty {
+	/// Timestamp of the block, in milliseconds.
+	pub timestamp: u64,
+	/// Difficulty the block was mined at.
+	pub difficulty: U256,
+}
+
+/// An algorithm computing the difficulty of the next block from the latest blocks.
+pub trait DifficultyAdjustment {
+	/// Compute the difficulty of the next block.
+	///
+	/// `history` contains the latest blocks mined with the algorithm, oldest first. It is
+	/// never empty, the last entry being the block just finalized. `target_block_time` is the
+	/// expected time between two of these blocks, in milliseconds.
+	fn next_difficulty(history: &[BlockDifficulty], target_block_time: u64) -> U256;
+}
+
+/// Linearly weighted moving average (LWMA-1) over the whole history.
+///
+/// The solve time of each block is weighted by its position in the history, so that the
+/// difficulty responds quickly to hashrate changes while remaining stable. Solve times are
+/// clamped to `[1, 6 * target_block_time]` to bound the effect of forged timestamps.
+pub struct Lwma;
+
+impl DifficultyAdjustment for Lwma {
+	fn next_difficulty(history: &[BlockDifficulty], target_block_time: u64) -> U256 {
+		let target_block_time = target_block_time.max(1);
+		let window = history.len().saturating_sub(1) as u128;
+		if window == 0 {
+			return history.last().map(|block| block.difficulty).unwrap_or_default()
+		}
+
+		let mut weighted_solve_times = 0u128;
+		let mut total_difficulty = U256::zero();
+		for (index, blocks) in history.windows(2).enumerate() {
+			let solve_time = blocks[1]
+				.timestamp
+				.saturating_sub(blocks[0].timestamp)
+				.clamp(1, target_block_time.saturating_mul(6));
+
+			weighted_solve_times =
+				weighted_solve_times.saturating_add((index as u128 + 1) * solve_time as u128);
+			total_difficulty = total_difficulty.saturating_add(blocks[1].difficulty);
+		}
+
+		// average_difficulty * target_block_time * sum_of_weights / weighted_solve_times
+		let sum_of_weights = window * (window + 1) / 2;
+		total_difficulty.saturating_mul(U256::from(target_block_time) * U256::from(sum_of_weights)) /
+			(U256::from(window) * U256::from(weighted_solve_times))
+	}
+}
+
+/// Retarget the difficulty on every block from the solve time of the last block only.
+///
+/// The solve time is clamped to `[target / ClampFactor, target * ClampFactor]` and damped by
+/// `DampFactor`, i.e. only `1 / DampFactor` of the deviation from the target is accounted for.
+pub struct PerBlockRetarget<DampFactor, ClampFactor>(PhantomData<(DampFactor, ClampFactor)>);
+
+impl<DampFactor, ClampFactor> DifficultyAdjustment for PerBlockRetarget<DampFactor, ClampFactor>
+where
+	DampFactor: Get<u64>,
+	ClampFactor: Get<u64>,
+{
+	fn next_difficulty(history: &[BlockDifficulty], target_block_time: u64) -> U256 {
+		let (parent, last) = match history {
+			[.., parent, last] => (parent, last),
+			[last] => return last.difficulty,
+			[] => return U256::zero(),
+		};
+
+		let target_block_time = target_block_time.max(1);
+		let damp_factor = DampFactor::get().max(1);
+		let clamp_factor = ClampFactor::get().max(1);
+
+		let solve_time = last.timestamp.saturating_sub(parent.timestamp).clamp(
+			target_block_time / clamp_factor,
+			target_block_time.saturating_mul(clamp_factor),
+		);
+		let damped_solve_time = (target_block_time as u128 * (damp_factor as u128 - 1) +
+			solve_time as u128) /
+			damp_factor as u128;
+
+		last.difficulty.saturating_mul(U256::from(target_block_time)) /
+			U256::from(damped_solve_time.max(1))
+	}
+}
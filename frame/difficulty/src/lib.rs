@@ -0,0 +1,185 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Difficulty adjustment for Proof-of-Work chains.
+//!
+//! The pallet keeps the difficulty of the next block of every mining algorithm of the chain,
+//! along with the timestamps and difficulties of their latest blocks. On every block, the
+//! history of the algorithm the block was mined with is updated and its difficulty adjusted
+//! with the configured [`DifficultyAdjustment`], e.g. [`Lwma`] or [`PerBlockRetarget`].
+//!
+//! The algorithm of a block is read from its [`POW_ALGORITHM_ENGINE_ID`] pre-runtime digest.
+//! Blocks without such a digest are mined with [`Config::DefaultAlgorithm`], so that chains
+//! mined with a single algorithm don't need any digest.
+//!
+//! The difficulties are exposed to the node through the
+//! [`DifficultyApi`](sp_consensus_pow::DifficultyApi) and
+//! [`AlgorithmDifficultyApi`](sp_consensus_pow::AlgorithmDifficultyApi) runtime APIs, to be
+//! implemented with [`Pallet::difficulty`] and [`Pallet::algorithm_difficulty`].
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+mod adjustment;
+
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+
+use frame_support::{
+	traits::{Get, UnixTime},
+	BoundedVec,
+};
+use sp_consensus_pow::{AlgorithmId, POW_ALGORITHM_ENGINE_ID};
+use sp_core::U256;
+use sp_runtime::SaturatedConversion;
+use sp_std::prelude::*;
+
+pub use adjustment::{BlockDifficulty, DifficultyAdjustment, Lwma, PerBlockRetarget};
+pub use pallet::*;
+
+#[frame_support::pallet]
+pub mod pallet {
+	use super::*;
+	use frame_support::pallet_prelude::*;
+	use frame_system::pallet_prelude::*;
+
+	#[pallet::pallet]
+	#[pallet::generate_storage_info]
+	pub struct Pallet<T>(_);
+
+	#[pallet::config]
+	pub trait Config: frame_system::Config {
+		/// Time used to measure the solve time of the blocks.
+		type UnixTime: UnixTime;
+
+		/// The algorithm computing the difficulty of the next block from the history.
+		type DifficultyAdjustment: DifficultyAdjustment;
+
+		/// Expected time between two blocks mined with the same algorithm, in milliseconds.
+		#[pallet::constant]
+		type TargetBlockTime: Get<u64>;
+
+		/// Lower bound of the difficulty of every algorithm.
+		#[pallet::constant]
+		type MinDifficulty: Get<U256>;
+
+		/// Number of latest blocks kept in the history of every algorithm. Must be at least 2
+		/// for the solve time of a block to be known.
+		#[pallet::constant]
+		type HistoryLength: Get<u32>;
+
+		/// Algorithm of the blocks without a [`POW_ALGORITHM_ENGINE_ID`] digest.
+		#[pallet::constant]
+		type DefaultAlgorithm: Get<AlgorithmId>;
+	}
+
+	/// Difficulty of the next block of every supported mining algorithm.
+	#[pallet::storage]
+	pub type Difficulties<T: Config> = StorageMap<_, Twox64Concat, AlgorithmId, U256>;
+
+	/// Timestamps and difficulties of the latest blocks of every mining algorithm, oldest first.
+	#[pallet::storage]
+	#[pallet::getter(fn history)]
+	pub type History<T: Config> = StorageMap<
+		_,
+		Twox64Concat,
+		AlgorithmId,
+		BoundedVec<BlockDifficulty, T::HistoryLength>,
+		ValueQuery,
+	>;
+
+	#[pallet::genesis_config]
+	#[derive(Default)]
+	pub struct GenesisConfig {
+		/// The supported mining algorithms with their initial difficulty. The default
+		/// algorithm starts at the minimum difficulty if not listed.
+		pub algorithms: Vec<(AlgorithmId, U256)>,
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> GenesisBuild<T> for GenesisConfig {
+		fn build(&self) {
+			for (algorithm, difficulty) in &self.algorithms {
+				Difficulties::<T>::insert(algorithm, (*difficulty).max(T::MinDifficulty::get()));
+			}
+
+			if !Difficulties::<T>::contains_key(T::DefaultAlgorithm::get()) {
+				Difficulties::<T>::insert(T::DefaultAlgorithm::get(), T::MinDifficulty::get());
+			}
+		}
+	}
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_initialize(_n: BlockNumberFor<T>) -> Weight {
+			// weight of `on_finalize`
+			T::DbWeight::get().reads_writes(2, 2)
+		}
+
+		fn on_finalize(_n: BlockNumberFor<T>) {
+			let now = T::UnixTime::now().as_millis().saturated_into::<u64>();
+			Self::note_block(Self::block_algorithm(), now);
+		}
+
+		fn integrity_test() {
+			assert!(
+				T::HistoryLength::get() >= 2,
+				"The history must keep at least 2 blocks to measure their solve time.",
+			);
+		}
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// Difficulty of the next block mined with the default algorithm.
+	pub fn difficulty() -> U256 {
+		Self::algorithm_difficulty(T::DefaultAlgorithm::get()).unwrap_or_else(T::MinDifficulty::get)
+	}
+
+	/// Difficulty of the next block mined with `algorithm`, `None` if the algorithm is not
+	/// supported.
+	pub fn algorithm_difficulty(algorithm: AlgorithmId) -> Option<U256> {
+		Difficulties::<T>::get(algorithm)
+	}
+
+	/// The algorithm the current block was mined with.
+	fn block_algorithm() -> AlgorithmId {
+		<frame_system::Pallet<T>>::digest()
+			.convert_first(|log| log.pre_runtime_try_to::<AlgorithmId>(&POW_ALGORITHM_ENGINE_ID))
+			.unwrap_or_else(T::DefaultAlgorithm::get)
+	}
+
+	/// Record a block mined with `algorithm` at `timestamp` and adjust the difficulty of the
+	/// next block of this algorithm. Blocks of unsupported algorithms are ignored.
+	fn note_block(algorithm: AlgorithmId, timestamp: u64) {
+		let difficulty = match Difficulties::<T>::get(algorithm) {
+			Some(difficulty) => difficulty,
+			None => return,
+		};
+
+		let next_difficulty = History::<T>::mutate(algorithm, |history| {
+			if history.len() >= T::HistoryLength::get() as usize {
+				history.remove(0);
+			}
+			let _ = history.try_push(BlockDifficulty { timestamp, difficulty });
+
+			T::DifficultyAdjustment::next_difficulty(history, T::TargetBlockTime::get())
+		});
+		Difficulties::<T>::insert(algorithm, next_difficulty.max(T::MinDifficulty::get()));
+	}
+}
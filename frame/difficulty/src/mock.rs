@@ -0,0 +1,130 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test utilities
+
+use crate::{self as pallet_difficulty, Config, Lwma};
+use codec::Encode;
+use frame_support::{
+	parameter_types,
+	traits::{GenesisBuild, OnFinalize},
+};
+use frame_system::InitKind;
+use sp_consensus_pow::{AlgorithmId, POW_ALGORITHM_ENGINE_ID};
+use sp_core::{H256, U256};
+use sp_runtime::{
+	testing::{Digest, DigestItem, Header},
+	traits::{BlakeTwo256, IdentityLookup},
+};
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+frame_support::construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+		Timestamp: pallet_timestamp::{Pallet, Call, Storage, Inherent},
+		Difficulty: pallet_difficulty::{Pallet, Storage, Config},
+	}
+);
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const MinimumPeriod: u64 = 1;
+}
+
+impl frame_system::Config for Test {
+	type BaseCallFilter = frame_support::traits::Everything;
+	type BlockWeights = ();
+	type BlockLength = ();
+	type DbWeight = ();
+	type Origin = Origin;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Call = Call;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = Event;
+	type BlockHashCount = BlockHashCount;
+	type Version = ();
+	type PalletInfo = PalletInfo;
+	type AccountData = ();
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+	type SS58Prefix = ();
+	type OnSetCode = ();
+}
+
+impl pallet_timestamp::Config for Test {
+	type Moment = u64;
+	type OnTimestampSet = ();
+	type MinimumPeriod = MinimumPeriod;
+	type WeightInfo = ();
+}
+
+pub const SHA3: AlgorithmId = *b"sha3";
+pub const BLAKE: AlgorithmId = *b"blk2";
+
+parameter_types! {
+	pub const TargetBlockTime: u64 = 10_000;
+	pub MinDifficulty: U256 = U256::from(1_000);
+	pub const HistoryLength: u32 = 5;
+	pub const DefaultAlgorithm: AlgorithmId = SHA3;
+}
+
+impl Config for Test {
+	type UnixTime = Timestamp;
+	type DifficultyAdjustment = Lwma;
+	type TargetBlockTime = TargetBlockTime;
+	type MinDifficulty = MinDifficulty;
+	type HistoryLength = HistoryLength;
+	type DefaultAlgorithm = DefaultAlgorithm;
+}
+
+/// Executes the next block, mined with `algorithm` (or without algorithm digest if `None`)
+/// at `timestamp`.
+pub fn mine_block(algorithm: Option<AlgorithmId>, timestamp: u64) {
+	let number = System::block_number() + 1;
+	let parent_hash = System::parent_hash();
+
+	let mut digest = Digest::default();
+	if let Some(algorithm) = algorithm {
+		digest.push(DigestItem::PreRuntime(POW_ALGORITHM_ENGINE_ID, algorithm.encode()));
+	}
+
+	System::initialize(&number, &parent_hash, &digest, InitKind::Full);
+	Timestamp::set_timestamp(timestamp);
+	Difficulty::on_finalize(number);
+	System::finalize();
+}
+
+pub fn new_test_ext(algorithms: Vec<(AlgorithmId, U256)>) -> sp_io::TestExternalities {
+	let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+
+	let config = pallet_difficulty::GenesisConfig { algorithms };
+	GenesisBuild::<Test>::assimilate_storage(&config, &mut t).unwrap();
+
+	t.into()
+}
@@ -0,0 +1,143 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the module.
+
+use super::*;
+use frame_support::parameter_types;
+use mock::*;
+
+fn block(timestamp: u64, difficulty: u64) -> BlockDifficulty {
+	BlockDifficulty { timestamp, difficulty: difficulty.into() }
+}
+
+#[test]
+fn genesis_supports_default_algorithm() {
+	new_test_ext(vec![]).execute_with(|| {
+		assert_eq!(Difficulty::difficulty(), U256::from(1_000));
+		assert_eq!(Difficulty::algorithm_difficulty(SHA3), Some(U256::from(1_000)));
+		assert_eq!(Difficulty::algorithm_difficulty(BLAKE), None);
+	});
+
+	new_test_ext(vec![(SHA3, 100_000.into()), (BLAKE, 10.into())]).execute_with(|| {
+		assert_eq!(Difficulty::difficulty(), U256::from(100_000));
+		assert_eq!(Difficulty::algorithm_difficulty(BLAKE), Some(U256::from(1_000)));
+	});
+}
+
+#[test]
+fn difficulty_is_stable_at_target_block_time() {
+	new_test_ext(vec![(SHA3, 100_000.into())]).execute_with(|| {
+		for i in 1..=10 {
+			mine_block(None, i * 10_000);
+			assert_eq!(Difficulty::difficulty(), U256::from(100_000));
+		}
+	});
+}
+
+#[test]
+fn difficulty_follows_block_time() {
+	new_test_ext(vec![(SHA3, 100_000.into())]).execute_with(|| {
+		mine_block(None, 10_000);
+		mine_block(None, 15_000);
+		assert_eq!(Difficulty::difficulty(), U256::from(200_000));
+
+		mine_block(None, 20_000);
+		assert!(Difficulty::difficulty() > U256::from(200_000));
+	});
+
+	new_test_ext(vec![(SHA3, 100_000.into())]).execute_with(|| {
+		mine_block(None, 10_000);
+		mine_block(None, 30_000);
+		assert_eq!(Difficulty::difficulty(), U256::from(50_000));
+	});
+}
+
+#[test]
+fn difficulty_is_bounded_by_min_difficulty() {
+	new_test_ext(vec![(SHA3, 1_500.into())]).execute_with(|| {
+		mine_block(None, 10_000);
+		mine_block(None, 70_000);
+		assert_eq!(Difficulty::difficulty(), U256::from(1_000));
+	});
+}
+
+#[test]
+fn history_is_bounded() {
+	new_test_ext(vec![]).execute_with(|| {
+		for i in 1..=8 {
+			mine_block(None, i * 10_000);
+		}
+
+		let history = Difficulty::history(SHA3);
+		assert_eq!(history.len(), 5);
+		assert_eq!(history.first().map(|block| block.timestamp), Some(40_000));
+		assert_eq!(history.last().map(|block| block.timestamp), Some(80_000));
+	});
+}
+
+#[test]
+fn algorithms_are_adjusted_separately() {
+	new_test_ext(vec![(SHA3, 100_000.into()), (BLAKE, 50_000.into())]).execute_with(|| {
+		mine_block(Some(BLAKE), 10_000);
+		mine_block(Some(BLAKE), 15_000);
+
+		assert_eq!(Difficulty::algorithm_difficulty(BLAKE), Some(U256::from(100_000)));
+		assert_eq!(Difficulty::history(BLAKE).len(), 2);
+		assert_eq!(Difficulty::difficulty(), U256::from(100_000));
+		assert!(Difficulty::history(SHA3).is_empty());
+
+		mine_block(Some(SHA3), 20_000);
+		assert_eq!(Difficulty::history(SHA3).len(), 1);
+		assert_eq!(Difficulty::history(BLAKE).len(), 2);
+
+		// blocks of unsupported algorithms are ignored
+		mine_block(Some(*b"none"), 25_000);
+		assert_eq!(Difficulty::algorithm_difficulty(*b"none"), None);
+		assert!(Difficulty::history(*b"none").is_empty());
+	});
+}
+
+#[test]
+fn lwma_weights_latest_blocks_most() {
+	let history = [block(0, 1_000), block(10_000, 1_000), block(30_000, 1_000)];
+	assert_eq!(Lwma::next_difficulty(&history, 10_000), U256::from(600));
+
+	let history = [block(0, 1_000), block(20_000, 1_000), block(30_000, 1_000)];
+	assert_eq!(Lwma::next_difficulty(&history, 10_000), U256::from(750));
+
+	assert_eq!(Lwma::next_difficulty(&history[..1], 10_000), U256::from(1_000));
+}
+
+#[test]
+fn per_block_retarget_clamps_and_damps_solve_time() {
+	parameter_types! {
+		pub const One: u64 = 1;
+		pub const Two: u64 = 2;
+		pub const Four: u64 = 4;
+	}
+
+	let history = [block(0, 1_000), block(20_000, 1_000)];
+	assert_eq!(PerBlockRetarget::<One, Four>::next_difficulty(&history, 10_000), U256::from(500));
+	assert_eq!(PerBlockRetarget::<Two, Four>::next_difficulty(&history, 10_000), U256::from(666));
+
+	let history = [block(0, 1_000), block(100_000, 1_000)];
+	assert_eq!(PerBlockRetarget::<One, Four>::next_difficulty(&history, 10_000), U256::from(250));
+
+	let history = [block(0, 1_000), block(1_000, 1_000)];
+	assert_eq!(PerBlockRetarget::<One, Four>::next_difficulty(&history, 10_000), U256::from(4_000));
+}
@@ -668,7 +668,7 @@ mod tests {
 
 			#[pallet::weight(0)]
 			pub fn calculate_storage_root(_origin: OriginFor<T>) -> DispatchResult {
-				let root = sp_io::storage::root();
+				let root = sp_io::storage::root(sp_runtime::StateVersion::V0);
 				sp_io::storage::set("storage_root".as_bytes(), &root);
 				Ok(())
 			}
@@ -0,0 +1,32 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Default weights for the Sassafras Pallet
+//! This file was not auto-generated.
+
+use frame_support::weights::{
+	constants::{RocksDbWeight as DbWeight, WEIGHT_PER_MICROS},
+	Weight,
+};
+
+impl crate::WeightInfo for () {
+	fn submit_tickets(tickets_count: u32) -> Weight {
+		// sorted insertion of each ticket in the list of the next epoch
+		(10 * WEIGHT_PER_MICROS)
+			.saturating_add((2 * WEIGHT_PER_MICROS).saturating_mul(tickets_count as u64))
+			.saturating_add(DbWeight::get().reads_writes(1, 1))
+	}
+}
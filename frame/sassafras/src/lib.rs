@@ -0,0 +1,763 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Consensus extension module for Sassafras consensus.
+//!
+//! Collects the tickets submitted during an epoch, which assign the slots of the next epoch,
+//! accumulates on-chain randomness from the VRF outputs of the block authors and manages epoch
+//! transitions.
+//!
+//! Tickets are submitted with the unsigned [`Call::submit_tickets`] during the first half of an
+//! epoch, which leaves the second half for them to be included in a block before the list is
+//! enacted. Every ticket comes with the proof that it is the output of the ticket VRF of an
+//! authority of the next epoch, and each authority can submit its tickets once per epoch, at
+//! most one per attempt. Only the lowest tickets are kept, up to the number of slots of an
+//! epoch.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![warn(unused_must_use, unsafe_code, unused_variables)]
+
+use codec::{Decode, Encode};
+use frame_support::{
+	traits::{FindAuthor, Get, OnTimestampSet, OneSessionHandler},
+	weights::Weight,
+	BoundedVec, WeakBoundedVec,
+};
+use sp_application_crypto::Public;
+use sp_runtime::{
+	generic::DigestItem,
+	traits::{One, SaturatedConversion, Saturating, Zero},
+	transaction_validity::{
+		InvalidTransaction, TransactionPriority, TransactionSource, TransactionValidity,
+		ValidTransaction,
+	},
+	ConsensusEngineId,
+};
+use sp_std::prelude::*;
+
+use sp_consensus_sassafras::{
+	digests::{NextEpochDescriptor, PreDigest},
+	AuthorityIndex, ConsensusLog, Epoch, SassafrasAuthorityWeight, SassafrasEpochConfiguration,
+	Slot, Ticket, TicketEnvelope, SASSAFRAS_ENGINE_ID,
+};
+use sp_consensus_vrf::schnorrkel;
+
+pub use sp_consensus_sassafras::{AuthorityId, RANDOMNESS_LENGTH, VRF_OUTPUT_LENGTH};
+
+mod default_weights;
+
+#[cfg(all(feature = "std", test))]
+mod mock;
+#[cfg(all(feature = "std", test))]
+mod tests;
+
+pub use pallet::*;
+
+pub trait WeightInfo {
+	fn submit_tickets(tickets_count: u32) -> Weight;
+}
+
+/// Trigger an epoch change, if any should take place.
+pub trait EpochChangeTrigger {
+	/// Trigger an epoch change, if any should take place. This should be called
+	/// during every block, after initialization is done.
+	fn trigger<T: Config>(now: T::BlockNumber);
+}
+
+/// A type signifying to Sassafras that an external trigger
+/// for epoch changes (e.g. pallet-session) is used.
+pub struct ExternalTrigger;
+
+impl EpochChangeTrigger for ExternalTrigger {
+	fn trigger<T: Config>(_: T::BlockNumber) {} // nothing - trigger is external.
+}
+
+/// A type signifying to Sassafras that it should perform epoch changes
+/// with an internal trigger, recycling the same authorities forever.
+pub struct SameAuthoritiesForever;
+
+impl EpochChangeTrigger for SameAuthoritiesForever {
+	fn trigger<T: Config>(now: T::BlockNumber) {
+		if <Pallet<T>>::should_epoch_change(now) {
+			let authorities = <Pallet<T>>::authorities();
+			let next_authorities = authorities.clone();
+
+			<Pallet<T>>::enact_epoch_change(authorities, next_authorities);
+		}
+	}
+}
+
+/// Error code of an unsigned tickets submission outside of the submission period.
+const TICKETS_SUBMISSION_CLOSED: u8 = 1;
+/// Error code of an unsigned tickets submission with a ticket above the threshold.
+const TICKET_ABOVE_THRESHOLD: u8 = 2;
+/// Error code of an unsigned tickets submission of an authority which already submitted its
+/// tickets for the next epoch.
+const TICKETS_ALREADY_SUBMITTED: u8 = 3;
+/// Error code of an unsigned tickets submission with an attempt number out of range or used
+/// more than once.
+const INVALID_TICKET_ATTEMPT: u8 = 4;
+/// Error code of an unsigned tickets submission of an authority which is not part of the next
+/// epoch.
+const UNKNOWN_TICKETS_AUTHORITY: u8 = 5;
+
+type MaybeRandomness = Option<schnorrkel::Randomness>;
+
+#[frame_support::pallet]
+pub mod pallet {
+	use super::*;
+	use frame_support::pallet_prelude::*;
+	use frame_system::pallet_prelude::*;
+
+	/// The Sassafras Pallet
+	#[pallet::pallet]
+	#[pallet::generate_storage_info]
+	pub struct Pallet<T>(_);
+
+	#[pallet::config]
+	#[pallet::disable_frame_system_supertrait_check]
+	pub trait Config:
+		pallet_timestamp::Config + frame_system::offchain::SendTransactionTypes<Call<Self>>
+	{
+		/// The amount of time, in slots, that each epoch should last.
+		/// NOTE: Currently it is not possible to change the epoch duration after
+		/// the chain has started. Attempting to do so will brick block production.
+		#[pallet::constant]
+		type EpochDuration: Get<u64>;
+
+		/// Sassafras requires some logic to be triggered on every block to query for whether an
+		/// epoch has ended and to perform the transition to the next epoch.
+		///
+		/// Typically, the `ExternalTrigger` type should be used. An internal trigger should only be
+		/// used when no other module is responsible for changing authority set.
+		type EpochChangeTrigger: EpochChangeTrigger;
+
+		type WeightInfo: WeightInfo;
+
+		/// Max number of authorities allowed
+		#[pallet::constant]
+		type MaxAuthorities: Get<u32>;
+
+		/// Max number of tickets kept for an epoch. Slots without a ticket are assigned to
+		/// fallback authors, so this should be at least `EpochDuration`.
+		#[pallet::constant]
+		type MaxTickets: Get<u32>;
+	}
+
+	/// Current epoch index.
+	#[pallet::storage]
+	#[pallet::getter(fn epoch_index)]
+	pub type EpochIndex<T> = StorageValue<_, u64, ValueQuery>;
+
+	/// Current epoch authorities.
+	#[pallet::storage]
+	#[pallet::getter(fn authorities)]
+	pub type Authorities<T: Config> = StorageValue<
+		_,
+		WeakBoundedVec<(AuthorityId, SassafrasAuthorityWeight), T::MaxAuthorities>,
+		ValueQuery,
+	>;
+
+	/// Next epoch authorities.
+	#[pallet::storage]
+	pub(super) type NextAuthorities<T: Config> = StorageValue<
+		_,
+		WeakBoundedVec<(AuthorityId, SassafrasAuthorityWeight), T::MaxAuthorities>,
+		ValueQuery,
+	>;
+
+	/// The slot at which the first epoch actually started. This is 0
+	/// until the first block of the chain.
+	#[pallet::storage]
+	#[pallet::getter(fn genesis_slot)]
+	pub type GenesisSlot<T> = StorageValue<_, Slot, ValueQuery>;
+
+	/// Current slot number.
+	#[pallet::storage]
+	#[pallet::getter(fn current_slot)]
+	pub type CurrentSlot<T> = StorageValue<_, Slot, ValueQuery>;
+
+	/// The epoch randomness for the *current* epoch.
+	///
+	/// # Security
+	///
+	/// This MUST NOT be used for gambling, as it can be influenced by a
+	/// malicious validator in the short term. It MAY be used in many
+	/// cryptographic protocols, however, so long as one remembers that this
+	/// (like everything else on-chain) it is public.
+	#[pallet::storage]
+	#[pallet::getter(fn randomness)]
+	pub type Randomness<T> = StorageValue<_, schnorrkel::Randomness, ValueQuery>;
+
+	/// Next epoch randomness.
+	#[pallet::storage]
+	pub(super) type NextRandomness<T> = StorageValue<_, schnorrkel::Randomness, ValueQuery>;
+
+	/// Randomness accumulated from the VRF outputs of the blocks of the current epoch.
+	#[pallet::storage]
+	pub(super) type RandomnessAccumulator<T> = StorageValue<_, schnorrkel::Randomness, ValueQuery>;
+
+	/// Temporary value (cleared at block finalization) which is `Some`
+	/// if per-block initialization has already been called for current block.
+	#[pallet::storage]
+	#[pallet::getter(fn initialized)]
+	pub(super) type Initialized<T> = StorageValue<_, MaybeRandomness>;
+
+	/// The configuration of the ticket lottery. Should never be `None` as it is initialized in
+	/// genesis.
+	#[pallet::storage]
+	pub(super) type EpochConfig<T> = StorageValue<_, SassafrasEpochConfiguration>;
+
+	/// Tickets assigning the slots of the current epoch, in increasing order.
+	#[pallet::storage]
+	#[pallet::getter(fn tickets)]
+	pub type Tickets<T: Config> = StorageValue<_, BoundedVec<Ticket, T::MaxTickets>, ValueQuery>;
+
+	/// Tickets submitted for the next epoch, in increasing order.
+	#[pallet::storage]
+	#[pallet::getter(fn next_tickets)]
+	pub type NextTickets<T: Config> =
+		StorageValue<_, BoundedVec<Ticket, T::MaxTickets>, ValueQuery>;
+
+	/// Authorities of the next epoch which already submitted their tickets.
+	#[pallet::storage]
+	pub(super) type NextTicketsSubmitters<T> =
+		StorageMap<_, Twox64Concat, AuthorityIndex, (), OptionQuery>;
+
+	#[pallet::genesis_config]
+	pub struct GenesisConfig {
+		pub authorities: Vec<(AuthorityId, SassafrasAuthorityWeight)>,
+		pub epoch_config: SassafrasEpochConfiguration,
+	}
+
+	#[cfg(feature = "std")]
+	impl Default for GenesisConfig {
+		fn default() -> Self {
+			GenesisConfig { authorities: Default::default(), epoch_config: Default::default() }
+		}
+	}
+
+	#[pallet::genesis_build]
+	impl<T: Config> GenesisBuild<T> for GenesisConfig {
+		fn build(&self) {
+			Pallet::<T>::initialize_authorities(&self.authorities);
+			EpochConfig::<T>::put(self.epoch_config.clone());
+		}
+	}
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		/// Initialization
+		fn on_initialize(now: BlockNumberFor<T>) -> Weight {
+			Self::do_initialize(now);
+			0
+		}
+
+		/// Block finalization
+		fn on_finalize(_n: BlockNumberFor<T>) {
+			// at the end of the block, we can safely include the new VRF output
+			// into the accumulated randomness. If this block was the first in a new
+			// epoch, the changeover logic has already occurred at this point, so the
+			// accumulator will only contain outputs from the right epoch.
+			if let Some(Some(randomness)) = Initialized::<T>::take() {
+				Self::deposit_randomness(&randomness);
+			}
+		}
+	}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T> {
+		/// Submit the tickets of the authority at `authority_index` for the next epoch. This
+		/// extrinsic must be called unsigned and is validated in `ValidateUnsigned`. Tickets that
+		/// are already known, or higher than all the tickets of a full list, are ignored.
+		#[pallet::weight(<T as Config>::WeightInfo::submit_tickets(tickets.len() as u32))]
+		pub fn submit_tickets(
+			origin: OriginFor<T>,
+			authority_index: AuthorityIndex,
+			tickets: Vec<TicketEnvelope>,
+		) -> DispatchResult {
+			ensure_none(origin)?;
+
+			NextTicketsSubmitters::<T>::insert(authority_index, ());
+			NextTickets::<T>::mutate(|next_tickets| {
+				for envelope in tickets {
+					Self::insert_ticket(next_tickets, envelope.ticket);
+				}
+			});
+
+			Ok(())
+		}
+	}
+
+	#[pallet::validate_unsigned]
+	impl<T: Config> ValidateUnsigned for Pallet<T> {
+		type Call = Call<T>;
+
+		fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+			if let Call::submit_tickets { authority_index, tickets } = call {
+				let submission_end =
+					Self::current_epoch_start().saturating_add(T::EpochDuration::get() / 2);
+				let current_slot = CurrentSlot::<T>::get();
+				if current_slot >= submission_end {
+					return InvalidTransaction::Custom(TICKETS_SUBMISSION_CLOSED).into()
+				}
+
+				let next_epoch = Self::next_epoch();
+				let attempts_number = next_epoch.config.attempts_number;
+				if tickets.is_empty() ||
+					tickets.len() > T::MaxTickets::get().min(attempts_number) as usize
+				{
+					return InvalidTransaction::ExhaustsResources.into()
+				}
+
+				let authority = match next_epoch.authorities.get(*authority_index as usize) {
+					Some((authority, _)) => authority,
+					None => return InvalidTransaction::Custom(UNKNOWN_TICKETS_AUTHORITY).into(),
+				};
+				if NextTicketsSubmitters::<T>::contains_key(authority_index) {
+					return InvalidTransaction::Custom(TICKETS_ALREADY_SUBMITTED).into()
+				}
+
+				let mut attempts =
+					tickets.iter().map(|envelope| envelope.attempt).collect::<Vec<_>>();
+				attempts.sort_unstable();
+				attempts.dedup();
+				if attempts.len() != tickets.len() ||
+					attempts.iter().any(|attempt| *attempt >= attempts_number)
+				{
+					return InvalidTransaction::Custom(INVALID_TICKET_ATTEMPT).into()
+				}
+
+				let threshold = next_epoch.ticket_threshold();
+				if !tickets.iter().all(|envelope| {
+					sp_consensus_sassafras::check_threshold(&envelope.ticket, threshold)
+				}) {
+					return InvalidTransaction::Custom(TICKET_ABOVE_THRESHOLD).into()
+				}
+
+				if !tickets
+					.iter()
+					.all(|envelope| Self::check_ticket_proof(envelope, authority, &next_epoch))
+				{
+					return InvalidTransaction::BadProof.into()
+				}
+
+				ValidTransaction::with_tag_prefix("SassafrasTickets")
+					.priority(TransactionPriority::MAX)
+					.and_provides((next_epoch.epoch_index, authority_index))
+					.longevity(*submission_end.saturating_sub(current_slot))
+					.propagate(true)
+					.build()
+			} else {
+				InvalidTransaction::Call.into()
+			}
+		}
+	}
+}
+
+impl<T: Config> FindAuthor<u32> for Pallet<T> {
+	fn find_author<'a, I>(digests: I) -> Option<u32>
+	where
+		I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+	{
+		for (id, mut data) in digests.into_iter() {
+			if id == SASSAFRAS_ENGINE_ID {
+				let pre_digest: PreDigest = PreDigest::decode(&mut data).ok()?;
+				return Some(pre_digest.authority_index)
+			}
+		}
+
+		None
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// Determine the Sassafras slot duration based on the Timestamp module configuration.
+	pub fn slot_duration() -> T::Moment {
+		// we double the minimum block-period so each author can always propose within
+		// the majority of their slot.
+		<T as pallet_timestamp::Config>::MinimumPeriod::get().saturating_mul(2u32.into())
+	}
+
+	/// Determine whether an epoch change should take place at this block.
+	/// Assumes that initialization has already taken place.
+	pub fn should_epoch_change(now: T::BlockNumber) -> bool {
+		// The epoch has technically ended during the passage of time
+		// between this block and the last, but we have to "end" the epoch now,
+		// since there is no earlier possible block we could have done it.
+		//
+		// The exception is for block 1: the genesis has slot 0, so we treat
+		// epoch 0 as having started at the slot of block 1. We want to use
+		// the same randomness and validator set as signalled in the genesis,
+		// so we don't rotate the epoch.
+		now != One::one() && {
+			let diff = CurrentSlot::<T>::get().saturating_sub(Self::current_epoch_start());
+			*diff >= T::EpochDuration::get()
+		}
+	}
+
+	/// DANGEROUS: Enact an epoch change. Should be done on every block where `should_epoch_change`
+	/// has returned `true`, and the caller is the only caller of this function.
+	///
+	/// Typically, this is not handled directly by the user, but by higher-level validator-set
+	/// manager logic like `pallet-session`.
+	pub fn enact_epoch_change(
+		authorities: WeakBoundedVec<(AuthorityId, SassafrasAuthorityWeight), T::MaxAuthorities>,
+		next_authorities: WeakBoundedVec<
+			(AuthorityId, SassafrasAuthorityWeight),
+			T::MaxAuthorities,
+		>,
+	) {
+		// PRECONDITION: caller has done initialization and is guaranteed
+		// by the session module to be called before this.
+		debug_assert!(Self::initialized().is_some());
+
+		// Update epoch index
+		let epoch_index = EpochIndex::<T>::get()
+			.checked_add(1)
+			.expect("epoch indices will never reach 2^64 before the death of the universe; qed");
+
+		EpochIndex::<T>::put(epoch_index);
+		Authorities::<T>::put(authorities);
+
+		// Update epoch randomness.
+		let next_epoch_index = epoch_index
+			.checked_add(1)
+			.expect("epoch indices will never reach 2^64 before the death of the universe; qed");
+
+		let randomness = Self::randomness_change_epoch(next_epoch_index);
+		Randomness::<T>::put(randomness);
+
+		// Update the next epoch authorities.
+		NextAuthorities::<T>::put(&next_authorities);
+
+		// The tickets submitted during the last epoch now assign the slots.
+		Tickets::<T>::put(NextTickets::<T>::take());
+		NextTicketsSubmitters::<T>::remove_all(None);
+
+		// After we update the current epoch, we signal the *next* epoch change
+		// so that nodes can track changes.
+		let next_epoch = NextEpochDescriptor {
+			authorities: next_authorities.to_vec(),
+			randomness: NextRandomness::<T>::get(),
+		};
+		Self::deposit_consensus(ConsensusLog::NextEpochData(next_epoch));
+	}
+
+	/// Finds the start slot of the current epoch. only guaranteed to
+	/// give correct results after `do_initialize` of the first block
+	/// in the chain (as its result is based off of `GenesisSlot`).
+	pub fn current_epoch_start() -> Slot {
+		Self::epoch_start(EpochIndex::<T>::get())
+	}
+
+	/// Produces information about the current epoch.
+	pub fn current_epoch() -> Epoch {
+		Epoch {
+			epoch_index: EpochIndex::<T>::get(),
+			start_slot: Self::current_epoch_start(),
+			duration: T::EpochDuration::get(),
+			authorities: Self::authorities().to_vec(),
+			randomness: Self::randomness(),
+			config: Self::epoch_config(),
+		}
+	}
+
+	/// Produces information about the next epoch (which was already previously
+	/// announced).
+	pub fn next_epoch() -> Epoch {
+		let next_epoch_index = EpochIndex::<T>::get().checked_add(1).expect(
+			"epoch index is u64; it is always only incremented by one; \
+			 if u64 is not enough we should crash for safety; qed.",
+		);
+
+		Epoch {
+			epoch_index: next_epoch_index,
+			start_slot: Self::epoch_start(next_epoch_index),
+			duration: T::EpochDuration::get(),
+			authorities: NextAuthorities::<T>::get().to_vec(),
+			randomness: NextRandomness::<T>::get(),
+			config: Self::epoch_config(),
+		}
+	}
+
+	/// Returns the ticket assigned to `slot`, if any.
+	///
+	/// The slot must belong to the current epoch, or to the next one if the block claiming it
+	/// enacts the epoch change.
+	pub fn slot_ticket(slot: Slot) -> Option<Ticket> {
+		let duration = T::EpochDuration::get();
+		let slot_index = slot.checked_sub(*Self::current_epoch_start())?;
+
+		let (tickets, slot_index) = if slot_index < duration {
+			(Tickets::<T>::get(), slot_index)
+		} else if slot_index < 2 * duration {
+			(NextTickets::<T>::get(), slot_index - duration)
+		} else {
+			return None
+		};
+
+		let ticket_index = sp_consensus_sassafras::ticket_index(slot_index, duration);
+		tickets.get(ticket_index as usize).copied()
+	}
+
+	/// Submits an extrinsic with the tickets of the authority at `authority_index` for the next
+	/// epoch. Only useful in an offchain context.
+	pub fn submit_tickets_unsigned_extrinsic(
+		authority_index: AuthorityIndex,
+		tickets: Vec<TicketEnvelope>,
+	) -> bool {
+		use frame_system::offchain::SubmitTransaction;
+
+		let call = Call::submit_tickets { authority_index, tickets };
+		match SubmitTransaction::<T, Call<T>>::submit_unsigned_transaction(call.into()) {
+			Ok(()) => true,
+			Err(()) => {
+				log::error!(target: "runtime::sassafras", "Error submitting tickets");
+				false
+			},
+		}
+	}
+
+	fn epoch_config() -> SassafrasEpochConfiguration {
+		EpochConfig::<T>::get()
+			.expect("EpochConfig is initialized in genesis; we never `take` or `kill` it; qed")
+	}
+
+	fn epoch_start(epoch_index: u64) -> Slot {
+		// (epoch_index * epoch_duration) + genesis_slot
+
+		const PROOF: &str = "slot number is u64; it should relate in some way to wall clock time; \
+							 if u64 is not enough we should crash for safety; qed.";
+
+		let epoch_start = epoch_index.checked_mul(T::EpochDuration::get()).expect(PROOF);
+
+		epoch_start.checked_add(*GenesisSlot::<T>::get()).expect(PROOF).into()
+	}
+
+	/// Checks that the ticket of `envelope` is the output of the ticket VRF of `authority` for
+	/// `epoch`.
+	fn check_ticket_proof(
+		envelope: &TicketEnvelope,
+		authority: &AuthorityId,
+		epoch: &Epoch,
+	) -> bool {
+		let transcript = sp_consensus_sassafras::make_ticket_transcript(
+			&epoch.randomness,
+			envelope.attempt,
+			epoch.epoch_index,
+		);
+
+		let output = match schnorrkel::VRFOutput::try_from(envelope.ticket) {
+			Ok(output) => output,
+			Err(_) => return false,
+		};
+		let proof = match schnorrkel::VRFProof::try_from(envelope.proof) {
+			Ok(proof) => proof,
+			Err(_) => return false,
+		};
+		schnorrkel::PublicKey::from_bytes(authority.as_slice())
+			.and_then(|public| public.vrf_verify(transcript, &output, &proof))
+			.is_ok()
+	}
+
+	/// Inserts `ticket` at its place in the sorted `tickets`, dropping the highest ticket if
+	/// the list is full.
+	fn insert_ticket(tickets: &mut BoundedVec<Ticket, T::MaxTickets>, ticket: Ticket) {
+		let max_tickets = (T::MaxTickets::get() as u64).min(T::EpochDuration::get()) as usize;
+		let index = match tickets.binary_search(&ticket) {
+			Ok(_) => return,
+			Err(index) => index,
+		};
+		if index >= max_tickets {
+			return
+		}
+		if tickets.len() >= max_tickets {
+			tickets.remove(tickets.len() - 1);
+		}
+		tickets
+			.try_insert(index, ticket)
+			.expect("a ticket was removed if the list was full; qed");
+	}
+
+	fn deposit_consensus<U: Encode>(new: U) {
+		let log = DigestItem::Consensus(SASSAFRAS_ENGINE_ID, new.encode());
+		<frame_system::Pallet<T>>::deposit_log(log)
+	}
+
+	fn deposit_randomness(randomness: &schnorrkel::Randomness) {
+		RandomnessAccumulator::<T>::mutate(|accumulator| {
+			let mut s = Vec::with_capacity(2 * RANDOMNESS_LENGTH);
+			s.extend_from_slice(&accumulator[..]);
+			s.extend_from_slice(&randomness[..]);
+			*accumulator = sp_io::hashing::blake2_256(&s);
+		});
+	}
+
+	fn do_initialize(now: T::BlockNumber) {
+		// since do_initialize can be called twice (if session module is present)
+		// => let's ensure that we only modify the storage once per block
+		let initialized = Self::initialized().is_some();
+		if initialized {
+			return
+		}
+
+		let maybe_pre_digest: Option<PreDigest> = <frame_system::Pallet<T>>::digest()
+			.logs
+			.iter()
+			.filter_map(|s| s.as_pre_runtime())
+			.filter_map(|(id, mut data)| {
+				if id == SASSAFRAS_ENGINE_ID {
+					PreDigest::decode(&mut data).ok()
+				} else {
+					None
+				}
+			})
+			.next();
+
+		let maybe_randomness: MaybeRandomness = maybe_pre_digest.and_then(|digest| {
+			// on the first non-zero block (i.e. block #1)
+			// this is where the first epoch (epoch #0) actually starts.
+			// we need to adjust internal storage accordingly.
+			if *GenesisSlot::<T>::get() == 0 {
+				GenesisSlot::<T>::put(digest.slot);
+				debug_assert_ne!(*GenesisSlot::<T>::get(), 0);
+
+				// deposit a log because this is the first block in epoch #0
+				// we use the same values as genesis because we haven't collected any
+				// randomness yet.
+				let next = NextEpochDescriptor {
+					authorities: Self::authorities().to_vec(),
+					randomness: Self::randomness(),
+				};
+
+				Self::deposit_consensus(ConsensusLog::NextEpochData(next))
+			}
+
+			CurrentSlot::<T>::put(digest.slot);
+
+			// Reconstruct the bytes of VRFInOut using the authority id.
+			Authorities::<T>::get()
+				.get(digest.authority_index as usize)
+				.and_then(|author| schnorrkel::PublicKey::from_bytes(author.0.as_slice()).ok())
+				.and_then(|pubkey| {
+					let transcript = sp_consensus_sassafras::make_slot_transcript(
+						&Self::randomness(),
+						digest.slot,
+						EpochIndex::<T>::get(),
+					);
+
+					digest.vrf_output.0.attach_input_hash(&pubkey, transcript).ok()
+				})
+				.map(|inout| inout.make_bytes(sp_consensus_sassafras::SASSAFRAS_VRF_INOUT_CONTEXT))
+		});
+
+		// The VRF output is placed in the `Initialized` storage item and it'll be put
+		// into the accumulated randomness later, once we've decided which epoch this
+		// block is in.
+		Initialized::<T>::put(maybe_randomness);
+
+		// enact epoch change, if necessary.
+		T::EpochChangeTrigger::trigger::<T>(now)
+	}
+
+	/// Call this function exactly once when an epoch changes, to update the
+	/// randomness. Returns the new randomness.
+	fn randomness_change_epoch(next_epoch_index: u64) -> schnorrkel::Randomness {
+		let this_randomness = NextRandomness::<T>::get();
+		let accumulator = RandomnessAccumulator::<T>::take();
+
+		let mut s = Vec::with_capacity(2 * RANDOMNESS_LENGTH + 8);
+		s.extend_from_slice(&this_randomness);
+		s.extend_from_slice(&next_epoch_index.to_le_bytes());
+		s.extend_from_slice(&accumulator);
+		NextRandomness::<T>::put(sp_io::hashing::blake2_256(&s));
+
+		this_randomness
+	}
+
+	fn initialize_authorities(authorities: &[(AuthorityId, SassafrasAuthorityWeight)]) {
+		if !authorities.is_empty() {
+			assert!(Authorities::<T>::get().is_empty(), "Authorities are already initialized!");
+			let bounded_authorities =
+				WeakBoundedVec::<_, T::MaxAuthorities>::try_from(authorities.to_vec())
+					.expect("Initial number of authorities should be lower than T::MaxAuthorities");
+			Authorities::<T>::put(&bounded_authorities);
+			NextAuthorities::<T>::put(&bounded_authorities);
+		}
+	}
+}
+
+impl<T: Config> OnTimestampSet<T::Moment> for Pallet<T> {
+	fn on_timestamp_set(moment: T::Moment) {
+		let slot_duration = Self::slot_duration();
+		assert!(!slot_duration.is_zero(), "Sassafras slot duration cannot be zero.");
+
+		let timestamp_slot = moment / slot_duration;
+		let timestamp_slot = Slot::from(timestamp_slot.saturated_into::<u64>());
+
+		assert!(
+			CurrentSlot::<T>::get() == timestamp_slot,
+			"Timestamp slot must match `CurrentSlot`"
+		);
+	}
+}
+
+impl<T: Config> sp_runtime::BoundToRuntimeAppPublic for Pallet<T> {
+	type Public = AuthorityId;
+}
+
+impl<T: Config> OneSessionHandler<T::AccountId> for Pallet<T> {
+	type Key = AuthorityId;
+
+	fn on_genesis_session<'a, I: 'a>(validators: I)
+	where
+		I: Iterator<Item = (&'a T::AccountId, AuthorityId)>,
+	{
+		let authorities = validators.map(|(_, k)| (k, 1)).collect::<Vec<_>>();
+		Self::initialize_authorities(&authorities);
+	}
+
+	fn on_new_session<'a, I: 'a>(_changed: bool, validators: I, queued_validators: I)
+	where
+		I: Iterator<Item = (&'a T::AccountId, AuthorityId)>,
+	{
+		let authorities = validators.map(|(_account, k)| (k, 1)).collect::<Vec<_>>();
+		let bounded_authorities = WeakBoundedVec::<_, T::MaxAuthorities>::force_from(
+			authorities,
+			Some(
+				"Warning: The session has more validators than expected. \
+				A runtime configuration adjustment may be needed.",
+			),
+		);
+
+		let next_authorities = queued_validators.map(|(_account, k)| (k, 1)).collect::<Vec<_>>();
+		let next_bounded_authorities = WeakBoundedVec::<_, T::MaxAuthorities>::force_from(
+			next_authorities,
+			Some(
+				"Warning: The session has more queued validators than expected. \
+				A runtime configuration adjustment may be needed.",
+			),
+		);
+
+		Self::enact_epoch_change(bounded_authorities, next_bounded_authorities)
+	}
+
+	fn on_disabled(i: u32) {
+		Self::deposit_consensus(ConsensusLog::OnDisabled(i))
+	}
+}
@@ -0,0 +1,214 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Test utilities
+
+use crate::{self as pallet_sassafras, Config, SameAuthoritiesForever};
+use codec::Encode;
+use frame_support::{
+	parameter_types,
+	traits::{GenesisBuild, OnFinalize, OnInitialize},
+};
+use frame_system::InitKind;
+use sp_consensus_sassafras::{
+	digests::PreDigest, AuthorityIndex, AuthorityPair, SassafrasEpochConfiguration, Slot, Ticket,
+	TicketEnvelope, VRFOutput, VRFProof, SASSAFRAS_ENGINE_ID, VRF_OUTPUT_LENGTH, VRF_PROOF_LENGTH,
+};
+use sp_core::{
+	crypto::{IsWrappedBy, Pair},
+	H256, U256,
+};
+use sp_runtime::{
+	testing::{Digest, DigestItem, Header, TestXt},
+	traits::IdentityLookup,
+};
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+frame_support::construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+		Timestamp: pallet_timestamp::{Pallet, Call, Storage, Inherent},
+		Sassafras: pallet_sassafras::{Pallet, Call, Storage, Config, ValidateUnsigned},
+	}
+);
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const MinimumPeriod: u64 = 1;
+}
+
+impl frame_system::Config for Test {
+	type BaseCallFilter = frame_support::traits::Everything;
+	type BlockWeights = ();
+	type BlockLength = ();
+	type DbWeight = ();
+	type Origin = Origin;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Call = Call;
+	type Hash = H256;
+	type Hashing = ::sp_runtime::traits::BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = Event;
+	type BlockHashCount = BlockHashCount;
+	type Version = ();
+	type PalletInfo = PalletInfo;
+	type AccountData = ();
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+	type SS58Prefix = ();
+	type OnSetCode = ();
+}
+
+impl<C> frame_system::offchain::SendTransactionTypes<C> for Test
+where
+	Call: From<C>,
+{
+	type OverarchingCall = Call;
+	type Extrinsic = TestXt<Call, ()>;
+}
+
+impl pallet_timestamp::Config for Test {
+	type Moment = u64;
+	type OnTimestampSet = Sassafras;
+	type MinimumPeriod = MinimumPeriod;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub const EpochDuration: u64 = 10;
+	pub const MaxAuthorities: u32 = 10;
+	pub const MaxTickets: u32 = 6;
+}
+
+impl Config for Test {
+	type EpochDuration = EpochDuration;
+	type EpochChangeTrigger = SameAuthoritiesForever;
+	type WeightInfo = ();
+	type MaxAuthorities = MaxAuthorities;
+	type MaxTickets = MaxTickets;
+}
+
+/// Initializes the block `n` at slot `s`, finalizing the current one.
+pub fn go_to_block(n: u64, s: u64, pair: &AuthorityPair) {
+	Sassafras::on_finalize(System::block_number());
+
+	let parent_hash = if System::block_number() > 1 {
+		let hdr = System::finalize();
+		hdr.hash()
+	} else {
+		System::parent_hash()
+	};
+
+	let pre_digest = make_pre_digest(0, s.into(), pair);
+
+	System::initialize(&n, &parent_hash, &pre_digest, InitKind::Full);
+
+	Sassafras::on_initialize(n);
+}
+
+/// Slots will grow accordingly to blocks
+pub fn progress_to_block(n: u64, pair: &AuthorityPair) {
+	let mut slot = u64::from(Sassafras::current_slot()) + 1;
+	for i in System::block_number() + 1..=n {
+		go_to_block(i, slot, pair);
+		slot += 1;
+	}
+}
+
+pub fn make_pre_digest(
+	authority_index: AuthorityIndex,
+	slot: Slot,
+	pair: &AuthorityPair,
+) -> Digest {
+	let pair = sp_core::sr25519::Pair::from_ref(pair).as_ref();
+	let transcript = sp_consensus_sassafras::make_slot_transcript(
+		&Sassafras::randomness(),
+		slot,
+		Sassafras::epoch_index(),
+	);
+	let vrf_inout = pair.vrf_sign(transcript);
+	let digest_data = PreDigest {
+		authority_index,
+		slot,
+		vrf_output: VRFOutput(vrf_inout.0.to_output()),
+		vrf_proof: VRFProof(vrf_inout.1),
+		ticket_aux: None,
+	};
+	let log = DigestItem::PreRuntime(SASSAFRAS_ENGINE_ID, digest_data.encode());
+	Digest { logs: vec![log] }
+}
+
+/// A ticket far below the threshold of the test epochs.
+pub fn make_ticket(i: u8) -> Ticket {
+	let mut ticket = [0u8; VRF_OUTPUT_LENGTH];
+	ticket[0] = i;
+	ticket
+}
+
+/// An envelope of [`make_ticket`] without a valid proof, for calls which are not validated.
+pub fn make_ticket_envelope(i: u8) -> TicketEnvelope {
+	TicketEnvelope { ticket: make_ticket(i), attempt: i as u32, proof: [0; VRF_PROOF_LENGTH] }
+}
+
+/// Generates the tickets of `pair` for all the attempts of the next epoch, whether they are
+/// below the threshold or not.
+pub fn make_ticket_envelopes(pair: &AuthorityPair) -> Vec<TicketEnvelope> {
+	let pair = sp_core::sr25519::Pair::from_ref(pair).as_ref();
+	let epoch = Sassafras::next_epoch();
+	(0..epoch.config.attempts_number)
+		.map(|attempt| {
+			let transcript = sp_consensus_sassafras::make_ticket_transcript(
+				&epoch.randomness,
+				attempt,
+				epoch.epoch_index,
+			);
+			let (inout, proof, _) = pair.vrf_sign(transcript);
+			TicketEnvelope {
+				ticket: inout.to_output().to_bytes(),
+				attempt,
+				proof: proof.to_bytes(),
+			}
+		})
+		.collect()
+}
+
+pub fn new_test_ext_with_pairs(
+	authorities_len: usize,
+) -> (Vec<AuthorityPair>, sp_io::TestExternalities) {
+	let pairs = (0..authorities_len)
+		.map(|i| AuthorityPair::from_seed(&U256::from(i).into()))
+		.collect::<Vec<_>>();
+
+	let mut t = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+
+	let config = pallet_sassafras::GenesisConfig {
+		authorities: pairs.iter().map(|p| (p.public(), 1)).collect(),
+		epoch_config: SassafrasEpochConfiguration::default(),
+	};
+	GenesisBuild::<Test>::assimilate_storage(&config, &mut t).unwrap();
+
+	(pairs, t.into())
+}
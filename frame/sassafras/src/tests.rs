@@ -0,0 +1,264 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Consensus extension module tests for Sassafras consensus.
+
+use super::{Call, *};
+use frame_support::{assert_ok, traits::OnFinalize, unsigned::ValidateUnsigned};
+use mock::*;
+use sp_consensus_sassafras::{check_threshold, digests::CompatibleDigestItem, AuthorityPair};
+use sp_core::crypto::Pair;
+use sp_runtime::transaction_validity::TransactionValidityError;
+
+fn validate_tickets(
+	authority_index: AuthorityIndex,
+	tickets: Vec<TicketEnvelope>,
+) -> TransactionValidity {
+	<Sassafras as ValidateUnsigned>::validate_unsigned(
+		TransactionSource::External,
+		&Call::submit_tickets { authority_index, tickets },
+	)
+}
+
+/// Returns the index of the first authority with at least two tickets below the threshold of
+/// the next epoch, along with these tickets.
+fn valid_tickets(pairs: &[AuthorityPair]) -> (AuthorityIndex, Vec<TicketEnvelope>) {
+	let threshold = Sassafras::next_epoch().ticket_threshold();
+	pairs
+		.iter()
+		.enumerate()
+		.map(|(index, pair)| {
+			let tickets = make_ticket_envelopes(pair)
+				.into_iter()
+				.filter(|envelope| check_threshold(&envelope.ticket, threshold))
+				.collect::<Vec<_>>();
+			(index as AuthorityIndex, tickets)
+		})
+		.find(|(_, tickets)| tickets.len() >= 2)
+		.expect("every authority has 2.5 valid tickets on average")
+}
+
+#[test]
+fn first_block_epoch_zero_start() {
+	let (pairs, mut ext) = new_test_ext_with_pairs(4);
+
+	ext.execute_with(|| {
+		let genesis_slot = Slot::from(100);
+		go_to_block(1, 100, &pairs[0]);
+
+		assert_eq!(Sassafras::genesis_slot(), genesis_slot);
+		assert_eq!(Sassafras::current_slot(), genesis_slot);
+		assert_eq!(Sassafras::epoch_index(), 0);
+		assert_eq!(Sassafras::current_epoch_start(), genesis_slot);
+		assert!(Sassafras::initialized().unwrap().is_some());
+
+		Sassafras::on_finalize(1);
+		let header = System::finalize();
+
+		assert!(Sassafras::initialized().is_none());
+		assert_ne!(RandomnessAccumulator::<Test>::get(), [0; RANDOMNESS_LENGTH]);
+
+		let next_epoch = header
+			.digest
+			.logs
+			.iter()
+			.find_map(|log| log.as_next_epoch_descriptor())
+			.expect("the first block announces epoch #1");
+		assert_eq!(
+			next_epoch.authorities,
+			pairs.iter().map(|p| (p.public(), 1)).collect::<Vec<_>>()
+		);
+	})
+}
+
+#[test]
+fn tickets_are_sorted_and_bounded() {
+	let (pairs, mut ext) = new_test_ext_with_pairs(4);
+
+	ext.execute_with(|| {
+		go_to_block(1, 100, &pairs[0]);
+
+		assert_ok!(Sassafras::submit_tickets(
+			Origin::none(),
+			0,
+			vec![make_ticket_envelope(5), make_ticket_envelope(1), make_ticket_envelope(3)]
+		));
+		assert_eq!(
+			Sassafras::next_tickets().to_vec(),
+			vec![make_ticket(1), make_ticket(3), make_ticket(5)]
+		);
+
+		// duplicates are ignored and only the `MaxTickets` lowest tickets are kept.
+		assert_ok!(Sassafras::submit_tickets(
+			Origin::none(),
+			1,
+			[7, 3, 0, 6, 2].into_iter().map(make_ticket_envelope).collect(),
+		));
+		assert_ok!(Sassafras::submit_tickets(Origin::none(), 2, vec![make_ticket_envelope(4)]));
+		assert_eq!(Sassafras::next_tickets().to_vec(), (0..6).map(make_ticket).collect::<Vec<_>>());
+		assert!(Sassafras::tickets().is_empty());
+	})
+}
+
+#[test]
+fn tickets_submission_is_validated() {
+	let (pairs, mut ext) = new_test_ext_with_pairs(4);
+
+	ext.execute_with(|| {
+		go_to_block(1, 100, &pairs[0]);
+
+		let (index, tickets) = valid_tickets(&pairs);
+		assert_ok!(validate_tickets(index, tickets.clone()));
+		assert_eq!(
+			validate_tickets(index, vec![]),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::ExhaustsResources)),
+		);
+		assert_eq!(
+			validate_tickets(index, make_ticket_envelopes(&pairs[index as usize])),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::ExhaustsResources)),
+		);
+
+		let threshold = Sassafras::next_epoch().ticket_threshold();
+		let above_threshold = make_ticket_envelopes(&pairs[index as usize])
+			.into_iter()
+			.find(|envelope| !check_threshold(&envelope.ticket, threshold))
+			.unwrap();
+		assert_eq!(
+			validate_tickets(index, vec![tickets[0].clone(), above_threshold]),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::Custom(
+				TICKET_ABOVE_THRESHOLD
+			))),
+		);
+
+		// the submission is closed during the second half of the epoch.
+		progress_to_block(5, &pairs[0]);
+		assert_eq!(Sassafras::current_slot(), Slot::from(104));
+		assert_ok!(validate_tickets(index, tickets.clone()));
+
+		progress_to_block(6, &pairs[0]);
+		assert_eq!(
+			validate_tickets(index, tickets),
+			Err(TransactionValidityError::Invalid(InvalidTransaction::Custom(
+				TICKETS_SUBMISSION_CLOSED
+			))),
+		);
+	})
+}
+
+#[test]
+fn forged_tickets_are_rejected() {
+	let (pairs, mut ext) = new_test_ext_with_pairs(4);
+
+	ext.execute_with(|| {
+		go_to_block(1, 100, &pairs[0]);
+
+		let (index, tickets) = valid_tickets(&pairs);
+		let invalid = |error| Err(TransactionValidityError::Invalid(error));
+
+		// a ticket far below the threshold, with the proof of another ticket.
+		let mut forged = tickets[0].clone();
+		forged.ticket = make_ticket(0);
+		assert_eq!(validate_tickets(index, vec![forged]), invalid(InvalidTransaction::BadProof));
+
+		// a valid ticket with the attempt number of another one.
+		let mut forged = tickets[0].clone();
+		forged.attempt = tickets[1].attempt;
+		assert_eq!(validate_tickets(index, vec![forged]), invalid(InvalidTransaction::BadProof));
+
+		// the tickets of another authority.
+		assert_eq!(
+			validate_tickets((index + 1) % 4, tickets.clone()),
+			invalid(InvalidTransaction::BadProof),
+		);
+		assert_eq!(
+			validate_tickets(4, tickets.clone()),
+			invalid(InvalidTransaction::Custom(UNKNOWN_TICKETS_AUTHORITY)),
+		);
+
+		// more than one ticket per attempt.
+		let attempts_number = Sassafras::next_epoch().config.attempts_number;
+		let mut forged = tickets[0].clone();
+		forged.attempt = attempts_number;
+		assert_eq!(
+			validate_tickets(index, vec![forged]),
+			invalid(InvalidTransaction::Custom(INVALID_TICKET_ATTEMPT)),
+		);
+		assert_eq!(
+			validate_tickets(index, vec![tickets[0].clone(), tickets[0].clone()]),
+			invalid(InvalidTransaction::Custom(INVALID_TICKET_ATTEMPT)),
+		);
+
+		// the tickets of an authority are only accepted once per epoch.
+		assert_ok!(validate_tickets(index, tickets.clone()));
+		assert_ok!(Sassafras::submit_tickets(Origin::none(), index, tickets[..1].to_vec()));
+		assert_eq!(
+			validate_tickets(index, tickets[1..].to_vec()),
+			invalid(InvalidTransaction::Custom(TICKETS_ALREADY_SUBMITTED)),
+		);
+
+		progress_to_block(11, &pairs[0]);
+		assert_eq!(Sassafras::epoch_index(), 1);
+		assert!(!NextTicketsSubmitters::<Test>::contains_key(index));
+	})
+}
+
+#[test]
+fn epoch_change_enacts_next_tickets() {
+	let (pairs, mut ext) = new_test_ext_with_pairs(4);
+
+	ext.execute_with(|| {
+		go_to_block(1, 100, &pairs[0]);
+
+		assert_ok!(Sassafras::submit_tickets(
+			Origin::none(),
+			0,
+			(0..6).map(make_ticket_envelope).collect()
+		));
+
+		// the tickets are assigned from the outside in, and the middle slots have none.
+		assert_eq!(Sassafras::slot_ticket(105.into()), None);
+		assert_eq!(Sassafras::slot_ticket(110.into()), Some(make_ticket(1)));
+		assert_eq!(Sassafras::slot_ticket(111.into()), Some(make_ticket(3)));
+		assert_eq!(Sassafras::slot_ticket(112.into()), Some(make_ticket(5)));
+		assert_eq!(Sassafras::slot_ticket(113.into()), None);
+		assert_eq!(Sassafras::slot_ticket(117.into()), Some(make_ticket(4)));
+		assert_eq!(Sassafras::slot_ticket(119.into()), Some(make_ticket(0)));
+		assert_eq!(Sassafras::slot_ticket(120.into()), None);
+
+		let next_randomness = NextRandomness::<Test>::get();
+		progress_to_block(11, &pairs[0]);
+
+		assert_eq!(Sassafras::epoch_index(), 1);
+		assert_eq!(Sassafras::current_epoch_start(), Slot::from(110));
+		assert_eq!(Sassafras::randomness(), next_randomness);
+		assert_ne!(NextRandomness::<Test>::get(), next_randomness);
+		assert_eq!(Sassafras::tickets().to_vec(), (0..6).map(make_ticket).collect::<Vec<_>>());
+		assert!(Sassafras::next_tickets().is_empty());
+
+		assert_eq!(Sassafras::slot_ticket(105.into()), None);
+		assert_eq!(Sassafras::slot_ticket(110.into()), Some(make_ticket(1)));
+		assert_eq!(Sassafras::slot_ticket(120.into()), None);
+
+		let header = System::finalize();
+		let next_epoch = header
+			.digest
+			.logs
+			.iter()
+			.find_map(|log| log.as_next_epoch_descriptor())
+			.expect("the first block of epoch #1 announces epoch #2");
+		assert_eq!(next_epoch.randomness, NextRandomness::<Test>::get());
+	})
+}
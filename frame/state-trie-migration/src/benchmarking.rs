@@ -0,0 +1,161 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks for the state trie migration pallet.
+
+use super::*;
+use frame_benchmarking::{benchmarks, whitelisted_caller};
+use frame_support::{assert_ok, traits::Currency};
+use frame_system::RawOrigin;
+use sp_runtime::traits::Saturating;
+use sp_std::prelude::*;
+
+use crate::Pallet as StateTrieMigration;
+
+const KEY: &[u8] = b"key";
+
+/// A signed caller that can afford the deposit of any signed migration.
+fn funded_caller<T: Config>() -> T::AccountId {
+	let caller: T::AccountId = whitelisted_caller();
+	let stash = T::Currency::minimum_balance()
+		.saturating_mul(1000u32.into())
+		.saturating_add(StateTrieMigration::<T>::deposit_for(1024));
+	T::Currency::make_free_balance_be(&caller, stash);
+	SignedMigrationMaxLimits::<T>::put(MigrationLimits { size: 1024, item: 1024 });
+	caller
+}
+
+benchmarks! {
+	continue_migrate {
+		// note that this benchmark should migrate nothing, as we only want the overhead weight of
+		// the bookkeeping, and the migration cost itself is noted via the `dynamic_weight`
+		// function.
+		let null = MigrationLimits::default();
+		let caller = funded_caller::<T>();
+	}: _(RawOrigin::Signed(caller), null, 0, StateTrieMigration::<T>::migration_process())
+	verify {
+		assert_eq!(StateTrieMigration::<T>::migration_process(), Default::default())
+	}
+
+	continue_migrate_wrong_witness {
+		let null = MigrationLimits::default();
+		let caller = funded_caller::<T>();
+		let bad_witness = MigrationTask {
+			progress_top: Progress::LastKey(vec![1u8]),
+			progress_child: Progress::LastKey(vec![1u8]),
+			..Default::default()
+		};
+	}: {
+		assert!(
+			StateTrieMigration::<T>::continue_migrate(
+				RawOrigin::Signed(caller).into(),
+				null,
+				0,
+				bad_witness,
+			)
+			.is_err()
+		)
+	}
+	verify {
+		assert_eq!(StateTrieMigration::<T>::migration_process(), Default::default())
+	}
+
+	migrate_custom_top_success {
+		let caller = funded_caller::<T>();
+		let stash = T::Currency::free_balance(&caller);
+	}: migrate_custom_top(RawOrigin::Signed(caller.clone()), Default::default(), 0)
+	verify {
+		assert_eq!(StateTrieMigration::<T>::migration_process(), Default::default());
+		assert_eq!(T::Currency::free_balance(&caller), stash)
+	}
+
+	migrate_custom_top_fail {
+		let caller = funded_caller::<T>();
+		let stash = T::Currency::free_balance(&caller);
+		// for tests, we need to make sure there is _something_ in storage that is being
+		// migrated.
+		sp_io::storage::set(KEY, &vec![1u8; 33]);
+	}: {
+		assert_ok!(
+			StateTrieMigration::<T>::migrate_custom_top(
+				RawOrigin::Signed(caller.clone()).into(),
+				vec![KEY.to_vec()],
+				1,
+			)
+		);
+	}
+	verify {
+		assert_eq!(StateTrieMigration::<T>::migration_process(), Default::default());
+		// must have gotten slashed
+		assert!(T::Currency::free_balance(&caller) < stash)
+	}
+
+	migrate_custom_child_success {
+		let caller = funded_caller::<T>();
+		let stash = T::Currency::free_balance(&caller);
+		sp_io::default_child_storage::set(KEY, KEY, &vec![1u8; 33]);
+	}: migrate_custom_child(
+		RawOrigin::Signed(caller.clone()),
+		KEY.to_vec(),
+		vec![KEY.to_vec()],
+		33
+	)
+	verify {
+		assert_eq!(StateTrieMigration::<T>::migration_process(), Default::default());
+		assert_eq!(T::Currency::free_balance(&caller), stash);
+	}
+
+	migrate_custom_child_fail {
+		let caller = funded_caller::<T>();
+		let stash = T::Currency::free_balance(&caller);
+		// for tests, we need to make sure there is _something_ in storage that is being
+		// migrated.
+		sp_io::default_child_storage::set(KEY, KEY, &vec![1u8; 33]);
+	}: {
+		assert_ok!(
+			StateTrieMigration::<T>::migrate_custom_child(
+				RawOrigin::Signed(caller.clone()).into(),
+				KEY.to_vec(),
+				vec![KEY.to_vec()],
+				1,
+			)
+		);
+	}
+	verify {
+		assert_eq!(StateTrieMigration::<T>::migration_process(), Default::default());
+		// must have gotten slashed
+		assert!(T::Currency::free_balance(&caller) < stash)
+	}
+
+	process_top_key {
+		let v in 1 .. (4 * 1024 * 1024);
+
+		let value = sp_std::vec![1u8; v as usize];
+		sp_io::storage::set(KEY, &value);
+	}: {
+		let data = sp_io::storage::get(KEY).unwrap();
+		sp_io::storage::set(KEY, &data);
+		let _next = sp_io::storage::next_key(KEY);
+		assert_eq!(data, value);
+	}
+
+	impl_benchmark_test_suite!(
+		StateTrieMigration,
+		crate::mock::new_test_ext(sp_runtime::StateVersion::V0),
+		crate::mock::Test
+	);
+}
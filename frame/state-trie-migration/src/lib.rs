@@ -0,0 +1,752 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! # Pallet State Trie Migration
+//!
+//! Reads and writes all keys and values in the entire state in a systematic way. This is useful for
+//! upgrading a chain to a new trie layout, such as [`sp_runtime::StateVersion::V1`], where a value
+//! is only stored with the new encoding once it has been written again.
+//!
+//! ## Migration Types
+//!
+//! This pallet provides 2 ways to do this, each of which is suited for a particular use-case, and
+//! can be enabled independently.
+//!
+//! ### Auto migration
+//!
+//! This system will try and migrate all keys by continuously using `on_initialize`. It is only
+//! sensible for a relay chain or a solo chain, where going slightly over weight is not a problem.
+//! It can be configured so that the migration takes at most `n` items and tries to not go over `x`
+//! bytes, but the latter is not guaranteed.
+//!
+//! For example, if a chain contains keys of 1 byte size, the `on_initialize` could read up to `x -
+//! 1` bytes from `n` different keys, while the next key is suddenly `:code:`, and there is no way
+//! to bail out of this.
+//!
+//! ### Signed migration
+//!
+//! As a backup, the migration process can be set in motion via signed transactions that basically
+//! say in advance how many items and how many bytes they will consume, and pay for it as well.
+//! This can be a good safe alternative, if the former system is not desirable.
+//!
+//! The (minor) caveat of this approach is that we cannot know in advance how many bytes reading a
+//! certain number of keys will incur. To overcome this, the runtime needs to configure this pallet
+//! with a `SignedDepositPerItem`. This is the per-item deposit that the origin of the signed
+//! migration transactions need to have in their account (on top of the normal fee) and if the size
+//! witness data that they claim is incorrect, this deposit is slashed.
+//!
+//! ---
+//!
+//! Initially, this pallet does not contain any auto migration. They must be manually enabled by
+//! the `ControlOrigin`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "runtime-benchmarks")]
+mod benchmarking;
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+pub mod weights;
+
+pub use pallet::*;
+pub use weights::WeightInfo;
+
+const LOG_TARGET: &'static str = "runtime::state-trie-migration";
+
+// syntactic sugar for logging.
+macro_rules! log {
+	($level:tt, $patter:expr $(, $values:expr)* $(,)?) => {
+		log::$level!(
+			target: crate::LOG_TARGET,
+			concat!("🚚 ", $patter) $(, $values)*
+		)
+	};
+}
+
+#[frame_support::pallet]
+pub mod pallet {
+	use frame_support::{
+		dispatch::{DispatchErrorWithPostInfo, DispatchResultWithPostInfo, PostDispatchInfo},
+		pallet_prelude::*,
+		traits::{Currency, Get},
+	};
+	use frame_system::pallet_prelude::*;
+	use sp_core::storage::well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX;
+	use sp_runtime::traits::{Saturating, Zero};
+	use sp_std::prelude::*;
+
+	use crate::WeightInfo;
+
+	pub(crate) type BalanceOf<T> =
+		<<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+
+	/// The progress of either the top or child keys.
+	#[derive(Clone, Encode, Decode, scale_info::TypeInfo, PartialEq, Eq, RuntimeDebug)]
+	pub enum Progress {
+		/// Yet to begin.
+		ToStart,
+		/// Ongoing, with the last key given.
+		LastKey(Vec<u8>),
+		/// All done.
+		Complete,
+	}
+
+	impl Default for Progress {
+		fn default() -> Self {
+			Progress::ToStart
+		}
+	}
+
+	/// A migration task stored in state.
+	///
+	/// It tracks the last top and child keys read.
+	#[derive(Clone, Encode, Decode, scale_info::TypeInfo, PartialEq, Eq, Default, RuntimeDebug)]
+	pub struct MigrationTask {
+		/// The current top trie migration progress.
+		pub progress_top: Progress,
+		/// The current child trie migration progress.
+		///
+		/// If `ToStart`, no further top keys are processed until the child key migration is
+		/// `Complete`.
+		pub progress_child: Progress,
+
+		/// A tracker for the total size of values read over the lifetime of the migration.
+		pub size: u32,
+		/// A tracker for the total number of top keys migrated over the lifetime of the migration.
+		pub top_items: u32,
+		/// A tracker for the total number of child keys migrated over the lifetime of the
+		/// migration.
+		pub child_items: u32,
+
+		/// Dynamic counter for the number of top items processed in the current run.
+		#[codec(skip)]
+		pub(crate) dyn_top_items: u32,
+		/// Dynamic counter for the number of child items processed in the current run.
+		#[codec(skip)]
+		pub(crate) dyn_child_items: u32,
+		/// Dynamic counter for the byte size of values processed in the current run.
+		#[codec(skip)]
+		pub(crate) dyn_size: u32,
+	}
+
+	impl MigrationTask {
+		/// Return true if the task is finished.
+		pub(crate) fn finished(&self) -> bool {
+			self.progress_top == Progress::Complete
+		}
+
+		/// Check if there's any work left, or if we have exhausted the limits already.
+		fn exhausted(&self, limits: MigrationLimits) -> bool {
+			self.dyn_total_items() >= limits.item || self.dyn_size >= limits.size
+		}
+
+		/// get the total number of keys affected by the current task.
+		pub(crate) fn dyn_total_items(&self) -> u32 {
+			self.dyn_child_items.saturating_add(self.dyn_top_items)
+		}
+
+		/// Migrate keys until either of the given limits are exhausted, or if no more top keys
+		/// exist.
+		///
+		/// Note that this can return after the **first** migration tick that causes exhaustion,
+		/// specifically in the case of the `size` constrain. The reason for this is that before
+		/// reading a key, we simply cannot know how many bytes it is. In other words, this should
+		/// not be used in any environment where resources are strictly bounded (e.g. a parachain),
+		/// but it is acceptable otherwise (relay chain, offchain workers).
+		pub(crate) fn migrate_until_exhaustion(&mut self, limits: MigrationLimits) {
+			log!(debug, "running migrations on top of {:?} until {:?}", self, limits);
+
+			if limits.item.is_zero() || limits.size.is_zero() {
+				// handle this minor edge case, else we would call `migrate_tick` at least once.
+				log!(warn, "limits are zero. stopping");
+				return
+			}
+
+			while !self.exhausted(limits) && !self.finished() {
+				self.migrate_tick();
+			}
+
+			// accumulate dynamic data into the storage items.
+			self.size = self.size.saturating_add(self.dyn_size);
+			self.child_items = self.child_items.saturating_add(self.dyn_child_items);
+			self.top_items = self.top_items.saturating_add(self.dyn_top_items);
+			log!(debug, "finished with {:?}", self);
+		}
+
+		/// Migrate AT MOST ONE KEY. This can be either a top or a child key.
+		///
+		/// This function is *the* core of this entire pallet.
+		fn migrate_tick(&mut self) {
+			match (&self.progress_top, &self.progress_child) {
+				(Progress::ToStart, _) => {
+					self.migrate_top();
+				},
+				(Progress::LastKey(_), Progress::LastKey(_)) => {
+					// we're in the middle of doing work on a child tree.
+					self.migrate_child();
+				},
+				(Progress::LastKey(top_key), Progress::ToStart) => {
+					if !top_key.starts_with(DEFAULT_CHILD_STORAGE_KEY_PREFIX) {
+						// we continue the top key migrations.
+						self.migrate_top();
+					} else {
+						// this is the root of a child key, and we start processing child keys (and
+						// should call `migrate_child`).
+						self.migrate_child();
+					}
+				},
+				(Progress::LastKey(_), Progress::Complete) => {
+					// we're done with migrating a child-root.
+					self.migrate_top();
+					self.progress_child = Progress::ToStart;
+				},
+				(Progress::Complete, _) => {
+					// nada
+				},
+			}
+		}
+
+		/// Migrate the current child key, setting it to its new value, if one exists.
+		///
+		/// It updates the dynamic counters.
+		fn migrate_child(&mut self) {
+			use sp_io::default_child_storage as child_io;
+			let (maybe_current_child, child_root) = match (&self.progress_child, &self.progress_top)
+			{
+				(Progress::LastKey(last_child), Progress::LastKey(last_top)) => {
+					let child_root = Self::child_storage_key(last_top);
+					let maybe_current_child = child_io::next_key(child_root, last_child);
+					(maybe_current_child, child_root)
+				},
+				(Progress::ToStart, Progress::LastKey(last_top)) => {
+					let child_root = Self::child_storage_key(last_top);
+					// Start with the empty key as first key.
+					(Some(Vec::new()), child_root)
+				},
+				_ => {
+					// defensive: there must be an ongoing top migration.
+					log!(
+						error,
+						"cannot migrate child key with top progress {:?}",
+						self.progress_top
+					);
+					debug_assert!(
+						false,
+						"cannot migrate child key without an ongoing top migration"
+					);
+					return
+				},
+			};
+
+			if let Some(current_child) = maybe_current_child.as_ref() {
+				let added_size = if let Some(data) = child_io::get(child_root, current_child) {
+					child_io::set(child_root, current_child, &data);
+					data.len() as u32
+				} else {
+					Zero::zero()
+				};
+				self.dyn_size = self.dyn_size.saturating_add(added_size);
+				self.dyn_child_items.saturating_inc();
+			}
+
+			log!(trace, "migrated a child key, next_child_key: {:?}", maybe_current_child);
+			self.progress_child = match maybe_current_child {
+				Some(last_child) => Progress::LastKey(last_child),
+				None => Progress::Complete,
+			}
+		}
+
+		/// Convert a child root key, aka. "Child-bearing top key", into the storage key of the
+		/// child trie.
+		fn child_storage_key(top_key: &[u8]) -> &[u8] {
+			top_key.strip_prefix(DEFAULT_CHILD_STORAGE_KEY_PREFIX).unwrap_or_else(|| {
+				// can only happen if the progress was forcefully set to an inconsistent state.
+				log!(error, "bad child root {:?}, no child keys will be migrated", top_key);
+				Default::default()
+			})
+		}
+
+		/// Migrate the current top key, setting it to its new value, if one exists.
+		///
+		/// It updates the dynamic counters.
+		fn migrate_top(&mut self) {
+			let maybe_current_top = match &self.progress_top {
+				Progress::LastKey(last_top) => sp_io::storage::next_key(last_top),
+				// Start with the empty key as first key.
+				Progress::ToStart => Some(Vec::new()),
+				Progress::Complete => {
+					// defensive: there must be an ongoing top migration.
+					log!(error, "cannot migrate top key of a complete migration");
+					debug_assert!(false, "cannot migrate top key of a complete migration");
+					return
+				},
+			};
+
+			if let Some(current_top) = maybe_current_top.as_ref() {
+				let added_size = if let Some(data) = sp_io::storage::get(current_top) {
+					sp_io::storage::set(current_top, &data);
+					data.len() as u32
+				} else {
+					Zero::zero()
+				};
+				self.dyn_size = self.dyn_size.saturating_add(added_size);
+				self.dyn_top_items.saturating_inc();
+			}
+
+			log!(trace, "migrated a top key, next_top_key = {:?}", maybe_current_top);
+			self.progress_top = match maybe_current_top {
+				Some(last_top) => Progress::LastKey(last_top),
+				None => Progress::Complete,
+			}
+		}
+	}
+
+	/// The limits of a migration.
+	#[derive(
+		Clone,
+		Copy,
+		Encode,
+		Decode,
+		scale_info::TypeInfo,
+		Default,
+		PartialEq,
+		Eq,
+		RuntimeDebug,
+		MaxEncodedLen,
+	)]
+	pub struct MigrationLimits {
+		/// The byte size limit.
+		pub size: u32,
+		/// The number of keys limit.
+		pub item: u32,
+	}
+
+	/// How a migration was computed.
+	#[derive(Clone, Copy, Encode, Decode, scale_info::TypeInfo, PartialEq, Eq, RuntimeDebug)]
+	pub enum MigrationCompute {
+		/// A signed origin triggered the migration.
+		Signed,
+		/// An automatic task triggered the migration.
+		Auto,
+	}
+
+	/// Inner events of this pallet.
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// Given number of `(top, child)` keys were migrated respectively, with the given
+		/// `compute`.
+		Migrated { top: u32, child: u32, compute: MigrationCompute },
+		/// Some account got slashed by the given amount.
+		Slashed { who: T::AccountId, amount: BalanceOf<T> },
+		/// The auto migration task finished.
+		AutoMigrationFinished,
+	}
+
+	/// The outer Pallet struct.
+	#[pallet::pallet]
+	#[pallet::generate_store(pub(super) trait Store)]
+	pub struct Pallet<T>(_);
+
+	/// Configurations of this pallet.
+	#[pallet::config]
+	pub trait Config: frame_system::Config {
+		/// Origin that can control the configurations of this pallet.
+		type ControlOrigin: frame_support::traits::EnsureOrigin<Self::Origin>;
+
+		/// Filter on which origin that trigger the manual migrations.
+		type SignedFilter: EnsureOrigin<Self::Origin, Success = Self::AccountId>;
+
+		/// The overarching event type.
+		type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
+
+		/// The currency provider type.
+		type Currency: Currency<Self::AccountId>;
+
+		/// The amount of deposit collected per item in advance, for signed migrations.
+		///
+		/// This should reflect the average storage value size in the worse case.
+		type SignedDepositPerItem: Get<BalanceOf<Self>>;
+
+		/// The base value of [`Config::SignedDepositPerItem`].
+		///
+		/// Final deposit is `items * SignedDepositPerItem + SignedDepositBase`.
+		type SignedDepositBase: Get<BalanceOf<Self>>;
+
+		/// The weight information of this pallet.
+		///
+		/// Note that [`crate::weights::SubstrateWeight`] is not benchmarked yet and only holds
+		/// placeholder values.
+		type WeightInfo: WeightInfo;
+	}
+
+	/// Migration progress.
+	///
+	/// This stores the snapshot of the last migrated keys. It can be set into motion and move
+	/// forward by any of the means provided by this pallet.
+	#[pallet::storage]
+	#[pallet::getter(fn migration_process)]
+	pub type MigrationProcess<T> = StorageValue<_, MigrationTask, ValueQuery>;
+
+	/// The limits that are imposed on automatic migrations.
+	///
+	/// If set to None, then no automatic migration happens.
+	#[pallet::storage]
+	#[pallet::getter(fn auto_limits)]
+	pub type AutoLimits<T> = StorageValue<_, Option<MigrationLimits>, ValueQuery>;
+
+	/// The maximum limits that the signed migration could use.
+	///
+	/// If not set, no signed submission is allowed.
+	#[pallet::storage]
+	#[pallet::getter(fn signed_migration_max_limits)]
+	pub type SignedMigrationMaxLimits<T> = StorageValue<_, MigrationLimits, OptionQuery>;
+
+	#[pallet::error]
+	pub enum Error<T> {
+		/// Max signed limits not respected.
+		MaxSignedLimits,
+		/// Submitter does not have enough funds.
+		NotEnoughFunds,
+		/// Bad witness data provided.
+		BadWitness,
+		/// Signed migration is not allowed because the maximum limit is not set yet.
+		SignedMigrationNotAllowed,
+		/// Bad child root provided.
+		BadChildRoot,
+	}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T> {
+		/// Control the automatic migration.
+		///
+		/// The dispatch origin of this call must be [`Config::ControlOrigin`].
+		#[pallet::weight(T::DbWeight::get().reads_writes(1, 1))]
+		pub fn control_auto_migration(
+			origin: OriginFor<T>,
+			maybe_config: Option<MigrationLimits>,
+		) -> DispatchResult {
+			T::ControlOrigin::ensure_origin(origin)?;
+			AutoLimits::<T>::put(maybe_config);
+			Ok(())
+		}
+
+		/// Continue the migration for the given `limits`.
+		///
+		/// The dispatch origin of this call can be any signed account.
+		///
+		/// This transaction has NO MONETARY INCENTIVES. calling it will not reward anyone. Albeit,
+		/// Upon successful execution, the transaction fee is returned.
+		///
+		/// The (potentially over-estimated) of the byte length of all the data read must be
+		/// provided for up-front fee-payment and weighing. In essence, the caller is guaranteeing
+		/// that executing the current `MigrationTask` with the given `limits` will not exceed
+		/// `real_size_upper` bytes of read data.
+		///
+		/// The `witness_task` is merely a helper to prevent the caller from being slashed or
+		/// generally trigger a migration that they do not intend. This parameter is just a message
+		/// from caller, saying that they believed `witness_task` was the last state of the
+		/// migration, and they only wish for their transaction to do anything, if this assumption
+		/// holds. In case `witness_task` does not match, the transaction fails.
+		///
+		/// Based on the documentation of [`MigrationTask::migrate_until_exhaustion`], the
+		/// recommended way of doing this is to pass a `limit` that only bounds `count`, as the
+		/// `size` limit can always be overwritten.
+		#[pallet::weight(
+			// the migration process
+			Pallet::<T>::dynamic_weight(limits.item, * real_size_upper)
+			// rest of the operations, like deposit etc.
+			+ T::WeightInfo::continue_migrate()
+		)]
+		pub fn continue_migrate(
+			origin: OriginFor<T>,
+			limits: MigrationLimits,
+			real_size_upper: u32,
+			witness_task: MigrationTask,
+		) -> DispatchResultWithPostInfo {
+			let who = T::SignedFilter::ensure_origin(origin)?;
+
+			let max_limits =
+				Self::signed_migration_max_limits().ok_or(Error::<T>::SignedMigrationNotAllowed)?;
+			ensure!(
+				limits.size <= max_limits.size && limits.item <= max_limits.item,
+				Error::<T>::MaxSignedLimits,
+			);
+
+			// ensure they can pay more than the fee.
+			let deposit = Self::deposit_for(limits.item);
+			ensure!(T::Currency::can_slash(&who, deposit), Error::<T>::NotEnoughFunds);
+
+			let mut task = Self::migration_process();
+			ensure!(
+				task == witness_task,
+				DispatchErrorWithPostInfo {
+					error: Error::<T>::BadWitness.into(),
+					post_info: PostDispatchInfo {
+						actual_weight: Some(T::WeightInfo::continue_migrate_wrong_witness()),
+						pays_fee: Pays::Yes
+					}
+				}
+			);
+			task.migrate_until_exhaustion(limits);
+
+			// ensure that the migration witness data was correct.
+			if real_size_upper < task.dyn_size {
+				// let the imbalance burn.
+				let (_imbalance, _remainder) = T::Currency::slash(&who, deposit);
+				Self::deposit_event(Event::<T>::Slashed { who, amount: deposit });
+				debug_assert!(_remainder.is_zero());
+				return Ok(().into())
+			}
+
+			Self::deposit_event(Event::<T>::Migrated {
+				top: task.dyn_top_items,
+				child: task.dyn_child_items,
+				compute: MigrationCompute::Signed,
+			});
+			MigrationProcess::<T>::put(task);
+			let post_info = PostDispatchInfo { actual_weight: None, pays_fee: Pays::No };
+			Ok(post_info)
+		}
+
+		/// Migrate the list of top keys by iterating each of them one by one.
+		///
+		/// This does not affect the global migration process tracker ([`MigrationProcess`]), and
+		/// should only be used in case any keys are leftover due to a bug.
+		#[pallet::weight(
+			T::WeightInfo::migrate_custom_top_success()
+				.max(T::WeightInfo::migrate_custom_top_fail())
+			.saturating_add(
+				Pallet::<T>::dynamic_weight(keys.len() as u32, *witness_size)
+			)
+		)]
+		pub fn migrate_custom_top(
+			origin: OriginFor<T>,
+			keys: Vec<Vec<u8>>,
+			witness_size: u32,
+		) -> DispatchResultWithPostInfo {
+			let who = T::SignedFilter::ensure_origin(origin)?;
+			Self::ensure_signed_limits(keys.len() as u32, witness_size)?;
+
+			// ensure they can pay more than the fee.
+			let deposit = Self::deposit_for(keys.len() as u32);
+			ensure!(T::Currency::can_slash(&who, deposit), Error::<T>::NotEnoughFunds);
+
+			let mut dyn_size = 0u32;
+			for key in &keys {
+				if let Some(data) = sp_io::storage::get(key) {
+					dyn_size = dyn_size.saturating_add(data.len() as u32);
+					sp_io::storage::set(key, &data);
+				}
+			}
+
+			if dyn_size > witness_size {
+				let (_imbalance, _remainder) = T::Currency::slash(&who, deposit);
+				Self::deposit_event(Event::<T>::Slashed { who, amount: deposit });
+				debug_assert!(_remainder.is_zero());
+				Ok(PostDispatchInfo {
+					actual_weight: Some(T::WeightInfo::migrate_custom_top_fail()),
+					pays_fee: Pays::Yes,
+				})
+			} else {
+				Self::deposit_event(Event::<T>::Migrated {
+					top: keys.len() as u32,
+					child: 0,
+					compute: MigrationCompute::Signed,
+				});
+				Ok(PostDispatchInfo {
+					actual_weight: Some(
+						T::WeightInfo::migrate_custom_top_success().saturating_add(
+							Pallet::<T>::dynamic_weight(keys.len() as u32, dyn_size),
+						),
+					),
+					pays_fee: Pays::No,
+				})
+			}
+		}
+
+		/// Migrate the list of child keys by iterating each of them one by one.
+		///
+		/// All of the given child keys must be present under the child trie with storage key
+		/// `root`, and `total_size` must be an upper bound of the size of their values.
+		///
+		/// This does not affect the global migration process tracker ([`MigrationProcess`]), and
+		/// should only be used in case any keys are leftover due to a bug.
+		#[pallet::weight(
+			T::WeightInfo::migrate_custom_child_success()
+				.max(T::WeightInfo::migrate_custom_child_fail())
+			.saturating_add(
+				Pallet::<T>::dynamic_weight(child_keys.len() as u32, *total_size)
+			)
+		)]
+		pub fn migrate_custom_child(
+			origin: OriginFor<T>,
+			root: Vec<u8>,
+			child_keys: Vec<Vec<u8>>,
+			total_size: u32,
+		) -> DispatchResultWithPostInfo {
+			use sp_io::default_child_storage as child_io;
+			let who = T::SignedFilter::ensure_origin(origin)?;
+			Self::ensure_signed_limits(child_keys.len() as u32, total_size)?;
+
+			// ensure they can pay more than the fee.
+			let deposit = Self::deposit_for(child_keys.len() as u32);
+			ensure!(T::Currency::can_slash(&who, deposit), Error::<T>::NotEnoughFunds);
+
+			// an empty child trie does not exist, and there is nothing to migrate in it.
+			ensure!(
+				child_io::exists(&root, &[]) || child_io::next_key(&root, &[]).is_some(),
+				Error::<T>::BadChildRoot,
+			);
+
+			let mut dyn_size = 0u32;
+			for child_key in &child_keys {
+				if let Some(data) = child_io::get(&root, child_key) {
+					dyn_size = dyn_size.saturating_add(data.len() as u32);
+					child_io::set(&root, child_key, &data);
+				}
+			}
+
+			if dyn_size > total_size {
+				let (_imbalance, _remainder) = T::Currency::slash(&who, deposit);
+				Self::deposit_event(Event::<T>::Slashed { who, amount: deposit });
+				debug_assert!(_remainder.is_zero());
+				Ok(PostDispatchInfo {
+					actual_weight: Some(T::WeightInfo::migrate_custom_child_fail()),
+					pays_fee: Pays::Yes,
+				})
+			} else {
+				Self::deposit_event(Event::<T>::Migrated {
+					top: 0,
+					child: child_keys.len() as u32,
+					compute: MigrationCompute::Signed,
+				});
+				Ok(PostDispatchInfo {
+					actual_weight: Some(
+						T::WeightInfo::migrate_custom_child_success().saturating_add(
+							Pallet::<T>::dynamic_weight(child_keys.len() as u32, dyn_size),
+						),
+					),
+					pays_fee: Pays::No,
+				})
+			}
+		}
+
+		/// Set the maximum limit of the signed migration.
+		///
+		/// Setting `None` disables all signed migrations.
+		#[pallet::weight(T::DbWeight::get().reads_writes(1, 1))]
+		pub fn set_signed_max_limits(
+			origin: OriginFor<T>,
+			maybe_limits: Option<MigrationLimits>,
+		) -> DispatchResult {
+			T::ControlOrigin::ensure_origin(origin)?;
+			SignedMigrationMaxLimits::<T>::set(maybe_limits);
+			Ok(())
+		}
+
+		/// Forcefully set the progress the running migration.
+		///
+		/// This is only useful in one case: the next key to migrate is too big to be migrated with
+		/// a signed account, in a parachain context, and we simply want to skip it. A reasonable
+		/// example of this would be `:code:`, which is both very expensive to migrate, and commonly
+		/// used, so probably it is already migrated.
+		///
+		/// In case you mess things up, you can also, in principle, use this to reset the migration
+		/// process.
+		#[pallet::weight(T::DbWeight::get().reads_writes(1, 1))]
+		pub fn force_set_progress(
+			origin: OriginFor<T>,
+			progress_top: Progress,
+			progress_child: Progress,
+		) -> DispatchResult {
+			T::ControlOrigin::ensure_origin(origin)?;
+			MigrationProcess::<T>::mutate(|task| {
+				task.progress_top = progress_top;
+				task.progress_child = progress_child;
+			});
+			Ok(())
+		}
+	}
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_initialize(_: BlockNumberFor<T>) -> Weight {
+			if let Some(limits) = Self::auto_limits() {
+				let mut task = Self::migration_process();
+				task.migrate_until_exhaustion(limits);
+				let weight = Self::dynamic_weight(task.dyn_total_items(), task.dyn_size);
+
+				log!(
+					info,
+					"migrated {} top keys, {} child keys, and a total of {} bytes.",
+					task.dyn_top_items,
+					task.dyn_child_items,
+					task.dyn_size,
+				);
+
+				if task.finished() {
+					Self::deposit_event(Event::<T>::AutoMigrationFinished);
+					AutoLimits::<T>::kill();
+				} else {
+					Self::deposit_event(Event::<T>::Migrated {
+						top: task.dyn_top_items,
+						child: task.dyn_child_items,
+						compute: MigrationCompute::Auto,
+					});
+				}
+
+				MigrationProcess::<T>::put(task);
+
+				weight
+			} else {
+				T::DbWeight::get().reads(1)
+			}
+		}
+	}
+
+	impl<T: Config> Pallet<T> {
+		/// The real weight of a migration of the given number of `items` with total `size`.
+		fn dynamic_weight(items: u32, size: u32) -> frame_support::pallet_prelude::Weight {
+			let items = items as Weight;
+			items
+				.saturating_mul(<T as frame_system::Config>::DbWeight::get().reads_writes(1, 1))
+				// we assume that the read/write per-byte weight is the same for child and top tree.
+				.saturating_add(T::WeightInfo::process_top_key(size))
+		}
+
+		/// The deposit a signed submitter needs to be able to cover for migrating `items` keys.
+		pub(crate) fn deposit_for(items: u32) -> BalanceOf<T> {
+			T::SignedDepositBase::get()
+				.saturating_add(T::SignedDepositPerItem::get().saturating_mul(items.into()))
+		}
+
+		/// Ensure that a custom signed migration of `items` keys and `size` bytes is within the
+		/// signed limits.
+		fn ensure_signed_limits(items: u32, size: u32) -> DispatchResult {
+			let max_limits =
+				Self::signed_migration_max_limits().ok_or(Error::<T>::SignedMigrationNotAllowed)?;
+			ensure!(
+				size <= max_limits.size && items <= max_limits.item,
+				Error::<T>::MaxSignedLimits,
+			);
+			Ok(())
+		}
+	}
+}
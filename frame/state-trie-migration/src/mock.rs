@@ -0,0 +1,170 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test environment for the state trie migration pallet.
+
+use crate as pallet_state_trie_migration;
+use codec::Decode;
+use frame_support::{parameter_types, traits::Hooks, weights::Weight};
+use frame_system::{EnsureRoot, EnsureSigned};
+use sp_core::{
+	storage::{ChildInfo, StateVersion},
+	H256,
+};
+use sp_runtime::{
+	testing::Header,
+	traits::{BlakeTwo256, IdentityLookup},
+	StorageChild,
+};
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+frame_support::construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
+		Balances: pallet_balances::{Pallet, Call, Storage, Config<T>, Event<T>},
+		StateTrieMigration: pallet_state_trie_migration::{Pallet, Call, Storage, Event<T>},
+	}
+);
+
+parameter_types! {
+	pub const BlockHashCount: u64 = 250;
+	pub const SS58Prefix: u8 = 42;
+	pub const ExistentialDeposit: u64 = 1;
+	pub const SignedDepositPerItem: u64 = 1;
+	pub const SignedDepositBase: u64 = 5;
+}
+
+impl frame_system::Config for Test {
+	type BaseCallFilter = frame_support::traits::Everything;
+	type BlockWeights = ();
+	type BlockLength = ();
+	type Origin = Origin;
+	type Call = Call;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = Event;
+	type BlockHashCount = BlockHashCount;
+	type DbWeight = ();
+	type Version = ();
+	type PalletInfo = PalletInfo;
+	type AccountData = pallet_balances::AccountData<u64>;
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+	type SS58Prefix = SS58Prefix;
+	type OnSetCode = ();
+}
+
+impl pallet_balances::Config for Test {
+	type Balance = u64;
+	type Event = Event;
+	type DustRemoval = ();
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+	type MaxLocks = ();
+	type MaxReserves = ();
+	type ReserveIdentifier = [u8; 8];
+	type WeightInfo = ();
+}
+
+impl pallet_state_trie_migration::Config for Test {
+	type Event = Event;
+	type ControlOrigin = EnsureRoot<u64>;
+	type SignedFilter = EnsureSigned<u64>;
+	type Currency = Balances;
+	type SignedDepositPerItem = SignedDepositPerItem;
+	type SignedDepositBase = SignedDepositBase;
+	type WeightInfo = ();
+}
+
+/// The storage key of the first child trie in the test state.
+pub(crate) const CHILD_1: &[u8] = b"chk1";
+/// The storage key of the second child trie in the test state.
+pub(crate) const CHILD_2: &[u8] = b"chk2";
+
+/// Build a test state containing both small values and values that are big enough to be stored
+/// as separate nodes with [`StateVersion::V1`].
+///
+/// Account `1` is endowed with enough funds for signed migrations, account `2` is not.
+pub(crate) fn new_test_ext(version: StateVersion) -> sp_io::TestExternalities {
+	let minimum_size = sp_core::storage::TRIE_VALUE_NODE_THRESHOLD as usize;
+	let mut custom_storage = sp_core::storage::Storage {
+		top: vec![
+			(b"key1".to_vec(), vec![1u8; minimum_size + 1]),
+			(b"key2".to_vec(), vec![1u8; minimum_size + 2]),
+			(b"key3".to_vec(), vec![1u8; minimum_size + 3]),
+			(b"key4".to_vec(), vec![1u8; minimum_size + 4]),
+			(b"key5".to_vec(), vec![1u8; minimum_size + 5]),
+			(b"key6".to_vec(), vec![1u8; minimum_size + 6]),
+			(b"key7".to_vec(), vec![1u8; minimum_size + 7]),
+			(b"key8".to_vec(), vec![1u8; minimum_size + 8]),
+			(b"key9".to_vec(), vec![1u8; minimum_size + 9]),
+			(b"small".to_vec(), vec![1u8; 2]),
+		]
+		.into_iter()
+		.collect(),
+		children_default: vec![
+			(CHILD_1, vec![(b"key1".to_vec(), vec![1u8; 55]), (b"key2".to_vec(), vec![2u8; 66])]),
+			(CHILD_2, vec![(b"key1".to_vec(), vec![1u8; 54]), (b"key2".to_vec(), vec![2u8; 64])]),
+		]
+		.into_iter()
+		.map(|(storage_key, data)| {
+			let child_info = ChildInfo::new_default(storage_key);
+			(
+				child_info.prefixed_storage_key().into_inner(),
+				StorageChild { data: data.into_iter().collect(), child_info },
+			)
+		})
+		.collect(),
+	};
+
+	pallet_balances::GenesisConfig::<Test> { balances: vec![(1, 1000), (2, 5)] }
+		.assimilate_storage(&mut custom_storage)
+		.unwrap();
+
+	sp_tracing::try_init_simple();
+	let code = vec![1u8; minimum_size + 100];
+	sp_io::TestExternalities::new_with_code_and_state(&code, custom_storage, version)
+}
+
+/// Run to block `n`, returning the state root computed with [`StateVersion::V1`] after the last
+/// block and the total weight consumed by the migration.
+pub(crate) fn run_to_block(n: u64) -> (H256, Weight) {
+	let mut root = Default::default();
+	let mut weight_sum = 0;
+	while System::block_number() < n {
+		System::set_block_number(System::block_number() + 1);
+		System::on_initialize(System::block_number());
+
+		weight_sum += StateTrieMigration::on_initialize(System::block_number());
+
+		root = H256::decode(&mut &sp_io::storage::root(StateVersion::V1)[..]).unwrap();
+		System::on_finalize(System::block_number());
+	}
+	(root, weight_sum)
+}
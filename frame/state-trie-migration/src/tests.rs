@@ -0,0 +1,385 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the state trie migration pallet.
+
+use super::*;
+use crate::mock::{
+	new_test_ext, run_to_block, Balances, Origin, StateTrieMigration, System, Test, CHILD_1,
+	CHILD_2,
+};
+use codec::Decode;
+use frame_support::{
+	assert_noop, assert_ok,
+	traits::Get,
+	weights::{Pays, RuntimeDbWeight},
+};
+use sp_core::{storage::StateVersion, H256};
+use sp_runtime::traits::BadOrigin;
+
+fn migration_events() -> Vec<Event<Test>> {
+	System::events()
+		.into_iter()
+		.filter_map(|r| match r.event {
+			mock::Event::StateTrieMigration(e) => Some(e),
+			_ => None,
+		})
+		.collect()
+}
+
+fn current_root() -> H256 {
+	H256::decode(&mut &sp_io::storage::root(StateVersion::V1)[..]).unwrap()
+}
+
+fn run_with_limits(limits: MigrationLimits, from: u64, until: u64) {
+	let mut ext = new_test_ext(StateVersion::V0);
+	let root_upgraded = ext.execute_with(|| {
+		assert_eq!(AutoLimits::<Test>::get(), None);
+		assert_eq!(MigrationProcess::<Test>::get(), Default::default());
+
+		// nothing happens if we don't set the limits.
+		let _ = run_to_block(from);
+		assert_eq!(MigrationProcess::<Test>::get(), Default::default());
+
+		// this should allow `limits` to be migrated in each block.
+		AutoLimits::<Test>::put(Some(limits));
+
+		let root = run_to_block(until).0;
+
+		// eventually everything is over.
+		assert!(StateTrieMigration::migration_process().finished());
+		assert_eq!(AutoLimits::<Test>::get(), None);
+		root
+	});
+
+	let mut ext2 = new_test_ext(StateVersion::V1);
+	let root = ext2.execute_with(|| {
+		// do the same in the already migrated state, so that both states contain the same items.
+		let _ = run_to_block(from);
+		AutoLimits::<Test>::put(Some(limits));
+		run_to_block(until).0
+	});
+	assert_eq!(root, root_upgraded);
+}
+
+#[test]
+fn roots_differ_without_migration() {
+	let old_root = new_test_ext(StateVersion::V0).execute_with(current_root);
+	let new_root = new_test_ext(StateVersion::V1).execute_with(current_root);
+	assert_ne!(old_root, new_root);
+}
+
+#[test]
+fn auto_migrate_works() {
+	run_with_limits(MigrationLimits { item: 1, size: 1000 }, 10, 100);
+	run_with_limits(MigrationLimits { item: 5, size: 1000 }, 10, 100);
+	run_with_limits(MigrationLimits { item: 1000, size: 128 }, 10, 100);
+	run_with_limits(MigrationLimits { item: 1000, size: 100_000 }, 10, 100);
+}
+
+#[test]
+fn auto_migrate_reports_progress() {
+	new_test_ext(StateVersion::V0).execute_with(|| {
+		AutoLimits::<Test>::put(Some(MigrationLimits { item: 3, size: 100_000 }));
+		let (_, weight) = run_to_block(1);
+		assert!(weight > 0);
+
+		let task = StateTrieMigration::migration_process();
+		assert_eq!(task.top_items, 3);
+		assert_eq!(task.child_items, 0);
+		assert!(matches!(task.progress_top, Progress::LastKey(_)));
+		assert_eq!(
+			migration_events(),
+			vec![Event::Migrated { top: 3, child: 0, compute: MigrationCompute::Auto }]
+		);
+
+		// finish the migration.
+		AutoLimits::<Test>::put(Some(MigrationLimits { item: 1000, size: 100_000 }));
+		let _ = run_to_block(2);
+		assert!(StateTrieMigration::migration_process().finished());
+		// child keys plus the empty key that every child migration starts with.
+		assert_eq!(StateTrieMigration::migration_process().child_items, 6);
+		assert_eq!(migration_events().last(), Some(&Event::AutoMigrationFinished));
+		assert_eq!(AutoLimits::<Test>::get(), None);
+
+		// nothing happens after the migration finished.
+		let (_, weight) = run_to_block(3);
+		let db_weight: RuntimeDbWeight = <Test as frame_system::Config>::DbWeight::get();
+		assert_eq!(weight, db_weight.reads(1));
+	});
+}
+
+#[test]
+fn zero_limits_do_not_migrate() {
+	new_test_ext(StateVersion::V0).execute_with(|| {
+		AutoLimits::<Test>::put(Some(MigrationLimits { item: 0, size: 1000 }));
+		let _ = run_to_block(5);
+		assert_eq!(StateTrieMigration::migration_process(), Default::default());
+	});
+}
+
+#[test]
+fn control_origin_is_required() {
+	new_test_ext(StateVersion::V0).execute_with(|| {
+		let limits = MigrationLimits { item: 5, size: 1000 };
+		assert_noop!(
+			StateTrieMigration::control_auto_migration(Origin::signed(1), Some(limits)),
+			BadOrigin,
+		);
+		assert_noop!(
+			StateTrieMigration::set_signed_max_limits(Origin::signed(1), Some(limits)),
+			BadOrigin,
+		);
+		assert_noop!(
+			StateTrieMigration::force_set_progress(
+				Origin::signed(1),
+				Progress::Complete,
+				Progress::Complete
+			),
+			BadOrigin,
+		);
+
+		assert_ok!(StateTrieMigration::control_auto_migration(Origin::root(), Some(limits)));
+		assert_eq!(AutoLimits::<Test>::get(), Some(limits));
+		assert_ok!(StateTrieMigration::set_signed_max_limits(Origin::root(), Some(limits)));
+		assert_eq!(SignedMigrationMaxLimits::<Test>::get(), Some(limits));
+		assert_ok!(StateTrieMigration::force_set_progress(
+			Origin::root(),
+			Progress::Complete,
+			Progress::ToStart
+		));
+		assert!(StateTrieMigration::migration_process().finished());
+	});
+}
+
+#[test]
+fn signed_migrate_works() {
+	let limits = MigrationLimits { item: 5, size: 1024 };
+	let signed_migrate = || {
+		SignedMigrationMaxLimits::<Test>::put(limits);
+		while !StateTrieMigration::migration_process().finished() {
+			// first we compute the task to get the accurate consumption.
+			let mut task = StateTrieMigration::migration_process();
+			task.migrate_until_exhaustion(limits);
+
+			let post_info = StateTrieMigration::continue_migrate(
+				Origin::signed(1),
+				limits,
+				task.dyn_size,
+				StateTrieMigration::migration_process(),
+			)
+			.unwrap();
+			assert_eq!(post_info.pays_fee, Pays::No);
+		}
+
+		// no funds were slashed.
+		assert_eq!(Balances::free_balance(&1), 1000);
+		current_root()
+	};
+
+	let root_upgraded = new_test_ext(StateVersion::V0).execute_with(signed_migrate);
+	let root = new_test_ext(StateVersion::V1).execute_with(signed_migrate);
+	assert_eq!(root, root_upgraded);
+}
+
+#[test]
+fn continue_migrate_checks_inputs() {
+	new_test_ext(StateVersion::V0).execute_with(|| {
+		let limits = MigrationLimits { item: 5, size: 1024 };
+
+		// not allowed before the maximum limits are set.
+		assert_noop!(
+			StateTrieMigration::continue_migrate(
+				Origin::signed(1),
+				limits,
+				1024,
+				Default::default()
+			),
+			Error::<Test>::SignedMigrationNotAllowed,
+		);
+
+		SignedMigrationMaxLimits::<Test>::put(limits);
+
+		// must be signed.
+		assert_noop!(
+			StateTrieMigration::continue_migrate(Origin::root(), limits, 1024, Default::default()),
+			BadOrigin,
+		);
+
+		// can't go over the maximum limits.
+		assert_noop!(
+			StateTrieMigration::continue_migrate(
+				Origin::signed(1),
+				MigrationLimits { item: 6, size: 1024 },
+				1024,
+				Default::default()
+			),
+			Error::<Test>::MaxSignedLimits,
+		);
+		assert_noop!(
+			StateTrieMigration::continue_migrate(
+				Origin::signed(1),
+				MigrationLimits { item: 5, size: 1025 },
+				1024,
+				Default::default()
+			),
+			Error::<Test>::MaxSignedLimits,
+		);
+
+		// must be able to pay the deposit.
+		assert_noop!(
+			StateTrieMigration::continue_migrate(
+				Origin::signed(2),
+				limits,
+				1024,
+				Default::default()
+			),
+			Error::<Test>::NotEnoughFunds,
+		);
+
+		// must know the current task.
+		let wrong_witness = MigrationTask {
+			progress_top: Progress::LastKey(b"key1".to_vec()),
+			..Default::default()
+		};
+		assert_eq!(
+			StateTrieMigration::continue_migrate(Origin::signed(1), limits, 1024, wrong_witness)
+				.unwrap_err()
+				.error,
+			Error::<Test>::BadWitness.into(),
+		);
+		assert_eq!(StateTrieMigration::migration_process(), Default::default());
+	});
+}
+
+#[test]
+fn continue_migrate_slashes_wrong_size() {
+	new_test_ext(StateVersion::V0).execute_with(|| {
+		System::set_block_number(1);
+		let limits = MigrationLimits { item: 5, size: 1024 };
+		SignedMigrationMaxLimits::<Test>::put(limits);
+
+		let post_info =
+			StateTrieMigration::continue_migrate(Origin::signed(1), limits, 1, Default::default())
+				.unwrap();
+		assert_eq!(post_info.pays_fee, Pays::Yes);
+
+		// the deposit for 5 items is slashed, and the progress is not recorded.
+		assert_eq!(Balances::free_balance(&1), 1000 - 10);
+		assert_eq!(migration_events(), vec![Event::Slashed { who: 1, amount: 10 }]);
+		assert_eq!(StateTrieMigration::migration_process(), Default::default());
+	});
+}
+
+#[test]
+fn custom_migrate_top_works() {
+	let correct_witness = sp_core::storage::TRIE_VALUE_NODE_THRESHOLD * 3 + 1 + 2 + 3;
+	let migrate_top = || {
+		SignedMigrationMaxLimits::<Test>::put(MigrationLimits { item: 5, size: 1024 });
+		let post_info = StateTrieMigration::migrate_custom_top(
+			Origin::signed(1),
+			vec![b"key1".to_vec(), b"key2".to_vec(), b"key3".to_vec()],
+			correct_witness,
+		)
+		.unwrap();
+		assert_eq!(post_info.pays_fee, Pays::No);
+
+		// no funds were slashed.
+		assert_eq!(Balances::free_balance(&1), 1000);
+		current_root()
+	};
+
+	let migrated_root = new_test_ext(StateVersion::V0).execute_with(migrate_top);
+	let unmigrated_root = new_test_ext(StateVersion::V0).execute_with(current_root);
+	assert_ne!(migrated_root, unmigrated_root);
+
+	new_test_ext(StateVersion::V0).execute_with(|| {
+		System::set_block_number(1);
+		SignedMigrationMaxLimits::<Test>::put(MigrationLimits { item: 5, size: 1024 });
+
+		// a witness that is too low gets slashed.
+		let post_info = StateTrieMigration::migrate_custom_top(
+			Origin::signed(1),
+			vec![b"key1".to_vec(), b"key2".to_vec(), b"key3".to_vec()],
+			correct_witness - 1,
+		)
+		.unwrap();
+		assert_eq!(post_info.pays_fee, Pays::Yes);
+		// deposit of 3 items.
+		assert_eq!(Balances::free_balance(&1), 1000 - 8);
+		assert_eq!(migration_events(), vec![Event::Slashed { who: 1, amount: 8 }]);
+
+		// more keys than allowed.
+		assert_noop!(
+			StateTrieMigration::migrate_custom_top(
+				Origin::signed(1),
+				vec![b"key1".to_vec(); 6],
+				correct_witness
+			),
+			Error::<Test>::MaxSignedLimits,
+		);
+	});
+}
+
+#[test]
+fn custom_migrate_child_works() {
+	new_test_ext(StateVersion::V0).execute_with(|| {
+		System::set_block_number(1);
+		SignedMigrationMaxLimits::<Test>::put(MigrationLimits { item: 5, size: 1024 });
+
+		// unknown child tries are rejected.
+		assert_noop!(
+			StateTrieMigration::migrate_custom_child(
+				Origin::signed(1),
+				b"unknown".to_vec(),
+				vec![b"key1".to_vec()],
+				55,
+			),
+			Error::<Test>::BadChildRoot,
+		);
+
+		let root_before = current_root();
+		let post_info = StateTrieMigration::migrate_custom_child(
+			Origin::signed(1),
+			CHILD_1.to_vec(),
+			vec![b"key1".to_vec(), b"key2".to_vec()],
+			55 + 66,
+		)
+		.unwrap();
+		assert_eq!(post_info.pays_fee, Pays::No);
+		assert_eq!(Balances::free_balance(&1), 1000);
+		assert_ne!(current_root(), root_before);
+
+		// a witness that is too low gets slashed.
+		let post_info = StateTrieMigration::migrate_custom_child(
+			Origin::signed(1),
+			CHILD_2.to_vec(),
+			vec![b"key1".to_vec(), b"key2".to_vec()],
+			54 + 64 - 1,
+		)
+		.unwrap();
+		assert_eq!(post_info.pays_fee, Pays::Yes);
+		assert_eq!(Balances::free_balance(&1), 1000 - 7);
+		assert_eq!(
+			migration_events(),
+			vec![
+				Event::Migrated { top: 0, child: 2, compute: MigrationCompute::Signed },
+				Event::Slashed { who: 1, amount: 7 },
+			]
+		);
+	});
+}
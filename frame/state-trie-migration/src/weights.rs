@@ -0,0 +1,153 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Placeholder weights for pallet_state_trie_migration
+//!
+//! These weights were NOT produced by the benchmark CLI and are not measured. They must be
+//! replaced by the output of the benchmarks before the pallet is used on a production chain.
+
+// Command to generate the real weights:
+// target/release/substrate
+// benchmark
+// --chain=dev
+// --steps=50
+// --repeat=20
+// --pallet=pallet_state_trie_migration
+// --extrinsic=*
+// --execution=wasm
+// --wasm-execution=compiled
+// --heap-pages=4096
+// --output=./frame/state-trie-migration/src/weights.rs
+// --template=./.maintain/frame-weight-template.hbs
+
+
+#![cfg_attr(rustfmt, rustfmt_skip)]
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{traits::Get, weights::{Weight, constants::RocksDbWeight}};
+use sp_std::marker::PhantomData;
+
+/// Weight functions needed for pallet_state_trie_migration.
+pub trait WeightInfo {
+	fn continue_migrate() -> Weight;
+	fn continue_migrate_wrong_witness() -> Weight;
+	fn migrate_custom_top_success() -> Weight;
+	fn migrate_custom_top_fail() -> Weight;
+	fn migrate_custom_child_success() -> Weight;
+	fn migrate_custom_child_fail() -> Weight;
+	fn process_top_key(v: u32, ) -> Weight;
+}
+
+/// Weights for pallet_state_trie_migration using the Substrate node and recommended hardware.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+	// Storage: StateTrieMigration SignedMigrationMaxLimits (r:1 w:0)
+	// Storage: StateTrieMigration MigrationProcess (r:1 w:1)
+	fn continue_migrate() -> Weight {
+		(13_385_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	// Storage: StateTrieMigration SignedMigrationMaxLimits (r:1 w:0)
+	// Storage: StateTrieMigration MigrationProcess (r:1 w:0)
+	fn continue_migrate_wrong_witness() -> Weight {
+		(1_757_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+	}
+	// Storage: StateTrieMigration SignedMigrationMaxLimits (r:1 w:0)
+	fn migrate_custom_top_success() -> Weight {
+		(12_813_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+	}
+	// Storage: StateTrieMigration SignedMigrationMaxLimits (r:1 w:0)
+	// Storage: System Account (r:1 w:1)
+	fn migrate_custom_top_fail() -> Weight {
+		(24_854_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	// Storage: StateTrieMigration SignedMigrationMaxLimits (r:1 w:0)
+	fn migrate_custom_child_success() -> Weight {
+		(13_497_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+	}
+	// Storage: StateTrieMigration SignedMigrationMaxLimits (r:1 w:0)
+	// Storage: System Account (r:1 w:1)
+	fn migrate_custom_child_fail() -> Weight {
+		(28_125_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+	// Storage: unknown [0x6b6579] (r:1 w:1)
+	fn process_top_key(v: u32, ) -> Weight {
+		(0 as Weight)
+			// Standard Error: 0
+			.saturating_add((2_000 as Weight).saturating_mul(v as Weight))
+			.saturating_add(T::DbWeight::get().reads(1 as Weight))
+			.saturating_add(T::DbWeight::get().writes(1 as Weight))
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	// Storage: StateTrieMigration SignedMigrationMaxLimits (r:1 w:0)
+	// Storage: StateTrieMigration MigrationProcess (r:1 w:1)
+	fn continue_migrate() -> Weight {
+		(13_385_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(2 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(1 as Weight))
+	}
+	// Storage: StateTrieMigration SignedMigrationMaxLimits (r:1 w:0)
+	// Storage: StateTrieMigration MigrationProcess (r:1 w:0)
+	fn continue_migrate_wrong_witness() -> Weight {
+		(1_757_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(2 as Weight))
+	}
+	// Storage: StateTrieMigration SignedMigrationMaxLimits (r:1 w:0)
+	fn migrate_custom_top_success() -> Weight {
+		(12_813_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(1 as Weight))
+	}
+	// Storage: StateTrieMigration SignedMigrationMaxLimits (r:1 w:0)
+	// Storage: System Account (r:1 w:1)
+	fn migrate_custom_top_fail() -> Weight {
+		(24_854_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(2 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(1 as Weight))
+	}
+	// Storage: StateTrieMigration SignedMigrationMaxLimits (r:1 w:0)
+	fn migrate_custom_child_success() -> Weight {
+		(13_497_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(2 as Weight))
+	}
+	// Storage: StateTrieMigration SignedMigrationMaxLimits (r:1 w:0)
+	// Storage: System Account (r:1 w:1)
+	fn migrate_custom_child_fail() -> Weight {
+		(28_125_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(3 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(1 as Weight))
+	}
+	// Storage: unknown [0x6b6579] (r:1 w:1)
+	fn process_top_key(v: u32, ) -> Weight {
+		(0 as Weight)
+			// Standard Error: 0
+			.saturating_add((2_000 as Weight).saturating_mul(v as Weight))
+			.saturating_add(RocksDbWeight::get().reads(1 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(1 as Weight))
+	}
+}
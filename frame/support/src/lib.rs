@@ -46,7 +46,7 @@ pub use sp_core_hashing_proc_macro;
 #[doc(hidden)]
 pub use sp_io::{self, storage::root as storage_root};
 #[doc(hidden)]
-pub use sp_runtime::RuntimeDebug;
+pub use sp_runtime::{RuntimeDebug, StateVersion};
 #[cfg(feature = "std")]
 #[doc(hidden)]
 pub use sp_state_machine::BasicExternalities;
@@ -751,9 +751,9 @@ macro_rules! assert_noop {
 		$x:expr,
 		$y:expr $(,)?
 	) => {
-		let h = $crate::storage_root();
+		let h = $crate::storage_root($crate::StateVersion::V1);
 		$crate::assert_err!($x, $y);
-		assert_eq!(h, $crate::storage_root());
+		assert_eq!(h, $crate::storage_root($crate::StateVersion::V1));
 	};
 }
 
@@ -766,9 +766,9 @@ macro_rules! assert_storage_noop {
 	(
 		$x:expr
 	) => {
-		let h = $crate::storage_root();
+		let h = $crate::storage_root($crate::StateVersion::V1);
 		$x;
-		assert_eq!(h, $crate::storage_root());
+		assert_eq!(h, $crate::storage_root($crate::StateVersion::V1));
 	};
 }
 
@@ -38,6 +38,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	impl_version: 0,
 	apis: sp_version::create_apis_vec!([]),
 	transaction_version: 0,
+	state_version: 0,
 };
 
 pub type Signature = sr25519::Signature;
@@ -234,11 +234,24 @@ fn generate_runtime_api_base_structures() -> Result<TokenStream> {
 				call: F,
 			) -> R where Self: Sized {
 				self.changes.borrow_mut().start_transaction();
+				if let Some(recorder) = &self.recorder {
+					recorder.start_transaction();
+				}
 				*self.commit_on_success.borrow_mut() = false;
 				let res = call(self);
 				*self.commit_on_success.borrow_mut() = true;
 
-				self.commit_or_rollback(matches!(res, #crate_::TransactionOutcome::Commit(_)));
+				let commit = matches!(res, #crate_::TransactionOutcome::Commit(_));
+				self.commit_or_rollback(commit);
+				// The storage accessed by a rolled back transaction is not needed to prove the block.
+				if let Some(recorder) = &self.recorder {
+					let result = if commit {
+						recorder.commit_transaction()
+					} else {
+						recorder.rollback_transaction()
+					};
+					result.expect("The recorder transaction was started above; qed");
+				}
 
 				res.into_inner()
 			}
@@ -293,10 +306,17 @@ fn generate_runtime_api_base_structures() -> Result<TokenStream> {
 				#crate_::StorageChanges<C::StateBackend, Block>,
 				String
 			> where Self: Sized {
+				let at = #crate_::BlockId::Hash(parent_hash.clone());
+				let state_version = self.call
+					.runtime_version_at(&at)
+					.map(|v| v.state_version())
+					.map_err(|e| format!("Failed to get state version: {}", e))?;
+
 				self.changes.replace(Default::default()).into_storage_changes(
 					backend,
 					parent_hash,
 					self.storage_transaction_cache.replace(Default::default()),
+					state_version,
 				)
 			}
 		}
@@ -26,9 +26,16 @@ use sp_std::vec::Vec;
 /// The `ConsensusEngineId` of PoW.
 pub const POW_ENGINE_ID: ConsensusEngineId = [b'p', b'o', b'w', b'_'];
 
+/// The `ConsensusEngineId` of the pre-runtime digest selecting the mining algorithm of a block,
+/// on chains supporting several of them. The digest contains the encoded [`AlgorithmId`].
+pub const POW_ALGORITHM_ENGINE_ID: ConsensusEngineId = [b'p', b'o', b'w', b'a'];
+
 /// Type of seal.
 pub type Seal = Vec<u8>;
 
+/// Identifier of a mining algorithm.
+pub type AlgorithmId = [u8; 4];
+
 /// Define methods that total difficulty should implement.
 pub trait TotalDifficulty {
 	fn increment(&mut self, other: Self);
@@ -62,4 +69,12 @@ sp_api::decl_runtime_apis! {
 		/// Return the target difficulty of the next block.
 		fn difficulty() -> Difficulty;
 	}
+
+	/// API for those chains that are mined with several algorithms, each of them having
+	/// its own difficulty adjusted on runtime.
+	pub trait AlgorithmDifficultyApi<Difficulty: Decode> {
+		/// Return the target difficulty of the next block mined with `algorithm`, or `None`
+		/// if the algorithm is not supported.
+		fn algorithm_difficulty(algorithm: AlgorithmId) -> Option<Difficulty>;
+	}
 }
@@ -0,0 +1,105 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Private implementation details of Sassafras digests.
+
+use super::{
+	AuthorityId, AuthorityIndex, AuthoritySignature, Randomness, SassafrasAuthorityWeight, Slot,
+	TicketAux, SASSAFRAS_ENGINE_ID,
+};
+use codec::{Decode, Encode};
+use sp_runtime::{DigestItem, RuntimeDebug};
+use sp_std::vec::Vec;
+
+use sp_consensus_vrf::schnorrkel::{VRFOutput, VRFProof};
+
+/// A Sassafras pre-runtime digest. This contains all data required to validate a
+/// block and for the Sassafras runtime module.
+#[derive(Clone, RuntimeDebug, Encode, Decode)]
+pub struct PreDigest {
+	/// Authority index
+	pub authority_index: AuthorityIndex,
+	/// Slot
+	pub slot: Slot,
+	/// VRF output of the slot claim
+	pub vrf_output: VRFOutput,
+	/// VRF proof of the slot claim
+	pub vrf_proof: VRFProof,
+	/// Data of the ticket the slot is claimed with, `None` for fallback slots
+	pub ticket_aux: Option<TicketAux>,
+}
+
+impl PreDigest {
+	/// Returns true if the slot is claimed with a ticket.
+	pub fn is_ticket_claim(&self) -> bool {
+		self.ticket_aux.is_some()
+	}
+}
+
+/// Information about the next epoch. This is broadcast in the first block
+/// of the epoch.
+#[derive(Decode, Encode, PartialEq, Eq, Clone, RuntimeDebug)]
+pub struct NextEpochDescriptor {
+	/// The authorities.
+	pub authorities: Vec<(AuthorityId, SassafrasAuthorityWeight)>,
+
+	/// The value of randomness to use for the slot-assignment.
+	pub randomness: Randomness,
+}
+
+/// A digest item which is usable with Sassafras consensus.
+pub trait CompatibleDigestItem: Sized {
+	/// Construct a digest item which contains a Sassafras pre-digest.
+	fn sassafras_pre_digest(seal: PreDigest) -> Self;
+
+	/// If this item is an Sassafras pre-digest, return it.
+	fn as_sassafras_pre_digest(&self) -> Option<PreDigest>;
+
+	/// Construct a digest item which contains a Sassafras seal.
+	fn sassafras_seal(signature: AuthoritySignature) -> Self;
+
+	/// If this item is a Sassafras signature, return the signature.
+	fn as_sassafras_seal(&self) -> Option<AuthoritySignature>;
+
+	/// If this item is a Sassafras epoch descriptor, return it.
+	fn as_next_epoch_descriptor(&self) -> Option<NextEpochDescriptor>;
+}
+
+impl CompatibleDigestItem for DigestItem {
+	fn sassafras_pre_digest(digest: PreDigest) -> Self {
+		DigestItem::PreRuntime(SASSAFRAS_ENGINE_ID, digest.encode())
+	}
+
+	fn as_sassafras_pre_digest(&self) -> Option<PreDigest> {
+		self.pre_runtime_try_to(&SASSAFRAS_ENGINE_ID)
+	}
+
+	fn sassafras_seal(signature: AuthoritySignature) -> Self {
+		DigestItem::Seal(SASSAFRAS_ENGINE_ID, signature.encode())
+	}
+
+	fn as_sassafras_seal(&self) -> Option<AuthoritySignature> {
+		self.seal_try_to(&SASSAFRAS_ENGINE_ID)
+	}
+
+	fn as_next_epoch_descriptor(&self) -> Option<NextEpochDescriptor> {
+		self.consensus_try_to(&SASSAFRAS_ENGINE_ID)
+			.and_then(|x: super::ConsensusLog| match x {
+				super::ConsensusLog::NextEpochData(n) => Some(n),
+				_ => None,
+			})
+	}
+}
@@ -0,0 +1,102 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Inherents for Sassafras
+
+use sp_inherents::{Error, InherentData, InherentIdentifier};
+
+use sp_std::result::Result;
+
+/// The Sassafras inherent identifier.
+pub const INHERENT_IDENTIFIER: InherentIdentifier = *b"sassslot";
+
+/// The type of the Sassafras inherent.
+pub type InherentType = sp_consensus_slots::Slot;
+/// Auxiliary trait to extract Sassafras inherent data.
+pub trait SassafrasInherentData {
+	/// Get Sassafras inherent data.
+	fn sassafras_inherent_data(&self) -> Result<Option<InherentType>, Error>;
+	/// Replace Sassafras inherent data.
+	fn sassafras_replace_inherent_data(&mut self, new: InherentType);
+}
+
+impl SassafrasInherentData for InherentData {
+	fn sassafras_inherent_data(&self) -> Result<Option<InherentType>, Error> {
+		self.get_data(&INHERENT_IDENTIFIER)
+	}
+
+	fn sassafras_replace_inherent_data(&mut self, new: InherentType) {
+		self.replace_data(INHERENT_IDENTIFIER, &new);
+	}
+}
+
+/// Provides the slot duration inherent data for Sassafras.
+#[cfg(feature = "std")]
+pub struct InherentDataProvider {
+	slot: InherentType,
+}
+
+#[cfg(feature = "std")]
+impl InherentDataProvider {
+	/// Create new inherent data provider from the given `slot`.
+	pub fn new(slot: InherentType) -> Self {
+		Self { slot }
+	}
+
+	/// Creates the inherent data provider by calculating the slot from the given
+	/// `timestamp` and `duration`.
+	pub fn from_timestamp_and_duration(
+		timestamp: sp_timestamp::Timestamp,
+		duration: std::time::Duration,
+	) -> Self {
+		let slot =
+			InherentType::from((timestamp.as_duration().as_millis() / duration.as_millis()) as u64);
+
+		Self { slot }
+	}
+
+	/// Returns the `slot` of this inherent data provider.
+	pub fn slot(&self) -> InherentType {
+		self.slot
+	}
+}
+
+#[cfg(feature = "std")]
+impl sp_std::ops::Deref for InherentDataProvider {
+	type Target = InherentType;
+
+	fn deref(&self) -> &Self::Target {
+		&self.slot
+	}
+}
+
+#[cfg(feature = "std")]
+#[async_trait::async_trait]
+impl sp_inherents::InherentDataProvider for InherentDataProvider {
+	fn provide_inherent_data(&self, inherent_data: &mut InherentData) -> Result<(), Error> {
+		inherent_data.put_data(INHERENT_IDENTIFIER, &self.slot)
+	}
+
+	async fn try_handle_error(
+		&self,
+		_: &InherentIdentifier,
+		_: &[u8],
+	) -> Option<Result<(), Error>> {
+		// There is no error anymore
+		None
+	}
+}
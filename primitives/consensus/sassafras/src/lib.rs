@@ -0,0 +1,350 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2021 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: Apache-2.0
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Primitives for Sassafras.
+//!
+//! Sassafras elects a single leader per slot. During an epoch, each authority computes a
+//! number of VRF outputs, the *tickets*, over the randomness of the next epoch, and submits
+//! the ones that fall below a threshold. The tickets collected on-chain are sorted, and this
+//! order assigns the slots of the next epoch. The owner of a ticket proves it when claiming the
+//! slot by revealing the VRF proof of the ticket. Slots without a ticket fall back to a
+//! round-robin author derived from the epoch randomness.
+//!
+//! Tickets are plain VRF outputs rather than ring-VRF outputs: they are submitted along with
+//! their VRF proof and the index of their author among the authorities of the next epoch, so
+//! that the runtime can reject forged tickets. They are therefore not anonymous.
+#![deny(warnings)]
+#![forbid(unsafe_code, missing_docs, unused_variables, unused_imports)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+pub mod digests;
+pub mod inherents;
+
+pub use merlin::Transcript;
+pub use sp_consensus_vrf::schnorrkel::{
+	Randomness, VRFOutput, VRFProof, RANDOMNESS_LENGTH, VRF_OUTPUT_LENGTH, VRF_PROOF_LENGTH,
+};
+
+use codec::{Decode, Encode, MaxEncodedLen};
+use scale_info::TypeInfo;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use sp_keystore::vrf::{VRFTranscriptData, VRFTranscriptValue};
+use sp_runtime::{ConsensusEngineId, RuntimeDebug};
+use sp_std::vec::Vec;
+
+use crate::digests::NextEpochDescriptor;
+
+/// Key type for Sassafras module.
+pub const KEY_TYPE: sp_core::crypto::KeyTypeId = sp_application_crypto::key_types::SASSAFRAS;
+
+mod app {
+	use sp_application_crypto::{app_crypto, key_types::SASSAFRAS, sr25519};
+	app_crypto!(sr25519, SASSAFRAS);
+}
+
+/// Sassafras VRFInOut context of the slot VRF, used for the epoch randomness.
+pub static SASSAFRAS_VRF_INOUT_CONTEXT: &[u8] = b"SassafrasVRFInOutContext";
+
+/// A Sassafras authority keypair.
+#[cfg(feature = "std")]
+pub type AuthorityPair = app::Pair;
+
+/// A Sassafras authority signature.
+pub type AuthoritySignature = app::Signature;
+
+/// A Sassafras authority identifier.
+pub type AuthorityId = app::Public;
+
+/// The `ConsensusEngineId` of Sassafras.
+pub const SASSAFRAS_ENGINE_ID: ConsensusEngineId = *b"SASS";
+
+/// The index of an authority.
+pub type AuthorityIndex = u32;
+
+pub use sp_consensus_slots::Slot;
+
+/// The weight of an authority.
+// NOTE: we use a unique name for the weight to avoid conflicts with other
+//       `Weight` types, since the metadata isn't able to disambiguate.
+pub type SassafrasAuthorityWeight = u64;
+
+/// The cumulative weight of a Sassafras block, i.e. sum of block weights starting
+/// at this block until the genesis block.
+///
+/// Blocks claimed with a ticket have a weight of 1 whereas fallback blocks have a
+/// weight of 0.
+pub type SassafrasBlockWeight = u32;
+
+/// A ticket, i.e. the output of the ticket VRF of an authority.
+pub type Ticket = [u8; VRF_OUTPUT_LENGTH];
+
+/// Data kept by the owner of a ticket, to claim the slot assigned to it.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct TicketAux {
+	/// Attempt number the ticket was generated with.
+	pub attempt: u32,
+	/// Proof of the ticket VRF.
+	pub proof: VRFProof,
+}
+
+/// A ticket submitted on-chain, with the proof that it is the output of the ticket VRF of an
+/// authority of the next epoch.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug, TypeInfo)]
+pub struct TicketEnvelope {
+	/// The ticket.
+	pub ticket: Ticket,
+	/// Attempt number the ticket was generated with.
+	pub attempt: u32,
+	/// Bytes of the proof of the ticket VRF.
+	pub proof: [u8; VRF_PROOF_LENGTH],
+}
+
+/// Make the VRF transcript of a slot claim from given randomness, slot number and epoch.
+pub fn make_slot_transcript(randomness: &Randomness, slot: Slot, epoch: u64) -> Transcript {
+	let mut transcript = Transcript::new(&SASSAFRAS_ENGINE_ID);
+	transcript.append_u64(b"type", 0);
+	transcript.append_u64(b"slot number", *slot);
+	transcript.append_u64(b"current epoch", epoch);
+	transcript.append_message(b"chain randomness", &randomness[..]);
+	transcript
+}
+
+/// Make the VRF transcript data container of a slot claim.
+#[cfg(feature = "std")]
+pub fn make_slot_transcript_data(
+	randomness: &Randomness,
+	slot: Slot,
+	epoch: u64,
+) -> VRFTranscriptData {
+	VRFTranscriptData {
+		label: &SASSAFRAS_ENGINE_ID,
+		items: vec![
+			("type", VRFTranscriptValue::U64(0)),
+			("slot number", VRFTranscriptValue::U64(*slot)),
+			("current epoch", VRFTranscriptValue::U64(epoch)),
+			("chain randomness", VRFTranscriptValue::Bytes(randomness.to_vec())),
+		],
+	}
+}
+
+/// Make the VRF transcript of a ticket from given randomness, attempt number and epoch.
+pub fn make_ticket_transcript(randomness: &Randomness, attempt: u32, epoch: u64) -> Transcript {
+	let mut transcript = Transcript::new(&SASSAFRAS_ENGINE_ID);
+	transcript.append_u64(b"type", 1);
+	transcript.append_u64(b"attempt", attempt as u64);
+	transcript.append_u64(b"current epoch", epoch);
+	transcript.append_message(b"chain randomness", &randomness[..]);
+	transcript
+}
+
+/// Make the VRF transcript data container of a ticket.
+#[cfg(feature = "std")]
+pub fn make_ticket_transcript_data(
+	randomness: &Randomness,
+	attempt: u32,
+	epoch: u64,
+) -> VRFTranscriptData {
+	VRFTranscriptData {
+		label: &SASSAFRAS_ENGINE_ID,
+		items: vec![
+			("type", VRFTranscriptValue::U64(1)),
+			("attempt", VRFTranscriptValue::U64(attempt as u64)),
+			("current epoch", VRFTranscriptValue::U64(epoch)),
+			("chain randomness", VRFTranscriptValue::Bytes(randomness.to_vec())),
+		],
+	}
+}
+
+/// Compute the threshold below which a ticket is valid.
+///
+/// The threshold is chosen so that, if every authority uses all its attempts, the expected
+/// number of valid tickets is `redundancy_factor` times the number of slots of the epoch.
+pub fn compute_threshold(
+	redundancy_factor: u32,
+	number_of_slots: u64,
+	attempts_number: u32,
+	number_of_validators: u32,
+) -> u128 {
+	let trials = (attempts_number as u128).saturating_mul(number_of_validators as u128).max(1);
+	(u128::MAX / trials)
+		.saturating_mul((redundancy_factor as u128).saturating_mul(number_of_slots as u128))
+}
+
+/// Returns true if the given ticket is below the threshold.
+pub fn check_threshold(ticket: &Ticket, threshold: u128) -> bool {
+	let mut bytes = [0u8; 16];
+	bytes.copy_from_slice(&ticket[..16]);
+	u128::from_le_bytes(bytes) < threshold
+}
+
+/// An consensus log item for Sassafras.
+#[derive(Decode, Encode, Clone, PartialEq, Eq)]
+pub enum ConsensusLog {
+	/// The epoch has changed. This provides information about the _next_
+	/// epoch - information about the _current_ epoch (i.e. the one we've just
+	/// entered) should already be available earlier in the chain.
+	#[codec(index = 1)]
+	NextEpochData(NextEpochDescriptor),
+	/// Disable the authority with given index.
+	#[codec(index = 2)]
+	OnDisabled(AuthorityIndex),
+}
+
+/// Parameters of the ticket lottery.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct SassafrasEpochConfiguration {
+	/// Expected number of valid tickets per slot of an epoch.
+	pub redundancy_factor: u32,
+	/// Number of tickets each authority tries to generate per epoch.
+	pub attempts_number: u32,
+}
+
+impl Default for SassafrasEpochConfiguration {
+	fn default() -> Self {
+		Self { redundancy_factor: 1, attempts_number: 32 }
+	}
+}
+
+/// Configuration data used by the Sassafras consensus engine.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct SassafrasConfiguration {
+	/// The slot duration in milliseconds.
+	pub slot_duration: u64,
+
+	/// The duration of epochs in slots.
+	pub epoch_duration: u64,
+
+	/// The authorities for the genesis epoch.
+	pub authorities: Vec<(AuthorityId, SassafrasAuthorityWeight)>,
+
+	/// The randomness for the genesis epoch.
+	pub randomness: Randomness,
+
+	/// Parameters of the ticket lottery.
+	pub threshold_params: SassafrasEpochConfiguration,
+}
+
+#[cfg(feature = "std")]
+impl sp_consensus::SlotData for SassafrasConfiguration {
+	fn slot_duration(&self) -> std::time::Duration {
+		std::time::Duration::from_millis(self.slot_duration)
+	}
+
+	const SLOT_KEY: &'static [u8] = b"sassafras_configuration";
+}
+
+/// Sassafras epoch information
+#[derive(Decode, Encode, PartialEq, Eq, Clone, Debug)]
+pub struct Epoch {
+	/// The epoch index.
+	pub epoch_index: u64,
+	/// The starting slot of the epoch.
+	pub start_slot: Slot,
+	/// The duration of this epoch.
+	pub duration: u64,
+	/// The authorities and their weights.
+	pub authorities: Vec<(AuthorityId, SassafrasAuthorityWeight)>,
+	/// Randomness for this epoch.
+	pub randomness: Randomness,
+	/// Configuration of the epoch.
+	pub config: SassafrasEpochConfiguration,
+}
+
+impl Epoch {
+	/// The threshold below which the tickets for this epoch are valid.
+	pub fn ticket_threshold(&self) -> u128 {
+		compute_threshold(
+			self.config.redundancy_factor,
+			self.duration,
+			self.config.attempts_number,
+			self.authorities.len() as u32,
+		)
+	}
+}
+
+/// Index of the ticket assigned to the slot at `slot_index` of an epoch of `epoch_duration`
+/// slots, if the tickets are sorted in increasing order.
+///
+/// The tickets are assigned from the outside in: the lowest tickets go alternatively to the
+/// first and the last slots of the epoch, so that the slots without a ticket, if any, are in
+/// the middle of the epoch.
+pub fn ticket_index(slot_index: u64, epoch_duration: u64) -> u64 {
+	if slot_index < epoch_duration / 2 {
+		2 * slot_index + 1
+	} else {
+		2 * (epoch_duration - (slot_index + 1))
+	}
+}
+
+sp_api::decl_runtime_apis! {
+	/// API necessary for block authorship with Sassafras.
+	pub trait SassafrasApi {
+		/// Return the configuration for Sassafras. The configuration is only read on genesis.
+		fn configuration() -> SassafrasConfiguration;
+
+		/// Returns information regarding the current epoch.
+		fn current_epoch() -> Epoch;
+
+		/// Returns information regarding the next epoch (which was already
+		/// previously announced).
+		fn next_epoch() -> Epoch;
+
+		/// Returns the ticket assigned to the given slot, if any. The slot must belong to
+		/// the current epoch or to the next one.
+		fn slot_ticket(slot: Slot) -> Option<Ticket>;
+
+		/// Submits an unsigned extrinsic with the tickets of the authority at `authority_index`
+		/// for the next epoch. Returns `false` if the extrinsic could not be submitted. Only
+		/// useful in an offchain context.
+		fn submit_tickets_unsigned_extrinsic(
+			authority_index: AuthorityIndex,
+			tickets: Vec<TicketEnvelope>,
+		) -> bool;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tickets_are_assigned_from_the_outside_in() {
+		let indices = (0..6).map(|slot| ticket_index(slot, 6)).collect::<Vec<_>>();
+		assert_eq!(indices, vec![1, 3, 5, 4, 2, 0]);
+
+		let indices = (0..5).map(|slot| ticket_index(slot, 5)).collect::<Vec<_>>();
+		assert_eq!(indices, vec![1, 3, 4, 2, 0]);
+	}
+
+	#[test]
+	fn threshold_scales_with_the_expected_number_of_tickets() {
+		let mut ticket = [0u8; VRF_OUTPUT_LENGTH];
+		ticket[15] = 0x3f;
+
+		// a quarter of the tickets are valid.
+		let threshold = compute_threshold(1, 10, 4, 10);
+		assert!(check_threshold(&ticket, threshold));
+		ticket[15] = 0x40;
+		assert!(!check_threshold(&ticket, threshold));
+
+		// every ticket is valid if there are fewer of them than slots.
+		assert_eq!(compute_threshold(1, 100, 1, 10), u128::MAX);
+	}
+}
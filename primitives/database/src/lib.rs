@@ -27,6 +27,9 @@ pub use mem::MemDb;
 /// An identifier for a column.
 pub type ColumnId = u32;
 
+/// An iterator over the key-value pairs of a column.
+pub type ColumnIter<'a> = Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + 'a>;
+
 /// An alteration to the database.
 #[derive(Clone)]
 pub enum Change<H> {
@@ -110,6 +113,13 @@ pub trait Database<H: Clone + AsRef<[u8]>>: Send + Sync {
 	fn supports_ref_counting(&self) -> bool {
 		false
 	}
+
+	/// Iterate over all the key-value pairs stored in column `col`.
+	///
+	/// Returns `None` if the database does not support iteration.
+	fn iter<'a>(&'a self, _col: ColumnId) -> Option<ColumnIter<'a>> {
+		None
+	}
 }
 
 impl<H> std::fmt::Debug for dyn Database<H> {
@@ -17,7 +17,7 @@
 
 //! In-memory implementation of `Database`
 
-use crate::{error, Change, ColumnId, Database, Transaction};
+use crate::{error, Change, ColumnId, ColumnIter, Database, Transaction};
 use parking_lot::RwLock;
 use std::collections::{hash_map::Entry, HashMap};
 
@@ -73,6 +73,15 @@ where
 		let s = self.0.read();
 		s.get(&col).and_then(|c| c.get(key).map(|(_, v)| v.clone()))
 	}
+
+	fn iter<'a>(&'a self, col: ColumnId) -> Option<ColumnIter<'a>> {
+		let s = self.0.read();
+		let values: Vec<_> = s
+			.get(&col)
+			.map(|c| c.iter().map(|(k, (_, v))| (k.clone(), v.clone())).collect())
+			.unwrap_or_default();
+		Some(Box::new(values.into_iter()))
+	}
 }
 
 impl MemDb {
@@ -201,6 +201,11 @@ impl<H, N> EquivocationProof<H, N> {
 	pub fn offender(&self) -> &AuthorityId {
 		self.equivocation.offender()
 	}
+
+	/// Returns the equivocation the proof refers to.
+	pub fn equivocation(&self) -> &Equivocation<H, N> {
+		&self.equivocation
+	}
 }
 
 /// Wrapper object for GRANDPA equivocation proofs, useful for unifying prevote
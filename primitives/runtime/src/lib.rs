@@ -41,7 +41,7 @@ pub use paste;
 pub use sp_application_crypto as app_crypto;
 
 #[cfg(feature = "std")]
-pub use sp_core::storage::{Storage, StorageChild};
+pub use sp_core::storage::{StateVersion, Storage, StorageChild};
 
 use sp_core::{
 	crypto::{self, Public},
@@ -24,7 +24,9 @@ use codec::{Decode, Encode, EncodeAppend};
 use hash_db::Hasher;
 #[cfg(feature = "std")]
 use sp_core::hexdisplay::HexDisplay;
-use sp_core::storage::{well_known_keys::is_child_storage_key, ChildInfo, TrackedStorageKey};
+use sp_core::storage::{
+	well_known_keys::is_child_storage_key, ChildInfo, StateVersion, TrackedStorageKey,
+};
 use sp_externalities::{Extension, ExtensionStore, Externalities};
 use sp_trie::{empty_child_trie_root, trie_types::Layout};
 
@@ -505,7 +507,7 @@ where
 		StorageAppend::new(current_value).append(value);
 	}
 
-	fn storage_root(&mut self) -> Vec<u8> {
+	fn storage_root(&mut self, state_version: StateVersion) -> Vec<u8> {
 		let _guard = guard();
 		if let Some(ref root) = self.storage_transaction_cache.transaction_storage_root {
 			trace!(
@@ -518,7 +520,9 @@ where
 			return root.encode()
 		}
 
-		let root = self.overlay.storage_root(self.backend, self.storage_transaction_cache);
+		let root =
+			self.overlay
+				.storage_root(self.backend, self.storage_transaction_cache, state_version);
 		trace!(
 			target: "state",
 			method = "StorageRoot",
@@ -529,7 +533,11 @@ where
 		root.encode()
 	}
 
-	fn child_storage_root(&mut self, child_info: &ChildInfo) -> Vec<u8> {
+	fn child_storage_root(
+		&mut self,
+		child_info: &ChildInfo,
+		state_version: StateVersion,
+	) -> Vec<u8> {
 		let _guard = guard();
 		let storage_key = child_info.storage_key();
 		let prefixed_storage_key = child_info.prefixed_storage_key();
@@ -550,7 +558,7 @@ where
 		} else {
 			let root = if let Some((changes, info)) = self.overlay.child_changes(storage_key) {
 				let delta = changes.map(|(k, v)| (k.as_ref(), v.value().map(AsRef::as_ref)));
-				Some(self.backend.child_storage_root(info, delta))
+				Some(self.backend.child_storage_root(info, delta, state_version))
 			} else {
 				None
 			};
@@ -647,8 +655,15 @@ where
 		for _ in 0..self.overlay.transaction_depth() {
 			self.overlay.rollback_transaction().expect(BENCHMARKING_FN);
 		}
+		// Bench always use default state version.
+		let state_version = StateVersion::default();
 		self.overlay
-			.drain_storage_changes(self.backend, Default::default(), self.storage_transaction_cache)
+			.drain_storage_changes(
+				self.backend,
+				Default::default(),
+				self.storage_transaction_cache,
+				state_version,
+			)
 			.expect(EXT_NOT_ALLOWED_TO_FAIL);
 		self.backend.wipe().expect(EXT_NOT_ALLOWED_TO_FAIL);
 		self.mark_dirty();
@@ -661,9 +676,16 @@ where
 		for _ in 0..self.overlay.transaction_depth() {
 			self.overlay.commit_transaction().expect(BENCHMARKING_FN);
 		}
+		// Bench always use default state version.
+		let state_version = StateVersion::default();
 		let changes = self
 			.overlay
-			.drain_storage_changes(self.backend, Default::default(), self.storage_transaction_cache)
+			.drain_storage_changes(
+				self.backend,
+				Default::default(),
+				self.storage_transaction_cache,
+				state_version,
+			)
 			.expect(EXT_NOT_ALLOWED_TO_FAIL);
 		self.backend
 			.commit(
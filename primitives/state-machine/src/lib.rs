@@ -151,8 +151,9 @@ mod std_reexport {
 		testing::TestExternalities,
 	};
 	pub use sp_trie::{
-		trie_types::{Layout, TrieDBMut},
-		CompactProof, DBValue, MemoryDB, StorageProof, TrieMut,
+		trie_types::{Layout, TrieDBMut, TrieDBMutV0, TrieDBMutV1},
+		CompactMultiProof, CompactProof, DBValue, LayoutV0, LayoutV1, MemoryDB, StorageProof,
+		TrieMut,
 	};
 }
 
@@ -164,7 +165,7 @@ mod execution {
 	use smallvec::SmallVec;
 	use sp_core::{
 		hexdisplay::HexDisplay,
-		storage::{ChildInfo, ChildType, PrefixedStorageKey},
+		storage::{ChildInfo, ChildType, PrefixedStorageKey, StateVersion},
 		traits::{CodeExecutor, ReadRuntimeVersionExt, RuntimeCode, SpawnNamed},
 		NativeOrEncoded, NeverNativeValue,
 	};
@@ -468,7 +469,8 @@ mod execution {
 				let (wasm_result, _) = self.execute_aux(false, native_call);
 
 				if (result.is_ok() &&
-					wasm_result.is_ok() && result.as_ref().ok() == wasm_result.as_ref().ok()) ||
+					wasm_result.is_ok() &&
+					result.as_ref().ok() == wasm_result.as_ref().ok()) ||
 					result.is_err() && wasm_result.is_err()
 				{
 					result
@@ -1092,6 +1094,44 @@ mod execution {
 		Ok(result)
 	}
 
+	/// Check storage read proof at several state roots, generated by
+	/// [`sp_trie::encode_compact_multi`] from the `prove_read` proofs of each root.
+	///
+	/// The `roots` are given with their state version, in the order of the proofs. Returns the
+	/// values of `keys` at each of the `roots`, in the same order.
+	pub fn read_proof_check_range<H, I>(
+		roots: &[(H::Out, StateVersion)],
+		proof: CompactMultiProof,
+		keys: I,
+	) -> Result<Vec<HashMap<Vec<u8>, Option<Vec<u8>>>>, Box<dyn Error>>
+	where
+		H: Hasher,
+		H::Out: Ord + Codec,
+		I: IntoIterator,
+		I::Item: AsRef<[u8]>,
+	{
+		let state_versions =
+			roots.iter().map(|(_, state_version)| *state_version).collect::<Vec<_>>();
+		let db = proof
+			.to_memory_db::<H>(&state_versions)
+			.map_err(|_| Box::new(ExecutionError::InvalidProof) as Box<dyn Error>)?;
+		let keys = keys.into_iter().collect::<Vec<_>>();
+		let mut results = Vec::with_capacity(roots.len());
+		for (root, _) in roots {
+			if !hash_db::HashDB::contains(&db, root, hash_db::EMPTY_PREFIX) {
+				return Err(Box::new(ExecutionError::InvalidProof))
+			}
+			let proving_backend = TrieBackend::new(db.clone(), *root);
+			let mut result = HashMap::new();
+			for key in &keys {
+				let value = read_proof_check_on_proving_backend(&proving_backend, key.as_ref())?;
+				result.insert(key.as_ref().to_vec(), value);
+			}
+			results.push(result);
+		}
+		Ok(results)
+	}
+
 	/// Check storage range proof with child trie included, generated by
 	/// `prove_range_read_with_child_with_size` call.
 	///
@@ -1350,7 +1390,7 @@ mod tests {
 	use codec::{Decode, Encode};
 	use sp_core::{
 		map,
-		storage::ChildInfo,
+		storage::{ChildInfo, StateVersion},
 		testing::TaskExecutor,
 		traits::{CodeExecutor, Externalities, RuntimeCode},
 		NativeOrEncoded, NeverNativeValue,
@@ -1413,7 +1453,12 @@ mod tests {
 
 	#[test]
 	fn execute_works() {
-		let backend = trie_backend::tests::test_trie();
+		execute_works_inner(StateVersion::V0);
+		execute_works_inner(StateVersion::V1);
+	}
+
+	fn execute_works_inner(state_version: StateVersion) {
+		let backend = trie_backend::tests::test_trie(state_version);
 		let mut overlayed_changes = Default::default();
 		let wasm_code = RuntimeCode::empty();
 
@@ -1437,7 +1482,12 @@ mod tests {
 
 	#[test]
 	fn execute_works_with_native_else_wasm() {
-		let backend = trie_backend::tests::test_trie();
+		execute_works_with_native_else_wasm_inner(StateVersion::V0);
+		execute_works_with_native_else_wasm_inner(StateVersion::V1);
+	}
+
+	fn execute_works_with_native_else_wasm_inner(state_version: StateVersion) {
+		let backend = trie_backend::tests::test_trie(state_version);
 		let mut overlayed_changes = Default::default();
 		let wasm_code = RuntimeCode::empty();
 
@@ -1461,8 +1511,13 @@ mod tests {
 
 	#[test]
 	fn dual_execution_strategy_detects_consensus_failure() {
+		dual_execution_strategy_detects_consensus_failure_inner(StateVersion::V0);
+		dual_execution_strategy_detects_consensus_failure_inner(StateVersion::V1);
+	}
+
+	fn dual_execution_strategy_detects_consensus_failure_inner(state_version: StateVersion) {
 		let mut consensus_failed = false;
-		let backend = trie_backend::tests::test_trie();
+		let backend = trie_backend::tests::test_trie(state_version);
 		let mut overlayed_changes = Default::default();
 		let wasm_code = RuntimeCode::empty();
 
@@ -1495,6 +1550,11 @@ mod tests {
 
 	#[test]
 	fn prove_execution_and_proof_check_works() {
+		prove_execution_and_proof_check_works_inner(StateVersion::V0);
+		prove_execution_and_proof_check_works_inner(StateVersion::V1);
+	}
+
+	fn prove_execution_and_proof_check_works_inner(state_version: StateVersion) {
 		let executor = DummyCodeExecutor {
 			native_available: true,
 			native_succeeds: true,
@@ -1502,8 +1562,8 @@ mod tests {
 		};
 
 		// fetch execution proof from 'remote' full node
-		let mut remote_backend = trie_backend::tests::test_trie();
-		let remote_root = remote_backend.storage_root(std::iter::empty()).0;
+		let mut remote_backend = trie_backend::tests::test_trie(state_version);
+		let remote_root = remote_backend.storage_root(std::iter::empty(), state_version).0;
 		let (remote_result, remote_proof) = prove_execution(
 			&mut remote_backend,
 			&mut Default::default(),
@@ -1784,15 +1844,67 @@ mod tests {
 			.0
 	}
 
+	#[test]
+	fn prove_read_at_several_roots_and_proof_check_works() {
+		use StateVersion::{V0, V1};
+		prove_read_at_several_roots_and_proof_check_works_inner(V0, V0);
+		prove_read_at_several_roots_and_proof_check_works_inner(V1, V1);
+		prove_read_at_several_roots_and_proof_check_works_inner(V0, V1);
+	}
+
+	fn prove_read_at_several_roots_and_proof_check_works_inner(
+		version1: StateVersion,
+		version2: StateVersion,
+	) {
+		let backend1 = trie_backend::tests::test_trie(version1);
+		let root1 = *backend1.root();
+		// The value is big enough to be stored in its own node from `StateVersion::V1`.
+		let value2 = vec![25u8; 40];
+		let (root2, transaction) =
+			backend1.storage_root(std::iter::once((&b"value2"[..], Some(&value2[..]))), version2);
+		let mut db = backend1.backend_storage().clone();
+		db.consolidate(transaction);
+		let backend2 = TrieBackend::new(db, root2);
+
+		let keys: &[&[u8]] = &[b"value1", b"value2"];
+		let proofs = vec![
+			(prove_read(backend1, keys).unwrap(), version1),
+			(prove_read(backend2, keys).unwrap(), version2),
+		];
+		let proof = sp_trie::encode_compact_multi::<BlakeTwo256, _>(proofs).unwrap();
+
+		let roots = [(root1, version1), (root2, version2)];
+		let results =
+			read_proof_check_range::<BlakeTwo256, _>(&roots, proof.clone(), keys).unwrap();
+		let expected = |value2| {
+			vec![(b"value1".to_vec(), Some(vec![42])), (b"value2".to_vec(), Some(value2))]
+				.into_iter()
+				.collect::<HashMap<_, _>>()
+		};
+		assert_eq!(results, vec![expected(vec![24]), expected(value2)]);
+
+		assert!(
+			read_proof_check_range::<BlakeTwo256, _>(&roots, proof.clone(), &[&[0xff]]).is_err()
+		);
+		assert!(read_proof_check_range::<BlakeTwo256, _>(&roots[..1], proof.clone(), keys).is_err());
+		let roots = [(root1, version1), (Default::default(), version2)];
+		assert!(read_proof_check_range::<BlakeTwo256, _>(&roots, proof, keys).is_err());
+	}
+
 	#[test]
 	fn prove_read_and_proof_check_works() {
+		prove_read_and_proof_check_works_inner(StateVersion::V0);
+		prove_read_and_proof_check_works_inner(StateVersion::V1);
+	}
+
+	fn prove_read_and_proof_check_works_inner(state_version: StateVersion) {
 		let child_info = ChildInfo::new_default(b"sub1");
 		let missing_child_info = ChildInfo::new_default(b"sub1sub2"); // key will include other child root to proof.
 		let child_info = &child_info;
 		let missing_child_info = &missing_child_info;
 		// fetch read proof from 'remote' full node
-		let remote_backend = trie_backend::tests::test_trie();
-		let remote_root = remote_backend.storage_root(std::iter::empty()).0;
+		let remote_backend = trie_backend::tests::test_trie(state_version);
+		let remote_root = remote_backend.storage_root(std::iter::empty(), state_version).0;
 		let remote_proof = prove_read(remote_backend, &[b"value2"]).unwrap();
 		let remote_proof = test_compact(remote_proof, &remote_root);
 		// check proof locally
@@ -1809,8 +1921,8 @@ mod tests {
 		);
 		assert_eq!(local_result2, false);
 		// on child trie
-		let remote_backend = trie_backend::tests::test_trie();
-		let remote_root = remote_backend.storage_root(std::iter::empty()).0;
+		let remote_backend = trie_backend::tests::test_trie(state_version);
+		let remote_root = remote_backend.storage_root(std::iter::empty(), state_version).0;
 		let remote_proof = prove_child_read(remote_backend, child_info, &[b"value3"]).unwrap();
 		let remote_proof = test_compact(remote_proof, &remote_root);
 		let local_result1 = read_child_proof_check::<BlakeTwo256, _>(
@@ -1937,15 +2049,20 @@ mod tests {
 
 	#[test]
 	fn prove_read_with_size_limit_works() {
-		let remote_backend = trie_backend::tests::test_trie();
-		let remote_root = remote_backend.storage_root(::std::iter::empty()).0;
+		prove_read_with_size_limit_works_inner(StateVersion::V0);
+		prove_read_with_size_limit_works_inner(StateVersion::V1);
+	}
+
+	fn prove_read_with_size_limit_works_inner(state_version: StateVersion) {
+		let remote_backend = trie_backend::tests::test_trie(state_version);
+		let remote_root = remote_backend.storage_root(::std::iter::empty(), state_version).0;
 		let (proof, count) =
 			prove_range_read_with_size(remote_backend, None, None, 0, None).unwrap();
 		// Always contains at least some nodes.
 		assert_eq!(proof.into_memory_db::<BlakeTwo256>().drain().len(), 3);
 		assert_eq!(count, 1);
 
-		let remote_backend = trie_backend::tests::test_trie();
+		let remote_backend = trie_backend::tests::test_trie(state_version);
 		let (proof, count) =
 			prove_range_read_with_size(remote_backend, None, None, 800, Some(&[])).unwrap();
 		assert_eq!(proof.clone().into_memory_db::<BlakeTwo256>().drain().len(), 9);
@@ -1968,7 +2085,7 @@ mod tests {
 		assert_eq!(results.len() as u32, 101);
 		assert_eq!(completed, false);
 
-		let remote_backend = trie_backend::tests::test_trie();
+		let remote_backend = trie_backend::tests::test_trie(state_version);
 		let (proof, count) =
 			prove_range_read_with_size(remote_backend, None, None, 50000, Some(&[])).unwrap();
 		assert_eq!(proof.clone().into_memory_db::<BlakeTwo256>().drain().len(), 11);
@@ -1988,8 +2105,13 @@ mod tests {
 
 	#[test]
 	fn prove_range_with_child_works() {
-		let remote_backend = trie_backend::tests::test_trie();
-		let remote_root = remote_backend.storage_root(::std::iter::empty()).0;
+		prove_range_with_child_works_inner(StateVersion::V0);
+		prove_range_with_child_works_inner(StateVersion::V1);
+	}
+
+	fn prove_range_with_child_works_inner(state_version: StateVersion) {
+		let remote_backend = trie_backend::tests::test_trie(state_version);
+		let remote_root = remote_backend.storage_root(::std::iter::empty(), state_version).0;
 		let mut start_at = smallvec::SmallVec::<[Vec<u8>; 2]>::new();
 		let trie_backend = remote_backend.as_trie_backend().unwrap();
 		let max_iter = 1000;
@@ -2027,13 +2149,18 @@ mod tests {
 
 	#[test]
 	fn compact_multiple_child_trie() {
+		compact_multiple_child_trie_inner(StateVersion::V0);
+		compact_multiple_child_trie_inner(StateVersion::V1);
+	}
+
+	fn compact_multiple_child_trie_inner(state_version: StateVersion) {
 		// this root will be queried
 		let child_info1 = ChildInfo::new_default(b"sub1");
 		// this root will not be include in proof
 		let child_info2 = ChildInfo::new_default(b"sub2");
 		// this root will be include in proof
 		let child_info3 = ChildInfo::new_default(b"sub");
-		let remote_backend = trie_backend::tests::test_trie();
+		let remote_backend = trie_backend::tests::test_trie(state_version);
 		let (remote_root, transaction) = remote_backend.full_storage_root(
 			std::iter::empty(),
 			vec![
@@ -2054,6 +2181,7 @@ mod tests {
 				),
 			]
 			.into_iter(),
+			state_version,
 		);
 		let mut remote_storage = remote_backend.into_storage();
 		remote_storage.consolidate(transaction);
@@ -2071,8 +2199,49 @@ mod tests {
 		assert_eq!(local_result1.get(&b"key1"[..]), Some(&Some(b"val2".to_vec())));
 	}
 
+	#[test]
+	fn compact_range_proof_decodes_into_key_spaces() {
+		compact_range_proof_decodes_into_key_spaces_inner(StateVersion::V0);
+		compact_range_proof_decodes_into_key_spaces_inner(StateVersion::V1);
+	}
+
+	fn compact_range_proof_decodes_into_key_spaces_inner(state_version: StateVersion) {
+		let child_info = ChildInfo::new_default(b"sub1");
+		let remote_backend = trie_backend::tests::test_trie(state_version);
+		let (remote_root, transaction) = remote_backend.full_storage_root(
+			std::iter::empty(),
+			vec![(&child_info, vec![(&b"key1"[..], Some(&b"val2"[..]))].into_iter())].into_iter(),
+			state_version,
+		);
+		let mut remote_storage = remote_backend.into_storage();
+		remote_storage.consolidate(transaction);
+		let remote_backend = TrieBackend::new(remote_storage, remote_root);
+		let (proof, _) =
+			prove_range_read_with_child_with_size(remote_backend, usize::MAX, &[]).unwrap();
+		let proof = proof.into_compact_proof::<BlakeTwo256>(remote_root).unwrap();
+
+		let mut db = sp_trie::PrefixedMemoryDB::<BlakeTwo256>::default();
+		sp_trie::decode_compact_with_key_spaces::<Layout<BlakeTwo256>, _, _>(
+			&mut db,
+			proof.iter_compact_encoded_nodes(),
+			Some(&remote_root),
+		)
+		.unwrap();
+		let local_backend = TrieBackend::new(db, remote_root);
+		assert_eq!(local_backend.storage(b"key").unwrap(), Some(b"value".to_vec()));
+		assert_eq!(
+			local_backend.child_storage(&child_info, b"key1").unwrap(),
+			Some(b"val2".to_vec())
+		);
+	}
+
 	#[test]
 	fn child_storage_uuid() {
+		child_storage_uuid_inner(StateVersion::V0);
+		child_storage_uuid_inner(StateVersion::V1);
+	}
+
+	fn child_storage_uuid_inner(state_version: StateVersion) {
 		let child_info_1 = ChildInfo::new_default(b"sub_test1");
 		let child_info_2 = ChildInfo::new_default(b"sub_test2");
 
@@ -2080,12 +2249,12 @@ mod tests {
 		let mut overlay = OverlayedChanges::default();
 
 		let mut transaction = {
-			let backend = test_trie();
+			let backend = test_trie(state_version);
 			let mut cache = StorageTransactionCache::default();
 			let mut ext = Ext::new(&mut overlay, &mut cache, &backend, None);
 			ext.set_child_storage(&child_info_1, b"abc".to_vec(), b"def".to_vec());
 			ext.set_child_storage(&child_info_2, b"abc".to_vec(), b"def".to_vec());
-			ext.storage_root();
+			ext.storage_root(state_version);
 			cache.transaction.unwrap()
 		};
 		let mut duplicate = false;
@@ -2131,12 +2300,19 @@ mod tests {
 
 	#[test]
 	fn runtime_registered_extensions_are_removed_after_execution() {
+		runtime_registered_extensions_are_removed_after_execution_inner(StateVersion::V0);
+		runtime_registered_extensions_are_removed_after_execution_inner(StateVersion::V1);
+	}
+
+	fn runtime_registered_extensions_are_removed_after_execution_inner(
+		state_version: StateVersion,
+	) {
 		use sp_externalities::ExternalitiesExt;
 		sp_externalities::decl_extension! {
 			struct DummyExt(u32);
 		}
 
-		let backend = trie_backend::tests::test_trie();
+		let backend = trie_backend::tests::test_trie(state_version);
 		let mut overlayed_changes = Default::default();
 		let wasm_code = RuntimeCode::empty();
 
@@ -18,6 +18,7 @@
 //! Proving state machine backend.
 
 use crate::{
+	overlayed_changes::NoOpenTransaction,
 	trie_backend::TrieBackend,
 	trie_backend_essence::{Ephemeral, TrieBackendEssence, TrieBackendStorage},
 	Backend, DBValue, Error, ExecutionError,
@@ -26,10 +27,10 @@ use codec::{Codec, Decode, Encode};
 use hash_db::{HashDB, Hasher, Prefix, EMPTY_PREFIX};
 use log::debug;
 use parking_lot::RwLock;
-use sp_core::storage::ChildInfo;
+use sp_core::storage::{ChildInfo, StateVersion};
 use sp_trie::{
 	empty_child_trie_root, read_child_trie_value_with, read_trie_value_with, record_all_keys,
-	MemoryDB, StorageProof,
+	MemoryDB, NodeCodec, StorageProof,
 };
 pub use sp_trie::{
 	trie_types::{Layout, TrieError},
@@ -39,6 +40,10 @@ use std::{
 	collections::{hash_map::Entry, HashMap},
 	sync::Arc,
 };
+use trie_db::{
+	node::{NodeHandlePlan, NodePlan},
+	NodeCodec as _,
+};
 
 /// Patricia trie-based backend specialized in get value proofs.
 pub struct ProvingBackendRecorder<'a, S: 'a + TrieBackendStorage<H>, H: 'a + Hasher> {
@@ -117,6 +122,12 @@ struct ProofRecorderInner<Hash> {
 	records: HashMap<Hash, Option<DBValue>>,
 	/// The encoded size of all recorded values.
 	encoded_size: usize,
+	/// The number of references to each node from the recorded nodes.
+	references: HashMap<Hash, usize>,
+	/// The size of the references between recorded nodes, which a compact proof omits.
+	omitted_size: usize,
+	/// The records added in each open transaction, with the nodes they reference.
+	transactions: Vec<Vec<(Hash, Vec<Hash>)>>,
 }
 
 /// Global proof recorder, act as a layer over a hash db for recording queried data.
@@ -125,20 +136,42 @@ pub struct ProofRecorder<Hash> {
 	inner: Arc<RwLock<ProofRecorderInner<Hash>>>,
 }
 
-impl<Hash: std::hash::Hash + Eq> ProofRecorder<Hash> {
+impl<Hash: std::hash::Hash + Eq + Clone + AsRef<[u8]>> ProofRecorder<Hash> {
 	/// Record the given `key` => `val` combination.
 	pub fn record(&self, key: Hash, val: Option<DBValue>) {
+		self.record_with_references(key, val, Vec::new());
+	}
+
+	/// Record the given trie node.
+	///
+	/// Unlike [`Self::record`], the references to other nodes are tracked, so that the size of
+	/// the compact proof can be estimated.
+	pub fn record_node<H: Hasher<Out = Hash>>(&self, key: Hash, val: Option<DBValue>) {
+		let references = val.as_deref().map(node_references::<H>).unwrap_or_default();
+		self.record_with_references(key, val, references);
+	}
+
+	fn record_with_references(&self, key: Hash, val: Option<DBValue>, references: Vec<Hash>) {
 		let mut inner = self.inner.write();
-		let encoded_size = if let Entry::Vacant(entry) = inner.records.entry(key) {
-			let encoded_size = val.as_ref().map(Encode::encoded_size).unwrap_or(0);
+		let inner = &mut *inner;
+		if inner.records.contains_key(&key) {
+			return
+		}
 
-			entry.insert(val);
-			encoded_size
-		} else {
-			0
-		};
+		let hash_len = key.as_ref().len();
+		inner.encoded_size += val.as_ref().map(Encode::encoded_size).unwrap_or(0);
+		inner.omitted_size += inner.references.get(&key).copied().unwrap_or(0) * hash_len;
+		for child in &references {
+			*inner.references.entry(child.clone()).or_default() += 1;
+			if inner.records.contains_key(child) {
+				inner.omitted_size += hash_len;
+			}
+		}
 
-		inner.encoded_size += encoded_size;
+		inner.records.insert(key.clone(), val);
+		if let Some(transaction) = inner.transactions.last_mut() {
+			transaction.push((key, references));
+		}
 	}
 
 	/// Returns the value at the given `key`.
@@ -155,6 +188,66 @@ impl<Hash: std::hash::Hash + Eq> ProofRecorder<Hash> {
 		inner.encoded_size + codec::Compact(inner.records.len() as u32).encoded_size()
 	}
 
+	/// Returns the estimated encoded size of the proof in compact form.
+	///
+	/// Only the references between the nodes recorded with [`Self::record_node`] are deducted
+	/// from [`Self::estimate_encoded_size`], so the estimation is usually a bit bigger than the
+	/// actual encoded [`sp_trie::CompactProof`]. Unlike
+	/// [`StorageProof::encoded_compact_size`], it is kept up to date while recording.
+	pub fn estimate_compact_encoded_size(&self) -> usize {
+		let inner = self.inner.read();
+		inner.encoded_size - inner.omitted_size +
+			codec::Compact(inner.records.len() as u32).encoded_size()
+	}
+
+	/// Start a new transaction.
+	///
+	/// The records added until the transaction is closed can be removed again with
+	/// [`Self::rollback_transaction`].
+	pub fn start_transaction(&self) {
+		self.inner.write().transactions.push(Vec::new());
+	}
+
+	/// Keep the records added in the last open transaction.
+	///
+	/// Returns an error if there is no open transaction.
+	pub fn commit_transaction(&self) -> Result<(), NoOpenTransaction> {
+		let mut inner = self.inner.write();
+		let records = inner.transactions.pop().ok_or(NoOpenTransaction)?;
+		if let Some(transaction) = inner.transactions.last_mut() {
+			transaction.extend(records);
+		}
+		Ok(())
+	}
+
+	/// Remove the records added in the last open transaction.
+	///
+	/// Returns an error if there is no open transaction.
+	pub fn rollback_transaction(&self) -> Result<(), NoOpenTransaction> {
+		let mut inner = self.inner.write();
+		let inner = &mut *inner;
+		let records = inner.transactions.pop().ok_or(NoOpenTransaction)?;
+		for (key, references) in records.into_iter().rev() {
+			let hash_len = key.as_ref().len();
+			for child in &references {
+				if let Entry::Occupied(mut entry) = inner.references.entry(child.clone()) {
+					*entry.get_mut() -= 1;
+					if *entry.get() == 0 {
+						entry.remove();
+					}
+				}
+				if inner.records.contains_key(child) {
+					inner.omitted_size -= hash_len;
+				}
+			}
+			inner.omitted_size -= inner.references.get(&key).copied().unwrap_or(0) * hash_len;
+			if let Some(val) = inner.records.remove(&key) {
+				inner.encoded_size -= val.as_ref().map(Encode::encoded_size).unwrap_or(0);
+			}
+		}
+		Ok(())
+	}
+
 	/// Convert into a [`StorageProof`].
 	pub fn to_storage_proof(&self) -> StorageProof {
 		let trie_nodes = self
@@ -173,9 +266,39 @@ impl<Hash: std::hash::Hash + Eq> ProofRecorder<Hash> {
 		let mut inner = self.inner.write();
 		inner.records.clear();
 		inner.encoded_size = 0;
+		inner.references.clear();
+		inner.omitted_size = 0;
+		inner.transactions.clear();
 	}
 }
 
+/// Returns the hashes of the nodes referenced by the encoded trie `node`.
+///
+/// Values that can't be decoded as a node, e.g. hashed values, don't reference anything.
+fn node_references<H: Hasher>(node: &[u8]) -> Vec<H::Out> {
+	let children = match NodeCodec::<H>::decode_plan(node) {
+		Ok(NodePlan::Extension { child, .. }) => vec![child],
+		Ok(NodePlan::Branch { children, .. }) | Ok(NodePlan::NibbledBranch { children, .. }) =>
+			children.into_iter().flatten().collect(),
+		_ => return Vec::new(),
+	};
+
+	children
+		.into_iter()
+		.filter_map(|child| match child {
+			NodeHandlePlan::Hash(range) => {
+				let mut hash = H::Out::default();
+				if hash.as_ref().len() != range.len() {
+					return None
+				}
+				hash.as_mut().copy_from_slice(&node[range]);
+				Some(hash)
+			},
+			NodeHandlePlan::Inline(_) => None,
+		})
+		.collect()
+}
+
 /// Patricia trie-based backend which also tracks all touched storage trie values.
 /// These can be sent to remote node and used as a proof of execution.
 pub struct ProvingBackend<'a, S: 'a + TrieBackendStorage<H>, H: 'a + Hasher>(
@@ -221,6 +344,17 @@ where
 	pub fn estimate_encoded_size(&self) -> usize {
 		self.0.essence().backend_storage().proof_recorder.estimate_encoded_size()
 	}
+
+	/// Returns the estimated encoded size of the proof in compact form.
+	///
+	/// See [`ProofRecorder::estimate_compact_encoded_size`].
+	pub fn estimate_compact_encoded_size(&self) -> usize {
+		self.0
+			.essence()
+			.backend_storage()
+			.proof_recorder
+			.estimate_compact_encoded_size()
+	}
 }
 
 impl<'a, S: 'a + TrieBackendStorage<H>, H: 'a + Hasher> TrieBackendStorage<H>
@@ -234,7 +368,7 @@ impl<'a, S: 'a + TrieBackendStorage<H>, H: 'a + Hasher> TrieBackendStorage<H>
 		}
 
 		let backend_value = self.backend.get(key, prefix)?;
-		self.proof_recorder.record(key.clone(), backend_value.clone());
+		self.proof_recorder.record_node::<H>(key.clone(), backend_value.clone());
 		Ok(backend_value)
 	}
 }
@@ -333,22 +467,24 @@ where
 	fn storage_root<'b>(
 		&self,
 		delta: impl Iterator<Item = (&'b [u8], Option<&'b [u8]>)>,
+		state_version: StateVersion,
 	) -> (H::Out, Self::Transaction)
 	where
 		H::Out: Ord,
 	{
-		self.0.storage_root(delta)
+		self.0.storage_root(delta, state_version)
 	}
 
 	fn child_storage_root<'b>(
 		&self,
 		child_info: &ChildInfo,
 		delta: impl Iterator<Item = (&'b [u8], Option<&'b [u8]>)>,
+		state_version: StateVersion,
 	) -> (H::Out, bool, Self::Transaction)
 	where
 		H::Out: Ord,
 	{
-		self.0.child_storage_root(child_info, delta)
+		self.0.child_storage_root(child_info, delta, state_version)
 	}
 
 	fn register_overlay_stats(&self, _stats: &crate::stats::StateMachineStats) {}
@@ -394,13 +530,23 @@ mod tests {
 
 	#[test]
 	fn proof_is_empty_until_value_is_read() {
-		let trie_backend = test_trie();
+		proof_is_empty_until_value_is_read_inner(StateVersion::V0);
+		proof_is_empty_until_value_is_read_inner(StateVersion::V1);
+	}
+
+	fn proof_is_empty_until_value_is_read_inner(state_version: StateVersion) {
+		let trie_backend = test_trie(state_version);
 		assert!(test_proving(&trie_backend).extract_proof().is_empty());
 	}
 
 	#[test]
 	fn proof_is_non_empty_after_value_is_read() {
-		let trie_backend = test_trie();
+		proof_is_non_empty_after_value_is_read_inner(StateVersion::V0);
+		proof_is_non_empty_after_value_is_read_inner(StateVersion::V1);
+	}
+
+	fn proof_is_non_empty_after_value_is_read_inner(state_version: StateVersion) {
+		let trie_backend = test_trie(state_version);
 		let backend = test_proving(&trie_backend);
 		assert_eq!(backend.storage(b"key").unwrap(), Some(b"value".to_vec()));
 		assert!(!backend.extract_proof().is_empty());
@@ -418,27 +564,39 @@ mod tests {
 
 	#[test]
 	fn passes_through_backend_calls() {
-		let trie_backend = test_trie();
+		passes_through_backend_calls_inner(StateVersion::V0);
+		passes_through_backend_calls_inner(StateVersion::V1);
+	}
+
+	fn passes_through_backend_calls_inner(state_version: StateVersion) {
+		let trie_backend = test_trie(state_version);
 		let proving_backend = test_proving(&trie_backend);
 		assert_eq!(trie_backend.storage(b"key").unwrap(), proving_backend.storage(b"key").unwrap());
 		assert_eq!(trie_backend.pairs(), proving_backend.pairs());
 
-		let (trie_root, mut trie_mdb) = trie_backend.storage_root(std::iter::empty());
-		let (proving_root, mut proving_mdb) = proving_backend.storage_root(std::iter::empty());
+		let (trie_root, mut trie_mdb) =
+			trie_backend.storage_root(std::iter::empty(), state_version);
+		let (proving_root, mut proving_mdb) =
+			proving_backend.storage_root(std::iter::empty(), state_version);
 		assert_eq!(trie_root, proving_root);
 		assert_eq!(trie_mdb.drain(), proving_mdb.drain());
 	}
 
 	#[test]
 	fn proof_recorded_and_checked() {
+		proof_recorded_and_checked_inner(StateVersion::V0);
+		proof_recorded_and_checked_inner(StateVersion::V1);
+	}
+
+	fn proof_recorded_and_checked_inner(state_version: StateVersion) {
 		let contents = (0..64).map(|i| (vec![i], Some(vec![i]))).collect::<Vec<_>>();
 		let in_memory = InMemoryBackend::<BlakeTwo256>::default();
-		let in_memory = in_memory.update(vec![(None, contents)]);
-		let in_memory_root = in_memory.storage_root(::std::iter::empty()).0;
+		let in_memory = in_memory.update(vec![(None, contents)], state_version);
+		let in_memory_root = in_memory.storage_root(::std::iter::empty(), state_version).0;
 		(0..64).for_each(|i| assert_eq!(in_memory.storage(&[i]).unwrap().unwrap(), vec![i]));
 
 		let trie = in_memory.as_trie_backend().unwrap();
-		let trie_root = trie.storage_root(::std::iter::empty()).0;
+		let trie_root = trie.storage_root(::std::iter::empty(), state_version).0;
 		assert_eq!(in_memory_root, trie_root);
 		(0..64).for_each(|i| assert_eq!(trie.storage(&[i]).unwrap().unwrap(), vec![i]));
 
@@ -454,6 +612,11 @@ mod tests {
 
 	#[test]
 	fn proof_recorded_and_checked_with_child() {
+		proof_recorded_and_checked_with_child_inner(StateVersion::V0);
+		proof_recorded_and_checked_with_child_inner(StateVersion::V1);
+	}
+
+	fn proof_recorded_and_checked_with_child_inner(state_version: StateVersion) {
 		let child_info_1 = ChildInfo::new_default(b"sub1");
 		let child_info_2 = ChildInfo::new_default(b"sub2");
 		let child_info_1 = &child_info_1;
@@ -464,12 +627,13 @@ mod tests {
 			(Some(child_info_2.clone()), (10..15).map(|i| (vec![i], Some(vec![i]))).collect()),
 		];
 		let in_memory = InMemoryBackend::<BlakeTwo256>::default();
-		let in_memory = in_memory.update(contents);
+		let in_memory = in_memory.update(contents, state_version);
 		let child_storage_keys = vec![child_info_1.to_owned(), child_info_2.to_owned()];
 		let in_memory_root = in_memory
 			.full_storage_root(
 				std::iter::empty(),
 				child_storage_keys.iter().map(|k| (k, std::iter::empty())),
+				state_version,
 			)
 			.0;
 		(0..64).for_each(|i| assert_eq!(in_memory.storage(&[i]).unwrap().unwrap(), vec![i]));
@@ -481,7 +645,7 @@ mod tests {
 		});
 
 		let trie = in_memory.as_trie_backend().unwrap();
-		let trie_root = trie.storage_root(std::iter::empty()).0;
+		let trie_root = trie.storage_root(std::iter::empty(), state_version).0;
 		assert_eq!(in_memory_root, trie_root);
 		(0..64).for_each(|i| assert_eq!(trie.storage(&[i]).unwrap().unwrap(), vec![i]));
 
@@ -509,7 +673,12 @@ mod tests {
 
 	#[test]
 	fn storage_proof_encoded_size_estimation_works() {
-		let trie_backend = test_trie();
+		storage_proof_encoded_size_estimation_works_inner(StateVersion::V0);
+		storage_proof_encoded_size_estimation_works_inner(StateVersion::V1);
+	}
+
+	fn storage_proof_encoded_size_estimation_works_inner(state_version: StateVersion) {
+		let trie_backend = test_trie(state_version);
 		let backend = test_proving(&trie_backend);
 
 		let check_estimation =
@@ -536,4 +705,65 @@ mod tests {
 		assert!(backend.storage(b"doesnotexist2").unwrap().is_none());
 		check_estimation(&backend);
 	}
+
+	#[test]
+	fn compact_proof_encoded_size_estimation_works() {
+		compact_proof_encoded_size_estimation_works_inner(StateVersion::V0);
+		compact_proof_encoded_size_estimation_works_inner(StateVersion::V1);
+	}
+
+	fn compact_proof_encoded_size_estimation_works_inner(state_version: StateVersion) {
+		let trie_backend = test_trie(state_version);
+		let root = *trie_backend.root();
+		let backend = test_proving(&trie_backend);
+
+		let check_estimation =
+			|backend: &ProvingBackend<'_, PrefixedMemoryDB<BlakeTwo256>, BlakeTwo256>| {
+				let compact_size =
+					backend.extract_proof().encoded_compact_size::<BlakeTwo256>(root).unwrap();
+				let estimation = backend.estimate_compact_encoded_size();
+
+				assert!(estimation >= compact_size);
+				assert!(estimation <= backend.estimate_encoded_size());
+			};
+
+		assert_eq!(backend.storage(b"key").unwrap(), Some(b"value".to_vec()));
+		check_estimation(&backend);
+
+		assert_eq!(backend.storage(b"value1").unwrap(), Some(vec![42]));
+		check_estimation(&backend);
+
+		assert!(backend.storage(b"doesnotexist").unwrap().is_none());
+		check_estimation(&backend);
+
+		// The branch node of this key is referenced by the root node.
+		assert_eq!(backend.storage(&[128]).unwrap(), Some(vec![128]));
+		check_estimation(&backend);
+		assert!(backend.estimate_compact_encoded_size() < backend.estimate_encoded_size());
+	}
+
+	#[test]
+	fn proof_recorder_transaction_rollback_removes_records() {
+		let trie_backend = test_trie(StateVersion::V1);
+		let recorder = ProofRecorder::default();
+		let backend = ProvingBackend::new_with_recorder(&trie_backend, recorder.clone());
+
+		assert_eq!(backend.storage(b"key").unwrap(), Some(b"value".to_vec()));
+		let proof = backend.extract_proof();
+		let encoded_size = recorder.estimate_encoded_size();
+		let compact_size = recorder.estimate_compact_encoded_size();
+
+		recorder.start_transaction();
+		assert_eq!(backend.storage(&[128]).unwrap(), Some(vec![128]));
+		recorder.start_transaction();
+		assert_eq!(backend.storage(&[144]).unwrap(), Some(vec![144]));
+		recorder.commit_transaction().unwrap();
+		assert!(recorder.estimate_compact_encoded_size() > compact_size);
+		recorder.rollback_transaction().unwrap();
+
+		assert_eq!(backend.extract_proof(), proof);
+		assert_eq!(recorder.estimate_encoded_size(), encoded_size);
+		assert_eq!(recorder.estimate_compact_encoded_size(), compact_size);
+		assert!(recorder.rollback_transaction().is_err());
+	}
 }
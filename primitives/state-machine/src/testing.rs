@@ -32,7 +32,7 @@ use sp_core::{
 	offchain::testing::TestPersistentOffchainDB,
 	storage::{
 		well_known_keys::{is_child_storage_key, CODE},
-		Storage,
+		StateVersion, Storage,
 	},
 	testing::TaskExecutor,
 	traits::TaskExecutorExt,
@@ -53,6 +53,8 @@ where
 	pub backend: InMemoryBackend<H>,
 	/// Extensions.
 	pub extensions: Extensions,
+	/// State version to use during tests.
+	pub state_version: StateVersion,
 }
 
 impl<H: Hasher> TestExternalities<H>
@@ -71,7 +73,12 @@ where
 
 	/// Create a new instance of `TestExternalities` with storage.
 	pub fn new(storage: Storage) -> Self {
-		Self::new_with_code(&[], storage)
+		Self::new_with_code_and_state(&[], storage, Default::default())
+	}
+
+	/// Create a new instance of `TestExternalities` with storage for a given state version.
+	pub fn new_with_state_version(storage: Storage, state_version: StateVersion) -> Self {
+		Self::new_with_code_and_state(&[], storage, state_version)
 	}
 
 	/// New empty test externalities.
@@ -80,7 +87,16 @@ where
 	}
 
 	/// Create a new instance of `TestExternalities` with code and storage.
-	pub fn new_with_code(code: &[u8], mut storage: Storage) -> Self {
+	pub fn new_with_code(code: &[u8], storage: Storage) -> Self {
+		Self::new_with_code_and_state(code, storage, Default::default())
+	}
+
+	/// Create a new instance of `TestExternalities` with code, storage and a given state version.
+	pub fn new_with_code_and_state(
+		code: &[u8],
+		mut storage: Storage,
+		state_version: StateVersion,
+	) -> Self {
 		let overlay = OverlayedChanges::default();
 
 		assert!(storage.top.keys().all(|key| !is_child_storage_key(key)));
@@ -97,8 +113,9 @@ where
 			overlay,
 			offchain_db,
 			extensions,
-			backend: storage.into(),
+			backend: (storage, state_version).into(),
 			storage_transaction_cache: Default::default(),
+			state_version,
 		}
 	}
 
@@ -119,7 +136,7 @@ where
 
 	/// Insert key/value into backend
 	pub fn insert(&mut self, k: StorageKey, v: StorageValue) {
-		self.backend.insert(vec![(None, vec![(k, Some(v))])]);
+		self.backend.insert(vec![(None, vec![(k, Some(v))])], self.state_version);
 	}
 
 	/// Registers the given extension for this instance.
@@ -143,7 +160,7 @@ where
 			))
 		}
 
-		self.backend.update(transaction)
+		self.backend.update(transaction, self.state_version)
 	}
 
 	/// Commit all pending changes to the underlying backend.
@@ -156,6 +173,7 @@ where
 			&self.backend,
 			Default::default(),
 			&mut Default::default(),
+			self.state_version,
 		)?;
 
 		self.backend
@@ -288,7 +306,7 @@ mod tests {
 		ext.set_storage(b"dogglesworth".to_vec(), b"cat".to_vec());
 		let root =
 			H256::from(hex!("ed4d8c799d996add422395a6abd7545491d40bd838d738afafa1b8a4de625489"));
-		assert_eq!(H256::from_slice(ext.storage_root().as_slice()), root);
+		assert_eq!(H256::from_slice(ext.storage_root(Default::default()).as_slice()), root);
 	}
 
 	#[test]
@@ -395,6 +395,62 @@ impl ChildTrieParentKeyId {
 	}
 }
 
+/// Version of the state trie layout.
+///
+/// Both versions use the same trie node format for reading, but `V1` stores any value of at
+/// least [`TRIE_VALUE_NODE_THRESHOLD`] bytes in a separate node, referenced from the trie by its
+/// hash. This keeps large values out of proofs that do not access them.
+#[derive(RuntimeDebug, Clone, Copy, Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize, Hash, PartialOrd, Ord))]
+pub enum StateVersion {
+	/// All values are inlined in their trie node.
+	V0 = 0,
+	/// Values of at least [`TRIE_VALUE_NODE_THRESHOLD`] bytes are stored in their own node.
+	V1 = 1,
+}
+
+impl Default for StateVersion {
+	fn default() -> Self {
+		StateVersion::V0
+	}
+}
+
+impl From<StateVersion> for u8 {
+	fn from(version: StateVersion) -> u8 {
+		version as u8
+	}
+}
+
+impl sp_std::convert::TryFrom<u8> for StateVersion {
+	type Error = ();
+	fn try_from(val: u8) -> sp_std::result::Result<StateVersion, ()> {
+		match val {
+			0 => Ok(StateVersion::V0),
+			1 => Ok(StateVersion::V1),
+			_ => Err(()),
+		}
+	}
+}
+
+impl StateVersion {
+	/// Returns the size threshold from which values are stored in a separate trie node.
+	///
+	/// `None` means that every value is inlined in its leaf or branch node.
+	pub fn state_value_threshold(&self) -> Option<u32> {
+		match self {
+			StateVersion::V0 => None,
+			StateVersion::V1 => Some(TRIE_VALUE_NODE_THRESHOLD),
+		}
+	}
+}
+
+/// Threshold size in bytes from which a value is stored in its own trie node when using
+/// [`StateVersion::V1`].
+///
+/// Values smaller than a hash plus its compact length prefix are always inlined, as hashing
+/// them would not reduce the size of the node.
+pub const TRIE_VALUE_NODE_THRESHOLD: u32 = 33;
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -143,7 +143,7 @@ pub mod registration {
 	use sp_trie::TrieMut;
 
 	type Hasher = sp_core::Blake2Hasher;
-	type TrieLayout = sp_trie::Layout<Hasher>;
+	type TrieLayout = sp_trie::LayoutV0<Hasher>;
 
 	/// Create a new inherent data provider instance for a given parent block hash.
 	pub fn new_data_provider<B, C>(
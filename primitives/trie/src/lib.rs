@@ -37,10 +37,15 @@ pub use memory_db::KeyFunction;
 /// The Substrate format implementation of `NodeCodec`.
 pub use node_codec::NodeCodec;
 use sp_std::{borrow::Borrow, boxed::Box, marker::PhantomData, vec::Vec};
-pub use storage_proof::{CompactProof, StorageProof};
+pub use storage_proof::{CompactMultiProof, CompactProof, StorageProof};
+#[cfg(feature = "std")]
+pub use trie_codec::encode_compact_multi;
 /// Trie codec reexport, mainly child trie support
 /// for trie compact proof.
-pub use trie_codec::{decode_compact, encode_compact, Error as CompactProofError};
+pub use trie_codec::{
+	decode_compact, decode_compact_multi, decode_compact_with_key_spaces, encode_compact,
+	Error as CompactProofError,
+};
 pub use trie_db::proof::VerifyError;
 use trie_db::proof::{generate_proof, verify_proof};
 /// Various re-exports from the `trie-db` crate.
@@ -52,24 +57,40 @@ pub use trie_db::{
 pub use trie_stream::TrieStream;
 
 #[derive(Default)]
-/// substrate trie layout
-pub struct Layout<H>(sp_std::marker::PhantomData<H>);
+/// substrate trie layout, every value is inlined in its trie node.
+pub struct LayoutV0<H>(sp_std::marker::PhantomData<H>);
+
+#[derive(Default)]
+/// substrate trie layout, values of at least `TRIE_VALUE_NODE_THRESHOLD` bytes are stored
+/// in a separate node and referenced by their hash.
+///
+/// Both layouts share the same node codec, so a trie built with either of them can be read
+/// using any layout; they only differ when writing values or computing roots.
+pub struct LayoutV1<H>(sp_std::marker::PhantomData<H>);
 
-impl<H: Hasher> TrieLayout for Layout<H> {
+impl<H> TrieLayout for LayoutV0<H>
+where
+	H: Hasher,
+{
 	const USE_EXTENSION: bool = false;
 	const ALLOW_EMPTY: bool = true;
+	const MAX_INLINE_VALUE: Option<u32> = None;
+
 	type Hash = H;
 	type Codec = NodeCodec<Self::Hash>;
 }
 
-impl<H: Hasher> TrieConfiguration for Layout<H> {
+impl<H> TrieConfiguration for LayoutV0<H>
+where
+	H: Hasher,
+{
 	fn trie_root<I, A, B>(input: I) -> <Self::Hash as Hasher>::Out
 	where
 		I: IntoIterator<Item = (A, B)>,
 		A: AsRef<[u8]> + Ord,
 		B: AsRef<[u8]>,
 	{
-		trie_root::trie_root_no_extension::<H, TrieStream, _, _, _>(input)
+		trie_root::trie_root_no_extension::<H, TrieStream, _, _, _>(input, Self::MAX_INLINE_VALUE)
 	}
 
 	fn trie_root_unhashed<I, A, B>(input: I) -> Vec<u8>
@@ -78,7 +99,52 @@ impl<H: Hasher> TrieConfiguration for Layout<H> {
 		A: AsRef<[u8]> + Ord,
 		B: AsRef<[u8]>,
 	{
-		trie_root::unhashed_trie_no_extension::<H, TrieStream, _, _, _>(input)
+		trie_root::unhashed_trie_no_extension::<H, TrieStream, _, _, _>(
+			input,
+			Self::MAX_INLINE_VALUE,
+		)
+	}
+
+	fn encode_index(input: u32) -> Vec<u8> {
+		codec::Encode::encode(&codec::Compact(input))
+	}
+}
+
+impl<H> TrieLayout for LayoutV1<H>
+where
+	H: Hasher,
+{
+	const USE_EXTENSION: bool = false;
+	const ALLOW_EMPTY: bool = true;
+	const MAX_INLINE_VALUE: Option<u32> = Some(sp_core::storage::TRIE_VALUE_NODE_THRESHOLD);
+
+	type Hash = H;
+	type Codec = NodeCodec<Self::Hash>;
+}
+
+impl<H> TrieConfiguration for LayoutV1<H>
+where
+	H: Hasher,
+{
+	fn trie_root<I, A, B>(input: I) -> <Self::Hash as Hasher>::Out
+	where
+		I: IntoIterator<Item = (A, B)>,
+		A: AsRef<[u8]> + Ord,
+		B: AsRef<[u8]>,
+	{
+		trie_root::trie_root_no_extension::<H, TrieStream, _, _, _>(input, Self::MAX_INLINE_VALUE)
+	}
+
+	fn trie_root_unhashed<I, A, B>(input: I) -> Vec<u8>
+	where
+		I: IntoIterator<Item = (A, B)>,
+		A: AsRef<[u8]> + Ord,
+		B: AsRef<[u8]>,
+	{
+		trie_root::unhashed_trie_no_extension::<H, TrieStream, _, _, _>(
+			input,
+			Self::MAX_INLINE_VALUE,
+		)
 	}
 
 	fn encode_index(input: u32) -> Vec<u8> {
@@ -122,11 +188,18 @@ pub type TrieHash<L> = <<L as TrieLayout>::Hash as Hasher>::Out;
 /// This module is for non generic definition of trie type.
 /// Only the `Hasher` trait is generic in this case.
 pub mod trie_types {
-	pub type Layout<H> = super::Layout<H>;
+	/// Default layout, without value nodes.
+	pub type Layout<H> = super::LayoutV0<H>;
 	/// Persistent trie database read-access interface for the a given hasher.
 	pub type TrieDB<'a, H> = super::TrieDB<'a, Layout<H>>;
 	/// Persistent trie database write-access interface for the a given hasher.
 	pub type TrieDBMut<'a, H> = super::TrieDBMut<'a, Layout<H>>;
+	/// Persistent trie database write-access interface for the a given hasher, using
+	/// the state version 0 layout.
+	pub type TrieDBMutV0<'a, H> = super::TrieDBMut<'a, super::LayoutV0<H>>;
+	/// Persistent trie database write-access interface for the a given hasher, using
+	/// the state version 1 layout.
+	pub type TrieDBMutV1<'a, H> = super::TrieDBMut<'a, super::LayoutV1<H>>;
 	/// Querying interface, as in `trie_db` but less generic.
 	pub type Lookup<'a, H, Q> = trie_db::Lookup<'a, Layout<H>, Q>;
 	/// As in `trie_db`, but less generic, error type for the crate.
@@ -167,13 +240,13 @@ pub fn verify_trie_proof<'a, L: TrieConfiguration, I, K, V>(
 	root: &TrieHash<L>,
 	proof: &[Vec<u8>],
 	items: I,
-) -> Result<(), VerifyError<TrieHash<L>, error::Error>>
+) -> Result<(), VerifyError<TrieHash<L>, CError<L>>>
 where
 	I: IntoIterator<Item = &'a (K, Option<V>)>,
 	K: 'a + AsRef<[u8]>,
 	V: 'a + AsRef<[u8]>,
 {
-	verify_proof::<Layout<L::Hash>, _, _, _>(root, proof, items)
+	verify_proof::<L, _, _, _>(root, proof, items)
 }
 
 /// Determine a trie root given a hash DB and delta values.
@@ -444,11 +517,15 @@ where
 
 /// Constants used into trie simplification codec.
 mod trie_constants {
-	pub const EMPTY_TRIE: u8 = 0;
+	const FIRST_PREFIX: u8 = 0b_00 << 6;
 	pub const NIBBLE_SIZE_BOUND: usize = u16::MAX as usize;
 	pub const LEAF_PREFIX_MASK: u8 = 0b_01 << 6;
 	pub const BRANCH_WITHOUT_MASK: u8 = 0b_10 << 6;
 	pub const BRANCH_WITH_MASK: u8 = 0b_11 << 6;
+	pub const EMPTY_TRIE: u8 = FIRST_PREFIX | (0b_00 << 4);
+	pub const ALT_HASHING_LEAF_PREFIX_MASK: u8 = FIRST_PREFIX | (0b_1 << 5);
+	pub const ALT_HASHING_BRANCH_WITH_MASK: u8 = FIRST_PREFIX | (0b_01 << 4);
+	pub const ESCAPE_COMPACT_HEADER: u8 = EMPTY_TRIE | 0b_00_01;
 }
 
 #[cfg(test)]
@@ -457,11 +534,15 @@ mod tests {
 	use codec::{Compact, Decode, Encode};
 	use hash_db::{HashDB, Hasher};
 	use hex_literal::hex;
-	use sp_core::Blake2Hasher;
+	use sp_core::{
+		storage::{StateVersion, TRIE_VALUE_NODE_THRESHOLD},
+		Blake2Hasher,
+	};
 	use trie_db::{DBValue, NodeCodec as NodeCodecT, Trie, TrieMut};
 	use trie_standardmap::{Alphabet, StandardMap, ValueMode};
 
-	type Layout = super::Layout<Blake2Hasher>;
+	type LayoutV0 = super::LayoutV0<Blake2Hasher>;
+	type LayoutV1 = super::LayoutV1<Blake2Hasher>;
 
 	fn hashed_null_node<T: TrieConfiguration>() -> TrieHash<T> {
 		<T::Codec as NodeCodecT>::hashed_null_node()
@@ -506,14 +587,21 @@ mod tests {
 		}
 	}
 
+	fn check_input(input: &Vec<(&[u8], &[u8])>) {
+		check_equivalent::<LayoutV0>(input);
+		check_iteration::<LayoutV0>(input);
+		check_equivalent::<LayoutV1>(input);
+		check_iteration::<LayoutV1>(input);
+	}
+
 	#[test]
 	fn default_trie_root() {
 		let mut db = MemoryDB::default();
-		let mut root = TrieHash::<Layout>::default();
-		let mut empty = TrieDBMut::<Layout>::new(&mut db, &mut root);
+		let mut root = TrieHash::<LayoutV1>::default();
+		let mut empty = TrieDBMut::<LayoutV1>::new(&mut db, &mut root);
 		empty.commit();
 		let root1 = empty.root().as_ref().to_vec();
-		let root2: Vec<u8> = Layout::trie_root::<_, Vec<u8>, Vec<u8>>(std::iter::empty())
+		let root2: Vec<u8> = LayoutV1::trie_root::<_, Vec<u8>, Vec<u8>>(std::iter::empty())
 			.as_ref()
 			.iter()
 			.cloned()
@@ -525,31 +613,27 @@ mod tests {
 	#[test]
 	fn empty_is_equivalent() {
 		let input: Vec<(&[u8], &[u8])> = vec![];
-		check_equivalent::<Layout>(&input);
-		check_iteration::<Layout>(&input);
+		check_input(&input);
 	}
 
 	#[test]
 	fn leaf_is_equivalent() {
 		let input: Vec<(&[u8], &[u8])> = vec![(&[0xaa][..], &[0xbb][..])];
-		check_equivalent::<Layout>(&input);
-		check_iteration::<Layout>(&input);
+		check_input(&input);
 	}
 
 	#[test]
 	fn branch_is_equivalent() {
 		let input: Vec<(&[u8], &[u8])> =
 			vec![(&[0xaa][..], &[0x10][..]), (&[0xba][..], &[0x11][..])];
-		check_equivalent::<Layout>(&input);
-		check_iteration::<Layout>(&input);
+		check_input(&input);
 	}
 
 	#[test]
 	fn extension_and_branch_is_equivalent() {
 		let input: Vec<(&[u8], &[u8])> =
 			vec![(&[0xaa][..], &[0x10][..]), (&[0xab][..], &[0x11][..])];
-		check_equivalent::<Layout>(&input);
-		check_iteration::<Layout>(&input);
+		check_input(&input);
 	}
 
 	#[test]
@@ -564,8 +648,7 @@ mod tests {
 		let mut d = st.make();
 		d.sort_by(|&(ref a, _), &(ref b, _)| a.cmp(b));
 		let dr = d.iter().map(|v| (&v.0[..], &v.1[..])).collect();
-		check_equivalent::<Layout>(&dr);
-		check_iteration::<Layout>(&dr);
+		check_input(&dr);
 	}
 
 	#[test]
@@ -575,8 +658,7 @@ mod tests {
 			(&[0xaa, 0xaa][..], &[0xaa][..]),
 			(&[0xaa, 0xbb][..], &[0xab][..]),
 		];
-		check_equivalent::<Layout>(&input);
-		check_iteration::<Layout>(&input);
+		check_input(&input);
 	}
 
 	#[test]
@@ -589,8 +671,7 @@ mod tests {
 			(&[0xbb, 0xbb][..], &[0xbb][..]),
 			(&[0xbb, 0xcc][..], &[0xbc][..]),
 		];
-		check_equivalent::<Layout>(&input);
-		check_iteration::<Layout>(&input);
+		check_input(&input);
 	}
 
 	#[test]
@@ -602,8 +683,7 @@ mod tests {
 			),
 			(&[0xba][..], &[0x11][..]),
 		];
-		check_equivalent::<Layout>(&input);
-		check_iteration::<Layout>(&input);
+		check_input(&input);
 	}
 
 	#[test]
@@ -618,8 +698,7 @@ mod tests {
 				&b"ABCABCABCABCABCABCABCABCABCABCABCABCABCABCABCABCABCABCABCABCABCABCABCABC"[..],
 			),
 		];
-		check_equivalent::<Layout>(&input);
-		check_iteration::<Layout>(&input);
+		check_input(&input);
 	}
 
 	fn populate_trie<'db, T: TrieConfiguration>(
@@ -648,6 +727,11 @@ mod tests {
 
 	#[test]
 	fn random_should_work() {
+		random_should_work_inner::<LayoutV1>();
+		random_should_work_inner::<LayoutV0>();
+	}
+
+	fn random_should_work_inner<L: TrieConfiguration>() {
 		let mut seed = <Blake2Hasher as Hasher>::Out::zero();
 		for test_i in 0..10000 {
 			if test_i % 50 == 0 {
@@ -662,10 +746,10 @@ mod tests {
 			}
 			.make_with(seed.as_fixed_bytes_mut());
 
-			let real = Layout::trie_root(x.clone());
+			let real = L::trie_root(x.clone());
 			let mut memdb = MemoryDB::default();
 			let mut root = Default::default();
-			let mut memtrie = populate_trie::<Layout>(&mut memdb, &mut root, &x);
+			let mut memtrie = populate_trie::<L>(&mut memdb, &mut root, &x);
 
 			memtrie.commit();
 			if *memtrie.root() != real {
@@ -677,9 +761,9 @@ mod tests {
 				}
 			}
 			assert_eq!(*memtrie.root(), real);
-			unpopulate_trie::<Layout>(&mut memtrie, &x);
+			unpopulate_trie::<L>(&mut memtrie, &x);
 			memtrie.commit();
-			let hashed_null_node = hashed_null_node::<Layout>();
+			let hashed_null_node = hashed_null_node::<L>();
 			if *memtrie.root() != hashed_null_node {
 				println!("- TRIE MISMATCH");
 				println!("");
@@ -699,7 +783,7 @@ mod tests {
 	#[test]
 	fn codec_trie_empty() {
 		let input: Vec<(&[u8], &[u8])> = vec![];
-		let trie = Layout::trie_root_unhashed::<_, _, _>(input);
+		let trie = LayoutV0::trie_root_unhashed::<_, _, _>(input);
 		println!("trie: {:#x?}", trie);
 		assert_eq!(trie, vec![0x0]);
 	}
@@ -707,7 +791,7 @@ mod tests {
 	#[test]
 	fn codec_trie_single_tuple() {
 		let input = vec![(vec![0xaa], vec![0xbb])];
-		let trie = Layout::trie_root_unhashed::<_, _, _>(input);
+		let trie = LayoutV0::trie_root_unhashed::<_, _, _>(input);
 		println!("trie: {:#x?}", trie);
 		assert_eq!(
 			trie,
@@ -723,7 +807,7 @@ mod tests {
 	#[test]
 	fn codec_trie_two_tuples_disjoint_keys() {
 		let input = vec![(&[0x48, 0x19], &[0xfe]), (&[0x13, 0x14], &[0xff])];
-		let trie = Layout::trie_root_unhashed::<_, _, _>(input);
+		let trie = LayoutV0::trie_root_unhashed::<_, _, _>(input);
 		println!("trie: {:#x?}", trie);
 		let mut ex = Vec::<u8>::new();
 		ex.push(0x80); // branch, no value (0b_10..) no nibble
@@ -745,8 +829,125 @@ mod tests {
 		assert_eq!(trie, ex);
 	}
 
+	#[test]
+	fn codec_trie_single_tuple_hashed_value() {
+		let value = vec![0xbb; TRIE_VALUE_NODE_THRESHOLD as usize];
+		let input = vec![(vec![0xaa], value.clone())];
+		let trie = LayoutV1::trie_root_unhashed::<_, _, _>(input.clone());
+		let mut ex = vec![
+			0x22, // hashed value leaf 0x20 (2^5) with (+) key of 2 nibbles (0x02)
+			0xaa, // key data
+		];
+		ex.extend_from_slice(Blake2Hasher::hash(&value).as_ref()); // hash of value data
+		assert_eq!(trie, ex);
+
+		// Values below the threshold stay inline, and `LayoutV0` never hashes them.
+		assert_eq!(LayoutV0::trie_root_unhashed::<_, _, _>(input)[..3], [0x42, 0xaa, 0x84]);
+		let small = vec![(vec![0xaa], vec![0xbb; TRIE_VALUE_NODE_THRESHOLD as usize - 1])];
+		assert_eq!(
+			LayoutV1::trie_root_unhashed::<_, _, _>(small.clone()),
+			LayoutV0::trie_root_unhashed::<_, _, _>(small),
+		);
+	}
+
+	#[test]
+	fn node_header_size_encoding_roundtrips() {
+		use crate::node_header::NodeHeader;
+
+		for nibble_count in [0, 1, 14, 15, 16, 30, 31, 32, 62, 63, 64, 300, 70_000] {
+			for header in [
+				NodeHeader::Leaf(nibble_count),
+				NodeHeader::Branch(true, nibble_count),
+				NodeHeader::Branch(false, nibble_count),
+				NodeHeader::HashedValueLeaf(nibble_count),
+				NodeHeader::HashedValueBranch(nibble_count),
+			] {
+				let expected = match header {
+					NodeHeader::Leaf(_) =>
+						NodeHeader::Leaf(nibble_count.min(trie_constants::NIBBLE_SIZE_BOUND)),
+					NodeHeader::Branch(v, _) =>
+						NodeHeader::Branch(v, nibble_count.min(trie_constants::NIBBLE_SIZE_BOUND)),
+					NodeHeader::HashedValueLeaf(_) => NodeHeader::HashedValueLeaf(
+						nibble_count.min(trie_constants::NIBBLE_SIZE_BOUND),
+					),
+					NodeHeader::HashedValueBranch(_) => NodeHeader::HashedValueBranch(
+						nibble_count.min(trie_constants::NIBBLE_SIZE_BOUND),
+					),
+					NodeHeader::Null => unreachable!(),
+				};
+				assert_eq!(NodeHeader::decode(&mut &header.encode()[..]).unwrap(), expected);
+			}
+		}
+		assert!(NodeHeader::decode(&mut &[trie_constants::ESCAPE_COMPACT_HEADER][..]).is_err());
+	}
+
+	#[test]
+	fn layout_v1_stores_big_values_in_separate_nodes() {
+		let big_value = vec![0xcd; 1024];
+		let pairs = vec![
+			(hex!("0102").to_vec(), hex!("01").to_vec()),
+			(hex!("02").to_vec(), big_value.clone()),
+			(hex!("0203").to_vec(), hex!("01").to_vec()),
+			(hex!("0204").to_vec(), hex!("0405").to_vec()),
+		];
+
+		let mut memdb_v0 = MemoryDB::default();
+		let mut root_v0 = Default::default();
+		populate_trie::<LayoutV0>(&mut memdb_v0, &mut root_v0, &pairs);
+		let mut memdb_v1 = MemoryDB::default();
+		let mut root_v1 = Default::default();
+		populate_trie::<LayoutV1>(&mut memdb_v1, &mut root_v1, &pairs);
+
+		assert_ne!(root_v0, root_v1);
+		assert_eq!(root_v1, LayoutV1::trie_root(pairs.clone()));
+		let value_hash = Blake2Hasher::hash(&big_value);
+		assert!(!memdb_v0.keys().keys().any(|k| k.as_ref().ends_with(value_hash.as_ref())));
+		assert!(memdb_v1.keys().keys().any(|k| k.as_ref().ends_with(value_hash.as_ref())));
+
+		// Both layouts can read a trie written with value nodes.
+		for (key, value) in pairs.iter() {
+			assert_eq!(
+				read_trie_value::<LayoutV0, _>(&memdb_v1, &root_v1, key).unwrap().as_ref(),
+				Some(value),
+			);
+			assert_eq!(
+				read_trie_value::<LayoutV1, _>(&memdb_v1, &root_v1, key).unwrap().as_ref(),
+				Some(value),
+			);
+		}
+
+		// A proof through the branch holding the big value does not contain the value anymore.
+		let key = [pairs[3].0.clone()];
+		let proof_v0 = generate_trie_proof::<LayoutV0, _, _, _>(&memdb_v0, root_v0, &key).unwrap();
+		let proof_v1 = generate_trie_proof::<LayoutV1, _, _, _>(&memdb_v1, root_v1, &key).unwrap();
+		let size = |proof: &Vec<Vec<u8>>| proof.iter().map(|n| n.len()).sum::<usize>();
+		assert!(size(&proof_v0) > big_value.len());
+		assert!(size(&proof_v1) < big_value.len());
+		assert!(verify_trie_proof::<LayoutV1, _, _, _>(
+			&root_v1,
+			&proof_v1,
+			&[(pairs[3].0.clone(), Some(pairs[3].1.clone()))],
+		)
+		.is_ok());
+
+		// A proof for the big value itself includes the value node.
+		let key = [pairs[1].0.clone()];
+		let proof_v1 = generate_trie_proof::<LayoutV1, _, _, _>(&memdb_v1, root_v1, &key).unwrap();
+		assert!(verify_trie_proof::<LayoutV1, _, _, _>(
+			&root_v1,
+			&proof_v1,
+			&[(pairs[1].0.clone(), Some(big_value))],
+		)
+		.is_ok());
+	}
+
 	#[test]
 	fn iterator_works() {
+		iterator_works_inner::<LayoutV0>();
+		iterator_works_inner::<LayoutV1>();
+	}
+
+	fn iterator_works_inner<Layout: TrieConfiguration>() {
 		let pairs = vec![
 			(hex!("0103000000000000000464").to_vec(), hex!("0400000000").to_vec()),
 			(hex!("0103000000000000000469").to_vec(), hex!("0401000000").to_vec()),
@@ -770,6 +971,11 @@ mod tests {
 
 	#[test]
 	fn proof_non_inclusion_works() {
+		proof_non_inclusion_works_inner::<LayoutV0>();
+		proof_non_inclusion_works_inner::<LayoutV1>();
+	}
+
+	fn proof_non_inclusion_works_inner<Layout: TrieConfiguration>() {
 		let pairs = vec![
 			(hex!("0102").to_vec(), hex!("01").to_vec()),
 			(hex!("0203").to_vec(), hex!("0405").to_vec()),
@@ -803,6 +1009,11 @@ mod tests {
 
 	#[test]
 	fn proof_inclusion_works() {
+		proof_inclusion_works_inner::<LayoutV0>();
+		proof_inclusion_works_inner::<LayoutV1>();
+	}
+
+	fn proof_inclusion_works_inner<Layout: TrieConfiguration>() {
 		let pairs = vec![
 			(hex!("0102").to_vec(), hex!("01").to_vec()),
 			(hex!("0203").to_vec(), hex!("0405").to_vec()),
@@ -865,13 +1076,13 @@ mod tests {
 		.unwrap();
 
 		let proof_db = proof.into_memory_db::<Blake2Hasher>();
-		let first_storage_root = delta_trie_root::<Layout, _, _, _, _, _>(
+		let first_storage_root = delta_trie_root::<LayoutV0, _, _, _, _, _>(
 			&mut proof_db.clone(),
 			storage_root,
 			valid_delta,
 		)
 		.unwrap();
-		let second_storage_root = delta_trie_root::<Layout, _, _, _, _, _>(
+		let second_storage_root = delta_trie_root::<LayoutV0, _, _, _, _, _>(
 			&mut proof_db.clone(),
 			storage_root,
 			invalid_delta,
@@ -880,4 +1091,74 @@ mod tests {
 
 		assert_eq!(first_storage_root, second_storage_root);
 	}
+
+	#[test]
+	fn compact_multi_proof_works() {
+		compact_multi_proof_works_inner::<LayoutV0>(StateVersion::V0);
+		compact_multi_proof_works_inner::<LayoutV1>(StateVersion::V1);
+	}
+
+	fn compact_multi_proof_works_inner<Layout: TrieConfiguration<Hash = Blake2Hasher>>(
+		state_version: StateVersion,
+	) {
+		let key = |i: u8| vec![i, i / 16];
+		let mut pairs = (0..=255u8).map(|i| (key(i), vec![i; 40])).collect::<Vec<_>>();
+		let mut memdb = MemoryDB::default();
+		let mut root1 = Default::default();
+		populate_trie::<Layout>(&mut memdb, &mut root1, &pairs);
+		pairs[0].1 = vec![1; 40];
+		let mut root2 = Default::default();
+		populate_trie::<Layout>(&mut memdb, &mut root2, &pairs);
+
+		let read_proof = |root: &TrieHash<Layout>, keys: &[Vec<u8>]| {
+			let mut recorder = Recorder::new();
+			for key in keys {
+				read_trie_value_with::<Layout, _, _>(&memdb, root, key, &mut recorder).unwrap();
+			}
+			StorageProof::new(recorder.drain().into_iter().map(|record| record.data).collect())
+		};
+		// The last read at the first root goes through a node of the second proof, and through
+		// a node that is only referenced by the already included root.
+		let reads = vec![
+			(root1, vec![key(0)]),
+			(root2, vec![key(0), key(128)]),
+			(root1, vec![key(129), key(255)]),
+		];
+		let proofs = reads.iter().map(|(root, keys)| read_proof(root, keys)).collect::<Vec<_>>();
+
+		let separate_size = reads
+			.iter()
+			.zip(proofs.clone())
+			.map(|((root, _), proof)| {
+				encode_compact::<Layout>(proof, *root).unwrap().encoded_size()
+			})
+			.sum::<usize>();
+		let proof = encode_compact_multi::<Blake2Hasher, _>(
+			proofs.into_iter().map(|proof| (proof, state_version)),
+		)
+		.unwrap();
+		assert!(proof.encoded_size() < separate_size);
+
+		let versions = vec![state_version; reads.len()];
+		assert!(decode_compact_multi::<Blake2Hasher, _>(
+			&mut MemoryDB::default(),
+			&proof,
+			&versions[1..]
+		)
+		.is_err());
+		let mut db = MemoryDB::<Layout::Hash>::default();
+		decode_compact_multi::<Blake2Hasher, _>(&mut db, &proof, &versions).unwrap();
+		for (root, keys) in reads {
+			for key in keys {
+				let expected = read_trie_value::<Layout, _>(&memdb, &root, &key).unwrap();
+				assert_eq!(read_trie_value::<Layout, _>(&db, &root, &key).unwrap(), expected);
+			}
+		}
+		// The nodes shared by both roots can be read at either of them.
+		assert_eq!(
+			read_trie_value::<Layout, _>(&db, &root2, &key(255)).unwrap(),
+			Some(vec![255; 40]),
+		);
+		assert!(read_trie_value::<Layout, _>(&db, &root1, &key(64)).is_err());
+	}
 }
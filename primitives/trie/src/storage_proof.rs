@@ -18,6 +18,7 @@
 use codec::{Decode, Encode};
 use hash_db::{HashDB, Hasher};
 use scale_info::TypeInfo;
+use sp_core::storage::StateVersion;
 use sp_std::vec::Vec;
 
 /// A proof that some set of key-value pairs are included in the storage trie. The proof contains
@@ -38,6 +39,20 @@ pub struct CompactProof {
 	pub encoded_nodes: Vec<Vec<u8>>,
 }
 
+/// Storage proof of reads at several state roots in compact form.
+///
+/// Trie nodes shared by the state roots are only included once, see
+/// [`encode_compact_multi`](crate::encode_compact_multi).
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode, TypeInfo)]
+pub struct CompactMultiProof {
+	/// Compact encoded subtries, one after the other.
+	pub encoded_nodes: Vec<Vec<u8>>,
+	/// Hashed values that are not part of a subtrie.
+	pub values: Vec<Vec<u8>>,
+	/// Number of subtries encoded for each of the proofs, in order.
+	pub subtries: Vec<u32>,
+}
+
 impl StorageProof {
 	/// Constructs a storage proof from a subset of encoded trie nodes in a storage backend.
 	pub fn new(trie_nodes: Vec<Vec<u8>>) -> Self {
@@ -94,8 +109,8 @@ impl StorageProof {
 	pub fn into_compact_proof<H: Hasher>(
 		self,
 		root: H::Out,
-	) -> Result<CompactProof, crate::CompactProofError<crate::Layout<H>>> {
-		crate::encode_compact::<crate::Layout<H>>(self, root)
+	) -> Result<CompactProof, crate::CompactProofError<crate::LayoutV0<H>>> {
+		crate::encode_compact::<crate::LayoutV0<H>>(self, root)
 	}
 
 	/// Returns the estimated encoded size of the compact proof.
@@ -122,9 +137,9 @@ impl CompactProof {
 	pub fn to_storage_proof<H: Hasher>(
 		&self,
 		expected_root: Option<&H::Out>,
-	) -> Result<(StorageProof, H::Out), crate::CompactProofError<crate::Layout<H>>> {
+	) -> Result<(StorageProof, H::Out), crate::CompactProofError<crate::LayoutV0<H>>> {
 		let mut db = crate::MemoryDB::<H>::new(&[]);
-		let root = crate::decode_compact::<crate::Layout<H>, _, _>(
+		let root = crate::decode_compact::<crate::LayoutV0<H>, _, _>(
 			&mut db,
 			self.iter_compact_encoded_nodes(),
 			expected_root,
@@ -141,6 +156,24 @@ impl CompactProof {
 	}
 }
 
+impl CompactMultiProof {
+	/// Return an iterator on the compact encoded nodes.
+	pub fn iter_compact_encoded_nodes(&self) -> impl Iterator<Item = &[u8]> {
+		self.encoded_nodes.iter().map(Vec::as_slice)
+	}
+
+	/// Decode to a `MemoryDB` containing the nodes of all the state roots, given the state
+	/// versions of the roots in the order of the proofs.
+	pub fn to_memory_db<H: Hasher>(
+		&self,
+		state_versions: &[StateVersion],
+	) -> Result<crate::MemoryDB<H>, crate::CompactProofError<crate::LayoutV0<H>>> {
+		let mut db = crate::MemoryDB::<H>::new(&[]);
+		crate::decode_compact_multi::<H, _>(&mut db, self, state_versions)?;
+		Ok(db)
+	}
+}
+
 /// An iterator over trie nodes constructed from a storage proof. The nodes are not guaranteed to
 /// be traversed in any particular order.
 pub struct StorageProofNodeIterator {
@@ -21,7 +21,12 @@
 //! it to substrate specific layout and child trie system.
 
 use crate::{
-	CompactProof, HashDBT, StorageProof, TrieConfiguration, TrieError, TrieHash, EMPTY_PREFIX,
+	CompactMultiProof, CompactProof, HashDBT, KeySpacedDBMut, LayoutV0, LayoutV1, StorageProof,
+	TrieConfiguration, TrieError, TrieHash, EMPTY_PREFIX,
+};
+use hash_db::Hasher;
+use sp_core::storage::{
+	well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX, ChildInfo, StateVersion,
 };
 use sp_std::{boxed::Box, vec::Vec};
 #[cfg(feature = "std")]
@@ -44,6 +49,8 @@ pub enum Error<L: TrieConfiguration> {
 	InvalidChildRoot(Vec<u8>, Vec<u8>),
 	/// Errors from trie crate.
 	TrieError(Box<TrieError<L>>),
+	/// The number of state versions does not match the number of proofs.
+	StateVersionsMismatch,
 }
 
 impl<L: TrieConfiguration> From<Box<TrieError<L>>> for Error<L> {
@@ -62,6 +69,7 @@ impl<L: TrieConfiguration> StdError for Error<L> {
 			Error::IncompleteProof => "Incomplete proof",
 			Error::ExtraneousChildNode => "Extraneous child node",
 			Error::ExtraneousChildProof(..) => "Extraneous child proof",
+			Error::StateVersionsMismatch => "State versions mismatch",
 		}
 	}
 }
@@ -90,6 +98,8 @@ impl<L: TrieConfiguration> fmt::Display for Error<L> {
 				root.as_ref(),
 				expected.as_ref(),
 			),
+			Error::StateVersionsMismatch =>
+				write!(f, "The number of state versions does not match the number of proofs"),
 		}
 	}
 }
@@ -112,8 +122,7 @@ where
 	I: IntoIterator<Item = &'a [u8]>,
 {
 	let mut nodes_iter = encoded.into_iter();
-	let (top_root, _nb_used) =
-		trie_db::decode_compact_from_iter::<L, _, _, _>(db, &mut nodes_iter)?;
+	let (top_root, _nb_used) = trie_db::decode_compact_from_iter::<L, _, _>(db, &mut nodes_iter)?;
 
 	// Only check root if expected root is passed as argument.
 	if let Some(expected_root) = expected_root {
@@ -122,39 +131,7 @@ where
 		}
 	}
 
-	let mut child_tries = Vec::new();
-	{
-		// fetch child trie roots
-		let trie = crate::TrieDB::<L>::new(db, &top_root)?;
-
-		let mut iter = trie.iter()?;
-
-		let childtrie_roots = sp_core::storage::well_known_keys::DEFAULT_CHILD_STORAGE_KEY_PREFIX;
-		if iter.seek(childtrie_roots).is_ok() {
-			loop {
-				match iter.next() {
-					Some(Ok((key, value))) if key.starts_with(childtrie_roots) => {
-						// we expect all default child trie root to be correctly encoded.
-						// see other child trie functions.
-						let mut root = TrieHash::<L>::default();
-						// still in a proof so prevent panic
-						if root.as_mut().len() != value.as_slice().len() {
-							return Err(Error::InvalidChildRoot(key, value))
-						}
-						root.as_mut().copy_from_slice(value.as_ref());
-						child_tries.push(root);
-					},
-					// allow incomplete database error: we only
-					// require access to data in the proof.
-					Some(Err(error)) => match *error {
-						trie_db::TrieError::IncompleteDatabase(..) => (),
-						e => return Err(Box::new(e).into()),
-					},
-					_ => break,
-				}
-			}
-		}
-	}
+	let child_tries = child_trie_roots::<L, _>(db, &top_root)?;
 
 	if !HashDBT::<L::Hash, _>::contains(db, &top_root, EMPTY_PREFIX) {
 		return Err(Error::IncompleteProof)
@@ -162,10 +139,9 @@ where
 
 	let mut previous_extracted_child_trie = None;
 	let mut nodes_iter = nodes_iter.peekable();
-	for child_root in child_tries.into_iter() {
+	for (_, child_root) in child_tries.into_iter() {
 		if previous_extracted_child_trie.is_none() && nodes_iter.peek().is_some() {
-			let (top_root, _) =
-				trie_db::decode_compact_from_iter::<L, _, _, _>(db, &mut nodes_iter)?;
+			let (top_root, _) = trie_db::decode_compact_from_iter::<L, _, _>(db, &mut nodes_iter)?;
 			previous_extracted_child_trie = Some(top_root);
 		}
 
@@ -190,6 +166,109 @@ where
 	Ok(top_root)
 }
 
+/// Decode a compact proof like [`decode_compact`], inserting the nodes of every child trie in the
+/// key space of the child trie.
+///
+/// The nodes can then be written as they are to a database that stores the child tries in their
+/// own key space.
+pub fn decode_compact_with_key_spaces<'a, L, DB, I>(
+	db: &mut DB,
+	encoded: I,
+	expected_root: Option<&TrieHash<L>>,
+) -> Result<TrieHash<L>, Error<L>>
+where
+	L: TrieConfiguration,
+	DB: HashDBT<L::Hash, trie_db::DBValue> + hash_db::HashDBRef<L::Hash, trie_db::DBValue>,
+	I: IntoIterator<Item = &'a [u8]>,
+{
+	let encoded: Vec<&[u8]> = encoded.into_iter().collect();
+	let mut nodes_iter = encoded.iter().copied();
+	let (top_root, _nb_used) = trie_db::decode_compact_from_iter::<L, _, _>(db, &mut nodes_iter)?;
+
+	if let Some(expected_root) = expected_root {
+		if expected_root != &top_root {
+			return Err(Error::RootMismatch(top_root, *expected_root))
+		}
+	}
+
+	let child_tries = child_trie_roots::<L, _>(db, &top_root)?;
+
+	if !HashDBT::<L::Hash, _>::contains(db, &top_root, EMPTY_PREFIX) {
+		return Err(Error::IncompleteProof)
+	}
+
+	let mut previous_extracted_child_trie = None;
+	for (key, child_root) in child_tries.into_iter() {
+		if previous_extracted_child_trie.is_none() && nodes_iter.clone().next().is_some() {
+			// The key space must be known before decoding, so the root of the next child trie
+			// is found by decoding it a first time.
+			let mut scratch = crate::MemoryDB::<L::Hash>::default();
+			let (root, _) =
+				trie_db::decode_compact_from_iter::<L, _, _>(&mut scratch, nodes_iter.clone())?;
+			previous_extracted_child_trie = Some(root);
+		}
+
+		if Some(child_root) == previous_extracted_child_trie {
+			let storage_key = &key[DEFAULT_CHILD_STORAGE_KEY_PREFIX.len()..];
+			let child_info = ChildInfo::new_default(storage_key);
+			let mut child_db = KeySpacedDBMut::new(db, child_info.keyspace());
+			trie_db::decode_compact_from_iter::<L, _, _>(&mut child_db, &mut nodes_iter)?;
+			previous_extracted_child_trie = None;
+		}
+	}
+
+	if let Some(child_root) = previous_extracted_child_trie {
+		return Err(Error::ExtraneousChildProof(child_root))
+	}
+
+	if nodes_iter.next().is_some() {
+		return Err(Error::ExtraneousChildNode)
+	}
+
+	Ok(top_root)
+}
+
+/// Storage keys and roots of default child tries.
+type ChildTrieRoots<L> = Vec<(Vec<u8>, TrieHash<L>)>;
+
+/// Read the keys and roots of the default child tries from the top trie of a proof.
+fn child_trie_roots<L, DB>(db: &DB, top_root: &TrieHash<L>) -> Result<ChildTrieRoots<L>, Error<L>>
+where
+	L: TrieConfiguration,
+	DB: hash_db::HashDBRef<L::Hash, trie_db::DBValue>,
+{
+	let mut child_tries = Vec::new();
+	let trie = crate::TrieDB::<L>::new(db, top_root)?;
+
+	let mut iter = trie.iter()?;
+
+	if iter.seek(DEFAULT_CHILD_STORAGE_KEY_PREFIX).is_ok() {
+		loop {
+			match iter.next() {
+				Some(Ok((key, value))) if key.starts_with(DEFAULT_CHILD_STORAGE_KEY_PREFIX) => {
+					// we expect all default child trie root to be correctly encoded.
+					// see other child trie functions.
+					let mut root = TrieHash::<L>::default();
+					// still in a proof so prevent panic
+					if root.as_mut().len() != value.as_slice().len() {
+						return Err(Error::InvalidChildRoot(key, value))
+					}
+					root.as_mut().copy_from_slice(value.as_ref());
+					child_tries.push((key, root));
+				},
+				// allow incomplete database error: we only
+				// require access to data in the proof.
+				Some(Err(error)) => match *error {
+					trie_db::TrieError::IncompleteDatabase(..) => (),
+					e => return Err(Box::new(e).into()),
+				},
+				_ => break,
+			}
+		}
+	}
+	Ok(child_tries)
+}
+
 /// Encode a compact proof.
 ///
 /// Takes as input all full encoded node from the proof, and
@@ -250,3 +329,173 @@ where
 
 	Ok(CompactProof { encoded_nodes: compact_proof })
 }
+
+/// Decode a compact proof of reads at several state roots.
+///
+/// `state_versions` are the state versions of the roots, in the order the proofs were given to
+/// [`encode_compact_multi`]. All the subtries and values of the proof are added to `db`, which
+/// can then be used to read the state at any of the roots the proof was generated for. Returns
+/// the roots of the decoded subtries.
+///
+/// Both layouts share their hash and error types.
+pub fn decode_compact_multi<H, DB>(
+	db: &mut DB,
+	proof: &CompactMultiProof,
+	state_versions: &[StateVersion],
+) -> Result<Vec<H::Out>, Error<LayoutV0<H>>>
+where
+	H: Hasher,
+	DB: HashDBT<H, trie_db::DBValue>,
+{
+	if proof.subtries.len() != state_versions.len() {
+		return Err(Error::StateVersionsMismatch)
+	}
+
+	let mut roots = Vec::new();
+	let mut nodes_iter = proof.iter_compact_encoded_nodes();
+	for (subtries, state_version) in proof.subtries.iter().zip(state_versions) {
+		for _ in 0..*subtries {
+			let (root, _) = match state_version {
+				StateVersion::V0 =>
+					trie_db::decode_compact_from_iter::<LayoutV0<H>, _, _>(db, &mut nodes_iter)?,
+				StateVersion::V1 =>
+					trie_db::decode_compact_from_iter::<LayoutV1<H>, _, _>(db, &mut nodes_iter)?,
+			};
+			roots.push(root);
+		}
+	}
+	if nodes_iter.next().is_some() {
+		return Err(Error::ExtraneousChildNode)
+	}
+
+	for value in &proof.values {
+		db.insert(EMPTY_PREFIX, value);
+	}
+
+	Ok(roots)
+}
+
+/// Encode the proofs of reads at several state roots as a single compact proof.
+///
+/// The nodes of a proof that are also part of an earlier proof are not encoded again, so the
+/// proofs should be given in the order they are expected to share the most nodes, e.g. by block
+/// number. Each remaining part of a proof is encoded as a compact subtrie with the layout of the
+/// state version of its root, starting at the nodes not referenced by any other remaining node.
+/// Hashed values only referenced by a node of an earlier proof are kept as is.
+#[cfg(feature = "std")]
+pub fn encode_compact_multi<H, I>(proofs: I) -> Result<CompactMultiProof, Error<LayoutV0<H>>>
+where
+	H: Hasher,
+	I: IntoIterator<Item = (StorageProof, StateVersion)>,
+{
+	use std::collections::{HashMap, HashSet};
+
+	let mut known = HashSet::new();
+	let mut encoded_nodes = Vec::new();
+	let mut values = Vec::new();
+	let mut subtries = Vec::new();
+	for (proof, state_version) in proofs {
+		let mut value_references = HashSet::new();
+		let mut new_nodes = HashMap::new();
+		for node in proof.iter_nodes() {
+			let hash = H::hash(&node);
+			value_references.extend(node_references::<H>(&node).1);
+			if !known.contains(&hash) {
+				new_nodes.insert(hash, node);
+			}
+		}
+
+		let referenced = new_nodes
+			.values()
+			.flat_map(|node| {
+				let (children, value) = node_references::<H>(node);
+				children.into_iter().chain(value)
+			})
+			.collect::<HashSet<_>>();
+		let mut entries = new_nodes
+			.keys()
+			.filter(|hash| !referenced.contains(*hash))
+			.cloned()
+			.collect::<Vec<_>>();
+		entries.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+
+		let mut db = crate::MemoryDB::<H>::default();
+		for node in new_nodes.values() {
+			db.insert(EMPTY_PREFIX, node);
+		}
+
+		let mut count = 0;
+		for entry in entries {
+			if value_references.contains(&entry) {
+				if let Some(value) = new_nodes.remove(&entry) {
+					values.push(value);
+					known.insert(entry);
+				}
+				continue
+			}
+
+			encoded_nodes.extend(match state_version {
+				StateVersion::V0 =>
+					trie_db::encode_compact(&crate::TrieDB::<LayoutV0<H>>::new(&db, &entry)?)?,
+				StateVersion::V1 =>
+					trie_db::encode_compact(&crate::TrieDB::<LayoutV1<H>>::new(&db, &entry)?)?,
+			});
+			count += 1;
+
+			// Remove the encoded nodes, the subtries of the next entries refer to them by hash.
+			let mut stack = vec![entry];
+			while let Some(hash) = stack.pop() {
+				if let Some(node) = new_nodes.remove(&hash) {
+					HashDBT::remove(&mut db, &hash, EMPTY_PREFIX);
+					let (children, value) = node_references::<H>(&node);
+					stack.extend(children.into_iter().chain(value));
+					known.insert(hash);
+				}
+			}
+		}
+		subtries.push(count);
+	}
+
+	Ok(CompactMultiProof { encoded_nodes, values, subtries })
+}
+
+/// Returns the hashes of the child nodes and of the value referenced by the encoded `node`.
+///
+/// Nothing is referenced by data that can't be decoded as a node, e.g. a hashed value.
+#[cfg(feature = "std")]
+fn node_references<H: Hasher>(node: &[u8]) -> (Vec<H::Out>, Option<H::Out>) {
+	use trie_db::{
+		node::{NodeHandlePlan, NodePlan, ValuePlan},
+		NodeCodec,
+	};
+
+	let to_hash = |range: &std::ops::Range<usize>| {
+		let mut hash = H::Out::default();
+		if hash.as_ref().len() != range.len() {
+			return None
+		}
+		hash.as_mut().copy_from_slice(&node[range.clone()]);
+		Some(hash)
+	};
+	let value_hash = |value: &Option<ValuePlan>| match value {
+		Some(ValuePlan::Node(range)) => to_hash(range),
+		_ => None,
+	};
+	let (children, value) = match crate::NodeCodec::<H>::decode_plan(node) {
+		Ok(NodePlan::Leaf { value, .. }) => (Vec::new(), value_hash(&Some(value))),
+		Ok(NodePlan::Extension { child, .. }) => (vec![child], None),
+		Ok(NodePlan::Branch { children, value }) |
+		Ok(NodePlan::NibbledBranch { children, value, .. }) =>
+			(children.into_iter().flatten().collect(), value_hash(&value)),
+		_ => (Vec::new(), None),
+	};
+	let children = children
+		.iter()
+		.filter_map(|child| match child {
+			NodeHandlePlan::Hash(range) => to_hash(range),
+			NodeHandlePlan::Inline(_) => None,
+		})
+		.collect();
+
+	(children, value)
+}
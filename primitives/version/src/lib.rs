@@ -26,10 +26,10 @@ use std::collections::HashSet;
 #[cfg(feature = "std")]
 use std::fmt;
 
-use codec::{Decode, Encode};
+use codec::{Decode, Encode, Input};
 use scale_info::TypeInfo;
-pub use sp_runtime::create_runtime_str;
 use sp_runtime::RuntimeString;
+pub use sp_runtime::{create_runtime_str, StateVersion};
 #[doc(hidden)]
 pub use sp_std;
 
@@ -65,6 +65,7 @@ pub mod embed;
 /// 	impl_version: 1,
 /// 	apis: RUNTIME_API_VERSIONS,
 /// 	transaction_version: 2,
+/// 	state_version: 1,
 /// };
 ///
 /// # const RUNTIME_API_VERSIONS: sp_version::ApisVec = sp_version::create_apis_vec!([]);
@@ -78,9 +79,9 @@ pub mod embed;
 /// - The `spec_name` and `impl_name` must be set by a macro-like expression. The name of the
 ///   macro doesn't matter though.
 ///
-/// - `authoring_version`, `spec_version`, `impl_version` and `transaction_version` must be set
-///   by a literal. Literal must be an integer. No other expressions are allowed there. In
-///   particular, you can't supply a constant variable.
+/// - `authoring_version`, `spec_version`, `impl_version`, `transaction_version` and
+///   `state_version` must be set by a literal. Literal must be an integer. No other
+///   expressions are allowed there. In particular, you can't supply a constant variable.
 ///
 /// - `apis` doesn't have any specific constraints. This is because this information doesn't
 ///   get into the custom section and is not parsed.
@@ -124,7 +125,7 @@ macro_rules! create_apis_vec {
 /// In particular: bug fixes should result in an increment of `spec_version` and possibly
 /// `authoring_version`, absolutely not `impl_version` since they change the semantics of the
 /// runtime.
-#[derive(Clone, PartialEq, Eq, Encode, Decode, Default, sp_runtime::RuntimeDebug, TypeInfo)]
+#[derive(Clone, PartialEq, Eq, Encode, Default, sp_runtime::RuntimeDebug, TypeInfo)]
 #[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
 pub struct RuntimeVersion {
@@ -177,6 +178,69 @@ pub struct RuntimeVersion {
 	///
 	/// It need *not* change when a new module is added or when a dispatchable is added.
 	pub transaction_version: u32,
+
+	/// Version of the state implementation used by this runtime.
+	/// Use of an incorrect version is consensus breaking.
+	pub state_version: u8,
+}
+
+impl RuntimeVersion {
+	/// `Decode` while giving a "version hint"
+	///
+	/// There exists multiple versions of [`RuntimeVersion`] and they are versioned using the `Core`
+	/// runtime api:
+	/// - `Core` version < 3 is a runtime version without a transaction version and state version.
+	/// - `Core` version 3 is a runtime version without a state version.
+	/// - `Core` version 4 is the latest runtime version.
+	///
+	/// When `core_version` is `None`, the version of the `Core` api found in the decoded `apis`
+	/// is used.
+	pub fn decode_with_version_hint<I: Input>(
+		input: &mut I,
+		core_version: Option<u32>,
+	) -> Result<RuntimeVersion, codec::Error> {
+		let spec_name = Decode::decode(input)?;
+		let impl_name = Decode::decode(input)?;
+		let authoring_version = Decode::decode(input)?;
+		let spec_version = Decode::decode(input)?;
+		let impl_version = Decode::decode(input)?;
+		let apis = Decode::decode(input)?;
+		let core_version = core_version.or_else(|| core_version_from_apis(&apis));
+		let transaction_version =
+			if core_version.map(|v| v >= 3).unwrap_or(false) { Decode::decode(input)? } else { 1 };
+		let state_version =
+			if core_version.map(|v| v >= 4).unwrap_or(false) { Decode::decode(input)? } else { 0 };
+		Ok(RuntimeVersion {
+			spec_name,
+			impl_name,
+			authoring_version,
+			spec_version,
+			impl_version,
+			apis,
+			transaction_version,
+			state_version,
+		})
+	}
+
+	/// Returns the state version to use for the state of this runtime.
+	///
+	/// Unknown versions are treated as the latest supported one.
+	pub fn state_version(&self) -> StateVersion {
+		StateVersion::try_from(self.state_version).unwrap_or(StateVersion::V1)
+	}
+}
+
+impl Decode for RuntimeVersion {
+	fn decode<I: Input>(input: &mut I) -> Result<Self, codec::Error> {
+		Self::decode_with_version_hint(input, None)
+	}
+}
+
+/// Returns the version of the `Core` runtime api found in the given `apis`.
+pub fn core_version_from_apis(apis: &ApisVec) -> Option<u32> {
+	let id = sp_core_hashing_proc_macro::blake2b_64!(b"Core");
+	apis.iter()
+		.find_map(|(api_id, version)| if *api_id == id { Some(*version) } else { None })
 }
 
 #[cfg(feature = "std")]
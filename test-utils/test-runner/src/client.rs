@@ -224,6 +224,7 @@ where
 		block_import,
 		env,
 		client: client.clone(),
+		backend: backend.clone(),
 		pool: transaction_pool.clone(),
 		commands_stream,
 		select_chain,
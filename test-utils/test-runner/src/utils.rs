@@ -78,6 +78,7 @@ pub fn default_config(tokio_handle: Handle, mut chain_spec: Box<dyn ChainSpec>)
 		database: DatabaseSource::RocksDb { path: root_path.join("db"), cache_size: 128 },
 		state_cache_size: 16777216,
 		state_cache_child_ratio: None,
+		trie_cache_size: 16777216,
 		chain_spec,
 		wasm_method: WasmExecutionMethod::Interpreted,
 		execution_strategies: ExecutionStrategies {
@@ -113,5 +114,6 @@ pub fn default_config(tokio_handle: Handle, mut chain_spec: Box<dyn ChainSpec>)
 		keep_blocks: KeepBlocks::All,
 		state_pruning: Default::default(),
 		transaction_storage: TransactionStorageMode::BlockBody,
+		state_diff_index: false,
 	}
 }
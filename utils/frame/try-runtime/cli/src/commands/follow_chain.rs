@@ -112,7 +112,7 @@ where
 				..Default::default()
 			}));
 
-			let new_ext = builder
+			let mut new_ext = builder
 				.inject_hashed_key_value(&[(code_key.clone(), code.clone())])
 				.build()
 				.await?;
@@ -123,7 +123,7 @@ where
 				new_ext.as_backend().root()
 			);
 
-			let (expected_spec_name, expected_spec_version) =
+			let (expected_spec_name, expected_spec_version, spec_state_version) =
 				local_spec::<Block, ExecDispatch>(&new_ext, &executor);
 			ensure_matching_spec::<Block>(
 				command.uri.clone(),
@@ -133,6 +133,7 @@ where
 			)
 			.await;
 
+			new_ext.state_version = spec_state_version;
 			maybe_state_ext = Some(new_ext);
 		}
 
@@ -152,7 +153,12 @@ where
 			.map_err(|e| format!("failed to decode output: {:?}", e))?;
 
 		let storage_changes = changes
-			.drain_storage_changes(&state_ext.backend, Default::default(), &mut Default::default())
+			.drain_storage_changes(
+				&state_ext.backend,
+				Default::default(),
+				&mut Default::default(),
+				state_ext.state_version,
+			)
 			.unwrap();
 		state_ext.backend.apply_transaction(
 			storage_changes.transaction_storage_root,
@@ -283,7 +283,7 @@ use sp_externalities::Extensions;
 use sp_keystore::{testing::KeyStore, KeystoreExt};
 use sp_runtime::{
 	traits::{Block as BlockT, NumberFor},
-	DeserializeOwned,
+	DeserializeOwned, StateVersion,
 };
 use sp_state_machine::{OverlayedChanges, StateMachine};
 use std::{fmt::Debug, path::PathBuf, str::FromStr};
@@ -697,11 +697,11 @@ pub(crate) fn state_machine_call<Block: BlockT, D: NativeExecutionDispatch + 'st
 	Ok((changes, encoded_results))
 }
 
-/// Get the spec `(name, version)` from the local runtime.
+/// Get the spec `(name, version, state_version)` from the local runtime.
 pub(crate) fn local_spec<Block: BlockT, D: NativeExecutionDispatch + 'static>(
 	ext: &TestExternalities,
 	executor: &NativeElseWasmExecutor<D>,
-) -> (String, u32) {
+) -> (String, u32, StateVersion) {
 	let (_, encoded) = state_machine_call::<Block, D>(
 		&ext,
 		&executor,
@@ -713,6 +713,6 @@ pub(crate) fn local_spec<Block: BlockT, D: NativeExecutionDispatch + 'static>(
 	.expect("all runtimes should have version; qed");
 	<sp_version::RuntimeVersion as Decode>::decode(&mut &*encoded)
 		.map_err(|e| format!("failed to decode output: {:?}", e))
-		.map(|v| (v.spec_name.into(), v.spec_version))
+		.map(|v| (v.spec_name.into(), v.spec_version, v.state_version()))
 		.expect("all runtimes should have version; qed")
 }